@@ -0,0 +1,178 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::error::AppError;
+
+/// Store file holding the workspace activity journal
+pub const STORE_FILE: &str = "activity.json";
+pub const STORE_KEY: &str = "journal";
+/// Directory (under the app data dir) holding version snapshots
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+/// Oldest entries are dropped beyond this many
+const MAX_ENTRIES: usize = 2000;
+/// Snapshots kept per file; older ones are deleted from disk
+const MAX_SNAPSHOTS_PER_FILE: usize = 20;
+/// Saves of the same file closer together than this form one timeline entry,
+/// so autosave doesn't flood the journal
+const SAVE_COALESCE_MS: i64 = 5 * 60 * 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityKind {
+    Created,
+    Saved,
+    Renamed,
+    Moved,
+    Deleted,
+    Restored,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActivityEntry {
+    pub id: String,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+    pub kind: ActivityKind,
+    pub path: String,
+    /// Source path of renames and moves
+    pub previous_path: Option<String>,
+    /// Workspace that was open when the change happened
+    pub workspace: Option<String>,
+    /// Snapshot of the content before this change, if one was taken
+    pub snapshot_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActivityTimeline {
+    pub range: String,
+    pub from: Option<i64>,
+    /// Newest first
+    pub entries: Vec<ActivityEntry>,
+    pub total: usize,
+}
+
+impl ActivityEntry {
+    pub fn new(kind: ActivityKind, path: String, workspace: Option<String>) -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        ActivityEntry {
+            id: format!("act_{}", nanos),
+            timestamp: crate::prompts::now_millis(),
+            kind,
+            path,
+            previous_path: None,
+            workspace,
+            snapshot_id: None,
+        }
+    }
+
+    pub fn with_previous_path(mut self, previous_path: String) -> Self {
+        self.previous_path = Some(previous_path);
+        self
+    }
+
+    /// Snapshots share the entry id, so there is exactly one per entry
+    pub fn with_snapshot(mut self) -> Self {
+        self.snapshot_id = Some(self.id.clone());
+        self
+    }
+}
+
+pub fn snapshot_file_name(snapshot_id: &str) -> Result<String, AppError> {
+    if snapshot_id.is_empty() || !snapshot_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(AppError::InvalidInput(format!("Invalid snapshot id: {}", snapshot_id)));
+    }
+    Ok(format!("{}.excalidraw", snapshot_id))
+}
+
+/// Folds a save into the previous save of the same file when it happened
+/// recently; the earlier snapshot already holds the content before the burst
+pub fn coalesce_save(journal: &mut [ActivityEntry], path: &str, now: i64) -> bool {
+    let Some(last) = journal.iter_mut().rev().find(|e| e.path == path) else {
+        return false;
+    };
+    if last.kind != ActivityKind::Saved || now - last.timestamp >= SAVE_COALESCE_MS {
+        return false;
+    }
+    last.timestamp = now;
+    true
+}
+
+/// Appends an entry and prunes the journal; returns the snapshot ids that
+/// are no longer referenced and should be deleted from disk
+pub fn append(journal: &mut Vec<ActivityEntry>, entry: ActivityEntry) -> Vec<String> {
+    journal.push(entry);
+
+    let mut dropped = Vec::new();
+    if journal.len() > MAX_ENTRIES {
+        let excess = journal.len() - MAX_ENTRIES;
+        dropped.extend(journal.drain(..excess).filter_map(|e| e.snapshot_id));
+    }
+
+    // Keep only the newest snapshots of each file; the entries themselves stay
+    let mut per_file: HashMap<&str, usize> = HashMap::new();
+    let mut expired = HashSet::new();
+    for entry in journal.iter().rev() {
+        if entry.snapshot_id.is_none() {
+            continue;
+        }
+        let count = per_file.entry(entry.path.as_str()).or_insert(0);
+        *count += 1;
+        if *count > MAX_SNAPSHOTS_PER_FILE {
+            expired.insert(entry.id.clone());
+        }
+    }
+    for entry in journal.iter_mut().filter(|e| expired.contains(&e.id)) {
+        dropped.extend(entry.snapshot_id.take());
+    }
+    dropped
+}
+
+/// Follows renames and moves so a snapshot taken under an old name can be
+/// restored to wherever the file lives now
+pub fn current_path(journal: &[ActivityEntry], entry: &ActivityEntry) -> String {
+    let mut path = entry.path.clone();
+    for later in journal.iter().filter(|e| e.timestamp >= entry.timestamp && e.id != entry.id) {
+        if matches!(later.kind, ActivityKind::Renamed | ActivityKind::Moved)
+            && later.previous_path.as_deref() == Some(path.as_str())
+        {
+            path = later.path.clone();
+        } else if let Some(rest) = later
+            .previous_path
+            .as_deref()
+            .and_then(|prev| path.strip_prefix(prev))
+            .filter(|rest| rest.starts_with('/'))
+        {
+            // A parent directory was renamed
+            path = format!("{}{}", later.path, rest);
+        }
+    }
+    path
+}
+
+/// Entries in `range` (see `usage::range_start`), optionally limited to a
+/// workspace, newest first
+pub fn timeline(
+    journal: &[ActivityEntry],
+    range: &str,
+    workspace: Option<&str>,
+    limit: usize,
+    now: i64,
+) -> Result<ActivityTimeline, AppError> {
+    let from = crate::usage::range_start(range, now)?;
+    let matching: Vec<&ActivityEntry> = journal
+        .iter()
+        .filter(|e| from.is_none_or(|from| e.timestamp >= from))
+        .filter(|e| workspace.is_none_or(|w| e.workspace.as_deref() == Some(w)))
+        .collect();
+
+    Ok(ActivityTimeline {
+        range: range.to_string(),
+        from,
+        total: matching.len(),
+        entries: matching.into_iter().rev().take(limit).cloned().collect(),
+    })
+}
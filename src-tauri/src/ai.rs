@@ -0,0 +1,501 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::error::{AppError, ResultExt};
+
+const GEMINI_HOST: &str = "generativelanguage.googleapis.com";
+const ANTHROPIC_HOST: &str = "anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Wire protocol spoken by the configured AI endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AIProvider {
+    /// OpenAI-compatible `/chat/completions` API
+    OpenAI,
+    /// Google Gemini `generateContent` API
+    Gemini,
+    /// Anthropic `/messages` API
+    Anthropic,
+}
+
+impl AIProvider {
+    pub fn name(self) -> &'static str {
+        match self {
+            AIProvider::OpenAI => "openai",
+            AIProvider::Gemini => "gemini",
+            AIProvider::Anthropic => "anthropic",
+        }
+    }
+
+    /// Resolves the provider from an explicit name, falling back to the base URL host
+    pub fn resolve(provider: Option<&str>, base_url: &str) -> Self {
+        match provider.map(|p| p.trim().to_ascii_lowercase()) {
+            Some(p) if p == "gemini" || p == "google" => AIProvider::Gemini,
+            Some(p) if p == "anthropic" || p == "claude" => AIProvider::Anthropic,
+            Some(p) if p == "openai" || p == "azure" => AIProvider::OpenAI,
+            // Other names, e.g. `custom`, say nothing about the protocol
+            _ if base_url.contains(GEMINI_HOST) => AIProvider::Gemini,
+            _ if base_url.contains(ANTHROPIC_HOST) => AIProvider::Anthropic,
+            _ => AIProvider::OpenAI,
+        }
+    }
+}
+
+/// Proxy settings for outgoing AI requests, stored in Preferences
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ProxyConfig {
+    pub enabled: bool,
+    /// `http://`, `https://`, `socks5://` or `socks5h://` URL
+    pub url: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Comma-separated hosts/domains/CIDRs that bypass the proxy
+    pub no_proxy: Option<String>,
+    /// PEM file of an extra root certificate to trust (e.g. a corporate MITM CA)
+    pub ca_certificate_path: Option<String>,
+}
+
+/// Builds the HTTP client shared by all AI commands, applying proxy and CA settings
+pub fn build_client(timeout: Duration, proxy: &ProxyConfig) -> Result<reqwest::Client, AppError> {
+    let mut builder = reqwest::Client::builder().timeout(timeout);
+
+    if proxy.enabled && !proxy.url.trim().is_empty() {
+        let mut p = reqwest::Proxy::all(proxy.url.trim())
+            .map_err(|e| AppError::InvalidInput(format!("Invalid proxy URL: {}", e)))?;
+        if let Some(username) = proxy.username.as_deref().filter(|u| !u.is_empty()) {
+            p = p.basic_auth(username, proxy.password.as_deref().unwrap_or(""));
+        }
+        p = p.no_proxy(
+            proxy
+                .no_proxy
+                .as_deref()
+                .and_then(reqwest::NoProxy::from_string),
+        );
+        builder = builder.proxy(p);
+    }
+
+    if let Some(path) = proxy.ca_certificate_path.as_deref().filter(|p| !p.is_empty()) {
+        let pem = std::fs::read(path)
+            .context(format!("Failed to read CA certificate {}", path))?;
+        let certificate = reqwest::Certificate::from_pem(&pem)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid CA certificate: {}", e)))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+
+    builder
+        .build()
+        .map_err(|e| AppError::Internal(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// An image attached to the user message, as raw base64 (no `data:` prefix)
+pub struct ImageInput<'a> {
+    pub media_type: &'a str,
+    pub data: &'a str,
+}
+
+/// Provider-independent parameters of a single chat generation
+pub struct ChatParams<'a> {
+    pub base_url: &'a str,
+    pub api_key: &'a str,
+    pub model: &'a str,
+    pub prompt: &'a str,
+    pub system_prompt: Option<&'a str>,
+    pub image: Option<ImageInput<'a>>,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub stream: bool,
+}
+
+/// Returns the endpoint URL for a chat generation (never includes credentials)
+pub fn chat_url(provider: AIProvider, params: &ChatParams) -> String {
+    let base_url = params.base_url.trim_end_matches('/');
+    match provider {
+        AIProvider::OpenAI => format!("{}/chat/completions", base_url),
+        AIProvider::Anthropic => format!("{}/messages", base_url),
+        AIProvider::Gemini => {
+            let method = if params.stream {
+                "streamGenerateContent"
+            } else {
+                "generateContent"
+            };
+            format!("{}/models/{}:{}", base_url, params.model, method)
+        }
+    }
+}
+
+/// Builds the HTTP request for a chat generation, including payload and auth
+pub fn build_chat_request(
+    client: &reqwest::Client,
+    provider: AIProvider,
+    params: &ChatParams,
+) -> reqwest::RequestBuilder {
+    let url = chat_url(provider, params);
+
+    match provider {
+        AIProvider::OpenAI => {
+            let mut messages = Vec::new();
+            if let Some(system_prompt) = params.system_prompt {
+                messages.push(serde_json::json!({"role": "system", "content": system_prompt}));
+            }
+            let content = match &params.image {
+                Some(image) => serde_json::json!([
+                    {"type": "text", "text": params.prompt},
+                    {
+                        "type": "image_url",
+                        "image_url": {"url": format!("data:{};base64,{}", image.media_type, image.data)}
+                    }
+                ]),
+                None => serde_json::json!(params.prompt),
+            };
+            messages.push(serde_json::json!({"role": "user", "content": content}));
+
+            let payload = serde_json::json!({
+                "model": params.model,
+                "messages": messages,
+                "max_tokens": params.max_tokens,
+                "temperature": params.temperature,
+                "stream": params.stream
+            });
+
+            client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .header("Authorization", format!("Bearer {}", params.api_key))
+                .json(&payload)
+        }
+        AIProvider::Anthropic => {
+            let mut content = vec![serde_json::json!({"type": "text", "text": params.prompt})];
+            if let Some(image) = &params.image {
+                // Anthropic recommends placing images before the question
+                content.insert(
+                    0,
+                    serde_json::json!({
+                        "type": "image",
+                        "source": {
+                            "type": "base64",
+                            "media_type": image.media_type,
+                            "data": image.data
+                        }
+                    }),
+                );
+            }
+
+            let mut payload = serde_json::json!({
+                "model": params.model,
+                "messages": [{"role": "user", "content": content}],
+                "max_tokens": params.max_tokens,
+                "temperature": params.temperature,
+                "stream": params.stream
+            });
+            if let Some(system_prompt) = params.system_prompt {
+                payload["system"] = serde_json::json!(system_prompt);
+            }
+
+            client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .header("x-api-key", params.api_key)
+                .header("anthropic-version", ANTHROPIC_VERSION)
+                .json(&payload)
+        }
+        AIProvider::Gemini => {
+            let mut parts = vec![serde_json::json!({"text": params.prompt})];
+            if let Some(image) = &params.image {
+                parts.push(serde_json::json!({
+                    "inline_data": {"mime_type": image.media_type, "data": image.data}
+                }));
+            }
+
+            let mut payload = serde_json::json!({
+                "contents": [{
+                    "role": "user",
+                    "parts": parts
+                }],
+                "generationConfig": {
+                    "maxOutputTokens": params.max_tokens,
+                    "temperature": params.temperature
+                }
+            });
+            if let Some(system_prompt) = params.system_prompt {
+                payload["systemInstruction"] = serde_json::json!({
+                    "parts": [{"text": system_prompt}]
+                });
+            }
+
+            // Gemini authenticates with the key in the query string;
+            // alt=sse makes the streaming endpoint emit `data:` lines
+            let mut query = vec![("key", params.api_key)];
+            if params.stream {
+                query.push(("alt", "sse"));
+            }
+
+            client
+                .post(&url)
+                .header("Content-Type", "application/json")
+                .query(&query)
+                .json(&payload)
+        }
+    }
+}
+
+/// Extracts the generated text and token usage from a non-streaming response
+pub fn parse_generate_response(provider: AIProvider, data: &Value) -> Option<(String, Option<u32>)> {
+    match provider {
+        AIProvider::OpenAI => {
+            let content = data
+                .get("choices")?
+                .as_array()?
+                .first()?
+                .get("message")?
+                .get("content")?
+                .as_str()?;
+            let tokens_used = data
+                .get("usage")
+                .and_then(|u| u.get("total_tokens"))
+                .and_then(|t| t.as_u64())
+                .map(|t| t as u32);
+            Some((content.to_string(), tokens_used))
+        }
+        AIProvider::Anthropic => {
+            let content: String = data
+                .get("content")?
+                .as_array()?
+                .iter()
+                .filter(|block| block.get("type").and_then(|t| t.as_str()) == Some("text"))
+                .filter_map(|block| block.get("text").and_then(|t| t.as_str()))
+                .collect();
+            let tokens_used = data.get("usage").map(|u| {
+                let tokens = |key: &str| u.get(key).and_then(|t| t.as_u64()).unwrap_or(0);
+                (tokens("input_tokens") + tokens("output_tokens")) as u32
+            });
+            Some((content, tokens_used))
+        }
+        AIProvider::Gemini => {
+            let content = gemini_candidate_text(data)?;
+            let tokens_used = data
+                .get("usageMetadata")
+                .and_then(|u| u.get("totalTokenCount"))
+                .and_then(|t| t.as_u64())
+                .map(|t| t as u32);
+            Some((content, tokens_used))
+        }
+    }
+}
+
+/// Extracts the text delta carried by one decoded SSE `data:` payload
+pub fn parse_stream_chunk(provider: AIProvider, chunk: &Value) -> Option<String> {
+    match provider {
+        AIProvider::OpenAI => chunk
+            .get("choices")?
+            .as_array()?
+            .first()?
+            .get("delta")?
+            .get("content")?
+            .as_str()
+            .map(|s| s.to_string()),
+        // Only `content_block_delta` events carry text; message_start/stop etc. are skipped
+        AIProvider::Anthropic => chunk
+            .get("delta")
+            .filter(|_| chunk.get("type").and_then(|t| t.as_str()) == Some("content_block_delta"))?
+            .get("text")?
+            .as_str()
+            .map(|s| s.to_string()),
+        AIProvider::Gemini => gemini_candidate_text(chunk),
+    }
+}
+
+/// Concatenates the text parts of the first Gemini candidate
+fn gemini_candidate_text(data: &Value) -> Option<String> {
+    let parts = data
+        .get("candidates")?
+        .as_array()?
+        .first()?
+        .get("content")?
+        .get("parts")?
+        .as_array()?;
+
+    let text: String = parts
+        .iter()
+        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+        .collect();
+
+    if text.is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+/// Builds the HTTP request that lists the models available at the endpoint
+pub fn build_models_request(
+    client: &reqwest::Client,
+    provider: AIProvider,
+    base_url: &str,
+    api_key: &str,
+) -> reqwest::RequestBuilder {
+    let url = format!("{}/models", base_url.trim_end_matches('/'));
+
+    match provider {
+        AIProvider::OpenAI => client
+            .get(&url)
+            .header("Authorization", format!("Bearer {}", api_key)),
+        AIProvider::Anthropic => client
+            .get(&url)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION),
+        AIProvider::Gemini => client.get(&url).query(&[("key", api_key)]),
+    }
+}
+
+/// Extracts model ids from a model listing response
+pub fn parse_models_response(provider: AIProvider, data: &Value) -> Vec<String> {
+    let mut models: Vec<String> = match provider {
+        AIProvider::OpenAI | AIProvider::Anthropic => data
+            .get("data")
+            .and_then(|d| d.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|m| m.get("id").and_then(|id| id.as_str()))
+                    .map(|id| id.to_string())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        AIProvider::Gemini => data
+            .get("models")
+            .and_then(|d| d.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    // Only keep models that can actually serve generateContent
+                    .filter(|m| {
+                        m.get("supportedGenerationMethods")
+                            .and_then(|s| s.as_array())
+                            .map(|methods| methods.iter().any(|x| x == "generateContent"))
+                            .unwrap_or(true)
+                    })
+                    .filter_map(|m| m.get("name").and_then(|n| n.as_str()))
+                    .map(|name| name.trim_start_matches("models/").to_string())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+
+    models.sort();
+    models.dedup();
+    models
+}
+
+/// Retry policy for transient AI endpoint failures (429, 5xx, timeouts)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub backoff_multiplier: f64,
+    /// Randomize each delay by up to ±50% to avoid synchronized retries
+    pub jitter: bool,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff_ms: 1000,
+            max_backoff_ms: 30_000,
+            backoff_multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Delay before retry number `attempt` (1-based), honoring a server-provided hint
+    pub fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(hint) = retry_after {
+            return hint.min(Duration::from_millis(self.max_backoff_ms));
+        }
+
+        let exponent = attempt.saturating_sub(1) as i32;
+        let mut delay_ms = (self.initial_backoff_ms as f64 * self.backoff_multiplier.powi(exponent))
+            .min(self.max_backoff_ms as f64);
+
+        if self.jitter {
+            // No RNG dependency; the clock's sub-second noise is random enough here
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.subsec_nanos())
+                .unwrap_or(0);
+            let factor = 0.5 + (nanos % 1000) as f64 / 1000.0;
+            delay_ms *= factor;
+        }
+
+        Duration::from_millis(delay_ms as u64)
+    }
+}
+
+/// Payload of the `ai-retrying` event
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetryNotice {
+    pub request_id: Option<String>,
+    /// The attempt about to be made (2 for the first retry)
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay_ms: u64,
+    pub reason: String,
+}
+
+/// Whether an HTTP status is worth retrying
+pub fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parses a `Retry-After` header given in seconds
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+/// Sends the request built by `build`, retrying transient failures per `config`.
+/// `on_retry` is called before each wait so callers can surface the status.
+/// The last response (successful or not) or transport error is returned.
+pub async fn send_with_retry<B, N>(
+    build: B,
+    config: &RetryConfig,
+    on_retry: N,
+) -> Result<reqwest::Response, reqwest::Error>
+where
+    B: Fn() -> reqwest::RequestBuilder,
+    N: Fn(u32, Duration, String),
+{
+    let max_attempts = config.max_attempts.max(1);
+    let mut attempt = 1;
+
+    loop {
+        let (reason, hint) = match build().send().await {
+            Ok(response) if attempt < max_attempts && is_retryable_status(response.status()) => {
+                (format!("HTTP {}", response.status()), retry_after(&response))
+            }
+            Err(e) if attempt < max_attempts && (e.is_timeout() || e.is_connect()) => {
+                (format!("Request failed: {}", e), None)
+            }
+            result => return result,
+        };
+
+        let delay = config.delay(attempt, hint);
+        attempt += 1;
+        on_retry(attempt, delay, reason);
+        tokio::time::sleep(delay).await;
+    }
+}
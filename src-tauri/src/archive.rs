@@ -0,0 +1,721 @@
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::Serialize;
+use std::fs;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{AppError, ResultExt};
+use crate::ignores::IgnoreRules;
+use crate::limits::FileLimits;
+use crate::{compression, paths, security};
+
+/// Emitted to the window creating an archive after each stored file
+pub const PROGRESS_EVENT: &str = "archive-progress";
+
+/// Exported images and extracted assets, only stored when asked for
+const ASSET_EXTENSIONS: &[&str] = &["png", "svg", "jpg", "jpeg", "webp", "gif"];
+
+/// Vault settings that travel with the drawings
+const VAULT_FILES: &[&str] = &[crate::config::CONFIG_FILE, crate::ignores::IGNORE_FILE];
+
+/// Folder inside the archive for a configured export folder outside the vault
+pub const EXPORTS_PREFIX: &str = "exports";
+
+/// Payload of `PROGRESS_EVENT`, while archiving or importing
+#[derive(Debug, Serialize, Clone)]
+pub struct ArchiveProgress {
+    pub done: usize,
+    pub total: usize,
+    /// Name of the file just handled, inside the archive
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ArchiveReport {
+    pub output_path: String,
+    pub files: usize,
+    /// Size of the written archive
+    pub bytes: u64,
+}
+
+/// A file to store and its `/`-separated name inside the archive
+pub struct ArchiveEntry {
+    pub source: PathBuf,
+    pub name: String,
+}
+
+fn extension(file_name: &str) -> String {
+    Path::new(file_name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Exported images and extracted assets
+pub fn is_asset(file_name: &str) -> bool {
+    ASSET_EXTENSIONS.contains(&extension(file_name).as_str())
+}
+
+/// Drawings and vault settings, plus images with `include_assets`
+pub fn is_supported(file_name: &str, include_assets: bool) -> bool {
+    compression::is_drawing(Path::new(file_name))
+        || VAULT_FILES.contains(&file_name)
+        || (include_assets && is_asset(file_name))
+}
+
+/// Drawings and vault settings below `directory`, plus images when
+/// `include_assets` is set. Ignored paths and `skip` (the archive being
+/// written) are left out.
+pub fn collect(
+    directory: &Path,
+    prefix: &str,
+    ignore: &IgnoreRules,
+    include_assets: bool,
+    skip: &Path,
+    entries: &mut Vec<ArchiveEntry>,
+) -> Result<(), AppError> {
+    let mut children: Vec<_> = fs::read_dir(directory)
+        .context(format!("Failed to read {}", directory.display()))?
+        .flatten()
+        .collect();
+    children.sort_by_key(|entry| entry.file_name());
+
+    for child in children {
+        let path = child.path();
+        let is_dir = path.is_dir();
+        if path == skip || ignore.is_ignored(&path, is_dir) {
+            continue;
+        }
+        let file_name = child.file_name().to_string_lossy().to_string();
+        let name = if prefix.is_empty() {
+            file_name.clone()
+        } else {
+            format!("{}/{}", prefix, file_name)
+        };
+
+        if is_dir {
+            collect(&path, &name, ignore, include_assets, skip, entries)?;
+            continue;
+        }
+        if is_supported(&file_name, include_assets) {
+            entries.push(ArchiveEntry { source: path, name });
+        }
+    }
+    Ok(())
+}
+
+/// Writes `entries` into a zip file at `output`. The archive is assembled
+/// next to it and only moved into place once complete; `on_progress`
+/// returning false abandons it.
+pub fn write(
+    entries: &[ArchiveEntry],
+    output: &Path,
+    on_progress: impl Fn(ArchiveProgress) -> bool,
+) -> Result<ArchiveReport, AppError> {
+    let temp = output.with_extension("zip.part");
+    let result = write_to(entries, &temp, on_progress).and_then(|()| {
+        fs::rename(&temp, output).context("Failed to move archive into place")
+    });
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(e);
+    }
+
+    Ok(ArchiveReport {
+        output_path: paths::normalize(output),
+        files: entries.len(),
+        bytes: fs::metadata(output).map(|m| m.len()).unwrap_or(0),
+    })
+}
+
+fn write_to(
+    entries: &[ArchiveEntry],
+    path: &Path,
+    on_progress: impl Fn(ArchiveProgress) -> bool,
+) -> Result<(), AppError> {
+    let file = fs::File::create(path).context("Failed to create archive")?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+
+    for (index, entry) in entries.iter().enumerate() {
+        let data = fs::read(&entry.source)
+            .context(format!("Failed to read {}", entry.source.display()))?;
+        let modified = fs::metadata(&entry.source)
+            .and_then(|m| m.modified())
+            .unwrap_or(UNIX_EPOCH);
+        zip.add(&entry.name, &data, modified)
+            .context(format!("Failed to write {}", entry.name))?;
+        let go_on = on_progress(ArchiveProgress {
+            done: index + 1,
+            total: entries.len(),
+            path: entry.name.clone(),
+        });
+        if !go_on {
+            return Err(AppError::Cancelled("Archiving was cancelled".to_string()));
+        }
+    }
+
+    zip.finish()
+        .and_then(|mut out| out.flush())
+        .context("Failed to finish archive")
+}
+
+/// An archive entry that was not extracted, and why
+#[derive(Debug, Serialize, Clone)]
+pub struct SkippedEntry {
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ImportReport {
+    /// Paths of the extracted files
+    pub imported: Vec<String>,
+    /// Unsupported file types and names already taken in the target
+    pub skipped: Vec<SkippedEntry>,
+    /// Unsafe paths, corrupt entries and content that failed validation
+    pub invalid: Vec<SkippedEntry>,
+}
+
+/// Checks that extracted bytes are what their extension claims
+fn validate_entry(name: &str, data: &[u8], limits: &FileLimits) -> Result<(), AppError> {
+    let text = || std::str::from_utf8(data)
+        .map_err(|_| AppError::InvalidInput("Content is not UTF-8".to_string()));
+    if compression::is_compressed(Path::new(name)) {
+        let limit = limits.max_bytes().unwrap_or(compression::MAX_DECODED_BYTES);
+        let content = compression::decode(data, limit).map_err(|e| match e.kind() {
+            std::io::ErrorKind::FileTooLarge => AppError::from(e),
+            _ => AppError::InvalidInput(format!("Not a compressed drawing: {}", e)),
+        })?;
+        limits.check_content(&content)?;
+        return security::validate_excalidraw_content(&content);
+    }
+    match extension(name).as_str() {
+        "excalidraw" => {
+            let content = text()?;
+            limits.check_content(content)?;
+            security::validate_excalidraw_content(content)
+        }
+        "json" => serde_json::from_str::<serde_json::Value>(text()?)
+            .map(|_| ())
+            .map_err(AppError::from),
+        "svg" if text()?.contains("<svg") => Ok(()),
+        "svg" => Err(AppError::InvalidInput("Not an SVG image".to_string())),
+        "" => text().map(|_| ()),
+        _ => image::guess_format(data)
+            .map(|_| ())
+            .map_err(|_| AppError::InvalidInput("Not a supported image".to_string())),
+    }
+}
+
+/// Extracts the drawings, vault settings and images of the zip file at
+/// `zip_path` into `target`. Every name goes through
+/// `security::safe_archive_path` and every file is validated before it is
+/// written; existing files are never overwritten. `on_progress` returning
+/// false stops the import, keeping the files extracted so far.
+pub fn import(
+    zip_path: &Path,
+    target: &Path,
+    limits: &FileLimits,
+    on_progress: impl Fn(ArchiveProgress) -> bool,
+) -> Result<ImportReport, AppError> {
+    let mut zip = ZipReader::open(zip_path)?;
+    let entries = std::mem::take(&mut zip.entries);
+    let mut report = ImportReport::default();
+    let skip = |list: &mut Vec<SkippedEntry>, name: &str, reason: String| {
+        list.push(SkippedEntry {
+            name: name.to_string(),
+            reason,
+        });
+    };
+
+    for (index, entry) in entries.iter().enumerate() {
+        let name = entry.name.as_str();
+        let file_name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+
+        if name.ends_with('/') {
+            // Folders are created along with the files in them
+        } else if !is_supported(file_name, true) {
+            skip(&mut report.skipped, name, "Unsupported file type".to_string());
+        } else {
+            match security::safe_archive_path(target, name) {
+                Err(e) => skip(&mut report.invalid, name, e.to_string()),
+                Ok(path) if path.exists() => {
+                    skip(&mut report.skipped, name, "A file with this name already exists".to_string())
+                }
+                Ok(path) => {
+                    let result = limits
+                        .check_size(entry.size)
+                        .and_then(|()| zip.read(entry))
+                        .and_then(|data| validate_entry(file_name, &data, limits).map(|()| data));
+                    match result {
+                        Ok(data) => {
+                            write_new(&path, &data)?;
+                            report.imported.push(paths::normalize(&path));
+                        }
+                        Err(e) => skip(&mut report.invalid, name, e.to_string()),
+                    }
+                }
+            }
+        }
+
+        let go_on = on_progress(ArchiveProgress {
+            done: index + 1,
+            total: entries.len(),
+            path: entry.name.clone(),
+        });
+        if !go_on {
+            return Err(AppError::Cancelled(format!(
+                "Import was cancelled after extracting {} file(s)",
+                report.imported.len()
+            )));
+        }
+    }
+
+    Ok(report)
+}
+
+fn write_new(path: &Path, data: &[u8]) -> Result<(), AppError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).context("Failed to create directory")?;
+    }
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(data))
+        .context(format!("Failed to write {}", path.display()))
+}
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+/// Version 2.0: deflate and folders
+const ZIP_VERSION: u16 = 20;
+/// General purpose flag for UTF-8 file names
+const UTF8_NAMES: u16 = 1 << 11;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+/// Minimal zip writer: deflated or stored entries, no ZIP64, so at most
+/// 65535 files and 4 GiB
+pub(crate) struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    central_directory: Vec<u8>,
+    count: u16,
+}
+
+fn too_large() -> std::io::Error {
+    std::io::Error::other("archive exceeds the zip format limits (65535 files, 4 GiB)")
+}
+
+fn to_u32(value: u64) -> std::io::Result<u32> {
+    u32::try_from(value).map_err(|_| too_large())
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub(crate) fn new(out: W) -> Self {
+        Self {
+            out,
+            offset: 0,
+            central_directory: Vec::new(),
+            count: 0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, name: &str, data: &[u8], modified: SystemTime) -> std::io::Result<()> {
+        let count = self.count.checked_add(1).ok_or_else(too_large)?;
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let deflated = encoder.finish()?;
+        // Images are usually compressed already
+        let (method, stored) = if deflated.len() < data.len() {
+            (METHOD_DEFLATED, deflated.as_slice())
+        } else {
+            (METHOD_STORED, data)
+        };
+
+        let crc = crc32fast::hash(data);
+        let (time, date) = dos_timestamp(modified);
+        let compressed_size = to_u32(stored.len() as u64)?;
+        let size = to_u32(data.len() as u64)?;
+        let offset = to_u32(self.offset)?;
+        let name_len = u16::try_from(name.len()).map_err(|_| too_large())?;
+
+        // Fields shared by the local and central headers, from "version needed" on
+        let mut common = Vec::with_capacity(26);
+        common.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        common.extend_from_slice(&UTF8_NAMES.to_le_bytes());
+        common.extend_from_slice(&method.to_le_bytes());
+        common.extend_from_slice(&time.to_le_bytes());
+        common.extend_from_slice(&date.to_le_bytes());
+        common.extend_from_slice(&crc.to_le_bytes());
+        common.extend_from_slice(&compressed_size.to_le_bytes());
+        common.extend_from_slice(&size.to_le_bytes());
+        common.extend_from_slice(&name_len.to_le_bytes());
+        // No extra field
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        self.out.write_all(&LOCAL_HEADER.to_le_bytes())?;
+        self.out.write_all(&common)?;
+        self.out.write_all(name.as_bytes())?;
+        self.out.write_all(stored)?;
+
+        let central = &mut self.central_directory;
+        central.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+        // Version made by
+        central.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        central.extend_from_slice(&common);
+        // Comment length, disk number, internal and external attributes
+        central.extend_from_slice(&[0; 10]);
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+
+        self.offset += (4 + common.len() + name.len() + stored.len()) as u64;
+        self.count = count;
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> std::io::Result<W> {
+        let size = to_u32(self.central_directory.len() as u64)?;
+        let offset = to_u32(self.offset)?;
+        self.out.write_all(&self.central_directory)?;
+
+        self.out.write_all(&END_OF_CENTRAL_DIRECTORY.to_le_bytes())?;
+        // This disk and the disk the central directory starts on
+        self.out.write_all(&[0; 4])?;
+        self.out.write_all(&self.count.to_le_bytes())?;
+        self.out.write_all(&self.count.to_le_bytes())?;
+        self.out.write_all(&size.to_le_bytes())?;
+        self.out.write_all(&offset.to_le_bytes())?;
+        // Comment length
+        self.out.write_all(&0u16.to_le_bytes())?;
+        Ok(self.out)
+    }
+}
+
+/// Where an entry lives in the archive, from the central directory
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc: u32,
+    compressed_size: u64,
+    size: u64,
+    local_header_offset: u64,
+}
+
+/// Reads archives written by `ZipWriter` and common zip tools: stored and
+/// deflated entries, without encryption or ZIP64
+struct ZipReader {
+    file: fs::File,
+    len: u64,
+    entries: Vec<ZipEntry>,
+}
+
+/// Longest possible end of central directory record, with a full comment
+const MAX_END_RECORD: u64 = 22 + u16::MAX as u64;
+/// Encrypted entries set bit 0 of the general purpose flags
+const ENCRYPTED: u16 = 1;
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+impl ZipReader {
+    fn open(path: &Path) -> Result<Self, AppError> {
+        let not_a_zip = || AppError::InvalidInput("Not a zip archive".to_string());
+        let mut file = fs::File::open(path).context("Failed to open archive")?;
+        let len = file.metadata().context("Failed to read archive")?.len();
+
+        let tail_len = len.min(MAX_END_RECORD);
+        let mut tail = vec![0; tail_len as usize];
+        file.seek(SeekFrom::Start(len - tail_len))
+            .and_then(|_| file.read_exact(&mut tail))
+            .context("Failed to read archive")?;
+        let signature = END_OF_CENTRAL_DIRECTORY.to_le_bytes();
+        let end = (0..tail.len().saturating_sub(21))
+            .rev()
+            .find(|&at| tail[at..at + 4] == signature)
+            .ok_or_else(not_a_zip)?;
+        let count = u16_at(&tail, end + 10) as usize;
+        let directory_len = u32_at(&tail, end + 12) as u64;
+        let directory_offset = u32_at(&tail, end + 16) as u64;
+        if directory_offset + directory_len > len {
+            return Err(not_a_zip());
+        }
+
+        let mut directory = vec![0; directory_len as usize];
+        file.seek(SeekFrom::Start(directory_offset))
+            .and_then(|_| file.read_exact(&mut directory))
+            .context("Failed to read archive")?;
+
+        let mut entries = Vec::with_capacity(count);
+        let mut at = 0;
+        for _ in 0..count {
+            if at + 46 > directory.len() || u32_at(&directory, at) != CENTRAL_HEADER {
+                return Err(AppError::InvalidInput("Corrupt zip central directory".to_string()));
+            }
+            let flags = u16_at(&directory, at + 8);
+            let name_len = u16_at(&directory, at + 28) as usize;
+            let extra_len = u16_at(&directory, at + 30) as usize;
+            let comment_len = u16_at(&directory, at + 32) as usize;
+            let name_end = at + 46 + name_len;
+            if name_end > directory.len() {
+                return Err(AppError::InvalidInput("Corrupt zip central directory".to_string()));
+            }
+            if flags & ENCRYPTED != 0 {
+                return Err(AppError::InvalidInput("Encrypted archives are not supported".to_string()));
+            }
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(&directory[at + 46..name_end]).to_string(),
+                method: u16_at(&directory, at + 10),
+                crc: u32_at(&directory, at + 16),
+                compressed_size: u32_at(&directory, at + 20) as u64,
+                size: u32_at(&directory, at + 24) as u64,
+                local_header_offset: u32_at(&directory, at + 42) as u64,
+            });
+            at = name_end + extra_len + comment_len;
+        }
+
+        Ok(Self { file, len, entries })
+    }
+
+    /// Decompresses one entry, refusing more data than its recorded size
+    fn read(&mut self, entry: &ZipEntry) -> Result<Vec<u8>, AppError> {
+        let corrupt = || AppError::InvalidInput(format!("Corrupt zip entry {}", entry.name));
+        let mut header = [0; 30];
+        self.file
+            .seek(SeekFrom::Start(entry.local_header_offset))
+            .and_then(|_| self.file.read_exact(&mut header))
+            .map_err(|_| corrupt())?;
+        if u32_at(&header, 0) != LOCAL_HEADER {
+            return Err(corrupt());
+        }
+        let data_offset = entry.local_header_offset
+            + 30
+            + u16_at(&header, 26) as u64
+            + u16_at(&header, 28) as u64;
+        if data_offset + entry.compressed_size > self.len {
+            return Err(corrupt());
+        }
+
+        self.file.seek(SeekFrom::Start(data_offset)).map_err(|_| corrupt())?;
+        let compressed = (&mut self.file).take(entry.compressed_size);
+        let mut data = Vec::new();
+        let read = match entry.method {
+            METHOD_STORED => compressed.take(entry.size + 1).read_to_end(&mut data),
+            METHOD_DEFLATED => DeflateDecoder::new(compressed)
+                .take(entry.size + 1)
+                .read_to_end(&mut data),
+            method => return Err(AppError::InvalidInput(format!("Unsupported compression method {}", method))),
+        };
+        read.map_err(|_| corrupt())?;
+
+        if data.len() as u64 != entry.size || crc32fast::hash(&data) != entry.crc {
+            return Err(corrupt());
+        }
+        Ok(data)
+    }
+}
+
+/// MS-DOS time and date fields in UTC; zip cannot represent anything before 1980
+fn dos_timestamp(time: SystemTime) -> (u16, u16) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (year, month, day) = crate::templates::civil_from_days((secs / 86_400) as i64);
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let seconds = secs % 86_400;
+    let time = ((seconds / 3600) << 11) | (((seconds % 3600) / 60) << 5) | ((seconds % 60) / 2);
+    let date = (((year - 1980).min(127) as u64) << 9) | ((month as u64) << 5) | day as u64;
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRAWING: &str = r#"{"type":"excalidraw","version":2,"elements":[]}"#;
+
+    /// Empty folder in the system temp dir for one test
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("excaliapp-archive-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn zip_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Vec::new());
+        for (name, data) in entries {
+            zip.add(name, data, UNIX_EPOCH).unwrap();
+        }
+        zip.finish().unwrap()
+    }
+
+    /// Offset of the first central directory header, read from the end record
+    fn central_directory(bytes: &[u8]) -> usize {
+        u32_at(bytes, bytes.len() - 22 + 16) as usize
+    }
+
+    fn open(dir: &Path, bytes: &[u8]) -> Result<ZipReader, AppError> {
+        let path = dir.join("test.zip");
+        fs::write(&path, bytes).unwrap();
+        ZipReader::open(&path)
+    }
+
+    fn import_bytes(dir: &Path, bytes: &[u8], limits: &FileLimits) -> ImportReport {
+        let path = dir.join("test.zip");
+        fs::write(&path, bytes).unwrap();
+        let target = dir.join("vault");
+        fs::create_dir_all(&target).unwrap();
+        import(&path, &target, limits, |_| true).unwrap()
+    }
+
+    #[test]
+    fn written_archive_reads_back() {
+        let dir = scratch("round-trip");
+        let mut images = Vec::new();
+        // Incompressible, so it is stored rather than deflated
+        let mut seed = 7u32;
+        for _ in 0..4096 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            images.push((seed >> 16) as u8);
+        }
+        let long = DRAWING.repeat(50);
+        let entries: [(&str, &[u8]); 4] = [
+            ("a.excalidraw", DRAWING.as_bytes()),
+            ("notes/b ü.excalidraw", long.as_bytes()),
+            ("assets/noise.png", &images),
+            ("empty.excalidraw", b""),
+        ];
+        let modified = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        let mut zip = ZipWriter::new(Vec::new());
+        for (name, data) in entries {
+            zip.add(name, data, modified).unwrap();
+        }
+        let bytes = zip.finish().unwrap();
+
+        let mut zip = open(&dir, &bytes).unwrap();
+        let read = std::mem::take(&mut zip.entries);
+        assert_eq!(read.len(), entries.len());
+        for ((name, data), entry) in entries.iter().zip(&read) {
+            assert_eq!(entry.name, *name);
+            assert_eq!(zip.read(entry).unwrap(), *data);
+        }
+        assert_eq!(read[1].method, METHOD_DEFLATED);
+        assert_eq!(read[2].method, METHOD_STORED);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn archive_paths_outside_the_target_are_refused() {
+        let base = Path::new("/vault");
+        for name in [
+            "../evil.excalidraw",
+            "notes/../../evil.excalidraw",
+            "notes\\..\\..\\evil.excalidraw",
+            "/etc/evil.excalidraw",
+            "\\evil.excalidraw",
+            "C:/evil.excalidraw",
+            "~/evil.excalidraw",
+            "./",
+        ] {
+            assert!(security::safe_archive_path(base, name).is_err(), "{} was accepted", name);
+        }
+        assert_eq!(
+            security::safe_archive_path(base, "notes/./a.excalidraw").unwrap(),
+            base.join("notes").join("a.excalidraw")
+        );
+    }
+
+    #[test]
+    fn import_skips_entries_with_unsafe_paths() {
+        let dir = scratch("unsafe-paths");
+        let bytes = zip_bytes(&[
+            ("../evil.excalidraw", DRAWING.as_bytes()),
+            ("/abs.excalidraw", DRAWING.as_bytes()),
+            ("notes/ok.excalidraw", DRAWING.as_bytes()),
+        ]);
+        let report = import_bytes(&dir, &bytes, &FileLimits::default());
+
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(report.invalid.len(), 2);
+        assert!(dir.join("vault/notes/ok.excalidraw").is_file());
+        assert!(!dir.join("evil.excalidraw").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_central_directory_is_refused() {
+        let dir = scratch("truncated");
+        let mut bytes = zip_bytes(&[("a.excalidraw", DRAWING.as_bytes()), ("b.excalidraw", DRAWING.as_bytes())]);
+
+        // The end record promises a third entry the directory doesn't hold
+        let mut more = bytes.clone();
+        let count = more.len() - 22 + 10;
+        more[count..count + 2].copy_from_slice(&3u16.to_le_bytes());
+        more[count - 2..count].copy_from_slice(&3u16.to_le_bytes());
+        assert!(matches!(open(&dir, &more), Err(AppError::InvalidInput(_))));
+
+        // Cut off in the middle of the directory, end record and all
+        bytes.truncate(central_directory(&bytes) + 20);
+        assert!(matches!(open(&dir, &bytes), Err(AppError::InvalidInput(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entry_with_a_bad_crc_is_refused() {
+        let dir = scratch("bad-crc");
+        // Too short to deflate, so the bytes are stored as they are
+        let mut bytes = zip_bytes(&[("a.svg", b"<svg/>")]);
+        let data = 30 + "a.svg".len();
+        assert_eq!(&bytes[data..data + 6], b"<svg/>");
+        bytes[data + 1] = b'S';
+
+        let mut zip = open(&dir, &bytes).unwrap();
+        let entries = std::mem::take(&mut zip.entries);
+        assert!(matches!(zip.read(&entries[0]), Err(AppError::InvalidInput(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entry_larger_than_recorded_is_refused() {
+        let dir = scratch("larger-than-recorded");
+        let mut bytes = zip_bytes(&[("a.excalidraw", DRAWING.as_bytes())]);
+        let size = central_directory(&bytes) + 24;
+        bytes[size..size + 4].copy_from_slice(&4u32.to_le_bytes());
+
+        let mut zip = open(&dir, &bytes).unwrap();
+        let entries = std::mem::take(&mut zip.entries);
+        assert!(matches!(zip.read(&entries[0]), Err(AppError::InvalidInput(_))));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn import_skips_entries_over_the_size_limit() {
+        let dir = scratch("too-large");
+        let mut large = DRAWING.to_string();
+        large.push_str(&" ".repeat(2 * 1024 * 1024));
+        let bytes = zip_bytes(&[("large.excalidraw", large.as_bytes()), ("small.excalidraw", DRAWING.as_bytes())]);
+        let limits = FileLimits {
+            max_file_mb: 1,
+            ..FileLimits::default()
+        };
+        let report = import_bytes(&dir, &bytes, &limits);
+
+        assert_eq!(report.imported.len(), 1);
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(report.invalid[0].name, "large.excalidraw");
+        assert!(!dir.join("vault/large.excalidraw").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::branding::ExportBranding;
+use crate::compression;
+use crate::config::{AutoExportConfig, AutoExportFormat};
+use crate::error::{AppError, ResultExt};
+use crate::export;
+
+/// Quiet time after a drawing's last change before it is exported, so a
+/// burst of saves exports once
+pub const DEBOUNCE: Duration = Duration::from_millis(1500);
+
+/// Longest side of automatic PNG exports, in pixels
+const MAX_PNG_SIDE: f32 = 16384.0;
+
+/// Collects changed drawings and hands each to the exporter once it has
+/// settled
+#[derive(Default)]
+pub struct AutoExporter {
+    tx: OnceLock<Sender<PathBuf>>,
+}
+
+impl AutoExporter {
+    /// Starts the worker thread; `export` runs on it for each settled drawing
+    pub fn start(&self, export: impl Fn(&Path) + Send + 'static) {
+        let (tx, rx) = mpsc::channel::<PathBuf>();
+        if self.tx.set(tx).is_err() {
+            return;
+        }
+        std::thread::spawn(move || {
+            let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+            loop {
+                let received = match pending.values().min() {
+                    Some(changed) => rx.recv_timeout((*changed + DEBOUNCE).saturating_duration_since(Instant::now())),
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match received {
+                    Ok(path) => {
+                        pending.insert(path, Instant::now());
+                    }
+                    Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+                let settled: Vec<PathBuf> = pending
+                    .iter()
+                    .filter(|(_, changed)| changed.elapsed() >= DEBOUNCE)
+                    .map(|(path, _)| path.clone())
+                    .collect();
+                for path in settled {
+                    pending.remove(&path);
+                    export(&path);
+                }
+            }
+        });
+    }
+
+    /// Exports `path` once it stops changing; each change restarts the wait
+    pub fn schedule(&self, path: &Path) {
+        if let Some(tx) = self.tx.get() {
+            let _ = tx.send(path.to_path_buf());
+        }
+    }
+}
+
+/// Writes the configured images of `drawing` into `directory` under the
+/// drawing's name and returns their paths
+pub fn export(
+    drawing: &Path,
+    config: &AutoExportConfig,
+    directory: &Path,
+    branding: Option<&ExportBranding>,
+) -> Result<Vec<PathBuf>, AppError> {
+    if config.scale <= 0.0 {
+        return Err(AppError::InvalidInput("PNG scale must be positive".to_string()));
+    }
+    let content = compression::read_to_string(drawing).context("Failed to read drawing")?;
+    let svg = export::render_file_svg(&content, branding, None)?;
+    let plain = compression::with_drawing_extension(drawing, false);
+    let stem = plain.file_stem().ok_or_else(|| AppError::InvalidPath("Invalid file name".to_string()))?;
+    fs::create_dir_all(directory).context("Failed to create export folder")?;
+
+    let mut outputs = Vec::new();
+    for format in &config.formats {
+        let (extension, data) = match format {
+            AutoExportFormat::Svg => ("svg", svg.clone().into_bytes()),
+            AutoExportFormat::Png => ("png", export::svg_to_png(&svg, config.scale, MAX_PNG_SIDE)?.0),
+        };
+        let output = directory.join(format!("{}.{}", stem.to_string_lossy(), extension));
+        fs::write(&output, data).context(format!("Failed to write {}", output.display()))?;
+        outputs.push(output);
+    }
+    Ok(outputs)
+}
@@ -0,0 +1,390 @@
+use serde::{Deserialize, Serialize};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::prompts;
+
+/// Store file holding the background-work settings
+pub const STORE_FILE: &str = "background.json";
+pub const STORE_KEY: &str = "settings";
+
+/// How long a battery reading is trusted before the OS is asked again
+const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Poll interval while background work is paused on battery
+const BATTERY_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Threads taking jobs off the queue; each job spreads its own items over
+/// `max_threads` with `TaskManager::run`
+const QUEUE_WORKERS: usize = 2;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct BackgroundSettings {
+    /// Worker threads used by indexing, health checks and batch export
+    pub max_threads: usize,
+    /// Disk throughput cap shared by all background jobs; unlimited when unset
+    pub io_limit_mb_per_sec: Option<f64>,
+    /// Hold background work while the machine runs on battery
+    pub pause_on_battery: bool,
+}
+
+impl Default for BackgroundSettings {
+    fn default() -> Self {
+        // Leave half the cores to the editor
+        let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        Self {
+            max_threads: (cores / 2).max(1),
+            io_limit_mb_per_sec: None,
+            pause_on_battery: true,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskPriority {
+    Low,
+    Normal,
+    High,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskKind {
+    LinkIndex,
+    MetadataIndex,
+    AutoExport,
+    LockCleanup,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskState {
+    Queued,
+    Running,
+}
+
+/// A queued or running job, as `get_background_tasks` reports it
+#[derive(Debug, Serialize, Clone)]
+pub struct BackgroundTask {
+    pub id: u64,
+    pub kind: TaskKind,
+    /// What the job works on, usually a path
+    pub target: String,
+    pub priority: TaskPriority,
+    pub state: TaskState,
+    /// Asked to stop, but still finishing its current step
+    pub cancelled: bool,
+    /// Milliseconds since the Unix epoch
+    pub queued_at: i64,
+    pub started_at: Option<i64>,
+}
+
+/// Given to a running job so it can stop early once cancelled
+pub struct TaskHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TaskHandle {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+type Job = Box<dyn FnOnce(&TaskHandle) + Send>;
+
+struct QueuedTask {
+    task: BackgroundTask,
+    cancelled: Arc<AtomicBool>,
+    job: Job,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: Vec<QueuedTask>,
+    running: Vec<(BackgroundTask, Arc<AtomicBool>)>,
+    next_id: u64,
+    workers: usize,
+}
+
+#[derive(Default)]
+struct TaskQueue {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+}
+
+impl TaskQueue {
+    /// Takes the most urgent job, oldest first among equals, until the app
+    /// quits
+    fn work(&self) {
+        loop {
+            let (job, handle, id) = {
+                let mut queue = self.queue.lock().unwrap();
+                while queue.pending.is_empty() {
+                    queue = self.ready.wait(queue).unwrap();
+                }
+                let next = queue
+                    .pending
+                    .iter()
+                    .enumerate()
+                    .max_by_key(|(_, queued)| (queued.task.priority, std::cmp::Reverse(queued.task.id)))
+                    .map(|(index, _)| index)
+                    .unwrap_or_default();
+                let QueuedTask { mut task, cancelled, job } = queue.pending.remove(next);
+                task.state = TaskState::Running;
+                task.started_at = Some(prompts::now_millis());
+                let id = task.id;
+                queue.running.push((task, cancelled.clone()));
+                (job, TaskHandle { cancelled }, id)
+            };
+
+            // A failing job must not take the worker down with it
+            if panic::catch_unwind(AssertUnwindSafe(|| job(&handle))).is_err() {
+                tracing::error!("Background task {} panicked", id);
+            }
+            self.queue.lock().unwrap().running.retain(|(task, _)| task.id != id);
+        }
+    }
+}
+
+/// Runs heavy maintenance work within the configured thread, IO and power
+/// limits, and queues the jobs that run detached from any command
+pub struct TaskManager {
+    settings: Mutex<BackgroundSettings>,
+    /// When the IO budget is free again; each transfer reserves its share
+    io_available_at: Mutex<Instant>,
+    power: Mutex<Option<(Instant, bool)>>,
+    tasks: Arc<TaskQueue>,
+}
+
+impl TaskManager {
+    pub fn new(settings: BackgroundSettings) -> Self {
+        Self {
+            settings: Mutex::new(settings),
+            io_available_at: Mutex::new(Instant::now()),
+            power: Mutex::new(None),
+            tasks: Arc::default(),
+        }
+    }
+
+    /// Queues `job`, starting another worker while there are fewer than
+    /// `QUEUE_WORKERS`. A job of the same kind already waiting for the same
+    /// target covers this one; it keeps the higher priority of the two.
+    pub fn submit(
+        &self,
+        kind: TaskKind,
+        target: String,
+        priority: TaskPriority,
+        job: impl FnOnce(&TaskHandle) + Send + 'static,
+    ) -> u64 {
+        let mut queue = self.tasks.queue.lock().unwrap();
+        if let Some(queued) = queue
+            .pending
+            .iter_mut()
+            .find(|queued| queued.task.kind == kind && queued.task.target == target)
+        {
+            queued.task.priority = queued.task.priority.max(priority);
+            return queued.task.id;
+        }
+
+        queue.next_id += 1;
+        let id = queue.next_id;
+        queue.pending.push(QueuedTask {
+            task: BackgroundTask {
+                id,
+                kind,
+                target,
+                priority,
+                state: TaskState::Queued,
+                cancelled: false,
+                queued_at: prompts::now_millis(),
+                started_at: None,
+            },
+            cancelled: Arc::default(),
+            job: Box::new(job),
+        });
+        if queue.workers < QUEUE_WORKERS {
+            queue.workers += 1;
+            let tasks = self.tasks.clone();
+            std::thread::spawn(move || tasks.work());
+        }
+        self.tasks.ready.notify_one();
+        id
+    }
+
+    /// Running jobs, then the queued ones in the order they will run
+    pub fn tasks(&self) -> Vec<BackgroundTask> {
+        let queue = self.tasks.queue.lock().unwrap();
+        let mut pending: Vec<BackgroundTask> = queue.pending.iter().map(|queued| queued.task.clone()).collect();
+        pending.sort_by_key(|task| (std::cmp::Reverse(task.priority), task.id));
+        queue
+            .running
+            .iter()
+            .map(|(task, cancelled)| BackgroundTask {
+                cancelled: cancelled.load(Ordering::Relaxed),
+                ..task.clone()
+            })
+            .chain(pending)
+            .collect()
+    }
+
+    /// Drops a queued job, or asks a running one to stop; returns whether
+    /// there was one with `id`
+    pub fn cancel(&self, id: u64) -> bool {
+        let mut queue = self.tasks.queue.lock().unwrap();
+        if let Some(index) = queue.pending.iter().position(|queued| queued.task.id == id) {
+            queue.pending.remove(index);
+            return true;
+        }
+        match queue.running.iter().find(|(task, _)| task.id == id) {
+            Some((_, cancelled)) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn settings(&self) -> BackgroundSettings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    /// New limits apply to running jobs from their next item on
+    pub fn update(&self, settings: BackgroundSettings) {
+        *self.settings.lock().unwrap() = settings;
+    }
+
+    /// Processes `items` on up to `max_threads` workers, returning results in
+    /// input order. Each item waits for mains power first when configured to.
+    pub fn run<T, R, F>(&self, items: Vec<T>, work: F) -> Vec<R>
+    where
+        T: Send,
+        R: Send,
+        F: Fn(T) -> R + Sync,
+    {
+        let threads = self.settings().max_threads.clamp(1, items.len().max(1));
+        let slots: Vec<Mutex<Option<T>>> = items.into_iter().map(|item| Mutex::new(Some(item))).collect();
+        let results: Vec<Mutex<Option<R>>> = slots.iter().map(|_| Mutex::new(None)).collect();
+        let next = AtomicUsize::new(0);
+
+        std::thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(slot) = slots.get(index) else {
+                            break;
+                        };
+                        self.wait_for_power();
+                        if let Some(item) = slot.lock().unwrap().take() {
+                            *results[index].lock().unwrap() = Some(work(item));
+                        }
+                    }
+                });
+            }
+        });
+
+        results
+            .into_iter()
+            .filter_map(|result| result.into_inner().unwrap())
+            .collect()
+    }
+
+    /// Accounts for `bytes` of disk IO, sleeping until the transfer fits in
+    /// the shared throughput budget
+    pub fn throttle_io(&self, bytes: usize) {
+        let Some(limit) = self.settings().io_limit_mb_per_sec.filter(|l| *l > 0.0) else {
+            return;
+        };
+        let cost = Duration::from_secs_f64(bytes as f64 / (limit * 1024.0 * 1024.0));
+
+        let wait = {
+            let mut available_at = self.io_available_at.lock().unwrap();
+            let now = Instant::now();
+            let start = (*available_at).max(now);
+            *available_at = start + cost;
+            start - now
+        };
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+
+    fn wait_for_power(&self) {
+        let mut announced = false;
+        while self.settings().pause_on_battery && self.on_battery() {
+            if !announced {
+                tracing::info!("Background work paused while on battery");
+                announced = true;
+            }
+            std::thread::sleep(BATTERY_POLL_INTERVAL);
+        }
+        if announced {
+            tracing::info!("Background work resumed");
+        }
+    }
+
+    fn on_battery(&self) -> bool {
+        let mut cached = self.power.lock().unwrap();
+        if let Some((checked, on_battery)) = *cached
+            && checked.elapsed() < POWER_CHECK_INTERVAL
+        {
+            return on_battery;
+        }
+        let on_battery = on_battery_power();
+        *cached = Some((Instant::now(), on_battery));
+        on_battery
+    }
+}
+
+/// Best-effort check whether the machine currently runs on battery; unknown
+/// power states count as mains power so work is never held forever
+#[cfg(target_os = "linux")]
+pub fn on_battery_power() -> bool {
+    let Ok(entries) = std::fs::read_dir("/sys/class/power_supply") else {
+        return false;
+    };
+    let mut discharging = false;
+    for entry in entries.flatten() {
+        let read = |name: &str| {
+            std::fs::read_to_string(entry.path().join(name))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default()
+        };
+        match read("type").as_str() {
+            "Mains" if read("online") == "1" => return false,
+            "Battery" if read("status") == "Discharging" => discharging = true,
+            _ => {}
+        }
+    }
+    discharging
+}
+
+#[cfg(target_os = "macos")]
+pub fn on_battery_power() -> bool {
+    std::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+        .unwrap_or(false)
+}
+
+#[cfg(target_os = "windows")]
+pub fn on_battery_power() -> bool {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    // BatteryStatus 1 means "discharging"
+    std::process::Command::new("wmic")
+        .args(["path", "Win32_Battery", "get", "BatteryStatus", "/value"])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("BatteryStatus=1"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn on_battery_power() -> bool {
+    false
+}
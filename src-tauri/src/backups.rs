@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, ResultExt};
+use crate::paths;
+use crate::storage::{EntryMetadata, StorageBackend};
+
+/// Hidden folder next to the drawings used by `BackupLocation::Folder`
+pub const BACKUP_DIR: &str = ".backups";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupLocation {
+    /// `name.excalidraw.bak1` next to the file
+    #[default]
+    Sibling,
+    /// `.backups/name.excalidraw.bak1` in the file's folder
+    Folder,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BackupSettings {
+    /// Previous versions kept per file; 0 turns backups off
+    pub keep: usize,
+    pub location: BackupLocation,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupInfo {
+    /// 1 is the most recent
+    pub index: usize,
+    pub path: String,
+    #[serde(flatten)]
+    pub metadata: EntryMetadata,
+}
+
+fn backup_dir(file: &Path, location: BackupLocation) -> PathBuf {
+    let parent = file.parent().unwrap_or(Path::new(""));
+    match location {
+        BackupLocation::Sibling => parent.to_path_buf(),
+        BackupLocation::Folder => parent.join(BACKUP_DIR),
+    }
+}
+
+fn backup_path(file: &Path, location: BackupLocation, index: usize) -> PathBuf {
+    let name = file.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+    backup_dir(file, location).join(format!("{}.bak{}", name, index))
+}
+
+/// Existing backups of `file`, most recent first
+pub fn list(backend: &dyn StorageBackend, file: &Path, location: BackupLocation) -> Vec<BackupInfo> {
+    let Some(name) = file.file_name().map(|n| n.to_string_lossy().to_string()) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}.bak", name);
+    let Ok(entries) = backend.read_dir(&backup_dir(file, location)) else {
+        return Vec::new();
+    };
+
+    let mut backups: Vec<BackupInfo> = entries
+        .into_iter()
+        .filter(|entry| !entry.is_directory)
+        .filter_map(|entry| {
+            let index = entry.name.strip_prefix(&prefix)?.parse::<usize>().ok()?;
+            (index > 0).then_some(BackupInfo {
+                index,
+                path: paths::normalize(&entry.path),
+                metadata: entry.metadata,
+            })
+        })
+        .collect();
+    backups.sort_by_key(|backup| backup.index);
+    backups
+}
+
+/// Shifts `bak1..` up by one, dropping those beyond `settings.keep`, and
+/// stores `previous` as `bak1`
+pub fn rotate(
+    backend: &dyn StorageBackend,
+    file: &Path,
+    previous: &str,
+    settings: &BackupSettings,
+) -> Result<(), AppError> {
+    if settings.keep == 0 {
+        return Ok(());
+    }
+
+    let dir = backup_dir(file, settings.location);
+    if !backend.is_dir(&dir) {
+        backend
+            .create_dir(&dir)
+            .context("Failed to create backup folder")?;
+    }
+
+    // Highest first, so each rename targets a free slot
+    for backup in list(backend, file, settings.location).into_iter().rev() {
+        let path = backup_path(file, settings.location, backup.index);
+        let result = if backup.index >= settings.keep {
+            backend.remove_file(&path)
+        } else {
+            backend.rename(&path, &backup_path(file, settings.location, backup.index + 1))
+        };
+        result.context("Failed to rotate backups")?;
+    }
+
+    backend
+        .write(&backup_path(file, settings.location, 1), previous)
+        .context("Failed to write backup")
+}
+
+pub fn read(
+    backend: &dyn StorageBackend,
+    file: &Path,
+    location: BackupLocation,
+    index: usize,
+) -> Result<String, AppError> {
+    backend
+        .read_to_string(&backup_path(file, location, index))
+        .context(format!("Backup {} not available", index))
+}
@@ -0,0 +1,79 @@
+use serde::Serialize;
+
+use crate::error::AppError;
+
+/// Emitted to the window running a batch while it works through the items
+pub const PROGRESS_EVENT: &str = "batch-progress";
+
+/// Smaller batches finish too quickly to be worth a `PROGRESS_EVENT`
+pub const PROGRESS_MIN_ITEMS: usize = 20;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchOperation {
+    Delete,
+    Move,
+    Export,
+    Compress,
+}
+
+/// Outcome for one path of a batch
+#[derive(Debug, Serialize)]
+pub struct BatchItemResult {
+    pub path: String,
+    /// The moved or compressed file's new path, or the written export
+    pub output: Option<String>,
+    pub error: Option<AppError>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    pub operation: BatchOperation,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// In the order the paths were given
+    pub items: Vec<BatchItemResult>,
+}
+
+/// Payload of `PROGRESS_EVENT`
+#[derive(Debug, Serialize, Clone)]
+pub struct BatchProgress {
+    pub operation: BatchOperation,
+    pub done: usize,
+    pub total: usize,
+    /// The item just finished
+    pub path: String,
+}
+
+/// Runs `work` for every path, collecting each result instead of stopping at
+/// the first failure
+pub fn run(
+    operation: BatchOperation,
+    paths: Vec<String>,
+    mut work: impl FnMut(&str) -> Result<Option<String>, AppError>,
+    on_progress: impl Fn(BatchProgress),
+) -> BatchReport {
+    let total = paths.len();
+    let mut items = Vec::with_capacity(total);
+    for (index, path) in paths.into_iter().enumerate() {
+        let (output, error) = match work(&path) {
+            Ok(output) => (output, None),
+            Err(e) => (None, Some(e)),
+        };
+        on_progress(BatchProgress {
+            operation,
+            done: index + 1,
+            total,
+            path: path.clone(),
+        });
+        items.push(BatchItemResult { path, output, error });
+    }
+
+    let failed = items.iter().filter(|item| item.error.is_some()).count();
+    BatchReport {
+        operation,
+        succeeded: total - failed,
+        failed,
+        items,
+    }
+}
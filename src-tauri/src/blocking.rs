@@ -0,0 +1,25 @@
+use tauri::{AppHandle, Manager};
+
+use crate::error::AppError;
+use crate::AppState;
+
+/// Runs file system work of a command on the blocking thread pool. Async
+/// commands share a few runtime threads, so a folder walk or delete on a
+/// slow disk or network share run in place would hold up every other
+/// command meanwhile.
+pub async fn run<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| AppError::Internal(format!("Background task failed: {}", e)))?
+}
+
+/// `run` for the usual command body, which needs the app and its state
+pub async fn with_state<T: Send + 'static>(
+    app: &AppHandle,
+    work: impl FnOnce(&AppHandle, &AppState) -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    let app = app.clone();
+    run(move || work(&app, &app.state::<AppState>())).await
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::paths;
+use crate::render::{self, Bounds};
+
+/// Store file holding per-workspace export branding, keyed by workspace directory
+pub const STORE_FILE: &str = "export_branding.json";
+pub const STORE_KEY: &str = "export_branding";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MarkPosition {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+    BottomCenter,
+    Center,
+}
+
+/// A text or image mark placed on every exported page
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BrandMark {
+    pub text: Option<String>,
+    /// `data:` URL of a logo; takes precedence over `text`
+    pub image_data_url: Option<String>,
+    pub position: MarkPosition,
+    /// 0.0 (invisible) to 1.0 (opaque)
+    pub opacity: Option<f64>,
+    /// Image width (or text size basis) in scene units
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ExportBranding {
+    pub watermark: Option<BrandMark>,
+    pub footer: Option<BrandMark>,
+    /// Color of a frame drawn around the exported page
+    pub brand_color: Option<String>,
+    pub frame_width: Option<f64>,
+}
+
+pub type BrandingMap = HashMap<String, ExportBranding>;
+
+/// Picks the branding of the innermost configured workspace containing `path`
+pub fn for_path<'a>(all: &'a BrandingMap, path: &Path) -> Option<&'a ExportBranding> {
+    let path = paths::normalize(path);
+    all.iter()
+        .filter(|(workspace, _)| Path::new(&path).starts_with(workspace))
+        .max_by_key(|(workspace, _)| workspace.len())
+        .map(|(_, branding)| branding)
+}
+
+fn render_mark(bounds: &Bounds, mark: &BrandMark, default_opacity: f64, margin: f64) -> String {
+    let opacity = mark.opacity.unwrap_or(default_opacity).clamp(0.0, 1.0);
+
+    if let Some(href) = mark.image_data_url.as_deref().filter(|h| !h.is_empty()) {
+        let width = mark.width.unwrap_or(bounds.width() / 6.0).min(bounds.width());
+        let height = mark.height.unwrap_or(width / 2.0).min(bounds.height());
+        let (x, y) = match mark.position {
+            MarkPosition::TopLeft => (bounds.min_x + margin, bounds.min_y + margin),
+            MarkPosition::TopRight => (bounds.max_x - margin - width, bounds.min_y + margin),
+            MarkPosition::BottomLeft => (bounds.min_x + margin, bounds.max_y - margin - height),
+            MarkPosition::BottomRight => (
+                bounds.max_x - margin - width,
+                bounds.max_y - margin - height,
+            ),
+            MarkPosition::BottomCenter => (
+                bounds.min_x + (bounds.width() - width) / 2.0,
+                bounds.max_y - margin - height,
+            ),
+            MarkPosition::Center => (
+                bounds.min_x + (bounds.width() - width) / 2.0,
+                bounds.min_y + (bounds.height() - height) / 2.0,
+            ),
+        };
+        return format!(
+            r#"<image x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" href="{}" opacity="{:.2}" preserveAspectRatio="xMidYMid meet"/>"#,
+            x,
+            y,
+            width,
+            height,
+            render::escape_xml(href),
+            opacity
+        );
+    }
+
+    let Some(text) = mark.text.as_deref().filter(|t| !t.trim().is_empty()) else {
+        return String::new();
+    };
+
+    let font_size = match mark.position {
+        MarkPosition::Center => (bounds.width().min(bounds.height()) / 10.0).clamp(12.0, 64.0),
+        _ => 12.0,
+    };
+    let (x, y, anchor) = match mark.position {
+        MarkPosition::TopLeft => (bounds.min_x + margin, bounds.min_y + margin + font_size, "start"),
+        MarkPosition::TopRight => (bounds.max_x - margin, bounds.min_y + margin + font_size, "end"),
+        MarkPosition::BottomLeft => (bounds.min_x + margin, bounds.max_y - margin, "start"),
+        MarkPosition::BottomRight => (bounds.max_x - margin, bounds.max_y - margin, "end"),
+        MarkPosition::BottomCenter => (
+            bounds.min_x + bounds.width() / 2.0,
+            bounds.max_y - margin,
+            "middle",
+        ),
+        MarkPosition::Center => (
+            bounds.min_x + bounds.width() / 2.0,
+            bounds.min_y + bounds.height() / 2.0,
+            "middle",
+        ),
+    };
+    format!(
+        r#"<text x="{:.2}" y="{:.2}" font-family="Helvetica" font-size="{:.0}" fill="{}" text-anchor="{}" opacity="{:.2}">{}</text>"#,
+        x,
+        y,
+        font_size,
+        render::escape_xml(mark.color.as_deref().unwrap_or("#888888")),
+        anchor,
+        opacity,
+        render::escape_xml(text)
+    )
+}
+
+/// Renders the branding overlay (frame, watermark, footer) for a page covering `bounds`
+pub fn render_overlay(bounds: &Bounds, branding: &ExportBranding) -> String {
+    let mut svg = String::new();
+    let frame_width = branding.frame_width.unwrap_or(6.0).max(0.0);
+
+    if let Some(color) = branding.brand_color.as_deref().filter(|c| !c.is_empty()) {
+        svg.push_str(&format!(
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="none" stroke="{}" stroke-width="{:.2}"/>"#,
+            bounds.min_x + frame_width / 2.0,
+            bounds.min_y + frame_width / 2.0,
+            (bounds.width() - frame_width).max(0.0),
+            (bounds.height() - frame_width).max(0.0),
+            render::escape_xml(color),
+            frame_width
+        ));
+    }
+
+    let margin = frame_width + 4.0;
+    if let Some(watermark) = &branding.watermark {
+        svg.push_str(&render_mark(bounds, watermark, 0.15, margin));
+    }
+    if let Some(footer) = &branding.footer {
+        svg.push_str(&render_mark(bounds, footer, 0.8, margin));
+    }
+
+    svg
+}
@@ -0,0 +1,245 @@
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::background::TaskManager;
+use crate::compression;
+use crate::error::AppError;
+use crate::ignores::IgnoreRules;
+use crate::paths;
+
+/// Emitted to the window reading a drawing whose checksum doesn't match
+pub const MISMATCH_EVENT: &str = "file-integrity-mismatch";
+
+/// `appState` entry holding the checksum; Excalidraw drops unknown entries
+/// on load, and the next save writes it again
+const CHECKSUM_KEY: &str = "excaliappChecksum";
+const ALGORITHM_PREFIX: &str = "sha256:";
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumStatus {
+    /// The content matches its checksum
+    Verified,
+    /// Saved without a checksum
+    Missing,
+    /// Changed since it was saved by something that didn't update the
+    /// checksum, or corrupted
+    Mismatch,
+    /// Not a readable scene at all
+    Unreadable,
+}
+
+/// Answer of `verify_file_integrity`, and the payload of `MISMATCH_EVENT`
+#[derive(Debug, Serialize, Clone)]
+pub struct FileVerification {
+    pub path: String,
+    pub status: ChecksumStatus,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+    /// Why the file couldn't be read
+    pub error: Option<String>,
+}
+
+/// Answer of `verify_vault_integrity`
+#[derive(Debug, Serialize, Clone)]
+pub struct VaultVerification {
+    pub directory: String,
+    pub files_checked: usize,
+    pub verified: usize,
+    pub missing: usize,
+    /// Mismatched and unreadable drawings, by path
+    pub failed: Vec<FileVerification>,
+}
+
+/// Passes everything written on to the hasher
+struct HashWriter<'a>(&'a mut Sha256);
+
+impl Write for HashWriter<'_> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.update(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Where in the scene `feed` is
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Scene,
+    AppState,
+    Nested,
+}
+
+/// Feeds `value` to the hasher as compact JSON with sorted keys, so the
+/// checksum doesn't depend on formatting or key order. The stored checksum
+/// is left out of `appState`.
+fn feed(hasher: &mut Sha256, value: &Value, level: Level) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map
+                .keys()
+                .filter(|key| level != Level::AppState || key.as_str() != CHECKSUM_KEY)
+                .collect();
+            keys.sort();
+            hasher.update(b"{");
+            for (index, key) in keys.into_iter().enumerate() {
+                if index > 0 {
+                    hasher.update(b",");
+                }
+                let _ = serde_json::to_writer(HashWriter(hasher), key);
+                hasher.update(b":");
+                let child = if level == Level::Scene && key == "appState" {
+                    Level::AppState
+                } else {
+                    Level::Nested
+                };
+                feed(hasher, &map[key], child);
+            }
+            hasher.update(b"}");
+        }
+        Value::Array(items) => {
+            hasher.update(b"[");
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    hasher.update(b",");
+                }
+                feed(hasher, item, Level::Nested);
+            }
+            hasher.update(b"]");
+        }
+        scalar => {
+            let _ = serde_json::to_writer(HashWriter(hasher), scalar);
+        }
+    }
+}
+
+/// Checksum of a scene, leaving out the stored checksum itself
+fn checksum(scene: &Value) -> String {
+    let mut hasher = Sha256::new();
+    feed(&mut hasher, scene, Level::Scene);
+    format!("{}{:x}", ALGORITHM_PREFIX, hasher.finalize())
+}
+
+/// Writes the checksum of `content` into its `appState`, keeping the input's
+/// formatting: pretty printed input stays pretty printed
+pub fn seal(content: &str) -> Result<String, AppError> {
+    let mut scene: Value = serde_json::from_str(content)?;
+    let sum = checksum(&scene);
+    let scene_map = scene.as_object_mut()
+        .ok_or_else(|| AppError::InvalidInput("Content is not a JSON object".to_string()))?;
+    let app_state = scene_map
+        .entry("appState")
+        .or_insert_with(|| Value::Object(Default::default()));
+    let Some(app_state) = app_state.as_object_mut() else {
+        return Err(AppError::InvalidInput("appState is not an object".to_string()));
+    };
+    app_state.insert(CHECKSUM_KEY.to_string(), Value::String(sum));
+
+    let pretty = content.trim_start().starts_with("{\n") || content.contains("\n  ");
+    let mut sealed = if pretty {
+        serde_json::to_string_pretty(&scene)
+    } else {
+        serde_json::to_string(&scene)
+    }
+    .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+    if content.ends_with('\n') {
+        sealed.push('\n');
+    }
+    Ok(sealed)
+}
+
+/// Brings the checksum of content the app rewrote itself up to date;
+/// content without one is returned as is
+pub fn refresh(content: String) -> String {
+    if !may_have_checksum(&content) {
+        return content;
+    }
+    seal(&content).unwrap_or(content)
+}
+
+/// Cheap test whether `content` may carry a checksum, before parsing it
+pub fn may_have_checksum(content: &str) -> bool {
+    content.contains(CHECKSUM_KEY)
+}
+
+/// Checks `content` against the checksum stored in it
+pub fn verify(path: &Path, content: &str) -> FileVerification {
+    let mut verification = FileVerification {
+        path: paths::normalize(path),
+        status: ChecksumStatus::Unreadable,
+        expected: None,
+        actual: None,
+        error: None,
+    };
+    let scene: Value = match serde_json::from_str(content) {
+        Ok(scene) => scene,
+        Err(e) => {
+            verification.error = Some(format!("Invalid JSON: {}", e));
+            return verification;
+        }
+    };
+    verification.expected = scene
+        .get("appState")
+        .and_then(|s| s.get(CHECKSUM_KEY))
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    let Some(expected) = &verification.expected else {
+        verification.status = ChecksumStatus::Missing;
+        return verification;
+    };
+    let actual = checksum(&scene);
+    verification.status = if *expected == actual {
+        ChecksumStatus::Verified
+    } else {
+        ChecksumStatus::Mismatch
+    };
+    verification.actual = Some(actual);
+    verification
+}
+
+fn verify_file(path: &Path, tasks: &TaskManager) -> FileVerification {
+    match compression::read_to_string(path) {
+        Ok(content) => {
+            tasks.throttle_io(content.len());
+            verify(path, &content)
+        }
+        Err(e) => FileVerification {
+            path: paths::normalize(path),
+            status: ChecksumStatus::Unreadable,
+            expected: None,
+            actual: None,
+            error: Some(format!("Failed to read file: {}", e)),
+        },
+    }
+}
+
+/// Verifies every drawing below `directory` on the background workers
+pub fn verify_vault(directory: &Path, ignore: &IgnoreRules, tasks: &TaskManager) -> Result<VaultVerification, AppError> {
+    let mut files = Vec::new();
+    crate::collect_excalidraw_files_recursive(directory, ignore, &mut files)?;
+    let paths: Vec<PathBuf> = files.iter().map(|f| PathBuf::from(&f.path)).collect();
+    let files_checked = paths.len();
+
+    let results = tasks.run(paths, |path| verify_file(&path, tasks));
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    let (verified, missing) = (count(ChecksumStatus::Verified), count(ChecksumStatus::Missing));
+    let mut failed: Vec<FileVerification> = results
+        .into_iter()
+        .filter(|r| matches!(r.status, ChecksumStatus::Mismatch | ChecksumStatus::Unreadable))
+        .collect();
+    failed.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(VaultVerification {
+        directory: paths::normalize(directory),
+        files_checked,
+        verified,
+        missing,
+        failed,
+    })
+}
@@ -0,0 +1,68 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tauri_plugin_dialog::MessageDialogResult;
+use tokio::sync::oneshot;
+
+/// Emitted to a window whose unsaved changes should be saved before it closes
+pub const SAVE_EVENT: &str = "save-before-close";
+
+/// How long a window's frontend gets to save before its drafts are written
+/// instead, e.g. when the webview hangs
+pub const SAVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+/// The answer to the unsaved-changes dialog; closing the dialog cancels
+pub fn choice(result: &MessageDialogResult, save: &str, discard: &str) -> CloseChoice {
+    match result {
+        MessageDialogResult::Yes => CloseChoice::Save,
+        MessageDialogResult::No => CloseChoice::Discard,
+        MessageDialogResult::Custom(label) if label == save => CloseChoice::Save,
+        MessageDialogResult::Custom(label) if label == discard => CloseChoice::Discard,
+        _ => CloseChoice::Cancel,
+    }
+}
+
+/// Windows showing the unsaved-changes dialog, and the frontend saves they
+/// wait for
+#[derive(Default)]
+pub struct CloseRequests {
+    asking: Mutex<HashSet<String>>,
+    saves: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+}
+
+impl CloseRequests {
+    /// False when window `label` is already asking, so a second close
+    /// request doesn't stack another dialog
+    pub fn begin(&self, label: &str) -> bool {
+        self.asking.lock().unwrap().insert(label.to_string())
+    }
+
+    pub fn end(&self, label: &str) {
+        self.asking.lock().unwrap().remove(label);
+        self.saves.lock().unwrap().remove(label);
+    }
+
+    /// Resolves with the frontend's report once it has saved
+    pub fn expect_save(&self, label: &str) -> oneshot::Receiver<bool> {
+        let (sender, receiver) = oneshot::channel();
+        self.saves.lock().unwrap().insert(label.to_string(), sender);
+        receiver
+    }
+
+    /// Returns whether window `label` was waiting for the save
+    pub fn save_finished(&self, label: &str, saved: bool) -> bool {
+        self.saves
+            .lock()
+            .unwrap()
+            .remove(label)
+            .is_some_and(|sender| sender.send(saved).is_ok())
+    }
+}
@@ -0,0 +1,400 @@
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tungstenite::handshake::server::{Callback, ErrorResponse, Request, Response};
+use tungstenite::{Message, WebSocket};
+
+use crate::error::AppError;
+use crate::share_server;
+
+/// Emitted to the collaborating window with elements changed by peers
+pub const UPDATE_EVENT: &str = "collab-update";
+
+/// Emitted to the collaborating window when peers come and go
+pub const STATUS_EVENT: &str = "collab-status";
+
+/// Port used when `host_collab_session` names none
+pub const DEFAULT_PORT: u16 = 8766;
+
+/// How long a peer thread waits for a message before sending queued ones
+const POLL: Duration = Duration::from_millis(50);
+
+/// How long connecting and the WebSocket handshake may take
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What peers send each other, as JSON text frames
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Envelope {
+    /// Every element, sent by the host to a peer that just joined
+    Snapshot { elements: Vec<Value> },
+    /// Elements changed since the last update
+    Update { elements: Vec<Value> },
+}
+
+fn version(element: &Value, field: &str) -> i64 {
+    element.get(field).and_then(Value::as_i64).unwrap_or(0)
+}
+
+/// Element-level last-writer-wins, the way Excalidraw reconciles scenes:
+/// the higher `version` wins, and on a tie the lower `versionNonce`
+pub fn wins(incoming: &Value, current: &Value) -> bool {
+    let (incoming_version, current_version) = (version(incoming, "version"), version(current, "version"));
+    incoming_version > current_version
+        || (incoming_version == current_version
+            && version(incoming, "versionNonce") < version(current, "versionNonce"))
+}
+
+/// The shared elements of a session, in drawing order
+#[derive(Debug, Default)]
+pub struct Scene {
+    elements: HashMap<String, Value>,
+    order: Vec<String>,
+}
+
+impl Scene {
+    pub fn from_content(content: &str) -> Result<Self, AppError> {
+        let scene: Value = serde_json::from_str(content)?;
+        let mut result = Self::default();
+        let elements = scene.get("elements").and_then(Value::as_array).cloned().unwrap_or_default();
+        result.merge(elements);
+        Ok(result)
+    }
+
+    /// Keeps the incoming elements that win over the known ones and returns
+    /// them; elements without an id are dropped
+    pub fn merge(&mut self, incoming: Vec<Value>) -> Vec<Value> {
+        let mut accepted = Vec::new();
+        for element in incoming {
+            let Some(id) = element.get("id").and_then(Value::as_str).map(str::to_string) else {
+                continue;
+            };
+            match self.elements.get(&id) {
+                Some(current) if !wins(&element, current) => continue,
+                Some(_) => {}
+                None => self.order.push(id.clone()),
+            }
+            self.elements.insert(id, element.clone());
+            accepted.push(element);
+        }
+        accepted
+    }
+
+    pub fn elements(&self) -> Vec<Value> {
+        self.order.iter().filter_map(|id| self.elements.get(id).cloned()).collect()
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CollabRole {
+    Host,
+    Guest,
+}
+
+/// Answer of `get_collab_status` and payload of `STATUS_EVENT`
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct CollabStatus {
+    pub active: bool,
+    pub role: Option<CollabRole>,
+    /// The hosted drawing; unset for guests
+    pub file_path: Option<String>,
+    /// Addresses guests join at, token included; the host's address for guests
+    pub urls: Vec<String>,
+    /// Connected peers; a guest only ever sees the host
+    pub peers: usize,
+}
+
+/// Payload of `UPDATE_EVENT`
+#[derive(Debug, Serialize, Clone)]
+pub struct CollabUpdate {
+    pub elements: Vec<Value>,
+}
+
+/// What a session reports back to the app, from its network threads
+pub enum CollabEvent {
+    /// Elements changed by peers
+    Update(CollabUpdate),
+    /// A peer joined or left
+    Status(CollabStatus),
+}
+
+pub type Notify = Arc<dyn Fn(CollabEvent) + Send + Sync>;
+
+struct Shared {
+    role: CollabRole,
+    file_path: Option<String>,
+    urls: Vec<String>,
+    scene: Mutex<Scene>,
+    /// Outgoing queues of the connected peers
+    peers: Mutex<Vec<(u64, Sender<String>)>>,
+    next_peer: AtomicU64,
+    stop: AtomicBool,
+    notify: Notify,
+}
+
+impl Shared {
+    fn new(role: CollabRole, file_path: Option<String>, urls: Vec<String>, scene: Scene, notify: Notify) -> Arc<Self> {
+        Arc::new(Self {
+            role,
+            file_path,
+            urls,
+            scene: Mutex::new(scene),
+            peers: Mutex::new(Vec::new()),
+            next_peer: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+            notify,
+        })
+    }
+
+    fn add_peer(&self) -> (u64, Receiver<String>) {
+        let (sender, receiver) = channel();
+        let id = self.next_peer.fetch_add(1, Ordering::Relaxed);
+        self.peers.lock().unwrap().push((id, sender));
+        (self.notify)(CollabEvent::Status(self.status()));
+        (id, receiver)
+    }
+
+    fn remove_peer(&self, id: u64) {
+        self.peers.lock().unwrap().retain(|(peer, _)| *peer != id);
+        (self.notify)(CollabEvent::Status(self.status()));
+    }
+
+    fn status(&self) -> CollabStatus {
+        CollabStatus {
+            active: true,
+            role: Some(self.role),
+            file_path: self.file_path.clone(),
+            urls: self.urls.clone(),
+            peers: self.peers.lock().unwrap().len(),
+        }
+    }
+
+    /// Queues an update for every peer but `except`
+    fn broadcast(&self, elements: &[Value], except: Option<u64>) {
+        if elements.is_empty() {
+            return;
+        }
+        let Ok(text) = serde_json::to_string(&Envelope::Update {
+            elements: elements.to_vec(),
+        }) else {
+            return;
+        };
+        for (id, sender) in self.peers.lock().unwrap().iter() {
+            if Some(*id) != except {
+                let _ = sender.send(text.clone());
+            }
+        }
+    }
+
+    /// Merges what a peer sent, tells the app and passes it on to the others
+    fn receive(&self, text: &str, from: u64) {
+        let elements = match serde_json::from_str(text) {
+            Ok(Envelope::Snapshot { elements } | Envelope::Update { elements }) => elements,
+            Err(e) => {
+                tracing::debug!("Ignoring malformed collaboration message: {}", e);
+                return;
+            }
+        };
+        let accepted = self.scene.lock().unwrap().merge(elements);
+        if accepted.is_empty() {
+            return;
+        }
+        self.broadcast(&accepted, Some(from));
+        (self.notify)(CollabEvent::Update(CollabUpdate { elements: accepted }));
+    }
+
+    /// Relays between one socket and the session until either side stops
+    fn pump(&self, socket: &mut WebSocket<TcpStream>, id: u64, outgoing: Receiver<String>) {
+        loop {
+            if self.stop.load(Ordering::Relaxed) {
+                let _ = socket.close(None);
+                let _ = socket.flush();
+                return;
+            }
+            while let Ok(text) = outgoing.try_recv() {
+                if let Err(e) = socket.send(Message::text(text)) {
+                    tracing::debug!("Collaboration peer {} went away: {}", id, e);
+                    return;
+                }
+            }
+            match socket.read() {
+                Ok(Message::Text(text)) => self.receive(text.as_str(), id),
+                Ok(Message::Close(_)) => return,
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                Err(e) => {
+                    tracing::debug!("Collaboration peer {} went away: {}", id, e);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+pub struct CollabSession {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl CollabSession {
+    /// Shares `content` with guests connecting to `port` on any interface;
+    /// 0 picks a free port
+    pub fn host(file_path: String, content: &str, port: u16, notify: Notify) -> Result<Self, AppError> {
+        let scene = Scene::from_content(content)?;
+        let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))
+            .map_err(|e| AppError::Network(format!("Failed to listen on port {}: {}", port, e)))?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let token = share_server::new_token()?;
+        let urls = share_server::lan_address()
+            .into_iter()
+            .chain([IpAddr::V4(Ipv4Addr::LOCALHOST)])
+            .map(|ip| format!("ws://{}/?token={}", SocketAddr::new(ip, port), token))
+            .collect();
+
+        let shared = Shared::new(CollabRole::Host, Some(file_path), urls, scene, notify);
+        let thread = {
+            let shared = shared.clone();
+            std::thread::spawn(move || accept_loop(listener, shared, token))
+        };
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Connects to a host; its snapshot arrives as the first update
+    pub fn join(url: &str, notify: Notify) -> Result<Self, AppError> {
+        let parsed = Url::parse(url.trim()).map_err(|e| AppError::InvalidInput(format!("Invalid session URL: {}", e)))?;
+        if parsed.scheme() != "ws" {
+            return Err(AppError::InvalidInput("Session URLs start with ws://".to_string()));
+        }
+        let address = parsed
+            .socket_addrs(|| Some(DEFAULT_PORT))
+            .ok()
+            .and_then(|addresses| addresses.into_iter().next())
+            .ok_or_else(|| AppError::InvalidInput("Session URL names no reachable host".to_string()))?;
+
+        let stream = TcpStream::connect_timeout(&address, HANDSHAKE_TIMEOUT)
+            .map_err(|e| AppError::Network(format!("Failed to reach {}: {}", address, e)))?;
+        stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let (mut socket, _) = tungstenite::client(parsed.as_str(), stream).map_err(|e| match e {
+            tungstenite::HandshakeError::Failure(tungstenite::Error::Http(response))
+                if response.status() == tungstenite::http::StatusCode::UNAUTHORIZED =>
+            {
+                AppError::PermissionDenied("The session refused the access token".to_string())
+            }
+            e => AppError::Network(format!("Failed to join session: {}", e)),
+        })?;
+        socket.get_mut().set_read_timeout(Some(POLL))?;
+
+        let shared = Shared::new(CollabRole::Guest, None, vec![url.trim().to_string()], Scene::default(), notify);
+        let (id, outgoing) = shared.add_peer();
+        let thread = {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                shared.pump(&mut socket, id, outgoing);
+                shared.remove_peer(id);
+            })
+        };
+        Ok(Self {
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Merges local edits and sends the ones that won to every peer
+    pub fn publish(&self, elements: Vec<Value>) -> usize {
+        let accepted = self.shared.scene.lock().unwrap().merge(elements);
+        self.shared.broadcast(&accepted, None);
+        accepted.len()
+    }
+
+    pub fn status(&self) -> CollabStatus {
+        self.shared.status()
+    }
+
+    /// Closes every connection; returns once the network threads are done
+    pub fn stop(mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn accept_loop(listener: TcpListener, shared: Arc<Shared>, token: String) {
+    let mut peers = Vec::new();
+    while !shared.stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((stream, address)) => {
+                let shared = shared.clone();
+                let token = token.clone();
+                peers.push(std::thread::spawn(move || {
+                    if let Err(e) = serve_peer(stream, &shared, &token) {
+                        tracing::debug!("Collaboration peer {} was refused: {}", address, e);
+                    }
+                }));
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL),
+            Err(e) => {
+                tracing::warn!("Collaboration server stopped accepting: {}", e);
+                break;
+            }
+        }
+        peers.retain(|peer: &JoinHandle<()>| !peer.is_finished());
+    }
+    for peer in peers {
+        let _ = peer.join();
+    }
+}
+
+/// Refuses handshakes whose URL lacks the session's `token=`
+struct TokenCheck<'a>(&'a str);
+
+impl Callback for TokenCheck<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let given = request
+            .uri()
+            .query()
+            .unwrap_or_default()
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+            .unwrap_or_default();
+        if share_server::token_matches(given, self.0) {
+            return Ok(response);
+        }
+        let mut refusal = ErrorResponse::new(Some("Missing or wrong access token".to_string()));
+        *refusal.status_mut() = tungstenite::http::StatusCode::UNAUTHORIZED;
+        Err(refusal)
+    }
+}
+
+fn serve_peer(stream: TcpStream, shared: &Shared, token: &str) -> Result<(), AppError> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let mut socket =
+        tungstenite::accept_hdr(stream, TokenCheck(token)).map_err(|e| AppError::Network(e.to_string()))?;
+    socket.get_mut().set_read_timeout(Some(POLL))?;
+
+    let snapshot = Envelope::Snapshot {
+        elements: shared.scene.lock().unwrap().elements(),
+    };
+    socket
+        .send(Message::text(serde_json::to_string(&snapshot)?))
+        .map_err(|e| AppError::Network(e.to_string()))?;
+
+    let (id, outgoing) = shared.add_peer();
+    shared.pump(&mut socket, id, outgoing);
+    shared.remove_peer(id);
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+use crate::error::AppError;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CompactResult {
+    pub content: String,
+    pub removed_elements: usize,
+    pub removed_files: usize,
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+}
+
+/// Strips `isDeleted` elements, references to them, and `files` entries no
+/// remaining image element points to. Formatting follows the input: pretty
+/// printed input stays pretty printed.
+pub fn compact(content: &str) -> Result<CompactResult, AppError> {
+    let mut scene: Value = serde_json::from_str(content)?;
+    let elements = scene
+        .get_mut("elements")
+        .and_then(|e| e.as_array_mut())
+        .ok_or_else(|| AppError::InvalidInput("Scene has no elements array".to_string()))?;
+
+    let before = elements.len();
+    let removed_ids: HashSet<String> = elements
+        .iter()
+        .filter(|e| e.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false))
+        .filter_map(|e| e.get("id").and_then(|id| id.as_str()).map(|id| id.to_string()))
+        .collect();
+    elements.retain(|e| !e.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false));
+    let removed_elements = before - elements.len();
+
+    // Drop dangling references so the remaining elements stay consistent
+    for element in elements.iter_mut() {
+        if let Some(bound) = element.get_mut("boundElements").and_then(|b| b.as_array_mut()) {
+            bound.retain(|b| {
+                b.get("id")
+                    .and_then(|id| id.as_str())
+                    .is_none_or(|id| !removed_ids.contains(id))
+            });
+        }
+        for key in ["startBinding", "endBinding"] {
+            let dangling = element
+                .get(key)
+                .and_then(|b| b.get("elementId"))
+                .and_then(|id| id.as_str())
+                .is_some_and(|id| removed_ids.contains(id));
+            if dangling {
+                element[key] = Value::Null;
+            }
+        }
+        let orphaned = element
+            .get("frameId")
+            .and_then(|id| id.as_str())
+            .is_some_and(|id| removed_ids.contains(id));
+        if orphaned {
+            element["frameId"] = Value::Null;
+        }
+    }
+
+    let referenced: HashSet<String> = elements
+        .iter()
+        .filter_map(|e| e.get("fileId").and_then(|id| id.as_str()).map(|id| id.to_string()))
+        .collect();
+    let mut removed_files = 0;
+    if let Some(files) = scene.get_mut("files").and_then(|f| f.as_object_mut()) {
+        let before = files.len();
+        files.retain(|id, _| referenced.contains(id));
+        removed_files = before - files.len();
+    }
+
+    let pretty = content.trim_start().starts_with("{\n") || content.contains("\n  ");
+    let compacted = if pretty {
+        serde_json::to_string_pretty(&scene)
+    } else {
+        serde_json::to_string(&scene)
+    }
+    .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+
+    Ok(CompactResult {
+        bytes_before: content.len(),
+        bytes_after: compacted.len(),
+        content: compacted,
+        removed_elements,
+        removed_files,
+    })
+}
@@ -0,0 +1,87 @@
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Drawings stored gzip-compressed end in this instead of `.excalidraw`
+pub const COMPRESSED_SUFFIX: &str = ".excalidraw.gz";
+
+pub fn is_compressed(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(COMPRESSED_SUFFIX))
+}
+
+/// Plain and compressed drawings alike
+pub fn is_drawing(path: &Path) -> bool {
+    is_compressed(path) || path.extension().is_some_and(|e| e == "excalidraw")
+}
+
+/// Where the compressed copy of the plain drawing at `path` goes
+pub fn compressed_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".gz");
+    path.with_file_name(name)
+}
+
+/// `path` named as a plain or compressed drawing: its extension is replaced
+/// unless it already is `.excalidraw` or `.excalidraw.gz`
+pub fn with_drawing_extension(path: &Path, compressed: bool) -> PathBuf {
+    let plain = if is_compressed(path) {
+        path.with_extension("")
+    } else if path.extension().is_some_and(|e| e == "excalidraw") {
+        path.to_path_buf()
+    } else {
+        path.with_extension("excalidraw")
+    };
+    if compressed {
+        compressed_path(&plain)
+    } else {
+        plain
+    }
+}
+
+pub fn encode(content: &str) -> io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(content.as_bytes())?;
+    encoder.finish()
+}
+
+/// Ceiling on decompressed drawings when no size limit is passed, so a small
+/// gzip bomb can't fill memory
+pub const MAX_DECODED_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Decompresses a drawing, failing with `FileTooLarge` once it grows past `limit` bytes
+pub fn decode(bytes: &[u8], limit: u64) -> io::Result<String> {
+    let mut content = String::new();
+    GzDecoder::new(bytes).take(limit.saturating_add(1)).read_to_string(&mut content)?;
+    if content.len() as u64 > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::FileTooLarge,
+            format!("Drawing decompresses to more than {} MB", limit / (1024 * 1024)),
+        ));
+    }
+    Ok(content)
+}
+
+/// Reads a drawing, decompressing it when its name says it is compressed
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    read_limited(path, MAX_DECODED_BYTES)
+}
+
+/// `read_to_string`, refusing compressed drawings that decompress to more than `limit` bytes
+pub fn read_limited(path: &Path, limit: u64) -> io::Result<String> {
+    if is_compressed(path) {
+        decode(&fs::read(path)?, limit)
+    } else {
+        fs::read_to_string(path)
+    }
+}
+
+/// Writes a drawing, compressing it when its name says so
+pub fn write(path: &Path, content: &str) -> io::Result<()> {
+    if is_compressed(path) {
+        fs::write(path, encode(content)?)
+    } else {
+        fs::write(path, content)
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::branding::ExportBranding;
+use crate::error::AppError;
+use crate::paths;
+
+/// Per-directory configuration file at the root of a vault
+pub const CONFIG_FILE: &str = ".excaliapp.json";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// Batch export target, relative to the vault root
+    pub output_dir: Option<String>,
+    /// Used when no branding was set for the folder in the app
+    pub branding: Option<ExportBranding>,
+    /// Images written next to each drawing whenever it is saved
+    pub auto: Option<AutoExportConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoExportFormat {
+    Png,
+    Svg,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AutoExportConfig {
+    pub enabled: bool,
+    pub formats: Vec<AutoExportFormat>,
+    /// Folder beside each drawing the images go to, under the drawing's name
+    pub directory: String,
+    /// Pixels per scene unit of PNG exports
+    pub scale: f32,
+}
+
+impl Default for AutoExportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            formats: vec![AutoExportFormat::Png],
+            directory: "exports".to_string(),
+            scale: 2.0,
+        }
+    }
+}
+
+/// AI defaults for drawings in this vault; API keys never live here
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct AIProfile {
+    pub provider: Option<String>,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    pub max_tokens: Option<u32>,
+    pub temperature: Option<f32>,
+    pub system_prompt: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DirectoryConfig {
+    /// Drawing (relative to the vault root) used as the content of new files
+    pub default_template: Option<String>,
+    pub export: ExportConfig,
+    /// Gitignore-style patterns of entries to hide from the workspace
+    pub ignore_patterns: Vec<String>,
+    pub ai: Option<AIProfile>,
+    /// Store drawings gzip-compressed as `.excalidraw.gz`; plain drawings
+    /// are converted when next saved
+    pub compress_drawings: bool,
+}
+
+/// Parsed configs of the known vault roots, keyed by normalized root path
+pub type ConfigMap = HashMap<String, DirectoryConfig>;
+
+/// Reads `<root>/.excaliapp.json`; a missing file is `Ok(None)`
+pub fn load(root: &Path) -> Result<Option<DirectoryConfig>, AppError> {
+    let path = root.join(CONFIG_FILE);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(AppError::from(e).with_context(format!("Failed to read {}", CONFIG_FILE))),
+    };
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid {}: {}", CONFIG_FILE, e)))
+}
+
+pub fn is_config_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == CONFIG_FILE)
+}
+
+/// Config of the nearest vault root at or above `path`
+pub fn for_path<'a>(configs: &'a ConfigMap, path: &Path) -> Option<(&'a str, &'a DirectoryConfig)> {
+    let path = paths::normalize(path);
+    configs
+        .iter()
+        .filter(|(root, _)| Path::new(&path).starts_with(root.as_str()))
+        .max_by_key(|(root, _)| root.len())
+        .map(|(root, config)| (root.as_str(), config))
+}
+
+/// Joins a config-relative path to the root, refusing paths that leave it
+fn vault_path(root: &Path, relative: &str) -> Result<PathBuf, AppError> {
+    let relative = Path::new(relative);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(AppError::InvalidPath(format!(
+            "{} must stay inside the vault: {}",
+            CONFIG_FILE,
+            relative.display()
+        )));
+    }
+    Ok(root.join(relative))
+}
+
+impl DirectoryConfig {
+    pub fn template_path(&self, root: &Path) -> Option<Result<PathBuf, AppError>> {
+        let template = self.default_template.as_deref()?;
+        Some(vault_path(root, template))
+    }
+
+    pub fn export_dir(&self, root: &Path) -> Option<Result<PathBuf, AppError>> {
+        let output_dir = self.export.output_dir.as_deref()?;
+        Some(vault_path(root, output_dir))
+    }
+
+    /// Automatic exports of `drawing` when they are turned on
+    pub fn auto_export(&self, drawing: &Path) -> Option<(&AutoExportConfig, Result<PathBuf, AppError>)> {
+        let auto = self.export.auto.as_ref().filter(|auto| auto.enabled && !auto.formats.is_empty())?;
+        let parent = drawing.parent()?;
+        Some((auto, vault_path(parent, &auto.directory)))
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::limits::FileLimits;
+use crate::paths;
+
+/// Drawings kept in memory at most
+const MAX_ENTRIES: usize = 32;
+
+/// Total size of the kept drawings at most; bigger drawings are not kept
+const MAX_BYTES: usize = 64 * 1024 * 1024;
+
+struct Entry {
+    modified: SystemTime,
+    len: u64,
+    content: String,
+    /// The limits the content was checked against, if it was
+    checked: Option<FileLimits>,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct Entries {
+    files: HashMap<String, Entry>,
+    bytes: usize,
+    clock: u64,
+}
+
+impl Entries {
+    fn remove(&mut self, key: &str) {
+        if let Some(entry) = self.files.remove(key) {
+            self.bytes -= entry.content.len();
+        }
+    }
+}
+
+/// A drawing as `read_file` last returned it
+pub struct Cached {
+    pub content: String,
+    pub checked: Option<FileLimits>,
+}
+
+/// Validated content of the drawings read last, so switching back to one
+/// skips reading and validating it again. Entries only count while the
+/// file's modification time and size are unchanged; saves and watcher
+/// events drop them early.
+#[derive(Default)]
+pub struct ContentCache {
+    entries: Mutex<Entries>,
+}
+
+impl ContentCache {
+    pub fn get(&self, path: &Path, modified: SystemTime, len: u64) -> Option<Cached> {
+        let key = paths::normalize(path);
+        let mut entries = self.entries.lock().unwrap();
+        entries.clock += 1;
+        let clock = entries.clock;
+        match entries.files.get_mut(&key) {
+            Some(entry) if entry.modified == modified && entry.len == len => {
+                entry.last_used = clock;
+                Some(Cached {
+                    content: entry.content.clone(),
+                    checked: entry.checked.clone(),
+                })
+            }
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Keeps `content`, read from `path` while it had `modified` and `len`,
+    /// dropping the least recently used drawings over the limits
+    pub fn insert(&self, path: &Path, modified: SystemTime, len: u64, content: &str, checked: Option<FileLimits>) {
+        let key = paths::normalize(path);
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(&key);
+        if content.len() > MAX_BYTES / 4 {
+            return;
+        }
+
+        while entries.files.len() >= MAX_ENTRIES || entries.bytes + content.len() > MAX_BYTES {
+            let Some(oldest) = entries
+                .files
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+
+        entries.clock += 1;
+        entries.bytes += content.len();
+        let last_used = entries.clock;
+        entries.files.insert(
+            key,
+            Entry {
+                modified,
+                len,
+                content: content.to_string(),
+                checked,
+                last_used,
+            },
+        );
+    }
+
+    /// Drops a drawing, or every drawing below a folder
+    pub fn invalidate(&self, path: &Path) {
+        let key = paths::normalize(path);
+        let prefix = format!("{}/", key.trim_end_matches('/'));
+        let mut entries = self.entries.lock().unwrap();
+        let stale: Vec<String> = entries
+            .files
+            .keys()
+            .filter(|cached| **cached == key || cached.starts_with(&prefix))
+            .cloned()
+            .collect();
+        for cached in stale {
+            entries.remove(&cached);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex;
+use tauri::{
+    menu::{Menu, MenuBuilder, MenuItemBuilder, PredefinedMenuItem},
+    AppHandle, Emitter, Manager, Runtime,
+};
+
+use crate::menu_text::MenuText;
+
+/// Menu ids of context menu entries start with this, e.g. `context_rename`
+pub const ID_PREFIX: &str = "context_";
+
+/// Where in the window to open the menu, in logical pixels
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Position {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// The tree item a context menu was opened for
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextTarget {
+    pub path: String,
+    pub is_directory: bool,
+}
+
+/// Payload of the `file-context-action` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ContextAction {
+    pub action: String,
+    pub path: String,
+    pub is_directory: bool,
+}
+
+/// The item of the last context menu and the window it was shown in; menu
+/// events only carry the entry id
+#[derive(Default)]
+pub struct PendingContextMenu(Mutex<Option<(String, ContextTarget)>>);
+
+impl PendingContextMenu {
+    pub fn set(&self, label: &str, target: ContextTarget) {
+        *self.0.lock().unwrap() = Some((label.to_string(), target));
+    }
+
+    fn take(&self) -> Option<(String, ContextTarget)> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+#[cfg(target_os = "macos")]
+const REVEAL_LABEL: &str = "Reveal in Finder";
+#[cfg(target_os = "windows")]
+const REVEAL_LABEL: &str = "Show in Explorer";
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const REVEAL_LABEL: &str = "Show in File Manager";
+
+pub fn build<R: Runtime>(
+    app: &AppHandle<R>,
+    text: &MenuText,
+    is_directory: bool,
+    pinned: bool,
+    read_only: bool,
+    conflict_copy: bool,
+) -> Result<Menu<R>, Box<dyn std::error::Error>> {
+    let item = |action: &str, label: &str| {
+        MenuItemBuilder::with_id(format!("{}{}", ID_PREFIX, action), text.get(label)).build(app)
+    };
+
+    let mut menu = MenuBuilder::new(app);
+    menu = if is_directory {
+        menu.item(&item("new_subfolder", "New Subfolder")?)
+    } else {
+        menu.item(&item("open_in_new_window", "Open in New Window")?).item(&if pinned {
+            item("unpin", "Unpin")?
+        } else {
+            item("pin", "Pin to Top")?
+        })
+    };
+    menu = menu.item(&if read_only {
+        item("allow_editing", "Allow Editing")?
+    } else {
+        item("make_read_only", "Make Read-only")?
+    });
+    menu = menu
+        .item(&PredefinedMenuItem::separator(app)?)
+        .item(&item("rename", "Rename")?);
+    if !is_directory {
+        menu = menu
+            .item(&item("duplicate", "Duplicate")?)
+            .item(&item("copy_to", "Copy To...")?)
+            .item(&item("save_as_template", "Save as Template")?);
+        if conflict_copy {
+            menu = menu
+                .item(&item("merge_conflict_copy", "Merge into Original")?)
+                .item(&item("keep_conflict_copy", "Replace Original")?)
+                .item(&item("discard_conflict_copy", "Discard Conflict Copy")?);
+        }
+    }
+    menu = menu
+        .item(&item("delete", "Delete")?)
+        .item(&PredefinedMenuItem::separator(app)?)
+        .item(&item("reveal", REVEAL_LABEL)?)
+        .item(&item("export", if is_directory { "Export All as SVG..." } else { "Export as SVG..." })?);
+    if is_directory {
+        menu = menu
+            .item(&item("archive", "Archive as ZIP...")?)
+            .item(&item("import_archive", "Import Archive...")?)
+            .item(&item("import_canvas", "Import Canvas...")?);
+    } else {
+        menu = menu
+            .item(&item("export_pdf", "Export as PDF...")?)
+            .item(&item("export_pptx", "Export as PowerPoint...")?)
+            .item(&item("export_canvas", "Export as Canvas...")?);
+    }
+    menu = menu
+        .item(&item("copy_path", "Copy Path")?)
+        .item(&item("copy_relative_path", "Copy Relative Path")?);
+    if !is_directory {
+        menu = menu.item(&item("copy_mermaid", "Copy as Mermaid")?);
+    }
+
+    Ok(menu.build()?)
+}
+
+/// Runs the chosen entry: revealing and copying happen here, everything else
+/// goes to the window that opened the menu as `file-context-action`
+pub fn dispatch<R: Runtime>(app: &AppHandle<R>, action: &str) {
+    let Some(state) = app.try_state::<crate::AppState>() else {
+        return;
+    };
+    let Some((label, target)) = state.context_menu.take() else {
+        return;
+    };
+
+    match action {
+        "reveal" => {
+            if let Err(e) = crate::reveal::reveal(app, Path::new(&target.path)) {
+                tracing::warn!("Failed to reveal {}: {}", target.path, e);
+            }
+        }
+        "copy_path" | "copy_relative_path" => {
+            let relative = action == "copy_relative_path";
+            if let Err(e) = crate::copy_path(app, &label, &target.path, relative) {
+                tracing::warn!("{}", e);
+            }
+        }
+        _ => {
+            let _ = app.emit_to(
+                label.as_str(),
+                "file-context-action",
+                ContextAction {
+                    action: action.to_string(),
+                    path: target.path,
+                    is_directory: target.is_directory,
+                },
+            );
+        }
+    }
+}
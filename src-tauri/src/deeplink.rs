@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Url;
+
+use crate::error::{AppError, ResultExt};
+use crate::security;
+
+/// Scheme for links from other tools; `ownexcalidesk://` stays reserved for
+/// library imports handled by the editor
+pub const SCHEME: &str = "excaliapp";
+
+#[derive(Debug)]
+pub enum DeepLinkAction {
+    /// `excaliapp://open?path=...` with a drawing or a folder
+    Open { path: PathBuf },
+    /// `excaliapp://new?template=...&dir=...&name=...`, all optional
+    New {
+        template: Option<String>,
+        directory: Option<PathBuf>,
+        name: Option<String>,
+    },
+}
+
+fn query_param(url: &Url, key: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.into_owned())
+        .filter(|v| !v.trim().is_empty())
+}
+
+/// Canonical form of an existing local path that passes the traversal checks
+fn existing_path(value: &str) -> Result<PathBuf, AppError> {
+    let path = Path::new(value).canonicalize().context(format!("Cannot open {}", value))?;
+    security::validate_path(&path, None)
+}
+
+/// The action of an `excaliapp://` link, or `None` for other schemes
+pub fn parse(url: &Url) -> Result<Option<DeepLinkAction>, AppError> {
+    if url.scheme() != SCHEME {
+        return Ok(None);
+    }
+
+    match url.host_str() {
+        Some("open") => {
+            let value = query_param(url, "path")
+                .ok_or_else(|| AppError::InvalidInput("Missing path parameter".to_string()))?;
+            let path = existing_path(&value)?;
+            if !path.is_dir() {
+                security::validate_excalidraw_file(&path)?;
+            }
+            Ok(Some(DeepLinkAction::Open { path }))
+        }
+        Some("new") => {
+            let template = query_param(url, "template")
+                .map(|value| {
+                    let path = existing_path(&value)?;
+                    security::validate_excalidraw_file(&path)?;
+                    let content = fs::read_to_string(&path).context("Failed to read template")?;
+                    security::validate_excalidraw_content(&content)?;
+                    Ok::<_, AppError>(content)
+                })
+                .transpose()?;
+            let directory = query_param(url, "dir")
+                .map(|value| {
+                    let path = existing_path(&value)?;
+                    if !path.is_dir() {
+                        return Err(AppError::InvalidPath(format!("Not a directory: {}", value)));
+                    }
+                    Ok(path)
+                })
+                .transpose()?;
+            Ok(Some(DeepLinkAction::New {
+                template,
+                directory,
+                name: query_param(url, "name"),
+            }))
+        }
+        other => Err(AppError::InvalidInput(format!(
+            "Unknown link action: {}",
+            other.unwrap_or_default()
+        ))),
+    }
+}
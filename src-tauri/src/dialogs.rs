@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use tauri_plugin_dialog::FilePath;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+use crate::error::AppError;
+
+/// How long a command waits for a native dialog before giving up
+pub const DIALOG_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+pub enum DialogOutcome {
+    Picked(Option<FilePath>),
+    Cancelled,
+}
+
+/// Native dialogs currently awaited by a command. Both the dialog callback and
+/// `cancel_pending_dialog` can complete a pending dialog, whichever comes first.
+#[derive(Default)]
+pub struct PendingDialogs {
+    next_id: AtomicU64,
+    senders: Mutex<HashMap<u64, UnboundedSender<DialogOutcome>>>,
+}
+
+pub struct PendingDialog {
+    pub id: u64,
+    sender: UnboundedSender<DialogOutcome>,
+    receiver: UnboundedReceiver<DialogOutcome>,
+}
+
+impl PendingDialog {
+    /// Sender to move into the native dialog callback
+    pub fn sender(&self) -> UnboundedSender<DialogOutcome> {
+        self.sender.clone()
+    }
+}
+
+impl PendingDialogs {
+    pub fn open(&self) -> PendingDialog {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let (sender, receiver) = unbounded_channel();
+        self.senders.lock().unwrap().insert(id, sender.clone());
+        PendingDialog {
+            id,
+            sender,
+            receiver,
+        }
+    }
+
+    /// Cancels one pending dialog, or all of them when `id` is `None`;
+    /// returns how many waiting commands were released
+    pub fn cancel(&self, id: Option<u64>) -> usize {
+        let mut senders = self.senders.lock().unwrap();
+        let cancelled: Vec<UnboundedSender<DialogOutcome>> = match id {
+            Some(id) => senders.remove(&id).into_iter().collect(),
+            None => senders.drain().map(|(_, sender)| sender).collect(),
+        };
+        cancelled
+            .iter()
+            .filter(|sender| sender.send(DialogOutcome::Cancelled).is_ok())
+            .count()
+    }
+
+    /// Waits for the dialog to complete. A cancelled dialog resolves like a
+    /// dismissed one (`Ok(None)`); the native window itself may stay open
+    pub async fn wait(
+        &self,
+        mut dialog: PendingDialog,
+        timeout: Duration,
+    ) -> Result<Option<FilePath>, AppError> {
+        let result = tokio::time::timeout(timeout, dialog.receiver.recv()).await;
+        self.senders.lock().unwrap().remove(&dialog.id);
+
+        match result {
+            Ok(Some(DialogOutcome::Picked(path))) => Ok(path),
+            Ok(Some(DialogOutcome::Cancelled)) => {
+                tracing::debug!("Dialog {} was cancelled", dialog.id);
+                Ok(None)
+            }
+            Ok(None) => Err(AppError::Cancelled("Dialog closed without a result".to_string())),
+            Err(_) => Err(AppError::Cancelled(format!("Dialog timed out after {} seconds", timeout.as_secs()))),
+        }
+    }
+}
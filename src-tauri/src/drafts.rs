@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, ResultExt};
+use crate::export::{fnv1a, FNV_OFFSET};
+
+/// Directory (under the app data dir) holding unsaved editor content
+pub const DRAFT_DIR: &str = "drafts";
+
+/// Editor content of a file as of the last draft snapshot
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Draft {
+    pub file_path: String,
+    /// Milliseconds since the Unix epoch
+    pub saved_at: i64,
+    pub content: String,
+}
+
+/// A draft left behind by a session that ended without saving
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecoverableDraft {
+    pub file_path: String,
+    pub saved_at: i64,
+    pub size: usize,
+    /// False when the file was deleted or moved since
+    pub file_exists: bool,
+}
+
+/// One draft per file, named after a stable hash of its path
+fn draft_file(dir: &Path, file_path: &str) -> PathBuf {
+    dir.join(format!("{:016x}.json", fnv1a(file_path.as_bytes(), FNV_OFFSET)))
+}
+
+/// Replaces the draft of `file_path`. Written to a temporary file first so a
+/// crash mid-write keeps the previous draft intact.
+pub fn save(dir: &Path, file_path: &str, content: &str) -> Result<(), AppError> {
+    fs::create_dir_all(dir).context("Failed to create drafts directory")?;
+    let draft = Draft {
+        file_path: file_path.to_string(),
+        saved_at: crate::prompts::now_millis(),
+        content: content.to_string(),
+    };
+    let json = serde_json::to_string(&draft).map_err(|e| AppError::Internal(e.to_string()))?;
+
+    let target = draft_file(dir, file_path);
+    let temp = target.with_extension("json.tmp");
+    fs::write(&temp, json).context("Failed to write draft")?;
+    fs::rename(&temp, &target).context("Failed to write draft")
+}
+
+pub fn load(dir: &Path, file_path: &str) -> Result<Option<Draft>, AppError> {
+    let content = match fs::read_to_string(draft_file(dir, file_path)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(AppError::from(e).with_context("Failed to read draft")),
+    };
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid draft: {}", e)))
+}
+
+/// Drops the draft of `file_path`; a missing draft is not an error
+pub fn clear(dir: &Path, file_path: &str) -> Result<(), AppError> {
+    match fs::remove_file(draft_file(dir, file_path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(AppError::from(e).with_context("Failed to remove draft")),
+        _ => Ok(()),
+    }
+}
+
+/// Drafts whose content differs from the file on disk, newest first.
+/// `current` reads a file's saved content (`None` when it is gone); drafts
+/// matching it are leftovers of a completed save and get removed.
+pub fn list_recoverable(dir: &Path, current: impl Fn(&str) -> Option<String>) -> Vec<RecoverableDraft> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut drafts: Vec<RecoverableDraft> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| {
+            let draft = fs::read_to_string(&path)
+                .ok()
+                .and_then(|content| serde_json::from_str::<Draft>(&content).ok());
+            let Some(draft) = draft else {
+                tracing::warn!("Skipping unreadable draft {}", path.display());
+                return None;
+            };
+
+            let saved = current(&draft.file_path);
+            if saved.as_deref() == Some(draft.content.as_str()) {
+                if let Err(e) = fs::remove_file(&path) {
+                    tracing::warn!("Failed to remove stale draft {}: {}", path.display(), e);
+                }
+                return None;
+            }
+            Some(RecoverableDraft {
+                size: draft.content.len(),
+                file_exists: saved.is_some(),
+                file_path: draft.file_path,
+                saved_at: draft.saved_at,
+            })
+        })
+        .collect();
+    drafts.sort_by_key(|draft| std::cmp::Reverse(draft.saved_at));
+    drafts
+}
@@ -0,0 +1,254 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+use crate::error::AppError;
+use crate::render;
+use crate::storage::EntryMetadata;
+
+/// Share of matching elements from which two drawings count as near-identical
+pub const SIMILARITY_THRESHOLD: f64 = 0.9;
+
+/// Element properties that change on every edit without changing the drawing
+const VOLATILE_PROPERTIES: &[&str] = &["version", "versionNonce", "updated", "seed"];
+
+/// What drawings are compared by
+#[derive(Debug, Clone)]
+pub struct SceneFingerprint {
+    /// Hash of the visible elements and the images they show, volatile
+    /// properties left out
+    pub hash: String,
+    /// One entry per visible element, ids and volatile properties left out,
+    /// sorted
+    pub elements: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DuplicateFile {
+    pub path: String,
+    pub size: u64,
+    pub modified_at: Option<i64>,
+    pub elements: usize,
+    /// Share of elements matching the group's first file; 1 for copies
+    pub similarity: f64,
+}
+
+/// Answer of `find_duplicate_scenes`: drawings that are copies of each
+/// other, newest first
+#[derive(Debug, Serialize, Clone)]
+pub struct DuplicateGroup {
+    /// "identical" when every file draws the same, else "similar"
+    pub kind: String,
+    pub files: Vec<DuplicateFile>,
+}
+
+fn str_field<'a>(element: &'a Value, key: &str) -> &'a str {
+    element.get(key).and_then(|v| v.as_str()).unwrap_or_default()
+}
+
+fn number(element: &Value, key: &str) -> i64 {
+    element.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0).round() as i64
+}
+
+/// An element as drawn, whatever its id
+fn element_key(element: &Value) -> String {
+    format!(
+        "{}|{}|{}|{}|{}|{}|{}|{}|{}",
+        str_field(element, "type"),
+        number(element, "x"),
+        number(element, "y"),
+        number(element, "width"),
+        number(element, "height"),
+        str_field(element, "strokeColor"),
+        str_field(element, "backgroundColor"),
+        str_field(element, "fileId"),
+        element
+            .get("originalText")
+            .or_else(|| element.get("text"))
+            .and_then(|t| t.as_str())
+            .unwrap_or_default(),
+    )
+}
+
+/// Fingerprint of a scene; `None` for content that isn't a scene and for
+/// empty drawings, which would all match each other
+pub fn fingerprint(content: &str) -> Option<SceneFingerprint> {
+    let scene: Value = serde_json::from_str(content).ok()?;
+    let elements = render::visible_elements(&scene);
+    if elements.is_empty() {
+        return None;
+    }
+
+    let mut hasher = Sha256::new();
+    let mut file_ids = Vec::new();
+    for element in &elements {
+        let mut element = (*element).clone();
+        if let Some(element) = element.as_object_mut() {
+            for key in VOLATILE_PROPERTIES {
+                element.remove(*key);
+            }
+        }
+        // Object keys serialize sorted, so equal elements hash equally
+        hasher.update(element.to_string());
+        if let Some(file_id) = element.get("fileId").and_then(|f| f.as_str()) {
+            file_ids.push(file_id.to_string());
+        }
+    }
+    for file_id in file_ids {
+        if let Some(data) = scene.pointer(&format!("/files/{}/dataURL", file_id)) {
+            hasher.update(data.to_string());
+        }
+    }
+
+    let mut keys: Vec<String> = elements.iter().map(|e| element_key(e)).collect();
+    keys.sort();
+    Some(SceneFingerprint {
+        hash: hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect(),
+        elements: keys,
+    })
+}
+
+/// Matching elements over all elements of either drawing
+pub fn similarity(a: &SceneFingerprint, b: &SceneFingerprint) -> f64 {
+    if a.hash == b.hash {
+        return 1.0;
+    }
+    // Both lists are sorted, so count the common part like a merge
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.elements.len() && j < b.elements.len() {
+        match a.elements[i].cmp(&b.elements[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    let total = a.elements.len() + b.elements.len() - common;
+    common as f64 / total as f64
+}
+
+fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+fn join(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    parents[a] = b;
+}
+
+/// Groups drawings that are copies or near copies of each other
+pub fn group(files: Vec<(String, EntryMetadata, SceneFingerprint)>) -> Vec<DuplicateGroup> {
+    let mut parents: Vec<usize> = (0..files.len()).collect();
+    let mut by_hash: HashMap<&str, usize> = HashMap::new();
+    for (i, (_, _, fingerprint)) in files.iter().enumerate() {
+        if let Some(&first) = by_hash.get(fingerprint.hash.as_str()) {
+            join(&mut parents, first, i);
+        } else {
+            by_hash.insert(&fingerprint.hash, i);
+        }
+    }
+
+    // Similarity can't reach the threshold unless the element counts are
+    // that close, so only compare neighbours in size
+    let mut order: Vec<usize> = by_hash.values().copied().collect();
+    order.sort_by_key(|&i| files[i].2.elements.len());
+    for (n, &i) in order.iter().enumerate() {
+        let count = files[i].2.elements.len() as f64;
+        for &j in &order[n + 1..] {
+            if count / (files[j].2.elements.len() as f64) < SIMILARITY_THRESHOLD {
+                break;
+            }
+            if similarity(&files[i].2, &files[j].2) >= SIMILARITY_THRESHOLD {
+                join(&mut parents, i, j);
+            }
+        }
+    }
+
+    let mut members: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..files.len() {
+        members.entry(find(&mut parents, i)).or_default().push(i);
+    }
+    let mut groups: Vec<DuplicateGroup> = members
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|mut members| {
+            members.sort_by(|&a, &b| files[b].1.modified_at.cmp(&files[a].1.modified_at).then(files[a].0.cmp(&files[b].0)));
+            let first = &files[members[0]].2;
+            let hashes: HashSet<&str> = members.iter().map(|&i| files[i].2.hash.as_str()).collect();
+            DuplicateGroup {
+                kind: if hashes.len() == 1 { "identical" } else { "similar" }.to_string(),
+                files: members
+                    .iter()
+                    .map(|&i| DuplicateFile {
+                        path: files[i].0.clone(),
+                        size: files[i].1.size,
+                        modified_at: files[i].1.modified_at,
+                        elements: files[i].2.elements.len(),
+                        similarity: similarity(first, &files[i].2),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| a.kind.cmp(&b.kind).then(a.files[0].path.cmp(&b.files[0].path)));
+    groups
+}
+
+/// Adds the elements of `other` that `keep` lacks, matched by id, and the
+/// images they show; elements both have keep `keep`'s version. Returns the
+/// merged scene and the number of elements added.
+pub fn merge(keep: &str, other: &str) -> Result<(String, usize), AppError> {
+    let mut scene: Value =
+        serde_json::from_str(keep).map_err(|e| AppError::InvalidInput(format!("Invalid drawing: {}", e)))?;
+    let other: Value =
+        serde_json::from_str(other).map_err(|e| AppError::InvalidInput(format!("Invalid duplicate: {}", e)))?;
+
+    let known: HashSet<String> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .into_iter()
+        .flatten()
+        .map(|e| str_field(e, "id").to_string())
+        .collect();
+    let added: Vec<Value> = render::visible_elements(&other)
+        .into_iter()
+        .filter(|e| !known.contains(str_field(e, "id")))
+        .cloned()
+        .collect();
+    if added.is_empty() {
+        return Ok((keep.to_string(), 0));
+    }
+
+    let scene_files = scene
+        .as_object_mut()
+        .ok_or_else(|| AppError::InvalidInput("Drawing is not a scene".to_string()))?
+        .entry("files")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if let (Some(scene_files), Some(other_files)) = (scene_files.as_object_mut(), other.get("files").and_then(|f| f.as_object())) {
+        for element in &added {
+            let file_id = str_field(element, "fileId");
+            if let Some(file) = other_files.get(file_id).filter(|_| !scene_files.contains_key(file_id)) {
+                scene_files.insert(file_id.to_string(), file.clone());
+            }
+        }
+    }
+
+    let count = added.len();
+    scene
+        .get_mut("elements")
+        .and_then(|e| e.as_array_mut())
+        .ok_or_else(|| AppError::InvalidInput("Drawing has no elements".to_string()))?
+        .extend(added);
+    let content = serde_json::to_string_pretty(&scene)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+    Ok((content, count))
+}
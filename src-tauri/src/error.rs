@@ -0,0 +1,158 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::io;
+
+/// Error returned by every command. The frontend receives
+/// `{ code, message, context }`, so it can tell a missing file from a
+/// permission problem or invalid content without parsing the message.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    PermissionDenied(String),
+    #[error("{0}")]
+    AlreadyExists(String),
+    /// Rejected by path validation: traversal, wrong extension, unknown mount
+    #[error("{0}")]
+    InvalidPath(String),
+    /// Unparseable or invalid drawing, config or argument
+    #[error("{0}")]
+    InvalidInput(String),
+    /// Over a configured file size, element count or image size limit
+    #[error("{0}")]
+    TooLarge(String),
+    /// Locked against changes with `set_read_only`
+    #[error("{0}")]
+    ReadOnly(String),
+    #[error("{0}")]
+    Io(String),
+    /// A request to an AI provider or other remote service failed
+    #[error("{0}")]
+    Network(String),
+    /// Stopped by `cancel_operation`
+    #[error("{0}")]
+    Cancelled(String),
+    #[error("{0}")]
+    Internal(String),
+    /// Any of the above, annotated with the file or step it concerns
+    #[error("{context}: {error}")]
+    WithContext { context: String, error: Box<AppError> },
+}
+
+impl AppError {
+    /// Stable identifier the frontend can match on
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::PermissionDenied(_) => "permission_denied",
+            AppError::AlreadyExists(_) => "already_exists",
+            AppError::InvalidPath(_) => "invalid_path",
+            AppError::InvalidInput(_) => "invalid_input",
+            AppError::TooLarge(_) => "too_large",
+            AppError::ReadOnly(_) => "read_only",
+            AppError::Io(_) => "io",
+            AppError::Network(_) => "network",
+            AppError::Cancelled(_) => "cancelled",
+            AppError::Internal(_) => "internal",
+            AppError::WithContext { error, .. } => error.code(),
+        }
+    }
+
+    /// The message without any context
+    pub fn message(&self) -> String {
+        match self {
+            AppError::WithContext { error, .. } => error.message(),
+            other => other.to_string(),
+        }
+    }
+
+    /// Context entries from outermost to innermost, joined by ": "
+    pub fn context(&self) -> Option<String> {
+        let AppError::WithContext { context, error } = self else {
+            return None;
+        };
+        Some(match error.context() {
+            Some(inner) => format!("{}: {}", context, inner),
+            None => context.clone(),
+        })
+    }
+
+    pub fn with_context(self, context: impl Into<String>) -> Self {
+        AppError::WithContext {
+            context: context.into(),
+            error: Box::new(self),
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.message())?;
+        state.serialize_field("context", &self.context())?;
+        state.end()
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(e: io::Error) -> Self {
+        let message = e.to_string();
+        match e.kind() {
+            io::ErrorKind::NotFound => AppError::NotFound(message),
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => AppError::PermissionDenied(message),
+            io::ErrorKind::AlreadyExists => AppError::AlreadyExists(message),
+            io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData => AppError::InvalidInput(message),
+            io::ErrorKind::FileTooLarge => AppError::TooLarge(message),
+            _ => AppError::Io(message),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::InvalidInput(format!("Invalid JSON: {}", e))
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(e: tauri::Error) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+impl From<tauri_plugin_store::Error> for AppError {
+    fn from(e: tauri_plugin_store::Error) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+/// Menu building reports boxed errors
+impl From<Box<dyn std::error::Error>> for AppError {
+    fn from(e: Box<dyn std::error::Error>) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+impl From<notify::Error> for AppError {
+    fn from(e: notify::Error) -> Self {
+        AppError::Internal(format!("File watcher error: {}", e))
+    }
+}
+
+impl From<reqwest::Error> for AppError {
+    fn from(e: reqwest::Error) -> Self {
+        AppError::Network(e.to_string())
+    }
+}
+
+pub trait ResultExt<T> {
+    /// Annotates the error with the file or step it concerns
+    fn context(self, context: impl Into<String>) -> Result<T, AppError>;
+}
+
+impl<T, E: Into<AppError>> ResultExt<T> for Result<T, E> {
+    fn context(self, context: impl Into<String>) -> Result<T, AppError> {
+        self.map_err(|e| e.into().with_context(context))
+    }
+}
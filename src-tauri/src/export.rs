@@ -0,0 +1,488 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::background::TaskManager;
+use crate::branding::{self, ExportBranding};
+use crate::compression;
+use crate::error::{AppError, ResultExt};
+use crate::ignores::IgnoreRules;
+use crate::paths;
+use crate::render;
+
+/// Manifest written into the output directory to remember the last export run
+const MANIFEST_FILE: &str = ".excaliapp-export.json";
+
+/// Padding around exported drawings in scene units
+const EXPORT_PADDING: f64 = 20.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct ExportManifest {
+    /// Content hash per source path relative to the exported directory
+    hashes: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExportFailure {
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchExportReport {
+    pub output_dir: String,
+    pub exported: Vec<String>,
+    /// Files whose content and export settings are unchanged since the last run
+    pub skipped: Vec<String>,
+    pub failed: Vec<ExportFailure>,
+}
+
+/// Initial state for `fnv1a`
+pub const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+
+/// 64-bit FNV-1a; unlike `DefaultHasher` it is stable across Rust releases,
+/// which matters because hashes are persisted between runs
+pub fn fnv1a(bytes: &[u8], mut hash: u64) -> u64 {
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+fn content_hash(content: &str, settings: &str) -> String {
+    let hash = fnv1a(content.as_bytes(), FNV_OFFSET);
+    format!("{:016x}", fnv1a(settings.as_bytes(), hash))
+}
+
+fn load_manifest(output_dir: &Path) -> ExportManifest {
+    fs::read_to_string(output_dir.join(MANIFEST_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save_manifest(output_dir: &Path, manifest: &ExportManifest) -> Result<(), AppError> {
+    let content = serde_json::to_string_pretty(manifest)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize export manifest: {}", e)))?;
+    fs::write(output_dir.join(MANIFEST_FILE), content)
+        .context("Failed to write export manifest")
+}
+
+/// Renders one drawing to a standalone SVG document, optionally stamped
+/// with a share watermark
+pub fn render_file_svg(
+    content: &str,
+    branding: Option<&ExportBranding>,
+    watermark: Option<&str>,
+) -> Result<String, AppError> {
+    let scene: serde_json::Value =
+        serde_json::from_str(content)?;
+    let elements = render::visible_elements(&scene);
+    let bounds = render::scene_bounds(&elements)
+        .ok_or_else(|| AppError::InvalidInput("Nothing to export: no visible elements found".to_string()))?
+        .expand(EXPORT_PADDING);
+    let background = scene
+        .get("appState")
+        .and_then(|a| a.get("viewBackgroundColor"))
+        .and_then(|c| c.as_str());
+    let files = scene.get("files").and_then(|f| f.as_object());
+
+    let mut svg = render::render_svg(&elements, files, &bounds, background);
+    if let (Some(branding), Some(end)) = (branding, svg.rfind("</svg>")) {
+        svg.insert_str(end, &branding::render_overlay(&bounds, branding));
+    }
+    if let (Some(text), Some(end)) = (watermark, svg.rfind("</svg>")) {
+        svg.insert_str(end, &render::render_watermark(&bounds, text));
+    }
+    Ok(svg)
+}
+
+/// Outcome of exporting one source file
+enum ExportOutcome {
+    Exported(String, String),
+    Skipped(String, String),
+    Failed(ExportFailure),
+}
+
+/// Exports every drawing below `directory` as SVG into `output_dir`, mirroring
+/// the folder structure. Files whose content hash (combined with the export
+/// settings) matches the previous run are skipped unless `force` is set.
+/// Files are rendered in parallel within the background-work limits.
+pub fn batch_export(
+    directory: &Path,
+    output_dir: &Path,
+    branding: Option<&ExportBranding>,
+    force: bool,
+    ignore: &IgnoreRules,
+    tasks: &TaskManager,
+) -> Result<BatchExportReport, AppError> {
+    let mut files = Vec::new();
+    crate::collect_excalidraw_files_recursive(directory, ignore, &mut files)?;
+    let mut sources: Vec<PathBuf> = files
+        .into_iter()
+        .map(|f| PathBuf::from(f.path))
+        // Never export the output directory into itself
+        .filter(|source| !source.starts_with(output_dir))
+        .collect();
+    sources.sort();
+
+    fs::create_dir_all(output_dir).context("Failed to create output directory")?;
+
+    // Settings changes (e.g. new branding) must invalidate every cached export
+    let settings = serde_json::to_string(&branding).unwrap_or_default();
+    let previous = if force {
+        ExportManifest::default()
+    } else {
+        load_manifest(output_dir)
+    };
+
+    let outcomes = tasks.run(sources, |source| {
+        let relative = source.strip_prefix(directory).unwrap_or(&source);
+        let key = relative.to_string_lossy().replace('\\', "/");
+        let target = output_dir
+            .join(compression::with_drawing_extension(relative, false))
+            .with_extension("svg");
+
+        let content = match compression::read_to_string(&source) {
+            Ok(content) => content,
+            Err(e) => {
+                return ExportOutcome::Failed(ExportFailure {
+                    path: key,
+                    error: format!("Failed to read file: {}", e),
+                });
+            }
+        };
+        tasks.throttle_io(content.len());
+
+        let hash = content_hash(&content, &settings);
+        if previous.hashes.get(&key) == Some(&hash) && target.exists() {
+            return ExportOutcome::Skipped(key, hash);
+        }
+
+        let result = render_file_svg(&content, branding, None).and_then(|svg| {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .context("Failed to create directory")?;
+            }
+            tasks.throttle_io(svg.len());
+            fs::write(&target, svg).context("Failed to write export")
+        });
+
+        match result {
+            Ok(()) => ExportOutcome::Exported(key, hash),
+            Err(e) => ExportOutcome::Failed(ExportFailure { path: key, error: e.to_string() }),
+        }
+    });
+
+    let mut manifest = ExportManifest::default();
+    let mut report = BatchExportReport {
+        output_dir: paths::normalize(output_dir),
+        exported: Vec::new(),
+        skipped: Vec::new(),
+        failed: Vec::new(),
+    };
+    for outcome in outcomes {
+        match outcome {
+            ExportOutcome::Exported(key, hash) => {
+                manifest.hashes.insert(key.clone(), hash);
+                report.exported.push(key);
+            }
+            ExportOutcome::Skipped(key, hash) => {
+                manifest.hashes.insert(key.clone(), hash);
+                report.skipped.push(key);
+            }
+            ExportOutcome::Failed(failure) => report.failed.push(failure),
+        }
+    }
+
+    save_manifest(output_dir, &manifest)?;
+    Ok(report)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PaperSize {
+    #[default]
+    A4,
+    A3,
+    A5,
+    Letter,
+    Legal,
+}
+
+impl PaperSize {
+    /// Portrait width and height in PDF points
+    fn points(self) -> (f32, f32) {
+        match self {
+            PaperSize::A4 => (595.28, 841.89),
+            PaperSize::A3 => (841.89, 1190.55),
+            PaperSize::A5 => (419.53, 595.28),
+            PaperSize::Letter => (612.0, 792.0),
+            PaperSize::Legal => (612.0, 1008.0),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    /// Per page, whichever suits its content
+    #[default]
+    Auto,
+    Portrait,
+    Landscape,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PdfLayout {
+    /// One page per frame, or the whole canvas when there are none
+    #[default]
+    Frames,
+    /// The whole canvas scaled onto one page
+    Fit,
+    /// The whole canvas at `tile_scale`, split across as many pages as needed
+    Tile,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PdfOptions {
+    pub paper: PaperSize,
+    pub orientation: Orientation,
+    pub margin_mm: f32,
+    pub layout: PdfLayout,
+    /// PDF points per scene pixel when tiling; 0.75 prints at 96 pixels per inch
+    pub tile_scale: f32,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            paper: PaperSize::default(),
+            orientation: Orientation::default(),
+            margin_mm: 10.0,
+            layout: PdfLayout::default(),
+            tile_scale: 0.75,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PdfReport {
+    pub output_path: String,
+    pub pages: usize,
+}
+
+/// System fonts for the text in drawings, loaded on the first PDF or PPTX export
+pub(crate) static FONTS: std::sync::LazyLock<svg2pdf::usvg::fontdb::Database> = std::sync::LazyLock::new(|| {
+    use svg2pdf::usvg::fontdb::{Database, Family, Query};
+
+    let mut fonts = Database::new();
+    fonts.load_system_fonts();
+    // Text in fonts that aren't installed, like Virgil, falls back to the
+    // serif family; it defaults to Times New Roman, which Linux often lacks
+    let serif = Query {
+        families: &[Family::Serif],
+        ..Query::default()
+    };
+    let installed = fonts
+        .faces()
+        .find_map(|face| face.families.first().map(|(name, _)| name.clone()));
+    if fonts.query(&serif).is_none()
+        && let Some(family) = installed
+    {
+        fonts.set_serif_family(family);
+    }
+    fonts
+});
+
+/// Rasterizes an exported SVG at `scale` pixels per unit, less when the
+/// longest side would exceed `max_side`; returns the PNG and the SVG's size
+pub fn svg_to_png(svg: &str, scale: f32, max_side: f32) -> Result<(Vec<u8>, f32, f32), AppError> {
+    use resvg::usvg::{self, PostProcessingSteps, TreeParsing, TreePostProc};
+
+    let mut tree = usvg::Tree::from_str(svg, &usvg::Options::default())
+        .map_err(|e| AppError::InvalidInput(format!("Failed to prepare image: {}", e)))?;
+    tree.postprocess(PostProcessingSteps::default(), &FONTS);
+    let (width, height) = (tree.size.width(), tree.size.height());
+    let scale = scale.min(max_side / width.max(height));
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(
+        (width * scale).ceil().max(1.0) as u32,
+        (height * scale).ceil().max(1.0) as u32,
+    )
+    .ok_or_else(|| AppError::TooLarge("Image is too large to render".to_string()))?;
+    resvg::render(&tree, resvg::tiny_skia::Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+    let png = pixmap
+        .encode_png()
+        .map_err(|e| AppError::Internal(format!("Failed to encode PNG: {}", e)))?;
+    Ok((png, width, height))
+}
+
+/// The scene split into page-sized pieces of `area` points at `scale`;
+/// pieces without content are left out
+fn tiles(scene: &serde_json::Value, area: (f32, f32), scale: f32) -> Vec<crate::slides::Slide> {
+    let elements = render::visible_elements(scene);
+    let Some(bounds) = render::scene_bounds(&elements).map(|b| b.expand(EXPORT_PADDING)) else {
+        return Vec::new();
+    };
+    let files = scene.get("files").and_then(|f| f.as_object());
+    let background = scene
+        .get("appState")
+        .and_then(|a| a.get("viewBackgroundColor"))
+        .and_then(|c| c.as_str());
+    let (tile_width, tile_height) = (f64::from(area.0 / scale), f64::from(area.1 / scale));
+
+    let mut pieces = Vec::new();
+    let mut y = bounds.min_y;
+    while y < bounds.max_y {
+        let mut x = bounds.min_x;
+        while x < bounds.max_x {
+            let tile = render::Bounds {
+                min_x: x,
+                min_y: y,
+                max_x: x + tile_width,
+                max_y: y + tile_height,
+            };
+            if elements.iter().any(|e| render::element_bounds(e).intersects(&tile)) {
+                pieces.push(crate::slides::Slide {
+                    title: String::new(),
+                    svg: render::render_svg(&elements, files, &tile, background),
+                    bounds: tile,
+                });
+            }
+            x += tile_width;
+        }
+        y += tile_height;
+    }
+    pieces
+}
+
+/// Renders a drawing into a PDF at `output`: a page per frame, the whole
+/// canvas on one page, or the canvas tiled at a fixed scale
+pub fn export_pdf(
+    content: &str,
+    title: &str,
+    options: &PdfOptions,
+    branding: Option<&ExportBranding>,
+    watermark: Option<&str>,
+    output: &Path,
+) -> Result<PdfReport, AppError> {
+    use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, TextStr};
+    use svg2pdf::usvg::{PostProcessingSteps, TreeParsing, TreePostProc};
+
+    let scene: serde_json::Value =
+        serde_json::from_str(content)?;
+    let (paper_width, paper_height) = options.paper.points();
+    let margin = options.margin_mm.max(0.0) * 72.0 / 25.4;
+    if margin * 2.0 >= paper_width.min(paper_height) {
+        return Err(AppError::InvalidInput("Margins leave no room on the page".to_string()));
+    }
+    let page_size = |landscape: bool| {
+        if landscape {
+            (paper_height, paper_width)
+        } else {
+            (paper_width, paper_height)
+        }
+    };
+
+    let mut pages = match options.layout {
+        PdfLayout::Frames => crate::slides::slides_from_scene(&scene, title),
+        PdfLayout::Fit => {
+            let mut scene = scene.clone();
+            // Without frames, `slides_from_scene` renders the whole canvas
+            if let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut()) {
+                elements.retain(|e| e.get("type").and_then(|t| t.as_str()) != Some("frame"));
+            }
+            crate::slides::slides_from_scene(&scene, title)
+        }
+        PdfLayout::Tile => {
+            if options.tile_scale <= 0.0 {
+                return Err(AppError::InvalidInput("Tile scale must be positive".to_string()));
+            }
+            let (width, height) = page_size(options.orientation == Orientation::Landscape);
+            tiles(&scene, (width - margin * 2.0, height - margin * 2.0), options.tile_scale)
+        }
+    };
+    if pages.is_empty() {
+        return Err(AppError::InvalidInput("Nothing to export: no visible elements found".to_string()));
+    }
+    for page in &mut pages {
+        if let Some(branding) = branding {
+            page.apply_branding(branding);
+        }
+        if let Some(text) = watermark {
+            page.stamp_watermark(text);
+        }
+    }
+
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let info_id = Ref::new(3);
+    let mut next = 4;
+    let mut pdf = Pdf::new();
+    let mut page_ids = Vec::new();
+    let svg_name = Name(b"S1");
+
+    for page in &pages {
+        let mut tree = svg2pdf::usvg::Tree::from_str(&page.svg, &svg2pdf::usvg::Options::default())
+            .map_err(|e| AppError::InvalidInput(format!("Failed to prepare page: {}", e)))?;
+        tree.postprocess(PostProcessingSteps::default(), &FONTS);
+
+        let (content_width, content_height) = (tree.size.width(), tree.size.height());
+        let landscape = match options.orientation {
+            Orientation::Auto => content_width > content_height,
+            orientation => orientation == Orientation::Landscape,
+        };
+        let (width, height) = page_size(landscape);
+        let (area_width, area_height) = (width - margin * 2.0, height - margin * 2.0);
+        // Tiles keep their scale and start at the top left; everything else
+        // is fitted into the printable area and centered
+        let (scale, x, y) = if options.layout == PdfLayout::Tile {
+            let scale = options.tile_scale;
+            (scale, margin, height - margin - content_height * scale)
+        } else {
+            let scale = (area_width / content_width).min(area_height / content_height);
+            (
+                scale,
+                margin + (area_width - content_width * scale) / 2.0,
+                margin + (area_height - content_height * scale) / 2.0,
+            )
+        };
+
+        let (page_id, content_id, svg_id) = (Ref::new(next), Ref::new(next + 1), Ref::new(next + 2));
+        // The SVG takes as many ids as it needs from `svg_id` on
+        next = svg2pdf::convert_tree_into(&tree, svg2pdf::Options::default(), &mut pdf, svg_id).get();
+
+        let mut content = Content::new();
+        content
+            .transform([content_width * scale, 0.0, 0.0, content_height * scale, x, y])
+            .x_object(svg_name);
+        pdf.stream(content_id, &content.finish());
+
+        let mut pdf_page = pdf.page(page_id);
+        pdf_page.media_box(Rect::new(0.0, 0.0, width, height));
+        pdf_page.parent(page_tree_id);
+        pdf_page.contents(content_id);
+        pdf_page.resources().x_objects().pair(svg_name, svg_id);
+        pdf_page.finish();
+        page_ids.push(page_id);
+    }
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).count(page_ids.len() as i32).kids(page_ids);
+    pdf.document_info(info_id).title(TextStr(title));
+
+    let temp = output.with_extension("pdf.part");
+    let result = fs::write(&temp, pdf.finish()).and_then(|()| fs::rename(&temp, output));
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp);
+        return Err(AppError::from(e).with_context("Failed to write PDF"));
+    }
+    Ok(PdfReport {
+        output_path: paths::normalize(output),
+        pages: pages.len(),
+    })
+}
@@ -0,0 +1,310 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::backups;
+use crate::error::{AppError, ResultExt};
+use crate::ignores::IgnoreRules;
+
+/// Lock files sit next to the drawing as `.~lock.<file name>#`, like the
+/// ones office suites leave, so synced folders carry them along
+const LOCK_PREFIX: &str = ".~lock.";
+const LOCK_SUFFIX: &str = "#";
+
+/// Locks of other machines not refreshed for this long are left over from
+/// a crash or a machine that went away
+const STALE_AFTER_MILLIS: i64 = 12 * 60 * 60 * 1000;
+
+/// Lock files that can't be parsed this long after they were written are
+/// not half-written but broken
+const UNREADABLE_STALE_AFTER: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Who holds a lock, as written to the lock file
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct LockOwner {
+    pub host: String,
+    #[serde(default)]
+    pub user: String,
+    pub pid: u32,
+    /// Milliseconds since the Unix epoch
+    pub acquired_at: i64,
+    /// Updated on every save while the drawing stays open
+    pub refreshed_at: i64,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LockState {
+    Unlocked,
+    /// Held by this app
+    Ours,
+    /// Held by another running instance, here or on another machine
+    Other,
+    /// Left over by an instance that is gone; taken over on open
+    Stale,
+}
+
+/// Answer of `get_file_lock_status`
+#[derive(Debug, Serialize, Clone)]
+pub struct FileLockStatus {
+    pub path: String,
+    pub state: LockState,
+    pub owner: Option<LockOwner>,
+}
+
+fn host_name() -> String {
+    ["COMPUTERNAME", "HOSTNAME"]
+        .iter()
+        .find_map(|key| std::env::var(key).ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
+
+fn user_name() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+impl LockOwner {
+    fn current(now: i64) -> Self {
+        Self {
+            host: host_name(),
+            user: user_name(),
+            pid: std::process::id(),
+            acquired_at: now,
+            refreshed_at: now,
+        }
+    }
+
+    fn is_current_process(&self) -> bool {
+        self.pid == std::process::id() && self.host == host_name()
+    }
+
+    fn is_stale(&self, now: i64) -> bool {
+        // Only one instance runs per user session, so a lock of the same user
+        // on this host with another process id outlived the app that took it.
+        // Other users on a shared machine run instances of their own.
+        let host = host_name();
+        (!host.is_empty() && self.host == host && self.user == user_name() && self.pid != std::process::id())
+            || now - self.refreshed_at > STALE_AFTER_MILLIS
+    }
+}
+
+/// The lock file of the drawing at `path`
+pub fn lock_path(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+    Some(path.with_file_name(format!("{}{}{}", LOCK_PREFIX, name, LOCK_SUFFIX)))
+}
+
+pub fn is_lock_file(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        .is_some_and(|name| name.starts_with(LOCK_PREFIX) && name.ends_with(LOCK_SUFFIX))
+}
+
+/// Owner recorded in a lock file; `None` when there is none or it can't be
+/// read, which counts as unlocked
+fn read_owner(lock: &Path) -> Option<LockOwner> {
+    serde_json::from_str(&fs::read_to_string(lock).ok()?).ok()
+}
+
+fn owner_json(owner: &LockOwner) -> Result<String, AppError> {
+    serde_json::to_string_pretty(owner).map_err(|e| AppError::Internal(e.to_string()))
+}
+
+fn write_owner(lock: &Path, owner: &LockOwner) -> Result<(), AppError> {
+    fs::write(lock, owner_json(owner)?).context(format!("Failed to write lock file {}", lock.display()))
+}
+
+/// Creates the lock file, failing with `AlreadyExists` when there is one, so
+/// two instances opening a drawing at once can't both take it
+fn create_owner(lock: &Path, owner: &LockOwner) -> Result<(), AppError> {
+    let content = owner_json(owner)?;
+    let result = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(lock)
+        .and_then(|mut file| file.write_all(content.as_bytes()));
+    match result {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(e.into()),
+        other => other.context(format!("Failed to write lock file {}", lock.display())),
+    }
+}
+
+/// An existing lock file nobody could parse for a while, left over
+/// half-written or broken
+fn is_unreadable_leftover(lock: &Path) -> bool {
+    fs::metadata(lock)
+        .and_then(|m| m.modified())
+        .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age > UNREADABLE_STALE_AFTER))
+}
+
+fn classify(owner: Option<&LockOwner>, now: i64) -> LockState {
+    match owner {
+        None => LockState::Unlocked,
+        Some(owner) if owner.is_current_process() => LockState::Ours,
+        Some(owner) if owner.is_stale(now) => LockState::Stale,
+        Some(_) => LockState::Other,
+    }
+}
+
+/// Lock state of the drawing at `path`, reported as `external`
+pub fn status(path: &Path, external: String, now: i64) -> FileLockStatus {
+    let owner = lock_path(path).and_then(|lock| read_owner(&lock));
+    FileLockStatus {
+        path: external,
+        state: classify(owner.as_ref(), now),
+        owner,
+    }
+}
+
+/// Lock files held by this app, one per window with a drawing open
+#[derive(Default)]
+pub struct FileLocks {
+    held: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl FileLocks {
+    /// Takes the lock on `path` for window `label`, releasing the one the
+    /// window held before. A stale lock is taken over; a live lock of
+    /// another instance is left alone and its state returned.
+    pub fn acquire(&self, label: &str, path: &Path, now: i64) -> Result<LockState, AppError> {
+        self.release(label);
+        let lock = lock_path(path).ok_or_else(|| AppError::InvalidPath("Invalid file path".to_string()))?;
+        let state = match create_owner(&lock, &LockOwner::current(now)) {
+            Ok(()) => LockState::Unlocked,
+            Err(AppError::AlreadyExists(_)) => match read_owner(&lock) {
+                Some(owner) => match classify(Some(&owner), now) {
+                    LockState::Stale => take_over(&lock, now)?,
+                    state => state,
+                },
+                None if is_unreadable_leftover(&lock) => take_over(&lock, now)?,
+                // Being written by an instance that got there first
+                None => LockState::Other,
+            },
+            Err(e) => return Err(e),
+        };
+        if state != LockState::Other {
+            self.held.lock().unwrap().insert(label.to_string(), path.to_path_buf());
+        }
+        Ok(state)
+    }
+
+    /// Drops the lock window `label` holds, unless another window has the
+    /// same drawing open
+    pub fn release(&self, label: &str) {
+        let mut held = self.held.lock().unwrap();
+        let Some(path) = held.remove(label) else {
+            return;
+        };
+        if held.values().any(|other| *other == path) {
+            return;
+        }
+        remove_own_lock(&path);
+    }
+
+    /// Drops every lock, when the app quits
+    pub fn release_all(&self) {
+        for (_, path) in self.held.lock().unwrap().drain() {
+            remove_own_lock(&path);
+        }
+    }
+
+    /// Marks the lock on `path` as still in use, if this app holds it
+    pub fn refresh(&self, path: &Path, now: i64) {
+        if !self.held.lock().unwrap().values().any(|held| held == path) {
+            return;
+        }
+        let Some(lock) = lock_path(path) else {
+            return;
+        };
+        match read_owner(&lock) {
+            Some(mut owner) if owner.is_current_process() => {
+                owner.refreshed_at = now;
+                if let Err(e) = write_owner(&lock, &owner) {
+                    tracing::warn!("{}", e);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Replaces a stale lock file with ours; `Other` when another instance took it
+/// over in between
+fn take_over(lock: &Path, now: i64) -> Result<LockState, AppError> {
+    match fs::remove_file(lock) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            return Err(e).context(format!("Failed to remove stale lock file {}", lock.display()));
+        }
+        _ => {}
+    }
+    match create_owner(lock, &LockOwner::current(now)) {
+        Ok(()) => Ok(LockState::Stale),
+        Err(AppError::AlreadyExists(_)) => Ok(LockState::Other),
+        Err(e) => Err(e),
+    }
+}
+
+/// Refuses changes to the drawing at `path` while another running instance
+/// has it open
+pub fn check_not_locked(path: &Path, now: i64) -> Result<(), AppError> {
+    let Some(owner) = lock_path(path).and_then(|lock| read_owner(&lock)) else {
+        return Ok(());
+    };
+    match classify(Some(&owner), now) {
+        LockState::Other => Err(AppError::ReadOnly(format!(
+            "{} is open in another instance ({} on {})",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            owner.user,
+            owner.host
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn remove_own_lock(path: &Path) {
+    let Some(lock) = lock_path(path) else {
+        return;
+    };
+    // Taken over by another instance while we weren't looking; theirs now
+    if read_owner(&lock).is_some_and(|owner| owner.is_current_process())
+        && let Err(e) = fs::remove_file(&lock)
+    {
+        tracing::warn!("Failed to remove lock file {}: {}", lock.display(), e);
+    }
+}
+
+/// Removes the stale lock files below `dir`; returns how many
+pub fn clean_stale(dir: &Path, ignore: &IgnoreRules, now: i64) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() {
+            if entry.file_name() != backups::BACKUP_DIR && !ignore.is_ignored(&path, true) {
+                removed += clean_stale(&path, ignore, now);
+            }
+            continue;
+        }
+        // Unreadable lock files can't be told apart from ones being written
+        if kind.is_file()
+            && is_lock_file(&path)
+            && read_owner(&path).is_some_and(|owner| owner.is_stale(now))
+            && fs::remove_file(&path).is_ok()
+        {
+            removed += 1;
+        }
+    }
+    removed
+}
@@ -0,0 +1,275 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::error::{AppError, ResultExt};
+use crate::storage::ResolvedPath;
+
+/// Shape of a synthetic workspace generated for tests and benchmarks
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TestWorkspaceSpec {
+    /// Directory to generate into: a local path or a `memory://<mount>/` path
+    pub target: String,
+    pub file_count: usize,
+    /// Nesting depth of the generated folder tree (0 = flat)
+    pub folder_depth: usize,
+    pub folders_per_level: usize,
+    pub elements_per_file: usize,
+    /// Every n-th file gets `large_scene_elements` elements instead (0 = never)
+    pub large_scene_every: usize,
+    pub large_scene_elements: usize,
+    /// Seed for element placement, so runs are reproducible
+    pub seed: u64,
+}
+
+impl Default for TestWorkspaceSpec {
+    fn default() -> Self {
+        Self {
+            target: String::new(),
+            file_count: 50,
+            folder_depth: 2,
+            folders_per_level: 3,
+            elements_per_file: 20,
+            large_scene_every: 0,
+            large_scene_elements: 5000,
+            seed: 42,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TestWorkspaceSummary {
+    pub root: String,
+    pub files_created: usize,
+    pub directories_created: usize,
+    pub total_bytes: usize,
+    pub elapsed_ms: u128,
+}
+
+/// Small deterministic PRNG (SplitMix64) so fixtures don't depend on an RNG crate
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn range(&mut self, max: u64) -> u64 {
+        self.next() % max.max(1)
+    }
+}
+
+fn scene(rng: &mut SplitMix64, element_count: usize) -> serde_json::Value {
+    const TYPES: [&str; 4] = ["rectangle", "ellipse", "diamond", "text"];
+
+    let elements: Vec<serde_json::Value> = (0..element_count)
+        .map(|i| {
+            let kind = TYPES[rng.range(TYPES.len() as u64) as usize];
+            let mut element = serde_json::json!({
+                "id": format!("el-{}-{}", i, rng.next() % 1_000_000),
+                "type": kind,
+                "x": rng.range(4000) as f64,
+                "y": rng.range(4000) as f64,
+                "width": 40 + rng.range(200),
+                "height": 40 + rng.range(120),
+                "angle": 0,
+                "strokeColor": "#1e1e1e",
+                "backgroundColor": "transparent",
+                "strokeWidth": 2,
+                "opacity": 100,
+                "seed": rng.next() % i32::MAX as u64,
+                "version": 1,
+                "versionNonce": rng.next() % i32::MAX as u64,
+                "isDeleted": false
+            });
+            if kind == "text" {
+                element["text"] = serde_json::json!(format!("Node {}", i));
+                element["fontSize"] = serde_json::json!(20);
+            }
+            element
+        })
+        .collect();
+
+    serde_json::json!({
+        "type": "excalidraw",
+        "version": 2,
+        "source": "ExcaliApp",
+        "elements": elements,
+        "appState": {
+            "gridSize": null,
+            "viewBackgroundColor": "#ffffff"
+        },
+        "files": {}
+    })
+}
+
+fn create_folders(
+    root: &ResolvedPath,
+    dir: &Path,
+    depth: usize,
+    per_level: usize,
+    folders: &mut Vec<PathBuf>,
+) -> Result<(), AppError> {
+    if depth == 0 {
+        return Ok(());
+    }
+    for i in 0..per_level {
+        let child = dir.join(format!("folder-{}", i + 1));
+        if !root.backend.is_dir(&child) {
+            root.backend
+                .create_dir(&child)
+                .context(format!("Failed to create {}", child.display()))?;
+        }
+        folders.push(child.clone());
+        create_folders(root, &child, depth - 1, per_level, folders)?;
+    }
+    Ok(())
+}
+
+/// Generates the workspace described by `spec` below `root`, spreading files
+/// round-robin across the root and every generated folder
+pub fn generate(root: &ResolvedPath, spec: &TestWorkspaceSpec) -> Result<TestWorkspaceSummary, AppError> {
+    let started = std::time::Instant::now();
+
+    if !root.backend.is_dir(&root.path) {
+        return Err(AppError::InvalidPath("Target is not a directory".to_string()));
+    }
+
+    let mut folders = vec![root.path.clone()];
+    create_folders(
+        root,
+        &root.path,
+        spec.folder_depth,
+        spec.folders_per_level,
+        &mut folders,
+    )?;
+
+    let mut rng = SplitMix64(spec.seed);
+    let mut total_bytes = 0;
+    for i in 0..spec.file_count {
+        let is_large = spec.large_scene_every > 0 && (i + 1) % spec.large_scene_every == 0;
+        let element_count = if is_large {
+            spec.large_scene_elements
+        } else {
+            spec.elements_per_file
+        };
+
+        let content = serde_json::to_string(&scene(&mut rng, element_count))
+            .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+        let path = folders[i % folders.len()].join(format!("fixture-{:05}.excalidraw", i + 1));
+        root.backend
+            .write(&path, &content)
+            .context(format!("Failed to write {}", path.display()))?;
+        total_bytes += content.len();
+    }
+
+    Ok(TestWorkspaceSummary {
+        root: root.to_external(&root.path),
+        files_created: spec.file_count,
+        directories_created: folders.len() - 1,
+        total_bytes,
+        elapsed_ms: started.elapsed().as_millis(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{BackendConfig, Mounts};
+
+    fn memory_mount() -> Mounts {
+        let mounts = Mounts::default();
+        mounts
+            .mount(&BackendConfig {
+                kind: "memory".to_string(),
+                name: "fixtures".to_string(),
+                root: None,
+            })
+            .unwrap();
+        mounts
+    }
+
+    fn memory_root() -> ResolvedPath {
+        memory_mount().resolve_root("memory://fixtures/").unwrap()
+    }
+
+    fn spec(file_count: usize) -> TestWorkspaceSpec {
+        TestWorkspaceSpec {
+            file_count,
+            folder_depth: 2,
+            folders_per_level: 2,
+            elements_per_file: 5,
+            ..TestWorkspaceSpec::default()
+        }
+    }
+
+    #[test]
+    fn generates_requested_layout() {
+        let root = memory_root();
+        let summary = generate(&root, &spec(14)).unwrap();
+
+        assert_eq!(summary.root, "memory://fixtures/");
+        assert_eq!(summary.files_created, 14);
+        // 2 folders, each with 2 subfolders
+        assert_eq!(summary.directories_created, 6);
+        assert!(root.backend.is_dir(Path::new("/folder-2/folder-1")));
+        assert!(root.backend.is_file(Path::new("/fixture-00001.excalidraw")));
+        assert!(root.backend.is_file(Path::new("/folder-1/fixture-00002.excalidraw")));
+    }
+
+    #[test]
+    fn generated_scenes_are_valid_drawings() {
+        let root = memory_root();
+        generate(&root, &spec(3)).unwrap();
+
+        let content = root
+            .backend
+            .read_to_string(Path::new("/fixture-00001.excalidraw"))
+            .unwrap();
+        crate::security::validate_excalidraw_content(&content).unwrap();
+        let scene: serde_json::Value = serde_json::from_str(&content).unwrap();
+        assert_eq!(scene["elements"].as_array().unwrap().len(), 5);
+    }
+
+    #[test]
+    fn large_scenes_follow_spec() {
+        let root = memory_root();
+        let spec = TestWorkspaceSpec {
+            large_scene_every: 2,
+            large_scene_elements: 50,
+            ..spec(2)
+        };
+        generate(&root, &spec).unwrap();
+
+        let count = |path: &str| {
+            let content = root.backend.read_to_string(Path::new(path)).unwrap();
+            let scene: serde_json::Value = serde_json::from_str(&content).unwrap();
+            scene["elements"].as_array().unwrap().len()
+        };
+        assert_eq!(count("/fixture-00001.excalidraw"), 5);
+        assert_eq!(count("/folder-1/fixture-00002.excalidraw"), 50);
+    }
+
+    #[test]
+    fn same_seed_gives_same_workspace() {
+        let (first, second) = (memory_root(), memory_root());
+        generate(&first, &spec(4)).unwrap();
+        generate(&second, &spec(4)).unwrap();
+
+        let path = Path::new("/folder-1/folder-2/fixture-00004.excalidraw");
+        assert_eq!(
+            first.backend.read_to_string(path).unwrap(),
+            second.backend.read_to_string(path).unwrap()
+        );
+    }
+
+    #[test]
+    fn refuses_missing_target() {
+        let missing = memory_mount().resolve("memory://fixtures/missing").unwrap();
+        assert!(generate(&missing, &spec(1)).is_err());
+    }
+}
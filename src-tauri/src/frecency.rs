@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Store file holding per-file open/edit history, keyed by file path
+pub const STORE_FILE: &str = "frecency.json";
+pub const STORE_KEY: &str = "file_usage";
+
+/// Weight of an edit relative to an open; saving a file is a stronger signal
+const EDIT_WEIGHT: f64 = 2.0;
+
+const HOUR_MS: i64 = 60 * 60 * 1000;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageKind {
+    Open,
+    Edit,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct FileUsage {
+    pub open_count: u32,
+    pub edit_count: u32,
+    /// Milliseconds since the Unix epoch
+    pub last_opened: i64,
+    pub last_edited: i64,
+}
+
+pub type UsageMap = HashMap<String, FileUsage>;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RankedFile {
+    pub path: String,
+    pub name: String,
+    /// Combined ranking score; higher is better
+    pub score: f64,
+    pub match_score: f64,
+    pub frecency: f64,
+    /// Character positions of `name` matched by the query, for highlighting
+    pub matched_indices: Vec<usize>,
+}
+
+pub fn record(usage: &mut UsageMap, path: &str, kind: UsageKind, now: i64) {
+    let entry = usage.entry(path.to_string()).or_default();
+    match kind {
+        UsageKind::Open => {
+            entry.open_count = entry.open_count.saturating_add(1);
+            entry.last_opened = now;
+        }
+        UsageKind::Edit => {
+            entry.edit_count = entry.edit_count.saturating_add(1);
+            entry.last_edited = now;
+        }
+    }
+}
+
+/// Moves the history of `old_path` (and of files below it, for directories) to `new_path`
+pub fn rename(usage: &mut UsageMap, old_path: &str, new_path: &str) {
+    let prefix = format!("{}/", old_path.trim_end_matches(['/', '\\']));
+    let moved: Vec<String> = usage
+        .keys()
+        .filter(|k| k.as_str() == old_path || k.replace('\\', "/").starts_with(&prefix))
+        .cloned()
+        .collect();
+
+    for key in moved {
+        if let Some(entry) = usage.remove(&key) {
+            let new_key = format!("{}{}", new_path, &key[old_path.len()..]);
+            usage.insert(new_key, entry);
+        }
+    }
+}
+
+/// Firefox-style frecency: activity count weighted by how recent the last activity was
+pub fn frecency(usage: &FileUsage, now: i64) -> f64 {
+    let last = usage.last_opened.max(usage.last_edited);
+    let age = (now - last).max(0);
+    let recency = if age < 4 * HOUR_MS {
+        100.0
+    } else if age < DAY_MS {
+        70.0
+    } else if age < 7 * DAY_MS {
+        50.0
+    } else if age < 30 * DAY_MS {
+        30.0
+    } else if age < 90 * DAY_MS {
+        10.0
+    } else {
+        1.0
+    };
+    let visits = usage.open_count as f64 + usage.edit_count as f64 * EDIT_WEIGHT;
+    recency * (1.0 + visits).ln()
+}
+
+/// Subsequence fuzzy match in the style of quick-open palettes. Returns the
+/// score (0.0..=1.0) and matched character positions, or `None` if not all
+/// query characters occur in order.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(f64, Vec<usize>)> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(|c| c.to_lowercase())
+        .collect();
+    if query.is_empty() {
+        return Some((0.0, Vec::new()));
+    }
+
+    let chars: Vec<char> = candidate.chars().collect();
+    let mut indices = Vec::with_capacity(query.len());
+    let mut points = 0.0;
+    let mut qi = 0;
+    let mut previous: Option<usize> = None;
+
+    for (i, c) in chars.iter().enumerate() {
+        if qi == query.len() {
+            break;
+        }
+        if !c.to_lowercase().eq(std::iter::once(query[qi])) {
+            continue;
+        }
+
+        let mut point = 1.0;
+        // Consecutive runs and word starts are what users type
+        if previous == Some(i.wrapping_sub(1)) {
+            point += 2.0;
+        }
+        let at_word_start = i == 0
+            || matches!(chars[i - 1], ' ' | '-' | '_' | '.' | '/' | '\\')
+            || (chars[i - 1].is_lowercase() && c.is_uppercase());
+        if at_word_start {
+            point += 1.5;
+        }
+
+        points += point;
+        indices.push(i);
+        previous = Some(i);
+        qi += 1;
+    }
+
+    if qi < query.len() {
+        return None;
+    }
+
+    // Normalize by the best possible score and penalize long candidates slightly
+    let best = query.len() as f64 * 4.5;
+    let length_penalty = 1.0 / (1.0 + (chars.len() as f64 - query.len() as f64).max(0.0) / 50.0);
+    Some(((points / best).min(1.0) * length_penalty, indices))
+}
+
+/// Ranks `files` (path, name) by fuzzy match against `query` blended with
+/// frecency; without a query, files are ordered by frecency alone
+pub fn rank(
+    files: Vec<(String, String)>,
+    usage: &UsageMap,
+    query: Option<&str>,
+    now: i64,
+) -> Vec<RankedFile> {
+    let query = query.map(str::trim).filter(|q| !q.is_empty());
+
+    let mut ranked: Vec<RankedFile> = files
+        .into_iter()
+        .filter_map(|(path, name)| {
+            let stem = name.trim_end_matches(".excalidraw");
+            let (match_score, matched_indices) = match query {
+                Some(q) => fuzzy_match(q, stem)
+                    // Fall back to matching the full path, without highlight positions
+                    .or_else(|| fuzzy_match(q, &path).map(|(s, _)| (s * 0.5, Vec::new())))?,
+                None => (0.0, Vec::new()),
+            };
+            let frecency = usage.get(&path).map(|u| frecency(u, now)).unwrap_or(0.0);
+            // Match quality dominates; frecency (saturating) breaks near-ties
+            let score = match_score * 100.0 + frecency.min(500.0) / 10.0;
+            Some(RankedFile {
+                path,
+                name,
+                score,
+                match_score,
+                frecency,
+                matched_indices,
+            })
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    ranked
+}
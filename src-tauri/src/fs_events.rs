@@ -0,0 +1,174 @@
+use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+use notify::{Event, EventKind};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::paths::{self, WorkspacePath};
+
+/// How long a move-away waits for its other half before it counts as a removal
+pub const RENAME_TIMEOUT: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+    Renamed,
+}
+
+/// One change below a watched root, with renames already paired up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub kind: ChangeKind,
+    pub path: PathBuf,
+    /// Where a renamed entry was before
+    pub old_path: Option<PathBuf>,
+    pub is_directory: bool,
+}
+
+impl Change {
+    fn new(kind: ChangeKind, path: &Path, is_directory: bool) -> Self {
+        Self {
+            kind,
+            path: path.to_path_buf(),
+            old_path: None,
+            is_directory,
+        }
+    }
+
+    fn renamed(from: &Path, to: &Path) -> Self {
+        Self {
+            kind: ChangeKind::Renamed,
+            path: to.to_path_buf(),
+            old_path: Some(from.to_path_buf()),
+            is_directory: to.is_dir(),
+        }
+    }
+}
+
+/// Payload of `file-system-change`
+#[derive(Debug, Serialize, Clone)]
+pub struct FileSystemChange {
+    pub kind: ChangeKind,
+    #[serde(flatten)]
+    pub path: WorkspacePath,
+    pub old_path: Option<String>,
+    pub is_directory: bool,
+    /// Ignore rules changed, so the tree has to be read again
+    pub rescan: bool,
+}
+
+impl FileSystemChange {
+    pub fn new(root: &Path, change: &Change) -> Self {
+        Self {
+            kind: change.kind,
+            path: WorkspacePath::new(Some(root), &change.path),
+            old_path: change.old_path.as_deref().map(paths::normalize),
+            is_directory: change.is_directory,
+            rescan: false,
+        }
+    }
+}
+
+struct MovedAway {
+    path: PathBuf,
+    tracker: Option<usize>,
+    at: Instant,
+}
+
+/// Turns raw watcher events into `Change`s. Backends report a rename as a
+/// move-away and a move-to, linked by a cookie on Linux and by order
+/// elsewhere; Linux then repeats the pair as one event with both paths.
+#[derive(Default)]
+pub struct RenamePairing {
+    moved_away: Option<MovedAway>,
+    last_paired: Option<usize>,
+}
+
+impl RenamePairing {
+    /// Whether a move-away is waiting for its other half; `flush` it once
+    /// `RENAME_TIMEOUT` passes without events
+    pub fn is_pending(&self) -> bool {
+        self.moved_away.is_some()
+    }
+
+    /// A move-away that was never matched: the entry left the watched tree
+    pub fn flush(&mut self) -> Vec<Change> {
+        self.moved_away
+            .take()
+            .map(|moved| Change::new(ChangeKind::Removed, &moved.path, false))
+            .into_iter()
+            .collect()
+    }
+
+    pub fn push(&mut self, event: &Event) -> Vec<Change> {
+        let mut changes = Vec::new();
+        if self
+            .moved_away
+            .as_ref()
+            .is_some_and(|moved| moved.at.elapsed() > RENAME_TIMEOUT)
+        {
+            changes.extend(self.flush());
+        }
+
+        let tracker = event.attrs.tracker();
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let [from, to] = event.paths.as_slice()
+                    && (tracker.is_none() || tracker != self.last_paired)
+                {
+                    changes.push(Change::renamed(from, to));
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(mode)) => {
+                for path in &event.paths {
+                    let away = match mode {
+                        RenameMode::From => true,
+                        RenameMode::To => false,
+                        // Only the path tells which half this is
+                        _ => !path.exists(),
+                    };
+                    if away {
+                        changes.extend(self.flush());
+                        self.moved_away = Some(MovedAway {
+                            path: path.clone(),
+                            tracker,
+                            at: Instant::now(),
+                        });
+                        continue;
+                    }
+                    match self.moved_away.take() {
+                        Some(moved) if moved.tracker == tracker => {
+                            self.last_paired = tracker;
+                            changes.push(Change::renamed(&moved.path, path));
+                        }
+                        other => {
+                            self.moved_away = other;
+                            changes.extend(self.flush());
+                            changes.push(Change::new(ChangeKind::Created, path, path.is_dir()));
+                        }
+                    }
+                }
+            }
+            EventKind::Create(kind) => changes.extend(event.paths.iter().map(|path| {
+                Change::new(ChangeKind::Created, path, kind == CreateKind::Folder || path.is_dir())
+            })),
+            EventKind::Remove(kind) => changes.extend(
+                event
+                    .paths
+                    .iter()
+                    .map(|path| Change::new(ChangeKind::Removed, path, kind == RemoveKind::Folder)),
+            ),
+            EventKind::Modify(_) => changes.extend(
+                event
+                    .paths
+                    .iter()
+                    .map(|path| Change::new(ChangeKind::Modified, path, path.is_dir())),
+            ),
+            _ => {}
+        }
+        changes
+    }
+}
@@ -0,0 +1,218 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::background::TaskManager;
+use crate::compression;
+use crate::error::AppError;
+use crate::ignores::IgnoreRules;
+use crate::links;
+use crate::paths;
+use crate::scene_schema::{self, Strictness};
+use crate::security;
+
+/// Embedded files larger than this are reported as oversized
+const OVERSIZED_ASSET_BYTES: usize = 2 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HealthIssue {
+    pub path: String,
+    /// One of: invalid_json, schema, oversized_asset, broken_link, permission
+    pub category: String,
+    /// Either "error" (file will not open) or "warning"
+    pub severity: String,
+    pub message: String,
+    /// Suggested action the user can take to resolve the issue
+    pub fix_action: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkspaceHealthReport {
+    pub directory: String,
+    pub files_checked: usize,
+    pub healthy_files: usize,
+    pub issues: Vec<HealthIssue>,
+    /// Issue count per category
+    pub categories: HashMap<String, usize>,
+}
+
+fn issue(
+    path: &Path,
+    category: &str,
+    severity: &str,
+    message: String,
+    fix_action: Option<&str>,
+) -> HealthIssue {
+    HealthIssue {
+        path: paths::normalize(path),
+        category: category.to_string(),
+        severity: severity.to_string(),
+        message,
+        fix_action: fix_action.map(|s| s.to_string()),
+    }
+}
+
+/// Runs every health check over all drawings below `directory`, spreading the
+/// files across the background workers
+pub fn check_workspace(
+    directory: &Path,
+    ignore: &IgnoreRules,
+    tasks: &TaskManager,
+) -> Result<WorkspaceHealthReport, AppError> {
+    let mut files = Vec::new();
+    crate::collect_excalidraw_files_recursive(directory, ignore, &mut files)?;
+    let paths: Vec<PathBuf> = files.iter().map(|f| PathBuf::from(&f.path)).collect();
+    let files_checked = paths.len();
+
+    let per_file: Vec<Vec<HealthIssue>> = tasks.run(paths, |path| {
+        let issues = check_file(&path);
+        tasks.throttle_io(fs::metadata(&path).map(|m| m.len() as usize).unwrap_or(0));
+        issues
+    });
+
+    let healthy_files = per_file.iter().filter(|issues| issues.is_empty()).count();
+    let mut issues: Vec<HealthIssue> = per_file.into_iter().flatten().collect();
+    issues.sort_by(|a, b| a.path.cmp(&b.path).then(a.category.cmp(&b.category)));
+
+    let mut categories = HashMap::new();
+    for issue in &issues {
+        *categories.entry(issue.category.clone()).or_insert(0) += 1;
+    }
+
+    Ok(WorkspaceHealthReport {
+        directory: paths::normalize(directory),
+        files_checked,
+        healthy_files,
+        issues,
+        categories,
+    })
+}
+
+pub(crate) fn check_file(path: &Path) -> Vec<HealthIssue> {
+    let mut issues = Vec::new();
+
+    // Permission issues
+    if let Ok(metadata) = fs::metadata(path)
+        && metadata.permissions().readonly()
+    {
+        issues.push(issue(
+            path,
+            "permission",
+            "warning",
+            "File is read-only; changes cannot be saved".to_string(),
+            Some("Make the file writable"),
+        ));
+    }
+
+    let content = match compression::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            issues.push(issue(
+                path,
+                "permission",
+                "error",
+                format!("File cannot be read: {}", e),
+                Some("Grant read permission to the file"),
+            ));
+            return issues;
+        }
+        Err(e) => {
+            issues.push(issue(
+                path,
+                "invalid_json",
+                "error",
+                format!("Failed to read file: {}", e),
+                Some("Restore the file from a backup"),
+            ));
+            return issues;
+        }
+    };
+
+    // JSON validity
+    let json: serde_json::Value = match serde_json::from_str(&content) {
+        Ok(json) => json,
+        Err(e) => {
+            issues.push(issue(
+                path,
+                "invalid_json",
+                "error",
+                format!("Invalid JSON: {}", e),
+                Some("Restore the file from a backup"),
+            ));
+            return issues;
+        }
+    };
+
+    // Excalidraw schema
+    if let Err(e) = security::validate_excalidraw_content(&content) {
+        issues.push(issue(
+            path,
+            "schema",
+            "error",
+            e.to_string(),
+            Some("Re-save the drawing from the editor"),
+        ));
+    } else {
+        issues.extend(
+            scene_schema::validate_elements(&json, Strictness::Lenient)
+                .into_iter()
+                .map(|warning| {
+                    issue(
+                        path,
+                        "schema",
+                        "warning",
+                        warning.message,
+                        Some("Re-save the drawing from the editor"),
+                    )
+                }),
+        );
+    }
+
+    // Oversized embedded assets
+    if let Some(files) = json.get("files").and_then(|f| f.as_object()) {
+        for (id, file) in files {
+            let size = file
+                .get("dataURL")
+                .and_then(|d| d.as_str())
+                .map(|d| d.len())
+                .unwrap_or(0);
+            if size > OVERSIZED_ASSET_BYTES {
+                issues.push(issue(
+                    path,
+                    "oversized_asset",
+                    "warning",
+                    format!(
+                        "Embedded file {} is {:.1} MB",
+                        id,
+                        size as f64 / (1024.0 * 1024.0)
+                    ),
+                    Some("Compress or replace the embedded image"),
+                ));
+            }
+        }
+    }
+
+    // Broken internal links to other drawings
+    if let Some(elements) = json.get("elements").and_then(|e| e.as_array()) {
+        let base = path.parent().unwrap_or(Path::new(""));
+        for element in elements {
+            let Some(link) = element.get("link").and_then(|l| l.as_str()) else {
+                continue;
+            };
+            if let Some(target) = links::internal_link_target(base, link)
+                && !target.exists()
+            {
+                issues.push(issue(
+                    path,
+                    "broken_link",
+                    "warning",
+                    format!("Link points to missing file: {}", link),
+                    Some("Update or remove the link"),
+                ));
+            }
+        }
+    }
+
+    issues
+}
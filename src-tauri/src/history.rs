@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::ai::{AIProvider, ChatParams};
+
+/// Store file holding the AI request/response history
+pub const STORE_FILE: &str = "ai_history.json";
+pub const STORE_KEY: &str = "entries";
+
+/// Oldest entries are dropped beyond this many
+const MAX_ENTRIES: usize = 500;
+/// Responses are stored up to this many characters
+const MAX_RESPONSE_CHARS: usize = 8000;
+const PREVIEW_CHARS: usize = 200;
+pub const DEFAULT_PAGE_SIZE: usize = 20;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AIHistoryEntry {
+    pub id: String,
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+    /// "generate", "stream" or "image"
+    pub operation: String,
+    pub provider: String,
+    pub model: String,
+    /// Kept (without the API key) so the request can be re-run
+    pub base_url: String,
+    pub prompt: String,
+    pub system_prompt: Option<String>,
+    pub max_tokens: u32,
+    pub temperature: f32,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub response: Option<String>,
+    #[serde(default)]
+    pub response_truncated: bool,
+    pub error: Option<String>,
+    pub tokens_used: Option<u32>,
+    pub request_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AIHistorySummary {
+    pub id: String,
+    pub timestamp: i64,
+    pub operation: String,
+    pub provider: String,
+    pub model: String,
+    pub prompt_preview: String,
+    pub response_preview: Option<String>,
+    pub duration_ms: u64,
+    pub success: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AIHistoryPage {
+    pub entries: Vec<AIHistorySummary>,
+    pub total: usize,
+    /// Zero-based page index
+    pub page: usize,
+    pub page_size: usize,
+    pub has_more: bool,
+}
+
+fn truncate(text: &str, max_chars: usize) -> (String, bool) {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => (text[..end].to_string(), true),
+        None => (text.to_string(), false),
+    }
+}
+
+fn nanos_since_epoch() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0)
+}
+
+fn preview(text: &str) -> String {
+    let (mut preview, truncated) = truncate(text, PREVIEW_CHARS);
+    if truncated {
+        preview.push('…');
+    }
+    preview
+}
+
+impl AIHistoryEntry {
+    /// Builds an entry from a finished call; `outcome` is the response text or the error
+    pub fn new(
+        operation: &str,
+        provider: AIProvider,
+        params: &ChatParams,
+        elapsed: Duration,
+        outcome: Result<&str, &str>,
+        tokens_used: Option<u32>,
+        request_id: Option<&str>,
+    ) -> Self {
+        let timestamp = crate::prompts::now_millis();
+        let (response, response_truncated) = match outcome {
+            Ok(text) => {
+                let (text, truncated) = truncate(text, MAX_RESPONSE_CHARS);
+                (Some(text), truncated)
+            }
+            Err(_) => (None, false),
+        };
+
+        AIHistoryEntry {
+            id: format!("ai_{}", nanos_since_epoch()),
+            timestamp,
+            operation: operation.to_string(),
+            provider: provider.name().to_string(),
+            model: params.model.to_string(),
+            base_url: params.base_url.to_string(),
+            prompt: params.prompt.to_string(),
+            system_prompt: params.system_prompt.map(|s| s.to_string()),
+            max_tokens: params.max_tokens,
+            temperature: params.temperature,
+            duration_ms: elapsed.as_millis() as u64,
+            success: outcome.is_ok(),
+            response,
+            response_truncated,
+            error: outcome.err().map(|e| e.to_string()),
+            tokens_used,
+            request_id: request_id.map(|id| id.to_string()),
+        }
+    }
+
+    fn summary(&self) -> AIHistorySummary {
+        AIHistorySummary {
+            id: self.id.clone(),
+            timestamp: self.timestamp,
+            operation: self.operation.clone(),
+            provider: self.provider.clone(),
+            model: self.model.clone(),
+            prompt_preview: preview(&self.prompt),
+            response_preview: self.response.as_deref().map(preview),
+            duration_ms: self.duration_ms,
+            success: self.success,
+        }
+    }
+}
+
+/// Appends an entry, dropping the oldest ones beyond the history limit
+pub fn append(entries: &mut Vec<AIHistoryEntry>, entry: AIHistoryEntry) {
+    entries.push(entry);
+    if entries.len() > MAX_ENTRIES {
+        let excess = entries.len() - MAX_ENTRIES;
+        entries.drain(..excess);
+    }
+}
+
+/// Returns one page of summaries, newest first
+pub fn page(entries: &[AIHistoryEntry], page: usize, page_size: usize) -> AIHistoryPage {
+    let page_size = page_size.clamp(1, 200);
+    let start = page.saturating_mul(page_size);
+
+    AIHistoryPage {
+        entries: entries
+            .iter()
+            .rev()
+            .skip(start)
+            .take(page_size)
+            .map(AIHistoryEntry::summary)
+            .collect(),
+        total: entries.len(),
+        page,
+        page_size,
+        has_more: start.saturating_add(page_size) < entries.len(),
+    }
+}
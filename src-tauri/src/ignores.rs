@@ -0,0 +1,77 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Gitignore-style file at the root of a vault
+pub const IGNORE_FILE: &str = ".excaliappignore";
+
+/// Global patterns used until the user edits them in the preferences
+pub fn default_patterns() -> Vec<String> {
+    vec![
+        "node_modules/".to_string(),
+        ".git/".to_string(),
+        format!("{}/", crate::backups::BACKUP_DIR),
+    ]
+}
+
+pub fn is_ignore_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == IGNORE_FILE)
+}
+
+/// Lines of `<root>/.excaliappignore`; a missing file has none
+pub fn read_ignore_file(root: &Path) -> Vec<String> {
+    match fs::read_to_string(root.join(IGNORE_FILE)) {
+        Ok(content) => content.lines().map(|line| line.to_string()).collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            tracing::warn!("Failed to read {}: {}", IGNORE_FILE, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Compiled ignore patterns of one vault, matched relative to its root
+pub struct IgnoreRules {
+    root: PathBuf,
+    matcher: Gitignore,
+}
+
+impl IgnoreRules {
+    /// Ignores nothing
+    pub fn none() -> Self {
+        Self {
+            root: PathBuf::new(),
+            matcher: Gitignore::empty(),
+        }
+    }
+
+    /// Compiles patterns in order, so later ones can re-include with `!`.
+    /// Invalid patterns are logged and skipped.
+    pub fn new(root: &Path, patterns: &[String]) -> Self {
+        let mut builder = GitignoreBuilder::new(root);
+        for line in patterns {
+            if let Err(e) = builder.add_line(None, line) {
+                tracing::warn!("Invalid ignore pattern '{}': {}", line, e);
+            }
+        }
+
+        match builder.build() {
+            Ok(matcher) => Self {
+                root: root.to_path_buf(),
+                matcher,
+            },
+            Err(e) => {
+                tracing::warn!("Failed to build ignore rules: {}", e);
+                Self::none()
+            }
+        }
+    }
+
+    /// True when `path` or one of its parent folders matches; paths outside
+    /// the vault are never ignored
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        path.starts_with(&self.root)
+            && path != self.root
+            && self.matcher.matched_path_or_any_parents(path, is_dir).is_ignore()
+    }
+}
@@ -0,0 +1,229 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use crate::background::TaskManager;
+use crate::error::{AppError, ResultExt};
+use crate::paths;
+
+pub const DEFAULT_QUALITY: u8 = 80;
+pub const DEFAULT_MAX_DIMENSION: u32 = 2048;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImageSkip {
+    pub file_id: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OptimizeImagesResult {
+    pub content: String,
+    pub images: usize,
+    pub optimized: usize,
+    /// Size of the embedded data URLs before and after
+    pub bytes_before: usize,
+    pub bytes_after: usize,
+    pub skipped: Vec<ImageSkip>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ExtractedImage {
+    pub file_id: String,
+    pub path: String,
+    pub mime_type: String,
+    pub bytes: usize,
+}
+
+/// Splits a `data:<mime>;base64,<payload>` URL into mime type and decoded bytes
+fn decode_data_url(data_url: &str) -> Result<(String, Vec<u8>), AppError> {
+    let rest = data_url.strip_prefix("data:")
+        .ok_or_else(|| AppError::InvalidInput("Not a data URL".to_string()))?;
+    let (header, payload) = rest.split_once(',')
+        .ok_or_else(|| AppError::InvalidInput("Malformed data URL".to_string()))?;
+    let mime_type = header.strip_suffix(";base64")
+        .ok_or_else(|| AppError::InvalidInput("Data URL is not base64 encoded".to_string()))?;
+    let bytes = BASE64
+        .decode(payload.trim())
+        .map_err(|e| AppError::InvalidInput(format!("Invalid base64 data: {}", e)))?;
+    Ok((mime_type.to_string(), bytes))
+}
+
+fn encode_data_url(mime_type: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime_type, BASE64.encode(bytes))
+}
+
+fn extension_for(mime_type: &str) -> &'static str {
+    match mime_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        "image/svg+xml" => "svg",
+        "image/bmp" => "bmp",
+        _ => "bin",
+    }
+}
+
+fn has_transparency(image: &DynamicImage) -> bool {
+    image.color().has_alpha() && image.to_rgba8().pixels().any(|p| p.0[3] < 255)
+}
+
+/// Downscales to `max_dimension` and re-encodes: JPEG at `quality` for
+/// opaque images, lossless WebP when transparency must be kept. Returns the
+/// new mime type and bytes, or `None` when the result would not be smaller.
+fn optimize_image(
+    mime_type: &str,
+    bytes: &[u8],
+    quality: u8,
+    max_dimension: u32,
+) -> Result<Option<(String, Vec<u8>)>, AppError> {
+    // Vector and animated images would lose content when rasterized
+    if matches!(mime_type, "image/svg+xml" | "image/gif") {
+        return Err(AppError::InvalidInput(format!("{} images are left untouched", mime_type)));
+    }
+
+    let image = image::load_from_memory(bytes)
+        .map_err(|e| AppError::InvalidInput(format!("Failed to decode image: {}", e)))?;
+    let (width, height) = image.dimensions();
+    let resized = width.max(height) > max_dimension;
+    let image = if resized {
+        image.resize(max_dimension, max_dimension, image::imageops::FilterType::Lanczos3)
+    } else {
+        image
+    };
+
+    let mut output = Cursor::new(Vec::new());
+    let mime_type = if has_transparency(&image) {
+        image
+            .to_rgba8()
+            .write_to(&mut output, ImageFormat::WebP)
+            .map_err(|e| AppError::Internal(format!("Failed to encode WebP: {}", e)))?;
+        "image/webp"
+    } else {
+        let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut output, quality.clamp(1, 100));
+        image
+            .to_rgb8()
+            .write_with_encoder(encoder)
+            .map_err(|e| AppError::Internal(format!("Failed to encode JPEG: {}", e)))?;
+        "image/jpeg"
+    };
+
+    let output = output.into_inner();
+    if !resized && output.len() >= bytes.len() {
+        return Ok(None);
+    }
+    Ok(Some((mime_type.to_string(), output)))
+}
+
+/// Re-encodes every embedded raster image of a scene; the scene's elements
+/// are untouched since they size images independently of their pixels
+pub fn optimize_scene(
+    content: &str,
+    quality: u8,
+    max_dimension: u32,
+    tasks: &TaskManager,
+) -> Result<OptimizeImagesResult, AppError> {
+    let mut scene: Value = serde_json::from_str(content)?;
+    let Some(files) = scene.get_mut("files").and_then(|f| f.as_object_mut()) else {
+        return Err(AppError::InvalidInput("Scene has no embedded images".to_string()));
+    };
+
+    let originals: Vec<(String, String)> = files
+        .iter()
+        .filter_map(|(id, file)| Some((id.clone(), file.get("dataURL")?.as_str()?.to_string())))
+        .collect();
+    let bytes_before: usize = originals.iter().map(|(_, url)| url.len()).sum();
+    let images = originals.len();
+
+    let outcomes = tasks.run(originals, |(id, data_url)| {
+        let result = decode_data_url(&data_url)
+            .and_then(|(mime_type, bytes)| optimize_image(&mime_type, &bytes, quality, max_dimension));
+        (id, data_url, result)
+    });
+
+    let mut optimized = 0;
+    let mut bytes_after = 0;
+    let mut skipped = Vec::new();
+    for (id, original, outcome) in outcomes {
+        match outcome {
+            Ok(Some((mime_type, bytes))) => {
+                let data_url = encode_data_url(&mime_type, &bytes);
+                bytes_after += data_url.len();
+                if let Some(file) = files.get_mut(&id) {
+                    file["dataURL"] = Value::String(data_url);
+                    file["mimeType"] = Value::String(mime_type);
+                }
+                optimized += 1;
+            }
+            Ok(None) => {
+                bytes_after += original.len();
+                skipped.push(ImageSkip {
+                    file_id: id,
+                    reason: "Already optimal".to_string(),
+                });
+            }
+            Err(e) => {
+                bytes_after += original.len();
+                skipped.push(ImageSkip {
+                    file_id: id,
+                    reason: e.to_string(),
+                });
+            }
+        }
+    }
+
+    Ok(OptimizeImagesResult {
+        content: serde_json::to_string(&scene)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?,
+        images,
+        optimized,
+        bytes_before,
+        bytes_after,
+        skipped,
+    })
+}
+
+/// Writes every embedded image to `out_dir` as `<file id>.<ext>`
+pub fn extract_scene(content: &str, out_dir: &Path) -> Result<Vec<ExtractedImage>, AppError> {
+    let scene: Value = serde_json::from_str(content)?;
+    let Some(files) = scene.get("files").and_then(|f| f.as_object()) else {
+        return Ok(Vec::new());
+    };
+
+    fs::create_dir_all(out_dir).context("Failed to create output directory")?;
+
+    let mut extracted = Vec::new();
+    for (id, file) in files {
+        let Some(data_url) = file.get("dataURL").and_then(|d| d.as_str()) else {
+            continue;
+        };
+        let (mime_type, bytes) = match decode_data_url(data_url) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                tracing::warn!("Skipping embedded file {}: {}", id, e);
+                continue;
+            }
+        };
+
+        // File ids come from the scene, so keep them to safe file name characters
+        let stem: String = id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect();
+        let path = out_dir.join(format!("{}.{}", stem, extension_for(&mime_type)));
+        fs::write(&path, &bytes).context(format!("Failed to write {}", path.display()))?;
+
+        extracted.push(ExtractedImage {
+            file_id: id.clone(),
+            path: paths::normalize(&path),
+            mime_type,
+            bytes: bytes.len(),
+        });
+    }
+    Ok(extracted)
+}
@@ -0,0 +1,391 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::background::TaskManager;
+use crate::compression;
+use crate::error::AppError;
+use crate::ignores::IgnoreRules;
+use crate::paths;
+use crate::repair;
+use crate::scene_schema::{self, Strictness, WarningCode};
+use crate::security;
+
+/// A repair `repair_file` can apply; each issue names the one that resolves it
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegrityFix {
+    /// Reparse leniently and fill, drop or renumber broken elements
+    RepairContent,
+    /// Clear references to elements that don't exist or are deleted
+    DropDanglingReferences,
+    /// Make `boundElements` agree with arrow bindings and text containers
+    SyncBindings,
+    /// Drop `files` entries no image shows
+    RemoveOrphanedFiles,
+    /// Drop images whose data is missing
+    RemoveBrokenImages,
+}
+
+impl IntegrityFix {
+    /// Order fixes are applied in: content repair first, as the others need
+    /// a valid scene, and reference cleanup after elements are removed
+    const ORDER: [IntegrityFix; 5] = [
+        IntegrityFix::RepairContent,
+        IntegrityFix::RemoveBrokenImages,
+        IntegrityFix::DropDanglingReferences,
+        IntegrityFix::SyncBindings,
+        IntegrityFix::RemoveOrphanedFiles,
+    ];
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct IntegrityIssue {
+    pub path: String,
+    pub element_id: Option<String>,
+    /// One of: invalid_content, schema, dangling_reference,
+    /// one_sided_binding, orphaned_file, missing_image_data
+    pub code: String,
+    /// Either "error" (file will not open) or "warning"
+    pub severity: String,
+    pub message: String,
+    pub fix: Option<IntegrityFix>,
+}
+
+/// Answer of `scan_vault`
+#[derive(Debug, Serialize, Clone)]
+pub struct IntegrityReport {
+    pub directory: String,
+    pub files_scanned: usize,
+    pub files_with_issues: usize,
+    pub issues: Vec<IntegrityIssue>,
+    /// Issue count per code
+    pub codes: HashMap<String, usize>,
+}
+
+/// Answer of `repair_file`
+#[derive(Debug, Serialize, Clone)]
+pub struct IntegrityRepair {
+    /// Description of every change made
+    pub applied: Vec<String>,
+    /// What a scan still finds afterwards
+    pub remaining: Vec<IntegrityIssue>,
+}
+
+fn str_field<'a>(element: &'a Value, key: &str) -> Option<&'a str> {
+    element.get(key).and_then(|v| v.as_str())
+}
+
+fn is_live(element: &Value) -> bool {
+    element.get("isDeleted").and_then(|d| d.as_bool()) != Some(true)
+}
+
+/// What each element's `boundElements` should list: the arrows bound to it
+/// and the text inside it, as (id, type)
+fn expected_bindings(elements: &[Value]) -> HashMap<String, Vec<(String, &'static str)>> {
+    let mut expected: HashMap<String, Vec<(String, &'static str)>> = HashMap::new();
+    for element in elements.iter().filter(|e| is_live(e)) {
+        let Some(id) = str_field(element, "id") else {
+            continue;
+        };
+        let mut targets = Vec::new();
+        if str_field(element, "type") == Some("arrow") {
+            for key in ["startBinding", "endBinding"] {
+                targets.extend(element.get(key).and_then(|b| str_field(b, "elementId")).map(|t| (t, "arrow")));
+            }
+        }
+        if str_field(element, "type") == Some("text") {
+            targets.extend(str_field(element, "containerId").map(|t| (t, "text")));
+        }
+        for (target, kind) in targets {
+            let list = expected.entry(target.to_string()).or_default();
+            if !list.iter().any(|(bound, _)| bound == id) {
+                list.push((id.to_string(), kind));
+            }
+        }
+    }
+    expected
+}
+
+fn bound_ids(element: &Value) -> Vec<&str> {
+    element
+        .get("boundElements")
+        .and_then(|b| b.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|b| str_field(b, "id"))
+        .collect()
+}
+
+/// Ids of the images live image elements show
+fn shown_files(elements: &[Value]) -> HashSet<&str> {
+    elements
+        .iter()
+        .filter(|e| is_live(e) && str_field(e, "type") == Some("image"))
+        .filter_map(|e| str_field(e, "fileId"))
+        .collect()
+}
+
+/// Every integrity problem of one drawing's content
+pub fn check(path: &Path, content: &str) -> Vec<IntegrityIssue> {
+    let path = paths::normalize(path);
+    let mut issues = Vec::new();
+    let mut issue = |element_id: Option<&str>, code: &str, severity: &str, message: String, fix: Option<IntegrityFix>| {
+        issues.push(IntegrityIssue {
+            path: path.clone(),
+            element_id: element_id.map(str::to_string),
+            code: code.to_string(),
+            severity: severity.to_string(),
+            message,
+            fix,
+        });
+    };
+
+    let scene: Value = match serde_json::from_str(content) {
+        Ok(scene) => scene,
+        Err(e) => {
+            issue(None, "invalid_content", "error", format!("Invalid JSON: {}", e), Some(IntegrityFix::RepairContent));
+            return issues;
+        }
+    };
+    if let Err(e) = security::validate_excalidraw_content(content) {
+        issue(None, "invalid_content", "error", e.to_string(), Some(IntegrityFix::RepairContent));
+        return issues;
+    }
+
+    for warning in scene_schema::validate_elements(&scene, Strictness::Lenient) {
+        if warning.code != WarningCode::DanglingReference {
+            issue(warning.element_id.as_deref(), "schema", "warning", warning.message, Some(IntegrityFix::RepairContent));
+        }
+    }
+    // References to deleted elements are as broken as those to missing ones
+    for warning in scene_schema::validate_elements(&scene, Strictness::Strict) {
+        if warning.code == WarningCode::DanglingReference {
+            issue(
+                warning.element_id.as_deref(),
+                "dangling_reference",
+                "warning",
+                warning.message,
+                Some(IntegrityFix::DropDanglingReferences),
+            );
+        }
+    }
+
+    let elements: &[Value] = scene.get("elements").and_then(|e| e.as_array()).map_or(&[], Vec::as_slice);
+    let live: HashMap<&str, &Value> = elements
+        .iter()
+        .filter(|e| is_live(e))
+        .filter_map(|e| Some((str_field(e, "id")?, e)))
+        .collect();
+    let expected = expected_bindings(elements);
+    for (target, bindings) in &expected {
+        let Some(element) = live.get(target.as_str()) else {
+            continue;
+        };
+        let listed = bound_ids(element);
+        for (id, kind) in bindings.iter().filter(|(id, _)| !listed.contains(&id.as_str())) {
+            issue(
+                Some(target),
+                "one_sided_binding",
+                "warning",
+                format!("Element \"{}\" doesn't list the {} \"{}\" bound to it", target, kind, id),
+                Some(IntegrityFix::SyncBindings),
+            );
+        }
+    }
+    for (id, element) in &live {
+        let bindings = expected.get(*id);
+        for bound in bound_ids(element) {
+            let reciprocated = bindings.is_some_and(|b| b.iter().any(|(other, _)| other == bound));
+            if !reciprocated && live.contains_key(bound) {
+                issue(
+                    Some(id),
+                    "one_sided_binding",
+                    "warning",
+                    format!("Element \"{}\" lists \"{}\" as bound, which isn't bound to it", id, bound),
+                    Some(IntegrityFix::SyncBindings),
+                );
+            }
+        }
+    }
+
+    let files = scene.get("files").and_then(|f| f.as_object());
+    let shown = shown_files(elements);
+    for file_id in files.into_iter().flat_map(|f| f.keys()) {
+        if !shown.contains(file_id.as_str()) {
+            issue(
+                None,
+                "orphaned_file",
+                "warning",
+                format!("Image data \"{}\" isn't shown by any element", file_id),
+                Some(IntegrityFix::RemoveOrphanedFiles),
+            );
+        }
+    }
+    for element in live.values().filter(|e| str_field(e, "type") == Some("image")) {
+        let file_id = str_field(element, "fileId").unwrap_or_default();
+        if !files.is_some_and(|f| f.contains_key(file_id)) {
+            issue(
+                str_field(element, "id"),
+                "missing_image_data",
+                "warning",
+                format!("Image data \"{}\" is missing", file_id),
+                Some(IntegrityFix::RemoveBrokenImages),
+            );
+        }
+    }
+    issues
+}
+
+/// Checks every drawing below `directory`, spreading the files across the
+/// background workers
+pub fn scan(directory: &Path, ignore: &IgnoreRules, tasks: &TaskManager) -> Result<IntegrityReport, AppError> {
+    let mut files = Vec::new();
+    crate::collect_excalidraw_files_recursive(directory, ignore, &mut files)?;
+    let paths: Vec<PathBuf> = files.iter().map(|f| PathBuf::from(&f.path)).collect();
+    let files_scanned = paths.len();
+
+    let per_file: Vec<Vec<IntegrityIssue>> = tasks.run(paths, |path| match compression::read_to_string(&path) {
+        Ok(content) => {
+            tasks.throttle_io(content.len());
+            check(&path, &content)
+        }
+        Err(e) => vec![IntegrityIssue {
+            path: paths::normalize(&path),
+            element_id: None,
+            code: "invalid_content".to_string(),
+            severity: "error".to_string(),
+            message: format!("Failed to read file: {}", e),
+            fix: None,
+        }],
+    });
+
+    let files_with_issues = per_file.iter().filter(|issues| !issues.is_empty()).count();
+    let mut issues: Vec<IntegrityIssue> = per_file.into_iter().flatten().collect();
+    issues.sort_by(|a, b| a.path.cmp(&b.path).then(a.code.cmp(&b.code)));
+    let mut codes = HashMap::new();
+    for issue in &issues {
+        *codes.entry(issue.code.clone()).or_insert(0) += 1;
+    }
+
+    Ok(IntegrityReport {
+        directory: paths::normalize(directory),
+        files_scanned,
+        files_with_issues,
+        issues,
+        codes,
+    })
+}
+
+/// Points `boundElements` at exactly the arrows bound to each element and
+/// the text inside it, keeping the listed order
+fn sync_bindings(elements: &mut [Value]) -> Vec<String> {
+    let expected = expected_bindings(elements);
+    let mut applied = Vec::new();
+    for element in elements.iter_mut().filter(|e| is_live(e)) {
+        let Some(id) = str_field(element, "id").map(str::to_string) else {
+            continue;
+        };
+        let bindings = expected.get(&id).map(Vec::as_slice).unwrap_or_default();
+        let listed = element.get("boundElements").and_then(|b| b.as_array()).cloned().unwrap_or_default();
+        let mut synced: Vec<Value> = listed
+            .iter()
+            .filter(|b| str_field(b, "id").is_some_and(|bound| bindings.iter().any(|(other, _)| other == bound)))
+            .cloned()
+            .collect();
+        for (bound, kind) in bindings {
+            if !synced.iter().any(|b| str_field(b, "id") == Some(bound)) {
+                synced.push(json!({"id": bound, "type": kind}));
+            }
+        }
+        if synced != listed {
+            applied.push(format!("Updated the bound elements of \"{}\"", id));
+            element["boundElements"] = if synced.is_empty() { Value::Null } else { Value::Array(synced) };
+        }
+    }
+    applied
+}
+
+fn remove_broken_images(scene: &mut Value) -> Vec<String> {
+    let files: HashSet<String> = scene
+        .get("files")
+        .and_then(|f| f.as_object())
+        .map(|f| f.keys().cloned().collect())
+        .unwrap_or_default();
+    let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut()) else {
+        return Vec::new();
+    };
+    let mut applied = Vec::new();
+    elements.retain(|e| {
+        let broken = is_live(e)
+            && str_field(e, "type") == Some("image")
+            && !str_field(e, "fileId").is_some_and(|f| files.contains(f));
+        if broken {
+            applied.push(format!("Removed image \"{}\" without data", str_field(e, "id").unwrap_or_default()));
+        }
+        !broken
+    });
+    applied
+}
+
+fn remove_orphaned_files(scene: &mut Value) -> Vec<String> {
+    let shown: HashSet<String> = scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|e| shown_files(e).into_iter().map(str::to_string).collect())
+        .unwrap_or_default();
+    let Some(files) = scene.get_mut("files").and_then(|f| f.as_object_mut()) else {
+        return Vec::new();
+    };
+    let orphaned: Vec<String> = files.keys().filter(|id| !shown.contains(*id)).cloned().collect();
+    for id in &orphaned {
+        files.remove(id);
+    }
+    orphaned
+        .into_iter()
+        .map(|id| format!("Removed unused image data \"{}\"", id))
+        .collect()
+}
+
+/// Applies the selected fixes to a drawing's content; returns the new
+/// content and a description of each change
+pub fn apply(content: &str, fixes: &[IntegrityFix]) -> Result<(String, Vec<String>), AppError> {
+    let mut applied = Vec::new();
+    let mut content = content.to_string();
+    if fixes.contains(&IntegrityFix::RepairContent) {
+        let repaired = repair::validate_and_repair_scene(&content)?;
+        applied.extend(repaired.fixes);
+        content = repaired.content;
+    }
+
+    let mut scene: Value = serde_json::from_str(&content)?;
+    for fix in IntegrityFix::ORDER.into_iter().filter(|fix| fixes.contains(fix)) {
+        let elements = scene.get_mut("elements").and_then(|e| e.as_array_mut());
+        match (fix, elements) {
+            (IntegrityFix::RepairContent, _) => {}
+            (IntegrityFix::RemoveBrokenImages, _) => {
+                let removed = remove_broken_images(&mut scene);
+                // Arrows bound to a removed image would point at nothing
+                if !removed.is_empty()
+                    && let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut())
+                {
+                    scene_schema::sanitize_elements(elements);
+                }
+                applied.extend(removed);
+            }
+            (IntegrityFix::DropDanglingReferences, Some(elements)) => {
+                applied.extend(scene_schema::sanitize_elements(elements))
+            }
+            (IntegrityFix::SyncBindings, Some(elements)) => applied.extend(sync_bindings(elements)),
+            (IntegrityFix::RemoveOrphanedFiles, _) => applied.extend(remove_orphaned_files(&mut scene)),
+            (_, None) => return Err(AppError::InvalidInput("Scene has no elements array".to_string())),
+        }
+    }
+    if applied.is_empty() {
+        return Ok((content, applied));
+    }
+    let content = serde_json::to_string_pretty(&scene)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+    Ok((content, applied))
+}
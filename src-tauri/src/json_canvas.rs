@@ -0,0 +1,476 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::render;
+
+/// Obsidian's preset colors "1" to "6" (red, orange, yellow, green, cyan,
+/// purple) as Excalidraw stroke colors
+const PRESET_COLORS: [&str; 6] = ["#e03131", "#e8590c", "#f08c00", "#2f9e44", "#0c8599", "#6741d9"];
+const DEFAULT_COLOR: &str = "#1e1e1e";
+const FONT_SIZE: f64 = 16.0;
+const LINE_HEIGHT: f64 = 1.25;
+/// Space between a card's border and its text
+const PADDING: f64 = 10.0;
+
+/// A JSON Canvas document (jsoncanvas.org), as written by Obsidian
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Canvas {
+    #[serde(default)]
+    pub nodes: Vec<CanvasNode>,
+    #[serde(default)]
+    pub edges: Vec<CanvasEdge>,
+}
+
+/// Canvas positions are integers; other writers may not round them
+fn integer<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+    f64::deserialize(deserializer).map(|value| value.round() as i64)
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CanvasNode {
+    pub id: String,
+    /// `text`, `file`, `link` or `group`
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(deserialize_with = "integer")]
+    pub x: i64,
+    #[serde(deserialize_with = "integer")]
+    pub y: i64,
+    #[serde(deserialize_with = "integer")]
+    pub width: i64,
+    #[serde(deserialize_with = "integer")]
+    pub height: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subpath: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Group title
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CanvasEdge {
+    pub id: String,
+    pub from_node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_side: Option<String>,
+    /// `none` (the default) or `arrow`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_end: Option<String>,
+    pub to_node: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_side: Option<String>,
+    /// `arrow` (the default) or `none`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_end: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// Answer of `import_json_canvas` and `export_json_canvas`
+#[derive(Debug, Serialize, Clone)]
+pub struct CanvasReport {
+    pub output_path: String,
+    pub nodes: usize,
+    pub edges: usize,
+    /// Nodes, edges or elements the other format has no place for
+    pub skipped: usize,
+}
+
+fn canvas_to_stroke(color: Option<&str>) -> &str {
+    match color {
+        Some(preset @ ("1" | "2" | "3" | "4" | "5" | "6")) => {
+            PRESET_COLORS[preset.parse::<usize>().unwrap_or(1) - 1]
+        }
+        Some(hex) if hex.starts_with('#') => hex,
+        _ => DEFAULT_COLOR,
+    }
+}
+
+fn stroke_to_canvas(color: Option<&str>) -> Option<String> {
+    let color = color?;
+    if color.eq_ignore_ascii_case(DEFAULT_COLOR) || color.eq_ignore_ascii_case("#000000") || !color.starts_with('#') {
+        return None;
+    }
+    match PRESET_COLORS.iter().position(|preset| preset.eq_ignore_ascii_case(color)) {
+        Some(i) => Some((i + 1).to_string()),
+        None => Some(color.to_string()),
+    }
+}
+
+/// Breaks lines at spaces to fit `width`, like Excalidraw does for text in
+/// containers; character widths are estimated
+fn wrap(text: &str, width: f64) -> String {
+    let max_chars = ((width / (FONT_SIZE * 0.6)).floor() as usize).max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+                lines.push(std::mem::take(&mut line));
+            } else if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(word);
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+fn text_element(id: &str, container: &str, text: &str, x: f64, y: f64, width: f64, centered: bool) -> Value {
+    let wrapped = wrap(text, width);
+    let height = wrapped.lines().count().max(1) as f64 * FONT_SIZE * LINE_HEIGHT;
+    json!({
+        "id": id,
+        "type": "text",
+        "x": x,
+        "y": y,
+        "width": width,
+        "height": height,
+        "text": wrapped,
+        "originalText": text,
+        "fontSize": FONT_SIZE,
+        "textAlign": if centered { "center" } else { "left" },
+        "verticalAlign": if centered { "middle" } else { "top" },
+        "containerId": container,
+        "autoResize": true,
+        "lineHeight": LINE_HEIGHT,
+    })
+}
+
+struct Rect {
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl Rect {
+    fn of(node: &CanvasNode) -> Self {
+        Rect {
+            x: node.x as f64,
+            y: node.y as f64,
+            width: node.width as f64,
+            height: node.height as f64,
+        }
+    }
+
+    fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+
+    fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.x + other.width <= self.x + self.width
+            && other.y + other.height <= self.y + self.height
+    }
+
+    /// Middle of `side`, or of the side facing `toward` when unset
+    fn anchor(&self, side: Option<&str>, toward: (f64, f64)) -> (f64, f64) {
+        let (cx, cy) = self.center();
+        let side = side.unwrap_or_else(|| {
+            let (dx, dy) = (toward.0 - cx, toward.1 - cy);
+            match (dx.abs() > dy.abs(), dx > 0.0, dy > 0.0) {
+                (true, true, _) => "right",
+                (true, false, _) => "left",
+                (false, _, true) => "bottom",
+                (false, _, false) => "top",
+            }
+        });
+        match side {
+            "top" => (cx, self.y),
+            "bottom" => (cx, self.y + self.height),
+            "left" => (self.x, cy),
+            _ => (self.x + self.width, cy),
+        }
+    }
+
+    /// The side whose middle is nearest to `point`
+    fn nearest_side(&self, point: (f64, f64)) -> &'static str {
+        ["top", "right", "bottom", "left"]
+            .into_iter()
+            .min_by(|a, b| {
+                let distance = |side| {
+                    let (x, y) = self.anchor(Some(side), point);
+                    (x - point.0).powi(2) + (y - point.1).powi(2)
+                };
+                distance(*a).total_cmp(&distance(*b))
+            })
+            .unwrap_or("right")
+    }
+}
+
+/// Turns a canvas into scene elements: cards become rounded rectangles with
+/// their text, file and link cards keep their target as the element's link,
+/// groups become frames and edges become bound arrows. Style defaults are
+/// left to scene repair.
+pub fn to_elements(canvas: &Canvas) -> (Vec<Value>, CanvasReport) {
+    let mut report = CanvasReport {
+        output_path: String::new(),
+        nodes: 0,
+        edges: 0,
+        skipped: 0,
+    };
+    let cards: Vec<&CanvasNode> = canvas
+        .nodes
+        .iter()
+        .filter(|n| matches!(n.kind.as_str(), "text" | "file" | "link"))
+        .collect();
+    let groups: Vec<&CanvasNode> = canvas.nodes.iter().filter(|n| n.kind == "group").collect();
+    report.skipped += canvas.nodes.len() - cards.len() - groups.len();
+    let rects: HashMap<&str, Rect> = cards.iter().map(|n| (n.id.as_str(), Rect::of(n))).collect();
+
+    // Arrows and labels bound to each card
+    let edges: Vec<&CanvasEdge> = canvas
+        .edges
+        .iter()
+        .filter(|e| rects.contains_key(e.from_node.as_str()) && rects.contains_key(e.to_node.as_str()))
+        .collect();
+    report.skipped += canvas.edges.len() - edges.len();
+    let mut bound: HashMap<&str, Vec<Value>> = HashMap::new();
+    for edge in &edges {
+        for node in [&edge.from_node, &edge.to_node] {
+            bound
+                .entry(node.as_str())
+                .or_default()
+                .push(json!({"id": edge.id, "type": "arrow"}));
+        }
+    }
+
+    let mut elements = Vec::new();
+    for card in &cards {
+        let rect = &rects[card.id.as_str()];
+        // Cards can't be in frames inside frames; the smallest group wins
+        let frame = groups
+            .iter()
+            .filter(|g| Rect::of(g).contains(rect))
+            .min_by_key(|g| g.width * g.height)
+            .map(|g| g.id.as_str());
+        let (label, link) = match card.kind.as_str() {
+            "file" => {
+                let file = card.file.clone().unwrap_or_default();
+                let target = format!("{}{}", file, card.subpath.as_deref().unwrap_or_default());
+                (target.clone(), Some(target))
+            }
+            "link" => (card.url.clone().unwrap_or_default(), card.url.clone()),
+            _ => (card.text.clone().unwrap_or_default(), None),
+        };
+        let text_id = format!("{}-text", card.id);
+        let mut bound_elements = bound.remove(card.id.as_str()).unwrap_or_default();
+        if !label.is_empty() {
+            bound_elements.push(json!({"id": text_id, "type": "text"}));
+        }
+        elements.push(json!({
+            "id": card.id,
+            "type": "rectangle",
+            "x": rect.x,
+            "y": rect.y,
+            "width": rect.width,
+            "height": rect.height,
+            "strokeColor": canvas_to_stroke(card.color.as_deref()),
+            "roundness": {"type": 3},
+            "frameId": frame,
+            "boundElements": bound_elements,
+            "link": link,
+        }));
+        if !label.is_empty() {
+            let mut text = text_element(
+                &text_id,
+                &card.id,
+                &label,
+                rect.x + PADDING,
+                rect.y + PADDING,
+                (rect.width - PADDING * 2.0).max(1.0),
+                false,
+            );
+            text["frameId"] = json!(frame);
+            elements.push(text);
+        }
+        report.nodes += 1;
+    }
+
+    for edge in &edges {
+        let (from, to) = (&rects[edge.from_node.as_str()], &rects[edge.to_node.as_str()]);
+        let start = from.anchor(edge.from_side.as_deref(), to.center());
+        let end = to.anchor(edge.to_side.as_deref(), from.center());
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let head = |end: Option<&str>, default: bool| {
+            if end.map_or(default, |e| e == "arrow") {
+                json!("arrow")
+            } else {
+                Value::Null
+            }
+        };
+        let label = edge.label.as_deref().filter(|l| !l.trim().is_empty());
+        let label_id = format!("{}-label", edge.id);
+        elements.push(json!({
+            "id": edge.id,
+            "type": "arrow",
+            "x": start.0,
+            "y": start.1,
+            "width": dx.abs(),
+            "height": dy.abs(),
+            "points": [[0.0, 0.0], [dx, dy]],
+            "strokeColor": canvas_to_stroke(edge.color.as_deref()),
+            "roundness": {"type": 2},
+            "startBinding": {"elementId": edge.from_node, "focus": 0, "gap": 1},
+            "endBinding": {"elementId": edge.to_node, "focus": 0, "gap": 1},
+            "startArrowhead": head(edge.from_end.as_deref(), false),
+            "endArrowhead": head(edge.to_end.as_deref(), true),
+            "boundElements": if label.is_some() { json!([{"id": label_id, "type": "text"}]) } else { json!([]) },
+        }));
+        if let Some(label) = label {
+            let width = (label.chars().count() as f64 * FONT_SIZE * 0.6).max(1.0);
+            let (mx, my) = (start.0 + dx / 2.0, start.1 + dy / 2.0);
+            elements.push(text_element(&label_id, &edge.id, label, mx - width / 2.0, my - FONT_SIZE, width, true));
+        }
+        report.edges += 1;
+    }
+
+    // Frames after their children, as Excalidraw orders them
+    for group in &groups {
+        let rect = Rect::of(group);
+        elements.push(json!({
+            "id": group.id,
+            "type": "frame",
+            "x": rect.x,
+            "y": rect.y,
+            "width": rect.width,
+            "height": rect.height,
+            "name": group.label,
+        }));
+    }
+    (elements, report)
+}
+
+/// Turns a scene into a canvas: shapes become cards with their label,
+/// elements with a link become link or file cards, loose text becomes text
+/// cards, frames become groups and arrows bound at both ends become edges
+pub fn from_scene(scene: &Value) -> (Canvas, CanvasReport) {
+    let elements = render::visible_elements(scene);
+    let str_field = |element: &Value, key: &str| element.get(key).and_then(|v| v.as_str()).map(str::to_string);
+    let mut report = CanvasReport {
+        output_path: String::new(),
+        nodes: 0,
+        edges: 0,
+        skipped: 0,
+    };
+
+    let mut labels: HashMap<String, String> = HashMap::new();
+    for element in &elements {
+        if let (Some("text"), Some(container)) = (
+            element.get("type").and_then(|t| t.as_str()),
+            str_field(element, "containerId"),
+        ) {
+            let text = str_field(element, "originalText").or_else(|| str_field(element, "text"));
+            labels.insert(container, text.unwrap_or_default());
+        }
+    }
+
+    let mut canvas = Canvas::default();
+    let mut groups = Vec::new();
+    let mut arrows = Vec::new();
+    for element in &elements {
+        let id = str_field(element, "id").unwrap_or_default();
+        let bounds = render::element_bounds(element);
+        let mut node = CanvasNode {
+            id: id.clone(),
+            kind: "text".to_string(),
+            x: bounds.min_x.round() as i64,
+            y: bounds.min_y.round() as i64,
+            width: (bounds.max_x - bounds.min_x).round() as i64,
+            height: (bounds.max_y - bounds.min_y).round() as i64,
+            color: stroke_to_canvas(element.get("strokeColor").and_then(|c| c.as_str())),
+            text: None,
+            file: None,
+            subpath: None,
+            url: None,
+            label: None,
+        };
+        match element.get("type").and_then(|t| t.as_str()).unwrap_or_default() {
+            "rectangle" | "ellipse" | "diamond" => {
+                match str_field(element, "link").filter(|l| !l.is_empty()) {
+                    Some(url) if url.contains("://") || url.starts_with("mailto:") => {
+                        node.kind = "link".to_string();
+                        node.url = Some(url);
+                    }
+                    Some(file) => {
+                        let (file, subpath) = match file.split_once('#') {
+                            Some((file, subpath)) => (file.to_string(), Some(format!("#{}", subpath))),
+                            None => (file, None),
+                        };
+                        node.kind = "file".to_string();
+                        node.file = Some(file);
+                        node.subpath = subpath;
+                    }
+                    None => node.text = Some(labels.get(&id).cloned().unwrap_or_default()),
+                }
+                canvas.nodes.push(node);
+            }
+            "text" if str_field(element, "containerId").is_none() => {
+                node.text = str_field(element, "originalText").or_else(|| str_field(element, "text"));
+                canvas.nodes.push(node);
+            }
+            "text" => {}
+            "frame" | "magicframe" => {
+                node.kind = "group".to_string();
+                node.color = None;
+                node.label = str_field(element, "name");
+                groups.push(node);
+            }
+            "arrow" => arrows.push(*element),
+            _ => report.skipped += 1,
+        }
+    }
+
+    let rects: HashMap<String, Rect> = canvas.nodes.iter().map(|n| (n.id.clone(), Rect::of(n))).collect();
+    for arrow in arrows {
+        let endpoint = |key: &str| {
+            let id = arrow.get(key)?.get("elementId")?.as_str()?;
+            rects.contains_key(id).then(|| id.to_string())
+        };
+        let (Some(from), Some(to)) = (endpoint("startBinding"), endpoint("endBinding")) else {
+            report.skipped += 1;
+            continue;
+        };
+        let points = arrow.get("points").and_then(|p| p.as_array()).cloned().unwrap_or_default();
+        let point = |p: Option<&Value>| {
+            let p = p.and_then(|p| p.as_array());
+            let coordinate = |i: usize| p.and_then(|p| p.get(i)).and_then(|v| v.as_f64()).unwrap_or(0.0);
+            let origin = |key| arrow.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0);
+            (origin("x") + coordinate(0), origin("y") + coordinate(1))
+        };
+        let head = |key: &str| arrow.get(key).is_some_and(|h| !h.is_null());
+        canvas.edges.push(CanvasEdge {
+            id: str_field(arrow, "id").unwrap_or_default(),
+            from_side: Some(rects[&from].nearest_side(point(points.first())).to_string()),
+            to_side: Some(rects[&to].nearest_side(point(points.last())).to_string()),
+            from_node: from,
+            from_end: head("startArrowhead").then(|| "arrow".to_string()),
+            to_node: to,
+            to_end: (!head("endArrowhead")).then(|| "none".to_string()),
+            color: stroke_to_canvas(arrow.get("strokeColor").and_then(|c| c.as_str())),
+            label: str_field(arrow, "id").and_then(|id| labels.get(&id).cloned()),
+        });
+    }
+
+    report.nodes = canvas.nodes.len();
+    report.edges = canvas.edges.len();
+    // Groups first, so Obsidian draws them below their cards
+    groups.append(&mut canvas.nodes);
+    canvas.nodes = groups;
+    (canvas, report)
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+use crate::security;
+
+/// A file or folder named on the command line
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LaunchTarget {
+    pub path: String,
+    pub is_directory: bool,
+}
+
+impl LaunchTarget {
+    /// The folder to open: the target itself or the drawing's parent
+    pub fn directory(&self) -> PathBuf {
+        let path = Path::new(&self.path);
+        if self.is_directory {
+            path.to_path_buf()
+        } else {
+            path.parent().unwrap_or(path).to_path_buf()
+        }
+    }
+}
+
+/// Existing drawings and folders among `args` (without the program name),
+/// resolved against the `cwd` of the launching process. Flags and deep-link
+/// URLs are left to their own handlers.
+pub fn parse_args(args: &[String], cwd: &Path) -> Vec<LaunchTarget> {
+    args.iter()
+        .filter(|arg| !arg.starts_with('-') && !arg.contains("://"))
+        .filter_map(|arg| {
+            let path = PathBuf::from(arg);
+            let path = if path.is_absolute() { path } else { cwd.join(path) };
+            // Canonical first, so relative `..` segments typed by the user pass
+            let path = path.canonicalize().ok()?;
+            let path = match security::validate_path(&path, None) {
+                Ok(path) => path,
+                Err(e) => {
+                    tracing::warn!("Ignoring launch argument {}: {}", arg, e);
+                    return None;
+                }
+            };
+            if path.is_dir() {
+                Some(LaunchTarget {
+                    path: paths::normalize(&path),
+                    is_directory: true,
+                })
+            } else {
+                security::validate_excalidraw_file(&path).ok()?;
+                Some(LaunchTarget {
+                    path: paths::normalize(&path),
+                    is_directory: false,
+                })
+            }
+        })
+        .collect()
+}
+
+/// Drawings and folders among the URLs the OS passes when files are opened
+/// with the app (macOS `Opened` events)
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub fn from_opened_urls(urls: &[tauri::Url]) -> Vec<LaunchTarget> {
+    let args: Vec<String> = urls
+        .iter()
+        .filter(|url| url.scheme() == "file")
+        .filter_map(|url| url.to_file_path().ok())
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    // File URLs are always absolute
+    parse_args(&args, Path::new(""))
+}
@@ -0,0 +1,912 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::error::AppError;
+use crate::render::{self, Bounds};
+
+/// Gap between an arrow tip and the shape it is bound to
+const ARROW_GAP: f64 = 4.0;
+
+/// Names match the frontend's elkjs algorithm ids; the aliases are the plain names
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutAlgorithm {
+    Layered,
+    #[serde(alias = "tree")]
+    Mrtree,
+    Grid,
+    #[serde(alias = "force")]
+    Stress,
+    #[serde(alias = "circle")]
+    Box,
+}
+
+impl LayoutAlgorithm {
+    pub fn parse(name: &str) -> Result<Self, AppError> {
+        serde_json::from_value(Value::String(name.trim().to_ascii_lowercase()))
+            .map_err(|_| AppError::InvalidInput(format!("Unknown layout algorithm: {}", name)))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Direction {
+    Up,
+    #[default]
+    Down,
+    Left,
+    Right,
+}
+
+impl Direction {
+    fn is_vertical(self) -> bool {
+        matches!(self, Direction::Up | Direction::Down)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LayoutOptions {
+    /// Flow direction of layered and tree layouts
+    pub direction: Direction,
+    /// Gap between neighbouring shapes
+    pub spacing_x: f64,
+    /// Gap between layers (layered, tree) or rows (grid)
+    pub spacing_y: f64,
+    /// Restricts the layout to these elements, e.g. the selection; all shapes when empty
+    pub element_ids: Vec<String>,
+    /// Grid column count; defaults to a square-ish grid
+    pub columns: Option<usize>,
+    /// Force-directed iterations
+    pub iterations: usize,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            direction: Direction::Down,
+            spacing_x: 60.0,
+            spacing_y: 80.0,
+            element_ids: Vec::new(),
+            columns: None,
+            iterations: 300,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LayoutResult {
+    /// The updated scene, in the same shape (scene object or element array) as the input
+    pub content: String,
+    pub moved: usize,
+    pub arrows_rerouted: usize,
+}
+
+/// A unit that is positioned as a whole: a single shape or an outermost group
+struct Node {
+    members: Vec<usize>,
+    bounds: Bounds,
+}
+
+impl Node {
+    /// (size along the flow direction, size across it)
+    fn extents(&self, direction: Direction) -> (f64, f64) {
+        if direction.is_vertical() {
+            (self.bounds.height(), self.bounds.width())
+        } else {
+            (self.bounds.width(), self.bounds.height())
+        }
+    }
+}
+
+fn str_field<'a>(element: &'a Value, key: &str) -> Option<&'a str> {
+    element.get(key).and_then(|v| v.as_str())
+}
+
+fn binding_id<'a>(element: &'a Value, key: &str) -> Option<&'a str> {
+    element.get(key).and_then(|b| b.get("elementId")).and_then(|id| id.as_str())
+}
+
+fn is_connector(element: &Value) -> bool {
+    matches!(str_field(element, "type"), Some("arrow") | Some("line"))
+}
+
+fn is_layout_shape(element: &Value) -> bool {
+    let deleted = element.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false);
+    let bound_text = element.get("containerId").is_some_and(|c| c.is_string());
+    let kind = str_field(element, "type").unwrap_or("");
+    !deleted && !bound_text && !is_connector(element) && !matches!(kind, "frame" | "magicframe" | "")
+}
+
+fn build_graph(elements: &[Value], options: &LayoutOptions) -> (Vec<Node>, Vec<(usize, usize)>) {
+    let selection: HashSet<&str> = options.element_ids.iter().map(|s| s.as_str()).collect();
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut node_by_key: HashMap<String, usize> = HashMap::new();
+    let mut node_by_element: HashMap<&str, usize> = HashMap::new();
+
+    for (index, element) in elements.iter().enumerate() {
+        let id = str_field(element, "id").unwrap_or("");
+        if !is_layout_shape(element) || (!selection.is_empty() && !selection.contains(id)) {
+            continue;
+        }
+
+        // Groups move as one; the last group id is the outermost group
+        let key = element
+            .get("groupIds")
+            .and_then(|g| g.as_array())
+            .and_then(|g| g.last())
+            .and_then(|g| g.as_str())
+            .map(|g| format!("group:{}", g))
+            .unwrap_or_else(|| format!("element:{}", id));
+        let bounds = render::element_bounds(element);
+
+        let node = *node_by_key.entry(key).or_insert_with(|| {
+            nodes.push(Node {
+                members: Vec::new(),
+                bounds,
+            });
+            nodes.len() - 1
+        });
+        nodes[node].members.push(index);
+        nodes[node].bounds = nodes[node].bounds.union(&bounds);
+        node_by_element.insert(id, node);
+    }
+
+    let mut edges = Vec::new();
+    let mut seen = HashSet::new();
+    for element in elements.iter().filter(|e| is_connector(e)) {
+        let (Some(start), Some(end)) = (
+            binding_id(element, "startBinding").and_then(|id| node_by_element.get(id)),
+            binding_id(element, "endBinding").and_then(|id| node_by_element.get(id)),
+        ) else {
+            continue;
+        };
+        if start != end && seen.insert((*start, *end)) {
+            edges.push((*start, *end));
+        }
+    }
+
+    (nodes, edges)
+}
+
+/// Reverses edges that close a cycle (found by DFS) so the graph becomes acyclic
+fn remove_cycles(n: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut successors = vec![Vec::new(); n];
+    for &(s, t) in edges {
+        successors[s].push(t);
+    }
+
+    // 0 = unvisited, 1 = on the DFS stack, 2 = done
+    let mut state = vec![0u8; n];
+    let mut back_edges = HashSet::new();
+    for root in 0..n {
+        if state[root] != 0 {
+            continue;
+        }
+        let mut stack = vec![(root, 0usize)];
+        state[root] = 1;
+        while let Some((node, next)) = stack.pop() {
+            if let Some(&succ) = successors[node].get(next) {
+                stack.push((node, next + 1));
+                match state[succ] {
+                    0 => {
+                        state[succ] = 1;
+                        stack.push((succ, 0));
+                    }
+                    1 => {
+                        back_edges.insert((node, succ));
+                    }
+                    _ => {}
+                }
+            } else {
+                state[node] = 2;
+            }
+        }
+    }
+
+    edges
+        .iter()
+        .map(|&(s, t)| if back_edges.contains(&(s, t)) { (t, s) } else { (s, t) })
+        .collect()
+}
+
+/// Longest-path layer index per node of an acyclic graph
+fn assign_layers(n: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut successors = vec![Vec::new(); n];
+    let mut in_degree = vec![0usize; n];
+    for &(s, t) in edges {
+        successors[s].push(t);
+        in_degree[t] += 1;
+    }
+
+    let mut layer = vec![0usize; n];
+    let mut queue: VecDeque<usize> = (0..n).filter(|&v| in_degree[v] == 0).collect();
+    while let Some(node) = queue.pop_front() {
+        for &succ in &successors[node] {
+            layer[succ] = layer[succ].max(layer[node] + 1);
+            in_degree[succ] -= 1;
+            if in_degree[succ] == 0 {
+                queue.push_back(succ);
+            }
+        }
+    }
+    layer
+}
+
+/// Stacks layers along the flow direction and centers each layer across it;
+/// returns the top-left corner of every node
+fn place_layers(nodes: &[Node], layers: &[Vec<usize>], options: &LayoutOptions) -> Vec<(f64, f64)> {
+    let direction = options.direction;
+    let layer_extent = |layer: &Vec<usize>| -> f64 {
+        layer.iter().map(|&v| nodes[v].extents(direction).1).sum::<f64>()
+            + options.spacing_x * layer.len().saturating_sub(1) as f64
+    };
+    let widest = layers.iter().map(layer_extent).fold(0.0, f64::max);
+    let thickness: Vec<f64> = layers
+        .iter()
+        .map(|layer| layer.iter().map(|&v| nodes[v].extents(direction).0).fold(0.0, f64::max))
+        .collect();
+    let total_main = thickness.iter().sum::<f64>() + options.spacing_y * layers.len().saturating_sub(1) as f64;
+
+    let mut positions = vec![(0.0, 0.0); nodes.len()];
+    let mut main = 0.0;
+    for (layer, thickness) in layers.iter().zip(&thickness) {
+        let mut cross = (widest - layer_extent(layer)) / 2.0;
+        for &v in layer {
+            let (main_size, cross_size) = nodes[v].extents(direction);
+            let node_main = main + (thickness - main_size) / 2.0;
+            positions[v] = to_xy(direction, node_main, cross, main_size, total_main);
+            cross += cross_size + options.spacing_x;
+        }
+        main += thickness + options.spacing_y;
+    }
+    positions
+}
+
+/// Maps flow coordinates to scene coordinates for the given direction
+fn to_xy(direction: Direction, main: f64, cross: f64, main_size: f64, total_main: f64) -> (f64, f64) {
+    match direction {
+        Direction::Down => (cross, main),
+        Direction::Up => (cross, total_main - main - main_size),
+        Direction::Right => (main, cross),
+        Direction::Left => (total_main - main - main_size, cross),
+    }
+}
+
+fn layered(nodes: &[Node], edges: &[(usize, usize)], options: &LayoutOptions) -> Vec<(f64, f64)> {
+    let n = nodes.len();
+    let edges = remove_cycles(n, edges);
+    let layer_of = assign_layers(n, &edges);
+    let layer_count = layer_of.iter().max().map(|l| l + 1).unwrap_or(0);
+
+    // Start from the current reading order across the flow direction
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); layer_count];
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| {
+        let key = |v: usize| {
+            if options.direction.is_vertical() {
+                nodes[v].bounds.min_x
+            } else {
+                nodes[v].bounds.min_y
+            }
+        };
+        key(a).partial_cmp(&key(b)).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    for v in order {
+        layers[layer_of[v]].push(v);
+    }
+
+    // Barycenter heuristic: alternate downward and upward sweeps to reduce crossings
+    let mut predecessors = vec![Vec::new(); n];
+    let mut successors = vec![Vec::new(); n];
+    for &(s, t) in &edges {
+        predecessors[t].push(s);
+        successors[s].push(t);
+    }
+    for sweep in 0..8 {
+        let downward = sweep % 2 == 0;
+        let mut position = vec![0.0; n];
+        for layer in &layers {
+            for (i, &v) in layer.iter().enumerate() {
+                position[v] = i as f64;
+            }
+        }
+
+        let range: Vec<usize> = if downward {
+            (1..layer_count).collect()
+        } else {
+            (0..layer_count.saturating_sub(1)).rev().collect()
+        };
+        for l in range {
+            let neighbours = if downward { &predecessors } else { &successors };
+            let mut keyed: Vec<(f64, usize)> = layers[l]
+                .iter()
+                .enumerate()
+                .map(|(i, &v)| {
+                    let adjacent = &neighbours[v];
+                    let key = if adjacent.is_empty() {
+                        i as f64
+                    } else {
+                        adjacent.iter().map(|&u| position[u]).sum::<f64>() / adjacent.len() as f64
+                    };
+                    (key, v)
+                })
+                .collect();
+            keyed.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+            layers[l] = keyed.into_iter().map(|(_, v)| v).collect();
+            for (i, &v) in layers[l].iter().enumerate() {
+                position[v] = i as f64;
+            }
+        }
+    }
+
+    place_layers(nodes, &layers, options)
+}
+
+fn tree(nodes: &[Node], edges: &[(usize, usize)], options: &LayoutOptions) -> Vec<(f64, f64)> {
+    let n = nodes.len();
+    let direction = options.direction;
+    let edges = remove_cycles(n, edges);
+
+    let mut successors = vec![Vec::new(); n];
+    let mut has_parent = vec![false; n];
+    for &(s, t) in &edges {
+        successors[s].push(t);
+        has_parent[t] = true;
+    }
+
+    // Spanning forest: BFS from every root; nodes with several parents keep the first
+    let mut children = vec![Vec::new(); n];
+    let mut depth = vec![0usize; n];
+    let mut visited = vec![false; n];
+    let mut roots = Vec::new();
+    let candidates = (0..n).filter(|&v| !has_parent[v]).chain(0..n);
+    for root in candidates {
+        if visited[root] {
+            continue;
+        }
+        visited[root] = true;
+        roots.push(root);
+        let mut queue = VecDeque::from([root]);
+        while let Some(v) = queue.pop_front() {
+            for &c in &successors[v] {
+                if !visited[c] {
+                    visited[c] = true;
+                    depth[c] = depth[v] + 1;
+                    children[v].push(c);
+                    queue.push_back(c);
+                }
+            }
+        }
+    }
+
+    // Subtree extents across the flow direction, children before parents
+    let mut post_order = Vec::with_capacity(n);
+    for &root in &roots {
+        let mut stack = vec![(root, false)];
+        while let Some((v, expanded)) = stack.pop() {
+            if expanded {
+                post_order.push(v);
+            } else {
+                stack.push((v, true));
+                stack.extend(children[v].iter().map(|&c| (c, false)));
+            }
+        }
+    }
+    let mut subtree = vec![0.0; n];
+    for &v in &post_order {
+        let children_extent = children[v].iter().map(|&c| subtree[c]).sum::<f64>()
+            + options.spacing_x * children[v].len().saturating_sub(1) as f64;
+        subtree[v] = nodes[v].extents(direction).1.max(children_extent);
+    }
+
+    let depth_count = depth.iter().max().map(|d| d + 1).unwrap_or(0);
+    let mut thickness = vec![0.0f64; depth_count];
+    for v in 0..n {
+        thickness[depth[v]] = thickness[depth[v]].max(nodes[v].extents(direction).0);
+    }
+    let mut depth_offset = vec![0.0; depth_count];
+    for d in 1..depth_count {
+        depth_offset[d] = depth_offset[d - 1] + thickness[d - 1] + options.spacing_y;
+    }
+    let total_main = depth_offset.last().copied().unwrap_or(0.0) + thickness.last().copied().unwrap_or(0.0);
+
+    // Parents are centered over their children
+    let mut positions = vec![(0.0, 0.0); n];
+    let mut left = 0.0;
+    for &root in &roots {
+        let mut stack = vec![(root, left)];
+        while let Some((v, start)) = stack.pop() {
+            let (main_size, cross_size) = nodes[v].extents(direction);
+            let cross = start + (subtree[v] - cross_size) / 2.0;
+            let main = depth_offset[depth[v]] + (thickness[depth[v]] - main_size) / 2.0;
+            positions[v] = to_xy(direction, main, cross, main_size, total_main);
+
+            let children_extent = children[v].iter().map(|&c| subtree[c]).sum::<f64>()
+                + options.spacing_x * children[v].len().saturating_sub(1) as f64;
+            let mut child_start = start + (subtree[v] - children_extent) / 2.0;
+            for &c in &children[v] {
+                stack.push((c, child_start));
+                child_start += subtree[c] + options.spacing_x;
+            }
+        }
+        left += subtree[root] + options.spacing_x;
+    }
+    positions
+}
+
+fn grid(nodes: &[Node], options: &LayoutOptions) -> Vec<(f64, f64)> {
+    let n = nodes.len();
+    let columns = options
+        .columns
+        .unwrap_or_else(|| (n as f64).sqrt().ceil() as usize)
+        .max(1);
+    let cell_width = nodes.iter().map(|node| node.bounds.width()).fold(0.0, f64::max);
+    let cell_height = nodes.iter().map(|node| node.bounds.height()).fold(0.0, f64::max);
+
+    // Keep the current reading order (top-to-bottom, left-to-right)
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&nodes[a].bounds, &nodes[b].bounds);
+        (a.min_y, a.min_x)
+            .partial_cmp(&(b.min_y, b.min_x))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut positions = vec![(0.0, 0.0); n];
+    for (slot, &v) in order.iter().enumerate() {
+        let (row, column) = (slot / columns, slot % columns);
+        positions[v] = (
+            column as f64 * (cell_width + options.spacing_x) + (cell_width - nodes[v].bounds.width()) / 2.0,
+            row as f64 * (cell_height + options.spacing_y) + (cell_height - nodes[v].bounds.height()) / 2.0,
+        );
+    }
+    positions
+}
+
+fn center(bounds: &Bounds) -> (f64, f64) {
+    (
+        (bounds.min_x + bounds.max_x) / 2.0,
+        (bounds.min_y + bounds.max_y) / 2.0,
+    )
+}
+
+/// Converts node centers into top-left positions
+fn centers_to_positions(nodes: &[Node], centers: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    nodes
+        .iter()
+        .zip(centers)
+        .map(|(node, (cx, cy))| (cx - node.bounds.width() / 2.0, cy - node.bounds.height() / 2.0))
+        .collect()
+}
+
+/// Fruchterman-Reingold force-directed placement followed by overlap removal
+fn stress(nodes: &[Node], edges: &[(usize, usize)], options: &LayoutOptions) -> Vec<(f64, f64)> {
+    let n = nodes.len();
+    let average_size = nodes
+        .iter()
+        .map(|node| node.bounds.width().max(node.bounds.height()))
+        .sum::<f64>()
+        / n.max(1) as f64;
+    let k = average_size + options.spacing_x;
+
+    // Start from the current positions; spread coincident nodes on a golden-angle spiral
+    let mut centers: Vec<(f64, f64)> = nodes.iter().map(|node| center(&node.bounds)).collect();
+    let mut seen = HashSet::new();
+    for (i, c) in centers.iter_mut().enumerate() {
+        if !seen.insert(((c.0 / 1.0) as i64, (c.1 / 1.0) as i64)) {
+            let angle = i as f64 * 2.399_963;
+            let radius = k * (i as f64).sqrt();
+            *c = (c.0 + radius * angle.cos(), c.1 + radius * angle.sin());
+        }
+    }
+
+    // The all-pairs repulsion is quadratic, so big graphs get fewer iterations
+    let iterations = options
+        .iterations
+        .min((50_000_000 / (n * n).max(1)).max(30));
+    let mut temperature = k * (n as f64).sqrt();
+    let cooling = temperature / (iterations.max(1) as f64 + 1.0);
+
+    for _ in 0..iterations {
+        let mut displacement = vec![(0.0, 0.0); n];
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let (dx, dy) = (centers[i].0 - centers[j].0, centers[i].1 - centers[j].1);
+                let distance = (dx * dx + dy * dy).sqrt().max(0.01);
+                let force = k * k / distance;
+                let (fx, fy) = (dx / distance * force, dy / distance * force);
+                displacement[i].0 += fx;
+                displacement[i].1 += fy;
+                displacement[j].0 -= fx;
+                displacement[j].1 -= fy;
+            }
+        }
+        for &(s, t) in edges {
+            let (dx, dy) = (centers[s].0 - centers[t].0, centers[s].1 - centers[t].1);
+            let distance = (dx * dx + dy * dy).sqrt().max(0.01);
+            let force = distance * distance / k;
+            let (fx, fy) = (dx / distance * force, dy / distance * force);
+            displacement[s].0 -= fx;
+            displacement[s].1 -= fy;
+            displacement[t].0 += fx;
+            displacement[t].1 += fy;
+        }
+        for (c, (dx, dy)) in centers.iter_mut().zip(&displacement) {
+            let length = (dx * dx + dy * dy).sqrt().max(0.01);
+            let step = length.min(temperature);
+            c.0 += dx / length * step;
+            c.1 += dy / length * step;
+        }
+        temperature = (temperature - cooling).max(1.0);
+    }
+
+    // Push overlapping shapes apart along the axis of least overlap
+    let margin = options.spacing_x / 2.0;
+    for _ in 0..50 {
+        let mut moved = false;
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let overlap_x = (nodes[i].bounds.width() + nodes[j].bounds.width()) / 2.0 + margin
+                    - (centers[i].0 - centers[j].0).abs();
+                let overlap_y = (nodes[i].bounds.height() + nodes[j].bounds.height()) / 2.0 + margin
+                    - (centers[i].1 - centers[j].1).abs();
+                if overlap_x <= 0.0 || overlap_y <= 0.0 {
+                    continue;
+                }
+                moved = true;
+                if overlap_x < overlap_y {
+                    let shift = overlap_x / 2.0 * if centers[i].0 < centers[j].0 { -1.0 } else { 1.0 };
+                    centers[i].0 += shift;
+                    centers[j].0 -= shift;
+                } else {
+                    let shift = overlap_y / 2.0 * if centers[i].1 < centers[j].1 { -1.0 } else { 1.0 };
+                    centers[i].1 += shift;
+                    centers[j].1 -= shift;
+                }
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    centers_to_positions(nodes, &centers)
+}
+
+/// Places nodes on a circle, ordered by a BFS over the connections so that
+/// connected shapes end up next to each other
+fn circle(nodes: &[Node], edges: &[(usize, usize)], options: &LayoutOptions) -> Vec<(f64, f64)> {
+    let n = nodes.len();
+    let mut neighbours = vec![Vec::new(); n];
+    for &(s, t) in edges {
+        neighbours[s].push(t);
+        neighbours[t].push(s);
+    }
+
+    let mut by_degree: Vec<usize> = (0..n).collect();
+    by_degree.sort_by_key(|&v| std::cmp::Reverse(neighbours[v].len()));
+    let mut visited = vec![false; n];
+    let mut order = Vec::with_capacity(n);
+    for start in by_degree {
+        if visited[start] {
+            continue;
+        }
+        visited[start] = true;
+        let mut queue = VecDeque::from([start]);
+        while let Some(v) = queue.pop_front() {
+            order.push(v);
+            for &u in &neighbours[v] {
+                if !visited[u] {
+                    visited[u] = true;
+                    queue.push_back(u);
+                }
+            }
+        }
+    }
+
+    let sizes: Vec<f64> = nodes
+        .iter()
+        .map(|node| node.bounds.width().hypot(node.bounds.height()) + options.spacing_x)
+        .collect();
+    let circumference: f64 = order.iter().map(|&v| sizes[v]).sum();
+    let radius = (circumference / std::f64::consts::TAU).max(sizes.iter().cloned().fold(0.0, f64::max));
+
+    let mut centers = vec![(0.0, 0.0); n];
+    let mut travelled = 0.0;
+    for &v in &order {
+        // Angle of the middle of this node's arc, starting at 12 o'clock
+        let angle = (travelled + sizes[v] / 2.0) / circumference * std::f64::consts::TAU
+            - std::f64::consts::FRAC_PI_2;
+        centers[v] = (radius * angle.cos(), radius * angle.sin());
+        travelled += sizes[v];
+    }
+    centers_to_positions(nodes, &centers)
+}
+
+/// Point where the ray from the element's center toward `toward` leaves its outline
+fn outline_point(element: &Value, toward: (f64, f64), gap: f64) -> (f64, f64) {
+    let bounds = render::element_bounds(element);
+    let (cx, cy) = center(&bounds);
+    let (dx, dy) = (toward.0 - cx, toward.1 - cy);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        return (cx, cy);
+    }
+    let (ux, uy) = (dx / length, dy / length);
+    let (hw, hh) = (bounds.width() / 2.0, bounds.height() / 2.0);
+
+    let t = match str_field(element, "type") {
+        Some("ellipse") => 1.0 / ((ux / hw).powi(2) + (uy / hh).powi(2)).sqrt(),
+        Some("diamond") => 1.0 / (ux.abs() / hw + uy.abs() / hh),
+        _ => {
+            let tx = if ux == 0.0 { f64::INFINITY } else { hw / ux.abs() };
+            let ty = if uy == 0.0 { f64::INFINITY } else { hh / uy.abs() };
+            tx.min(ty)
+        }
+    };
+    let t = if t.is_finite() { t } else { 0.0 };
+    (cx + ux * (t + gap), cy + uy * (t + gap))
+}
+
+fn shift(element: &mut Value, dx: f64, dy: f64) {
+    let x = element.get("x").and_then(|v| v.as_f64()).unwrap_or(0.0);
+    let y = element.get("y").and_then(|v| v.as_f64()).unwrap_or(0.0);
+    element["x"] = (x + dx).into();
+    element["y"] = (y + dy).into();
+}
+
+fn bump_version(element: &mut Value) {
+    let version = element.get("version").and_then(|v| v.as_i64()).unwrap_or(1);
+    element["version"] = (version + 1).into();
+}
+
+/// Reroutes connectors touching moved shapes; returns how many were changed
+fn reroute_connectors(elements: &mut [Value], deltas: &HashMap<String, (f64, f64)>) -> usize {
+    let index_by_id: HashMap<String, usize> = elements
+        .iter()
+        .enumerate()
+        .filter_map(|(i, e)| Some((str_field(e, "id")?.to_string(), i)))
+        .collect();
+
+    let mut rerouted = 0;
+    for i in 0..elements.len() {
+        if !is_connector(&elements[i]) {
+            continue;
+        }
+        let start = binding_id(&elements[i], "startBinding").map(|s| s.to_string());
+        let end = binding_id(&elements[i], "endBinding").map(|s| s.to_string());
+        let moved = |id: &Option<String>| id.as_ref().and_then(|id| deltas.get(id)).copied();
+
+        match (&start, &end) {
+            // Both ends bound: draw a straight connector between the outlines
+            (Some(start_id), Some(end_id)) if moved(&start).is_some() || moved(&end).is_some() => {
+                let (Some(&s), Some(&e)) = (index_by_id.get(start_id), index_by_id.get(end_id)) else {
+                    continue;
+                };
+                let start_center = center(&render::element_bounds(&elements[s]));
+                let end_center = center(&render::element_bounds(&elements[e]));
+                let from = outline_point(&elements[s], end_center, ARROW_GAP);
+                let to = outline_point(&elements[e], start_center, ARROW_GAP);
+
+                let connector = &mut elements[i];
+                connector["x"] = from.0.into();
+                connector["y"] = from.1.into();
+                connector["points"] = serde_json::json!([[0.0, 0.0], [to.0 - from.0, to.1 - from.1]]);
+                connector["width"] = (to.0 - from.0).abs().into();
+                connector["height"] = (to.1 - from.1).abs().into();
+                bump_version(connector);
+                rerouted += 1;
+            }
+            // One end bound: move only that endpoint with its shape
+            _ => {
+                let start_delta = moved(&start);
+                let end_delta = moved(&end);
+                if start_delta.is_none() && end_delta.is_none() {
+                    continue;
+                }
+                let connector = &mut elements[i];
+                let Some(points) = connector.get_mut("points").and_then(|p| p.as_array_mut()) else {
+                    continue;
+                };
+                let last = points.len().saturating_sub(1);
+                let offset = |point: &mut Value, dx: f64, dy: f64| {
+                    let px = point.get(0).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    let py = point.get(1).and_then(|v| v.as_f64()).unwrap_or(0.0);
+                    *point = serde_json::json!([px + dx, py + dy]);
+                };
+                if let Some((dx, dy)) = start_delta {
+                    // The first point stays at the origin, so shift the rest back
+                    for point in points.iter_mut().skip(1) {
+                        offset(point, -dx, -dy);
+                    }
+                }
+                if let (Some((dx, dy)), Some(point)) = (end_delta, points.get_mut(last)) {
+                    offset(point, dx, dy);
+                }
+                if let Some((dx, dy)) = start_delta {
+                    shift(connector, dx, dy);
+                }
+                bump_version(connector);
+                rerouted += 1;
+            }
+        }
+    }
+    rerouted
+}
+
+/// Lays out the shapes of a scene (or of `options.element_ids`) with the given
+/// algorithm, moves bound text with its containers and reroutes connectors
+pub fn apply_layout(
+    scene_json: &str,
+    algorithm: LayoutAlgorithm,
+    options: &LayoutOptions,
+) -> Result<LayoutResult, AppError> {
+    let mut scene: Value =
+        serde_json::from_str(scene_json)?;
+    let elements = match &mut scene {
+        Value::Array(elements) => elements,
+        Value::Object(scene) => scene
+            .get_mut("elements")
+            .and_then(|e| e.as_array_mut())
+            .ok_or_else(|| AppError::InvalidInput("Scene has no elements array".to_string()))?,
+        _ => return Err(AppError::InvalidInput("Content is not a JSON object or array".to_string())),
+    };
+
+    let (nodes, edges) = build_graph(elements, options);
+    if nodes.len() < 2 {
+        return Err(AppError::InvalidInput("Select at least two shapes to lay out".to_string()));
+    }
+
+    let positions = match algorithm {
+        LayoutAlgorithm::Layered => layered(&nodes, &edges, options),
+        LayoutAlgorithm::Mrtree => tree(&nodes, &edges, options),
+        LayoutAlgorithm::Grid => grid(&nodes, options),
+        LayoutAlgorithm::Stress => stress(&nodes, &edges, options),
+        LayoutAlgorithm::Box => circle(&nodes, &edges, options),
+    };
+
+    // Keep the laid out block where the shapes were
+    let original = nodes
+        .iter()
+        .map(|node| node.bounds)
+        .reduce(|a, b| a.union(&b))
+        .expect("at least two nodes");
+    let min_x = positions.iter().map(|p| p.0).fold(f64::INFINITY, f64::min);
+    let min_y = positions.iter().map(|p| p.1).fold(f64::INFINITY, f64::min);
+
+    let mut deltas: HashMap<String, (f64, f64)> = HashMap::new();
+    let mut moved = 0;
+    for (node, (x, y)) in nodes.iter().zip(&positions) {
+        let dx = x - min_x + original.min_x - node.bounds.min_x;
+        let dy = y - min_y + original.min_y - node.bounds.min_y;
+        if dx.abs() < 0.01 && dy.abs() < 0.01 {
+            continue;
+        }
+        moved += 1;
+        for &member in &node.members {
+            if let Some(id) = str_field(&elements[member], "id") {
+                deltas.insert(id.to_string(), (dx, dy));
+            }
+        }
+    }
+
+    // Apply to shapes and to the text bound inside them
+    for element in elements.iter_mut() {
+        let key = if element.get("containerId").is_some_and(|c| c.is_string()) {
+            str_field(element, "containerId")
+        } else if is_connector(element) {
+            None
+        } else {
+            str_field(element, "id")
+        };
+        if let Some(&(dx, dy)) = key.and_then(|k| deltas.get(k)) {
+            shift(element, dx, dy);
+            bump_version(element);
+        }
+    }
+    let arrows_rerouted = reroute_connectors(elements, &deltas);
+
+    Ok(LayoutResult {
+        content: serde_json::to_string(&scene)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?,
+        moved,
+        arrows_rerouted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn shape(id: &str, x: f64, y: f64, width: f64, height: f64) -> Value {
+        json!({"id": id, "type": "rectangle", "x": x, "y": y, "width": width, "height": height, "version": 1})
+    }
+
+    fn arrow(id: &str, from: &str, to: &str) -> Value {
+        json!({
+            "id": id, "type": "arrow", "x": 0.0, "y": 0.0, "version": 1,
+            "points": [[0.0, 0.0], [10.0, 10.0]],
+            "startBinding": {"elementId": from},
+            "endBinding": {"elementId": to},
+        })
+    }
+
+    /// a points at b and c; b and c start out stacked far from a
+    fn sample_graph() -> Vec<Value> {
+        vec![
+            shape("a", 0.0, 0.0, 100.0, 50.0),
+            shape("b", 300.0, 300.0, 100.0, 50.0),
+            shape("c", 0.0, 300.0, 100.0, 50.0),
+            json!({"id": "label", "type": "text", "x": 10.0, "y": 10.0, "width": 20.0, "height": 10.0, "containerId": "a"}),
+            arrow("ab", "a", "b"),
+            arrow("ac", "a", "c"),
+        ]
+    }
+
+    fn layout(elements: Vec<Value>, algorithm: LayoutAlgorithm, options: &LayoutOptions) -> (LayoutResult, Vec<Value>) {
+        let result = apply_layout(&Value::Array(elements).to_string(), algorithm, options).unwrap();
+        let elements = serde_json::from_str::<Vec<Value>>(&result.content).unwrap();
+        (result, elements)
+    }
+
+    fn position(elements: &[Value], id: &str) -> (f64, f64) {
+        let element = elements.iter().find(|e| e["id"] == id).unwrap();
+        (element["x"].as_f64().unwrap(), element["y"].as_f64().unwrap())
+    }
+
+    #[test]
+    fn layered_down_puts_children_on_the_next_layer() {
+        let (result, elements) = layout(sample_graph(), LayoutAlgorithm::Layered, &LayoutOptions::default());
+
+        // Layer 1 is 100 + 60 + 100 wide, so a is centered 80 in; layer 2 starts 50 + 80 down
+        assert_eq!(position(&elements, "a"), (80.0, 0.0));
+        assert_eq!(position(&elements, "c"), (0.0, 130.0));
+        assert_eq!(position(&elements, "b"), (160.0, 130.0));
+        assert_eq!(position(&elements, "label"), (90.0, 10.0));
+        assert_eq!((result.moved, result.arrows_rerouted), (3, 2));
+    }
+
+    #[test]
+    fn layered_right_flows_across() {
+        let options = LayoutOptions {
+            direction: Direction::Right,
+            ..LayoutOptions::default()
+        };
+        let (_, elements) = layout(sample_graph(), LayoutAlgorithm::Layered, &options);
+
+        assert_eq!(position(&elements, "a"), (0.0, 55.0));
+        assert_eq!(position(&elements, "b"), (180.0, 0.0));
+        assert_eq!(position(&elements, "c"), (180.0, 110.0));
+    }
+
+    #[test]
+    fn grid_fills_rows_in_reading_order_and_keeps_the_block_in_place() {
+        let elements = vec![
+            shape("p", 500.0, 500.0, 100.0, 50.0),
+            shape("q", 900.0, 500.0, 100.0, 50.0),
+            shape("r", 500.0, 800.0, 100.0, 50.0),
+            shape("s", 700.0, 800.0, 50.0, 30.0),
+        ];
+        let options = LayoutOptions {
+            columns: Some(2),
+            ..LayoutOptions::default()
+        };
+        let (_, elements) = layout(elements, LayoutAlgorithm::Grid, &options);
+
+        assert_eq!(position(&elements, "p"), (500.0, 500.0));
+        assert_eq!(position(&elements, "q"), (660.0, 500.0));
+        assert_eq!(position(&elements, "r"), (500.0, 630.0));
+        // Smaller shapes are centered in their cell
+        assert_eq!(position(&elements, "s"), (685.0, 640.0));
+    }
+
+    #[test]
+    fn fewer_than_two_shapes_is_refused() {
+        let elements = vec![shape("a", 0.0, 0.0, 100.0, 50.0)];
+        let result = apply_layout(&Value::Array(elements).to_string(), LayoutAlgorithm::Grid, &LayoutOptions::default());
+        assert!(matches!(result, Err(AppError::InvalidInput(_))));
+    }
+}
@@ -1,25 +1,116 @@
+mod activity;
+mod ai;
+mod archive;
+mod auto_export;
+mod background;
+mod backups;
+mod batch;
+mod blocking;
+mod branding;
+mod checksum;
+mod close_flow;
+mod collab;
+mod compact;
+mod compression;
+mod config;
+mod content_cache;
+mod context_menu;
+mod deeplink;
+mod dialogs;
+mod error;
+mod drafts;
+mod duplicates;
+mod export;
+mod file_locks;
+#[cfg(any(debug_assertions, test))]
+mod fixtures;
+mod frecency;
+mod fs_events;
+mod health;
+mod history;
+mod ignores;
+mod json_canvas;
+mod images;
+mod integrity;
+mod launch;
+mod layout;
+mod limits;
+mod links;
+mod locations;
+mod logging;
 mod menu;
+mod menu_text;
+mod merge;
+mod mermaid;
+mod metadata_index;
+mod migrate;
+mod network_fs;
+mod normalize;
+mod open_file;
+mod operations;
+mod partial;
+mod pins;
+mod paths;
+mod pptx;
+mod preferences;
+mod profiles;
+mod prompts;
+mod recent_saves;
+mod recently_deleted;
+mod render;
+mod repair;
+mod replace;
+mod read_only;
+mod reveal;
+mod s3;
+mod scene_outline;
+mod scene_schema;
 mod security;
+mod share;
+mod share_server;
+mod shortcuts;
+mod slides;
+mod storage;
+mod sync;
+mod templates;
+mod translate;
+mod usage;
+mod vault;
+mod vault_backup;
+mod vault_stats;
+mod webdav;
+mod watch_pause;
+mod windows;
+mod workspace;
 
-use notify::{Event, EventKind, RecursiveMode, Watcher};
+use notify::{RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use tauri::{AppHandle, Emitter, Manager, State};
+use tauri::{AppHandle, Emitter, Manager, State, WebviewWindow};
+use tracing::{debug, error, info, warn};
+use error::{AppError, ResultExt};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ExcalidrawFile {
     pub name: String,
     pub path: String,
+    /// `/`-separated path relative to the listed directory, for comparisons
+    #[serde(default)]
+    pub relative_path: String,
     pub modified: bool,
+    #[serde(flatten, default)]
+    pub metadata: storage::EntryMetadata,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AITestRequest {
-    pub base_url: String,
-    pub api_key: String,
-    pub model: String,
+    /// Settings as entered; an empty API key uses the one stored for `profile.id`
+    pub profile: profiles::AIProviderProfile,
+    #[serde(default)]
+    pub retry: ai::RetryConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,13 +122,27 @@ pub struct AITestResponse {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AIGenerateRequest {
-    pub base_url: String,
-    pub api_key: String,
-    pub model: String,
+    /// Profile to use; the default profile when unset
+    #[serde(default)]
+    pub profile_id: Option<String>,
     pub prompt: String,
-    pub max_tokens: u32,
-    pub temperature: f32,
+    /// Override the profile's defaults
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
     pub stream: bool,
+    #[serde(default)]
+    pub retry: ai::RetryConfig,
+    /// Replaces the profile's system prompt
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Prompt template to render instead of sending `prompt` verbatim
+    #[serde(default)]
+    pub template_id: Option<String>,
+    /// Values for `{{variable}}` placeholders, e.g. `selection`, `file_name`
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -50,13 +155,69 @@ pub struct AIGenerateResponse {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AIStreamRequest {
-    pub base_url: String,
-    pub api_key: String,
-    pub model: String,
+    /// Profile to use; the default profile when unset
+    #[serde(default)]
+    pub profile_id: Option<String>,
     pub prompt: String,
-    pub max_tokens: u32,
-    pub temperature: f32,
+    /// Override the profile's defaults
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
     pub request_id: String,
+    #[serde(default)]
+    pub retry: ai::RetryConfig,
+    /// Replaces the profile's system prompt
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Prompt template to render instead of sending `prompt` verbatim
+    #[serde(default)]
+    pub template_id: Option<String>,
+    /// Values for `{{variable}}` placeholders, e.g. `selection`, `file_name`
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AIImageAnalysisRequest {
+    /// Profile to use; the default profile when unset
+    #[serde(default)]
+    pub profile_id: Option<String>,
+    /// Canvas screenshot as base64, with or without a `data:` URL prefix
+    pub image_base64: String,
+    #[serde(default)]
+    pub media_type: Option<String>,
+    /// What to ask about the image; defaults to a general diagram review
+    #[serde(default)]
+    pub prompt: Option<String>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    pub request_id: String,
+    #[serde(default)]
+    pub retry: ai::RetryConfig,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TranslateSceneRequest {
+    pub file_path: String,
+    /// Language name or code, e.g. "German" or "ja"
+    pub target_language: String,
+    /// Profile to use; the default profile when unset
+    #[serde(default)]
+    pub profile_id: Option<String>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Overwrite the source file instead of writing `<name>.<language>.excalidraw`
+    #[serde(default)]
+    pub in_place: bool,
+    #[serde(default)]
+    pub retry: ai::RetryConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -72,26 +233,172 @@ pub struct AIStreamChunk {
 pub struct FileTreeNode {
     pub name: String,
     pub path: String,
+    /// `/`-separated path relative to the tree root, for comparisons
+    #[serde(default)]
+    pub relative_path: String,
     pub is_directory: bool,
+    /// Unsaved changes in the editor; see `metadata.modified_at` for the file time
     pub modified: bool,
+    #[serde(flatten, default)]
+    pub metadata: storage::EntryMetadata,
+    /// Pinned to the top of its folder
+    #[serde(default)]
+    pub pinned: bool,
+    /// Locked against edits with `set_read_only`, directly or by a folder
+    /// above it
+    #[serde(default)]
+    pub read_only: bool,
+    /// Set on files of vaults synced over WebDAV
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_status: Option<sync::SyncStatus>,
+    /// Conflict copies Dropbox, Syncthing or the app's own sync left next to
+    /// this drawing; they are listed here instead of beside it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<FileTreeNode>,
+    /// `None` for files, and for folders whose contents were not loaded yet
     pub children: Option<Vec<FileTreeNode>>,
 }
 
+/// One page of the direct children of a folder
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileTreePage {
+    pub children: Vec<FileTreeNode>,
+    /// Number of children after ignore rules, across all pages
+    pub total: usize,
+    /// Offset of the next page, if any
+    pub next_offset: Option<usize>,
+}
+
+/// Order of the entries within each folder of the tree; folders always come first
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeSort {
+    #[default]
+    Name,
+    /// Most recently modified first
+    Modified,
+    /// Largest first
+    Size,
+}
+
+/// Bounds of a recursive tree scan. Folders beyond them come back with
+/// `children: None` and are loaded on demand through get_file_tree_children.
+struct TreeScan<'a> {
+    ignore: &'a ignores::IgnoreRules,
+    /// Levels listed below the root; 1 lists only its direct children
+    max_depth: Option<usize>,
+    /// Entries after which no further folders are descended into
+    max_entries: Option<usize>,
+    sort: TreeSort,
+    scanned: usize,
+    on_progress: &'a dyn Fn(usize),
+}
+
+/// Entries scanned between two "file-tree-progress" events
+const TREE_PROGRESS_INTERVAL: usize = 500;
+
+/// Stored in `preferences.json`; fields missing from older files take their
+/// defaults, and `preferences::migrate` upgrades renamed or reshaped ones
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct Preferences {
+    /// `preferences::SCHEMA_VERSION` of the code that last wrote the file
+    pub schema_version: u32,
     pub last_directory: Option<String>,
     pub recent_directories: Vec<String>,
+    /// Drawings opened last, newest first
+    #[serde(default)]
+    pub recent_files: Vec<String>,
     pub theme: String,
     pub sidebar_visible: bool,
+    #[serde(default)]
+    pub proxy: ai::ProxyConfig,
+    /// Strip deleted elements and unused image data whenever a file is saved
+    #[serde(default)]
+    pub compact_on_save: bool,
+    /// Write drawings in a stable form, keys sorted and session state left
+    /// out, so saving an unchanged drawing gives no diff
+    #[serde(default)]
+    pub normalize_on_save: bool,
+    /// Store a checksum in each saved drawing, checked whenever it is read
+    #[serde(default)]
+    pub checksum_on_save: bool,
+    /// Folders shown side by side in the sidebar
+    #[serde(default)]
+    pub workspace_roots: Vec<String>,
+    /// Gitignore-style patterns hidden in every workspace
+    #[serde(default = "ignores::default_patterns")]
+    pub ignore_patterns: Vec<String>,
+    /// Rotating `.bak` copies of the previous versions written on save
+    #[serde(default)]
+    pub backup: backups::BackupSettings,
+    /// Most verbose level written to stdout and the log files
+    #[serde(default)]
+    pub log_level: logging::LogLevel,
+    /// Menu command id to accelerator, overriding the defaults; an empty
+    /// accelerator removes the shortcut
+    #[serde(default)]
+    pub shortcuts: HashMap<String, String>,
+    /// Drawings kept at the top of the sidebar, per workspace folder
+    #[serde(default)]
+    pub pinned_files: pins::PinMap,
+    /// Drawings and folders that can't be saved, renamed, moved or deleted
+    #[serde(default)]
+    pub read_only_paths: Vec<String>,
+    /// Seconds between automatic saves of unsaved changes; 0 turns it off
+    pub autosave_interval_secs: u32,
+    pub default_export_format: preferences::ExportFormat,
+    /// Font for new text in drawings
+    pub default_font: preferences::FontFamily,
+    pub grid: preferences::GridSettings,
+    /// Native file events or polling, for vaults on network shares
+    pub watcher: preferences::WatcherSettings,
+    /// Largest drawings opened or saved without asking
+    pub limits: limits::FileLimits,
+    /// Scheduled copies of the open vaults to a backup folder
+    pub vault_backup: vault_backup::VaultBackupSettings,
+    /// Bucket that `upload_backup` sends vault archives to
+    pub s3_backup: s3::S3Settings,
+    /// Ask before deleting files and folders
+    pub confirm_on_delete: bool,
+    /// Days deleted drawings and local folders stay in Recently Deleted
+    /// before they move on to the system trash; 0 deletes them right away
+    pub deleted_retention_days: u32,
+    /// Interface and menu language; unset until the user picks one
+    pub language: Option<String>,
 }
 
 impl Default for Preferences {
     fn default() -> Self {
         Self {
+            schema_version: preferences::SCHEMA_VERSION,
             last_directory: None,
             recent_directories: Vec::new(),
+            recent_files: Vec::new(),
             theme: "system".to_string(),
             sidebar_visible: true,
+            proxy: ai::ProxyConfig::default(),
+            compact_on_save: false,
+            normalize_on_save: false,
+            checksum_on_save: false,
+            workspace_roots: Vec::new(),
+            ignore_patterns: ignores::default_patterns(),
+            backup: backups::BackupSettings::default(),
+            log_level: logging::LogLevel::default(),
+            shortcuts: HashMap::new(),
+            pinned_files: pins::PinMap::new(),
+            read_only_paths: Vec::new(),
+            autosave_interval_secs: 0,
+            default_export_format: preferences::ExportFormat::default(),
+            default_font: preferences::FontFamily::default(),
+            grid: preferences::GridSettings::default(),
+            watcher: preferences::WatcherSettings::default(),
+            limits: limits::FileLimits::default(),
+            vault_backup: vault_backup::VaultBackupSettings::default(),
+            s3_backup: s3::S3Settings::default(),
+            confirm_on_delete: true,
+            deleted_retention_days: recently_deleted::DEFAULT_RETENTION_DAYS,
+            language: None,
         }
     }
 }
@@ -105,45 +412,353 @@ pub struct LibraryItem {
 }
 
 pub struct AppState {
-    pub current_directory: Mutex<Option<PathBuf>>,
-    pub modified_files: Mutex<Vec<String>>,
+    /// Open folder, unsaved files and pending targets of each window
+    pub windows: windows::WindowStates,
+    pub storage: storage::Mounts,
+    pub dialogs: dialogs::PendingDialogs,
+    /// Long commands started with an operation id, so they can be cancelled
+    pub operations: operations::Operations,
+    pub close_requests: close_flow::CloseRequests,
+    pub context_menu: context_menu::PendingContextMenu,
+    pub background: background::TaskManager,
+    pub links: Mutex<links::LinkIndex>,
+    /// Metadata of the drawings in watched vaults, for quick open, search
+    /// and statistics
+    pub metadata: metadata_index::MetadataIndex,
+    /// One watcher per watched root, keyed by normalized path; dropping a
+    /// watcher ends its event thread
+    pub watchers: Mutex<HashMap<String, Box<dyn Watcher + Send>>>,
+    /// The drawing each window has open, checked for changes made elsewhere
+    pub open_files: open_file::OpenFileWatches,
+    /// Lock files of the drawings open in this app's windows
+    pub file_locks: file_locks::FileLocks,
+    /// Files the app just wrote, whose watcher events are not passed on
+    pub recent_saves: recent_saves::RecentSaves,
+    /// Drawings `read_file` returned lately, served again while unchanged
+    pub content_cache: content_cache::ContentCache,
+    /// Holds back watcher events during bulk operations
+    pub watch_pause: watch_pause::WatchPause,
+    /// Parsed `.excaliapp.json` of every loaded vault root
+    pub configs: Mutex<config::ConfigMap>,
+    /// Saved drawings waiting for their automatic exports
+    pub auto_export: auto_export::AutoExporter,
+    /// Folders and picked files the file commands may touch
+    pub vault: vault::VaultAccess,
+    /// Held while vault backups run
+    pub vault_backups: vault_backup::BackupLock,
+    /// Held while a WebDAV sync runs
+    pub syncing: tokio::sync::Mutex<()>,
+    /// The read-only LAN server, while it runs
+    pub share_server: Mutex<Option<share_server::ShareServer>>,
+    /// The collaboration session this app hosts or joined, if any
+    pub collab: Mutex<Option<collab::CollabSession>>,
+    pub logger: logging::Logger,
+}
+
+fn load_prompt_templates(app: &AppHandle) -> Result<Vec<prompts::PromptTemplate>, AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(prompts::STORE_FILE)?;
+
+    if let Some(value) = store.get(prompts::STORE_KEY) {
+        match serde_json::from_value::<Vec<prompts::PromptTemplate>>(value.clone()) {
+            Ok(templates) => Ok(templates),
+            Err(e) => {
+                warn!("Failed to deserialize prompt templates: {}", e);
+                Ok(vec![])
+            }
+        }
+    } else {
+        Ok(vec![])
+    }
+}
+
+fn store_prompt_templates(
+    app: &AppHandle,
+    templates: &[prompts::PromptTemplate],
+) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(prompts::STORE_FILE)?;
+    store.set(
+        prompts::STORE_KEY,
+        serde_json::to_value(templates)?,
+    );
+    store.save().map_err(AppError::from)
+}
+
+/// Resolves the final prompt and system prompt for an AI call: renders the
+/// selected template (or the raw prompt) with the request's variables, and
+/// lets a template's own system prompt override the profile's
+fn prepare_prompt(
+    app: &AppHandle,
+    prompt: &str,
+    system_prompt: Option<&str>,
+    template_id: Option<&str>,
+    variables: &HashMap<String, String>,
+) -> Result<(String, Option<String>), AppError> {
+    let mut variables = variables.clone();
+    variables
+        .entry("prompt".to_string())
+        .or_insert_with(|| prompt.to_string());
+
+    let system_prompt = system_prompt
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| prompts::render(s, &variables));
+
+    let Some(template_id) = template_id.filter(|id| !id.is_empty()) else {
+        return Ok((prompts::render(prompt, &variables), system_prompt));
+    };
+
+    let template = load_prompt_templates(app)?
+        .into_iter()
+        .find(|t| t.id == template_id)
+        .ok_or_else(|| AppError::NotFound(format!("Prompt template not found: {}", template_id)))?;
+
+    let template_system_prompt = template
+        .system_prompt
+        .as_deref()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| prompts::render(s, &variables));
+
+    Ok((
+        prompts::render(&template.template, &variables),
+        template_system_prompt.or(system_prompt),
+    ))
+}
+
+#[tauri::command]
+async fn list_prompt_templates(app: AppHandle) -> Result<Vec<prompts::PromptTemplate>, AppError> {
+    load_prompt_templates(&app)
+}
+
+#[tauri::command]
+async fn save_prompt_template(
+    app: AppHandle,
+    template: prompts::PromptTemplate,
+) -> Result<prompts::PromptTemplate, AppError> {
+    if template.name.trim().is_empty() {
+        return Err(AppError::InvalidInput("Template name cannot be empty".to_string()));
+    }
+
+    let mut template = template;
+    if template.id.trim().is_empty() {
+        template.id = format!("tpl_{}", prompts::now_millis());
+    }
+    template.updated_at = prompts::now_millis();
+
+    let mut templates = load_prompt_templates(&app)?;
+    match templates.iter_mut().find(|t| t.id == template.id) {
+        Some(existing) => *existing = template.clone(),
+        None => templates.push(template.clone()),
+    }
+    store_prompt_templates(&app, &templates)?;
+
+    Ok(template)
+}
+
+#[tauri::command]
+async fn delete_prompt_template(app: AppHandle, id: String) -> Result<(), AppError> {
+    let mut templates = load_prompt_templates(&app)?;
+    let count = templates.len();
+    templates.retain(|t| t.id != id);
+
+    if templates.len() == count {
+        return Err(AppError::NotFound(format!("Prompt template not found: {}", id)));
+    }
+
+    store_prompt_templates(&app, &templates)
+}
+
+fn load_ai_profiles(app: &AppHandle) -> Result<(Vec<profiles::AIProviderProfile>, Option<String>), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(profiles::STORE_FILE)?;
+
+    let mut list = match store.get(profiles::STORE_KEY) {
+        Some(value) => serde_json::from_value(value).unwrap_or_else(|e| {
+            warn!("Failed to deserialize AI profiles: {}", e);
+            vec![]
+        }),
+        None => vec![],
+    };
+    let default_id = store
+        .get(profiles::DEFAULT_KEY)
+        .and_then(|value| value.as_str().map(str::to_string));
+    if profiles::migrate_api_keys(&mut list) {
+        store_ai_profiles(app, &list, default_id.as_deref())?;
+    }
+    Ok((list, default_id))
+}
+
+fn store_ai_profiles(
+    app: &AppHandle,
+    list: &[profiles::AIProviderProfile],
+    default_id: Option<&str>,
+) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(profiles::STORE_FILE)?;
+    store.set(
+        profiles::STORE_KEY,
+        serde_json::to_value(list)?,
+    );
+    match default_id {
+        Some(id) => store.set(profiles::DEFAULT_KEY, id),
+        None => {
+            store.delete(profiles::DEFAULT_KEY);
+        }
+    }
+    store.save().map_err(AppError::from)
+}
+
+/// The profile an AI command runs with, including its API key
+fn resolve_ai_profile(app: &AppHandle, profile_id: Option<&str>) -> Result<profiles::AIProviderProfile, AppError> {
+    let (list, default_id) = load_ai_profiles(app)?;
+    profiles::with_api_key(profiles::find(&list, profile_id, default_id.as_deref())?.clone())
+}
+
+#[tauri::command]
+async fn list_ai_profiles(app: AppHandle) -> Result<Vec<profiles::ProfileSummary>, AppError> {
+    let (list, default_id) = load_ai_profiles(&app)?;
+    Ok(profiles::summaries(&list, default_id.as_deref()))
+}
+
+/// Creates or updates a profile; leave `api_key` empty to keep the stored key.
+/// The first profile becomes the default.
+#[tauri::command]
+async fn save_ai_profile(
+    app: AppHandle,
+    profile: profiles::AIProviderProfile,
+) -> Result<profiles::ProfileSummary, AppError> {
+    if profile.name.trim().is_empty() {
+        return Err(AppError::InvalidInput("Profile name cannot be empty".to_string()));
+    }
+    if profile.base_url.trim().is_empty() || profile.model.trim().is_empty() {
+        return Err(AppError::InvalidInput("Profile needs an endpoint and a model".to_string()));
+    }
+
+    let (mut list, default_id) = load_ai_profiles(&app)?;
+    let saved = profiles::upsert(&mut list, profile);
+    if !saved.api_key.is_empty() {
+        profiles::store_api_key(&saved.id, &saved.api_key)?;
+    }
+    let default_id = default_id.unwrap_or_else(|| saved.id.clone());
+    store_ai_profiles(&app, &list, Some(&default_id))?;
+
+    info!("Saved AI profile {} ({})", saved.name, saved.id);
+    profiles::summaries(&list, Some(&default_id))
+        .into_iter()
+        .find(|summary| summary.profile.id == saved.id)
+        .ok_or_else(|| AppError::Internal("Saved profile disappeared".to_string()))
+}
+
+/// Removes a profile; when it was the default, the first remaining one takes over
+#[tauri::command]
+async fn delete_ai_profile(app: AppHandle, id: String) -> Result<(), AppError> {
+    let (mut list, default_id) = load_ai_profiles(&app)?;
+    let count = list.len();
+    list.retain(|p| p.id != id);
+
+    if list.len() == count {
+        return Err(AppError::NotFound(format!("AI profile not found: {}", id)));
+    }
+
+    let default_id = default_id
+        .filter(|default_id| *default_id != id)
+        .or_else(|| list.first().map(|p| p.id.clone()));
+    store_ai_profiles(&app, &list, default_id.as_deref())?;
+    if let Err(e) = profiles::store_api_key(&id, "") {
+        warn!("Failed to remove the API key of AI profile {}: {}", id, e);
+    }
+    Ok(())
+}
+
+/// Makes `id` the profile AI commands use when they don't name one
+#[tauri::command]
+async fn set_default_ai_profile(app: AppHandle, id: String) -> Result<(), AppError> {
+    let (list, _) = load_ai_profiles(&app)?;
+    if !list.iter().any(|p| p.id == id) {
+        return Err(AppError::NotFound(format!("AI profile not found: {}", id)));
+    }
+    store_ai_profiles(&app, &list, Some(&id))
+}
+
+/// Tells the UI that a transient AI failure is being retried
+fn notify_ai_retry(
+    app: &AppHandle,
+    request_id: Option<&str>,
+    max_attempts: u32,
+    attempt: u32,
+    delay: std::time::Duration,
+    reason: String,
+) {
+    debug!(
+        "AI request failed ({}), retrying in {:?} (attempt {}/{})",
+        reason, delay, attempt, max_attempts
+    );
+    let _ = app.emit(
+        "ai-retrying",
+        ai::RetryNotice {
+            request_id: request_id.map(|id| id.to_string()),
+            attempt,
+            max_attempts,
+            delay_ms: delay.as_millis() as u64,
+            reason,
+        },
+    );
 }
 
 #[tauri::command]
-async fn test_ai_connection(request: AITestRequest) -> Result<AITestResponse, String> {
-    println!("Testing AI connection to: {}", request.base_url);
+async fn test_ai_connection(app: AppHandle, request: AITestRequest) -> Result<AITestResponse, AppError> {
+    let mut profile = request.profile;
+    info!("Testing AI connection to: {}", profile.base_url);
+
+    if profile.api_key.is_empty() && !profile.id.is_empty() {
+        let (list, _) = load_ai_profiles(&app)?;
+        if let Some(stored) = list.into_iter().find(|p| p.id == profile.id) {
+            profile.api_key = profiles::with_api_key(stored)?.api_key;
+        }
+    }
     
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(10))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-    let test_payload = serde_json::json!({
-        "model": request.model,
-        "messages": [{"role": "user", "content": "你好"}],
-        "max_tokens": 10,
-        "temperature": 0.1
-    });
+    let client = ai::build_client(
+        std::time::Duration::from_secs(10),
+        &load_preferences(&app).proxy,
+    )?;
 
-    let url = format!("{}/chat/completions", request.base_url);
-    println!("Making request to: {}", url);
+    let provider = profile.wire_provider();
+    let params = ai::ChatParams {
+        base_url: &profile.base_url,
+        api_key: &profile.api_key,
+        model: &profile.model,
+        prompt: "你好",
+        system_prompt: None,
+        image: None,
+        max_tokens: 10,
+        temperature: 0.1,
+        stream: false,
+    };
 
-    match client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", request.api_key))
-        .json(&test_payload)
-        .send()
-        .await
+    let url = ai::chat_url(provider, &params);
+    debug!("Making request to: {}", url);
+
+    match ai::send_with_retry(
+        || ai::build_chat_request(&client, provider, &params),
+        &request.retry,
+        |attempt, delay, reason| {
+            notify_ai_retry(&app, None, request.retry.max_attempts, attempt, delay, reason)
+        },
+    )
+    .await
     {
         Ok(response) => {
             let status = response.status();
-            println!("Response status: {}", status);
+            debug!("Response status: {}", status);
             
             if status.is_success() {
                 match response.json::<serde_json::Value>().await {
                     Ok(data) => {
-                        println!("Success response: {:?}", data);
+                        debug!("Success response: {:?}", data);
                         Ok(AITestResponse {
                             success: true,
                             error_message: None,
@@ -152,7 +767,7 @@ async fn test_ai_connection(request: AITestRequest) -> Result<AITestResponse, St
                     }
                     Err(e) => {
                         let error_msg = format!("Failed to parse response: {}", e);
-                        println!("Parse error: {}", error_msg);
+                        debug!("Parse error: {}", error_msg);
                         Ok(AITestResponse {
                             success: false,
                             error_message: Some(error_msg),
@@ -165,7 +780,7 @@ async fn test_ai_connection(request: AITestRequest) -> Result<AITestResponse, St
                     Ok(text) => format!("HTTP {}: {}", status, text),
                     Err(_) => format!("HTTP {} error", status),
                 };
-                println!("HTTP error: {}", error_msg);
+                debug!("HTTP error: {}", error_msg);
                 Ok(AITestResponse {
                     success: false,
                     error_message: Some(error_msg),
@@ -175,7 +790,7 @@ async fn test_ai_connection(request: AITestRequest) -> Result<AITestResponse, St
         }
         Err(e) => {
             let error_msg = format!("Request failed: {}", e);
-            println!("Request error: {}", error_msg);
+            debug!("Request error: {}", error_msg);
             Ok(AITestResponse {
                 success: false,
                 error_message: Some(error_msg),
@@ -188,36 +803,288 @@ async fn test_ai_connection(request: AITestRequest) -> Result<AITestResponse, St
 
 
 #[tauri::command]
-async fn call_ai_api(request: AIGenerateRequest) -> Result<AIGenerateResponse, String> {
-    println!("Calling AI API: {} (stream: {})", request.base_url, request.stream);
-    
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-    let payload = serde_json::json!({
-        "model": request.model,
-        "messages": [{"role": "user", "content": request.prompt}],
-        "max_tokens": request.max_tokens,
-        "temperature": request.temperature,
-        "stream": request.stream
-    });
+async fn list_ai_models(app: AppHandle, profile_id: Option<String>) -> Result<Vec<String>, AppError> {
+    let profile = resolve_ai_profile(&app, profile_id.as_deref())?;
+    debug!("Listing AI models from: {}", profile.base_url);
+
+    let client = ai::build_client(
+        std::time::Duration::from_secs(10),
+        &load_preferences(&app).proxy,
+    )?;
 
-    let url = format!("{}/chat/completions", request.base_url);
-    println!("Making AI generation request to: {}", url);
+    let provider = profile.wire_provider();
 
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", request.api_key))
-        .json(&payload)
+    let response = ai::build_models_request(&client, provider, &profile.base_url, &profile.api_key)
         .send()
         .await
-        .map_err(|e| format!("Request failed: {}", e))?;
+        .map_err(|e| AppError::Network(format!("Request failed: {}", e)))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(AppError::Network(format!("HTTP {}: {}", status, error_text)));
+    }
+
+    let data = response
+        .json::<serde_json::Value>()
+        .await
+        .context("Failed to parse response")?;
+
+    let models = ai::parse_models_response(provider, &data);
+    debug!("Found {} models", models.len());
+    Ok(models)
+}
+
+fn load_ai_usage(app: &AppHandle) -> Result<(Vec<usage::UsageRecord>, usage::UsageBudget), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(usage::STORE_FILE)?;
+
+    let ledger = store
+        .get(usage::LEDGER_KEY)
+        .and_then(|value| match serde_json::from_value(value.clone()) {
+            Ok(ledger) => Some(ledger),
+            Err(e) => {
+                warn!("Failed to deserialize AI usage ledger: {}", e);
+                None
+            }
+        })
+        .unwrap_or_default();
+    let budget = store
+        .get(usage::BUDGET_KEY)
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .unwrap_or_default();
+
+    Ok((ledger, budget))
+}
+
+/// Fails when the configured monthly AI budget has been used up
+fn check_ai_budget(app: &AppHandle) -> Result<(), AppError> {
+    let (ledger, budget) = load_ai_usage(app)?;
+    budget.check(&ledger, prompts::now_millis())
+}
+
+/// Appends a call to the usage ledger; failures are logged, not propagated
+fn record_ai_usage(
+    app: &AppHandle,
+    provider: ai::AIProvider,
+    model: &str,
+    operation: &str,
+    tokens_used: u32,
+    estimated: bool,
+) {
+    use tauri_plugin_store::StoreExt;
+
+    let result = load_ai_usage(app).and_then(|(mut ledger, _)| {
+        usage::append(
+            &mut ledger,
+            usage::UsageRecord {
+                timestamp: prompts::now_millis(),
+                provider: provider.name().to_string(),
+                model: model.to_string(),
+                operation: operation.to_string(),
+                tokens_used,
+                estimated,
+            },
+        );
+        let store = app.store(usage::STORE_FILE)?;
+        store.set(
+            usage::LEDGER_KEY,
+            serde_json::to_value(&ledger)?,
+        );
+        store.save().map_err(AppError::from)
+    });
+
+    if let Err(e) = result {
+        warn!("Failed to record AI usage: {}", e);
+    }
+}
+
+#[tauri::command]
+async fn get_ai_usage_stats(app: AppHandle, range: Option<String>) -> Result<usage::UsageStats, AppError> {
+    let (ledger, budget) = load_ai_usage(&app)?;
+    usage::stats(
+        &ledger,
+        &budget,
+        range.as_deref().unwrap_or("month"),
+        prompts::now_millis(),
+    )
+}
+
+#[tauri::command]
+async fn set_ai_usage_budget(app: AppHandle, budget: usage::UsageBudget) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    if budget.monthly_token_limit == Some(0)
+        || budget.monthly_cost_limit.is_some_and(|l| l.is_nan() || l < 0.0)
+        || budget.prices_per_1k_tokens.values().any(|p| p.is_nan() || *p < 0.0)
+    {
+        return Err(AppError::InvalidInput("Budget limits and prices must be positive".to_string()));
+    }
+
+    let store = app.store(usage::STORE_FILE)?;
+    store.set(
+        usage::BUDGET_KEY,
+        serde_json::to_value(&budget)?,
+    );
+    Ok(store.save()?)
+}
+
+fn load_ai_history(app: &AppHandle) -> Result<Vec<history::AIHistoryEntry>, AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(history::STORE_FILE)?;
+
+    if let Some(value) = store.get(history::STORE_KEY) {
+        match serde_json::from_value::<Vec<history::AIHistoryEntry>>(value.clone()) {
+            Ok(entries) => Ok(entries),
+            Err(e) => {
+                warn!("Failed to deserialize AI history: {}", e);
+                Ok(vec![])
+            }
+        }
+    } else {
+        Ok(vec![])
+    }
+}
+
+fn store_ai_history(app: &AppHandle, entries: &[history::AIHistoryEntry]) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(history::STORE_FILE)?;
+    store.set(
+        history::STORE_KEY,
+        serde_json::to_value(entries)?,
+    );
+    store.save().map_err(AppError::from)
+}
+
+/// Appends a finished AI call to the history; failures are logged, not propagated
+fn record_ai_history(app: &AppHandle, entry: history::AIHistoryEntry) {
+    let result = load_ai_history(app).and_then(|mut entries| {
+        history::append(&mut entries, entry);
+        store_ai_history(app, &entries)
+    });
+
+    if let Err(e) = result {
+        warn!("Failed to record AI history: {}", e);
+    }
+}
+
+#[tauri::command]
+async fn list_ai_history(
+    app: AppHandle,
+    page: Option<usize>,
+    page_size: Option<usize>,
+) -> Result<history::AIHistoryPage, AppError> {
+    let entries = load_ai_history(&app)?;
+    Ok(history::page(
+        &entries,
+        page.unwrap_or(0),
+        page_size.unwrap_or(history::DEFAULT_PAGE_SIZE),
+    ))
+}
+
+#[tauri::command]
+async fn get_ai_history_entry(app: AppHandle, id: String) -> Result<history::AIHistoryEntry, AppError> {
+    load_ai_history(&app)?
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| AppError::NotFound(format!("AI history entry not found: {}", id)))
+}
+
+/// Deletes the given entries, or the whole history when `ids` is omitted
+#[tauri::command]
+async fn delete_ai_history(app: AppHandle, ids: Option<Vec<String>>) -> Result<usize, AppError> {
+    let mut entries = load_ai_history(&app)?;
+    let count = entries.len();
+    match ids {
+        Some(ids) => entries.retain(|entry| !ids.contains(&entry.id)),
+        None => entries.clear(),
+    }
+    store_ai_history(&app, &entries)?;
+    Ok(count - entries.len())
+}
+
+#[tauri::command]
+async fn call_ai_api(app: AppHandle, request: AIGenerateRequest) -> Result<AIGenerateResponse, AppError> {
+    let profile = resolve_ai_profile(&app, request.profile_id.as_deref())?;
+    info!("Calling AI API: {} (stream: {})", profile.base_url, request.stream);
+    check_ai_budget(&app)?;
+
+    let client = ai::build_client(
+        std::time::Duration::from_secs(30),
+        &load_preferences(&app).proxy,
+    )?;
+
+    let provider = profile.wire_provider();
+    let (prompt, system_prompt) = prepare_prompt(
+        &app,
+        &request.prompt,
+        request.system_prompt.as_deref().or(profile.system_prompt.as_deref()),
+        request.template_id.as_deref(),
+        &request.variables,
+    )?;
+    let params = ai::ChatParams {
+        base_url: &profile.base_url,
+        api_key: &profile.api_key,
+        model: &profile.model,
+        prompt: &prompt,
+        system_prompt: system_prompt.as_deref(),
+        image: None,
+        max_tokens: request.max_tokens.unwrap_or(profile.max_tokens),
+        temperature: request.temperature.unwrap_or(profile.temperature),
+        stream: request.stream,
+    };
+
+    let started = std::time::Instant::now();
+    let result = generate_chat(&app, &client, provider, &params, &request.retry).await;
+
+    let error = result.as_ref().err().map(AppError::to_string);
+    let outcome = match &result {
+        Ok(response) if response.success => Ok(response.content.as_deref().unwrap_or_default()),
+        Ok(response) => Err(response.error_message.as_deref().unwrap_or("Unknown error")),
+        Err(_) => Err(error.as_deref().unwrap_or_default()),
+    };
+    let tokens_used = result.as_ref().ok().and_then(|r| r.tokens_used);
+    record_ai_history(
+        &app,
+        history::AIHistoryEntry::new(
+            "generate",
+            provider,
+            &params,
+            started.elapsed(),
+            outcome,
+            tokens_used,
+            None,
+        ),
+    );
+
+    result
+}
+
+async fn generate_chat(
+    app: &AppHandle,
+    client: &reqwest::Client,
+    provider: ai::AIProvider,
+    params: &ai::ChatParams<'_>,
+    retry: &ai::RetryConfig,
+) -> Result<AIGenerateResponse, AppError> {
+    let url = ai::chat_url(provider, params);
+    debug!("Making AI generation request to: {}", url);
+
+    let response = ai::send_with_retry(
+        || ai::build_chat_request(client, provider, params),
+        retry,
+        |attempt, delay, reason| {
+            notify_ai_retry(app, None, retry.max_attempts, attempt, delay, reason)
+        },
+    )
+    .await
+    .map_err(|e| AppError::Network(format!("Request failed: {}", e)))?;
 
     let status = response.status();
-    println!("AI API response status: {}", status);
+    debug!("AI API response status: {}", status);
     
     if !status.is_success() {
         let error_text = response.text().await.unwrap_or_default();
@@ -229,7 +1096,7 @@ async fn call_ai_api(request: AIGenerateRequest) -> Result<AIGenerateResponse, S
         });
     }
 
-    if request.stream {
+    if params.stream {
         // Handle streaming response
         use futures_util::StreamExt;
         
@@ -257,14 +1124,8 @@ async fn call_ai_api(request: AIGenerateRequest) -> Result<AIGenerateResponse, S
                             
                             // Parse JSON chunk
                             if let Ok(chunk_data) = serde_json::from_str::<serde_json::Value>(data_part) {
-                                if let Some(choices) = chunk_data.get("choices").and_then(|c| c.as_array()) {
-                                    if let Some(choice) = choices.first() {
-                                        if let Some(delta) = choice.get("delta") {
-                                            if let Some(content) = delta.get("content").and_then(|c| c.as_str()) {
-                                                accumulated_content.push_str(content);
-                                            }
-                                        }
-                                    }
+                                if let Some(content) = ai::parse_stream_chunk(provider, &chunk_data) {
+                                    accumulated_content.push_str(&content);
                                 }
                             }
                         }
@@ -290,7 +1151,15 @@ async fn call_ai_api(request: AIGenerateRequest) -> Result<AIGenerateResponse, S
             });
         }
         
-        println!("Streaming generation successful, content length: {}", accumulated_content.len());
+        info!("Streaming generation successful, content length: {}", accumulated_content.len());
+        record_ai_usage(
+            app,
+            provider,
+            params.model,
+            "generate",
+            usage::estimate_tokens(params.prompt) + usage::estimate_tokens(&accumulated_content),
+            true,
+        );
         Ok(AIGenerateResponse {
             success: true,
             content: Some(accumulated_content),
@@ -301,29 +1170,28 @@ async fn call_ai_api(request: AIGenerateRequest) -> Result<AIGenerateResponse, S
         // Handle non-streaming response (existing logic)
         match response.json::<serde_json::Value>().await {
             Ok(data) => {
-                if let Some(choices) = data.get("choices").and_then(|c| c.as_array()) {
-                    if let Some(first_choice) = choices.first() {
-                        if let Some(message) = first_choice.get("message") {
-                            if let Some(content) = message.get("content").and_then(|c| c.as_str()) {
-                                let tokens_used = data.get("usage")
-                                    .and_then(|u| u.get("total_tokens"))
-                                    .and_then(|t| t.as_u64())
-                                    .map(|t| t as u32);
-                                
-                                println!("AI generation successful, content length: {}", content.len());
-                                return Ok(AIGenerateResponse {
-                                    success: true,
-                                    content: Some(content.to_string()),
-                                    error_message: None,
-                                    tokens_used,
-                                });
-                            }
-                        }
-                    }
+                if let Some((content, tokens_used)) = ai::parse_generate_response(provider, &data) {
+                    info!("AI generation successful, content length: {}", content.len());
+                    record_ai_usage(
+                        app,
+                        provider,
+                        params.model,
+                        "generate",
+                        tokens_used.unwrap_or_else(|| {
+                            usage::estimate_tokens(params.prompt) + usage::estimate_tokens(&content)
+                        }),
+                        tokens_used.is_none(),
+                    );
+                    return Ok(AIGenerateResponse {
+                        success: true,
+                        content: Some(content),
+                        error_message: None,
+                        tokens_used,
+                    });
                 }
                 
                 let error_msg = "Invalid response format: no content found".to_string();
-                println!("Response parsing error: {}", error_msg);
+                info!("Response parsing error: {}", error_msg);
                 Ok(AIGenerateResponse {
                     success: false,
                     content: None,
@@ -333,7 +1201,7 @@ async fn call_ai_api(request: AIGenerateRequest) -> Result<AIGenerateResponse, S
             }
             Err(e) => {
                 let error_msg = format!("Failed to parse response: {}", e);
-                println!("JSON parse error: {}", error_msg);
+                info!("JSON parse error: {}", error_msg);
                 Ok(AIGenerateResponse {
                     success: false,
                     content: None,
@@ -345,840 +1213,6309 @@ async fn call_ai_api(request: AIGenerateRequest) -> Result<AIGenerateResponse, S
     }
 }
 
-#[tauri::command]
-async fn call_ai_api_stream(app: AppHandle, request: AIStreamRequest) -> Result<(), String> {
-    println!("Starting streaming AI API call: {} (request_id: {})", request.base_url, request.request_id);
-    
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(30))
-        .build()
-        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
-
-    let payload = serde_json::json!({
-        "model": request.model,
-        "messages": [{"role": "user", "content": request.prompt}],
-        "max_tokens": request.max_tokens,
-        "temperature": request.temperature,
-        "stream": true
-    });
+/// Sends a streaming chat request and forwards the deltas to the frontend as
+/// `ai-stream-chunk` events; returns the complete text received
+async fn forward_chat_stream(
+    app: &AppHandle,
+    client: &reqwest::Client,
+    provider: ai::AIProvider,
+    params: &ai::ChatParams<'_>,
+    request_id: &str,
+    retry: &ai::RetryConfig,
+) -> Result<String, AppError> {
+    let url = ai::chat_url(provider, params);
+    debug!("Making streaming request to: {}", url);
 
-    let url = format!("{}/chat/completions", request.base_url);
-    println!("Making streaming request to: {}", url);
+    let response = ai::send_with_retry(
+        || ai::build_chat_request(client, provider, params),
+        retry,
+        |attempt, delay, reason| {
+            notify_ai_retry(
+                app,
+                Some(request_id),
+                retry.max_attempts,
+                attempt,
+                delay,
+                reason,
+            )
+        },
+    )
+    .await
+    .map_err(|e| AppError::Network(format!("Request failed: {}", e)))?;
 
-    // Spawn async task to handle streaming
-    let app_clone = app.clone();
-    let request_id = request.request_id.clone();
-    
-    tauri::async_runtime::spawn(async move {
-        match client
-            .post(&url)
-            .header("Content-Type", "application/json")
-            .header("Authorization", format!("Bearer {}", request.api_key))
-            .json(&payload)
-            .send()
-            .await
-        {
-            Ok(response) => {
-                let status = response.status();
-                
-                if !status.is_success() {
-                    let error_text = response.text().await.unwrap_or_default();
-                    let _ = app_clone.emit("ai-stream-error", serde_json::json!({
-                        "request_id": request_id,
-                        "error": format!("HTTP {}: {}", status, error_text)
-                    }));
-                    return;
-                }
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await.unwrap_or_default();
+        return Err(AppError::Network(format!("HTTP {}: {}", status, error_text)));
+    }
 
-                use futures_util::StreamExt;
-                let mut stream = response.bytes_stream();
-                let mut buffer = String::new();
-                
-                while let Some(chunk) = stream.next().await {
-                    match chunk {
-                        Ok(bytes) => {
-                            let chunk_str = String::from_utf8_lossy(&bytes);
-                            buffer.push_str(&chunk_str);
-                            
-                            // Process complete lines
-                            while let Some(newline_pos) = buffer.find("\n") {
-                                let line = buffer[..newline_pos].trim().to_string();
-                                buffer.drain(..=newline_pos);
-                                
-                                if line.starts_with("data: ") {
-                                    let data_part = &line[6..]; // Remove "data: " prefix
-                                    
-                                    if data_part == "[DONE]" {
-                                        // Send completion event
-                                        let _ = app_clone.emit("ai-stream-complete", serde_json::json!({
-                                            "request_id": request_id
-                                        }));
-                                        return;
-                                    }
-                                    
-                                    // Parse JSON chunk
-                                    if let Ok(chunk_data) = serde_json::from_str::<serde_json::Value>(data_part) {
-                                        if let Some(choices) = chunk_data.get("choices").and_then(|c| c.as_array()) {
-                                            if let Some(choice) = choices.first() {
-                                                if let Some(delta) = choice.get("delta") {
-                                                    if let Some(content) = delta.get("content").and_then(|c| c.as_str()) {
-                                                        // Emit chunk to frontend
-                                                        let _ = app_clone.emit("ai-stream-chunk", AIStreamChunk {
-                                                            request_id: request_id.clone(),
-                                                            content: content.to_string(),
-                                                            finished: false,
-                                                        });
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => {
-                            let _ = app_clone.emit("ai-stream-error", serde_json::json!({
-                                "request_id": request_id,
-                                "error": format!("Stream error: {}", e)
-                            }));
-                            return;
-                        }
-                    }
-                }
-                
-                // If we reach here without [DONE], send completion anyway
-                let _ = app_clone.emit("ai-stream-complete", serde_json::json!({
-                    "request_id": request_id
-                }));
+    use futures_util::StreamExt;
+    let mut stream = response.bytes_stream();
+    let mut buffer = String::new();
+    let mut received = String::new();
+
+    while let Some(chunk) = stream.next().await {
+        let bytes = chunk.map_err(|e| AppError::Network(format!("Stream error: {}", e)))?;
+        buffer.push_str(&String::from_utf8_lossy(&bytes));
+
+        // Process complete lines
+        while let Some(newline_pos) = buffer.find('\n') {
+            let line = buffer[..newline_pos].trim().to_string();
+            buffer.drain(..=newline_pos);
+
+            let Some(data_part) = line.strip_prefix("data: ") else {
+                continue;
+            };
+            if data_part == "[DONE]" {
+                return Ok(received);
             }
-            Err(e) => {
-                let _ = app_clone.emit("ai-stream-error", serde_json::json!({
-                    "request_id": request_id,
-                    "error": format!("Request failed: {}", e)
-                }));
+
+            let content = serde_json::from_str::<serde_json::Value>(data_part)
+                .ok()
+                .and_then(|chunk_data| ai::parse_stream_chunk(provider, &chunk_data));
+            if let Some(content) = content {
+                received.push_str(&content);
+                // Emit chunk to frontend
+                let _ = app.emit("ai-stream-chunk", AIStreamChunk {
+                    request_id: request_id.to_string(),
+                    content,
+                    finished: false,
+                });
             }
         }
-    });
+    }
 
-    Ok(())
+    // Not every provider sends [DONE]; the end of the body completes the stream
+    Ok(received)
 }
 
-#[tauri::command]
-async fn select_directory(app: AppHandle) -> Result<Option<String>, String> {
-    use std::sync::mpsc;
-    use tauri_plugin_dialog::DialogExt;
-
-    let (tx, rx) = mpsc::channel();
-
-    app.dialog().file().pick_folder(move |path| {
-        let _ = tx.send(path);
-    });
+/// Streams a chat generation to the frontend, finishing with `ai-stream-complete`
+/// or `ai-stream-error`, and records the call in the usage ledger and history
+async fn stream_chat_to_frontend(
+    app: &AppHandle,
+    client: &reqwest::Client,
+    provider: ai::AIProvider,
+    params: &ai::ChatParams<'_>,
+    request_id: &str,
+    retry: &ai::RetryConfig,
+    operation: &str,
+) {
+    let started = std::time::Instant::now();
+    let result = forward_chat_stream(app, client, provider, params, request_id, retry)
+        .await
+        .map_err(|e| e.to_string());
 
-    match rx.recv() {
-        Ok(Some(path)) => Ok(Some(path.to_string())),
-        Ok(None) => Ok(None),
-        Err(e) => Err(e.to_string()),
+    match &result {
+        Ok(received) => {
+            // Streams don't report usage consistently, so tokens are estimated from the text
+            let tokens = usage::estimate_tokens(params.prompt)
+                + params.system_prompt.map(usage::estimate_tokens).unwrap_or(0)
+                + usage::estimate_tokens(received);
+            record_ai_usage(app, provider, params.model, operation, tokens, true);
+            let _ = app.emit("ai-stream-complete", serde_json::json!({
+                "request_id": request_id
+            }));
+        }
+        Err(error) => {
+            let _ = app.emit("ai-stream-error", serde_json::json!({
+                "request_id": request_id,
+                "error": error
+            }));
+        }
     }
+
+    record_ai_history(
+        app,
+        history::AIHistoryEntry::new(
+            operation,
+            provider,
+            params,
+            started.elapsed(),
+            result.as_deref().map_err(String::as_str),
+            None,
+            Some(request_id),
+        ),
+    );
 }
 
 #[tauri::command]
-async fn list_excalidraw_files(directory: String) -> Result<Vec<ExcalidrawFile>, String> {
-    let path = Path::new(&directory);
+async fn call_ai_api_stream(app: AppHandle, request: AIStreamRequest) -> Result<(), AppError> {
+    let profile = resolve_ai_profile(&app, request.profile_id.as_deref())?;
+    info!("Starting streaming AI API call: {} (request_id: {})", profile.base_url, request.request_id);
+    check_ai_budget(&app)?;
 
-    if !path.exists() {
-        return Err("Directory does not exist".to_string());
-    }
+    let client = ai::build_client(
+        std::time::Duration::from_secs(30),
+        &load_preferences(&app).proxy,
+    )?;
 
-    let mut files = Vec::new();
-    collect_excalidraw_files_recursive(path, &mut files)?;
-    files.sort_by(|a, b| a.name.cmp(&b.name));
-    Ok(files)
+    let provider = profile.wire_provider();
+    let (prompt, system_prompt) = prepare_prompt(
+        &app,
+        &request.prompt,
+        request.system_prompt.as_deref().or(profile.system_prompt.as_deref()),
+        request.template_id.as_deref(),
+        &request.variables,
+    )?;
+
+    // Spawn async task to handle streaming
+    let app_clone = app.clone();
+    let request_id = request.request_id.clone();
+    
+    tauri::async_runtime::spawn(async move {
+        let params = ai::ChatParams {
+            base_url: &profile.base_url,
+            api_key: &profile.api_key,
+            model: &profile.model,
+            prompt: &prompt,
+            system_prompt: system_prompt.as_deref(),
+            image: None,
+            max_tokens: request.max_tokens.unwrap_or(profile.max_tokens),
+            temperature: request.temperature.unwrap_or(profile.temperature),
+            stream: true,
+        };
+
+        stream_chat_to_frontend(
+            &app_clone,
+            &client,
+            provider,
+            &params,
+            &request_id,
+            &request.retry,
+            "stream",
+        )
+        .await;
+    });
+
+    Ok(())
+}
+
+const DEFAULT_IMAGE_ANALYSIS_PROMPT: &str = "Review this diagram. Describe what it shows, then point out unclear labels, missing connections, layout problems and concrete improvements.";
+
+#[tauri::command]
+async fn ai_analyze_image(app: AppHandle, request: AIImageAnalysisRequest) -> Result<(), AppError> {
+    let profile = resolve_ai_profile(&app, request.profile_id.as_deref())?;
+    info!("Starting AI image analysis: {} (request_id: {})", profile.base_url, request.request_id);
+    check_ai_budget(&app)?;
+
+    // Accept both raw base64 and `data:image/png;base64,...` URLs
+    let (data_url_type, image_data) = match request.image_base64.split_once(";base64,") {
+        Some((prefix, data)) if prefix.starts_with("data:") => {
+            (Some(prefix.trim_start_matches("data:").to_string()), data.to_string())
+        }
+        _ => (None, request.image_base64.clone()),
+    };
+    let image_data: String = image_data.chars().filter(|c| !c.is_whitespace()).collect();
+    if image_data.is_empty() {
+        return Err(AppError::InvalidInput("Image data is empty".to_string()));
+    }
+    let media_type = request
+        .media_type
+        .clone()
+        .or(data_url_type)
+        .unwrap_or_else(|| "image/png".to_string());
+    if !media_type.starts_with("image/") {
+        return Err(AppError::InvalidInput(format!("Unsupported media type: {}", media_type)));
+    }
+
+    let client = ai::build_client(
+        std::time::Duration::from_secs(60),
+        &load_preferences(&app).proxy,
+    )?;
+    let provider = profile.wire_provider();
+
+    let app_clone = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let prompt = request
+            .prompt
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .unwrap_or(DEFAULT_IMAGE_ANALYSIS_PROMPT);
+        let params = ai::ChatParams {
+            base_url: &profile.base_url,
+            api_key: &profile.api_key,
+            model: &profile.model,
+            prompt,
+            system_prompt: request.system_prompt.as_deref().or(profile.system_prompt.as_deref()),
+            image: Some(ai::ImageInput {
+                media_type: &media_type,
+                data: &image_data,
+            }),
+            max_tokens: request.max_tokens.unwrap_or(profile.max_tokens),
+            temperature: request.temperature.unwrap_or(profile.temperature),
+            stream: true,
+        };
+
+        stream_chat_to_frontend(
+            &app_clone,
+            &client,
+            provider,
+            &params,
+            &request.request_id,
+            &request.retry,
+            "image",
+        )
+        .await;
+    });
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn translate_scene(
+    app: AppHandle,
+    request: TranslateSceneRequest,
+    state: State<'_, AppState>,
+) -> Result<translate::TranslateSceneResult, AppError> {
+    if request.target_language.trim().is_empty() {
+        return Err(AppError::InvalidInput("Target language cannot be empty".to_string()));
+    }
+    let profile = resolve_ai_profile(&app, request.profile_id.as_deref())?;
+    check_ai_budget(&app)?;
+
+    let file = resolve_in_vault(&state, &request.file_path)?;
+    security::validate_excalidraw_file(&file.path)?;
+    let output_path = if request.in_place {
+        file.path.clone()
+    } else {
+        translate::translated_path(&file.path, &request.target_language)
+    };
+    // Before any tokens are spent on a drawing that can't be written
+    read_only::check(&load_preferences(&app).read_only_paths, &file.to_external(&output_path))?;
+    let content = file.backend.read_to_string(&file.path)?;
+    security::validate_excalidraw_content(&content)?;
+
+    let mut scene: serde_json::Value =
+        serde_json::from_str(&content)?;
+    let segments = translate::collect_segments(&scene);
+    if segments.is_empty() {
+        return Err(AppError::InvalidInput("The drawing contains no text to translate".to_string()));
+    }
+
+    let client = ai::build_client(
+        std::time::Duration::from_secs(120),
+        &load_preferences(&app).proxy,
+    )?;
+    let provider = profile.wire_provider();
+    let batches = translate::batches(&segments);
+    info!(
+        "Translating {} text(s) of {} into {} in {} batch(es)",
+        segments.len(),
+        request.file_path,
+        request.target_language,
+        batches.len()
+    );
+
+    let mut translations = HashMap::new();
+    for (index, batch) in batches.iter().enumerate() {
+        let _ = app.emit("translate-progress", serde_json::json!({
+            "file_path": request.file_path,
+            "batch": index + 1,
+            "total_batches": batches.len()
+        }));
+
+        let prompt = translate::build_prompt(batch, &request.target_language);
+        let params = ai::ChatParams {
+            base_url: &profile.base_url,
+            api_key: &profile.api_key,
+            model: &profile.model,
+            prompt: &prompt,
+            system_prompt: Some("You are a professional translator for diagram labels."),
+            image: None,
+            max_tokens: request.max_tokens.unwrap_or(4000),
+            temperature: request.temperature.unwrap_or(0.2),
+            stream: false,
+        };
+
+        let started = std::time::Instant::now();
+        let response = generate_chat(&app, &client, provider, &params, &request.retry).await?;
+        let reply = match (response.success, response.content) {
+            (true, Some(content)) => content,
+            _ => {
+                let error = response.error_message.unwrap_or_else(|| "Unknown error".to_string());
+                record_ai_history(
+                    &app,
+                    history::AIHistoryEntry::new(
+                        "translate",
+                        provider,
+                        &params,
+                        started.elapsed(),
+                        Err(&error),
+                        None,
+                        None,
+                    ),
+                );
+                return Err(AppError::Network(format!("Translation failed: {}", error)));
+            }
+        };
+        record_ai_history(
+            &app,
+            history::AIHistoryEntry::new(
+                "translate",
+                provider,
+                &params,
+                started.elapsed(),
+                Ok(&reply),
+                response.tokens_used,
+                None,
+            ),
+        );
+
+        translations.extend(translate::parse_translations(&reply)?);
+    }
+
+    let translated = translate::apply(&mut scene, &segments, &translations);
+    let untranslated: Vec<String> = segments
+        .iter()
+        .filter(|s| !translations.contains_key(&s.key))
+        .map(|s| s.text.clone())
+        .collect();
+
+    let output = serde_json::to_string_pretty(&scene)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+    file.backend.write(&output_path, &output)
+        .context("Failed to write translated drawing")?;
+
+    Ok(translate::TranslateSceneResult {
+        output_path: file.to_external(&output_path),
+        translated,
+        total: segments.len(),
+        untranslated,
+        batches: batches.len(),
+    })
+}
+
+#[tauri::command]
+async fn validate_and_repair_scene(content: String) -> Result<repair::SceneRepairResult, AppError> {
+    let result = repair::validate_and_repair_scene(&content)?;
+    info!(
+        "Repaired AI scene with {} fix(es), valid: {}",
+        result.fixes.len(),
+        result.valid
+    );
+    Ok(result)
+}
+
+#[tauri::command]
+async fn validate_scene_elements(
+    content: String,
+    strictness: Option<scene_schema::Strictness>,
+) -> Result<Vec<scene_schema::SchemaWarning>, AppError> {
+    security::validate_excalidraw_content(&content)?;
+    let scene: serde_json::Value = serde_json::from_str(&content)?;
+    Ok(scene_schema::validate_elements(&scene, strictness.unwrap_or_default()))
+}
+
+#[tauri::command]
+async fn apply_layout(
+    scene_json: String,
+    algorithm: String,
+    options: Option<layout::LayoutOptions>,
+) -> Result<layout::LayoutResult, AppError> {
+    let algorithm = layout::LayoutAlgorithm::parse(&algorithm)?;
+    let options = options.unwrap_or_default();
+    let result = layout::apply_layout(&scene_json, algorithm, &options)?;
+    info!(
+        "Applied {:?} layout: {} shape(s) moved, {} connector(s) rerouted",
+        algorithm, result.moved, result.arrows_rerouted
+    );
+    Ok(result)
+}
+
+#[tauri::command]
+async fn select_directory(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, AppError> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let dialog = state.dialogs.open();
+    let tx = dialog.sender();
+
+    app.dialog().file().pick_folder(move |path| {
+        let _ = tx.send(dialogs::DialogOutcome::Picked(path));
+    });
+
+    let path = state.dialogs.wait(dialog, dialogs::DIALOG_TIMEOUT).await?;
+    if let Some(picked) = path.as_ref().and_then(|p| p.as_path()) {
+        state.vault.allow_root(picked);
+    }
+    Ok(path.map(|path| path.as_path().map(paths::normalize).unwrap_or_else(|| path.to_string())))
+}
+
+#[derive(Debug, Deserialize)]
+struct FileFilter {
+    name: String,
+    extensions: Vec<String>,
+}
+
+/// Picks a file to export to (`save`) or import from; the file commands
+/// accept it afterwards even outside the opened folders
+#[tauri::command]
+async fn select_file(
+    app: AppHandle,
+    save: bool,
+    filters: Option<Vec<FileFilter>>,
+    default_path: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, AppError> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let dialog = state.dialogs.open();
+    let tx = dialog.sender();
+
+    let mut builder = app.dialog().file();
+    for filter in filters.unwrap_or_default() {
+        let extensions: Vec<&str> = filter.extensions.iter().map(String::as_str).collect();
+        builder = builder.add_filter(filter.name, &extensions);
+    }
+    if let Some(default_path) = default_path.as_deref().map(Path::new) {
+        if let Some(dir) = default_path.parent().filter(|dir| dir.is_dir()) {
+            builder = builder.set_directory(dir);
+        }
+        if let Some(name) = default_path.file_name() {
+            builder = builder.set_file_name(name.to_string_lossy());
+        }
+    }
+    let picked = move |path| {
+        let _ = tx.send(dialogs::DialogOutcome::Picked(path));
+    };
+    if save {
+        builder.save_file(picked);
+    } else {
+        builder.pick_file(picked);
+    }
+
+    let path = state.dialogs.wait(dialog, dialogs::DIALOG_TIMEOUT).await?;
+    if let Some(picked) = path.as_ref().and_then(|p| p.as_path()) {
+        state.vault.allow_file(picked);
+    }
+    Ok(path.map(|path| path.as_path().map(paths::normalize).unwrap_or_else(|| path.to_string())))
+}
+
+/// Releases commands stuck waiting on a native dialog; cancels every pending
+/// dialog when no id is given
+#[tauri::command]
+async fn cancel_pending_dialog(
+    dialog_id: Option<u64>,
+    state: State<'_, AppState>,
+) -> Result<usize, AppError> {
+    let cancelled = state.dialogs.cancel(dialog_id);
+    info!("Cancelled {} pending dialog(s)", cancelled);
+    Ok(cancelled)
+}
+
+/// Stops a delete, move, export or archive started with this operation id
+/// at its next step; returns whether it was still running
+#[tauri::command]
+async fn cancel_operation(id: String, state: State<'_, AppState>) -> Result<bool, AppError> {
+    let cancelled = state.operations.cancel(&id);
+    if cancelled {
+        info!("Cancelling operation {}", id);
+    }
+    Ok(cancelled)
+}
+
+#[tauri::command]
+async fn mount_backend(
+    config: storage::BackendConfig,
+    state: State<'_, AppState>,
+) -> Result<String, AppError> {
+    info!("Mounting {} storage as '{}'", config.kind, config.name);
+    // Mounted paths skip the vault check, so the root itself must pass it
+    if config.kind == "local"
+        && let Some(root) = config.root.as_deref()
+    {
+        state.vault.check(Path::new(root))?;
+    }
+    state.storage.mount(&config)
+}
+
+/// Development aid for benchmarking large vaults; not part of release builds
+#[cfg(debug_assertions)]
+#[tauri::command]
+async fn generate_test_workspace(
+    app: AppHandle,
+    spec: fixtures::TestWorkspaceSpec,
+) -> Result<fixtures::TestWorkspaceSummary, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        info!(
+            "Generating test workspace at {} ({} files)",
+            spec.target, spec.file_count
+        );
+        let root = resolve_in_vault(state, &spec.target)?;
+        let summary = fixtures::generate(&root, &spec)?;
+        info!(
+            "Generated {} files in {} directories ({} bytes) in {} ms",
+            summary.files_created, summary.directories_created, summary.total_bytes, summary.elapsed_ms
+        );
+        Ok(summary)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn list_excalidraw_files(
+    app: AppHandle,
+    directory: String,
+) -> Result<Vec<ExcalidrawFile>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let root = resolve_root_in_vault(state, &directory)?;
+
+        if !root.backend.exists(&root.path) {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        let ignore = ignore_rules(app, &root);
+        let mut files = Vec::new();
+        collect_excalidraw_files(&root, &root.path, &ignore, &mut files)?;
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    })
+    .await
+}
+
+/// Re-reads the `.excaliapp.json` of `root` into the cache. An invalid file
+/// keeps the last good config and reports the error.
+fn refresh_directory_config(state: &AppState, root: &Path) -> Result<Option<config::DirectoryConfig>, AppError> {
+    let key = paths::normalize(root);
+    let loaded = config::load(root)?;
+    let mut configs = state.configs.lock().unwrap();
+    match &loaded {
+        Some(config) => configs.insert(key, config.clone()),
+        None => configs.remove(&key),
+    };
+    Ok(loaded)
+}
+
+/// Config of the vault containing `path`, from the cache
+fn directory_config_for(state: &AppState, path: &Path) -> Option<(PathBuf, config::DirectoryConfig)> {
+    let configs = state.configs.lock().unwrap();
+    config::for_path(&configs, path).map(|(root, config)| (PathBuf::from(root), config.clone()))
+}
+
+/// Nearest watched folder at or above `path`, else `path` itself
+fn vault_root(state: &AppState, path: &Path) -> PathBuf {
+    let key = paths::normalize(path);
+    let watchers = state.watchers.lock().unwrap();
+    watchers
+        .keys()
+        .filter(|root| Path::new(&key).starts_with(root.as_str()))
+        .max_by_key(|root| root.len())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Ignore rules for walking `root`: the global patterns from the preferences,
+/// then for local folders those of the vault's `.excaliapp.json` and
+/// `.excaliappignore`
+fn ignore_rules(app: &AppHandle, root: &storage::ResolvedPath) -> ignores::IgnoreRules {
+    let mut patterns = load_preferences(app).ignore_patterns;
+    if !root.is_local() {
+        return ignores::IgnoreRules::new(&root.path, &patterns);
+    }
+
+    let state = app.state::<AppState>();
+    let vault = vault_root(&state, &root.path);
+    let cached = state.configs.lock().unwrap().get(&paths::normalize(&vault)).cloned();
+    if let Some(config) = cached.or_else(|| config::load(&vault).ok().flatten()) {
+        patterns.extend(config.ignore_patterns);
+    }
+    patterns.extend(ignores::read_ignore_file(&vault));
+    ignores::IgnoreRules::new(&vault, &patterns)
+}
+
+#[tauri::command]
+async fn get_directory_config(
+    directory: String,
+    state: State<'_, AppState>,
+) -> Result<Option<config::DirectoryConfig>, AppError> {
+    let validated_dir = state.vault.check(Path::new(&directory))?;
+    if let Some(config) = refresh_directory_config(&state, &validated_dir)? {
+        return Ok(Some(config));
+    }
+    // Subfolders inherit the config of their vault root
+    Ok(directory_config_for(&state, &validated_dir).map(|(_, config)| config))
+}
+
+#[tauri::command]
+async fn get_file_tree(
+    app: AppHandle,
+    window: WebviewWindow,
+    directory: String,
+    max_depth: Option<usize>,
+    max_entries: Option<usize>,
+    sort: Option<TreeSort>,
+) -> Result<Vec<FileTreeNode>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let root = resolve_root_in_vault(state, &directory)?;
+
+        if !root.backend.exists(&root.path) {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        if !directory.contains("://")
+            && let Err(e) = refresh_directory_config(state, &root.path)
+        {
+            error!("{}", e);
+        }
+
+        let emit_progress = |scanned: usize, done: bool| {
+            let _ = app.emit(
+                "file-tree-progress",
+                serde_json::json!({ "directory": directory, "scanned": scanned, "done": done }),
+            );
+        };
+        let ignore = ignore_rules(app, &root);
+        let mut scan = TreeScan {
+            ignore: &ignore,
+            max_depth,
+            max_entries,
+            sort: sort.unwrap_or_default(),
+            scanned: 0,
+            on_progress: &|scanned| emit_progress(scanned, false),
+        };
+        let mut tree = read_tree(&root, &mut scan)?;
+        emit_progress(scan.scanned, true);
+        mark_modified(&mut tree, &state.windows.modified_files(window.label()));
+        pins::mark(&mut tree, &pinned_files(app, state, window.label()));
+        read_only::mark(&mut tree, &load_preferences(app).read_only_paths);
+        mark_sync_status(app, &mut tree);
+        merge::group_conflicts(&mut tree);
+        Ok(tree)
+    })
+    .await
+}
+
+/// Lists one level of a folder, a page at a time. `root` is the tree the
+/// folder belongs to, so relative paths and ignore rules match the full tree.
+#[tauri::command]
+async fn get_file_tree_children(
+    app: AppHandle,
+    window: WebviewWindow,
+    path: String,
+    root: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    sort: Option<TreeSort>,
+) -> Result<FileTreePage, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let folder = resolve_root_in_vault(state, &path)?;
+        let tree_root = match root {
+            Some(root) => resolve_root_in_vault(state, &root)?,
+            None => resolve_root_in_vault(state, &path)?,
+        };
+        if !tree_root.same_mount(&folder) || !folder.path.starts_with(&tree_root.path) {
+            return Err(AppError::InvalidPath("Folder is outside the tree root".to_string()));
+        }
+        if !folder.backend.is_dir(&folder.path) {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        let ignore = ignore_rules(app, &tree_root);
+        let mut scan = TreeScan {
+            ignore: &ignore,
+            max_depth: Some(1),
+            max_entries: None,
+            sort: sort.unwrap_or_default(),
+            scanned: 0,
+            on_progress: &|_| {},
+        };
+        let mut children = Vec::new();
+        build_file_tree(&tree_root, &folder.path, 0, &mut scan, &mut children)?;
+        sort_tree(&mut children, scan.sort);
+        mark_modified(&mut children, &state.windows.modified_files(window.label()));
+        pins::mark(&mut children, &pinned_files(app, state, window.label()));
+        read_only::mark(&mut children, &load_preferences(app).read_only_paths);
+        mark_sync_status(app, &mut children);
+        merge::group_conflicts(&mut children);
+
+        let total = children.len();
+        let offset = offset.unwrap_or(0).min(total);
+        let end = limit.map_or(total, |limit| offset.saturating_add(limit).min(total));
+        Ok(FileTreePage {
+            children: children.drain(offset..end).collect(),
+            total,
+            next_offset: (end < total).then_some(end),
+        })
+    })
+    .await
+}
+
+fn load_sync_store<T: serde::de::DeserializeOwned + Default>(app: &AppHandle, key: &str) -> T {
+    use tauri_plugin_store::StoreExt;
+
+    app.store(sync::STORE_FILE)
+        .ok()
+        .and_then(|store| store.get(key))
+        .and_then(|value| serde_json::from_value(value).ok())
+        .unwrap_or_default()
+}
+
+fn save_sync_store<T: Serialize>(app: &AppHandle, key: &str, value: &T) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(sync::STORE_FILE)?;
+    store.set(key, serde_json::to_value(value)?);
+    store.save()?;
+    Ok(())
+}
+
+/// Sets `sync_status` on the files of synced vaults
+fn mark_sync_status(app: &AppHandle, tree: &mut [FileTreeNode]) {
+    let configs: sync::ConfigMap = load_sync_store(app, sync::CONFIGS_KEY);
+    if configs.is_empty() {
+        return;
+    }
+    let states: sync::StateMap = load_sync_store(app, sync::STATE_KEY);
+    let none = HashMap::new();
+    for vault in configs.keys() {
+        sync::mark(tree, Path::new(vault), states.get(vault).unwrap_or(&none));
+    }
+}
+
+/// Sets `modified` on the nodes of files with unsaved changes
+fn mark_modified(tree: &mut [FileTreeNode], modified_files: &[String]) {
+    if modified_files.is_empty() {
+        return;
+    }
+    for node in tree {
+        node.modified = modified_files.contains(&node.path);
+        if let Some(children) = node.children.as_mut() {
+            mark_modified(children, modified_files);
+        }
+    }
+}
+
+/// Directories first, then by `sort`, falling back to names
+fn sort_tree(tree: &mut [FileTreeNode], sort: TreeSort) {
+    tree.sort_by(|a, b| {
+        b.is_directory
+            .cmp(&a.is_directory)
+            .then_with(|| match sort {
+                TreeSort::Name => std::cmp::Ordering::Equal,
+                TreeSort::Modified => b.metadata.modified_at.cmp(&a.metadata.modified_at),
+                TreeSort::Size => b.metadata.size.cmp(&a.metadata.size),
+            })
+            .then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+fn read_tree(root: &storage::ResolvedPath, scan: &mut TreeScan) -> Result<Vec<FileTreeNode>, AppError> {
+    let mut tree = Vec::new();
+    build_file_tree(root, &root.path, 0, scan, &mut tree)?;
+    sort_tree(&mut tree, scan.sort);
+    Ok(tree)
+}
+
+#[tauri::command]
+async fn add_workspace_root(
+    app: AppHandle,
+    path: String,
+) -> Result<Vec<workspace::WorkspaceRoot>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let validated = security::validate_path(Path::new(&path), None)?;
+        if !validated.is_dir() {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+        // Only folders the user picked become roots
+        state.vault.check(&validated)?;
+
+        let mut preferences = load_preferences(app);
+        if workspace::add_root(&mut preferences.workspace_roots, paths::normalize(&validated))? {
+            store_preferences(app, &preferences)?;
+        }
+        start_watching(app, state, &validated)?;
+
+        Ok(preferences.workspace_roots.iter().map(|r| workspace::root_info(r)).collect())
+    })
+    .await
+}
+
+#[tauri::command]
+async fn remove_workspace_root(
+    app: AppHandle,
+    window: WebviewWindow,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<workspace::WorkspaceRoot>, AppError> {
+    let key = paths::normalize(Path::new(&path));
+
+    let mut preferences = load_preferences(&app);
+    if workspace::remove_root(&mut preferences.workspace_roots, &key) {
+        store_preferences(&app, &preferences)?;
+    }
+    // The folder opened through watch_directory keeps its watcher
+    if current_workspace_key(&state, window.label()).as_deref() != Some(key.as_str()) {
+        stop_watching(&state, &key);
+    }
+
+    Ok(preferences.workspace_roots.iter().map(|r| workspace::root_info(r)).collect())
+}
+
+/// The workspace as a forest, one tree per root; without configured roots
+/// the folder opened through watch_directory is the only root
+#[tauri::command]
+async fn get_workspace_tree(
+    app: AppHandle,
+    window: WebviewWindow,
+) -> Result<Vec<workspace::WorkspaceTree>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let mut roots = load_preferences(app).workspace_roots;
+        if roots.is_empty() {
+            roots.extend(current_workspace_key(state, window.label()));
+        }
+
+        Ok(roots
+            .iter()
+            .map(|root| {
+                let tree = resolve_root_in_vault(state, root).and_then(|resolved| {
+                    if !resolved.backend.exists(&resolved.path) {
+                        return Err(AppError::NotFound("Directory does not exist".to_string()));
+                    }
+                    let ignore = ignore_rules(app, &resolved);
+                    let mut scan = TreeScan {
+                        ignore: &ignore,
+                        max_depth: None,
+                        max_entries: None,
+                        sort: TreeSort::Name,
+                        scanned: 0,
+                        on_progress: &|_| {},
+                    };
+                    let mut tree = read_tree(&resolved, &mut scan)?;
+                    merge::group_conflicts(&mut tree);
+                    Ok(tree)
+                });
+                let (children, error) = match tree {
+                    Ok(children) => (children, None),
+                    Err(e) => (Vec::new(), Some(e.to_string())),
+                };
+                workspace::WorkspaceTree {
+                    root: workspace::root_info(root),
+                    children,
+                    error,
+                }
+            })
+            .collect())
+    })
+    .await
+}
+
+#[tauri::command]
+async fn check_workspace_health(
+    app: AppHandle,
+    directory: String,
+) -> Result<health::WorkspaceHealthReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let validated_dir = state.vault.check(Path::new(&directory))?;
+
+        if !validated_dir.is_dir() {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        let ignore = ignore_rules(app, &storage::ResolvedPath::local(&validated_dir));
+        health::check_workspace(&validated_dir, &ignore, &state.background)
+    })
+    .await
+}
+
+/// Checks the content, bindings and images of every drawing below `directory`
+#[tauri::command]
+async fn scan_vault(
+    app: AppHandle,
+    directory: String,
+) -> Result<integrity::IntegrityReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let validated_dir = state.vault.check(Path::new(&directory))?;
+        if !validated_dir.is_dir() {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        let ignore = ignore_rules(app, &storage::ResolvedPath::local(&validated_dir));
+        let report = integrity::scan(&validated_dir, &ignore, &state.background)?;
+        info!(
+            "Scanned {} drawing(s) in {}: {} issue(s)",
+            report.files_scanned,
+            directory,
+            report.issues.len()
+        );
+        Ok(report)
+    })
+    .await
+}
+
+/// Checks a drawing against the checksum saved in it
+#[tauri::command]
+async fn verify_file_integrity(
+    app: AppHandle,
+    path: String,
+) -> Result<checksum::FileVerification, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let file = resolve_in_vault(state, &path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        check_compression_supported(&file)?;
+
+        let content = file.backend.read_to_string(&file.path)?;
+        let mut verification = checksum::verify(&file.path, &content);
+        verification.path = file.to_external(&file.path);
+        Ok(verification)
+    })
+    .await
+}
+
+/// Checks every drawing below `directory` against its saved checksum
+#[tauri::command]
+async fn verify_vault_integrity(
+    app: AppHandle,
+    directory: String,
+) -> Result<checksum::VaultVerification, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let validated_dir = state.vault.check(Path::new(&directory))?;
+        if !validated_dir.is_dir() {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        let ignore = ignore_rules(app, &storage::ResolvedPath::local(&validated_dir));
+        let report = checksum::verify_vault(&validated_dir, &ignore, &state.background)?;
+        info!(
+            "Verified {} drawing(s) in {}: {} failed, {} without checksum",
+            report.files_checked,
+            directory,
+            report.failed.len(),
+            report.missing
+        );
+        Ok(report)
+    })
+    .await
+}
+
+/// Applies the selected integrity fixes to one drawing, keeping the old
+/// version as a backup
+#[tauri::command]
+async fn repair_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    file_path: String,
+    fixes: Vec<integrity::IntegrityFix>,
+) -> Result<integrity::IntegrityRepair, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        let preferences = load_preferences(app);
+        read_only::check(&preferences.read_only_paths, &file.to_external(&file.path))?;
+
+        let previous = file.backend.read_to_string(&file.path)?;
+        let (content, applied) = integrity::apply(&previous, &fixes)?;
+        let content = checksum::refresh(content);
+        if content != previous {
+            security::validate_excalidraw_content(&content)?;
+            if let Err(e) = backups::rotate(file.backend.as_ref(), &file.path, &previous, &preferences.backup) {
+                warn!("[repair_file] {}", e);
+            }
+            state.recent_saves.record(&file.path, &content);
+            state.open_files.note_write(&file.path, &content);
+            state.content_cache.invalidate(&file.path);
+            file.backend.write(&file.path, &content)?;
+            record_activity(
+                app,
+                activity::ActivityEntry::new(
+                    activity::ActivityKind::Saved,
+                    file.to_external(&file.path),
+                    current_workspace_key(state, window.label()),
+                ),
+                Some(&previous),
+            );
+            info!("Repaired {}: {} change(s)", file_path, applied.len());
+        }
+
+        Ok(integrity::IntegrityRepair {
+            applied,
+            remaining: integrity::check(&file.path, &content),
+        })
+    })
+    .await
+}
+
+/// Drawings below `directory` that are copies or near copies of each other
+#[tauri::command]
+async fn find_duplicate_scenes(
+    app: AppHandle,
+    directory: String,
+) -> Result<Vec<duplicates::DuplicateGroup>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let validated_dir = state.vault.check(Path::new(&directory))?;
+        if !validated_dir.is_dir() {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        let ignore = ignore_rules(app, &storage::ResolvedPath::local(&validated_dir));
+        let mut files = Vec::new();
+        collect_excalidraw_files_recursive(&validated_dir, &ignore, &mut files)?;
+        let fingerprinted = state.background.run(files, |file| {
+            let content = compression::read_to_string(Path::new(&file.path)).unwrap_or_default();
+            state.background.throttle_io(content.len());
+            duplicates::fingerprint(&content).map(|fingerprint| (file.path, file.metadata, fingerprint))
+        });
+
+        let groups = duplicates::group(fingerprinted.into_iter().flatten().collect());
+        info!("Found {} group(s) of duplicate drawings in {}", groups.len(), directory);
+        Ok(groups)
+    })
+    .await
+}
+
+/// Deletes the drawings in `remove` as duplicates of `keep`; `merge` first
+/// copies their elements `keep` lacks into it
+#[tauri::command]
+async fn resolve_duplicates(
+    app: AppHandle,
+    window: WebviewWindow,
+    keep: String,
+    remove: Vec<String>,
+    merge: Option<bool>,
+) -> Result<batch::BatchReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let label = window.label();
+        let file = resolve_in_vault(state, &keep)?;
+        security::validate_excalidraw_file(&file.path)?;
+        let kept = file.to_external(&file.path);
+        if remove.iter().any(|path| resolve_in_vault(state, path).is_ok_and(|other| other.to_external(&other.path) == kept)) {
+            return Err(AppError::InvalidInput("The drawing to keep is among the duplicates".to_string()));
+        }
+
+        if merge.unwrap_or(false) {
+            let previous = file.backend.read_to_string(&file.path)?;
+            let mut content = previous.clone();
+            let mut added = 0;
+            for path in &remove {
+                let other = resolve_in_vault(state, path)?;
+                security::validate_excalidraw_file(&other.path)?;
+                let (merged, count) = duplicates::merge(&content, &other.backend.read_to_string(&other.path)?)?;
+                content = merged;
+                added += count;
+            }
+            if added > 0 {
+                let content = checksum::refresh(content);
+                security::validate_excalidraw_content(&content)?;
+                let preferences = load_preferences(app);
+                if let Err(e) = backups::rotate(file.backend.as_ref(), &file.path, &previous, &preferences.backup) {
+                    warn!("[resolve_duplicates] {}", e);
+                }
+                state.recent_saves.record(&file.path, &content);
+                state.open_files.note_write(&file.path, &content);
+                state.content_cache.invalidate(&file.path);
+                file.backend.write(&file.path, &content)?;
+                record_activity(
+                    app,
+                    activity::ActivityEntry::new(activity::ActivityKind::Saved, kept.clone(), current_workspace_key(state, label)),
+                    Some(&previous),
+                );
+                info!("Merged {} element(s) from duplicates into {}", added, kept);
+            }
+        }
+
+        let _paused = WatcherPause::new(app);
+        let report = batch::run(
+            batch::BatchOperation::Delete,
+            remove,
+            |path| delete_file_at(app, state, label, path).map(|()| None),
+            |progress| emit_batch_progress(app, label, progress),
+        );
+        info!("Removed duplicates of {}: {} deleted, {} failed", kept, report.succeeded, report.failed);
+        Ok(report)
+    })
+    .await
+}
+
+/// Three-way merge of the open drawing's unsaved edits (`mine`) with a
+/// version changed elsewhere (`theirs`), both made from `base`
+#[tauri::command]
+async fn merge_scene_changes(
+    base: Option<String>,
+    mine: String,
+    theirs: String,
+) -> Result<merge::SceneMerge, AppError> {
+    let merged = merge::merge(base.as_deref(), &mine, &theirs)?;
+    info!(
+        "Merged scene changes: {} from here, {} from the other version, {} conflict(s)",
+        merged.from_mine,
+        merged.from_theirs,
+        merged.conflicts.len()
+    );
+    Ok(merged)
+}
+
+/// Conflict copies Dropbox, Syncthing or the app's own sync left below
+/// `directory`, next to the drawings they were made of
+#[tauri::command]
+async fn find_sync_conflicts(
+    app: AppHandle,
+    directory: String,
+) -> Result<Vec<merge::SyncConflictCopy>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let validated_dir = state.vault.check(Path::new(&directory))?;
+        if !validated_dir.is_dir() {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        let ignore = ignore_rules(app, &storage::ResolvedPath::local(&validated_dir));
+        let mut files = Vec::new();
+        collect_excalidraw_files_recursive(&validated_dir, &ignore, &mut files)?;
+        let paths: Vec<PathBuf> = files.iter().map(|f| PathBuf::from(&f.path)).collect();
+        Ok(merge::find_conflict_copies(&paths))
+    })
+    .await
+}
+
+/// Settles the sync conflict copy `conflict` of `original` by `strategy`
+/// and deletes the copy. Merging keeps the newer version of elements both
+/// changed; the returned merge is `None` for the other strategies.
+#[tauri::command]
+async fn resolve_sync_conflict(
+    app: AppHandle,
+    window: WebviewWindow,
+    original: String,
+    conflict: String,
+    strategy: merge::ConflictStrategy,
+) -> Result<Option<merge::SceneMerge>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let label = window.label();
+        let copy = resolve_in_vault(state, &conflict)?;
+        security::validate_excalidraw_file(&copy.path)?;
+        check_compression_supported(&copy)?;
+        let file = resolve_in_vault(state, &original)?;
+        if merge::conflict_original(&copy.path).is_none_or(|(path, _)| path != file.path) {
+            return Err(AppError::InvalidInput(format!("{} is not a conflict copy of {}", conflict, original)));
+        }
+        if !file.backend.exists(&file.path) {
+            return Err(AppError::NotFound("The original drawing no longer exists".to_string()));
+        }
+
+        let mut merged = None;
+        if strategy != merge::ConflictStrategy::KeepOriginal {
+            let original = file.to_external(&file.path);
+            read_only::check(&load_preferences(app).read_only_paths, &original)?;
+
+            let previous = file.backend.read_to_string(&file.path)?;
+            let theirs = copy.backend.read_to_string(&copy.path)?;
+            let content = if strategy == merge::ConflictStrategy::Merge {
+                let mut merge = merge::merge(None, &previous, &theirs)?;
+                merge.content = checksum::refresh(merge.content);
+                let content = merge.content.clone();
+                merged = Some(merge);
+                content
+            } else {
+                theirs
+            };
+            security::validate_excalidraw_content(&content)?;
+
+            let preferences = load_preferences(app);
+            if let Err(e) = backups::rotate(file.backend.as_ref(), &file.path, &previous, &preferences.backup) {
+                warn!("[resolve_sync_conflict] {}", e);
+            }
+            // Not noted as our own write, so a window with the original open
+            // hears of the change and can merge its unsaved edits in turn
+            state.recent_saves.record(&file.path, &content);
+            state.content_cache.invalidate(&file.path);
+            file.backend.write(&file.path, &content)?;
+            record_activity(
+                app,
+                activity::ActivityEntry::new(activity::ActivityKind::Saved, original, current_workspace_key(state, label)),
+                Some(&previous),
+            );
+        }
+
+        let _paused = WatcherPause::new(app);
+        delete_file_at(app, state, label, &conflict)?;
+        info!(
+            "Resolved conflict copy {} of {} ({:?}): {} conflict(s)",
+            conflict,
+            original,
+            strategy,
+            merged.as_ref().map_or(0, |m| m.conflicts.len())
+        );
+        Ok(merged)
+    })
+    .await
+}
+
+/// What takes up space in a folder and which drawings need attention
+#[tauri::command]
+async fn get_vault_stats(
+    app: AppHandle,
+    directory: String,
+) -> Result<vault_stats::VaultStats, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let validated_dir = state.vault.check(Path::new(&directory))?;
+        if !validated_dir.is_dir() {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        ensure_metadata_index(app, state, &validated_dir)?;
+        let ignore = ignore_rules(app, &storage::ResolvedPath::local(&validated_dir));
+        vault_stats::collect(&validated_dir, &ignore, &state.metadata, &state.background, prompts::now_millis())
+    })
+    .await
+}
+
+fn load_background_settings(app: &AppHandle) -> background::BackgroundSettings {
+    use tauri_plugin_store::StoreExt;
+
+    let Ok(store) = app.store(background::STORE_FILE) else {
+        return background::BackgroundSettings::default();
+    };
+
+    match store.get(background::STORE_KEY) {
+        Some(value) => serde_json::from_value(value).unwrap_or_else(|e| {
+            warn!("Failed to deserialize background settings: {}", e);
+            background::BackgroundSettings::default()
+        }),
+        None => background::BackgroundSettings::default(),
+    }
+}
+
+/// Queued and running background jobs, running ones first
+#[tauri::command]
+async fn get_background_tasks(state: State<'_, AppState>) -> Result<Vec<background::BackgroundTask>, AppError> {
+    Ok(state.background.tasks())
+}
+
+/// Drops a queued background job or stops a running one after its current
+/// step; false when no job has that id
+#[tauri::command]
+async fn cancel_background_task(id: u64, state: State<'_, AppState>) -> Result<bool, AppError> {
+    Ok(state.background.cancel(id))
+}
+
+#[tauri::command]
+async fn get_background_settings(state: State<'_, AppState>) -> Result<background::BackgroundSettings, AppError> {
+    Ok(state.background.settings())
+}
+
+#[tauri::command]
+async fn set_background_settings(
+    app: AppHandle,
+    settings: background::BackgroundSettings,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    if settings.max_threads == 0 {
+        return Err(AppError::InvalidInput("max_threads must be at least 1".to_string()));
+    }
+    if settings.io_limit_mb_per_sec.is_some_and(|limit| limit <= 0.0) {
+        return Err(AppError::InvalidInput("io_limit_mb_per_sec must be positive".to_string()));
+    }
+
+    let store = app.store(background::STORE_FILE)?;
+    store.set(
+        background::STORE_KEY,
+        serde_json::to_value(&settings)?,
+    );
+    store.save()?;
+
+    state.background.update(settings);
+    Ok(())
+}
+
+fn load_share_permissions(app: &AppHandle) -> Result<share::PermissionMap, AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(share::STORE_FILE)?;
+
+    Ok(store
+        .get(share::STORE_KEY)
+        .and_then(|value| serde_json::from_value::<share::PermissionMap>(value).ok())
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+async fn get_share_permissions(
+    app: AppHandle,
+    file_path: String,
+) -> Result<Option<share::SharePermissions>, AppError> {
+    let validated_path = security::validate_path(Path::new(&file_path), None)?;
+    let key = paths::normalize(&validated_path);
+    Ok(load_share_permissions(&app)?.remove(&key))
+}
+
+#[tauri::command]
+async fn set_share_permissions(
+    app: AppHandle,
+    file_path: String,
+    permissions: Option<share::SharePermissions>,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let validated_path = state.vault.check(Path::new(&file_path))?;
+    security::validate_excalidraw_file(&validated_path)?;
+
+    if let Some(expires_at) = permissions.as_ref().and_then(|p| p.expires_at.as_deref()) {
+        share::parse_date(expires_at)?;
+    }
+
+    let key = paths::normalize(&validated_path);
+    let mut all = load_share_permissions(&app)?;
+    match permissions {
+        Some(p) => {
+            all.insert(key, p);
+        }
+        None => {
+            all.remove(&key);
+        }
+    }
+
+    let store = app.store(share::STORE_FILE)?;
+    store.set(
+        share::STORE_KEY,
+        serde_json::to_value(&all)?,
+    );
+    Ok(store.save()?)
+}
+
+/// Serves a vault read-only over HTTP on the local network: a file list,
+/// SVG previews and raw files, behind a fresh access token. Restarts the
+/// server when one is running already.
+#[tauri::command]
+async fn start_share_server(
+    app: AppHandle,
+    directory: String,
+    port: Option<u16>,
+    state: State<'_, AppState>,
+) -> Result<share_server::ShareServerStatus, AppError> {
+    let dir = resolve_in_vault(&state, &directory)?;
+    if !dir.is_local() {
+        return Err(AppError::InvalidPath("Only local folders can be shared".to_string()));
+    }
+    if !dir.backend.is_dir(&dir.path) {
+        return Err(AppError::NotFound("Directory does not exist".to_string()));
+    }
+    let all_branding = load_export_branding(&app)?;
+    let context = share_server::ShareContext {
+        ignore: ignore_rules(&app, &dir),
+        permissions: load_share_permissions(&app)?,
+        branding: branding::for_path(&all_branding, &dir.path).cloned(),
+        root: dir.path.clone(),
+    };
+
+    let mut running = state.share_server.lock().unwrap();
+    // Frees the port before binding it again
+    if let Some(server) = running.take() {
+        server.stop();
+    }
+    let server = share_server::ShareServer::start(
+        context,
+        paths::normalize(&dir.path),
+        port.unwrap_or(share_server::DEFAULT_PORT),
+        prompts::now_millis(),
+    )?;
+    let status = server.status();
+    *running = Some(server);
+    info!("Sharing {} on port {:?}", directory, status.port);
+    let _ = app.emit(share_server::STATUS_EVENT, &status);
+    Ok(status)
+}
+
+#[tauri::command]
+async fn stop_share_server(app: AppHandle, state: State<'_, AppState>) -> Result<(), AppError> {
+    let server = state.share_server.lock().unwrap().take();
+    if let Some(server) = server {
+        server.stop();
+        info!("Stopped sharing");
+        let _ = app.emit(share_server::STATUS_EVENT, share_server::ShareServerStatus::default());
+    }
+    Ok(())
+}
+
+/// Forwards what a collaboration session reports to the window using it
+fn collab_notify(app: &AppHandle, label: &str) -> collab::Notify {
+    let app = app.clone();
+    let label = label.to_string();
+    std::sync::Arc::new(move |event| {
+        let _ = match event {
+            collab::CollabEvent::Update(update) => app.emit_to(&label, collab::UPDATE_EVENT, update),
+            collab::CollabEvent::Status(status) => app.emit_to(&label, collab::STATUS_EVENT, status),
+        };
+    })
+}
+
+/// Ends the running collaboration session; the session's threads are
+/// joined outside the state lock, since they report through it
+fn end_collab_session(state: &AppState) {
+    let session = state.collab.lock().unwrap().take();
+    if let Some(session) = session {
+        session.stop();
+    }
+}
+
+/// Lets other instances on the network co-edit a drawing: starts a
+/// WebSocket server that relays element changes between peers, merging
+/// them element by element (last writer wins). Ends a running session first.
+#[tauri::command]
+async fn host_collab_session(
+    app: AppHandle,
+    window: WebviewWindow,
+    file_path: String,
+    port: Option<u16>,
+    state: State<'_, AppState>,
+) -> Result<collab::CollabStatus, AppError> {
+    let file = resolve_in_vault(&state, &file_path)?;
+    let content = file.backend.read_to_string(&file.path)?;
+    security::validate_excalidraw_content(&content)?;
+
+    end_collab_session(&state);
+    let session = collab::CollabSession::host(
+        paths::normalize(&file.path),
+        &content,
+        port.unwrap_or(collab::DEFAULT_PORT),
+        collab_notify(&app, window.label()),
+    )?;
+    let status = session.status();
+    *state.collab.lock().unwrap() = Some(session);
+    info!("Hosting collaboration on {} at {:?}", file_path, status.urls);
+    Ok(status)
+}
+
+/// Connects to a session hosted by another instance; the host's elements
+/// arrive as the first `collab-update`. Ends a running session first.
+#[tauri::command]
+async fn join_collab_session(
+    app: AppHandle,
+    window: WebviewWindow,
+    url: String,
+    state: State<'_, AppState>,
+) -> Result<collab::CollabStatus, AppError> {
+    end_collab_session(&state);
+    let notify = collab_notify(&app, window.label());
+    let session = blocking::run(move || collab::CollabSession::join(&url, notify)).await?;
+    let status = session.status();
+    *state.collab.lock().unwrap() = Some(session);
+    info!("Joined collaboration session");
+    Ok(status)
+}
+
+/// Sends local edits to the peers; elements older than what the session
+/// already has are dropped. Returns how many were sent.
+#[tauri::command]
+async fn send_collab_update(elements: Vec<serde_json::Value>, state: State<'_, AppState>) -> Result<usize, AppError> {
+    let collab = state.collab.lock().unwrap();
+    let session = collab
+        .as_ref()
+        .ok_or_else(|| AppError::InvalidInput("No collaboration session is running".to_string()))?;
+    Ok(session.publish(elements))
+}
+
+#[tauri::command]
+async fn leave_collab_session(state: State<'_, AppState>) -> Result<(), AppError> {
+    end_collab_session(&state);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_collab_status(state: State<'_, AppState>) -> Result<collab::CollabStatus, AppError> {
+    Ok(state
+        .collab
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|session| session.status())
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+async fn get_share_server_status(state: State<'_, AppState>) -> Result<share_server::ShareServerStatus, AppError> {
+    Ok(state
+        .share_server
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|server| server.status())
+        .unwrap_or_default())
+}
+
+fn load_export_branding(app: &AppHandle) -> Result<branding::BrandingMap, AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(branding::STORE_FILE)?;
+
+    Ok(store
+        .get(branding::STORE_KEY)
+        .and_then(|value| serde_json::from_value::<branding::BrandingMap>(value).ok())
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+async fn get_export_branding(
+    app: AppHandle,
+    directory: String,
+) -> Result<Option<branding::ExportBranding>, AppError> {
+    let validated_dir = security::validate_path(Path::new(&directory), None)?;
+    let key = paths::normalize(&validated_dir);
+    Ok(load_export_branding(&app)?.remove(&key))
+}
+
+#[tauri::command]
+async fn set_export_branding(
+    app: AppHandle,
+    directory: String,
+    branding: Option<branding::ExportBranding>,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let validated_dir = state.vault.check(Path::new(&directory))?;
+    if !validated_dir.is_dir() {
+        return Err(AppError::NotFound("Directory does not exist".to_string()));
+    }
+
+    let key = paths::normalize(&validated_dir);
+    let mut all = load_export_branding(&app)?;
+    match branding {
+        Some(b) => {
+            all.insert(key, b);
+        }
+        None => {
+            all.remove(&key);
+        }
+    }
+
+    let store = app.store(branding::STORE_FILE)?;
+    store.set(
+        branding::STORE_KEY,
+        serde_json::to_value(&all)?,
+    );
+    Ok(store.save()?)
+}
+
+#[tauri::command]
+async fn export_slides(
+    app: AppHandle,
+    path_or_dir: String,
+    format: String,
+    permissions: Option<share::SharePermissions>,
+    output: Option<String>,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let validated_path = state.vault.check(Path::new(&path_or_dir))?;
+
+        if !validated_path.exists() {
+            return Err(AppError::NotFound("Path does not exist".to_string()));
+        }
+        let output = output
+            .map(|output| state.vault.check(Path::new(&output)))
+            .transpose()?;
+
+        let format = slides::SlideFormat::parse(&format)?;
+
+        // Explicit permissions apply to every file; otherwise use each file's stored ones
+        let stored = load_share_permissions(app)?;
+        let all_branding = load_export_branding(app)?;
+        let output = slides::export(
+            &validated_path,
+            format,
+            |file| {
+                permissions
+                    .clone()
+                    .or_else(|| stored.get(&paths::normalize(file)).cloned())
+            },
+            branding::for_path(&all_branding, &validated_path),
+            &ignore_rules(app, &storage::ResolvedPath::local(&validated_path)),
+            output.as_deref(),
+        )?;
+        Ok(paths::normalize(&output))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn batch_export(
+    app: AppHandle,
+    window: WebviewWindow,
+    directory: String,
+    output_dir: Option<String>,
+    force: Option<bool>,
+) -> Result<export::BatchExportReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let validated_dir = state.vault.check(Path::new(&directory))?;
+        if !validated_dir.is_dir() {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        // Without an explicit target, fall back to the vault's configured export folder
+        let vault = directory_config_for(state, &validated_dir);
+        let output_dir = match output_dir {
+            Some(output_dir) => PathBuf::from(output_dir),
+            None => vault
+                .as_ref()
+                .and_then(|(root, config)| config.export_dir(root))
+                .ok_or_else(|| AppError::InvalidInput("No output directory given".to_string()))??,
+        };
+        let output_dir = state.vault.check(&output_dir)?;
+        let all_branding = load_export_branding(app)?;
+        let branding = branding::for_path(&all_branding, &validated_dir)
+            .or_else(|| vault.as_ref().and_then(|(_, config)| config.export.branding.as_ref()));
+
+        let _paused = WatcherPause::new(app);
+        let report = export::batch_export(
+            &validated_dir,
+            &output_dir,
+            branding,
+            force.unwrap_or(false),
+            &ignore_rules(app, &storage::ResolvedPath::local(&validated_dir)),
+            &state.background,
+        )?;
+        info!(
+            "Batch export: {} exported, {} unchanged, {} failed",
+            report.exported.len(),
+            report.skipped.len(),
+            report.failed.len()
+        );
+
+        let workspace = current_workspace_key(state, window.label());
+        remember_location(app, workspace.as_deref(), locations::LocationKind::Export, &output_dir);
+        Ok(report)
+    })
+    .await
+}
+
+/// Zips the drawings and settings of a folder, and optionally its exported
+/// images and assets, into one file; progress arrives as `archive-progress`,
+/// and as `operation-progress` when started with an `operation_id`
+#[tauri::command]
+async fn archive_directory(
+    app: AppHandle,
+    window: WebviewWindow,
+    directory: String,
+    output_path: String,
+    include_exports: Option<bool>,
+    operation_id: Option<String>,
+) -> Result<archive::ArchiveReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let dir = resolve_in_vault(state, &directory)?;
+        if !dir.is_local() {
+            return Err(AppError::InvalidPath("Only local folders can be archived".to_string()));
+        }
+        if !dir.backend.is_dir(&dir.path) {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+        let output = state.vault.check(Path::new(&output_path))?;
+        let entries = archive_entries(app, state, &dir, include_exports.unwrap_or(false), &output)?;
+
+        let label = window.label();
+        let operation = state.operations.start(app, label, operation_id)?;
+        let report = archive::write(&entries, &output, |progress| {
+            operation.progress(progress.done, progress.total, progress.path.as_str());
+            let _ = app.emit_to(label, archive::PROGRESS_EVENT, progress);
+            !operation.is_cancelled()
+        })
+        .map_err(|e| if operation.is_cancelled() { AppError::Cancelled(e.message()) } else { e })?;
+        info!("Archived {} files from {} to {}", report.files, directory, report.output_path);
+
+        if let Some(parent) = output.parent() {
+            let workspace = current_workspace_key(state, label);
+            remember_location(app, workspace.as_deref(), locations::LocationKind::Export, parent);
+        }
+        Ok(report)
+    })
+    .await
+}
+
+/// Files of `dir` to archive into `output`; with `include_exports`, images
+/// too, and a configured export folder outside the vault under exports/
+fn archive_entries(
+    app: &AppHandle,
+    state: &AppState,
+    dir: &storage::ResolvedPath,
+    include_exports: bool,
+    output: &Path,
+) -> Result<Vec<archive::ArchiveEntry>, AppError> {
+    let mut entries = Vec::new();
+    archive::collect(&dir.path, "", &ignore_rules(app, dir), include_exports, output, &mut entries)?;
+    if include_exports
+        && let Some((root, config)) = directory_config_for(state, &dir.path)
+        && let Some(Ok(export_dir)) = config.export_dir(&root)
+        && !export_dir.starts_with(&dir.path)
+        && export_dir.is_dir()
+    {
+        archive::collect(
+            &export_dir,
+            archive::EXPORTS_PREFIX,
+            &ignores::IgnoreRules::none(),
+            true,
+            output,
+            &mut entries,
+        )?;
+    }
+    Ok(entries)
+}
+
+/// Extracts the drawings, settings and images of a zip file into a folder,
+/// reporting what was left out; progress arrives as `archive-progress`, and
+/// as `operation-progress` when started with an `operation_id`
+#[tauri::command]
+async fn import_archive(
+    app: AppHandle,
+    window: WebviewWindow,
+    zip_path: String,
+    target_directory: String,
+    operation_id: Option<String>,
+) -> Result<archive::ImportReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let zip_path = state.vault.check(Path::new(&zip_path))?;
+        if !zip_path.is_file() {
+            return Err(AppError::NotFound("Archive does not exist".to_string()));
+        }
+        let target = resolve_in_vault(state, &target_directory)?;
+        if !target.is_local() {
+            return Err(AppError::InvalidPath("Archives can only be imported into local folders".to_string()));
+        }
+        if !target.backend.is_dir(&target.path) {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        let limits = load_preferences(app).limits;
+        let label = window.label();
+        let operation = state.operations.start(app, label, operation_id)?;
+        let _paused = WatcherPause::new(app);
+        let report = archive::import(&zip_path, &target.path, &limits, |progress| {
+            operation.progress(progress.done, progress.total, progress.path.as_str());
+            let _ = app.emit_to(label, archive::PROGRESS_EVENT, progress);
+            !operation.is_cancelled()
+        })
+        .map_err(|e| if operation.is_cancelled() { AppError::Cancelled(e.message()) } else { e })?;
+        info!(
+            "Imported {} files from {}: {} skipped, {} invalid",
+            report.imported.len(),
+            zip_path.display(),
+            report.skipped.len(),
+            report.invalid.len()
+        );
+        Ok(report)
+    })
+    .await
+}
+
+fn collect_excalidraw_files_recursive(
+    dir: &Path,
+    ignore: &ignores::IgnoreRules,
+    files: &mut Vec<ExcalidrawFile>,
+) -> Result<(), AppError> {
+    collect_excalidraw_files(&storage::ResolvedPath::local(dir), dir, ignore, files)
+}
+
+fn collect_excalidraw_files(
+    root: &storage::ResolvedPath,
+    dir: &Path,
+    ignore: &ignores::IgnoreRules,
+    files: &mut Vec<ExcalidrawFile>,
+) -> Result<(), AppError> {
+    let entries = root.backend.read_dir(dir)?;
+    for entry in entries {
+        if ignore.is_ignored(&entry.path, entry.is_directory) {
+            continue;
+        }
+        if entry.is_directory {
+            collect_excalidraw_files(root, &entry.path, ignore, files)?;
+        } else if compression::is_drawing(&entry.path) {
+            files.push(ExcalidrawFile {
+                path: root.to_external(&entry.path),
+                relative_path: root.relative(&entry.path),
+                name: paths::compose(&entry.name),
+                modified: false,
+                metadata: entry.metadata,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// `depth` is the level of `dir` below the scanned root, starting at 0
+fn build_file_tree(
+    root: &storage::ResolvedPath,
+    dir: &Path,
+    depth: usize,
+    scan: &mut TreeScan,
+    tree: &mut Vec<FileTreeNode>,
+) -> Result<(), AppError> {
+    let entries = root.backend.read_dir(dir)?;
+    for entry in entries {
+        if scan.ignore.is_ignored(&entry.path, entry.is_directory) {
+            continue;
+        }
+        scan.scanned += 1;
+        if scan.scanned.is_multiple_of(TREE_PROGRESS_INTERVAL) {
+            (scan.on_progress)(scan.scanned);
+        }
+
+        if entry.is_directory {
+            let descend = scan.max_depth.is_none_or(|max| depth + 1 < max)
+                && scan.max_entries.is_none_or(|max| scan.scanned < max);
+            // Always include directories (don't filter empty ones)
+            let children = if descend {
+                let mut children = Vec::new();
+                build_file_tree(root, &entry.path, depth + 1, scan, &mut children)?;
+                sort_tree(&mut children, scan.sort);
+                Some(children)
+            } else {
+                None
+            };
+
+            tree.push(FileTreeNode {
+                name: paths::compose(&entry.name),
+                path: root.to_external(&entry.path),
+                relative_path: root.relative(&entry.path),
+                is_directory: true,
+                modified: false,
+                metadata: entry.metadata,
+                pinned: false,
+                read_only: false,
+                sync_status: None,
+                conflicts: Vec::new(),
+                children,
+            });
+        } else if compression::is_drawing(&entry.path) {
+            tree.push(FileTreeNode {
+                name: paths::compose(&entry.name),
+                path: root.to_external(&entry.path),
+                relative_path: root.relative(&entry.path),
+                is_directory: false,
+                modified: false,
+                metadata: entry.metadata,
+                pinned: false,
+                read_only: false,
+                sync_status: None,
+                conflicts: Vec::new(),
+                children: None,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn has_excalidraw_files(dir: &Path) -> Result<bool, AppError> {
+    match fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_file() {
+                    if compression::is_drawing(&path) {
+                        return Ok(true);
+                    }
+                } else if path.is_dir() && has_excalidraw_files(&path)? {
+                    return Ok(true);
+                }
+            }
+        }
+        Err(e) => return Err(e.into()),
+    }
+    Ok(false)
+}
+
+fn load_file_usage(app: &AppHandle) -> Result<frecency::UsageMap, AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(frecency::STORE_FILE)?;
+
+    if let Some(value) = store.get(frecency::STORE_KEY) {
+        match serde_json::from_value::<frecency::UsageMap>(value.clone()) {
+            Ok(usage) => Ok(usage),
+            Err(e) => {
+                warn!("Failed to deserialize file usage: {}", e);
+                Ok(frecency::UsageMap::new())
+            }
+        }
+    } else {
+        Ok(frecency::UsageMap::new())
+    }
+}
+
+/// Applies `update` to the stored file usage history. Tracking is best-effort:
+/// failures are logged and never fail the file operation that triggered them
+fn update_file_usage(app: &AppHandle, update: impl FnOnce(&mut frecency::UsageMap)) {
+    use tauri_plugin_store::StoreExt;
+
+    let result = load_file_usage(app).and_then(|mut usage| {
+        update(&mut usage);
+        let store = app.store(frecency::STORE_FILE)?;
+        store.set(
+            frecency::STORE_KEY,
+            serde_json::to_value(&usage)?,
+        );
+        store.save().map_err(AppError::from)
+    });
+
+    if let Err(e) = result {
+        warn!("Failed to update file usage: {}", e);
+    }
+}
+
+/// Pinned drawings of the window's workspace, in the order they were pinned
+fn pinned_files(app: &AppHandle, state: &AppState, label: &str) -> Vec<String> {
+    current_workspace_key(state, label)
+        .and_then(|workspace| load_preferences(app).pinned_files.remove(&workspace))
+        .unwrap_or_default()
+}
+
+/// Applies `update` to the pins of every workspace, storing them when it
+/// reports a change. Like usage tracking, failures are only logged
+fn update_pins(app: &AppHandle, update: impl FnOnce(&mut pins::PinMap) -> bool) {
+    let mut preferences = load_preferences(app);
+    if update(&mut preferences.pinned_files)
+        && let Err(e) = store_preferences(app, &preferences)
+    {
+        warn!("Failed to update pinned files: {}", e);
+    }
+}
+
+fn record_file_usage(app: &AppHandle, file_path: &str, kind: frecency::UsageKind) {
+    update_file_usage(app, |usage| {
+        frecency::record(usage, file_path, kind, prompts::now_millis())
+    });
+}
+
+fn load_activity(app: &AppHandle) -> Result<Vec<activity::ActivityEntry>, AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(activity::STORE_FILE)?;
+
+    if let Some(value) = store.get(activity::STORE_KEY) {
+        match serde_json::from_value::<Vec<activity::ActivityEntry>>(value.clone()) {
+            Ok(journal) => return Ok(journal),
+            Err(e) => {
+                warn!("Failed to deserialize activity journal: {}", e);
+            }
+        }
+    }
+
+    Ok(Vec::new())
+}
+
+fn store_activity(app: &AppHandle, journal: &[activity::ActivityEntry]) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(activity::STORE_FILE)?;
+    store.set(
+        activity::STORE_KEY,
+        serde_json::to_value(journal)?,
+    );
+    store.save().map_err(AppError::from)
+}
+
+fn snapshot_path(app: &AppHandle, snapshot_id: &str) -> Result<PathBuf, AppError> {
+    let dir = app
+        .path()
+        .app_data_dir()?
+        .join(activity::SNAPSHOT_DIR);
+    Ok(dir.join(activity::snapshot_file_name(snapshot_id)?))
+}
+
+/// Journals a file operation, snapshotting `previous_content` (the content
+/// before the change) when given; failures are logged, not propagated
+fn record_activity(app: &AppHandle, entry: activity::ActivityEntry, previous_content: Option<&str>) {
+    let result = load_activity(app).and_then(|mut journal| {
+        if entry.kind == activity::ActivityKind::Saved
+            && activity::coalesce_save(&mut journal, &entry.path, entry.timestamp)
+        {
+            return store_activity(app, &journal);
+        }
+
+        let entry = match previous_content {
+            Some(content) => {
+                let entry = entry.with_snapshot();
+                let path = snapshot_path(app, &entry.id)?;
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(&path, content)?;
+                entry
+            }
+            None => entry,
+        };
+
+        for snapshot_id in activity::append(&mut journal, entry) {
+            if let Ok(path) = snapshot_path(app, &snapshot_id) {
+                let _ = fs::remove_file(path);
+            }
+        }
+        store_activity(app, &journal)
+    });
+
+    if let Err(e) = result {
+        warn!("Failed to record activity: {}", e);
+    }
+}
+
+#[tauri::command]
+async fn get_activity_timeline(
+    app: AppHandle,
+    window: WebviewWindow,
+    range: Option<String>,
+    all_workspaces: Option<bool>,
+    limit: Option<usize>,
+    state: State<'_, AppState>,
+) -> Result<activity::ActivityTimeline, AppError> {
+    let journal = load_activity(&app)?;
+    let workspace = match all_workspaces.unwrap_or(false) {
+        true => None,
+        false => current_workspace_key(&state, window.label()),
+    };
+    activity::timeline(
+        &journal,
+        range.as_deref().unwrap_or("week"),
+        workspace.as_deref(),
+        limit.unwrap_or(500),
+        prompts::now_millis(),
+    )
+}
+
+/// Content of a version snapshot, for previewing before a restore
+#[tauri::command]
+async fn read_version(app: AppHandle, snapshot_id: String) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, _state| {
+        fs::read_to_string(snapshot_path(app, &snapshot_id)?).context("Version not available")
+    })
+    .await
+}
+
+/// Writes a snapshot back to where its file lives now (following later
+/// renames and moves); the current content is snapshotted first so the
+/// restore itself can be undone
+#[tauri::command]
+async fn restore_version(
+    app: AppHandle,
+    window: WebviewWindow,
+    entry_id: String,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let journal = load_activity(app)?;
+        let entry = journal
+            .iter()
+            .find(|e| e.id == entry_id)
+            .ok_or_else(|| AppError::NotFound(format!("Activity entry not found: {}", entry_id)))?;
+        let snapshot_id = entry.snapshot_id.as_deref()
+            .ok_or_else(|| AppError::NotFound("This change has no saved version".to_string()))?;
+
+        let content = fs::read_to_string(snapshot_path(app, snapshot_id)?)
+            .context("Version not available")?;
+        security::validate_excalidraw_content(&content)?;
+
+        let target = activity::current_path(&journal, entry);
+        let file = resolve_in_vault(state, &target)?;
+        security::validate_excalidraw_file(&file.path)?;
+
+        let current = file.backend.read_to_string(&file.path).ok();
+        file.backend.write(&file.path, &content)?;
+
+        let external_path = file.to_external(&file.path);
+        info!("Restored {} to the version from {}", external_path, entry.timestamp);
+        record_activity(
+            app,
+            activity::ActivityEntry::new(
+                activity::ActivityKind::Restored,
+                external_path.clone(),
+                current_workspace_key(state, window.label()),
+            ),
+            current.as_deref(),
+        );
+        Ok(external_path)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn rank_files(
+    app: AppHandle,
+    window: WebviewWindow,
+    query: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<frecency::RankedFile>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let usage = load_file_usage(app)?;
+        let current_directory = state.windows.current_directory(window.label());
+
+        // Rank the open workspace; without one, fall back to every tracked file still on disk
+        let candidates: Vec<(String, String)> = match current_directory {
+            Some(dir) if state.metadata.is_indexed(&paths::normalize(&dir)) => {
+                state.metadata.files_under(&paths::normalize(&dir))?
+            }
+            Some(dir) => {
+                let ignore = ignore_rules(app, &storage::ResolvedPath::local(&dir));
+                let mut files = Vec::new();
+                collect_excalidraw_files_recursive(&dir, &ignore, &mut files)?;
+                files.into_iter().map(|f| (f.path, f.name)).collect()
+            }
+            None => usage
+                .keys()
+                .filter(|path| {
+                    state
+                        .storage
+                        .resolve(path)
+                        .is_ok_and(|file| file.backend.is_file(&file.path))
+                })
+                .map(|path| {
+                    let name = Path::new(path)
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_else(|| path.clone());
+                    (path.clone(), name)
+                })
+                .collect(),
+        };
+
+        let mut ranked = frecency::rank(candidates, &usage, query.as_deref(), prompts::now_millis());
+        ranked.truncate(limit.unwrap_or(50));
+        Ok(ranked)
+    })
+    .await
+}
+
+/// Size of a drawing against the configured limit, so the UI can ask before
+/// loading a huge file
+#[tauri::command]
+async fn get_file_size(
+    app: AppHandle,
+    file_path: String,
+) -> Result<limits::FileSizeInfo, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        let size = file.backend.file_size(&file.path)?;
+        Ok(load_preferences(app).limits.size_info(size))
+    })
+    .await
+}
+
+/// `force` opens drawings over the configured limits
+#[tauri::command]
+async fn read_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    file_path: String,
+    force: Option<bool>,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        // Resolve the storage backend; local paths are validated against traversal attacks
+        let file = resolve_in_vault(state, &file_path)?;
+    
+        // Validate it's an excalidraw file
+        security::validate_excalidraw_file(&file.path)?;
+        check_compression_supported(&file)?;
+
+        let limits = load_preferences(app).limits;
+        let force = force.unwrap_or(false);
+        let external_path = file.to_external(&file.path);
+
+        // Switching back to a drawing read before, and unchanged since, skips
+        // reading and validating it; only local files have reliable timestamps
+        let stamp = file
+            .is_local()
+            .then(|| fs::metadata(&file.path).ok())
+            .flatten()
+            .and_then(|m| Some((m.modified().ok()?, m.len())));
+        if let Some((modified, len)) = stamp
+            && let Some(cached) = state.content_cache.get(&file.path, modified, len)
+        {
+            if !force && cached.checked.as_ref() != Some(&limits) {
+                limits.check_content(&cached.content)?;
+            }
+            record_file_usage(app, &external_path, frecency::UsageKind::Open);
+            return Ok(cached.content);
+        }
+
+        // Refuse huge files before reading them into memory
+        if !force {
+            limits.check_size(file.backend.file_size(&file.path)?)?;
+        }
+    
+        // Read and validate content; compressed drawings are held to the size
+        // limit while they decompress
+        let limit = limits.max_bytes().filter(|_| !force).unwrap_or(compression::MAX_DECODED_BYTES);
+        let content = file.backend.read_limited(&file.path, limit)?;
+
+        // Drawings from old Excalidraw versions are upgraded on the way in; the
+        // window is told so it can offer to write the upgrade back. They aren't
+        // cached, so every read tells it.
+        let mut migrated = false;
+        let content = match migrate::migrate(&content) {
+            Some(migration) => {
+                migrated = true;
+                info!(
+                    "Upgraded legacy drawing {} (version {:?}): {} change(s)",
+                    file_path,
+                    migration.from_version,
+                    migration.changes.len()
+                );
+                let _ = window.emit_to(
+                    window.label(),
+                    migrate::MIGRATED_EVENT,
+                    migrate::MigrationNotice {
+                        path: file_path.clone(),
+                        from_version: migration.from_version,
+                        changes: migration.changes,
+                    },
+                );
+                migration.content
+            }
+            None => content,
+        };
+    
+        // Validate the content is valid Excalidraw JSON
+        security::validate_excalidraw_content(&content)?;
+        if !force {
+            limits.check_content(&content)?;
+        }
+
+        // A checksum that no longer matches points at a sync that went wrong
+        // or a damaged disk; the drawing still opens, the window is warned
+        if !migrated && checksum::may_have_checksum(&content) {
+            let mut verification = checksum::verify(&file.path, &content);
+            if verification.status == checksum::ChecksumStatus::Mismatch {
+                warn!("Checksum mismatch in {}", file_path);
+                verification.path = external_path.clone();
+                let _ = window.emit_to(window.label(), checksum::MISMATCH_EVENT, verification);
+            }
+        }
+
+        if let Some((modified, len)) = stamp
+            && !migrated
+        {
+            let checked = (!force).then_some(limits);
+            state.content_cache.insert(&file.path, modified, len, &content, checked);
+        }
+        record_file_usage(app, &external_path, frecency::UsageKind::Open);
+
+        Ok(content)
+    })
+    .await
+}
+
+/// Writes the upgrade `read_file` made of a legacy drawing back to disk,
+/// keeping the old version as a backup. False when the file needs no upgrade.
+#[tauri::command]
+async fn upgrade_legacy_file(app: AppHandle, file_path: String) -> Result<bool, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+
+        let previous = file.backend.read_to_string(&file.path)?;
+        let Some(mut migration) = migrate::migrate(&previous) else {
+            return Ok(false);
+        };
+        migration.content = checksum::refresh(migration.content);
+        security::validate_excalidraw_content(&migration.content)?;
+
+        let preferences = load_preferences(app);
+        read_only::check(&preferences.read_only_paths, &file.to_external(&file.path))?;
+        if let Err(e) = backups::rotate(file.backend.as_ref(), &file.path, &previous, &preferences.backup) {
+            warn!("[upgrade_legacy_file] {}", e);
+        }
+        state.recent_saves.record(&file.path, &migration.content);
+        state.open_files.note_write(&file.path, &migration.content);
+        state.content_cache.invalidate(&file.path);
+        file.backend.write(&file.path, &migration.content)?;
+
+        info!("Wrote upgraded legacy drawing {}", file_path);
+        Ok(true)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn read_scene_partial(
+    app: AppHandle,
+    file_path: String,
+    selection: partial::SceneSelection,
+) -> Result<partial::PartialScene, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+
+        let content = file.backend.read_to_string(&file.path)?;
+        security::validate_excalidraw_content(&content)?;
+
+        let scene = partial::read_partial(&content, &selection)?;
+        info!(
+            "Partially loaded {}: {} of {} elements",
+            file_path, scene.element_count, scene.total_element_count
+        );
+        Ok(scene)
+    })
+    .await
+}
+
+/// Compressed drawings are read and written through the local file system
+fn check_compression_supported(file: &storage::ResolvedPath) -> Result<(), AppError> {
+    if compression::is_compressed(&file.path) && !file.is_local() {
+        return Err(AppError::InvalidPath(
+            "Compressed drawings are only supported in local folders".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// `force` saves drawings over the configured limits. In vaults set to
+/// compress drawings a plain drawing is saved compressed instead, and the
+/// new path returned.
+#[tauri::command]
+async fn save_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    file_path: String,
+    content: String,
+    force: Option<bool>,
+) -> Result<Option<String>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        // Resolve the storage backend; local paths are validated against traversal attacks
+        let file = resolve_in_vault(state, &file_path)?;
+    
+        // Validate it's an excalidraw file
+        security::validate_excalidraw_file(&file.path)?;
+        check_compression_supported(&file)?;
+    
+        // Validate the content before saving
+        security::validate_excalidraw_content(&content)?;
+
+        let preferences = load_preferences(app);
+        read_only::check(&preferences.read_only_paths, &file.to_external(&file.path))?;
+        if file.is_local() {
+            file_locks::check_not_locked(&file.path, prompts::now_millis())?;
+        }
+        if !force.unwrap_or(false) {
+            preferences.limits.check_content(&content)?;
+        }
+        let content = if preferences.compact_on_save {
+            compact::compact(&content)?.content
+        } else {
+            content
+        };
+        let content = if preferences.normalize_on_save {
+            normalize::normalize(&content)?
+        } else {
+            content
+        };
+        let content = if preferences.checksum_on_save {
+            checksum::seal(&content)?
+        } else {
+            content
+        };
+
+        let previous = file.backend.read_to_string(&file.path).ok();
+        if let Some(previous) = previous.as_deref().filter(|p| *p != content) {
+            // A failed backup must not cost the user the save itself
+            if let Err(e) = backups::rotate(file.backend.as_ref(), &file.path, previous, &preferences.backup) {
+                warn!("[save_file] {}", e);
+            }
+        }
+
+        // Vaults set to compress drawings convert plain ones as they are saved
+        let compress = previous.is_some()
+            && file.is_local()
+            && !compression::is_compressed(&file.path)
+            && directory_config_for(state, &file.path).is_some_and(|(_, config)| config.compress_drawings);
+        let saved = if compress {
+            compress_drawing(app, state, &file, &content)?
+        } else {
+            // New files still reach the frontend, whose file tree has to show them
+            if previous.is_some() {
+                state.recent_saves.record(&file.path, &content);
+            }
+            state.open_files.note_write(&file.path, &content);
+            state.content_cache.invalidate(&file.path);
+            file.backend.write(&file.path, &content)?;
+            file.path.clone()
+        };
+        state.file_locks.refresh(&saved, prompts::now_millis());
+
+        clear_draft(app, &file.to_external(&file.path));
+        let external_path = file.to_external(&saved);
+        record_file_usage(app, &external_path, frecency::UsageKind::Edit);
+        if previous.as_deref() != Some(content.as_str()) {
+            record_activity(
+                app,
+                activity::ActivityEntry::new(activity::ActivityKind::Saved, external_path.clone(), current_workspace_key(state, window.label())),
+                previous.as_deref(),
+            );
+        }
+
+        Ok(compress.then_some(external_path))
+    })
+    .await
+}
+
+/// Writes `content` as the compressed version of the plain local drawing
+/// `file`, checks it reads back the same, then removes the plain file.
+/// Usage, pins and the window watching the drawing follow it; returns the
+/// new path.
+fn compress_drawing(
+    app: &AppHandle,
+    state: &AppState,
+    file: &storage::ResolvedPath,
+    content: &str,
+) -> Result<PathBuf, AppError> {
+    let compressed = compression::compressed_path(&file.path);
+    if compressed.exists() {
+        return Err(AppError::AlreadyExists(format!(
+            "{} already exists",
+            paths::normalize(&compressed)
+        )));
+    }
+
+    compression::write(&compressed, content).context("Failed to write compressed drawing")?;
+    if compression::read_to_string(&compressed).ok().as_deref() != Some(content) {
+        let _ = fs::remove_file(&compressed);
+        return Err(AppError::Io("Compressed drawing verification failed".to_string()));
+    }
+
+    let old_external_path = file.to_external(&file.path);
+    let new_external_path = file.to_external(&compressed);
+    state.open_files.moved(&file.path, &compressed, new_external_path.clone(), content);
+    state.content_cache.invalidate(&file.path);
+    if let Err(e) = fs::remove_file(&file.path) {
+        warn!("Failed to remove {} after compressing it: {}", old_external_path, e);
+    }
+
+    update_file_usage(app, |usage| {
+        frecency::rename(usage, &old_external_path, &new_external_path)
+    });
+    update_pins(app, |pins| pins::rename(pins, &old_external_path, &new_external_path));
+    Ok(compressed)
+}
+
+/// Vaults covered by vault backups: the workspace folders and the folder
+/// opened last
+fn backup_vault_roots(preferences: &Preferences) -> Vec<PathBuf> {
+    let mut roots: Vec<PathBuf> = Vec::new();
+    for root in preferences.workspace_roots.iter().chain(&preferences.last_directory) {
+        let root = PathBuf::from(root);
+        if root.is_dir() && !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+    roots
+}
+
+/// Backs up every vault, or with `only_due` those whose interval has passed,
+/// announcing each one as `vault-backup-status`. Fails only when no backup
+/// could be made at all.
+fn run_vault_backups(app: &AppHandle, only_due: bool) -> Result<Vec<String>, AppError> {
+    let preferences = load_preferences(app);
+    let settings = &preferences.vault_backup;
+    let directory = settings
+        .directory()
+        .ok_or_else(|| AppError::InvalidInput("No backup folder is set".to_string()))?;
+    let now = prompts::now_millis();
+
+    let mut created = Vec::new();
+    let mut first_error = None;
+    for vault in backup_vault_roots(&preferences) {
+        if only_due && !vault_backup::is_due(settings, &directory, &vault, now) {
+            continue;
+        }
+        let status = |state, path: Option<String>, error: Option<String>| {
+            let _ = app.emit(
+                vault_backup::STATUS_EVENT,
+                vault_backup::BackupStatus {
+                    state,
+                    vault: vault_backup::vault_name(&vault),
+                    path,
+                    error,
+                },
+            );
+        };
+
+        status(vault_backup::BackupState::Started, None, None);
+        let ignore = ignore_rules(app, &storage::ResolvedPath::local(&vault));
+        match vault_backup::run(&vault, &directory, &ignore, settings, now) {
+            Ok(backup) => {
+                let backup = paths::normalize(&backup);
+                info!("Backed up {} to {}", vault.display(), backup);
+                status(vault_backup::BackupState::Finished, Some(backup.clone()), None);
+                created.push(backup);
+            }
+            Err(e) => {
+                error!("Failed to back up {}: {}", vault.display(), e);
+                status(vault_backup::BackupState::Failed, None, Some(e.to_string()));
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) if created.is_empty() => Err(e),
+        _ => Ok(created),
+    }
+}
+
+/// Runs from the scheduler thread; skipped while another backup is running
+fn scheduled_vault_backup(app: &AppHandle) {
+    let settings = load_preferences(app).vault_backup;
+    if !settings.enabled || settings.interval_minutes == 0 {
+        return;
+    }
+    let state = app.state::<AppState>();
+    let Ok(_running) = state.vault_backups.try_lock() else {
+        return;
+    };
+    if let Err(e) = run_vault_backups(app, true) {
+        warn!("Scheduled vault backup failed: {}", e);
+    }
+}
+
+/// Blocks the exit until the vaults are backed up, waiting for a running backup first
+fn exit_vault_backup(app: &AppHandle) {
+    let settings = load_preferences(app).vault_backup;
+    if !settings.enabled || !settings.on_exit {
+        return;
+    }
+    let state = app.state::<AppState>();
+    let _running = state.vault_backups.lock();
+    if let Err(e) = run_vault_backups(app, false) {
+        warn!("Vault backup on exit failed: {}", e);
+    }
+}
+
+/// Backs up every vault to the backup folder now, whether or not scheduled
+/// backups are enabled; returns the new backups
+#[tauri::command]
+async fn run_backup_now(app: AppHandle) -> Result<Vec<String>, AppError> {
+    blocking::with_state(&app, |app, state| {
+        let _running = state.vault_backups.try_lock()?;
+        run_vault_backups(app, false)
+    })
+    .await
+}
+
+/// Vault backups in the backup folder, newest first. Named apart from
+/// `list_backups`, which lists the `.bak` copies of one drawing
+#[tauri::command]
+async fn list_vault_backups(app: AppHandle) -> Result<Vec<vault_backup::VaultBackupInfo>, AppError> {
+    let settings = load_preferences(&app).vault_backup;
+    Ok(settings
+        .directory()
+        .map(|directory| vault_backup::list(&directory, None))
+        .unwrap_or_default())
+}
+
+/// Stores the secret key of an S3 access key in the system keychain; an
+/// empty secret removes it
+#[tauri::command]
+async fn set_s3_secret(access_key_id: String, secret_access_key: String) -> Result<(), AppError> {
+    if access_key_id.trim().is_empty() {
+        return Err(AppError::InvalidInput("Access key id is empty".to_string()));
+    }
+    s3::store_secret(access_key_id.trim(), &secret_access_key)
+}
+
+/// Client for the S3 settings in the preferences and the bucket to use
+fn s3_client(app: &AppHandle, bucket: Option<String>) -> Result<(s3::S3Client, String), AppError> {
+    let preferences = load_preferences(app);
+    let settings = &preferences.s3_backup;
+    let bucket = bucket
+        .filter(|b| !b.trim().is_empty())
+        .unwrap_or_else(|| settings.bucket.clone());
+    if bucket.trim().is_empty() {
+        return Err(AppError::InvalidInput("No S3 bucket is set".to_string()));
+    }
+    let secret = s3::load_secret(&settings.access_key_id)?;
+    let client = ai::build_client(std::time::Duration::from_secs(300), &preferences.proxy)?;
+    Ok((s3::S3Client::new(settings, secret, client)?, bucket.trim().to_string()))
+}
+
+/// Archives a vault with its images and exports and uploads it to
+/// `<prefix>/<vault>-YYYYMMDD-HHMMSS.zip`; progress arrives as `s3-progress`
+#[tauri::command]
+async fn upload_backup(
+    app: AppHandle,
+    window: WebviewWindow,
+    directory: String,
+    bucket: Option<String>,
+    prefix: String,
+    state: State<'_, AppState>,
+) -> Result<s3::S3UploadReport, AppError> {
+    let dir = resolve_in_vault(&state, &directory)?;
+    if !dir.is_local() {
+        return Err(AppError::InvalidPath("Only local folders can be backed up".to_string()));
+    }
+    if !dir.backend.is_dir(&dir.path) {
+        return Err(AppError::NotFound("Directory does not exist".to_string()));
+    }
+    let (client, bucket) = s3_client(&app, bucket)?;
+
+    let name = format!(
+        "{}.zip",
+        vault_backup::backup_name(&vault_backup::vault_name(&dir.path), prompts::now_millis())
+    );
+    let temp = std::env::temp_dir().join(&name);
+    let entries = archive_entries(&app, &state, &dir, true, &temp)?;
+    archive::write(&entries, &temp, |_| true)?;
+
+    let key = s3::object_key(&prefix, &name);
+    let label = window.label();
+    let result = client
+        .upload(&bucket, &key, &temp, |progress| {
+            let _ = app.emit_to(label, s3::PROGRESS_EVENT, progress);
+        })
+        .await;
+    let _ = fs::remove_file(&temp);
+    let bytes = result?;
+
+    info!("Uploaded {} ({} bytes) to s3://{}/{}", directory, bytes, bucket, key);
+    Ok(s3::S3UploadReport { bucket, key, bytes })
+}
+
+/// Downloads a vault archive and extracts it into a folder like
+/// `import_archive`. `prefix` is the key of a `.zip`, or a prefix whose
+/// newest `.zip` is restored.
+#[tauri::command]
+async fn restore_from_s3(
+    app: AppHandle,
+    window: WebviewWindow,
+    prefix: String,
+    target_dir: String,
+    bucket: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<archive::ImportReport, AppError> {
+    let target = resolve_in_vault(&state, &target_dir)?;
+    if !target.is_local() {
+        return Err(AppError::InvalidPath("Archives can only be restored into local folders".to_string()));
+    }
+    if !target.backend.is_dir(&target.path) {
+        return Err(AppError::NotFound("Directory does not exist".to_string()));
+    }
+    let (client, bucket) = s3_client(&app, bucket)?;
+
+    let prefix = prefix.trim_start_matches('/');
+    let key = if prefix.ends_with(".zip") {
+        prefix.to_string()
+    } else {
+        client
+            .list(&bucket, prefix)
+            .await?
+            .into_iter()
+            .filter(|object| object.key.ends_with(".zip"))
+            .max_by(|a, b| a.last_modified.cmp(&b.last_modified).then_with(|| a.key.cmp(&b.key)))
+            .map(|object| object.key)
+            .ok_or_else(|| AppError::NotFound(format!("No archives under s3://{}/{}", bucket, prefix)))?
+    };
+
+    let file_name = key.rsplit('/').next().unwrap_or("restore.zip");
+    let temp = std::env::temp_dir().join(format!("restore-{}", file_name));
+    let label = window.label();
+    client
+        .download(&bucket, &key, &temp, |progress| {
+            let _ = app.emit_to(label, s3::PROGRESS_EVENT, progress);
+        })
+        .await?;
+
+    let limits = load_preferences(&app).limits;
+    let result = {
+        let _paused = WatcherPause::new(&app);
+        archive::import(&temp, &target.path, &limits, |progress| {
+            let _ = app.emit_to(label, archive::PROGRESS_EVENT, progress);
+            true
+        })
+    };
+    let _ = fs::remove_file(&temp);
+    let report = result?;
+    info!(
+        "Restored s3://{}/{} into {}: {} files, {} skipped, {} invalid",
+        bucket,
+        key,
+        target_dir,
+        report.imported.len(),
+        report.skipped.len(),
+        report.invalid.len()
+    );
+    Ok(report)
+}
+
+/// Backups of a file in the configured location, most recent first
+#[tauri::command]
+async fn list_backups(
+    app: AppHandle,
+    file_path: String,
+) -> Result<Vec<backups::BackupInfo>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+
+        let location = load_preferences(app).backup.location;
+        Ok(backups::list(file.backend.as_ref(), &file.path, location)
+            .into_iter()
+            .map(|mut backup| {
+                backup.path = file.to_external(Path::new(&backup.path));
+                backup
+            })
+            .collect())
+    })
+    .await
+}
+
+/// Replaces a file with one of its backups; the current content becomes the
+/// newest backup, so the restore can be undone the same way
+#[tauri::command]
+async fn restore_backup(
+    app: AppHandle,
+    window: WebviewWindow,
+    file_path: String,
+    index: usize,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+
+        let settings = load_preferences(app).backup;
+        let content = backups::read(file.backend.as_ref(), &file.path, settings.location, index)?;
+        security::validate_excalidraw_content(&content)?;
+
+        let current = file.backend.read_to_string(&file.path).ok();
+        if let Some(current) = current.as_deref() {
+            // Keep at least one slot so the replaced content is never lost
+            let settings = backups::BackupSettings {
+                keep: settings.keep.max(1),
+                ..settings
+            };
+            backups::rotate(file.backend.as_ref(), &file.path, current, &settings)?;
+            state.recent_saves.record(&file.path, &content);
+        }
+        state.open_files.note_write(&file.path, &content);
+        state.content_cache.invalidate(&file.path);
+        file.backend.write(&file.path, &content)?;
+
+        let external_path = file.to_external(&file.path);
+        info!("Restored {} from backup {}", external_path, index);
+        record_activity(
+            app,
+            activity::ActivityEntry::new(
+                activity::ActivityKind::Restored,
+                external_path,
+                current_workspace_key(state, window.label()),
+            ),
+            current.as_deref(),
+        );
+        Ok(content)
+    })
+    .await
+}
+
+fn drafts_dir(app: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(app
+        .path()
+        .app_data_dir()?
+        .join(drafts::DRAFT_DIR))
+}
+
+/// Best-effort: a leftover draft is pruned on the next listing anyway
+fn clear_draft(app: &AppHandle, external_path: &str) {
+    if let Err(e) = drafts_dir(app).and_then(|dir| drafts::clear(&dir, external_path)) {
+        warn!("[drafts] {}", e);
+    }
+}
+
+/// Snapshots unsaved editor content so it survives a crash
+#[tauri::command]
+async fn save_draft(
+    app: AppHandle,
+    file_path: String,
+    content: String,
+) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        security::validate_excalidraw_content(&content)?;
+        drafts::save(&drafts_dir(app)?, &file.to_external(&file.path), &content)
+    })
+    .await
+}
+
+/// Drafts left by a previous session that differ from the saved files
+#[tauri::command]
+async fn list_recoverable_drafts(
+    app: AppHandle,
+) -> Result<Vec<drafts::RecoverableDraft>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let dir = drafts_dir(app)?;
+        Ok(drafts::list_recoverable(&dir, |file_path| {
+            let file = resolve_in_vault(state, file_path).ok()?;
+            file.backend.read_to_string(&file.path).ok()
+        }))
+    })
+    .await
+}
+
+/// Content of the draft of `file_path`; the draft is kept until the file is
+/// saved or the draft discarded
+#[tauri::command]
+async fn recover_draft(app: AppHandle, file_path: String) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        let draft = drafts::load(&drafts_dir(app)?, &file.to_external(&file.path))?
+            .ok_or_else(|| AppError::NotFound("No draft found for this file".to_string()))?;
+        security::validate_excalidraw_content(&draft.content)?;
+        Ok(draft.content)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn discard_draft(app: AppHandle, file_path: String) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        drafts::clear(&drafts_dir(app)?, &file.to_external(&file.path))
+    })
+    .await
+}
+
+fn recently_deleted_dir(app: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(app
+        .path()
+        .app_data_dir()?
+        .join(recently_deleted::DELETED_DIR))
+}
+
+/// Moves what has been in Recently Deleted longer than the preferences keep
+/// it on to the system trash; best-effort like the drafts
+fn purge_recently_deleted(app: &AppHandle) {
+    let retention_days = load_preferences(app).deleted_retention_days;
+    match recently_deleted_dir(app) {
+        Ok(dir) => {
+            let purged = recently_deleted::purge(&dir, retention_days, prompts::now_millis());
+            if purged > 0 {
+                info!("Purged {} item(s) from Recently Deleted", purged);
+            }
+        }
+        Err(e) => warn!("[recently_deleted] {}", e),
+    }
+}
+
+/// Deleted drawings and folders that can still be restored, most recently
+/// deleted first
+#[tauri::command]
+async fn list_recently_deleted(app: AppHandle) -> Result<Vec<recently_deleted::DeletedEntry>, AppError> {
+    blocking::with_state(&app, |app, _state| {
+        purge_recently_deleted(app);
+        Ok(recently_deleted::list(&recently_deleted_dir(app)?))
+    })
+    .await
+}
+
+/// Puts a deleted drawing or folder back where it was, under a new name if
+/// that place has been taken since, and returns its path
+#[tauri::command]
+async fn restore_deleted(
+    app: AppHandle,
+    window: WebviewWindow,
+    id: String,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let dir = recently_deleted_dir(app)?;
+        let (entry, item) = recently_deleted::load(&dir, &id)?;
+        let original = resolve_in_vault(state, &entry.original_path)?;
+        let parent = original.path.parent().ok_or_else(|| AppError::InvalidPath("Invalid path".to_string()))?;
+        if !original.backend.is_dir(parent) {
+            return Err(AppError::NotFound("The folder it was deleted from no longer exists".to_string()));
+        }
+        read_only::check(&load_preferences(app).read_only_paths, &original.to_external(parent))?;
+
+        let path = recently_deleted::free_path(&original.path, entry.is_directory, |p| original.backend.exists(p));
+        if entry.is_directory {
+            if !original.is_local() {
+                return Err(AppError::InvalidInput("Folders can only be restored to local storage".to_string()));
+            }
+            recently_deleted::restore_directory(&item, &path).context("Failed to restore folder")?;
+        } else {
+            security::validate_excalidraw_file(&path)?;
+            let content = fs::read_to_string(&item).context("Failed to read deleted drawing")?;
+            original.backend.create_new(&path, &content)?;
+        }
+        recently_deleted::remove(&dir, &id)?;
+
+        let external_path = original.to_external(&path);
+        info!("Restored deleted {} as {}", entry.original_path, external_path);
+        record_activity(
+            app,
+            activity::ActivityEntry::new(
+                activity::ActivityKind::Restored,
+                external_path.clone(),
+                current_workspace_key(state, window.label()),
+            ),
+            None,
+        );
+        Ok(external_path)
+    })
+    .await
+}
+
+/// Finds and replaces text in every drawing below `directory`. Without
+/// `options.confirm` this is a dry run returning the preview; confirmed runs
+/// copy the affected files to a backup folder before writing any of them.
+#[tauri::command]
+async fn replace_text(
+    app: AppHandle,
+    window: WebviewWindow,
+    directory: String,
+    query: String,
+    replacement: String,
+    options: Option<replace::ReplaceOptions>,
+) -> Result<replace::ReplaceReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let options = options.unwrap_or_default();
+        let matcher = replace::Matcher::new(&query, &options)?;
+
+        let validated_dir = state.vault.check(Path::new(&directory))?;
+        if !validated_dir.is_dir() {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+
+        let ignore = ignore_rules(app, &storage::ResolvedPath::local(&validated_dir));
+        let mut files = Vec::new();
+        collect_excalidraw_files_recursive(&validated_dir, &ignore, &mut files)?;
+        if let (true, Some(selected)) = (options.confirm, &options.files) {
+            let selected: Vec<String> = selected.iter().map(|p| paths::normalize(Path::new(p))).collect();
+            files.retain(|f| selected.contains(&f.path));
+        }
+
+        let outcomes = state.background.run(files, |file| {
+            let content = compression::read_to_string(Path::new(&file.path))
+                .map_err(|e| format!("{}: {}", file.path, e))?;
+            state.background.throttle_io(content.len());
+            let replaced = replace::replace_in_scene(&content, &matcher, &replacement)
+                .map_err(|e| format!("{}: {}", file.path, e))?;
+            Ok::<_, String>(replaced.map(|(new_content, changes)| (file, new_content, changes)))
+        });
+
+        let mut report = replace::ReplaceReport {
+            files: Vec::new(),
+            total_matches: 0,
+            written: false,
+            backup_dir: None,
+            failed: Vec::new(),
+        };
+        let read_only_paths = load_preferences(app).read_only_paths;
+        let mut pending = Vec::new();
+        for outcome in outcomes {
+            match outcome {
+                Ok(Some((file, new_content, changes))) => {
+                    // Locked drawings are reported as failed and never written
+                    if let Err(e) = read_only::check(&read_only_paths, &file.path) {
+                        report.failed.push(e.to_string());
+                        continue;
+                    }
+                    let matches = changes.iter().map(|c| c.matches).sum();
+                    report.total_matches += matches;
+                    report.files.push(replace::FileReplacement {
+                        path: file.path.clone(),
+                        matches,
+                        elements: changes,
+                    });
+                    pending.push((file, new_content));
+                }
+                Ok(None) => {}
+                Err(e) => report.failed.push(e),
+            }
+        }
+
+        if !options.confirm || pending.is_empty() {
+            return Ok(report);
+        }
+
+        // Back up every affected file before touching any of them
+        let backup_dir = app
+            .path()
+            .app_data_dir()?
+            .join("backups")
+            .join(format!("replace-{}", prompts::now_millis()));
+        for (file, _) in &pending {
+            let backup = backup_dir.join(&file.relative_path);
+            if let Some(parent) = backup.parent() {
+                fs::create_dir_all(parent).context("Failed to create backup folder")?;
+            }
+            fs::copy(&file.path, &backup).context(format!("Failed to back up {}", file.path))?;
+        }
+        report.backup_dir = Some(paths::normalize(&backup_dir));
+
+        let workspace = current_workspace_key(state, window.label());
+        let _paused = WatcherPause::new(app);
+        for (file, new_content) in pending {
+            let new_content = checksum::refresh(new_content);
+            if let Err(e) = security::validate_excalidraw_content(&new_content)
+                .and_then(|_| compression::write(Path::new(&file.path), &new_content).map_err(AppError::from))
+            {
+                report.failed.push(format!("{}: {}", file.path, e));
+                continue;
+            }
+            record_activity(
+                app,
+                activity::ActivityEntry::new(activity::ActivityKind::Saved, file.path, workspace.clone()),
+                None,
+            );
+        }
+        report.written = true;
+        info!(
+            "Replaced {} match(es) in {} file(s) below {}, backups in {:?}",
+            report.total_matches,
+            report.files.len(),
+            directory,
+            report.backup_dir
+        );
+        Ok(report)
+    })
+    .await
+}
+
+/// Removes deleted elements and unreferenced image data from a drawing and
+/// saves it; the previous content is kept as a version snapshot
+#[tauri::command]
+async fn compact_scene(
+    app: AppHandle,
+    window: WebviewWindow,
+    file_path: String,
+) -> Result<compact::CompactResult, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        read_only::check(&load_preferences(app).read_only_paths, &file.to_external(&file.path))?;
+
+        let content = file.backend.read_to_string(&file.path)?;
+        let mut result = compact::compact(&content)?;
+        info!(
+            "Compacted {}: {} element(s) and {} file(s) removed, {} -> {} bytes",
+            file_path, result.removed_elements, result.removed_files, result.bytes_before, result.bytes_after
+        );
+
+        if result.removed_elements > 0 || result.removed_files > 0 {
+            result.content = checksum::refresh(result.content);
+            security::validate_excalidraw_content(&result.content)?;
+            file.backend.write(&file.path, &result.content)?;
+            record_activity(
+                app,
+                activity::ActivityEntry::new(
+                    activity::ActivityKind::Saved,
+                    file.to_external(&file.path),
+                    current_workspace_key(state, window.label()),
+                ),
+                Some(&content),
+            );
+        }
+        Ok(result)
+    })
+    .await
+}
+
+/// Downscales and re-encodes the images embedded in a drawing and saves it;
+/// the previous content is kept as a version snapshot
+#[tauri::command]
+async fn optimize_scene_images(
+    app: AppHandle,
+    window: WebviewWindow,
+    file_path: String,
+    quality: Option<u8>,
+    max_dimension: Option<u32>,
+) -> Result<images::OptimizeImagesResult, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        read_only::check(&load_preferences(app).read_only_paths, &file.to_external(&file.path))?;
+
+        let content = file.backend.read_to_string(&file.path)?;
+        let mut result = images::optimize_scene(
+            &content,
+            quality.unwrap_or(images::DEFAULT_QUALITY),
+            max_dimension.unwrap_or(images::DEFAULT_MAX_DIMENSION).max(1),
+            &state.background,
+        )?;
+        info!(
+            "Optimized {} of {} image(s) in {}: {} -> {} bytes",
+            result.optimized, result.images, file_path, result.bytes_before, result.bytes_after
+        );
+
+        if result.optimized > 0 {
+            result.content = checksum::refresh(result.content);
+            security::validate_excalidraw_content(&result.content)?;
+            file.backend.write(&file.path, &result.content)?;
+            record_activity(
+                app,
+                activity::ActivityEntry::new(
+                    activity::ActivityKind::Saved,
+                    file.to_external(&file.path),
+                    current_workspace_key(state, window.label()),
+                ),
+                Some(&content),
+            );
+        }
+        Ok(result)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn extract_scene_images(
+    app: AppHandle,
+    file_path: String,
+    out_dir: String,
+) -> Result<Vec<images::ExtractedImage>, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        let out_dir = state.vault.check(Path::new(&out_dir))?;
+
+        let content = file.backend.read_to_string(&file.path)?;
+        let extracted = images::extract_scene(&content, &out_dir)?;
+        info!("Extracted {} image(s) from {} to {:?}", extracted.len(), file_path, out_dir);
+        Ok(extracted)
+    })
+    .await
+}
+
+fn load_locations(app: &AppHandle) -> Result<locations::LocationMap, AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store(locations::STORE_FILE)?;
+
+    Ok(store
+        .get(locations::STORE_KEY)
+        .and_then(|value| serde_json::from_value::<locations::LocationMap>(value).ok())
+        .unwrap_or_default())
+}
+
+/// Resolves a path for a file command; local paths must lie in a folder the
+/// user opened or be a file picked in a dialog
+fn resolve_in_vault(state: &AppState, path: &str) -> Result<storage::ResolvedPath, AppError> {
+    let resolved = state.storage.resolve(path)?;
+    if resolved.is_local() {
+        state.vault.check(&resolved.path)?;
+    }
+    Ok(resolved)
+}
+
+/// `resolve_in_vault` for listings, which keep local paths as given
+fn resolve_root_in_vault(state: &AppState, path: &str) -> Result<storage::ResolvedPath, AppError> {
+    let resolved = state.storage.resolve_root(path)?;
+    if resolved.is_local() {
+        state.vault.check(&resolved.path)?;
+    }
+    Ok(resolved)
+}
+
+fn current_workspace_key(state: &AppState, label: &str) -> Option<String> {
+    state.windows.current_directory(label).as_deref().map(paths::normalize)
+}
+
+/// Best-effort: a failure to remember the folder must not fail the save or export
+fn remember_location(app: &AppHandle, workspace: Option<&str>, kind: locations::LocationKind, directory: &Path) {
+    use tauri_plugin_store::StoreExt;
+
+    let result = load_locations(app).and_then(|mut all| {
+        locations::remember(&mut all, workspace, kind, paths::normalize(directory));
+        let store = app.store(locations::STORE_FILE)?;
+        store.set(
+            locations::STORE_KEY,
+            serde_json::to_value(&all)?,
+        );
+        store.save().map_err(AppError::from)
+    });
+
+    if let Err(e) = result {
+        warn!("Failed to remember {:?} location: {}", kind, e);
+    }
+}
+
+#[tauri::command]
+async fn get_last_locations(
+    app: AppHandle,
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+) -> Result<locations::WorkspaceLocations, AppError> {
+    let workspace = current_workspace_key(&state, window.label());
+    Ok(locations::for_workspace(&load_locations(&app)?, workspace.as_deref())
+        .cloned()
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+async fn save_file_as(
+    app: AppHandle,
+    window: WebviewWindow,
+    content: String,
+    suggested_name: Option<String>,
+    default_directory: Option<String>,
+    current_file: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<locations::SaveAsResult>, AppError> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let workspace = current_workspace_key(&state, window.label());
+
+    // Explicit folder, then the current file's folder, then the last save location, then the workspace
+    let start_directory = default_directory
+        .map(PathBuf::from)
+        .or_else(|| current_file.as_deref().and_then(|f| Path::new(f).parent().map(Path::to_path_buf)))
+        .or_else(|| {
+            load_locations(&app)
+                .ok()
+                .and_then(|all| locations::last(&all, workspace.as_deref(), locations::LocationKind::Save))
+                .map(PathBuf::from)
+        })
+        .or_else(|| state.windows.current_directory(window.label()))
+        .filter(|dir| dir.is_dir());
+    let file_name = suggested_name
+        .as_deref()
+        .or_else(|| current_file.as_deref().and_then(|f| Path::new(f).file_name()?.to_str()))
+        .and_then(locations::suggested_file_name);
+
+    let dialog = state.dialogs.open();
+    let tx = dialog.sender();
+
+    let mut builder = app
+        .dialog()
+        .file()
+        .add_filter("Excalidraw", &["excalidraw"])
+        .set_title("Save As");
+    if let Some(dir) = start_directory {
+        builder = builder.set_directory(dir);
+    }
+    if let Some(name) = file_name {
+        builder = builder.set_file_name(name);
+    }
+    builder.save_file(move |path| {
+        let _ = tx.send(dialogs::DialogOutcome::Picked(path));
+    });
+
+    let Some(path) = state.dialogs.wait(dialog, dialogs::DIALOG_TIMEOUT).await? else {
+        return Ok(None);
+    };
+    let path_str = path.as_path().map(paths::normalize).unwrap_or_else(|| path.to_string());
+    let overwritten = Path::new(&path_str).exists();
+    fs::write(&path_str, content)?;
+    // The picked target stays writable even outside the opened folders
+    state.vault.allow_file(Path::new(&path_str));
+
+    if let Some(parent) = Path::new(&path_str).parent() {
+        remember_location(&app, workspace.as_deref(), locations::LocationKind::Save, parent);
+    }
+    info!("Saved as {} (overwritten: {})", path_str, overwritten);
+
+    Ok(Some(locations::SaveAsResult {
+        path: path_str,
+        overwritten,
+    }))
+}
+
+/// Picks the next free "Untitled-N" name from the directory's actual contents,
+/// so concurrently open windows never compute the same number
+fn next_untitled_file_name(dir: &storage::ResolvedPath, locale: &str) -> String {
+    let prefix = if locale == "zh-CN" { "未命名" } else { "Untitled" };
+
+    let mut highest = 0u32;
+    if let Ok(entries) = dir.backend.read_dir(&dir.path) {
+        for entry in entries {
+            let number = entry
+                .name
+                .strip_suffix(".excalidraw")
+                .and_then(|stem| stem.strip_prefix(prefix))
+                .and_then(|rest| rest.strip_prefix('-'))
+                .and_then(|n| n.parse::<u32>().ok());
+            if let Some(n) = number {
+                highest = highest.max(n);
+            }
+        }
+    }
+
+    format!("{}-{}.excalidraw", prefix, highest + 1)
+}
+
+#[tauri::command]
+async fn create_new_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    directory: String,
+    file_name: Option<String>,
+    template: Option<String>,
+    variables: Option<HashMap<String, String>>,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let template = template
+            .filter(|name| !name.trim().is_empty())
+            .map(|name| {
+                let path = templates::path(&templates::dir(app)?, &name)?;
+                if !path.exists() {
+                    return Err(AppError::NotFound(format!("Template not found: {}", name)));
+                }
+                let content = fs::read_to_string(&path).context("Failed to read template")?;
+                security::validate_excalidraw_content(&content)?;
+                Ok(content)
+            })
+            .transpose()?;
+
+        create_drawing(
+            app,
+            state,
+            window.label(),
+            &directory,
+            file_name,
+            template,
+            &variables.unwrap_or_default(),
+        )
+    })
+    .await
+}
+
+/// Creates a drawing in `directory`. `template` content replaces the vault's
+/// default template and the empty scene; placeholders in the text of either
+/// template are filled from `templates::variables`.
+fn create_drawing(
+    app: &AppHandle,
+    state: &AppState,
+    label: &str,
+    directory: &str,
+    file_name: Option<String>,
+    template: Option<String>,
+    variables: &HashMap<String, String>,
+) -> Result<String, AppError> {
+    debug!(
+        "[create_new_file] Called with directory: {}, file_name: {:?}",
+        directory, file_name
+    );
+
+    // Resolve the storage backend; local paths are validated and canonicalized
+    let dir = resolve_in_vault(state, directory)?;
+    
+    if !dir.backend.is_dir(&dir.path) {
+        return Err(AppError::InvalidPath(format!("Path is not a directory: {}", directory)));
+    }
+
+    // Without a name the backend owns the "Untitled-N" numbering
+    let (file_name, untitled_locale) = match file_name.filter(|n| !n.trim().is_empty()) {
+        Some(name) => (name, None),
+        None => {
+            let locale = menu::get_current_locale(app);
+            (next_untitled_file_name(&dir, &locale), Some(locale))
+        }
+    };
+
+    // Safely join the filename to the directory
+    let mut path = security::safe_path_join(&dir.path, &file_name)?;
+    debug!("[create_new_file] Initial path: {:?}", path);
+
+    // Check if file already exists and suggest alternative
+    if dir.backend.exists(&path) {
+        debug!("[create_new_file] File already exists, finding unique name");
+        // Find a unique name by appending numbers
+        let mut counter = 1;
+
+        // Get the base name without extension
+        let stem = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| AppError::InvalidPath("Invalid file name".to_string()))?
+            .to_string(); // Convert to owned String
+
+        // Handle the .excalidraw extension properly
+        let base_stem = if stem.ends_with(".excalidraw") {
+            stem.trim_end_matches(".excalidraw").to_string()
+        } else {
+            stem
+        };
+
+        loop {
+            let new_name = format!("{}-{}.excalidraw", base_stem, counter);
+            path = dir.path.join(&new_name);
+
+            if !dir.backend.exists(&path) {
+                debug!("[create_new_file] Found unique name: {:?}", path);
+                break;
+            }
+            counter += 1;
+
+            if counter > 100 {
+                return Err(AppError::Io("Could not find unique file name".to_string()));
+            }
+        }
+    }
+
+    let default_content = serde_json::json!({
+        "type": "excalidraw",
+        "version": 2,
+        "source": "ExcaliApp",
+        "elements": [],
+        "appState": {
+            "gridSize": null,
+            "viewBackgroundColor": "#ffffff"
+        },
+        "files": {}
+    });
+
+    // The vault's default template, when configured, replaces the empty scene
+    let template = template.or_else(|| {
+        let (root, config) = directory_config_for(state, &dir.path)?;
+        let content = config
+            .template_path(&root)?
+            .and_then(|path| fs::read_to_string(&path).context("Failed to read template"))
+            .and_then(|content| {
+                security::validate_excalidraw_content(&content).map(|_| content)
+            });
+        content.map_err(|e| warn!("[create_new_file] Ignoring default template: {}", e)).ok()
+    });
+
+    let empty_scene = serde_json::to_string_pretty(&default_content)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+    let now = prompts::now_millis();
+    // `{{filename}}` depends on the final name, which may still change below
+    let content_for = |path: &Path| match &template {
+        Some(content) => templates::render(content, &templates::variables(path, now, variables)),
+        None => empty_scene.clone(),
+    };
+
+    debug!("[create_new_file] Writing to path: {:?}", path);
+    let mut write_result = dir.backend.create_new(&path, &content_for(&path));
+
+    // Another window may have claimed the same untitled name in the meantime
+    if let Some(locale) = &untitled_locale {
+        let mut attempts = 0;
+        while attempts < 100
+            && matches!(&write_result, Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists)
+        {
+            path = dir.path.join(next_untitled_file_name(&dir, locale));
+            debug!("[create_new_file] Name taken, retrying with: {:?}", path);
+            write_result = dir.backend.create_new(&path, &content_for(&path));
+            attempts += 1;
+        }
+    }
+
+    match write_result {
+        Ok(_) => {
+            info!("[create_new_file] Successfully created file: {:?}", path);
+
+            // Verify the file was created
+            if !dir.backend.exists(&path) {
+                error!("[create_new_file] File doesn't exist after creation!");
+                return Err(AppError::Io("File creation verification failed".to_string()));
+            }
+
+            // Verify we can read it back
+            match dir.backend.read_to_string(&path) {
+                Ok(read_content) => {
+                    debug!(
+                        "[create_new_file] File verified, content length: {}",
+                        read_content.len()
+                    );
+                }
+                Err(e) => {
+                    warn!(
+                        "[create_new_file] Could not verify file content: {}",
+                        e
+                    );
+                }
+            }
+
+            let external_path = dir.to_external(&path);
+            record_activity(
+                app,
+                activity::ActivityEntry::new(
+                    activity::ActivityKind::Created,
+                    external_path.clone(),
+                    current_workspace_key(state, label),
+                ),
+                None,
+            );
+            Ok(external_path)
+        }
+        Err(e) => {
+            error!("[create_new_file] Failed to create file: {}", e);
+            Err(AppError::Io(format!("Failed to create file: {}", e)))
+        }
+    }
+}
+
+/// Reads the stored preferences, falling back to defaults when missing or invalid
+fn load_preferences(app: &AppHandle) -> Preferences {
+    use tauri_plugin_store::StoreExt;
+
+    let Ok(store) = app.store("preferences.json") else {
+        return Preferences::default();
+    };
+
+    let Some(mut value) = store.get("preferences") else {
+        return Preferences::default();
+    };
+
+    let original = value.clone();
+    let migrated = preferences::migrate(&mut value);
+    let (loaded, skipped) = preferences::merge_lenient(Preferences::default(), &value);
+
+    if !skipped.is_empty() {
+        warn!("Reset unreadable preferences to defaults: {}", skipped.join(", "));
+    }
+    if migrated || !skipped.is_empty() {
+        // Keep what was there in case the upgrade lost something
+        store.set("preferences_backup", original);
+        if let Err(e) = store_preferences(app, &loaded) {
+            warn!("Failed to write upgraded preferences: {}", e);
+        } else {
+            info!("Upgraded preferences to schema version {}", preferences::SCHEMA_VERSION);
+        }
+    }
+    loaded
+}
+
+#[tauri::command]
+async fn get_preferences(app: AppHandle) -> Result<Preferences, AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    // Surface store errors to the caller instead of silently using defaults
+    app.store("preferences.json")?;
+
+    Ok(load_preferences(&app))
+}
+
+#[tauri::command]
+async fn rename_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    old_path: String,
+    new_name: String,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        // Resolve and validate the old path
+        let file = resolve_in_vault(state, &old_path)?;
+        let old_path = file.path.as_path();
+    
+        if !file.backend.exists(old_path) {
+            return Err(AppError::NotFound("File does not exist".to_string()));
+        }
+    
+        security::validate_excalidraw_file(old_path)?;
+        read_only::check(&load_preferences(app).read_only_paths, &file.to_external(old_path))?;
+
+        let parent = old_path.parent().ok_or_else(|| AppError::InvalidPath("Invalid file path".to_string()))?;
+    
+        // Safely create the new path
+        let new_path = security::safe_path_join(parent, &new_name)?;
+    
+        // Ensure the new path also has the extension, compressed drawings staying so
+        let new_path = compression::with_drawing_extension(&new_path, compression::is_compressed(old_path));
+
+        if file.backend.exists(&new_path) && new_path != old_path {
+            return Err(AppError::AlreadyExists("A file with that name already exists".to_string()));
+        }
+
+        // CRITICAL FIX: Read the content first, then write to new file, then delete old
+        // This prevents data loss if something goes wrong
+        debug!("Renaming file from {:?} to {:?}", old_path, new_path);
+
+        // Step 1: Read the original file content
+        let content = match file.backend.read_to_string(old_path) {
+            Ok(content) => {
+                debug!(
+                    "Successfully read original file, content length: {}",
+                    content.len()
+                );
+                content
+            }
+            Err(e) => {
+                error!("Failed to read original file: {}", e);
+                return Err(AppError::Io(format!("Failed to read original file: {}", e)));
+            }
+        };
+
+        // Step 2: Write content to the new file
+        match file.backend.write(&new_path, &content) {
+            Ok(_) => {
+                debug!("Successfully wrote content to new file");
+            }
+            Err(e) => {
+                error!("Failed to write to new file: {}", e);
+                return Err(AppError::Io(format!("Failed to create new file: {}", e)));
+            }
+        }
+
+        // Step 3: Verify the new file exists and has content
+        match file.backend.read_to_string(&new_path) {
+            Ok(new_content) => {
+                if new_content != content {
+                    warn!("New file content doesn't match original!");
+                    // Delete the corrupted new file
+                    let _ = file.backend.remove_file(&new_path);
+                    return Err(AppError::Io("File content verification failed".to_string()));
+                }
+                debug!("New file verified successfully");
+            }
+            Err(e) => {
+                error!("Failed to verify new file: {}", e);
+                // Delete the potentially corrupted new file
+                let _ = file.backend.remove_file(&new_path);
+                return Err(AppError::Io(format!("Failed to verify new file: {}", e)));
+            }
+        }
+
+        // Step 4: Only delete the original file after successful verification
+        let new_external_path = file.to_external(&new_path);
+        match file.backend.remove_file(old_path) {
+            Ok(_) => {
+                debug!("Successfully deleted original file");
+            }
+            Err(e) => {
+                warn!("Failed to delete original file: {}", e);
+                // The rename was successful, but cleanup failed
+                // Return success but log the warning
+            }
+        }
+
+        let old_external_path = file.to_external(old_path);
+        update_file_usage(app, |usage| {
+            frecency::rename(usage, &old_external_path, &new_external_path)
+        });
+        update_pins(app, |pins| pins::rename(pins, &old_external_path, &new_external_path));
+        record_activity(
+            app,
+            activity::ActivityEntry::new(activity::ActivityKind::Renamed, new_external_path.clone(), current_workspace_key(state, window.label()))
+                .with_previous_path(old_external_path),
+            None,
+        );
+        Ok(new_external_path)
+    })
+    .await
+}
+
+/// Writes `content` into `dir` under the first free name: `<stem>.excalidraw`
+/// when `keep_name` is set, then "<stem> copy", "<stem> copy 2", ...
+fn create_copy(
+    target: &storage::ResolvedPath,
+    dir: &Path,
+    stem: &str,
+    content: &str,
+    keep_name: bool,
+) -> Result<PathBuf, AppError> {
+    let first = if keep_name { 0 } else { 1 };
+    for counter in first..=100 {
+        let name = match counter {
+            0 => format!("{}.excalidraw", stem),
+            1 => format!("{} copy.excalidraw", stem),
+            _ => format!("{} copy {}.excalidraw", stem, counter),
+        };
+        let path = security::safe_path_join(dir, &name)?;
+        match target.backend.create_new(&path, content) {
+            Ok(()) => return Ok(path),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(AppError::Io("Could not find unique file name".to_string()))
+}
+
+/// Copies a drawing next to itself as "<name> copy", "<name> copy 2", ...
+/// and returns the new path
+#[tauri::command]
+async fn duplicate_file(app: AppHandle, file_path: String) -> Result<String, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        if !file.backend.exists(&file.path) {
+            return Err(AppError::NotFound("File does not exist".to_string()));
+        }
+        security::validate_excalidraw_file(&file.path)?;
+
+        let parent = file.path.parent().ok_or_else(|| AppError::InvalidPath("Invalid file path".to_string()))?;
+        let stem = file
+            .path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| AppError::InvalidPath("Invalid file name".to_string()))?;
+        let content = file.backend.read_to_string(&file.path)?;
+
+        let path = create_copy(&file, parent, stem, &content, false)?;
+        info!("Duplicated {} to {:?}", file_path, path);
+        Ok(paths::normalize(&path))
+    })
+    .await
+}
+
+/// Copies a drawing into `target_directory`, possibly on another backend, as
+/// `new_name` or under its own name; a taken name gets a " copy" suffix.
+/// Returns the new path.
+#[tauri::command]
+async fn copy_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    source_path: String,
+    target_directory: String,
+    new_name: Option<String>,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let source = resolve_in_vault(state, &source_path)?;
+        if !source.backend.exists(&source.path) {
+            return Err(AppError::NotFound("Source file does not exist".to_string()));
+        }
+        security::validate_excalidraw_file(&source.path)?;
+
+        let target_dir = resolve_in_vault(state, &target_directory)?;
+        if !target_dir.backend.is_dir(&target_dir.path) {
+            return Err(AppError::InvalidPath("Target is not a directory".to_string()));
+        }
+
+        let stem = match new_name.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+            Some(name) => name.strip_suffix(".excalidraw").unwrap_or(name).to_string(),
+            None => source
+                .path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .ok_or_else(|| AppError::InvalidPath("Invalid file name".to_string()))?
+                .to_string(),
+        };
+        let content = source.backend.read_to_string(&source.path)
+            .context("Failed to read source file")?;
+
+        let path = create_copy(&target_dir, &target_dir.path, &stem, &content, true)?;
+        let external_path = target_dir.to_external(&path);
+        info!("Copied {} to {}", source_path, external_path);
+        record_activity(
+            app,
+            activity::ActivityEntry::new(activity::ActivityKind::Created, external_path.clone(), current_workspace_key(state, window.label())),
+            None,
+        );
+        Ok(external_path)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn list_templates(app: AppHandle) -> Result<Vec<templates::TemplateInfo>, AppError> {
+    Ok(templates::list(&templates::dir(&app)?))
+}
+
+/// Copies a drawing into the templates directory as `name`, replacing a
+/// template of the same name
+#[tauri::command]
+async fn save_as_template(
+    app: AppHandle,
+    file_path: String,
+    name: String,
+) -> Result<templates::TemplateInfo, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        let content = file.backend.read_to_string(&file.path)?;
+        security::validate_excalidraw_content(&content)?;
+
+        let dir = templates::dir(app)?;
+        fs::create_dir_all(&dir).context("Failed to create templates directory")?;
+        let path = templates::path(&dir, &name)?;
+        fs::write(&path, content).context("Failed to write template")?;
+        info!("Saved {} as template {:?}", file_path, path);
+
+        let _ = menu::update_templates_menu(app);
+        templates::list(&dir)
+            .into_iter()
+            .find(|t| Path::new(&t.path) == path)
+            .ok_or_else(|| AppError::Internal("Saved template disappeared".to_string()))
+    })
+    .await
+}
+
+#[tauri::command]
+async fn delete_template(app: AppHandle, name: String) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, _state| {
+        let path = templates::path(&templates::dir(app)?, &name)?;
+        if !path.exists() {
+            return Err(AppError::NotFound(format!("Template not found: {}", name)));
+        }
+        fs::remove_file(&path).context("Failed to delete template")?;
+        let _ = menu::update_templates_menu(app);
+        Ok(())
+    })
+    .await
+}
+
+/// Renders one drawing to an SVG file, with the branding of its folder
+#[tauri::command]
+async fn export_file_svg(
+    app: AppHandle,
+    file_path: String,
+    output_path: String,
+) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let source = state.vault.check(Path::new(&file_path))?;
+        security::validate_excalidraw_file(&source)?;
+        let output = state.vault.check(Path::new(&output_path))?;
+
+        let content = compression::read_to_string(&source).context("Failed to read file")?;
+        let all_branding = load_export_branding(app)?;
+        let svg = export::render_file_svg(&content, branding::for_path(&all_branding, &source), None)?;
+        fs::write(&output, svg).context("Failed to write SVG")?;
+        Ok(())
+    })
+    .await
+}
+
+/// Converts a drawing's shapes and arrows to a Mermaid flowchart, reporting
+/// the elements that have no equivalent
+#[tauri::command]
+async fn export_mermaid(
+    app: AppHandle,
+    file_path: String,
+) -> Result<mermaid::MermaidReport, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let source = state.vault.check(Path::new(&file_path))?;
+        security::validate_excalidraw_file(&source)?;
+
+        let content = compression::read_to_string(&source).context("Failed to read file")?;
+        let scene: serde_json::Value = serde_json::from_str(&content)?;
+        let report = mermaid::convert(&scene);
+        if report.nodes == 0 {
+            return Err(AppError::InvalidInput(
+                "No rectangles, ellipses or diamonds to convert".to_string(),
+            ));
+        }
+        Ok(report)
+    })
+    .await
+}
+
+/// Converts an Obsidian canvas into a new drawing at `target`
+#[tauri::command]
+async fn import_json_canvas(
+    app: AppHandle,
+    path: String,
+    target: String,
+) -> Result<json_canvas::CanvasReport, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let source = state.vault.check(Path::new(&path))?;
+        if source.extension().is_none_or(|ext| ext != "canvas") {
+            return Err(AppError::InvalidPath("Expected a .canvas file".to_string()));
+        }
+        let target = resolve_in_vault(state, &target)?;
+        security::validate_excalidraw_file(&target.path)?;
+        if target.backend.exists(&target.path) {
+            return Err(AppError::AlreadyExists(target.to_external(&target.path)));
+        }
+
+        let content = fs::read_to_string(&source).context("Failed to read canvas")?;
+        let canvas: json_canvas::Canvas = serde_json::from_str(&content)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid canvas: {}", e)))?;
+        let (elements, mut report) = json_canvas::to_elements(&canvas);
+        let scene = serde_json::json!({
+            "type": "excalidraw",
+            "version": 2,
+            "source": "ExcaliApp",
+            "elements": elements,
+        });
+        let repaired = repair::validate_and_repair_scene(&scene.to_string())?;
+        target.backend.write(&target.path, &repaired.content)?;
+
+        report.output_path = target.to_external(&target.path);
+        info!(
+            "Imported {} as {}: {} nodes, {} edges, {} skipped",
+            source.display(),
+            report.output_path,
+            report.nodes,
+            report.edges,
+            report.skipped
+        );
+        Ok(report)
+    })
+    .await
+}
+
+/// Writes a drawing's shapes, text, frames and bound arrows as an Obsidian
+/// canvas at `target`
+#[tauri::command]
+async fn export_json_canvas(
+    app: AppHandle,
+    path: String,
+    target: String,
+) -> Result<json_canvas::CanvasReport, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let source = state.vault.check(Path::new(&path))?;
+        security::validate_excalidraw_file(&source)?;
+        let target = state.vault.check(Path::new(&target))?;
+
+        let content = compression::read_to_string(&source).context("Failed to read file")?;
+        let scene: serde_json::Value = serde_json::from_str(&content)?;
+        let (canvas, mut report) = json_canvas::from_scene(&scene);
+        fs::write(&target, serde_json::to_string_pretty(&canvas)?).context("Failed to write canvas")?;
+
+        report.output_path = paths::normalize(&target);
+        Ok(report)
+    })
+    .await
+}
+
+/// Renders one drawing to a PDF, a page per frame by default, with the
+/// branding of its folder and the watermark of its share permissions
+#[tauri::command]
+async fn export_pdf(
+    app: AppHandle,
+    file_path: String,
+    output_path: String,
+    options: Option<export::PdfOptions>,
+    state: State<'_, AppState>,
+) -> Result<export::PdfReport, AppError> {
+    let source = state.vault.check(Path::new(&file_path))?;
+    security::validate_excalidraw_file(&source)?;
+    let output = state.vault.check(Path::new(&output_path))?;
+
+    let permissions = load_share_permissions(&app)?.remove(&paths::normalize(&source));
+    if let Some(permissions) = &permissions {
+        permissions.check_not_expired()?;
+    }
+    let content = compression::read_to_string(&source).context("Failed to read file")?;
+    let all_branding = load_export_branding(&app)?;
+    let title = compression::with_drawing_extension(&source, false)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let options = options.unwrap_or_default();
+    let report = blocking::run(move || {
+        export::export_pdf(
+            &content,
+            &title,
+            &options,
+            branding::for_path(&all_branding, &source),
+            permissions.as_ref().and_then(|p| p.watermark_text()),
+            &output,
+        )
+    })
+    .await?;
+    info!("Exported {} as a {}-page PDF", file_path, report.pages);
+    Ok(report)
+}
+
+/// Shows a file or folder selected in the system file manager
+#[tauri::command]
+async fn reveal_in_file_manager(app: AppHandle, path: String) -> Result<(), AppError> {
+    let path = security::validate_path(Path::new(&path), None)?;
+    if !path.exists() {
+        return Err(AppError::NotFound(format!("Path does not exist: {}", path.display())));
+    }
+    reveal::reveal(&app, &path)
+}
+
+/// Copies `path` to the clipboard, relative to the window's folder or the
+/// workspace root containing it when `relative` is set; returns the copied text
+#[tauri::command]
+async fn copy_path_to_clipboard(
+    app: AppHandle,
+    window: WebviewWindow,
+    path: String,
+    relative: Option<bool>,
+) -> Result<String, AppError> {
+    copy_path(&app, window.label(), &path, relative.unwrap_or(false))
+}
+
+pub(crate) fn copy_path<R: tauri::Runtime>(
+    app: &AppHandle<R>,
+    label: &str,
+    path: &str,
+    relative: bool,
+) -> Result<String, AppError> {
+    use tauri_plugin_clipboard_manager::ClipboardExt;
+
+    let text = if relative {
+        let state = app.state::<AppState>();
+        let roots = state
+            .windows
+            .current_directory(label)
+            .into_iter()
+            .chain(menu::stored_preferences(app).workspace_roots.into_iter().map(PathBuf::from));
+        roots
+            .filter_map(|root| paths::relative(&root, Path::new(path)))
+            .find(|relative| !relative.is_empty())
+            .unwrap_or_else(|| path.to_string())
+    } else {
+        path.to_string()
+    };
+
+    app.clipboard()
+        .write_text(text.clone())
+        .map_err(|e| AppError::Internal(format!("Failed to copy path: {}", e)))?;
+    Ok(text)
+}
+
+/// Keeps a drawing at the top of its folder in the window's workspace
+#[tauri::command]
+async fn pin_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    path: String,
+) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        if !file.backend.exists(&file.path) {
+            return Err(AppError::NotFound("File does not exist".to_string()));
+        }
+        let workspace = current_workspace_key(state, window.label())
+            .ok_or_else(|| AppError::InvalidInput("No folder is open".to_string()))?;
+
+        let external_path = file.to_external(&file.path);
+        update_pins(app, |pins| pins::pin(pins, &workspace, &external_path));
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+async fn unpin_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    path: String,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    let Some(workspace) = current_workspace_key(&state, window.label()) else {
+        return Ok(());
+    };
+    update_pins(&app, |pins| pins::unpin(pins, &workspace, &path));
+    Ok(())
+}
+
+/// Pinned drawings of the window's workspace, in the order they were pinned
+#[tauri::command]
+async fn get_pinned_files(
+    app: AppHandle,
+    window: WebviewWindow,
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, AppError> {
+    Ok(pinned_files(&app, &state, window.label()))
+}
+
+/// Locks a drawing, or a folder with everything in it, against saving,
+/// renaming, moving and deleting; `read_only: false` lifts the lock
+#[tauri::command]
+async fn set_read_only(
+    app: AppHandle,
+    path: String,
+    read_only: bool,
+) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let entry = resolve_in_vault(state, &path)?;
+        if !entry.backend.exists(&entry.path) {
+            return Err(AppError::NotFound("File or directory does not exist".to_string()));
+        }
+        if !entry.backend.is_dir(&entry.path) {
+            security::validate_excalidraw_file(&entry.path)?;
+        }
+
+        let external_path = entry.to_external(&entry.path);
+        let mut preferences = load_preferences(app);
+        if read_only::set(&mut preferences.read_only_paths, &external_path, read_only) {
+            store_preferences(app, &preferences)?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Locked drawings and folders, everything below the folders included
+#[tauri::command]
+async fn get_read_only_paths(app: AppHandle) -> Result<Vec<String>, AppError> {
+    Ok(load_preferences(&app).read_only_paths)
 }
 
+/// Mirrors a vault to a WebDAV folder, or with `config: None` stops syncing
+/// it. An empty password keeps the stored one. The server is checked, and
+/// the folder created, before anything is saved.
 #[tauri::command]
-async fn get_file_tree(directory: String) -> Result<Vec<FileTreeNode>, String> {
-    let path = Path::new(&directory);
+async fn configure_webdav_sync(
+    app: AppHandle,
+    directory: String,
+    config: Option<webdav::WebDavConfig>,
+    state: State<'_, AppState>,
+) -> Result<Option<sync::SyncConfigSummary>, AppError> {
+    let vault = resolve_in_vault(&state, &directory)?;
+    if !vault.is_local() || !vault.backend.is_dir(&vault.path) {
+        return Err(AppError::InvalidPath("Only local folders can be synced".to_string()));
+    }
+    let key = paths::normalize(&vault.path);
+    let mut configs: sync::ConfigMap = load_sync_store(&app, sync::CONFIGS_KEY);
+
+    let Some(mut config) = config else {
+        configs.remove(&key);
+        save_sync_store(&app, sync::CONFIGS_KEY, &configs)?;
+        let mut states: sync::StateMap = load_sync_store(&app, sync::STATE_KEY);
+        states.remove(&key);
+        save_sync_store(&app, sync::STATE_KEY, &states)?;
+        info!("Stopped syncing {}", key);
+        return Ok(None);
+    };
+
+    if config.password.is_empty()
+        && let Some(existing) = configs.get(&key)
+    {
+        config.password = existing.password.clone();
+    }
+    let client = ai::build_client(std::time::Duration::from_secs(30), &load_preferences(&app).proxy)?;
+    webdav::WebDavClient::new(&config, client)?.check().await?;
+
+    let summary = sync::SyncConfigSummary::new(&config);
+    // Another server means another history
+    if configs.get(&key).is_none_or(|existing| existing.url != config.url) {
+        let mut states: sync::StateMap = load_sync_store(&app, sync::STATE_KEY);
+        states.remove(&key);
+        save_sync_store(&app, sync::STATE_KEY, &states)?;
+    }
+    configs.insert(key.clone(), config);
+    save_sync_store(&app, sync::CONFIGS_KEY, &configs)?;
+    info!("Syncing {} with {}", key, summary.config.url);
+    Ok(Some(summary))
+}
+
+#[tauri::command]
+async fn get_webdav_sync(
+    app: AppHandle,
+    directory: String,
+) -> Result<Option<sync::SyncConfigSummary>, AppError> {
+    let key = paths::normalize(Path::new(&directory));
+    let configs: sync::ConfigMap = load_sync_store(&app, sync::CONFIGS_KEY);
+    Ok(configs.get(&key).map(sync::SyncConfigSummary::new))
+}
+
+/// Uploads local changes and downloads the server's for one synced vault
+#[tauri::command]
+async fn sync_now(
+    app: AppHandle,
+    directory: String,
+    state: State<'_, AppState>,
+) -> Result<sync::SyncReport, AppError> {
+    let vault = resolve_in_vault(&state, &directory)?;
+    let key = paths::normalize(&vault.path);
+    let configs: sync::ConfigMap = load_sync_store(&app, sync::CONFIGS_KEY);
+    let config = configs
+        .get(&key)
+        .ok_or_else(|| AppError::InvalidInput("WebDAV sync is not set up for this folder".to_string()))?;
+    let Ok(_syncing) = state.syncing.try_lock() else {
+        return Err(AppError::Internal("A sync is already running".to_string()));
+    };
+
+    let client = ai::build_client(std::time::Duration::from_secs(60), &load_preferences(&app).proxy)?;
+    let client = webdav::WebDavClient::new(config, client)?;
+    let ignore = ignore_rules(&app, &vault);
+    let mut states: sync::StateMap = load_sync_store(&app, sync::STATE_KEY);
+    let base = states.remove(&key).unwrap_or_default();
+
+    let stamp = sync::stamp(prompts::now_millis());
+    let (report, files) = sync::run(&client, &vault.path, &ignore, &base, &stamp).await?;
+    // Reloaded, so vaults synced meanwhile keep their state
+    let mut states: sync::StateMap = load_sync_store(&app, sync::STATE_KEY);
+    states.insert(key.clone(), files);
+    save_sync_store(&app, sync::STATE_KEY, &states)?;
+
+    info!(
+        "Synced {}: {} up, {} down, {} deleted here, {} deleted there, {} conflicts, {} failed",
+        key,
+        report.uploaded.len(),
+        report.downloaded.len(),
+        report.deleted_local.len(),
+        report.deleted_remote.len(),
+        report.conflicts.len(),
+        report.failed.len()
+    );
+    Ok(report)
+}
+
+/// Pops up the native menu for a file-tree item at `position`, or at the
+/// cursor; the chosen entry comes back as `file-context-action`
+#[tauri::command]
+async fn show_file_context_menu(
+    app: AppHandle,
+    window: WebviewWindow,
+    path: String,
+    is_directory: bool,
+    position: Option<context_menu::Position>,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    let text = menu_text::MenuText::load(&app, &menu::get_current_locale(&app));
+    let pinned = !is_directory && pinned_files(&app, &state, window.label()).contains(&path);
+    let read_only = load_preferences(&app).read_only_paths.contains(&path);
+    // Only copies whose original is still there can be settled with it
+    let conflict_copy = !is_directory
+        && resolve_in_vault(&state, &path).is_ok_and(|file| {
+            merge::conflict_original(&file.path).is_some_and(|(original, _)| file.backend.exists(&original))
+        });
+    let menu = context_menu::build(&app, &text, is_directory, pinned, read_only, conflict_copy)?;
+    state
+        .context_menu
+        .set(window.label(), context_menu::ContextTarget { path, is_directory });
+
+    match position {
+        Some(position) => {
+            window.popup_menu_at(&menu, tauri::LogicalPosition::new(position.x, position.y))?
+        }
+        None => window.popup_menu(&menu)?,
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn rename_directory(
+    app: AppHandle,
+    window: WebviewWindow,
+    old_path: String,
+    new_name: String,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        // Resolve and validate the old path
+        let dir = resolve_in_vault(state, &old_path)?;
+        let old_path = dir.path.as_path();
+    
+        if !dir.backend.exists(old_path) {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+    
+        if !dir.backend.is_dir(old_path) {
+            return Err(AppError::InvalidPath("Path is not a directory".to_string()));
+        }
+
+        if dir.is_local() && state.vault.is_root(old_path) {
+            return Err(AppError::InvalidPath("Cannot rename an opened folder".to_string()));
+        }
+        read_only::check_tree(&load_preferences(app).read_only_paths, &dir.to_external(old_path))?;
+
+        let parent = old_path.parent()
+            .ok_or_else(|| AppError::InvalidPath("Invalid directory path".to_string()))?;
+    
+        // Safely create the new path
+        let new_path = security::safe_path_join(parent, &new_name)?;
+
+        if dir.backend.exists(&new_path) && new_path != old_path {
+            return Err(AppError::AlreadyExists("A directory with that name already exists".to_string()));
+        }
+
+        // Rename the directory
+        dir.backend.rename(old_path, &new_path)
+            .context("Failed to rename directory")?;
+
+        let old_external_path = dir.to_external(old_path);
+        let new_external_path = dir.to_external(&new_path);
+        update_file_usage(app, |usage| {
+            frecency::rename(usage, &old_external_path, &new_external_path)
+        });
+        update_pins(app, |pins| pins::rename(pins, &old_external_path, &new_external_path));
+        record_activity(
+            app,
+            activity::ActivityEntry::new(activity::ActivityKind::Renamed, new_external_path.clone(), current_workspace_key(state, window.label()))
+                .with_previous_path(old_external_path),
+            None,
+        );
+        Ok(new_external_path)
+    })
+    .await
+}
+
+/// Moves a folder with everything in it under `target_parent` on the same
+/// storage and returns its new path
+#[tauri::command]
+async fn move_directory(
+    app: AppHandle,
+    window: WebviewWindow,
+    source_path: String,
+    target_parent: String,
+    operation_id: Option<String>,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let source = resolve_in_vault(state, &source_path)?;
+        if !source.backend.is_dir(&source.path) {
+            return Err(AppError::NotFound("Directory does not exist".to_string()));
+        }
+        if source.is_local() && state.vault.is_root(&source.path) {
+            return Err(AppError::InvalidPath("Cannot move an opened folder".to_string()));
+        }
+        read_only::check_tree(&load_preferences(app).read_only_paths, &source.to_external(&source.path))?;
+
+        let target = resolve_in_vault(state, &target_parent)?;
+        if !target.backend.is_dir(&target.path) {
+            return Err(AppError::InvalidPath("Target is not a directory".to_string()));
+        }
+        if !source.same_mount(&target) {
+            return Err(AppError::InvalidInput("Folders can only be moved within the same storage".to_string()));
+        }
+        if target.path.starts_with(&source.path) {
+            return Err(AppError::InvalidPath("Cannot move a folder into itself".to_string()));
+        }
+
+        let name = source.path.file_name()
+            .ok_or_else(|| AppError::InvalidPath("Invalid directory name".to_string()))?;
+        let new_path = security::safe_path_join(&target.path, &name.to_string_lossy())?;
+        if new_path == source.path {
+            return Ok(source.to_external(&source.path));
+        }
+        if target.backend.exists(&new_path) {
+            return Err(AppError::AlreadyExists("A file or directory with that name already exists in the target directory".to_string()));
+        }
+
+        // A rename keeps nested content as is; the watcher reports it as one
+        // move. It can't be split up, so progress only marks start and end.
+        let operation = state.operations.start(app, window.label(), operation_id)?;
+        operation.progress(0, 1, paths::normalize(&source.path));
+        source.backend.rename(&source.path, &new_path)
+            .context("Failed to move directory")?;
+        operation.progress(1, 1, paths::normalize(&new_path));
+
+        let old_external_path = source.to_external(&source.path);
+        let new_external_path = target.to_external(&new_path);
+        update_file_usage(app, |usage| {
+            frecency::rename(usage, &old_external_path, &new_external_path)
+        });
+        update_pins(app, |pins| pins::rename(pins, &old_external_path, &new_external_path));
+        record_activity(
+            app,
+            activity::ActivityEntry::new(activity::ActivityKind::Moved, new_external_path.clone(), current_workspace_key(state, window.label()))
+                .with_previous_path(old_external_path),
+            None,
+        );
+        Ok(new_external_path)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn delete_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    file_path: String,
+) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        delete_file_at(app, state, window.label(), &file_path)
+    })
+    .await
+}
+
+/// Deletes one drawing; its content stays in the activity timeline and, unless
+/// the retention is 0, in Recently Deleted
+fn delete_file_at(
+    app: &AppHandle,
+    state: &AppState,
+    label: &str,
+    file_path: &str,
+) -> Result<(), AppError> {
+    // Resolve the storage backend; local paths are validated against traversal attacks
+    let file = resolve_in_vault(state, file_path)?;
+    
+    if !file.backend.exists(&file.path) {
+        return Err(AppError::NotFound("File does not exist".to_string()));
+    }
+    
+    // Ensure we're only deleting excalidraw files
+    security::validate_excalidraw_file(&file.path)?;
+    let preferences = load_preferences(app);
+    read_only::check(&preferences.read_only_paths, &file.to_external(&file.path))?;
+    if file.is_local() {
+        file_locks::check_not_locked(&file.path, prompts::now_millis())?;
+    }
+    let external_path = file.to_external(&file.path);
+
+    // Kept in Recently Deleted first, from where it can be put back as is; a
+    // drawing that can't be kept stays where it is
+    let (previous, kept) = if preferences.deleted_retention_days > 0 {
+        let content = file.backend.read_to_string(&file.path).context("Failed to keep deleted drawing")?;
+        let name = file.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let bin = recently_deleted_dir(app)?;
+        let entry = recently_deleted::keep_file(&bin, &external_path, &paths::compose(&name), &content)?;
+        (Some(content), Some((bin, entry.id)))
+    } else {
+        // Still a snapshot, so the deletion can be undone from the timeline
+        (file.backend.read_to_string(&file.path).ok(), None)
+    };
+
+    if let Err(e) = file.backend.remove_file(&file.path) {
+        if let Some((bin, id)) = &kept {
+            let _ = recently_deleted::remove(bin, id);
+        }
+        return Err(e.into());
+    }
+    if kept.is_some() {
+        purge_recently_deleted(app);
+    }
+
+    clear_draft(app, &external_path);
+    update_file_usage(app, |usage| {
+        usage.remove(&external_path);
+    });
+    update_pins(app, |pins| pins::remove(pins, &external_path));
+    record_activity(
+        app,
+        activity::ActivityEntry::new(activity::ActivityKind::Deleted, external_path, current_workspace_key(state, label)),
+        previous.as_deref(),
+    );
+
+    Ok(())
+}
+
+/// With an `operation_id`, reports each deleted file as `operation-progress`
+/// and can be cancelled between files
+#[tauri::command]
+async fn delete_directory(
+    app: AppHandle,
+    window: WebviewWindow,
+    dir_path: String,
+    operation_id: Option<String>,
+) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let operation = state.operations.start(app, window.label(), operation_id)?;
+        delete_directory_at(app, state, window.label(), &dir_path, |done, total, path| {
+            operation.progress(done, total, paths::normalize(path));
+            operation.check()
+        })
+    })
+    .await
+}
+
+/// Deletes a folder and everything in it. Local folders go file by file,
+/// calling `step` after each, which stops the delete by failing.
+fn delete_directory_at(
+    app: &AppHandle,
+    state: &AppState,
+    label: &str,
+    dir_path: &str,
+    step: impl FnMut(usize, usize, &Path) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    // Resolve the storage backend; local paths are validated against traversal attacks
+    let dir = resolve_in_vault(state, dir_path)?;
+    
+    if !dir.backend.exists(&dir.path) {
+        return Err(AppError::NotFound("Directory does not exist".to_string()));
+    }
+    
+    if !dir.backend.is_dir(&dir.path) {
+        return Err(AppError::InvalidPath("Path is not a directory".to_string()));
+    }
+
+    if dir.is_local() && state.vault.is_root(&dir.path) {
+        return Err(AppError::InvalidPath("Cannot delete an opened folder".to_string()));
+    }
+    let preferences = load_preferences(app);
+    read_only::check_tree(&preferences.read_only_paths, &dir.to_external(&dir.path))?;
+    let external_path = dir.to_external(&dir.path);
+
+    // Local folders are moved to Recently Deleted, and left in place when
+    // that fails; remote ones can't be kept
+    if dir.is_local() && preferences.deleted_retention_days > 0 {
+        let name = dir.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let bin = recently_deleted_dir(app)?;
+        recently_deleted::keep_directory(&bin, &external_path, &paths::compose(&name), &dir.path)?;
+        purge_recently_deleted(app);
+    }
+
+    // Recursively remove the directory and all its contents, unless it was
+    // moved away whole
+    if dir.backend.exists(&dir.path) {
+        if dir.is_local() {
+            remove_dir_stepwise(&dir.path, step)?;
+        }
+        dir.backend.remove_dir_all(&dir.path)
+            .context("Failed to delete directory")?;
+    }
+
+    update_pins(app, |pins| pins::remove(pins, &external_path));
+    record_activity(
+        app,
+        activity::ActivityEntry::new(
+            activity::ActivityKind::Deleted,
+            external_path,
+            current_workspace_key(state, label),
+        ),
+        None,
+    );
+    Ok(())
+}
+
+/// Removes the files below a local folder one at a time, leaving the empty
+/// folders; symlinks are removed, not followed
+fn remove_dir_stepwise(
+    dir: &Path,
+    mut step: impl FnMut(usize, usize, &Path) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    fn collect(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                collect(&entry.path(), files)?;
+            } else {
+                files.push(entry.path());
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    collect(dir, &mut files).context("Failed to read directory")?;
+    let total = files.len();
+    for (index, file) in files.iter().enumerate() {
+        fs::remove_file(file).context(format!("Failed to delete {}", file.display()))?;
+        step(index + 1, total, file)?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn move_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    source_path: String,
+    target_directory: String,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        move_file_to(app, state, window.label(), &source_path, &target_directory)
+    })
+    .await
+}
+
+/// Moves one drawing into `target_directory`, possibly on another backend,
+/// and returns its new path
+fn move_file_to(
+    app: &AppHandle,
+    state: &AppState,
+    label: &str,
+    source_path: &str,
+    target_directory: &str,
+) -> Result<String, AppError> {
+    // Resolve and validate source path
+    let source = resolve_in_vault(state, source_path)?;
+    
+    if !source.backend.exists(&source.path) {
+        return Err(AppError::NotFound("Source file does not exist".to_string()));
+    }
+    
+    // Ensure we're only moving excalidraw files
+    security::validate_excalidraw_file(&source.path)?;
+    read_only::check(&load_preferences(app).read_only_paths, &source.to_external(&source.path))?;
+    
+    // Resolve and validate target directory, which may live on another backend
+    let target_dir = resolve_in_vault(state, target_directory)?;
+    
+    if !target_dir.backend.is_dir(&target_dir.path) {
+        return Err(AppError::InvalidPath("Target is not a directory".to_string()));
+    }
+    
+    // Get the filename from the source
+    let file_name = source
+        .path
+        .file_name()
+        .ok_or_else(|| AppError::InvalidPath("Invalid source file name".to_string()))?;
+    
+    // Create the target path
+    let target_path = security::safe_path_join(&target_dir.path, &file_name.to_string_lossy())?;
+    
+    // Check if source and target are the same (moving to same directory)
+    if source.same_mount(&target_dir) && source.path == target_path {
+        return Ok(source.to_external(&source.path));
+    }
+    
+    // Check if target already exists
+    if target_dir.backend.exists(&target_path) {
+        return Err(AppError::AlreadyExists("A file with that name already exists in the target directory".to_string()));
+    }
+    
+    // Read content from source
+    let content = source.backend.read_to_string(&source.path)
+        .context("Failed to read source file")?;
+    
+    // Write to target
+    target_dir.backend.write(&target_path, &content)
+        .context("Failed to write to target")?;
+    
+    // Verify target file
+    let verify_content = target_dir.backend.read_to_string(&target_path)
+        .context("Failed to verify target file")?;
+    
+    if verify_content != content {
+        // Cleanup failed target file
+        let _ = target_dir.backend.remove_file(&target_path);
+        return Err(AppError::Io("File content verification failed".to_string()));
+    }
+    
+    // Remove source file after successful copy
+    source.backend.remove_file(&source.path)
+        .context("Failed to remove source file")?;
+
+    let old_external_path = source.to_external(&source.path);
+    let new_external_path = target_dir.to_external(&target_path);
+    update_file_usage(app, |usage| {
+        frecency::rename(usage, &old_external_path, &new_external_path)
+    });
+    update_pins(app, |pins| pins::rename(pins, &old_external_path, &new_external_path));
+    record_activity(
+        app,
+        activity::ActivityEntry::new(activity::ActivityKind::Moved, new_external_path.clone(), current_workspace_key(state, label))
+            .with_previous_path(old_external_path),
+        None,
+    );
+    Ok(new_external_path)
+}
+
+/// Sends a batch's progress to the window that started it
+fn emit_batch_progress(app: &AppHandle, label: &str, progress: batch::BatchProgress) {
+    if progress.total >= batch::PROGRESS_MIN_ITEMS {
+        let _ = app.emit_to(label, batch::PROGRESS_EVENT, progress);
+    }
+}
+
+/// Deletes drawings and folders, reporting the outcome for each path. With
+/// an `operation_id`, paths left when it is cancelled fail as cancelled.
+#[tauri::command]
+async fn delete_files(
+    app: AppHandle,
+    window: WebviewWindow,
+    paths: Vec<String>,
+    operation_id: Option<String>,
+) -> Result<batch::BatchReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let label = window.label();
+        let operation = state.operations.start(app, label, operation_id)?;
+        let _paused = WatcherPause::new(app);
+        let report = batch::run(
+            batch::BatchOperation::Delete,
+            paths,
+            |path| {
+                operation.check()?;
+                let is_dir = resolve_in_vault(state, path).is_ok_and(|entry| entry.backend.is_dir(&entry.path));
+                let result = if is_dir {
+                    delete_directory_at(app, state, label, path, |_, _, _| operation.check())
+                } else {
+                    delete_file_at(app, state, label, path)
+                };
+                result.map(|()| None)
+            },
+            |progress| {
+                operation.progress(progress.done, progress.total, progress.path.as_str());
+                emit_batch_progress(app, label, progress)
+            },
+        );
+        info!("Batch delete: {} deleted, {} failed", report.succeeded, report.failed);
+        Ok(report)
+    })
+    .await
+}
+
+/// Moves drawings into `target_directory`, reporting each new path or
+/// failure; cancellable like `delete_files`
+#[tauri::command]
+async fn move_files(
+    app: AppHandle,
+    window: WebviewWindow,
+    paths: Vec<String>,
+    target_directory: String,
+    operation_id: Option<String>,
+) -> Result<batch::BatchReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let label = window.label();
+        let operation = state.operations.start(app, label, operation_id)?;
+        let _paused = WatcherPause::new(app);
+        let report = batch::run(
+            batch::BatchOperation::Move,
+            paths,
+            |path| {
+                operation.check()?;
+                move_file_to(app, state, label, path, &target_directory).map(Some)
+            },
+            |progress| {
+                operation.progress(progress.done, progress.total, progress.path.as_str());
+                emit_batch_progress(app, label, progress)
+            },
+        );
+        info!("Batch move: {} moved, {} failed", report.succeeded, report.failed);
+        Ok(report)
+    })
+    .await
+}
+
+/// Exports drawings as SVG (`svg`) or slide decks (`reveal`, `marp`). SVGs
+/// go to `output_dir` when given and next to their drawing otherwise; decks
+/// always go next to their drawing. Cancellable like `delete_files`.
+#[tauri::command]
+async fn export_files(
+    app: AppHandle,
+    window: WebviewWindow,
+    paths: Vec<String>,
+    format: String,
+    output_dir: Option<String>,
+    operation_id: Option<String>,
+) -> Result<batch::BatchReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let slide_format = if format.eq_ignore_ascii_case("svg") {
+            None
+        } else {
+            Some(slides::SlideFormat::parse(&format)?)
+        };
+        let output_dir = output_dir
+            .map(|dir| state.vault.check(Path::new(&dir)))
+            .transpose()?;
+        if let Some(dir) = &output_dir {
+            fs::create_dir_all(dir).context("Failed to create output directory")?;
+        }
+        let all_branding = load_export_branding(app)?;
+        let permissions = load_share_permissions(app)?;
+
+        let label = window.label();
+        let operation = state.operations.start(app, label, operation_id)?;
+        let _paused = WatcherPause::new(app);
+        let report = batch::run(
+            batch::BatchOperation::Export,
+            paths,
+            |path| {
+                operation.check()?;
+                let file = resolve_in_vault(state, path)?;
+                if !file.is_local() {
+                    return Err(AppError::InvalidPath("Only local drawings can be exported".to_string()));
+                }
+                security::validate_excalidraw_file(&file.path)?;
+                let branding = branding::for_path(&all_branding, &file.path);
+
+                let output = match slide_format {
+                    Some(format) => slides::export(
+                        &file.path,
+                        format,
+                        |source| permissions.get(&paths::normalize(source)).cloned(),
+                        branding,
+                        &ignore_rules(app, &file),
+                        None,
+                    )?,
+                    None => {
+                        let content = compression::read_to_string(&file.path).context("Failed to read file")?;
+                        let svg = export::render_file_svg(&content, branding, None)?;
+                        let output = match &output_dir {
+                            Some(dir) => dir.join(file.path.file_name()
+                                .ok_or_else(|| AppError::InvalidPath("Invalid file name".to_string()))?),
+                            None => file.path.clone(),
+                        };
+                        let output = compression::with_drawing_extension(&output, false).with_extension("svg");
+                        fs::write(&output, svg).context("Failed to write SVG")?;
+                        output
+                    }
+                };
+                Ok(Some(paths::normalize(&output)))
+            },
+            |progress| {
+                operation.progress(progress.done, progress.total, progress.path.as_str());
+                emit_batch_progress(app, label, progress)
+            },
+        );
+        info!("Batch export: {} exported, {} failed", report.succeeded, report.failed);
+        Ok(report)
+    })
+    .await
+}
+
+/// Converts the plain drawings below `directory` to compressed
+/// `.excalidraw.gz` files, reporting each new path or failure. Read-only
+/// drawings and ones open in a window are left alone. Cancellable like
+/// `delete_files`.
+#[tauri::command]
+async fn compress_existing_files(
+    app: AppHandle,
+    window: WebviewWindow,
+    directory: String,
+    operation_id: Option<String>,
+) -> Result<batch::BatchReport, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let dir = resolve_in_vault(state, &directory)?;
+        if !dir.is_local() {
+            return Err(AppError::InvalidPath(
+                "Compressed drawings are only supported in local folders".to_string(),
+            ));
+        }
+        let mut files = Vec::new();
+        collect_excalidraw_files(&dir, &dir.path, &ignore_rules(app, &dir), &mut files)?;
+        let paths: Vec<String> = files
+            .into_iter()
+            .map(|file| file.path)
+            .filter(|path| !compression::is_compressed(Path::new(path)))
+            .collect();
+        let read_only_paths = load_preferences(app).read_only_paths;
+
+        let label = window.label();
+        let operation = state.operations.start(app, label, operation_id)?;
+        let _paused = WatcherPause::new(app);
+        let report = batch::run(
+            batch::BatchOperation::Compress,
+            paths,
+            |path| {
+                operation.check()?;
+                let file = resolve_in_vault(state, path)?;
+                read_only::check(&read_only_paths, path)?;
+                if state.open_files.is_open(&file.path) {
+                    return Err(AppError::InvalidInput(format!("{} is open in a window", path)));
+                }
+                let content = compression::read_to_string(&file.path).context("Failed to read file")?;
+                security::validate_excalidraw_content(&content)?;
+                let compressed = compress_drawing(app, state, &file, &content)?;
+                Ok(Some(file.to_external(&compressed)))
+            },
+            |progress| {
+                operation.progress(progress.done, progress.total, progress.path.as_str());
+                emit_batch_progress(app, label, progress)
+            },
+        );
+        info!("Compressed drawings: {} compressed, {} failed", report.succeeded, report.failed);
+        Ok(report)
+    })
+    .await
+}
+
+#[tauri::command]
+async fn create_directory(
+    app: AppHandle,
+    parent_path: String,
+    directory_name: String,
+) -> Result<String, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        // Resolve and validate parent path
+        let parent = resolve_in_vault(state, &parent_path)?;
+    
+        if !parent.backend.is_dir(&parent.path) {
+            return Err(AppError::InvalidPath("Parent path is not a directory".to_string()));
+        }
+    
+        // Validate directory name (no path separators, etc.)
+        if directory_name.contains('/') || directory_name.contains('\\') || directory_name.trim().is_empty() {
+            return Err(AppError::InvalidPath("Invalid directory name".to_string()));
+        }
+    
+        // Create the new directory path
+        let new_dir_path = security::safe_path_join(&parent.path, &directory_name)?;
+    
+        // Check if directory already exists
+        if parent.backend.exists(&new_dir_path) {
+            return Err(AppError::AlreadyExists("A file or directory with that name already exists".to_string()));
+        }
+    
+        // Create the directory
+        parent.backend.create_dir(&new_dir_path)
+            .context("Failed to create directory")?;
+    
+        // Verify directory was created
+        if !parent.backend.is_dir(&new_dir_path) {
+            return Err(AppError::Io("Directory creation verification failed".to_string()));
+        }
+    
+        Ok(parent.to_external(&new_dir_path))
+    })
+    .await
+}
+
+fn store_preferences(app: &AppHandle, preferences: &Preferences) -> Result<(), AppError> {
+    use tauri_plugin_store::StoreExt;
+
+    let store = app.store("preferences.json")?;
+
+    let mut value = serde_json::to_value(preferences).unwrap();
+    value["schema_version"] = serde_json::json!(preferences::SCHEMA_VERSION);
+    store.set("preferences", value);
+    store.save().map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn save_preferences(
+    app: AppHandle,
+    mut preferences: Preferences,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    let previous = load_preferences(&app);
+    // Only `set_shortcut` changes shortcuts, so a stale copy cannot undo it
+    preferences.shortcuts = previous.shortcuts;
+    // Likewise for pins and `pin_file` / `unpin_file`
+    preferences.pinned_files = previous.pinned_files;
+    // And for locks and `set_read_only`
+    preferences.read_only_paths = previous.read_only_paths;
+    store_preferences(&app, &preferences)?;
+    state.logger.set_level(preferences.log_level);
+
+    if preferences.watcher != previous.watcher {
+        restart_watchers(&app, &state);
+    }
+
+    // Update recent directories menu
+    let _ = menu::update_recent_directories_menu(&app, preferences.recent_directories.clone());
+    let _ = menu::update_recent_files_menu(&app, preferences.recent_files.clone());
+    let _ = menu::sync_menu_state(
+        &app,
+        &menu::MenuState {
+            sidebar_visible: preferences.sidebar_visible,
+            theme: preferences.theme.clone(),
+            language: None,
+        },
+    );
+
+    Ok(())
+}
+
+/// Checkmarks for toggles the frontend changes without saving preferences,
+/// and for the focused window's own sidebar
+#[tauri::command]
+async fn sync_menu_state(app: AppHandle, state: menu::MenuState) -> Result<(), AppError> {
+    Ok(menu::sync_menu_state(&app, &state)?)
+}
+
+fn log_dir(app: &AppHandle) -> Result<PathBuf, AppError> {
+    Ok(app.path().app_data_dir()?.join(logging::LOG_DIR))
+}
+
+/// The tail of the log files, for attaching to bug reports
+#[tauri::command]
+async fn get_recent_logs(app: AppHandle, lines: Option<usize>) -> Result<Vec<String>, AppError> {
+    Ok(logging::recent_lines(&log_dir(&app)?, lines.unwrap_or(500)))
+}
+
+#[tauri::command]
+async fn open_log_directory(app: AppHandle) -> Result<(), AppError> {
+    use tauri_plugin_opener::OpenerExt;
+
+    let dir = log_dir(&app)?;
+    fs::create_dir_all(&dir)?;
+    app.opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| AppError::Internal(format!("Failed to open log directory: {}", e)))
+}
+
+/// Called once a window's webview listens for events; emits
+/// `open-file-on-startup` to it for its pending target, if any. Later
+/// launches arrive through the `open-paths` event instead.
+#[tauri::command]
+async fn frontend_ready(window: WebviewWindow, state: State<'_, AppState>) -> Result<(), AppError> {
+    let target = state.windows.with(window.label(), |w| {
+        w.ready = true;
+        w.startup_target.take()
+    });
+    if let Some(target) = target {
+        window.emit_to(window.label(), "open-file-on-startup", &target)?;
+    }
+    Ok(())
+}
 
-    if !path.exists() {
-        return Err("Directory does not exist".to_string());
+/// Brings the focused (or main) window forward and opens `targets` in it.
+/// Before its frontend is ready the last target replaces its startup target.
+/// Their folders join the vault, so targets must come from the OS (command
+/// line, Finder) or have been confirmed by the user.
+fn open_launch_targets(app: &AppHandle, mut targets: Vec<launch::LaunchTarget>) {
+    let Some(window) = windows::target_window(app) else {
+        return;
+    };
+    let _ = window.unminimize();
+    let _ = window.show();
+    let _ = window.set_focus();
+    if targets.is_empty() {
+        return;
     }
 
-    let mut tree = Vec::new();
-    build_file_tree(path, &mut tree)?;
-    tree.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-        (true, false) => std::cmp::Ordering::Less,
-        (false, true) => std::cmp::Ordering::Greater,
-        _ => a.name.cmp(&b.name),
+    let state = app.state::<AppState>();
+    for target in &targets {
+        state.vault.allow_root(&target.directory());
+    }
+    let ready = state.windows.with(window.label(), |w| {
+        if !w.ready && let Some(target) = targets.pop() {
+            w.current_directory = Some(target.directory());
+            w.startup_target = Some(target);
+        }
+        w.ready
     });
-    Ok(tree)
+    if ready {
+        let _ = window.emit_to(window.label(), "open-paths", &targets);
+    }
 }
 
-fn collect_excalidraw_files_recursive(
-    dir: &Path,
-    files: &mut Vec<ExcalidrawFile>,
-) -> Result<(), String> {
-    match fs::read_dir(dir) {
-        Ok(entries) => {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.is_file() {
-                    if let Some(extension) = path.extension() {
-                        if extension == "excalidraw" {
-                            if let Some(file_name) = path.file_name() {
-                                files.push(ExcalidrawFile {
-                                    name: file_name.to_string_lossy().to_string(),
-                                    path: path.to_string_lossy().to_string(),
-                                    modified: false,
-                                });
-                            }
-                        }
-                    }
-                } else if path.is_dir() {
-                    collect_excalidraw_files_recursive(&path, files)?;
-                }
+/// Acts on `excaliapp://` links; other schemes are left to the editor. Any
+/// web page can fire these, so a link to a folder outside the opened ones
+/// is only followed once the user agrees.
+fn handle_deep_links(app: &AppHandle, urls: &[tauri::Url]) {
+    let label = windows::target_window(app)
+        .map(|w| w.label().to_string())
+        .unwrap_or_else(|| windows::MAIN_WINDOW.to_string());
+
+    for url in urls {
+        match deeplink::parse(url) {
+            Ok(None) => {}
+            Ok(Some(action)) => {
+                let link = follow_deep_link(app.clone(), label.clone(), url.clone(), action);
+                tauri::async_runtime::spawn(link);
             }
+            Err(e) => reject_deep_link(app, &label, url, &e),
         }
-        Err(e) => return Err(e.to_string()),
     }
-    Ok(())
 }
 
-fn build_file_tree(dir: &Path, tree: &mut Vec<FileTreeNode>) -> Result<(), String> {
-    match fs::read_dir(dir) {
-        Ok(entries) => {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                let name = path
-                    .file_name()
-                    .ok_or("Invalid file name")?
-                    .to_string_lossy()
-                    .to_string();
-
-                if path.is_dir() {
-                    let mut children = Vec::new();
-                    build_file_tree(&path, &mut children)?;
-
-                    // Always include directories (don't filter empty ones)
-                    children.sort_by(|a, b| match (a.is_directory, b.is_directory) {
-                        (true, false) => std::cmp::Ordering::Less,
-                        (false, true) => std::cmp::Ordering::Greater,
-                        _ => a.name.cmp(&b.name),
-                    });
+fn reject_deep_link(app: &AppHandle, label: &str, url: &tauri::Url, e: &AppError) {
+    warn!("Rejected link {}: {}", url, e);
+    let _ = app.emit_to(label, "deep-link-error", e);
+}
 
-                    tree.push(FileTreeNode {
-                        name,
-                        path: path.to_string_lossy().to_string(),
-                        is_directory: true,
-                        modified: false,
-                        children: Some(children),
-                    });
-                } else if path.is_file() {
-                    if let Some(extension) = path.extension() {
-                        if extension == "excalidraw" {
-                            tree.push(FileTreeNode {
-                                name,
-                                path: path.to_string_lossy().to_string(),
-                                is_directory: false,
-                                modified: false,
-                                children: None,
-                            });
-                        }
-                    }
-                }
-            }
+async fn follow_deep_link(app: AppHandle, label: String, url: tauri::Url, action: deeplink::DeepLinkAction) {
+    let state = app.state::<AppState>();
+    let folder = match &action {
+        deeplink::DeepLinkAction::Open { path } if path.is_dir() => Some(path.clone()),
+        deeplink::DeepLinkAction::Open { path } => path.parent().map(Path::to_path_buf),
+        deeplink::DeepLinkAction::New { directory, .. } => directory.clone(),
+    };
+    if let Some(folder) = &folder
+        && state.vault.check(folder).is_err()
+        && !confirm_deep_link(&app, &label, &url, folder).await
+    {
+        let e = AppError::Cancelled(format!("{} was not opened", folder.display()));
+        reject_deep_link(&app, &label, &url, &e);
+        return;
+    }
+
+    let result = match action {
+        deeplink::DeepLinkAction::Open { path } => Ok(launch::LaunchTarget {
+            path: paths::normalize(&path),
+            is_directory: path.is_dir(),
+        }),
+        deeplink::DeepLinkAction::New { template, directory, name } => directory
+            .or_else(|| state.windows.current_directory(&label))
+            .ok_or_else(|| AppError::InvalidInput("No folder to create the drawing in".to_string()))
+            .and_then(|directory| {
+                // Confirmed above, or the window's own folder
+                state.vault.allow_root(&directory);
+                create_drawing(
+                    &app,
+                    &state,
+                    &label,
+                    &paths::normalize(&directory),
+                    name,
+                    template,
+                    &HashMap::new(),
+                )
+            })
+            .map(|path| launch::LaunchTarget { path, is_directory: false }),
+    };
+    match result {
+        Ok(target) => {
+            info!("Opening {} from a link", target.path);
+            open_launch_targets(&app, vec![target]);
         }
-        Err(e) => return Err(e.to_string()),
+        Err(e) => reject_deep_link(&app, &label, &url, &e),
     }
-    Ok(())
 }
 
-fn has_excalidraw_files(dir: &Path) -> Result<bool, String> {
-    match fs::read_dir(dir) {
-        Ok(entries) => {
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.is_file() {
-                    if let Some(extension) = path.extension() {
-                        if extension == "excalidraw" {
-                            return Ok(true);
-                        }
-                    }
-                } else if path.is_dir() && has_excalidraw_files(&path)? {
-                    return Ok(true);
+/// Asks before a link opens or creates a drawing in a folder the user
+/// hasn't opened
+async fn confirm_deep_link(app: &AppHandle, label: &str, url: &tauri::Url, folder: &Path) -> bool {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let text = menu_text::MenuText::load(app, &menu::get_current_locale(app));
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    let mut dialog = app
+        .dialog()
+        .message(format!(
+            "{}\n\n{}\n\n{}",
+            text.get("A link wants to open a folder that isn't open in ExcaliApp:"),
+            folder.display(),
+            url
+        ))
+        .title(text.get("Open Link"))
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            text.get("Open").to_string(),
+            text.get("Cancel").to_string(),
+        ));
+    if let Some(window) = app.get_webview_window(label) {
+        dialog = dialog.parent(&window);
+    }
+    dialog.show(move |confirmed| {
+        let _ = tx.send(confirmed);
+    });
+    rx.await.unwrap_or(false)
+}
+
+/// Asks about unsaved changes before a window closes, and drops the window's
+/// state once it is gone
+fn setup_window_events(window: &WebviewWindow) {
+    let app = window.app_handle().clone();
+    let label = window.label().to_string();
+    window.on_window_event(move |event| match event {
+        tauri::WindowEvent::CloseRequested { api, .. } => {
+            let state = app.state::<AppState>();
+            // Nothing to lose, so close right away
+            if !state.windows.has_unsaved_changes(&label) {
+                return;
+            }
+            api.prevent_close();
+            if state.close_requests.begin(&label) {
+                tauri::async_runtime::spawn(confirm_close(app.clone(), label.clone()));
+            }
+        }
+        tauri::WindowEvent::Focused(true) => {
+            // The shared app menu (macOS) follows the focused window
+            apply_file_menu_state(&app, &label);
+        }
+        tauri::WindowEvent::Destroyed => {
+            let state = app.state::<AppState>();
+            state.open_files.unwatch(&label);
+            state.file_locks.release(&label);
+            let Some(closed) = state.windows.remove(&label) else {
+                return;
+            };
+            // Stop watching its folder unless another window or the workspace uses it
+            if let Some(directory) = closed.current_directory {
+                let key = paths::normalize(&directory);
+                if !state.windows.is_open_elsewhere(&label, &directory)
+                    && !load_preferences(&app).workspace_roots.contains(&key)
+                {
+                    stop_watching(&state, &key);
                 }
             }
         }
-        Err(e) => return Err(e.to_string()),
+        _ => {}
+    });
+}
+
+/// Enables the file-dependent menu items for what window `label` has open
+fn apply_file_menu_state(app: &AppHandle, label: &str) {
+    let state = app.state::<AppState>();
+    let (has_file, dirty) = state
+        .windows
+        .with(label, |w| (w.active_file.is_some(), w.active_file_dirty));
+    if let Err(e) = menu::update_file_items_state(app, has_file, dirty) {
+        warn!("Failed to update menu items: {}", e);
     }
-    Ok(false)
 }
 
+/// Called by the frontend whenever its active drawing or dirty state changes
 #[tauri::command]
-async fn read_file(file_path: String) -> Result<String, String> {
-    // Validate path to prevent traversal attacks
-    let path = Path::new(&file_path);
-    let validated_path = security::validate_path(path, None)?;
-    
-    // Validate it's an excalidraw file
-    security::validate_excalidraw_file(&validated_path)?;
-    
-    // Read and validate content
-    let content = fs::read_to_string(&validated_path)
-        .map_err(|e| e.to_string())?;
-    
-    // Validate the content is valid Excalidraw JSON
-    security::validate_excalidraw_content(&content)?;
-    
-    Ok(content)
+async fn set_active_file_state(
+    app: AppHandle,
+    window: WebviewWindow,
+    active_file: Option<String>,
+    is_dirty: bool,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state.windows.with(window.label(), |w| {
+        w.active_file = active_file;
+        w.active_file_dirty = is_dirty;
+    });
+    apply_file_menu_state(&app, window.label());
+    Ok(())
 }
 
+/// Records unsaved changes to `path` in the calling window, so its tree node
+/// shows them and closing the window asks first
 #[tauri::command]
-async fn save_file(file_path: String, content: String) -> Result<(), String> {
-    // Validate path to prevent traversal attacks
-    let path = Path::new(&file_path);
-    let validated_path = security::validate_path(path, None)?;
-    
-    // Validate it's an excalidraw file
-    security::validate_excalidraw_file(&validated_path)?;
-    
-    // Validate the content before saving
-    security::validate_excalidraw_content(&content)?;
-    
-    fs::write(&validated_path, content)
-        .map_err(|e| e.to_string())?;
-    
+async fn mark_file_dirty(window: WebviewWindow, path: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    state.windows.set_modified(window.label(), &path, true);
     Ok(())
 }
 
 #[tauri::command]
-async fn save_file_as(app: AppHandle, content: String) -> Result<Option<String>, String> {
-    use std::sync::mpsc;
-    use tauri_plugin_dialog::DialogExt;
+async fn mark_file_clean(window: WebviewWindow, path: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    state.windows.set_modified(window.label(), &path, false);
+    Ok(())
+}
 
-    let (tx, rx) = mpsc::channel();
+/// Files with unsaved changes in the calling window
+#[tauri::command]
+async fn get_dirty_files(window: WebviewWindow, state: State<'_, AppState>) -> Result<Vec<String>, AppError> {
+    Ok(state.windows.modified_files(window.label()))
+}
 
-    app.dialog()
-        .file()
-        .add_filter("Excalidraw", &["excalidraw"])
-        .set_title("Save As")
-        .save_file(move |path| {
-            let _ = tx.send(path);
-        });
+/// Opens `file_path` in an additional editor window with its own folder,
+/// unsaved-changes tracking and close confirmation
+#[tauri::command]
+async fn open_in_new_window(
+    app: AppHandle,
+    file_path: String,
+    state: State<'_, AppState>,
+) -> Result<String, AppError> {
+    let file = resolve_in_vault(&state, &file_path)?;
+    if !file.is_local() {
+        return Err(AppError::InvalidInput("Only local drawings can open in a new window".to_string()));
+    }
+    if !file.backend.exists(&file.path) {
+        return Err(AppError::NotFound(format!("File does not exist: {}", file_path)));
+    }
+    security::validate_excalidraw_file(&file.path)?;
 
-    match rx.recv() {
-        Ok(Some(path)) => {
-            let path_str = path.to_string();
-            match fs::write(&path_str, content) {
-                Ok(_) => Ok(Some(path_str)),
-                Err(e) => Err(e.to_string()),
-            }
+    let target = launch::LaunchTarget {
+        path: paths::normalize(&file.path),
+        is_directory: false,
+    };
+    let label = state.windows.next_label();
+    state.windows.with(&label, |w| {
+        w.current_directory = Some(target.directory());
+        w.startup_target = Some(target);
+    });
+
+    let window = tauri::WebviewWindowBuilder::new(&app, &label, tauri::WebviewUrl::App("index.html".into()))
+        .title("OwnExcaliDesk")
+        .inner_size(1600.0, 900.0)
+        .min_inner_size(1600.0, 900.0)
+        .menu(menu::create_menu(&app).map_err(|e| AppError::Internal(e.to_string()))?)
+        .build();
+    let window = match window {
+        Ok(window) => window,
+        Err(e) => {
+            state.windows.remove(&label);
+            return Err(e.into());
         }
-        Ok(None) => Ok(None),
-        Err(e) => Err(e.to_string()),
+    };
+    setup_window_events(&window);
+
+    info!("Opened {} in window {}", file_path, label);
+    Ok(label)
+}
+
+/// Closes the calling window; the app exits with its last window
+/// Switches the native menu to `locale` without a restart
+#[tauri::command]
+async fn set_app_language(app: AppHandle, locale: String) -> Result<(), AppError> {
+    if !menu_text::is_available(&app, &locale) {
+        return Err(AppError::InvalidInput(format!("Unsupported language: {}", locale)));
+    }
+    menu::set_current_locale(&app, &locale)?;
+    let mut preferences = load_preferences(&app);
+    preferences.language = Some(locale.clone());
+    store_preferences(&app, &preferences)?;
+    refresh_menu(&app)?;
+
+    info!("Switched menu language to {}", locale);
+    Ok(())
+}
+
+/// Rebuilds the menu for a new language or new shortcuts
+fn refresh_menu(app: &AppHandle) -> Result<(), AppError> {
+    menu::rebuild_menu(app)?;
+    // The rebuilt menus start with empty recent lists and every item enabled
+    let preferences = load_preferences(app);
+    menu::update_recent_directories_menu(app, preferences.recent_directories)?;
+    menu::update_recent_files_menu(app, preferences.recent_files)?;
+    if let Some(window) = windows::target_window(app) {
+        apply_file_menu_state(app, window.label());
     }
+    Ok(())
 }
 
 #[tauri::command]
-async fn create_new_file(directory: String, file_name: String) -> Result<String, String> {
-    println!(
-        "[create_new_file] Called with directory: {}, file_name: {}",
-        directory, file_name
-    );
+async fn get_shortcuts(app: AppHandle) -> Result<Vec<shortcuts::ShortcutInfo>, AppError> {
+    Ok(shortcuts::Shortcuts::new(&load_preferences(&app).shortcuts).list())
+}
 
-    // Validate and canonicalize the directory path
-    let dir_path = Path::new(&directory);
-    let validated_dir = security::validate_path(dir_path, None)?;
-    
-    if !validated_dir.is_dir() {
-        return Err(format!("Path is not a directory: {}", directory));
+/// Binds `command_id` to `accelerator`, or back to its default when `None`;
+/// an empty accelerator removes the shortcut. Fails without changing anything
+/// when another command already uses the same keys.
+#[tauri::command]
+async fn set_shortcut(
+    app: AppHandle,
+    command_id: String,
+    accelerator: Option<String>,
+) -> Result<(), AppError> {
+    if !shortcuts::is_known_command(&command_id) {
+        return Err(AppError::InvalidInput(format!("Unknown menu command: {}", command_id)));
     }
 
-    // Safely join the filename to the directory
-    let mut path = security::safe_path_join(&validated_dir, &file_name)?;
-    println!("[create_new_file] Initial path: {:?}", path);
+    let mut preferences = load_preferences(&app);
+    match accelerator.map(|a| a.trim().to_string()) {
+        None => {
+            preferences.shortcuts.remove(&command_id);
+        }
+        Some(accelerator) if accelerator.is_empty() => {
+            preferences.shortcuts.insert(command_id.clone(), accelerator);
+        }
+        Some(accelerator) => {
+            shortcuts::canonical(&accelerator)?;
+            let current = shortcuts::Shortcuts::new(&preferences.shortcuts);
+            if let Some(other) = current.conflict(&command_id, &accelerator) {
+                return Err(AppError::AlreadyExists(format!(
+                    "{} is already used by {}",
+                    accelerator, other
+                )));
+            }
+            preferences.shortcuts.insert(command_id.clone(), accelerator);
+        }
+    }
 
-    // Check if file already exists and suggest alternative
-    if path.exists() {
-        println!("[create_new_file] File already exists, finding unique name");
-        // Find a unique name by appending numbers
-        let mut counter = 1;
+    store_preferences(&app, &preferences)?;
+    refresh_menu(&app)?;
+    let _ = app.emit("shortcuts-changed", shortcuts::Shortcuts::new(&preferences.shortcuts).list());
+    info!("Updated shortcut for {}", command_id);
+    Ok(())
+}
 
-        // Get the base name without extension
-        let stem = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .ok_or("Invalid file name")?
-            .to_string(); // Convert to owned String
+/// Writes preferences, AI profiles, prompt templates and custom shortcuts to
+/// one JSON file for another machine. API keys, the proxy password and
+/// machine-specific entries stay out.
+#[tauri::command]
+async fn export_settings(app: AppHandle, path: String) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let path = state.vault.check(Path::new(&path))?;
+        let stored = load_preferences(app);
+        let (ai_profiles, default_ai_profile) = load_ai_profiles(app)?;
 
-        // Handle the .excalidraw extension properly
-        let base_stem = if stem.ends_with(".excalidraw") {
-            stem.trim_end_matches(".excalidraw").to_string()
+        let mut preferences = serde_json::to_value(&stored)?;
+        preferences["schema_version"] = serde_json::json!(preferences::SCHEMA_VERSION);
+        let bundle = preferences::SettingsBundle {
+            format: preferences::BUNDLE_FORMAT.to_string(),
+            app_version: app.package_info().version.to_string(),
+            exported_at: prompts::now_millis(),
+            preferences: preferences::portable(preferences),
+            ai_profiles: ai_profiles.iter().map(profiles::AIProviderProfile::without_secret).collect(),
+            default_ai_profile,
+            prompt_templates: load_prompt_templates(app)?,
+            shortcuts: stored.shortcuts,
+        };
+
+        let content = serde_json::to_string_pretty(&bundle)?;
+        fs::write(&path, content).context("Failed to write settings file")?;
+        info!("Exported settings to {}", path.display());
+        Ok(())
+    })
+    .await
+}
+
+/// Applies a file written by `export_settings`. Preferences are migrated and
+/// laid over the local ones, profiles and templates with the same id are
+/// replaced (profiles keep their local API key), and the shortcuts replace the
+/// custom ones unless they conflict.
+#[tauri::command]
+async fn import_settings(
+    app: AppHandle,
+    path: String,
+) -> Result<preferences::ImportedSettings, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let path = state.vault.check(Path::new(&path))?;
+        let content = fs::read_to_string(&path).context("Failed to read settings file")?;
+        let bundle: preferences::SettingsBundle = serde_json::from_str(&content)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid settings file: {}", e)))?;
+        if bundle.format != preferences::BUNDLE_FORMAT {
+            return Err(AppError::InvalidInput("Not an ExcaliApp settings file".to_string()));
+        }
+
+        let current = load_preferences(app);
+        let current_value = serde_json::to_value(&current)?;
+        let merged = preferences::overlay_imported(&current_value, bundle.preferences);
+        let (mut imported, mut skipped) = preferences::merge_lenient(current, &merged);
+
+        let mut custom_shortcuts = HashMap::new();
+        for (command, accelerator) in bundle.shortcuts {
+            let accelerator = accelerator.trim().to_string();
+            if shortcuts::is_known_command(&command)
+                && (accelerator.is_empty() || shortcuts::canonical(&accelerator).is_ok())
+            {
+                custom_shortcuts.insert(command, accelerator);
+            } else {
+                skipped.push(format!("shortcuts.{}", command));
+            }
+        }
+        // Checked as a whole, since swapped shortcuts only work together
+        let keys = shortcuts::Shortcuts::new(&custom_shortcuts);
+        let conflicting = custom_shortcuts
+            .iter()
+            .any(|(command, accelerator)| !accelerator.is_empty() && keys.conflict(command, accelerator).is_some());
+        let shortcut_count = if conflicting {
+            skipped.push("shortcuts".to_string());
+            0
         } else {
-            stem
+            imported.shortcuts = custom_shortcuts;
+            imported.shortcuts.len()
         };
 
-        loop {
-            let new_name = format!("{}-{}.excalidraw", base_stem, counter);
-            path = dir_path.join(&new_name);
+        let mut templates = load_prompt_templates(app)?;
+        let mut template_count = 0;
+        for template in bundle.prompt_templates {
+            if template.id.trim().is_empty() || template.name.trim().is_empty() {
+                skipped.push("prompt_templates".to_string());
+                continue;
+            }
+            match templates.iter_mut().find(|t| t.id == template.id) {
+                Some(existing) => *existing = template,
+                None => templates.push(template),
+            }
+            template_count += 1;
+        }
+        store_prompt_templates(app, &templates)?;
 
-            if !path.exists() {
-                println!("[create_new_file] Found unique name: {:?}", path);
-                break;
+        let (mut ai_profiles, default_ai_profile) = load_ai_profiles(app)?;
+        let mut profile_count = 0;
+        for mut profile in bundle.ai_profiles {
+            if profile.id.trim().is_empty() || profile.name.trim().is_empty() {
+                skipped.push("ai_profiles".to_string());
+                continue;
             }
-            counter += 1;
+            profile.api_key.clear();
+            profiles::upsert(&mut ai_profiles, profile);
+            profile_count += 1;
+        }
+        let default_ai_profile = default_ai_profile
+            .or(bundle.default_ai_profile)
+            .filter(|id| ai_profiles.iter().any(|p| p.id == *id))
+            .or_else(|| ai_profiles.first().map(|p| p.id.clone()));
+        store_ai_profiles(app, &ai_profiles, default_ai_profile.as_deref())?;
 
-            if counter > 100 {
-                return Err("Could not find unique file name".to_string());
+        store_preferences(app, &imported)?;
+        state.logger.set_level(imported.log_level);
+        if let Some(locale) = imported.language.as_deref()
+            && menu_text::is_available(app, locale)
+        {
+            menu::set_current_locale(app, locale)?;
+        }
+        refresh_menu(app)?;
+        let _ = menu::sync_menu_state(
+            app,
+            &menu::MenuState {
+                sidebar_visible: imported.sidebar_visible,
+                theme: imported.theme.clone(),
+                language: None,
+            },
+        );
+        let _ = app.emit("shortcuts-changed", shortcuts::Shortcuts::new(&imported.shortcuts).list());
+
+        skipped.dedup();
+        if !skipped.is_empty() {
+            warn!("Skipped unusable imported settings: {}", skipped.join(", "));
+        }
+        info!("Imported settings from {}", path.display());
+        Ok(preferences::ImportedSettings {
+            ai_profiles: profile_count,
+            prompt_templates: template_count,
+            shortcuts: shortcut_count,
+            skipped,
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+async fn force_close_app(app: AppHandle, window: WebviewWindow) -> Result<(), AppError> {
+    close_window(&app, &window)
+}
+
+/// Closes `window` without asking; the last window quits the app
+fn close_window(app: &AppHandle, window: &WebviewWindow) -> Result<(), AppError> {
+    if app.webview_windows().len() > 1 {
+        window.destroy()?;
+    } else {
+        app.exit(0);
+    }
+    Ok(())
+}
+
+/// Files window `label` would lose changes to, as the frontend knows them
+fn unsaved_files(state: &AppState, label: &str) -> Vec<String> {
+    let (mut files, active) = state
+        .windows
+        .with(label, |w| (w.modified_files.clone(), w.active_file.clone().filter(|_| w.active_file_dirty)));
+    if let Some(active) = active
+        && !files.contains(&active)
+    {
+        files.push(active);
+    }
+    files
+}
+
+/// The native Save / Don't Save / Cancel dialog for a window with unsaved
+/// changes, run by the backend so a hung webview cannot keep the window open
+/// or lose the changes
+async fn confirm_close(app: AppHandle, label: String) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    let state = app.state::<AppState>();
+    let Some(window) = app.get_webview_window(&label) else {
+        state.close_requests.end(&label);
+        return;
+    };
+
+    let files = unsaved_files(&state, &label);
+    let text = menu_text::MenuText::load(&app, &menu::get_current_locale(&app));
+    let save = text.get("Save").to_string();
+    let discard = text.get("Don't Save").to_string();
+    let names: Vec<String> = files
+        .iter()
+        .map(|f| Path::new(f).file_name().map_or(f.clone(), |n| n.to_string_lossy().to_string()))
+        .collect();
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .message(format!(
+            "{}\n\n{}",
+            text.get("Do you want to save your changes before closing?"),
+            names.join("\n")
+        ))
+        .title(text.get("Unsaved Changes"))
+        .kind(MessageDialogKind::Warning)
+        .parent(&window)
+        .buttons(MessageDialogButtons::YesNoCancelCustom(
+            save.clone(),
+            discard.clone(),
+            text.get("Cancel").to_string(),
+        ))
+        .show_with_result(move |result| {
+            let _ = tx.send(result);
+        });
+    let result = rx.await.unwrap_or_default();
+
+    let close = match close_flow::choice(&result, &save, &discard) {
+        close_flow::CloseChoice::Cancel => false,
+        close_flow::CloseChoice::Discard => {
+            for file in &files {
+                clear_draft(&app, file);
+                state.windows.set_modified(&label, file, false);
             }
+            info!("Closing {} without saving {} file(s)", label, files.len());
+            true
         }
+        close_flow::CloseChoice::Save => save_before_close(&app, &window, &text).await,
+    };
+    state.close_requests.end(&label);
+
+    if close && let Err(e) = close_window(&app, &window) {
+        error!("Failed to close {}: {}", label, e);
     }
+}
 
-    let default_content = serde_json::json!({
-        "type": "excalidraw",
-        "version": 2,
-        "source": "ExcaliApp",
-        "elements": [],
-        "appState": {
-            "gridSize": null,
-            "viewBackgroundColor": "#ffffff"
-        },
-        "files": {}
-    });
+/// Has the frontend save, falling back to the drafts it last wrote when it
+/// doesn't answer; returns whether everything was saved
+async fn save_before_close(app: &AppHandle, window: &WebviewWindow, text: &menu_text::MenuText) -> bool {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
 
-    let content_str = serde_json::to_string_pretty(&default_content)
-        .map_err(|e| format!("Failed to serialize content: {}", e))?;
+    let state = app.state::<AppState>();
+    let label = window.label();
+    let receiver = state.close_requests.expect_save(label);
+    let _ = window.emit_to(label, close_flow::SAVE_EVENT, ());
 
-    println!("[create_new_file] Writing to path: {:?}", path);
-    match fs::write(&path, &content_str) {
-        Ok(_) => {
-            println!("[create_new_file] Successfully created file: {:?}", path);
+    // The frontend reports its own save errors
+    if let Ok(Ok(saved)) = tokio::time::timeout(close_flow::SAVE_TIMEOUT, receiver).await {
+        return saved;
+    }
 
-            // Verify the file was created
-            if !path.exists() {
-                eprintln!("[create_new_file] File doesn't exist after creation!");
-                return Err("File creation verification failed".to_string());
+    warn!("{} did not save within {:?}; writing its drafts", label, close_flow::SAVE_TIMEOUT);
+    let failed = save_drafts(app, label);
+    if failed.is_empty() {
+        return true;
+    }
+    app.dialog()
+        .message(format!(
+            "{}\n\n{}",
+            text.get("These drawings could not be saved, so the window stays open:"),
+            failed.join("\n")
+        ))
+        .title(text.get("Unsaved Changes"))
+        .kind(MessageDialogKind::Error)
+        .parent(window)
+        .show(|_| {});
+    false
+}
+
+/// Writes the last draft of every unsaved file of window `label`; returns the
+/// files without a usable draft
+fn save_drafts(app: &AppHandle, label: &str) -> Vec<String> {
+    let state = app.state::<AppState>();
+    let Ok(dir) = drafts_dir(app) else {
+        return unsaved_files(&state, label);
+    };
+
+    let mut failed = Vec::new();
+    for external_path in unsaved_files(&state, label) {
+        match write_draft(&state, &dir, &external_path) {
+            Ok(()) => {
+                clear_draft(app, &external_path);
+                state.windows.set_modified(label, &external_path, false);
+                info!("Saved {} from its draft", external_path);
+            }
+            Err(e) => {
+                warn!("Could not save {} from its draft: {}", external_path, e);
+                failed.push(external_path);
             }
+        }
+    }
+    failed
+}
 
-            // Verify we can read it back
-            match fs::read_to_string(&path) {
-                Ok(read_content) => {
-                    println!(
-                        "[create_new_file] File verified, content length: {}",
-                        read_content.len()
-                    );
-                }
-                Err(e) => {
-                    eprintln!(
-                        "[create_new_file] Warning: Could not verify file content: {}",
-                        e
-                    );
-                }
+fn write_draft(state: &AppState, dir: &Path, external_path: &str) -> Result<(), AppError> {
+    let file = resolve_in_vault(state, external_path)?;
+    let draft = drafts::load(dir, external_path)?
+        .ok_or_else(|| AppError::NotFound(format!("No draft of {}", external_path)))?;
+    security::validate_excalidraw_content(&draft.content)?;
+    state.recent_saves.record(&file.path, &draft.content);
+    state.open_files.note_write(&file.path, &draft.content);
+    state.content_cache.invalidate(&file.path);
+    file.backend.write(&file.path, &draft.content)?;
+    Ok(())
+}
+
+/// The frontend's answer to `save-before-close`
+#[tauri::command]
+async fn finish_save_before_close(
+    window: WebviewWindow,
+    saved: bool,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    if !state.close_requests.save_finished(window.label(), saved) {
+        debug!("{} reported a save nobody waited for", window.label());
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn restart_app(app: AppHandle) -> Result<(), AppError> {
+    app.restart();
+}
+
+#[tauri::command]
+async fn set_title(title: String, window: tauri::Window) -> Result<(), AppError> {
+    window.set_title(&title)
+        .context("Failed to set title")?;
+    Ok(())
+}
+
+
+/// Rescans every drawing below `directory` for links on a background thread
+/// Removes lock files left behind by crashed instances, in the background
+fn clean_stale_locks(app: &AppHandle, directory: &Path) {
+    let task_app = app.clone();
+    let directory = directory.to_path_buf();
+    let target = paths::normalize(&directory);
+    let background = &app.state::<AppState>().background;
+    background.submit(background::TaskKind::LockCleanup, target, background::TaskPriority::Low, move |_| {
+        let ignore = ignore_rules(&task_app, &storage::ResolvedPath::local(&directory));
+        let removed = file_locks::clean_stale(&directory, &ignore, prompts::now_millis());
+        if removed > 0 {
+            info!("Removed {} stale lock file(s) below {}", removed, directory.display());
+        }
+    });
+}
+
+fn rebuild_link_index(app: &AppHandle, directory: &Path) {
+    let task_app = app.clone();
+    let directory = directory.to_path_buf();
+    let target = paths::normalize(&directory);
+    let background = &app.state::<AppState>().background;
+    background.submit(background::TaskKind::LinkIndex, target, background::TaskPriority::Low, move |task| {
+        let ignore = ignore_rules(&task_app, &storage::ResolvedPath::local(&directory));
+        let mut files = Vec::new();
+        if let Err(e) = collect_excalidraw_files_recursive(&directory, &ignore, &mut files) {
+            warn!("Failed to index links: {}", e);
+            return;
+        }
+
+        let state = task_app.state::<AppState>();
+        let scanned = state.background.run(files, |file| {
+            if task.is_cancelled() {
+                return None;
+            }
+            let path = PathBuf::from(&file.path);
+            let content = compression::read_to_string(&path).unwrap_or_default();
+            state.background.throttle_io(content.len());
+            let links = links::scan(&path, &content);
+            Some((path, links))
+        });
+        // A cancelled scan is incomplete; the index keeps what it had
+        if task.is_cancelled() {
+            info!("Link indexing of {:?} cancelled", directory);
+            return;
+        }
+
+        let mut index = state.links.lock().unwrap();
+        index.remove_under(&paths::normalize(&directory));
+        let count = scanned.len();
+        for (path, links) in scanned.into_iter().flatten() {
+            index.insert(&path, links);
+        }
+        info!("Indexed links of {} drawing(s) in {:?}", count, directory);
+    });
+}
+
+/// The metadata index in the app data folder; without it queries scan the
+/// file system
+fn open_metadata_index(app: &AppHandle) -> metadata_index::MetadataIndex {
+    let opened = app
+        .path()
+        .app_data_dir()
+        .map_err(AppError::from)
+        .and_then(|dir| metadata_index::MetadataIndex::open(&dir.join(metadata_index::DB_FILE)));
+    opened.unwrap_or_else(|e| {
+        warn!("Failed to open the metadata index: {}", e);
+        metadata_index::MetadataIndex::default()
+    })
+}
+
+/// Brings the metadata index of `directory` up to date, reading only the
+/// drawings whose size or modification time changed since they were indexed.
+/// Once `task` is cancelled the drawings read so far are stored and the
+/// vault stays marked as not indexed.
+fn index_vault_metadata(app: &AppHandle, directory: &Path, task: Option<&background::TaskHandle>) -> Result<(), AppError> {
+    let state = app.state::<AppState>();
+    let key = paths::normalize(directory);
+    let ignore = ignore_rules(app, &storage::ResolvedPath::local(directory));
+    let mut files = Vec::new();
+    collect_excalidraw_files_recursive(directory, &ignore, &mut files)?;
+
+    let stamps = state.metadata.stamps_under(&key)?;
+    let current: HashSet<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    for gone in stamps.keys().filter(|path| !current.contains(path.as_str())) {
+        state.metadata.remove_under(gone)?;
+    }
+    let changed: Vec<ExcalidrawFile> = files
+        .into_iter()
+        .filter(|f| stamps.get(&f.path) != Some(&(f.metadata.size, f.metadata.modified_at)))
+        .collect();
+    let count = changed.len();
+    let cancelled = || task.is_some_and(|task| task.is_cancelled());
+    let indexed: Vec<_> = state
+        .background
+        .run(changed, |file| {
+            if cancelled() {
+                return None;
             }
+            let path = PathBuf::from(&file.path);
+            let content = compression::read_to_string(&path).unwrap_or_default();
+            state.background.throttle_io(content.len());
+            Some(metadata_index::extract(&path, &content, &file.metadata))
+        })
+        .into_iter()
+        .flatten()
+        .collect();
+    state.metadata.upsert(&indexed)?;
+    if cancelled() {
+        info!("Metadata indexing of {:?} cancelled after {} drawing(s)", directory, indexed.len());
+        return Ok(());
+    }
+    state.metadata.mark_indexed(&key);
+    info!("Indexed metadata of {} changed drawing(s) in {:?}", count, directory);
+    Ok(())
+}
 
-            Ok(path.to_string_lossy().to_string())
+fn rebuild_metadata_index(app: &AppHandle, directory: &Path) {
+    let task_app = app.clone();
+    let directory = directory.to_path_buf();
+    let target = paths::normalize(&directory);
+    let state = app.state::<AppState>();
+    state.metadata.mark_stale(&target);
+    state.background.submit(background::TaskKind::MetadataIndex, target, background::TaskPriority::Normal, move |task| {
+        if let Err(e) = index_vault_metadata(&task_app, &directory, Some(task)) {
+            warn!("Failed to index metadata: {}", e);
         }
-        Err(e) => {
-            eprintln!("[create_new_file] Failed to create file: {}", e);
-            Err(format!("Failed to create file: {}", e))
+    });
+}
+
+/// Indexes `directory` first if the index doesn't cover it yet
+fn ensure_metadata_index(app: &AppHandle, state: &AppState, directory: &Path) -> Result<(), AppError> {
+    if !state.metadata.is_indexed(&paths::normalize(directory)) {
+        index_vault_metadata(app, directory, None)?;
+    }
+    Ok(())
+}
+
+/// Re-reads one drawing into the metadata index, or drops it once it's gone
+fn update_file_metadata(state: &AppState, path: &Path) {
+    let result = match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => {
+            let content = compression::read_to_string(path).unwrap_or_default();
+            let metadata = storage::EntryMetadata::from_fs(&metadata);
+            state.metadata.upsert(&[metadata_index::extract(path, &content, &metadata)])
         }
+        _ => state.metadata.remove_under(&paths::normalize(path)),
+    };
+    if let Err(e) = result {
+        debug!("{}", e);
     }
 }
 
+/// Drawings below the window's folder whose name, text or `#tags` contain
+/// every word of `query`
 #[tauri::command]
-async fn get_preferences(app: AppHandle) -> Result<Preferences, String> {
-    use tauri_plugin_store::StoreExt;
+async fn search_drawings(
+    app: AppHandle,
+    window: WebviewWindow,
+    query: String,
+    directory: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<metadata_index::SearchHit>, AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let directory = directory
+            .map(PathBuf::from)
+            .or_else(|| state.windows.current_directory(window.label()))
+            .ok_or_else(|| AppError::InvalidInput("No folder is open".to_string()))?;
+        let directory = state.vault.check(&directory)?;
+        ensure_metadata_index(app, state, &directory)?;
+        state
+            .metadata
+            .search(&paths::normalize(&directory), &query, limit.unwrap_or(50))
+    })
+    .await
+}
 
-    let store = app.store("preferences.json").map_err(|e| e.to_string())?;
+#[tauri::command]
+async fn get_outgoing_links(
+    app: AppHandle,
+    file_path: String,
+) -> Result<Vec<links::DrawingLink>, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        let key = paths::normalize(&file.path);
 
-    let prefs = if let Some(value) = store.get("preferences") {
-        // Try to deserialize, but ensure all fields have values
-        match serde_json::from_value::<Preferences>(value.clone()) {
-            Ok(mut p) => {
-                // Ensure recent_directories is never None/null
-                if p.recent_directories.is_empty() {
-                    p.recent_directories = Vec::new();
-                }
-                p
-            }
-            Err(_) => Preferences::default(),
+        let outgoing = state.links.lock().unwrap().outgoing(&key);
+        if !outgoing.is_empty() {
+            return Ok(outgoing);
         }
-    } else {
-        Preferences::default()
-    };
 
-    Ok(prefs)
+        // Drawings outside the indexed workspace are scanned on demand
+        let content = file.backend.read_to_string(&file.path)?;
+        Ok(links::scan(&file.path, &content))
+    })
+    .await
 }
 
 #[tauri::command]
-async fn rename_file(old_path: String, new_name: String) -> Result<String, String> {
-    // Validate the old path
-    let old_path = Path::new(&old_path);
-    let validated_old = security::validate_path(old_path, None)?;
-    
-    if !validated_old.exists() {
-        return Err("File does not exist".to_string());
-    }
-    
-    security::validate_excalidraw_file(&validated_old)?;
+async fn get_backlinks(
+    app: AppHandle,
+    file_path: String,
+) -> Result<Vec<links::DrawingLink>, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let file = resolve_in_vault(state, &file_path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        Ok(state.links.lock().unwrap().backlinks(&paths::normalize(&file.path)))
+    })
+    .await
+}
 
-    let parent = validated_old.parent().ok_or("Invalid file path")?;
-    
-    // Safely create the new path
-    let new_path = security::safe_path_join(parent, &new_name)?;
-    
-    // Ensure the new path also has .excalidraw extension
-    let new_path = if new_path.extension() != Some(std::ffi::OsStr::new("excalidraw")) {
-        new_path.with_extension("excalidraw")
-    } else {
-        new_path
+/// A watcher for `path` that sends to `tx`: polling when the preferences ask
+/// for it or `path` is on a network share native events don't arrive from
+fn create_watcher(
+    app: &AppHandle,
+    path: &Path,
+    tx: std::sync::mpsc::Sender<notify::Result<notify::Event>>,
+) -> Result<Box<dyn Watcher + Send>, AppError> {
+    let settings = load_preferences(app).watcher;
+    let poll = match settings.mode {
+        preferences::WatchMode::Native => false,
+        preferences::WatchMode::Poll => true,
+        preferences::WatchMode::Auto => network_fs::is_network_path(path),
     };
 
-    if new_path.exists() && new_path != old_path {
-        return Err("A file with that name already exists".to_string());
+    if poll {
+        info!(
+            "Polling {} every {}s for changes",
+            path.display(),
+            settings.poll_interval().as_secs()
+        );
+        let config = notify::Config::default().with_poll_interval(settings.poll_interval());
+        let watcher = notify::PollWatcher::new(tx, config)?;
+        return Ok(Box::new(watcher));
     }
+    let watcher = notify::recommended_watcher(tx)?;
+    Ok(Box::new(watcher))
+}
 
-    // CRITICAL FIX: Read the content first, then write to new file, then delete old
-    // This prevents data loss if something goes wrong
-    println!("Renaming file from {:?} to {:?}", old_path, new_path);
+/// Watches `root` for drawing changes and indexes its links; a root that is
+/// already watched is left alone
+fn start_watching(app: &AppHandle, state: &AppState, root: &Path) -> Result<(), AppError> {
+    let key = paths::normalize(root);
+    if state.watchers.lock().unwrap().contains_key(&key) {
+        return Ok(());
+    }
 
-    // Step 1: Read the original file content
-    let content = match fs::read_to_string(old_path) {
-        Ok(content) => {
-            println!(
-                "Successfully read original file, content length: {}",
-                content.len()
-            );
-            content
-        }
-        Err(e) => {
-            eprintln!("Failed to read original file: {}", e);
-            return Err(format!("Failed to read original file: {}", e));
-        }
-    };
+    let app_handle = app.clone();
+    let (tx, rx) = std::sync::mpsc::channel();
 
-    // Step 2: Write content to the new file
-    match fs::write(&new_path, &content) {
-        Ok(_) => {
-            println!("Successfully wrote content to new file");
-        }
-        Err(e) => {
-            eprintln!("Failed to write to new file: {}", e);
-            return Err(format!("Failed to create new file: {}", e));
-        }
+    let mut watcher = create_watcher(app, root, tx)?;
+    watcher
+        .watch(root, RecursiveMode::Recursive)?;
+
+    if let Err(e) = refresh_directory_config(state, root) {
+        error!("{}", e);
     }
+    rebuild_link_index(app, root);
+    rebuild_metadata_index(app, root);
+    clean_stale_locks(app, root);
 
-    // Step 3: Verify the new file exists and has content
-    match fs::read_to_string(&new_path) {
-        Ok(new_content) => {
-            if new_content != content {
-                eprintln!("Warning: New file content doesn't match original!");
-                // Delete the corrupted new file
-                let _ = fs::remove_file(&new_path);
-                return Err("File content verification failed".to_string());
+    // Spawn a thread to handle file system events; it ends once the watcher
+    // is dropped from `AppState::watchers` and the channel closes
+    let watched_dir = root.to_path_buf();
+    std::thread::spawn(move || {
+        let mut pairing = fs_events::RenamePairing::default();
+        loop {
+            // Wake up for a move-away that never gets its other half
+            let received = if pairing.is_pending() {
+                rx.recv_timeout(fs_events::RENAME_TIMEOUT)
+            } else {
+                rx.recv().map_err(|_| std::sync::mpsc::RecvTimeoutError::Disconnected)
+            };
+            let changes = match received {
+                Ok(Ok(event)) => pairing.push(&event),
+                Ok(Err(e)) => {
+                    error!("Watch error: {:?}", e);
+                    continue;
+                }
+                Err(std::sync::mpsc::RecvTimeoutError::Timeout) => pairing.flush(),
+                Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            for change in changes {
+                handle_watched_change(&app_handle, &watched_dir, change);
             }
-            println!("New file verified successfully");
-        }
-        Err(e) => {
-            eprintln!("Failed to verify new file: {}", e);
-            // Delete the potentially corrupted new file
-            let _ = fs::remove_file(&new_path);
-            return Err(format!("Failed to verify new file: {}", e));
         }
-    }
+    });
 
-    // Step 4: Only delete the original file after successful verification
-    match fs::remove_file(old_path) {
-        Ok(_) => {
-            println!("Successfully deleted original file");
-            Ok(new_path.to_string_lossy().to_string())
-        }
-        Err(e) => {
-            eprintln!("Warning: Failed to delete original file: {}", e);
-            // The rename was successful, but cleanup failed
-            // Return success but log the warning
-            Ok(new_path.to_string_lossy().to_string())
-        }
-    }
+    state.watchers.lock().unwrap().insert(key, watcher);
+    Ok(())
 }
 
+/// Tracks the drawing window `label` has open and emits
+/// `open-file-changed-externally` to that window when it changes on disk
+/// other than through its own saves. Remote files are not watched.
 #[tauri::command]
-async fn rename_directory(old_path: String, new_name: String) -> Result<String, String> {
-    // Validate the old path
-    let old_path = Path::new(&old_path);
-    let validated_old = security::validate_path(old_path, None)?;
-    
-    if !validated_old.exists() {
-        return Err("Directory does not exist".to_string());
-    }
-    
-    if !validated_old.is_dir() {
-        return Err("Path is not a directory".to_string());
-    }
-
-    let parent = validated_old.parent().ok_or("Invalid directory path")?;
-    
-    // Safely create the new path
-    let new_path = security::safe_path_join(parent, &new_name)?;
+async fn watch_open_file(
+    app: AppHandle,
+    window: WebviewWindow,
+    path: String,
+) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        let file = resolve_in_vault(state, &path)?;
+        security::validate_excalidraw_file(&file.path)?;
 
-    if new_path.exists() && new_path != old_path {
-        return Err("A directory with that name already exists".to_string());
-    }
+        let label = window.label().to_string();
+        if !file.is_local() {
+            state.open_files.unwatch(&label);
+            state.file_locks.release(&label);
+            debug!("Not watching remote file {}", path);
+            return Ok(());
+        }
+        // A drawing another instance has open still opens, but saving and
+        // deleting it are refused until that instance lets go
+        match state.file_locks.acquire(&label, &file.path, prompts::now_millis()) {
+            Ok(file_locks::LockState::Other) => info!("{} is open in another instance", path),
+            Ok(_) => {}
+            Err(e) => warn!("{}", e),
+        }
+        let parent = file
+            .path
+            .parent()
+            .ok_or_else(|| AppError::InvalidPath(path.clone()))?;
 
-    // Rename the directory
-    fs::rename(old_path, &new_path)
-        .map_err(|e| format!("Failed to rename directory: {}", e))?;
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = create_watcher(app, parent, tx)?;
+        watcher
+            .watch(parent, RecursiveMode::NonRecursive)
+            .map_err(|e| AppError::Internal(e.to_string()))?;
+        state
+            .open_files
+            .watch(&label, &file.path, file.to_external(&file.path), watcher);
 
-    Ok(new_path.to_string_lossy().to_string())
+        // Ends once the watcher is replaced or dropped and the channel closes
+        let watched = file.path.clone();
+        let app = app.clone();
+        std::thread::spawn(move || loop {
+            match rx.recv() {
+                Ok(Ok(event)) => {
+                    if !event.paths.contains(&watched) {
+                        continue;
+                    }
+                    let change = app.state::<AppState>().open_files.check(&label);
+                    if let Some(change) = change {
+                        info!("{} changed on disk ({:?})", change.path, change.kind);
+                        let _ = app.emit_to(label.as_str(), open_file::CHANGED_EVENT, change);
+                    }
+                }
+                Ok(Err(e)) => error!("Watch error: {:?}", e),
+                Err(_) => break,
+            }
+        });
+        Ok(())
+    })
+    .await
 }
 
 #[tauri::command]
-async fn delete_file(file_path: String) -> Result<(), String> {
-    // Validate path to prevent traversal attacks
-    let path = Path::new(&file_path);
-    let validated_path = security::validate_path(path, None)?;
-    
-    if !validated_path.exists() {
-        return Err("File does not exist".to_string());
-    }
-    
-    // Ensure we're only deleting excalidraw files
-    security::validate_excalidraw_file(&validated_path)?;
-
-    fs::remove_file(&validated_path)
-        .map_err(|e| e.to_string())?;
-    
+async fn unwatch_open_file(window: WebviewWindow, state: State<'_, AppState>) -> Result<(), AppError> {
+    state.open_files.unwatch(window.label());
+    state.file_locks.release(window.label());
     Ok(())
 }
 
+/// Whether another instance, on this machine or one sharing the vault
+/// through a synced folder, has the drawing open. Remote files are never
+/// locked.
 #[tauri::command]
-async fn delete_directory(dir_path: String) -> Result<(), String> {
-    // Validate path to prevent traversal attacks
-    let path = Path::new(&dir_path);
-    let validated_path = security::validate_path(path, None)?;
-    
-    if !validated_path.exists() {
-        return Err("Directory does not exist".to_string());
-    }
-    
-    if !validated_path.is_dir() {
-        return Err("Path is not a directory".to_string());
-    }
-
-    // Recursively remove the directory and all its contents
-    fs::remove_dir_all(&validated_path)
-        .map_err(|e| format!("Failed to delete directory: {}", e))?;
-    
-    Ok(())
+async fn get_file_lock_status(
+    app: AppHandle,
+    path: String,
+) -> Result<file_locks::FileLockStatus, AppError> {
+    blocking::with_state(&app, move |_app, state| {
+        let file = resolve_in_vault(state, &path)?;
+        security::validate_excalidraw_file(&file.path)?;
+        let external_path = file.to_external(&file.path);
+        if !file.is_local() {
+            return Ok(file_locks::FileLockStatus {
+                path: external_path,
+                state: file_locks::LockState::Unlocked,
+                owner: None,
+            });
+        }
+        Ok(file_locks::status(&file.path, external_path, prompts::now_millis()))
+    })
+    .await
 }
 
-#[tauri::command]
-async fn move_file(source_path: String, target_directory: String) -> Result<String, String> {
-    // Validate source path
-    let source = Path::new(&source_path);
-    let validated_source = security::validate_path(source, None)?;
-    
-    if !validated_source.exists() {
-        return Err("Source file does not exist".to_string());
+/// Keeps the link index and vault config up to date for one change below
+/// `watched_dir` and passes drawing and folder changes on to the frontend
+fn handle_watched_change(app: &AppHandle, watched_dir: &Path, mut change: fs_events::Change) {
+    let state = app.state::<AppState>();
+    state.content_cache.invalidate(&change.path);
+    if let Some(old_path) = &change.old_path {
+        state.content_cache.invalidate(old_path);
     }
-    
-    // Ensure we're only moving excalidraw files
-    security::validate_excalidraw_file(&validated_source)?;
-    
-    // Validate target directory
-    let target_dir = Path::new(&target_directory);
-    let validated_target_dir = security::validate_path(target_dir, None)?;
-    
-    if !validated_target_dir.is_dir() {
-        return Err("Target is not a directory".to_string());
+    let at_root = |path: &Path| path.parent() == Some(watched_dir);
+
+    // Changed patterns hide or reveal files, so rescan the whole vault
+    if at_root(&change.path) && ignores::is_ignore_file(&change.path) {
+        rebuild_link_index(app, watched_dir);
+        rebuild_metadata_index(app, watched_dir);
+        let mut payload = fs_events::FileSystemChange::new(watched_dir, &change);
+        payload.rescan = true;
+        emit_file_system_change(app, payload);
+        return;
     }
-    
-    // Get the filename from the source
-    let file_name = validated_source
-        .file_name()
-        .ok_or("Invalid source file name")?;
-    
-    // Create the target path
-    let target_path = security::safe_path_join(&validated_target_dir, &file_name.to_string_lossy())?;
-    
-    // Check if source and target are the same (moving to same directory)
-    if validated_source.canonicalize().unwrap_or(validated_source.clone()) == 
-       target_path.canonicalize().unwrap_or(target_path.clone()) {
-        return Ok(validated_source.to_string_lossy().to_string());
+    if at_root(&change.path) && config::is_config_file(&change.path) {
+        match refresh_directory_config(&state, watched_dir) {
+            Ok(config) => {
+                rebuild_link_index(app, watched_dir);
+                let _ = app.emit(
+                    "directory-config-changed",
+                    serde_json::json!({
+                        "directory": paths::normalize(watched_dir),
+                        "config": config,
+                    }),
+                );
+            }
+            Err(e) => warn!("{}", e),
+        }
+        return;
     }
-    
-    // Check if target already exists
-    if target_path.exists() {
-        return Err("A file with that name already exists in the target directory".to_string());
+
+    let ignore = ignore_rules(app, &storage::ResolvedPath::local(watched_dir));
+    let hidden = |path: &Path| ignore.is_ignored(path, change.is_directory);
+
+    // A rename across ignore rules looks like a plain create or remove
+    if let Some(old_path) = change.old_path.clone() {
+        match (hidden(&old_path), hidden(&change.path)) {
+            (true, true) => return,
+            (true, false) => {
+                change.kind = fs_events::ChangeKind::Created;
+                change.old_path = None;
+            }
+            (false, true) => {
+                change.kind = fs_events::ChangeKind::Removed;
+                change.path = old_path;
+                change.old_path = None;
+            }
+            (false, false) => {}
+        }
+    } else if hidden(&change.path) {
+        return;
     }
-    
-    // Read content from source
-    let content = fs::read_to_string(&validated_source)
-        .map_err(|e| format!("Failed to read source file: {}", e))?;
-    
-    // Write to target
-    fs::write(&target_path, &content)
-        .map_err(|e| format!("Failed to write to target: {}", e))?;
-    
-    // Verify target file
-    let verify_content = fs::read_to_string(&target_path)
-        .map_err(|e| format!("Failed to verify target file: {}", e))?;
-    
-    if verify_content != content {
-        // Cleanup failed target file
-        let _ = fs::remove_file(&target_path);
-        return Err("File content verification failed".to_string());
+
+    let relevant = change.is_directory
+        || compression::is_drawing(&change.path)
+        || change.kind == fs_events::ChangeKind::Removed
+        || change.old_path.as_deref().is_some_and(compression::is_drawing);
+    if !relevant {
+        return;
     }
-    
-    // Remove source file after successful copy
-    fs::remove_file(&validated_source)
-        .map_err(|e| format!("Failed to remove source file: {}", e))?;
-    
-    Ok(target_path.to_string_lossy().to_string())
-}
 
-#[tauri::command]
-async fn create_directory(parent_path: String, directory_name: String) -> Result<String, String> {
-    // Validate parent path
-    let parent = Path::new(&parent_path);
-    let validated_parent = security::validate_path(parent, None)?;
-    
-    if !validated_parent.is_dir() {
-        return Err("Parent path is not a directory".to_string());
+    match (&change.old_path, change.is_directory) {
+        (Some(_), true) => {
+            rebuild_link_index(app, watched_dir);
+            rebuild_metadata_index(app, watched_dir);
+        }
+        (None, _) if change.kind == fs_events::ChangeKind::Removed => {
+            let mut links = state.links.lock().unwrap();
+            links.update_file(&change.path);
+            links.remove_under(&paths::normalize(&change.path));
+            if let Err(e) = state.metadata.remove_under(&paths::normalize(&change.path)) {
+                debug!("{}", e);
+            }
+        }
+        (old_path, _) => {
+            let mut links = state.links.lock().unwrap();
+            if let Some(old_path) = old_path {
+                links.update_file(old_path);
+                update_file_metadata(&state, old_path);
+            }
+            if compression::is_drawing(&change.path) {
+                links.update_file(&change.path);
+                update_file_metadata(&state, &change.path);
+            }
+        }
     }
-    
-    // Validate directory name (no path separators, etc.)
-    if directory_name.contains('/') || directory_name.contains('\\') || directory_name.trim().is_empty() {
-        return Err("Invalid directory name".to_string());
+
+    let written = matches!(change.kind, fs_events::ChangeKind::Created | fs_events::ChangeKind::Modified)
+        && !change.is_directory;
+    if written
+        && compression::is_drawing(&change.path)
+        && directory_config_for(&state, &change.path).is_some_and(|(_, config)| config.auto_export(&change.path).is_some())
+    {
+        state.auto_export.schedule(&change.path);
     }
-    
-    // Create the new directory path
-    let new_dir_path = security::safe_path_join(&validated_parent, &directory_name)?;
-    
-    // Check if directory already exists
-    if new_dir_path.exists() {
-        return Err("A file or directory with that name already exists".to_string());
+
+    // Our own saves would make the frontend reload what it just wrote
+    if written && state.recent_saves.is_own(&change.path) {
+        debug!("Ignoring watcher event for own save of {}", change.path.display());
+        return;
     }
-    
-    // Create the directory
-    fs::create_dir(&new_dir_path)
-        .map_err(|e| format!("Failed to create directory: {}", e))?;
-    
-    // Verify directory was created
-    if !new_dir_path.is_dir() {
-        return Err("Directory creation verification failed".to_string());
+
+    emit_file_system_change(app, fs_events::FileSystemChange::new(watched_dir, &change));
+}
+
+/// Queues the export of a drawing whose saves settled; the drawing the
+/// user just worked on goes before indexing and cleanup
+fn queue_auto_export(app: &AppHandle, path: &Path) {
+    let task_app = app.clone();
+    let drawing = path.to_path_buf();
+    let background = &app.state::<AppState>().background;
+    background.submit(background::TaskKind::AutoExport, paths::normalize(path), background::TaskPriority::High, move |_| {
+        run_auto_export(&task_app, &drawing)
+    });
+}
+
+/// Writes the images the vault's `.excaliapp.json` asks for next to a
+/// drawing that changed; runs as a background task
+fn run_auto_export(app: &AppHandle, path: &Path) {
+    let state = app.state::<AppState>();
+    let Some((_, config)) = directory_config_for(&state, path) else {
+        return;
+    };
+    // The drawing may be gone, or the config changed, by the time its saves settle
+    let Some((auto, directory)) = config.auto_export(path).filter(|_| path.is_file()) else {
+        return;
+    };
+    let result = directory.and_then(|directory| {
+        let all_branding = load_export_branding(app)?;
+        let branding = branding::for_path(&all_branding, path).or(config.export.branding.as_ref());
+        auto_export::export(path, auto, &directory, branding)
+    });
+    match result {
+        Ok(outputs) => debug!("Auto-exported {} to {} file(s)", path.display(), outputs.len()),
+        Err(e) => warn!("Auto-export of {} failed: {}", path.display(), e),
     }
-    
-    Ok(new_dir_path.to_string_lossy().to_string())
 }
 
-#[tauri::command]
-async fn save_preferences(app: AppHandle, preferences: Preferences) -> Result<(), String> {
-    use tauri_plugin_store::StoreExt;
+fn emit_file_system_change(app: &AppHandle, change: fs_events::FileSystemChange) {
+    if let Some(change) = app.state::<AppState>().watch_pause.hold(change) {
+        let _ = app.emit("file-system-change", change);
+    }
+}
 
-    let store = app.store("preferences.json").map_err(|e| e.to_string())?;
+/// Holds back watcher events until dropped; they then arrive as one summary
+struct WatcherPause {
+    app: AppHandle,
+}
 
-    store.set("preferences", serde_json::to_value(&preferences).unwrap());
-    store.save().map_err(|e| e.to_string())?;
+impl WatcherPause {
+    fn new(app: &AppHandle) -> Self {
+        app.state::<AppState>().watch_pause.pause();
+        Self { app: app.clone() }
+    }
+}
 
-    // Update recent directories menu
-    let _ = menu::update_recent_directories_menu(&app, preferences.recent_directories.clone());
+impl Drop for WatcherPause {
+    fn drop(&mut self) {
+        resume_watcher_later(&self.app);
+    }
+}
 
-    Ok(())
+/// Ends one pause once the last events of the batch had time to arrive
+fn resume_watcher_later(app: &AppHandle) {
+    let app = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(watch_pause::SETTLE_DELAY);
+        if let Some(summary) = app.state::<AppState>().watch_pause.resume() {
+            info!(
+                "Watching resumed: {} created, {} modified, {} removed, {} renamed",
+                summary.created, summary.modified, summary.removed, summary.renamed
+            );
+            let _ = app.emit(watch_pause::SUMMARY_EVENT, summary);
+        }
+    });
 }
 
+/// Holds back `file-system-change` events, e.g. around a git checkout, until
+/// `resume_watcher`; pauses nest
 #[tauri::command]
-async fn force_close_app(app: AppHandle) -> Result<(), String> {
-    app.exit(0);
+async fn pause_watcher(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.watch_pause.pause();
     Ok(())
 }
 
+/// Ends a `pause_watcher`; the held-back changes arrive as one
+/// `file-system-summary` event
 #[tauri::command]
-async fn restart_app(app: AppHandle) -> Result<(), String> {
-    app.restart();
+async fn resume_watcher(app: AppHandle) -> Result<(), AppError> {
+    resume_watcher_later(&app);
+    Ok(())
 }
 
-#[tauri::command]
-async fn set_title(title: String, window: tauri::Window) -> Result<(), String> {
-    window.set_title(&title)
-        .map_err(|e| format!("Failed to set title: {}", e))?;
-    Ok(())
+/// Watches every watched folder again, e.g. with a new watch mode
+fn restart_watchers(app: &AppHandle, state: &AppState) {
+    let keys: Vec<String> = state.watchers.lock().unwrap().keys().cloned().collect();
+    for key in keys {
+        stop_watching(state, &key);
+        if let Err(e) = start_watching(app, state, Path::new(&key)) {
+            error!("Failed to watch {} again: {}", key, e);
+        }
+    }
 }
 
+fn stop_watching(state: &AppState, key: &str) {
+    if state.watchers.lock().unwrap().remove(key).is_some() {
+        state.links.lock().unwrap().remove_under(key);
+        // Rows stay, so watching the folder again only reads what changed
+        state.metadata.mark_stale(key);
+        state.configs.lock().unwrap().remove(key);
+        info!("Stopped watching {}", key);
+    }
+}
 
 #[tauri::command]
 async fn watch_directory(
     app: AppHandle,
+    window: WebviewWindow,
     directory: String,
-    state: State<'_, AppState>,
-) -> Result<(), String> {
-    let path = PathBuf::from(&directory);
+) -> Result<(), AppError> {
+    blocking::with_state(&app, move |app, state| {
+        // Watching indexes the folder and clears stale locks in it
+        state.vault.check(Path::new(&directory))?;
+        let path = PathBuf::from(&directory);
 
-    {
-        let mut current_dir = state.current_directory.lock().unwrap();
-        *current_dir = Some(path.clone());
-    }
-
-    // Set up file watcher
-    let app_handle = app.clone();
-    let (tx, rx) = std::sync::mpsc::channel();
-
-    let mut watcher = notify::recommended_watcher(tx).map_err(|e| e.to_string())?;
+        let previous = state
+            .windows
+            .with(window.label(), |w| w.current_directory.replace(path.clone()));
 
-    watcher
-        .watch(&path, RecursiveMode::Recursive)
-        .map_err(|e| e.to_string())?;
-
-    // Spawn a thread to handle file system events
-    std::thread::spawn(move || loop {
-        match rx.recv() {
-            Ok(Ok(Event {
-                kind: EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(_),
-                paths,
-                ..
-            })) => {
-                for path in paths {
-                    if let Some(extension) = path.extension() {
-                        if extension == "excalidraw" {
-                            let _ = app_handle.emit("file-system-change", &path);
-                        }
-                    }
-                }
-            }
-            Ok(Err(e)) => eprintln!("Watch error: {:?}", e),
-            Err(e) => {
-                eprintln!("Watch channel error: {:?}", e);
-                break;
+        // Switching folders stops watching the old one unless it is a workspace
+        // root or open in another window
+        if let Some(previous) = previous.filter(|p| p != &path) {
+            let key = paths::normalize(&previous);
+            if !state.windows.is_open_elsewhere(window.label(), &previous)
+                && !load_preferences(app).workspace_roots.contains(&key)
+            {
+                stop_watching(state, &key);
             }
-            _ => {}
         }
-    });
 
-    Ok(())
+        start_watching(app, state, &path)
+    })
+    .await
 }
 
 #[tauri::command]
-async fn save_personal_library_items(app: AppHandle, items: Vec<LibraryItem>) -> Result<(), String> {
+async fn save_personal_library_items(app: AppHandle, items: Vec<LibraryItem>) -> Result<(), AppError> {
     use tauri_plugin_store::StoreExt;
     
-    let store = app.store("personal_library.json").map_err(|e| e.to_string())?;
+    let store = app.store("personal_library.json")?;
     
-    store.set("personal_library_items", serde_json::to_value(&items).map_err(|e| e.to_string())?);
-    store.save().map_err(|e| e.to_string())?;
+    store.set("personal_library_items", serde_json::to_value(&items)?);
+    store.save()?;
     
     Ok(())
 }
 
 #[tauri::command]
-async fn load_personal_library_items(app: AppHandle) -> Result<Vec<LibraryItem>, String> {
+async fn load_personal_library_items(app: AppHandle) -> Result<Vec<LibraryItem>, AppError> {
     use tauri_plugin_store::StoreExt;
     
-    let store = app.store("personal_library.json").map_err(|e| e.to_string())?;
+    let store = app.store("personal_library.json")?;
     
     if let Some(value) = store.get("personal_library_items") {
         match serde_json::from_value::<Vec<LibraryItem>>(value.clone()) {
             Ok(items) => Ok(items),
             Err(e) => {
-                eprintln!("Failed to deserialize personal library items: {}", e);
+                warn!("Failed to deserialize personal library items: {}", e);
                 Ok(vec![])
             }
         }
@@ -1188,100 +7525,100 @@ async fn load_personal_library_items(app: AppHandle) -> Result<Vec<LibraryItem>,
 }
 
 #[tauri::command]
-async fn save_excalidraw_library_items(app: AppHandle, items_json: String, append: Option<bool>) -> Result<(), String> {
+async fn save_excalidraw_library_items(app: AppHandle, items_json: String, append: Option<bool>) -> Result<(), AppError> {
     use tauri_plugin_store::StoreExt;
     
     // Parse JSON string to Vec<LibraryItem>
     let new_items: Vec<LibraryItem> = serde_json::from_str(&items_json)
-        .map_err(|e| format!("Failed to parse library items JSON: {}", e))?;
+        .context("Failed to parse library items JSON")?;
     
-    let store = app.store("excalidraw_library.json").map_err(|e| e.to_string())?;
+    let store = app.store("excalidraw_library.json")?;
     
     let final_items = if append.unwrap_or(true) {
         // Append mode (default): load existing items and append new ones
         let mut existing_items = if let Some(value) = store.get("excalidraw_library_items") {
             match serde_json::from_value::<Vec<LibraryItem>>(value.clone()) {
                 Ok(items) => {
-                    println!("Found {} existing Excalidraw library items", items.len());
+                    debug!("Found {} existing Excalidraw library items", items.len());
                     items
                 },
                 Err(_) => {
-                    println!("Failed to parse existing items, starting with empty");
+                    debug!("Failed to parse existing items, starting with empty");
                     Vec::new()
                 }
             }
         } else {
-            println!("No existing Excalidraw library items found");
+            debug!("No existing Excalidraw library items found");
             Vec::new()
         };
         
-        println!("Appending {} new items to {} existing items", new_items.len(), existing_items.len());
+        debug!("Appending {} new items to {} existing items", new_items.len(), existing_items.len());
         existing_items.extend(new_items);
-        println!("Final item count: {}", existing_items.len());
+        debug!("Final item count: {}", existing_items.len());
         existing_items
     } else {
         // Replace mode: use new items directly
-        println!("Replace mode: using {} new items directly", new_items.len());
+        debug!("Replace mode: using {} new items directly", new_items.len());
         new_items
     };
     
-    store.set("excalidraw_library_items", serde_json::to_value(&final_items).map_err(|e| e.to_string())?);
-    store.save().map_err(|e| e.to_string())?;
+    store.set("excalidraw_library_items", serde_json::to_value(&final_items)?);
+    store.save()?;
     
     Ok(())
 }
 
 #[tauri::command]
-async fn load_excalidraw_library_items(app: AppHandle) -> Result<Vec<LibraryItem>, String> {
+async fn load_excalidraw_library_items(app: AppHandle) -> Result<Vec<LibraryItem>, AppError> {
     use tauri_plugin_store::StoreExt;
     
-    let store = app.store("excalidraw_library.json").map_err(|e| e.to_string())?;
+    let store = app.store("excalidraw_library.json")?;
     
     // Debug: List all keys in the store
-    println!("=== DEBUG load_excalidraw_library_items ===");
+    debug!("Loading excalidraw library items");
     let keys = store.keys();
-    println!("All keys in excalidraw_library.json store: {:?}", keys);
+    debug!("All keys in excalidraw_library.json store: {:?}", keys);
     
     if let Some(value) = store.get("excalidraw_library_items") {
-        println!("Found excalidraw_library_items key, raw value type: {:?}", value);
+        debug!("Found excalidraw_library_items key, raw value type: {:?}", value);
         match serde_json::from_value::<Vec<LibraryItem>>(value.clone()) {
             Ok(items) => {
-                println!("Successfully loaded {} excalidraw library items", items.len());
+                debug!("Successfully loaded {} excalidraw library items", items.len());
                 Ok(items)
             },
             Err(e) => {
-                println!("Failed to deserialize excalidraw library items: {}", e);
-                println!("Raw value: {:?}", value);
+                debug!("Failed to deserialize excalidraw library items: {}", e);
+                debug!("Raw value: {:?}", value);
                 Ok(vec![])
             }
         }
     } else {
-        println!("No excalidraw_library_items key found in store");
+        debug!("No excalidraw_library_items key found in store");
         Ok(vec![])
     }
 }
 
 #[tauri::command]
-async fn clear_excalidraw_library_items(app: AppHandle) -> Result<(), String> {
+async fn clear_excalidraw_library_items(app: AppHandle) -> Result<(), AppError> {
     use tauri_plugin_store::StoreExt;
     
-    let store = app.store("excalidraw_library.json").map_err(|e| e.to_string())?;
+    let store = app.store("excalidraw_library.json")?;
     
     // Set empty array
     store.set("excalidraw_library_items", serde_json::Value::Array(vec![]));
-    store.save().map_err(|e| e.to_string())?;
+    store.save()?;
     
     Ok(())
 }
 
 // Legacy function for backward compatibility
 #[tauri::command]
-async fn save_library_items(app: AppHandle, items: Vec<LibraryItem>) -> Result<(), String> {
+async fn save_library_items(app: AppHandle, items: Vec<LibraryItem>) -> Result<(), AppError> {
     save_personal_library_items(app, items).await
 }
 
 #[tauri::command]
-async fn load_combined_library_items(app: AppHandle) -> Result<Vec<LibraryItem>, String> {
+async fn load_combined_library_items(app: AppHandle) -> Result<Vec<LibraryItem>, AppError> {
     // Load both personal and excalidraw libraries and combine them
     let personal_items = load_personal_library_items(app.clone()).await?;
     let excalidraw_items = load_excalidraw_library_items(app).await?;
@@ -1294,10 +7631,10 @@ async fn load_combined_library_items(app: AppHandle) -> Result<Vec<LibraryItem>,
 }
 
 #[tauri::command]
-async fn load_library_items(app: AppHandle) -> Result<Vec<LibraryItem>, String> {
+async fn load_library_items(app: AppHandle) -> Result<Vec<LibraryItem>, AppError> {
     use tauri_plugin_store::StoreExt;
 
-    let store = app.store("library.json").map_err(|e| e.to_string())?;
+    let store = app.store("library.json")?;
 
     let items = if let Some(value) = store.get("library_items") {
         match serde_json::from_value::<Vec<LibraryItem>>(value.clone()) {
@@ -1308,30 +7645,115 @@ async fn load_library_items(app: AppHandle) -> Result<Vec<LibraryItem>, String>
         Vec::new()
     };
 
-    println!("Loaded {} library items from store", items.len());
+    debug!("Loaded {} library items from store", items.len());
     Ok(items)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // With several paths the last one is opened, as later ones would replace it
+    let startup_target = launch::parse_args(
+        &std::env::args().skip(1).collect::<Vec<_>>(),
+        &std::env::current_dir().unwrap_or_default(),
+    )
+    .pop();
+
     tauri::Builder::default()
+        // Must come first so a second launch exits before anything else starts
+        .plugin(tauri_plugin_single_instance::init(|app, argv, cwd| {
+            let targets = launch::parse_args(argv.get(1..).unwrap_or_default(), Path::new(&cwd));
+            info!("Second launch forwarded {} path(s) to the running instance", targets.len());
+            open_launch_targets(app, targets);
+        }))
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_deep_link::init())
-        .setup(|app| {
+        .setup(move |app| {
+            let logger = logging::init(&log_dir(app.handle())?, load_preferences(app.handle()).log_level);
+            info!("Starting {} {}", app.package_info().name, app.package_info().version);
+
+            if let Some(target) = &startup_target {
+                info!("Opening {} from the command line", target.path);
+            }
+
+            let windows = windows::WindowStates::default();
+            windows.with(windows::MAIN_WINDOW, |w| {
+                w.current_directory = startup_target.as_ref().map(|t| t.directory());
+                w.startup_target = startup_target;
+            });
+
             app.manage(AppState {
-                current_directory: Mutex::new(None),
-                modified_files: Mutex::new(Vec::new()),
+                windows,
+                storage: storage::Mounts::default(),
+                dialogs: dialogs::PendingDialogs::default(),
+                operations: operations::Operations::default(),
+                close_requests: close_flow::CloseRequests::default(),
+                context_menu: context_menu::PendingContextMenu::default(),
+                background: background::TaskManager::new(load_background_settings(app.handle())),
+                links: Mutex::new(links::LinkIndex::default()),
+                metadata: open_metadata_index(app.handle()),
+                watchers: Mutex::new(HashMap::new()),
+                open_files: open_file::OpenFileWatches::default(),
+                file_locks: file_locks::FileLocks::default(),
+                recent_saves: recent_saves::RecentSaves::default(),
+                content_cache: content_cache::ContentCache::default(),
+                watch_pause: watch_pause::WatchPause::default(),
+                configs: Mutex::new(config::ConfigMap::new()),
+                auto_export: auto_export::AutoExporter::default(),
+                vault: vault::VaultAccess::default(),
+                vault_backups: vault_backup::BackupLock::default(),
+                syncing: tokio::sync::Mutex::new(()),
+                share_server: Mutex::new(None),
+                collab: Mutex::new(None),
+                logger,
             });
 
+            // Folders opened in earlier sessions, and the one passed on the command line
+            {
+                let state = app.state::<AppState>();
+                let preferences = load_preferences(app.handle());
+                let recent_file_folders = preferences
+                    .recent_files
+                    .iter()
+                    .filter_map(|f| Path::new(f).parent().map(Path::to_path_buf));
+                preferences
+                    .workspace_roots
+                    .iter()
+                    .chain(&preferences.recent_directories)
+                    .chain(&preferences.last_directory)
+                    .map(PathBuf::from)
+                    .chain(recent_file_folders)
+                    .chain(state.windows.current_directory(windows::MAIN_WINDOW))
+                    .for_each(|root| state.vault.allow_root(&root));
+            }
+
+            // excaliapp:// links, including one the app was launched with
+            {
+                use tauri_plugin_deep_link::DeepLinkExt;
+
+                let handle = app.handle().clone();
+                app.deep_link().on_open_url(move |event| handle_deep_links(&handle, &event.urls()));
+                if let Ok(Some(urls)) = app.deep_link().get_current() {
+                    handle_deep_links(app.handle(), &urls);
+                }
+            }
+
             // Create and set up the menu
             let menu = menu::create_menu(app.handle())?;
             app.set_menu(menu)?;
             menu::setup_menu_event_handler(app.handle());
 
+            // Resume watching the persisted workspace roots
+            for root in load_preferences(app.handle()).workspace_roots {
+                let state = app.state::<AppState>();
+                if let Err(e) = start_watching(app.handle(), &state, Path::new(&root)) {
+                    warn!("Failed to watch workspace root {}: {}", root, e);
+                }
+            }
+
             // Load preferences and update recent directories menu
             let app_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
@@ -1343,49 +7765,188 @@ pub fn run() {
                                 &app_handle,
                                 prefs.recent_directories,
                             );
+                            let _ = menu::update_recent_files_menu(&app_handle, prefs.recent_files);
                         }
                     }
                 }
             });
 
-            // Add window close handler
-            let window = app.get_webview_window("main").unwrap();
-            let window_clone = window.clone();
-            window.on_window_event(move |event| {
-                if let tauri::WindowEvent::CloseRequested { api, .. } = event {
-                    // Prevent default close
-                    api.prevent_close();
-                    
-                    // Emit event to frontend to check for unsaved changes
-                    let _ = window_clone.emit("check-unsaved-before-close", ());
-                }
+            // Exports drawings whose vault asks for it once their saves settle
+            let app_handle = app.handle().clone();
+            app.state::<AppState>()
+                .auto_export
+                .start(move |path| queue_auto_export(&app_handle, path));
+
+            // Checks for due vault backups for as long as the app runs
+            let app_handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(vault_backup::TICK);
+                scheduled_vault_backup(&app_handle);
             });
 
+            // Add window close handler
+            let window = app.get_webview_window(windows::MAIN_WINDOW).unwrap();
+            setup_window_events(&window);
+
 
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             test_ai_connection,
+            list_ai_models,
             call_ai_api,
             call_ai_api_stream,
+            ai_analyze_image,
+            translate_scene,
+            list_prompt_templates,
+            save_prompt_template,
+            delete_prompt_template,
+            list_ai_profiles,
+            save_ai_profile,
+            delete_ai_profile,
+            set_default_ai_profile,
+            rank_files,
+            get_activity_timeline,
+            read_version,
+            restore_version,
+            get_ai_usage_stats,
+            set_ai_usage_budget,
+            list_ai_history,
+            get_ai_history_entry,
+            delete_ai_history,
+            validate_and_repair_scene,
+            validate_scene_elements,
+            apply_layout,
 
             select_directory,
+            select_file,
+            cancel_pending_dialog,
+            cancel_operation,
+            mount_backend,
+            #[cfg(debug_assertions)]
+            generate_test_workspace,
             list_excalidraw_files,
             get_file_tree,
+            add_workspace_root,
+            remove_workspace_root,
+            get_workspace_tree,
+            get_directory_config,
+            get_file_tree_children,
+            list_backups,
+            run_backup_now,
+            list_vault_backups,
+            set_s3_secret,
+            upload_backup,
+            restore_from_s3,
+            restore_backup,
+            save_draft,
+            list_recoverable_drafts,
+            list_recently_deleted,
+            restore_deleted,
+            recover_draft,
+            discard_draft,
+            check_workspace_health,
+            get_vault_stats,
+            scan_vault,
+            repair_file,
+            verify_file_integrity,
+            verify_vault_integrity,
+            find_duplicate_scenes,
+            resolve_duplicates,
+            merge_scene_changes,
+            find_sync_conflicts,
+            resolve_sync_conflict,
+            get_background_settings,
+            get_background_tasks,
+            cancel_background_task,
+            set_background_settings,
+            export_slides,
+            batch_export,
+            archive_directory,
+            import_archive,
+            get_share_permissions,
+            start_share_server,
+            stop_share_server,
+            get_share_server_status,
+            host_collab_session,
+            join_collab_session,
+            send_collab_update,
+            leave_collab_session,
+            get_collab_status,
+            set_share_permissions,
+            get_export_branding,
+            set_export_branding,
+            get_file_size,
             read_file,
+            upgrade_legacy_file,
+            read_scene_partial,
+            optimize_scene_images,
+            compact_scene,
+            replace_text,
+            extract_scene_images,
             save_file,
             save_file_as,
+            get_last_locations,
             create_new_file,
             rename_file,
             rename_directory,
+            move_directory,
             delete_file,
             delete_directory,
             move_file,
+            move_files,
+            delete_files,
+            export_files,
+            compress_existing_files,
             create_directory,
             get_preferences,
             save_preferences,
+            get_recent_logs,
+            open_log_directory,
             watch_directory,
+            watch_open_file,
+            unwatch_open_file,
+            get_file_lock_status,
+            pause_watcher,
+            resume_watcher,
+            get_outgoing_links,
+            get_backlinks,
+            frontend_ready,
+            open_in_new_window,
+            set_app_language,
+            sync_menu_state,
+            set_active_file_state,
+            show_file_context_menu,
+            pin_file,
+            unpin_file,
+            get_pinned_files,
+            set_read_only,
+            get_read_only_paths,
+            configure_webdav_sync,
+            get_webdav_sync,
+            sync_now,
+            duplicate_file,
+            copy_file,
+            list_templates,
+            save_as_template,
+            delete_template,
+            export_file_svg,
+            export_pdf,
+            export_mermaid,
+            import_json_canvas,
+            export_json_canvas,
+            search_drawings,
+            reveal_in_file_manager,
+            copy_path_to_clipboard,
+            get_shortcuts,
+            set_shortcut,
+            export_settings,
+            import_settings,
             force_close_app,
+            finish_save_before_close,
+            mark_file_dirty,
+            mark_file_clean,
+            get_dirty_files,
             restart_app,
             set_title,
             save_library_items,
@@ -1396,6 +7957,144 @@ pub fn run() {
             load_excalidraw_library_items,
             clear_excalidraw_library_items,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Files opened from Finder while the app runs or starts
+            #[cfg(any(target_os = "macos", target_os = "ios"))]
+            if let tauri::RunEvent::Opened { urls } = &event {
+                open_launch_targets(app, launch::from_opened_urls(urls));
+            }
+            if let tauri::RunEvent::Exit = event {
+                exit_vault_backup(app);
+                app.state::<AppState>().file_locks.release_all();
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_root(name: &str) -> storage::ResolvedPath {
+        let mounts = storage::Mounts::default();
+        let uri = mounts
+            .mount(&storage::BackendConfig {
+                kind: "memory".to_string(),
+                name: name.to_string(),
+                root: None,
+            })
+            .unwrap();
+        mounts.resolve_root(&uri).unwrap()
+    }
+
+    fn fixture_root(name: &str, file_count: usize) -> storage::ResolvedPath {
+        let root = memory_root(name);
+        let spec = fixtures::TestWorkspaceSpec {
+            file_count,
+            folder_depth: 1,
+            folders_per_level: 2,
+            elements_per_file: 3,
+            ..fixtures::TestWorkspaceSpec::default()
+        };
+        fixtures::generate(&root, &spec).unwrap();
+        root
+    }
+
+    fn scan(ignore: &ignores::IgnoreRules, max_depth: Option<usize>) -> TreeScan<'_> {
+        TreeScan {
+            ignore,
+            max_depth,
+            max_entries: None,
+            sort: TreeSort::Name,
+            scanned: 0,
+            on_progress: &|_| {},
+        }
+    }
+
+    #[test]
+    fn lists_every_drawing_of_a_workspace() {
+        let root = fixture_root("list", 9);
+        root.backend.write(Path::new("/notes.txt"), "not a drawing").unwrap();
+
+        let mut files = Vec::new();
+        collect_excalidraw_files(&root, &root.path, &ignores::IgnoreRules::none(), &mut files).unwrap();
+        files.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+
+        assert_eq!(files.len(), 9);
+        assert_eq!(files[0].path, "memory://list/fixture-00001.excalidraw");
+        assert_eq!(files[0].relative_path, "fixture-00001.excalidraw");
+        assert!(files.iter().any(|f| f.relative_path == "folder-2/fixture-00003.excalidraw"));
+    }
+
+    #[test]
+    fn tree_and_listing_skip_ignored_entries() {
+        let root = fixture_root("ignored", 9);
+        let patterns = ["folder-2/".to_string(), "fixture-00001.excalidraw".to_string()];
+        let ignore = ignores::IgnoreRules::new(&root.path, &patterns);
+
+        let mut files = Vec::new();
+        collect_excalidraw_files(&root, &root.path, &ignore, &mut files).unwrap();
+        assert_eq!(files.len(), 5);
+        assert!(files
+            .iter()
+            .all(|f| !f.relative_path.starts_with("folder-2/") && f.relative_path != "fixture-00001.excalidraw"));
+
+        let tree = read_tree(&root, &mut scan(&ignore, None)).unwrap();
+        let names: Vec<&str> = tree.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, ["folder-1", "fixture-00004.excalidraw", "fixture-00007.excalidraw"]);
+    }
+
+    #[test]
+    fn tree_lists_folders_first() {
+        let root = fixture_root("tree", 3);
+        let ignore = ignores::IgnoreRules::none();
+        let tree = read_tree(&root, &mut scan(&ignore, None)).unwrap();
+
+        let names: Vec<&str> = tree.iter().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, ["folder-1", "folder-2", "fixture-00001.excalidraw"]);
+        let children = tree[0].children.as_ref().unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].path, "memory://tree/folder-1/fixture-00002.excalidraw");
+    }
+
+    #[test]
+    fn tree_limits_leave_folders_unloaded() {
+        let root = fixture_root("shallow", 3);
+        let ignore = ignores::IgnoreRules::none();
+
+        let tree = read_tree(&root, &mut scan(&ignore, Some(1))).unwrap();
+        assert_eq!(tree.len(), 3);
+        assert!(tree.iter().filter(|node| node.is_directory).all(|node| node.children.is_none()));
+
+        // The root lists fixture-00001 and folder-1 before folder-2, and
+        // folder-1 holds one drawing, so folder-2 comes after the limit
+        let mut limited = scan(&ignore, None);
+        limited.max_entries = Some(3);
+        let tree = read_tree(&root, &mut limited).unwrap();
+        assert_eq!(tree.len(), 3);
+        assert_eq!(tree[0].children.as_ref().map(|c| c.len()), Some(1));
+        assert!(tree[1].children.is_none());
+        assert_eq!(limited.scanned, 4);
+    }
+
+    #[test]
+    fn untitled_names_count_per_directory() {
+        let dir = std::env::temp_dir().join(format!("excaliapp-untitled-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        let root = storage::ResolvedPath::local(&dir);
+        let nested = storage::ResolvedPath::local(&dir.join("nested"));
+        assert_eq!(next_untitled_file_name(&root, "en-US"), "Untitled-1.excalidraw");
+
+        for name in ["Untitled-1.excalidraw", "Untitled-7.excalidraw", "Untitled-9.png", "Untitled-x.excalidraw"] {
+            std::fs::write(dir.join(name), "{}").unwrap();
+        }
+        assert_eq!(next_untitled_file_name(&root, "en-US"), "Untitled-8.excalidraw");
+        assert_eq!(next_untitled_file_name(&nested, "en-US"), "Untitled-1.excalidraw");
+
+        std::fs::write(dir.join("未命名-2.excalidraw"), "{}").unwrap();
+        assert_eq!(next_untitled_file_name(&root, "zh-CN"), "未命名-3.excalidraw");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::scene_outline::SceneOutline;
+
+const MB: u64 = 1024 * 1024;
+
+/// Size limits enforced when drawings are opened or saved; 0 turns a limit off
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct FileLimits {
+    pub max_file_mb: u64,
+    pub max_elements: usize,
+    /// Per embedded image
+    pub max_image_mb: u64,
+}
+
+impl Default for FileLimits {
+    fn default() -> Self {
+        Self {
+            max_file_mb: 100,
+            max_elements: 100_000,
+            max_image_mb: 25,
+        }
+    }
+}
+
+/// Answer of `get_file_size`, checked before a drawing is loaded
+#[derive(Debug, Serialize, Clone)]
+pub struct FileSizeInfo {
+    pub size: u64,
+    /// `None` when the file size limit is off
+    pub max_bytes: Option<u64>,
+    pub too_large: bool,
+}
+
+impl FileLimits {
+    /// `None` when the file size limit is off
+    pub fn max_bytes(&self) -> Option<u64> {
+        (self.max_file_mb > 0).then(|| self.max_file_mb * MB)
+    }
+
+    pub fn size_info(&self, size: u64) -> FileSizeInfo {
+        let max_bytes = self.max_bytes();
+        FileSizeInfo {
+            size,
+            max_bytes,
+            too_large: max_bytes.is_some_and(|max| size > max),
+        }
+    }
+
+    pub fn check_size(&self, size: u64) -> Result<(), AppError> {
+        match self.max_bytes() {
+            Some(max) if size > max => Err(AppError::TooLarge(format!(
+                "File is {} MB, the limit is {} MB",
+                megabytes(size),
+                self.max_file_mb
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the file size, element count and embedded images of a drawing
+    pub fn check_content(&self, content: &str) -> Result<(), AppError> {
+        self.check_size(content.len() as u64)?;
+
+        let scene = SceneOutline::parse(content)?;
+        let elements = scene.element_count();
+        if self.max_elements > 0 && elements > self.max_elements {
+            return Err(AppError::TooLarge(format!(
+                "Drawing has {} elements, the limit is {}",
+                elements, self.max_elements
+            )));
+        }
+
+        if self.max_image_mb > 0 {
+            for (id, data_url_len) in &scene.images {
+                // Base64 takes 4 characters for every 3 bytes
+                let size = data_url_len * 3 / 4;
+                if size > self.max_image_mb * MB {
+                    return Err(AppError::TooLarge(format!(
+                        "Embedded image {} is {} MB, the limit is {} MB",
+                        id,
+                        megabytes(size),
+                        self.max_image_mb
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn megabytes(bytes: u64) -> String {
+    format!("{:.1}", bytes as f64 / MB as f64)
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+use crate::compression;
+use crate::paths;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DrawingLink {
+    /// Drawing containing the linking element
+    pub source: String,
+    /// Drawing the link points at
+    pub target: String,
+    pub element_id: String,
+    /// Text of the linking element (or of its bound label), for display
+    pub label: Option<String>,
+    /// The link as written in the scene
+    pub link: String,
+    pub exists: bool,
+}
+
+/// Outgoing links of every drawing in the workspace, keyed by normalized path
+#[derive(Debug, Default)]
+pub struct LinkIndex {
+    outgoing: HashMap<String, Vec<DrawingLink>>,
+}
+
+/// Resolves a link to a local drawing, ignoring web URLs and in-scene anchors
+pub fn internal_link_target(base: &Path, link: &str) -> Option<PathBuf> {
+    let link = link.trim();
+    if link.is_empty() || link.contains("://") || link.starts_with('#') {
+        return None;
+    }
+
+    let link = link.split('#').next().unwrap_or(link);
+    if !link.ends_with(".excalidraw") {
+        return None;
+    }
+
+    let target = Path::new(link);
+    if target.is_absolute() {
+        Some(target.to_path_buf())
+    } else {
+        Some(base.join(target))
+    }
+}
+
+/// Resolves `.` and `..` without touching the file system, so links to
+/// missing files still get a stable key
+fn clean(path: &Path) -> PathBuf {
+    let mut cleaned = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                cleaned.pop();
+            }
+            other => cleaned.push(other),
+        }
+    }
+    cleaned
+}
+
+/// Text shown for a linking element: its own text or the label bound to it
+fn element_label(element: &Value, labels: &HashMap<&str, &str>) -> Option<String> {
+    let text = element.get("text").and_then(|t| t.as_str()).or_else(|| {
+        let id = element.get("id").and_then(|id| id.as_str())?;
+        labels.get(id).copied()
+    })?;
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.replace('\n', " "))
+}
+
+/// Internal links of one drawing
+pub fn scan(path: &Path, content: &str) -> Vec<DrawingLink> {
+    let Ok(scene) = serde_json::from_str::<Value>(content) else {
+        return Vec::new();
+    };
+    let Some(elements) = scene.get("elements").and_then(|e| e.as_array()) else {
+        return Vec::new();
+    };
+
+    let labels: HashMap<&str, &str> = elements
+        .iter()
+        .filter_map(|e| Some((e.get("containerId")?.as_str()?, e.get("text")?.as_str()?)))
+        .collect();
+    let base = path.parent().unwrap_or(Path::new(""));
+    let source = paths::normalize(path);
+
+    elements
+        .iter()
+        .filter(|e| !e.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false))
+        .filter_map(|element| {
+            let link = element.get("link")?.as_str()?;
+            let target = clean(&internal_link_target(base, link)?);
+            Some(DrawingLink {
+                source: source.clone(),
+                target: paths::normalize(&target),
+                element_id: element.get("id").and_then(|id| id.as_str()).unwrap_or("").to_string(),
+                label: element_label(element, &labels),
+                link: link.to_string(),
+                exists: target.exists(),
+            })
+        })
+        .collect()
+}
+
+impl LinkIndex {
+    /// Drops the drawings at or below `root`
+    pub fn remove_under(&mut self, root: &str) {
+        let prefix = format!("{}/", root.trim_end_matches('/'));
+        self.outgoing.retain(|path, _| path != root && !path.starts_with(&prefix));
+    }
+
+    /// Re-reads one drawing; a missing file drops its links
+    pub fn update_file(&mut self, path: &Path) {
+        let key = paths::normalize(path);
+        match compression::read_to_string(path) {
+            Ok(content) => {
+                self.outgoing.insert(key, scan(path, &content));
+            }
+            Err(_) => {
+                self.outgoing.remove(&key);
+            }
+        }
+    }
+
+    pub fn insert(&mut self, path: &Path, links: Vec<DrawingLink>) {
+        self.outgoing.insert(paths::normalize(path), links);
+    }
+
+    pub fn outgoing(&self, path: &str) -> Vec<DrawingLink> {
+        self.outgoing
+            .get(path)
+            .map(|links| {
+                links
+                    .iter()
+                    .cloned()
+                    .map(|mut link| {
+                        link.exists = Path::new(&link.target).exists();
+                        link
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Links from other drawings pointing at `path`, sorted by source
+    pub fn backlinks(&self, path: &str) -> Vec<DrawingLink> {
+        let mut backlinks: Vec<DrawingLink> = self
+            .outgoing
+            .iter()
+            .filter(|(source, _)| source.as_str() != path)
+            .flat_map(|(_, links)| links.iter().filter(|link| link.target == path).cloned())
+            .collect();
+        backlinks.sort_by(|a, b| a.source.cmp(&b.source).then(a.element_id.cmp(&b.element_id)));
+        backlinks
+    }
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Store file holding the last-used save/export folders, keyed by workspace path
+pub const STORE_FILE: &str = "locations.json";
+pub const STORE_KEY: &str = "last_used";
+
+/// Key used while no workspace is open
+const NO_WORKSPACE: &str = "";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LocationKind {
+    Save,
+    Export,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WorkspaceLocations {
+    pub save: Option<String>,
+    pub export: Option<String>,
+}
+
+pub type LocationMap = HashMap<String, WorkspaceLocations>;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SaveAsResult {
+    pub path: String,
+    /// Set when the chosen file already existed and was replaced
+    pub overwritten: bool,
+}
+
+pub fn for_workspace<'a>(map: &'a LocationMap, workspace: Option<&str>) -> Option<&'a WorkspaceLocations> {
+    map.get(workspace.unwrap_or(NO_WORKSPACE))
+}
+
+pub fn last(map: &LocationMap, workspace: Option<&str>, kind: LocationKind) -> Option<String> {
+    let locations = for_workspace(map, workspace)?;
+    match kind {
+        LocationKind::Save => locations.save.clone(),
+        LocationKind::Export => locations.export.clone(),
+    }
+}
+
+pub fn remember(map: &mut LocationMap, workspace: Option<&str>, kind: LocationKind, directory: String) {
+    let locations = map.entry(workspace.unwrap_or(NO_WORKSPACE).to_string()).or_default();
+    match kind {
+        LocationKind::Save => locations.save = Some(directory),
+        LocationKind::Export => locations.export = Some(directory),
+    }
+}
+
+/// Cleans a suggested name for the dialog: strips separators and makes sure
+/// it carries the `.excalidraw` extension
+pub fn suggested_file_name(name: &str) -> Option<String> {
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|c| if matches!(c, '/' | '\\' | ':') { '-' } else { c })
+        .collect();
+    let stem = name.strip_suffix(".excalidraw").unwrap_or(&name).trim();
+    if stem.is_empty() {
+        return None;
+    }
+    Some(format!("{}.excalidraw", stem))
+}
@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, Registry};
+
+/// Directory (under the app data dir) holding the rolling log files
+pub const LOG_DIR: &str = "logs";
+
+/// Log files are named `excaliapp.<date>.log`
+const LOG_FILE_PREFIX: &str = "excaliapp";
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// Daily files kept before the oldest is deleted
+const MAX_LOG_FILES: usize = 7;
+
+/// Upper bound for `recent_lines` so a bug report stays attachable
+pub const MAX_RECENT_LINES: usize = 5000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Keeps the file writer alive and lets the level change at runtime
+pub struct Logger {
+    level: reload::Handle<LevelFilter, Registry>,
+    /// Flushes buffered lines when dropped at exit
+    _guard: Option<WorkerGuard>,
+}
+
+impl Logger {
+    pub fn set_level(&self, level: LogLevel) {
+        if let Err(e) = self.level.reload(level.filter()) {
+            tracing::warn!("Failed to change log level: {}", e);
+        }
+    }
+}
+
+/// Installs the global subscriber, writing to stdout and to a daily rolling
+/// file in `dir`. Falls back to stdout only when the directory is unusable.
+pub fn init(dir: &Path, level: LogLevel) -> Logger {
+    let (filter, handle) = reload::Layer::new(level.filter());
+
+    let appender = fs::create_dir_all(dir)
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            RollingFileAppender::builder()
+                .rotation(Rotation::DAILY)
+                .filename_prefix(LOG_FILE_PREFIX)
+                .filename_suffix(LOG_FILE_SUFFIX)
+                .max_log_files(MAX_LOG_FILES)
+                .build(dir)
+                .map_err(|e| e.to_string())
+        });
+
+    let (file_layer, guard, file_error) = match appender {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            let layer = fmt::layer().with_writer(writer).with_ansi(false);
+            (Some(layer), Some(guard), None)
+        }
+        Err(e) => (None, None, Some(e)),
+    };
+
+    let installed = tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(file_layer)
+        .try_init();
+
+    if let Err(e) = installed {
+        eprintln!("Failed to install logger: {}", e);
+    }
+    if let Some(e) = file_error {
+        tracing::error!("Failed to open log directory {}: {}", dir.display(), e);
+    }
+
+    Logger {
+        level: handle,
+        _guard: guard,
+    }
+}
+
+/// Log files in `dir`, newest first (the date in the name sorts them)
+fn log_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().starts_with(LOG_FILE_PREFIX))
+                .unwrap_or(false)
+        })
+        .collect();
+    files.sort();
+    files.reverse();
+    files
+}
+
+/// The last `lines` log lines, oldest first, reaching into earlier days when
+/// today's file is shorter
+pub fn recent_lines(dir: &Path, lines: usize) -> Vec<String> {
+    let wanted = lines.min(MAX_RECENT_LINES);
+    let mut collected: Vec<String> = Vec::new();
+
+    for file in log_files(dir) {
+        if collected.len() >= wanted {
+            break;
+        }
+        let Ok(content) = fs::read_to_string(&file) else {
+            continue;
+        };
+        let remaining = wanted - collected.len();
+        let file_lines: Vec<&str> = content.lines().collect();
+        let start = file_lines.len().saturating_sub(remaining);
+        // Earlier files go in front of what was already collected
+        let mut chunk: Vec<String> = file_lines[start..].iter().map(|l| l.to_string()).collect();
+        chunk.append(&mut collected);
+        collected = chunk;
+    }
+
+    collected
+}
@@ -1,13 +1,16 @@
 use serde::{Deserialize, Serialize};
 use tauri::{
     menu::{
-        AboutMetadataBuilder, Menu, MenuBuilder, MenuId, MenuItemBuilder, PredefinedMenuItem,
-        Submenu, SubmenuBuilder,
+        AboutMetadataBuilder, CheckMenuItemBuilder, Menu, MenuBuilder, MenuId, MenuItem,
+        MenuItemBuilder, PredefinedMenuItem, Submenu, SubmenuBuilder,
     },
     AppHandle, Emitter, Manager, Runtime,
 };
 use tauri_plugin_store::StoreExt;
 
+use crate::error::{AppError, ResultExt};
+use crate::menu_text::{self, MenuText};
+use crate::shortcuts::Shortcuts;
 use crate::AppState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,94 +19,46 @@ pub struct MenuCommand {
     pub data: Option<serde_json::Value>,
 }
 
-// Simple menu translation function  
-fn get_menu_text(key: &str, locale: &str) -> &'static str {
-    match (locale, key) {
-        ("zh-CN", "File") => "文件",
-        ("zh-CN", "Edit") => "编辑",
-        ("zh-CN", "Layout") => "布局",
-        ("zh-CN", "View") => "视图",
-        ("zh-CN", "Language") => "语言",
-        ("zh-CN", "Preferences") => "偏好设置",
-        ("zh-CN", "Window") => "窗口",
-        ("zh-CN", "Help") => "帮助",
-        ("zh-CN", "layout_mrtree") => "树形布局",
-        ("zh-CN", "layout_layered") => "分层布局",
-        ("zh-CN", "layout_box") => "环形布局",
-        ("zh-CN", "layout_grid") => "网格布局",
-        ("zh-CN", "Open Directory") => "打开目录",
-        ("zh-CN", "New File") => "新建文件",
-        ("zh-CN", "Save") => "保存",
-        ("zh-CN", "Save As...") => "另存为...",
-        ("zh-CN", "Recent Directories") => "最近目录",
-        ("zh-CN", "Clear Recent") => "清除最近",
-        ("zh-CN", "Quit") => "退出",
-        ("zh-CN", "Cut") => "剪切",
-        ("zh-CN", "Copy") => "复制",
-        ("zh-CN", "Paste") => "粘贴",
-        ("zh-CN", "Select All") => "全选",
-        ("zh-CN", "Toggle Sidebar") => "切换侧边栏",
-        ("zh-CN", "Zoom In") => "放大",
-        ("zh-CN", "Zoom Out") => "缩小",
-        ("zh-CN", "Reset Zoom") => "重置缩放",
-        ("zh-CN", "Toggle Fullscreen") => "切换全屏",
-        ("zh-CN", "AI Settings") => "AI 设置",
-        ("zh-CN", "Minimize") => "最小化",
-        ("zh-CN", "Close Window") => "关闭窗口",
-        ("zh-CN", "Keyboard Shortcuts") => "键盘快捷键",
-        ("zh-CN", "About ExcaliApp") => "关于 ExcaliApp",
-        ("en-US", "File") => "File",
-        ("en-US", "Edit") => "Edit", 
-        ("en-US", "Layout") => "Layout",
-        ("en-US", "View") => "View",
-        ("en-US", "Language") => "Language",
-        ("en-US", "Preferences") => "Preferences",
-        ("en-US", "Window") => "Window",
-        ("en-US", "Help") => "Help",
-        ("en-US", "layout_mrtree") => "Tree Layout",
-        ("en-US", "layout_layered") => "Layer Layout",
-        ("en-US", "layout_box") => "Circle Layout",
-        ("en-US", "layout_grid") => "Grid Layout",
-        ("en-US", "Open Directory") => "Open Directory",
-        ("en-US", "New File") => "New File",
-        ("en-US", "Save") => "Save",
-        ("en-US", "Save As...") => "Save As...",
-        ("en-US", "Recent Directories") => "Recent Directories",
-        ("en-US", "Clear Recent") => "Clear Recent",
-        ("en-US", "Quit") => "Quit",
-        ("en-US", "Cut") => "Cut",
-        ("en-US", "Copy") => "Copy",
-        ("en-US", "Paste") => "Paste",
-        ("en-US", "Select All") => "Select All",
-        ("en-US", "Toggle Sidebar") => "Toggle Sidebar",
-        ("en-US", "Zoom In") => "Zoom In",
-        ("en-US", "Zoom Out") => "Zoom Out",
-        ("en-US", "Reset Zoom") => "Reset Zoom",
-        ("en-US", "Toggle Fullscreen") => "Toggle Fullscreen",
-        ("en-US", "AI Settings") => "AI Settings",
-        ("en-US", "Minimize") => "Minimize",
-        ("en-US", "Close Window") => "Close Window",
-        ("en-US", "Keyboard Shortcuts") => "Keyboard Shortcuts",
-        ("en-US", "About ExcaliApp") => "About ExcaliApp",
-        // Fallback to English for unknown keys
-        (_, "File") => "File",
-        (_, "Edit") => "Edit",
-        (_, "Layout") => "Layout", 
-        (_, "View") => "View",
-        (_, "Language") => "Language",
-        (_, "Preferences") => "Preferences",
-        (_, "Window") => "Window",
-        (_, "Help") => "Help",
-        (_, "layout_mrtree") => "Tree Layout",
-        (_, "layout_layered") => "Layer Layout",
-        (_, "layout_box") => "Circle Layout",
-        (_, "layout_grid") => "Grid Layout",
-        _ => "Unknown"
+/// Themes offered in View > Theme, as stored in `Preferences::theme`
+const THEMES: &[&str] = &["light", "dark", "system"];
+
+/// What the checkable menu items show
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MenuState {
+    pub sidebar_visible: bool,
+    pub theme: String,
+    /// Menu locale; the persisted one when left out
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+impl MenuState {
+    /// The state saved in the preferences and the i18n store
+    pub fn stored<R: Runtime>(app: &AppHandle<R>) -> Self {
+        let preferences = stored_preferences(app);
+        Self {
+            sidebar_visible: preferences.sidebar_visible,
+            theme: preferences.theme,
+            language: Some(get_current_locale(app)),
+        }
     }
 }
 
+pub(crate) fn stored_preferences<R: Runtime>(app: &AppHandle<R>) -> crate::Preferences {
+    app.store("preferences.json")
+        .ok()
+        .and_then(|store| store.get("preferences"))
+        .and_then(|value| serde_json::from_value::<crate::Preferences>(value).ok())
+        .unwrap_or_default()
+}
+
+fn language_item_id(locale: &str) -> String {
+    format!("language_{}", locale.replace('-', "_"))
+}
+
 // Get current locale from app state or default to Chinese
-fn get_current_locale<R: Runtime>(app: &AppHandle<R>) -> String {
+pub(crate) fn get_current_locale<R: Runtime>(app: &AppHandle<R>) -> String {
     // Try to read from the i18n store, default to Chinese
     use tauri_plugin_store::StoreExt;
     
@@ -124,33 +79,67 @@ fn get_current_locale<R: Runtime>(app: &AppHandle<R>) -> String {
     "zh-CN".to_string()
 }
 
+/// Persists `locale` where `get_current_locale` reads it
+pub(crate) fn set_current_locale<R: Runtime>(
+    app: &AppHandle<R>,
+    locale: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let store = app.store("i18n-store.json")?;
+    let mut state = store
+        .get("state")
+        .filter(|value| value.is_object())
+        .unwrap_or_else(|| serde_json::json!({}));
+    state["config"]["currentLanguage"] = serde_json::json!(locale);
+    store.set("state", state);
+    store.save()?;
+    Ok(())
+}
+
+/// Builds the menu again in the current locale and swaps it into the app
+/// (macOS) and every window that carries its own copy (Windows, Linux)
+pub fn rebuild_menu<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn std::error::Error>> {
+    let app_menu = create_menu(app)?;
+    app.set_menu(app_menu.clone())?;
+    for window in app.webview_windows().values() {
+        if let Some(menu) = window.menu()
+            && menu.id() != app_menu.id()
+        {
+            window.set_menu(create_menu(app)?)?;
+        }
+    }
+    Ok(())
+}
+
 fn create_language_menu<R: Runtime>(
     app: &AppHandle<R>,
+    text: &MenuText,
+    state: &MenuState,
 ) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
-    let locale = get_current_locale(app);
-    
-    let chinese = MenuItemBuilder::with_id("language_zh_CN", "🇨🇳 中文 (Chinese)")
-        .build(app)?;
-    
-    let english = MenuItemBuilder::with_id("language_en_US", "🇺🇸 English")
-        .build(app)?;
-
-    let language_menu = SubmenuBuilder::new(app, get_menu_text("Language", &locale))
-        .items(&[&chinese, &english])
-        .build()?;
+    // One entry per translation found, e.g. `language_en_US`
+    let mut language_menu = SubmenuBuilder::new(app, text.get("Language"));
+    for locale in menu_text::available(app) {
+        let checked = state.language.as_deref() == Some(locale.code.as_str());
+        let item = CheckMenuItemBuilder::with_id(language_item_id(&locale.code), locale.name)
+            .checked(checked)
+            .build(app)?;
+        language_menu = language_menu.item(&item);
+    }
 
-    Ok(language_menu)
+    Ok(language_menu.build()?)
 }
 
 pub fn create_menu<R: Runtime>(app: &AppHandle<R>) -> Result<Menu<R>, Box<dyn std::error::Error>> {
-    let file_menu = create_file_menu(app)?;
-    let edit_menu = create_edit_menu(app)?;
-    let layout_menu = create_layout_menu(app)?;
-    let view_menu = create_view_menu(app)?;
-    let language_menu = create_language_menu(app)?;
-    let preferences_menu = create_preferences_menu(app)?;
-    let window_menu = create_window_menu(app)?;
-    let help_menu = create_help_menu(app)?;
+    let state = MenuState::stored(app);
+    let text = MenuText::load(app, &get_current_locale(app));
+    let keys = Shortcuts::new(&stored_preferences(app).shortcuts);
+    let file_menu = create_file_menu(app, &text, &keys)?;
+    let edit_menu = create_edit_menu(app, &text)?;
+    let layout_menu = create_layout_menu(app, &text, &keys)?;
+    let view_menu = create_view_menu(app, &text, &keys, &state)?;
+    let language_menu = create_language_menu(app, &text, &state)?;
+    let preferences_menu = create_preferences_menu(app, &text)?;
+    let window_menu = create_window_menu(app, &text, &keys)?;
+    let help_menu = create_help_menu(app, &text, &keys)?;
 
     let menu = MenuBuilder::new(app)
         .items(&[&file_menu, &edit_menu, &layout_menu, &view_menu, &language_menu, &preferences_menu, &window_menu, &help_menu])
@@ -159,50 +148,60 @@ pub fn create_menu<R: Runtime>(app: &AppHandle<R>) -> Result<Menu<R>, Box<dyn st
     Ok(menu)
 }
 
+/// A menu item with the shortcut configured for its id, if any
+fn shortcut_item<R: Runtime>(
+    app: &AppHandle<R>,
+    keys: &Shortcuts,
+    id: &str,
+    label: &str,
+) -> tauri::Result<MenuItem<R>> {
+    let mut builder = MenuItemBuilder::with_id(id, label);
+    if let Some(accelerator) = keys.get(id) {
+        builder = builder.accelerator(accelerator);
+    }
+    builder.build(app)
+}
+
 fn create_file_menu<R: Runtime>(
     app: &AppHandle<R>,
+    text: &MenuText,
+    keys: &Shortcuts,
 ) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
-    let locale = get_current_locale(app);
-    let open_directory = MenuItemBuilder::with_id("open_directory", get_menu_text("Open Directory", &locale))
-        .accelerator("CmdOrCtrl+O")
-        .build(app)?;
+    let open_directory = shortcut_item(app, keys, "open_directory", text.get("Open Directory"))?;
 
-    let new_file = MenuItemBuilder::with_id("new_file", get_menu_text("New File", &locale))
-        .accelerator("CmdOrCtrl+N")
-        .build(app)?;
+    let new_file = shortcut_item(app, keys, "new_file", text.get("New File"))?;
 
-    let save = MenuItemBuilder::with_id("save", get_menu_text("Save", &locale))
-        .accelerator("CmdOrCtrl+S")
-        .build(app)?;
+    let templates_menu = create_templates_menu(app, text)?;
 
-    let save_as = MenuItemBuilder::with_id("save_as", get_menu_text("Save As...", &locale))
-        .accelerator("CmdOrCtrl+Shift+S")
-        .build(app)?;
+    let save = shortcut_item(app, keys, "save", text.get("Save"))?;
+
+    let save_as = shortcut_item(app, keys, "save_as", text.get("Save As..."))?;
 
     let separator = PredefinedMenuItem::separator(app)?;
 
-    // Recent directories submenu
-    let recent_menu = create_recent_directories_menu(app)?;
+    // Recent directories and files submenus
+    let recent_menu = create_recent_directories_menu(app, text)?;
+    let recent_files_menu = create_recent_files_menu(app, text)?;
 
     let separator2 = PredefinedMenuItem::separator(app)?;
 
     #[cfg(not(target_os = "macos"))]
-    let quit = MenuItemBuilder::with_id("quit", "Quit")
-        .accelerator("CmdOrCtrl+Q")
-        .build(app)?;
+    let quit = shortcut_item(app, keys, "quit", text.get("Quit"))?;
 
     #[cfg(target_os = "macos")]
     let quit = PredefinedMenuItem::quit(app, None)?;
 
-    let file_menu = SubmenuBuilder::new(app, get_menu_text("File", &locale))
+    let file_menu = SubmenuBuilder::new(app, text.get("File"))
         .items(&[
             &open_directory,
             &new_file,
+            &templates_menu,
             &separator,
             &save,
             &save_as,
             &separator2,
             &recent_menu,
+            &recent_files_menu,
             &separator2,
             &quit,
         ])
@@ -213,27 +212,105 @@ fn create_file_menu<R: Runtime>(
 
 fn create_recent_directories_menu<R: Runtime>(
     app: &AppHandle<R>,
+    text: &MenuText,
 ) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
-    let locale = get_current_locale(app);
-    let recent_menu = SubmenuBuilder::new(app, get_menu_text("Recent Directories", &locale))
+    let recent_menu = SubmenuBuilder::new(app, text.get("Recent Directories"))
         .id(MenuId::from("recent_directories"))
         .build()?;
 
     Ok(recent_menu)
 }
 
+fn create_recent_files_menu<R: Runtime>(
+    app: &AppHandle<R>,
+    text: &MenuText,
+) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
+    let recent_menu = SubmenuBuilder::new(app, text.get("Recent Files"))
+        .id(MenuId::from("recent_files"))
+        .build()?;
+
+    Ok(recent_menu)
+}
+
+/// Menu ids of template entries start with this, followed by the template name
+const TEMPLATE_ITEM_PREFIX: &str = "new_template_";
+
+fn create_templates_menu<R: Runtime>(
+    app: &AppHandle<R>,
+    text: &MenuText,
+) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
+    let templates_menu = SubmenuBuilder::new(app, text.get("New from Template"))
+        .id(MenuId::from("templates"))
+        .build()?;
+    fill_templates_menu(app, &templates_menu, text)?;
+
+    Ok(templates_menu)
+}
+
+/// Lists the templates directory, then a way to open it for adding files
+fn fill_templates_menu<R: Runtime>(
+    app: &AppHandle<R>,
+    submenu: &Submenu<R>,
+    text: &MenuText,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for item in submenu.items()? {
+        submenu.remove(&item)?;
+    }
+
+    let templates = crate::templates::dir(app)
+        .map(|dir| crate::templates::list(&dir))
+        .unwrap_or_default();
+    for template in &templates {
+        let id = format!("{}{}", TEMPLATE_ITEM_PREFIX, template.name);
+        submenu.append(&MenuItemBuilder::with_id(id, &template.name).build(app)?)?;
+    }
+    if templates.is_empty() {
+        let none = MenuItemBuilder::with_id("no_templates", text.get("No Templates"))
+            .enabled(false)
+            .build(app)?;
+        submenu.append(&none)?;
+    }
+
+    submenu.append(&PredefinedMenuItem::separator(app)?)?;
+    let open_folder = MenuItemBuilder::with_id("open_templates_folder", text.get("Open Templates Folder"))
+        .build(app)?;
+    submenu.append(&open_folder)?;
+    Ok(())
+}
+
+pub fn update_templates_menu<R: Runtime>(app: &AppHandle<R>) -> Result<(), Box<dyn std::error::Error>> {
+    let text = MenuText::load(app, &get_current_locale(app));
+    for menu in all_menus(app) {
+        if let Some(item) = menu.get("templates")
+            && let Some(submenu) = item.as_submenu()
+        {
+            fill_templates_menu(app, submenu, &text)?;
+        }
+    }
+    Ok(())
+}
+
+fn open_templates_folder<R: Runtime>(app: &AppHandle<R>) -> Result<(), AppError> {
+    use tauri_plugin_opener::OpenerExt;
+
+    let dir = crate::templates::dir(app)?;
+    std::fs::create_dir_all(&dir).context("Failed to create templates directory")?;
+    app.opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| AppError::Internal(format!("Failed to open {}: {}", dir.display(), e)))
+}
 
 fn create_edit_menu<R: Runtime>(
     app: &AppHandle<R>,
+    text: &MenuText,
 ) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
-    let locale = get_current_locale(app);
     // Use predefined menu items for proper system clipboard integration
     let cut = PredefinedMenuItem::cut(app, None)?;
     let copy = PredefinedMenuItem::copy(app, None)?;
     let paste = PredefinedMenuItem::paste(app, None)?;
     let select_all = PredefinedMenuItem::select_all(app, None)?;
 
-    let edit_menu = SubmenuBuilder::new(app, get_menu_text("Edit", &locale))
+    let edit_menu = SubmenuBuilder::new(app, text.get("Edit"))
         .items(&[
             &cut,
             &copy,
@@ -248,23 +325,30 @@ fn create_edit_menu<R: Runtime>(
 
 fn create_layout_menu<R: Runtime>(
     app: &AppHandle<R>,
+    text: &MenuText,
+    keys: &Shortcuts,
 ) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
-    let locale = get_current_locale(app);
     
-    let flowchart_layout = MenuItemBuilder::with_id("layout_mrtree", get_menu_text("layout_mrtree", &locale))
+    let flowchart_layout = MenuItemBuilder::with_id("layout_mrtree", text.get("layout_mrtree"))
         .build(app)?;
     
-    let sequence_layout = MenuItemBuilder::with_id("layout_layered", get_menu_text("layout_layered", &locale))
+    let sequence_layout = MenuItemBuilder::with_id("layout_layered", text.get("layout_layered"))
         .build(app)?;
     
-    let architecture_layout = MenuItemBuilder::with_id("layout_box", get_menu_text("layout_box", &locale))
+    let architecture_layout = MenuItemBuilder::with_id("layout_box", text.get("layout_box"))
         .build(app)?;
     
-    let grid_layout = MenuItemBuilder::with_id("layout_grid", get_menu_text("layout_grid", &locale))
+    let grid_layout = MenuItemBuilder::with_id("layout_grid", text.get("layout_grid"))
         .build(app)?;
 
-    let layout_menu = SubmenuBuilder::new(app, get_menu_text("Layout", &locale))
+    let separator = PredefinedMenuItem::separator(app)?;
+
+    let auto_layout = shortcut_item(app, keys, "auto_layout", text.get("Auto Layout"))?;
+
+    let layout_menu = SubmenuBuilder::with_id(app, "layout", text.get("Layout"))
         .items(&[
+            &auto_layout,
+            &separator,
             &flowchart_layout,
             &sequence_layout,
             &architecture_layout,
@@ -277,41 +361,47 @@ fn create_layout_menu<R: Runtime>(
 
 fn create_view_menu<R: Runtime>(
     app: &AppHandle<R>,
+    text: &MenuText,
+    keys: &Shortcuts,
+    state: &MenuState,
 ) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
-    let locale = get_current_locale(app);
-    let toggle_sidebar = MenuItemBuilder::with_id("toggle_sidebar", get_menu_text("Toggle Sidebar", &locale))
-        .accelerator("CmdOrCtrl+B")
-        .build(app)?;
+    let mut toggle_sidebar = CheckMenuItemBuilder::with_id("toggle_sidebar", text.get("Show Sidebar"))
+        .checked(state.sidebar_visible);
+    if let Some(accelerator) = keys.get("toggle_sidebar") {
+        toggle_sidebar = toggle_sidebar.accelerator(accelerator);
+    }
+    let toggle_sidebar = toggle_sidebar.build(app)?;
+
+    let mut theme_menu = SubmenuBuilder::new(app, text.get("Theme"));
+    for theme in THEMES {
+        let label = match *theme {
+            "light" => "Light",
+            "dark" => "Dark",
+            _ => "System",
+        };
+        let item = CheckMenuItemBuilder::with_id(format!("theme_{}", theme), text.get(label))
+            .checked(state.theme == *theme)
+            .build(app)?;
+        theme_menu = theme_menu.item(&item);
+    }
+    let theme_menu = theme_menu.build()?;
 
     let separator = PredefinedMenuItem::separator(app)?;
 
-    let zoom_in = MenuItemBuilder::with_id("zoom_in", get_menu_text("Zoom In", &locale))
-        .accelerator("CmdOrCtrl+Plus")
-        .build(app)?;
+    let zoom_in = shortcut_item(app, keys, "zoom_in", text.get("Zoom In"))?;
 
-    let zoom_out = MenuItemBuilder::with_id("zoom_out", get_menu_text("Zoom Out", &locale))
-        .accelerator("CmdOrCtrl+-")
-        .build(app)?;
+    let zoom_out = shortcut_item(app, keys, "zoom_out", text.get("Zoom Out"))?;
 
-    let reset_zoom = MenuItemBuilder::with_id("reset_zoom", get_menu_text("Reset Zoom", &locale))
-        .accelerator("CmdOrCtrl+0")
-        .build(app)?;
+    let reset_zoom = shortcut_item(app, keys, "reset_zoom", text.get("Reset Zoom"))?;
 
     let separator2 = PredefinedMenuItem::separator(app)?;
 
-    #[cfg(target_os = "macos")]
-    let fullscreen = MenuItemBuilder::with_id("fullscreen", get_menu_text("Toggle Fullscreen", &locale))
-        .accelerator("Ctrl+Cmd+F")
-        .build(app)?;
-
-    #[cfg(not(target_os = "macos"))]
-    let fullscreen = MenuItemBuilder::with_id("fullscreen", get_menu_text("Toggle Fullscreen", &locale))
-        .accelerator("F11")
-        .build(app)?;
+    let fullscreen = shortcut_item(app, keys, "fullscreen", text.get("Toggle Fullscreen"))?;
 
-    let view_menu = SubmenuBuilder::new(app, get_menu_text("View", &locale))
+    let view_menu = SubmenuBuilder::new(app, text.get("View"))
         .items(&[
             &toggle_sidebar,
+            &theme_menu,
             &separator,
             &zoom_in,
             &zoom_out,
@@ -327,13 +417,24 @@ fn create_view_menu<R: Runtime>(
 
 fn create_preferences_menu<R: Runtime>(
     app: &AppHandle<R>,
+    text: &MenuText,
 ) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
-    let locale = get_current_locale(app);
-    let ai_settings = MenuItemBuilder::with_id("ai_settings", get_menu_text("AI Settings", &locale))
+    let ai_settings = MenuItemBuilder::with_id("ai_settings", text.get("AI Settings"))
+        .build(app)?;
+
+    let export_settings = MenuItemBuilder::with_id("export_settings", text.get("Export Settings..."))
+        .build(app)?;
+
+    let import_settings = MenuItemBuilder::with_id("import_settings", text.get("Import Settings..."))
         .build(app)?;
 
-    let preferences_menu = SubmenuBuilder::new(app, get_menu_text("Preferences", &locale))
-        .items(&[&ai_settings])
+    let preferences_menu = SubmenuBuilder::new(app, text.get("Preferences"))
+        .items(&[
+            &ai_settings,
+            &PredefinedMenuItem::separator(app)?,
+            &export_settings,
+            &import_settings,
+        ])
         .build()?;
 
     Ok(preferences_menu)
@@ -341,25 +442,22 @@ fn create_preferences_menu<R: Runtime>(
 
 fn create_window_menu<R: Runtime>(
     app: &AppHandle<R>,
+    text: &MenuText,
+    keys: &Shortcuts,
 ) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
-    let locale = get_current_locale(app);
     #[cfg(target_os = "macos")]
     let minimize = PredefinedMenuItem::minimize(app, None)?;
 
     #[cfg(not(target_os = "macos"))]
-    let minimize = MenuItemBuilder::with_id("minimize", get_menu_text("Minimize", &locale))
-        .accelerator("CmdOrCtrl+M")
-        .build(app)?;
+    let minimize = shortcut_item(app, keys, "minimize", text.get("Minimize"))?;
 
     #[cfg(target_os = "macos")]
     let close_window = PredefinedMenuItem::close_window(app, None)?;
 
     #[cfg(not(target_os = "macos"))]
-    let close_window = MenuItemBuilder::with_id("close_window", get_menu_text("Close Window", &locale))
-        .accelerator("CmdOrCtrl+W")
-        .build(app)?;
+    let close_window = shortcut_item(app, keys, "close_window", text.get("Close Window"))?;
 
-    let window_menu = SubmenuBuilder::new(app, get_menu_text("Window", &locale))
+    let window_menu = SubmenuBuilder::new(app, text.get("Window"))
         .items(&[&minimize, &close_window])
         .build()?;
 
@@ -368,16 +466,16 @@ fn create_window_menu<R: Runtime>(
 
 fn create_help_menu<R: Runtime>(
     app: &AppHandle<R>,
+    text: &MenuText,
+    keys: &Shortcuts,
 ) -> Result<Submenu<R>, Box<dyn std::error::Error>> {
-    let locale = get_current_locale(app);
-    let keyboard_shortcuts =
-        MenuItemBuilder::with_id("keyboard_shortcuts", get_menu_text("Keyboard Shortcuts", &locale)).build(app)?;
+    let keyboard_shortcuts = shortcut_item(app, keys, "keyboard_shortcuts", text.get("Keyboard Shortcuts"))?;
 
     let separator = PredefinedMenuItem::separator(app)?;
 
     let about = PredefinedMenuItem::about(
         app,
-        Some(get_menu_text("About ExcaliApp", &locale)),
+        Some(text.get("About ExcaliApp")),
         Some(
             AboutMetadataBuilder::new()
                 .version(Some(env!("CARGO_PKG_VERSION").to_string()))
@@ -389,49 +487,120 @@ fn create_help_menu<R: Runtime>(
         ),
     )?;
 
-    let help_menu = SubmenuBuilder::new(app, get_menu_text("Help", &locale))
+    let help_menu = SubmenuBuilder::new(app, text.get("Help"))
         .items(&[&keyboard_shortcuts, &separator, &about])
         .build()?;
 
     Ok(help_menu)
 }
 
+/// Puts the checkmarks of the sidebar, theme and language items in line with
+/// `state` across every menu
+pub fn sync_menu_state<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &MenuState,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let language = state.language.clone().unwrap_or_else(|| get_current_locale(app));
+    let mut checks = vec![("toggle_sidebar".to_string(), state.sidebar_visible)];
+    checks.extend(THEMES.iter().map(|theme| (format!("theme_{}", theme), state.theme == *theme)));
+    checks.extend(
+        menu_text::available(app)
+            .into_iter()
+            .map(|locale| (language_item_id(&locale.code), locale.code == language)),
+    );
+    set_checks(app, &checks)
+}
+
+fn set_checks<R: Runtime>(
+    app: &AppHandle<R>,
+    checks: &[(String, bool)],
+) -> Result<(), Box<dyn std::error::Error>> {
+    for menu in all_menus(app) {
+        for (id, checked) in checks {
+            if let Some(item) = menu.get(id.as_str())
+                && let Some(check_item) = item.as_check_menuitem()
+            {
+                check_item.set_checked(*checked)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Clicking a check item flips it, so a theme or language entry clicked while
+/// already selected would lose its mark; keeps the clicked one checked like a
+/// radio item until the frontend syncs the real state
+fn check_radio_group<R: Runtime>(app: &AppHandle<R>, selected: &str) {
+    let group: Vec<String> = if selected.starts_with("theme_") {
+        THEMES.iter().map(|theme| format!("theme_{}", theme)).collect()
+    } else if selected.starts_with("language_") {
+        menu_text::available(app)
+            .iter()
+            .map(|locale| language_item_id(&locale.code))
+            .collect()
+    } else {
+        return;
+    };
+    let checks: Vec<(String, bool)> = group
+        .into_iter()
+        .map(|id| {
+            let checked = id == selected;
+            (id, checked)
+        })
+        .collect();
+    let _ = set_checks(app, &checks);
+}
+
 pub fn update_recent_directories_menu<R: Runtime>(
     app: &AppHandle<R>,
     recent_dirs: Vec<String>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // Get the main window
-    let window = app.get_webview_window("main").ok_or("No main window")?;
-
-    // Get the menu
-    if let Some(menu) = window.menu() {
-        // Find the recent directories submenu
-        if let Some(recent_menu) = menu.get("recent_directories") {
-            if let Some(submenu) = recent_menu.as_submenu() {
-                // Clear existing items
-                let items = submenu.items()?;
-                for item in items {
-                    submenu.remove(&item)?;
-                }
+    fill_recent_menu(app, "recent_directories", "recent_dir_", "clear_recent", &recent_dirs)
+}
 
-                // Add new items
-                for (index, dir) in recent_dirs.iter().enumerate().take(10) {
-                    let shortened_path = shorten_path(dir, 50);
-                    let item =
-                        MenuItemBuilder::with_id(format!("recent_dir_{}", index), shortened_path)
-                            .build(app)?;
-                    submenu.append(&item)?;
-                }
+pub fn update_recent_files_menu<R: Runtime>(
+    app: &AppHandle<R>,
+    recent_files: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    fill_recent_menu(app, "recent_files", "recent_file_", "clear_recent_files", &recent_files)
+}
 
-                // Add separator and clear item if there are recent directories
-                if !recent_dirs.is_empty() {
-                    let separator = PredefinedMenuItem::separator(app)?;
-                    submenu.append(&separator)?;
+/// Replaces the entries of the `submenu_id` submenu with `<item_prefix><index>`
+/// items for `paths`, followed by a `clear_id` item when there are any
+fn fill_recent_menu<R: Runtime>(
+    app: &AppHandle<R>,
+    submenu_id: &str,
+    item_prefix: &str,
+    clear_id: &str,
+    paths: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let text = MenuText::load(app, &get_current_locale(app));
+
+    for menu in all_menus(app) {
+        if let Some(recent_menu) = menu.get(submenu_id)
+            && let Some(submenu) = recent_menu.as_submenu()
+        {
+            // Clear existing items
+            let items = submenu.items()?;
+            for item in items {
+                submenu.remove(&item)?;
+            }
 
-                    let clear_item =
-                        MenuItemBuilder::with_id("clear_recent", "Clear Recent").build(app)?;
-                    submenu.append(&clear_item)?;
-                }
+            // Add new items
+            for (index, path) in paths.iter().enumerate().take(10) {
+                let shortened_path = shorten_path(path, 50);
+                let item = MenuItemBuilder::with_id(format!("{}{}", item_prefix, index), shortened_path)
+                    .build(app)?;
+                submenu.append(&item)?;
+            }
+
+            // Add separator and clear item if there are recent entries
+            if !paths.is_empty() {
+                let separator = PredefinedMenuItem::separator(app)?;
+                submenu.append(&separator)?;
+
+                let clear_item = MenuItemBuilder::with_id(clear_id, text.get("Clear Recent")).build(app)?;
+                submenu.append(&clear_item)?;
             }
         }
     }
@@ -439,6 +608,19 @@ pub fn update_recent_directories_menu<R: Runtime>(
     Ok(())
 }
 
+/// The app menu (macOS) and the copy each window carries (Windows, Linux)
+fn all_menus<R: Runtime>(app: &AppHandle<R>) -> Vec<Menu<R>> {
+    let mut menus: Vec<Menu<R>> = app.menu().into_iter().collect();
+    for window in app.webview_windows().values() {
+        if let Some(menu) = window.menu()
+            && !menus.iter().any(|m| m.id() == menu.id())
+        {
+            menus.push(menu);
+        }
+    }
+    menus
+}
+
 fn shorten_path(path: &str, max_len: usize) -> String {
     if path.len() <= max_len {
         return path.to_string();
@@ -500,7 +682,7 @@ pub fn setup_menu_event_handler<R: Runtime>(app: &AppHandle<R>) {
                                             let mut command = command_clone;
                                             command.data =
                                                 Some(serde_json::json!({ "directory": dir }));
-                                            let _ = app_handle_clone.emit("menu-command", command);
+                                            emit_to_target_window(&app_handle_clone, command);
                                         }
                                     }
                                 }
@@ -509,27 +691,75 @@ pub fn setup_menu_event_handler<R: Runtime>(app: &AppHandle<R>) {
                     }
                 });
             }
+        } else if let Some(name) = menu_id.strip_prefix(TEMPLATE_ITEM_PREFIX) {
+            let command = MenuCommand {
+                command: "new_from_template".to_string(),
+                data: Some(serde_json::json!({ "template": name })),
+            };
+            emit_to_target_window(&app_handle, command);
+        } else if menu_id == "open_templates_folder" {
+            if let Err(e) = open_templates_folder(&app_handle) {
+                tracing::warn!("{}", e);
+            }
+        } else if let Some(action) = menu_id.strip_prefix(crate::context_menu::ID_PREFIX) {
+            crate::context_menu::dispatch(&app_handle, action);
+        } else if let Some(index) = menu_id.strip_prefix("recent_file_") {
+            // Look up the file the same way, from the stored preferences
+            if let Ok(index) = index.parse::<usize>()
+                && let Some(file) = stored_preferences(&app_handle).recent_files.get(index)
+            {
+                let mut command = command;
+                command.data = Some(serde_json::json!({ "file": file }));
+                emit_to_target_window(&app_handle, command);
+            }
         } else {
-            let _ = app_handle.emit("menu-command", command);
+            check_radio_group(&app_handle, menu_id);
+            emit_to_target_window(&app_handle, command);
         }
     });
 }
 
-#[allow(dead_code)]
+/// Menu commands act on the focused window only, so a second window does not
+/// save or switch folders along with it
+fn emit_to_target_window<R: Runtime>(app: &AppHandle<R>, command: MenuCommand) {
+    let windows = app.webview_windows();
+    let target = windows
+        .values()
+        .find(|w| w.is_focused().unwrap_or(false))
+        .or_else(|| windows.get(crate::windows::MAIN_WINDOW))
+        .or_else(|| windows.values().next());
+    if let Some(window) = target {
+        let _ = window.emit_to(window.label(), "menu-command", command);
+    }
+}
+
 pub fn update_menu_item_state<R: Runtime>(
     app: &AppHandle<R>,
     item_id: &str,
     enabled: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let window = app.get_webview_window("main").ok_or("No main window")?;
-
-    if let Some(menu) = window.menu() {
+    for menu in all_menus(app) {
         if let Some(item) = menu.get(item_id) {
             if let Some(menu_item) = item.as_menuitem() {
                 menu_item.set_enabled(enabled)?;
+            } else if let Some(submenu) = item.as_submenu() {
+                submenu.set_enabled(enabled)?;
             }
         }
     }
 
     Ok(())
 }
+
+/// Greys out Save without unsaved changes, and Save As and the Layout menu
+/// without an open drawing
+pub fn update_file_items_state<R: Runtime>(
+    app: &AppHandle<R>,
+    has_active_file: bool,
+    is_dirty: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    update_menu_item_state(app, "save", has_active_file && is_dirty)?;
+    update_menu_item_state(app, "save_as", has_active_file)?;
+    update_menu_item_state(app, "layout", has_active_file)?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Runtime};
+
+/// Folder of `<code>.json` menu translations, both among the bundled
+/// resources and in the app config dir for user-provided overrides
+pub const LOCALE_DIR: &str = "locales";
+
+/// Fills in keys a translation leaves out
+const FALLBACK_LOCALE: &str = "en-US";
+
+/// Compiled in so the menu stays translated when resources are missing
+const BUILTIN_LOCALES: &[(&str, &str)] = &[
+    ("en-US", include_str!("../locales/en-US.json")),
+    ("zh-CN", include_str!("../locales/zh-CN.json")),
+];
+
+/// Contents of one locale file; keys are the English menu labels
+#[derive(Debug, Deserialize, Default)]
+struct LocaleFile {
+    /// Label of the locale in the Language menu
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    strings: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct LocaleInfo {
+    pub code: String,
+    pub name: String,
+}
+
+/// Locale codes name files, so they must not carry path separators
+fn is_valid_code(code: &str) -> bool {
+    !code.is_empty() && code.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Resource folder first, so user files in the config folder win
+fn locale_dirs<R: Runtime>(app: &AppHandle<R>) -> Vec<PathBuf> {
+    [app.path().resource_dir(), app.path().app_config_dir()]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(LOCALE_DIR))
+        .collect()
+}
+
+/// The built-in translation of `code` with every file of the same name laid
+/// over it
+fn read_locale<R: Runtime>(app: &AppHandle<R>, code: &str) -> Option<LocaleFile> {
+    if !is_valid_code(code) {
+        return None;
+    }
+
+    let builtin = BUILTIN_LOCALES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .and_then(|(_, content)| serde_json::from_str::<LocaleFile>(content).ok());
+    let mut found = builtin.is_some();
+    let mut locale = builtin.unwrap_or_default();
+
+    for dir in locale_dirs(app) {
+        let path = dir.join(format!("{}.json", code));
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        match serde_json::from_str::<LocaleFile>(&content) {
+            Ok(file) => {
+                found = true;
+                locale.name = file.name.or(locale.name);
+                locale.strings.extend(file.strings);
+            }
+            Err(e) => tracing::warn!("Ignoring menu translation {}: {}", path.display(), e),
+        }
+    }
+
+    found.then_some(locale)
+}
+
+/// Built-in locales plus any others found in the locale folders
+pub fn available<R: Runtime>(app: &AppHandle<R>) -> Vec<LocaleInfo> {
+    let mut codes: Vec<String> = BUILTIN_LOCALES.iter().map(|(c, _)| c.to_string()).collect();
+    for dir in locale_dirs(app) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().is_some_and(|ext| ext == "json")
+                && let Some(code) = path.file_stem().map(|s| s.to_string_lossy().to_string())
+                && is_valid_code(&code)
+                && !codes.contains(&code)
+            {
+                codes.push(code);
+            }
+        }
+    }
+
+    codes
+        .into_iter()
+        .filter_map(|code| {
+            let name = read_locale(app, &code)?.name.unwrap_or_else(|| code.clone());
+            Some(LocaleInfo { code, name })
+        })
+        .collect()
+}
+
+pub fn is_available<R: Runtime>(app: &AppHandle<R>, code: &str) -> bool {
+    read_locale(app, code).is_some()
+}
+
+/// Menu labels in one locale, falling back to English
+pub struct MenuText {
+    strings: HashMap<String, String>,
+}
+
+impl MenuText {
+    pub fn load<R: Runtime>(app: &AppHandle<R>, locale: &str) -> Self {
+        let mut strings = read_locale(app, FALLBACK_LOCALE).unwrap_or_default().strings;
+        if locale != FALLBACK_LOCALE {
+            strings.extend(read_locale(app, locale).unwrap_or_default().strings);
+        }
+        Self { strings }
+    }
+
+    /// Unknown keys show as themselves
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).map(String::as_str).unwrap_or(key)
+    }
+}
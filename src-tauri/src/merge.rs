@@ -0,0 +1,578 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::compression::COMPRESSED_SUFFIX;
+use crate::error::AppError;
+use crate::{paths, FileTreeNode};
+
+/// Which version of a scene an element was taken from
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Mine,
+    Theirs,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Both versions changed the element, or added one with the same id
+    BothChanged,
+    /// Changed in mine, deleted in theirs
+    DeletedInTheirs,
+    /// Changed in theirs, deleted in mine
+    DeletedInMine,
+}
+
+/// An element both versions changed; the merge keeps the side in `kept`
+#[derive(Debug, Serialize, Clone)]
+pub struct MergeConflict {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub element_type: Option<String>,
+    pub kind: ConflictKind,
+    pub kept: Side,
+}
+
+/// Answer of `merge_scene_changes`
+#[derive(Debug, Serialize, Clone)]
+pub struct SceneMerge {
+    pub content: String,
+    /// Elements changed only in mine
+    pub from_mine: usize,
+    /// Elements changed only in theirs
+    pub from_theirs: usize,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+fn parse(content: &str, what: &str) -> Result<Value, AppError> {
+    let scene: Value =
+        serde_json::from_str(content)
+            .map_err(|e| AppError::InvalidInput(format!("Invalid {}: {}", what, e)))?;
+    if !scene.is_object() {
+        return Err(AppError::InvalidInput(format!("Invalid {}: not a scene", what)));
+    }
+    Ok(scene)
+}
+
+fn elements(scene: &Value) -> Vec<&Value> {
+    scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|e| e.get("id").and_then(Value::as_str).is_some())
+        .collect()
+}
+
+fn id(element: &Value) -> &str {
+    element.get("id").and_then(Value::as_str).unwrap_or_default()
+}
+
+fn by_id<'a>(elements: &[&'a Value]) -> HashMap<&'a str, &'a Value> {
+    elements.iter().map(|e| (id(e), *e)).collect()
+}
+
+fn is_live(element: Option<&Value>) -> bool {
+    element.is_some_and(|e| e.get("isDeleted").and_then(Value::as_bool) != Some(true))
+}
+
+fn version(element: Option<&Value>) -> u64 {
+    element.and_then(|e| e.get("version")).and_then(Value::as_u64).unwrap_or(0)
+}
+
+/// The side Excalidraw itself would keep when collaborators collide: the
+/// higher `version`, mine on a tie
+fn newer(mine: Option<&Value>, theirs: Option<&Value>) -> Side {
+    if version(theirs) > version(mine) {
+        Side::Theirs
+    } else {
+        Side::Mine
+    }
+}
+
+/// Merges two edited versions of a scene element by element. Changes only
+/// one side made are taken; an element both changed is a conflict, settled
+/// for the side that still has it or else the one with the newer version.
+/// Without `base` (e.g. for sync conflict copies) an element only one side
+/// has is kept, since a deletion can't be told from an addition.
+pub fn merge(base: Option<&str>, mine: &str, theirs: &str) -> Result<SceneMerge, AppError> {
+    let base = base.map(|b| parse(b, "base version")).transpose()?;
+    let mut scene = parse(mine, "local version")?;
+    let theirs = parse(theirs, "other version")?;
+
+    let base_elements = base.as_ref().map(|b| elements(b)).unwrap_or_default();
+    let base_map = base.as_ref().map(|_| by_id(&base_elements));
+    let (mine_elements, theirs_elements) = (elements(&scene), elements(&theirs));
+    let (mine_map, theirs_map) = (by_id(&mine_elements), by_id(&theirs_elements));
+
+    let mut from_mine = 0;
+    let mut from_theirs = 0;
+    let mut conflicts = Vec::new();
+    let mut merged: HashMap<&str, &Value> = HashMap::new();
+    let ids: Vec<&str> = mine_elements.iter().chain(&theirs_elements).map(|e| id(e)).collect();
+    let mut seen = HashSet::new();
+    for id in ids.into_iter().filter(|id| seen.insert(*id)) {
+        let (m, t) = (mine_map.get(id).copied(), theirs_map.get(id).copied());
+        let side = if m == t {
+            Side::Mine
+        } else if let Some(base_map) = &base_map
+            && base_map.get(id).copied() == m
+        {
+            from_theirs += 1;
+            Side::Theirs
+        } else if let Some(base_map) = &base_map
+            && base_map.get(id).copied() == t
+        {
+            from_mine += 1;
+            Side::Mine
+        } else if base_map.is_none() && (m.is_none() || t.is_none()) {
+            if m.is_some() {
+                from_mine += 1;
+                Side::Mine
+            } else {
+                from_theirs += 1;
+                Side::Theirs
+            }
+        } else if !is_live(m) && !is_live(t) {
+            // Deleted on both sides, only the tombstones differ
+            newer(m, t)
+        } else {
+            let (kind, kept) = match (is_live(m), is_live(t)) {
+                (true, false) => (ConflictKind::DeletedInTheirs, Side::Mine),
+                (false, true) => (ConflictKind::DeletedInMine, Side::Theirs),
+                _ => (ConflictKind::BothChanged, newer(m, t)),
+            };
+            let element = m.or(t);
+            conflicts.push(MergeConflict {
+                id: id.to_string(),
+                element_type: element.and_then(|e| e.get("type")).and_then(Value::as_str).map(str::to_string),
+                kind,
+                kept,
+            });
+            kept
+        };
+        let chosen = match side {
+            Side::Mine => m,
+            Side::Theirs => t,
+        };
+        if let Some(element) = chosen {
+            merged.insert(id, element);
+        }
+    }
+
+    let elements = Value::Array(
+        stacking_order(&mine_elements, &theirs_elements)
+            .into_iter()
+            .filter_map(|id| merged.get(id).map(|e| (*e).clone()))
+            .collect(),
+    );
+    let app_state = merge_app_state(base.as_ref(), &scene, &theirs);
+    let files = merge_files(&scene, &theirs);
+
+    let scene_map = scene.as_object_mut()
+        .ok_or_else(|| AppError::InvalidInput("Invalid local version: not a scene".to_string()))?;
+    scene_map.insert("elements".to_string(), elements);
+    if let Some(app_state) = app_state {
+        scene_map.insert("appState".to_string(), app_state);
+    }
+    if let Some(files) = files {
+        scene_map.insert("files".to_string(), files);
+    }
+
+    let content = serde_json::to_string_pretty(&scene)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+    Ok(SceneMerge {
+        content,
+        from_mine,
+        from_theirs,
+        conflicts,
+    })
+}
+
+/// Mine's order, with elements only theirs has placed after the element
+/// they follow in theirs
+fn stacking_order<'a>(mine: &[&'a Value], theirs: &[&'a Value]) -> Vec<&'a str> {
+    let in_mine: HashSet<&str> = mine.iter().map(|e| id(e)).collect();
+    let mut following: HashMap<Option<&str>, Vec<&str>> = HashMap::new();
+    let mut anchor = None;
+    for element in theirs {
+        let id = id(element);
+        if in_mine.contains(id) {
+            anchor = Some(id);
+        } else {
+            following.entry(anchor).or_default().push(id);
+        }
+    }
+
+    let mut order = following.remove(&None).unwrap_or_default();
+    for element in mine {
+        let id = id(element);
+        order.push(id);
+        order.extend(following.remove(&Some(id)).unwrap_or_default());
+    }
+    order
+}
+
+/// Mine's `appState`, with the entries only theirs changed since `base`
+fn merge_app_state(base: Option<&Value>, mine: &Value, theirs: &Value) -> Option<Value> {
+    let base = base?.get("appState")?.as_object()?;
+    let mut app_state = mine.get("appState")?.as_object()?.clone();
+    for (key, value) in theirs.get("appState")?.as_object()? {
+        if app_state.get(key) == base.get(key) {
+            app_state.insert(key.clone(), value.clone());
+        }
+    }
+    Some(Value::Object(app_state))
+}
+
+/// Embedded images of both versions; they never change once added, so
+/// mine's are kept where both have one
+fn merge_files(mine: &Value, theirs: &Value) -> Option<Value> {
+    let theirs = theirs.get("files")?.as_object()?;
+    let mut files = mine.get("files").and_then(|f| f.as_object()).cloned().unwrap_or_else(Map::new);
+    for (id, file) in theirs {
+        files.entry(id.clone()).or_insert_with(|| file.clone());
+    }
+    Some(Value::Object(files))
+}
+
+/// Who left a conflict copy next to a drawing
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictSource {
+    /// `plan (Jane's conflicted copy 2025-01-02).excalidraw`
+    Dropbox,
+    /// `plan.sync-conflict-20250102-153045-ABCDEFG.excalidraw`
+    Syncthing,
+    /// `plan (conflict 20250102-1530).excalidraw`, from the app's own sync
+    Sync,
+}
+
+/// Answer of `find_sync_conflicts`
+#[derive(Debug, Serialize, Clone)]
+pub struct SyncConflictCopy {
+    pub path: String,
+    /// The drawing it is a copy of
+    pub original: String,
+    pub source: ConflictSource,
+}
+
+/// The drawing a sync conflict copy at `path` was made of, judged by name
+pub fn conflict_original(path: &Path) -> Option<(PathBuf, ConflictSource)> {
+    let name = path.file_name()?.to_str()?;
+    let suffix = if name.ends_with(COMPRESSED_SUFFIX) {
+        COMPRESSED_SUFFIX
+    } else {
+        ".excalidraw"
+    };
+    let stem = name.strip_suffix(suffix)?;
+
+    let (original, source) = if let Some(index) = stem.find(".sync-conflict-") {
+        (&stem[..index], ConflictSource::Syncthing)
+    } else {
+        let inner_start = stem.strip_suffix(')')?.rfind(" (")?;
+        let inner = &stem[inner_start + 2..stem.len() - 1];
+        let source = if inner.contains("conflicted copy") {
+            ConflictSource::Dropbox
+        } else if inner.starts_with("conflict ") {
+            ConflictSource::Sync
+        } else {
+            return None;
+        };
+        (&stem[..inner_start], source)
+    };
+    if original.is_empty() {
+        return None;
+    }
+    Some((path.with_file_name(format!("{}{}", original, suffix)), source))
+}
+
+/// The conflict copies among `files` whose original still exists
+pub fn find_conflict_copies(files: &[PathBuf]) -> Vec<SyncConflictCopy> {
+    let mut copies: Vec<SyncConflictCopy> = files
+        .iter()
+        .filter_map(|path| {
+            let (original, source) = conflict_original(path)?;
+            original.is_file().then(|| SyncConflictCopy {
+                path: paths::normalize(path),
+                original: paths::normalize(&original),
+                source,
+            })
+        })
+        .collect();
+    copies.sort_by(|a, b| a.path.cmp(&b.path));
+    copies
+}
+
+/// How `resolve_sync_conflict` settles a conflict copy; the copy is
+/// deleted in every case
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    /// Merge the copy into the original
+    Merge,
+    /// Keep the original as it is
+    KeepOriginal,
+    /// Replace the original with the copy
+    KeepConflict,
+}
+
+/// Name of the drawing the conflict copy `node` was made of
+fn original_name(node: &FileTreeNode) -> Option<String> {
+    if node.is_directory {
+        return None;
+    }
+    let (original, _) = conflict_original(Path::new(&node.name))?;
+    Some(original.file_name()?.to_str()?.to_string())
+}
+
+/// Moves the conflict copies in each folder of `tree` into the `conflicts`
+/// of the drawing they were made of; copies whose original is not in the
+/// same folder stay where they are
+pub fn group_conflicts(tree: &mut Vec<FileTreeNode>) {
+    for node in tree.iter_mut() {
+        if let Some(children) = node.children.as_mut() {
+            group_conflicts(children);
+        }
+    }
+
+    let originals: HashSet<String> = tree
+        .iter()
+        .filter(|node| !node.is_directory && original_name(node).is_none())
+        .map(|node| node.name.clone())
+        .collect();
+    let mut copies: HashMap<String, Vec<FileTreeNode>> = HashMap::new();
+    for node in std::mem::take(tree) {
+        match original_name(&node).filter(|name| originals.contains(name)) {
+            Some(name) => copies.entry(name).or_default().push(node),
+            None => tree.push(node),
+        }
+    }
+    for node in tree.iter_mut() {
+        if let Some(mut conflicts) = copies.remove(&node.name) {
+            conflicts.sort_by(|a, b| a.name.cmp(&b.name));
+            node.conflicts = conflicts;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn element(id: &str, version: u64, x: i64) -> Value {
+        json!({"id": id, "type": "rectangle", "version": version, "x": x})
+    }
+
+    fn deleted(id: &str, version: u64) -> Value {
+        json!({"id": id, "type": "rectangle", "version": version, "isDeleted": true})
+    }
+
+    fn scene(elements: Vec<Value>) -> String {
+        json!({"type": "excalidraw", "version": 2, "elements": elements}).to_string()
+    }
+
+    fn merged(merge: &SceneMerge) -> Vec<Value> {
+        let scene: Value = serde_json::from_str(&merge.content).unwrap();
+        scene["elements"].as_array().unwrap().clone()
+    }
+
+    fn ids(elements: &[Value]) -> Vec<&str> {
+        elements.iter().map(|e| id(e)).collect()
+    }
+
+    fn node(name: &str, children: Option<Vec<FileTreeNode>>) -> FileTreeNode {
+        FileTreeNode {
+            name: name.to_string(),
+            path: format!("/vault/{}", name),
+            relative_path: name.to_string(),
+            is_directory: children.is_some(),
+            modified: false,
+            metadata: Default::default(),
+            pinned: false,
+            read_only: false,
+            sync_status: None,
+            conflicts: Vec::new(),
+            children,
+        }
+    }
+
+    fn names(nodes: &[FileTreeNode]) -> Vec<&str> {
+        nodes.iter().map(|n| n.name.as_str()).collect()
+    }
+
+    #[test]
+    fn edits_to_different_elements_are_both_taken() {
+        let base = scene(vec![element("a", 1, 0), element("b", 1, 0)]);
+        let mine = scene(vec![element("a", 2, 10), element("b", 1, 0)]);
+        let theirs = scene(vec![element("a", 1, 0), element("b", 2, 20)]);
+
+        let merge = merge(Some(&base), &mine, &theirs).unwrap();
+        assert!(merge.conflicts.is_empty());
+        assert_eq!((merge.from_mine, merge.from_theirs), (1, 1));
+        assert_eq!(merged(&merge), vec![element("a", 2, 10), element("b", 2, 20)]);
+    }
+
+    #[test]
+    fn deletion_on_one_side_is_taken_when_the_other_left_it_alone() {
+        let base = scene(vec![element("a", 1, 0), element("b", 1, 0)]);
+        let mine = scene(vec![element("a", 1, 0), element("b", 1, 0)]);
+        let theirs = scene(vec![element("b", 1, 0)]);
+
+        let merge = merge(Some(&base), &mine, &theirs).unwrap();
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(ids(&merged(&merge)), vec!["b"]);
+    }
+
+    #[test]
+    fn deletion_against_an_edit_keeps_the_edit() {
+        let base = scene(vec![element("a", 1, 0), element("b", 1, 0)]);
+        // a: edited in mine, deleted in theirs; b: removed in mine, edited in theirs
+        let mine = scene(vec![element("a", 2, 10)]);
+        let theirs = scene(vec![deleted("a", 2), element("b", 2, 20)]);
+
+        let merge = merge(Some(&base), &mine, &theirs).unwrap();
+        let kinds: Vec<(&str, ConflictKind, Side)> =
+            merge.conflicts.iter().map(|c| (c.id.as_str(), c.kind, c.kept)).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("a", ConflictKind::DeletedInTheirs, Side::Mine),
+                ("b", ConflictKind::DeletedInMine, Side::Theirs),
+            ]
+        );
+        assert_eq!(merge.conflicts[0].element_type.as_deref(), Some("rectangle"));
+        assert_eq!(merged(&merge), vec![element("a", 2, 10), element("b", 2, 20)]);
+    }
+
+    #[test]
+    fn both_changed_keeps_the_newer_version() {
+        let base = scene(vec![element("a", 1, 0), element("b", 1, 0)]);
+        let mine = scene(vec![element("a", 3, 10), element("b", 2, 10)]);
+        let theirs = scene(vec![element("a", 2, 20), element("b", 4, 20)]);
+
+        let merge = merge(Some(&base), &mine, &theirs).unwrap();
+        let kept: Vec<Side> = merge.conflicts.iter().map(|c| c.kept).collect();
+        assert_eq!(kept, vec![Side::Mine, Side::Theirs]);
+        assert!(merge.conflicts.iter().all(|c| c.kind == ConflictKind::BothChanged));
+        assert_eq!(merged(&merge), vec![element("a", 3, 10), element("b", 4, 20)]);
+    }
+
+    #[test]
+    fn without_base_elements_only_one_side_has_are_kept() {
+        let mine = scene(vec![element("a", 1, 0), element("m", 1, 0)]);
+        let theirs = scene(vec![element("t", 1, 0), element("a", 1, 0)]);
+
+        let merge = merge(None, &mine, &theirs).unwrap();
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(ids(&merged(&merge)), vec!["t", "a", "m"]);
+    }
+
+    #[test]
+    fn stacking_order_follows_mine_with_their_additions_in_place() {
+        let base = scene(vec![element("a", 1, 0), element("b", 1, 0), element("c", 1, 0)]);
+        // Mine moved c to the back; theirs added x above a and y on top
+        let mine = scene(vec![element("c", 1, 0), element("a", 1, 0), element("b", 1, 0)]);
+        let theirs = scene(vec![
+            element("a", 1, 0),
+            element("x", 1, 0),
+            element("b", 1, 0),
+            element("c", 1, 0),
+            element("y", 1, 0),
+        ]);
+
+        let merge = merge(Some(&base), &mine, &theirs).unwrap();
+        assert_eq!(ids(&merged(&merge)), vec!["c", "y", "a", "x", "b"]);
+    }
+
+    #[test]
+    fn reordering_alone_is_not_a_conflict() {
+        let base = scene(vec![element("a", 1, 0), element("b", 1, 0)]);
+        let mine = scene(vec![element("a", 1, 0), element("b", 1, 0)]);
+        let theirs = scene(vec![element("b", 1, 0), element("a", 1, 0)]);
+
+        let merge = merge(Some(&base), &mine, &theirs).unwrap();
+        assert!(merge.conflicts.is_empty());
+        assert_eq!((merge.from_mine, merge.from_theirs), (0, 0));
+        assert_eq!(ids(&merged(&merge)), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn conflict_copies_are_recognised_by_name() {
+        let cases = [
+            ("plan (Jane's conflicted copy 2025-01-02).excalidraw", "plan.excalidraw", ConflictSource::Dropbox),
+            (
+                "plan.sync-conflict-20250102-153045-ABCDEFG.excalidraw",
+                "plan.excalidraw",
+                ConflictSource::Syncthing,
+            ),
+            ("plan (conflict 20250102-1530).excalidraw", "plan.excalidraw", ConflictSource::Sync),
+            (
+                "my plan (v2) (conflict 20250102-1530).excalidraw.gz",
+                "my plan (v2).excalidraw.gz",
+                ConflictSource::Sync,
+            ),
+        ];
+        for (name, original, source) in cases {
+            let path = Path::new("/vault/notes").join(name);
+            assert_eq!(
+                conflict_original(&path),
+                Some((Path::new("/vault/notes").join(original), source)),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn other_names_are_not_conflict_copies() {
+        for name in [
+            "plan.excalidraw",
+            "plan (draft).excalidraw",
+            "plan (conflict 20250102-1530).png",
+            " (conflict 20250102-1530).excalidraw",
+            ".sync-conflict-20250102-153045-ABCDEFG.excalidraw",
+        ] {
+            assert_eq!(conflict_original(Path::new(name)), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn conflict_copies_are_grouped_under_their_original() {
+        let mut tree = vec![
+            node(
+                "notes",
+                Some(vec![
+                    node("plan.excalidraw", None),
+                    node("plan.sync-conflict-20250102-153045-ABCDEFG.excalidraw", None),
+                    node("plan (Jane's conflicted copy 2025-01-02).excalidraw", None),
+                ]),
+            ),
+            node("idea.excalidraw", None),
+            node("gone (conflict 20250102-1530).excalidraw", None),
+        ];
+
+        group_conflicts(&mut tree);
+
+        // A copy whose original is missing stays in place
+        assert_eq!(
+            names(&tree),
+            ["notes", "idea.excalidraw", "gone (conflict 20250102-1530).excalidraw"]
+        );
+        assert!(tree[1].conflicts.is_empty());
+
+        let notes = tree[0].children.as_ref().unwrap();
+        assert_eq!(names(notes), ["plan.excalidraw"]);
+        assert_eq!(
+            names(&notes[0].conflicts),
+            [
+                "plan (Jane's conflicted copy 2025-01-02).excalidraw",
+                "plan.sync-conflict-20250102-153045-ABCDEFG.excalidraw",
+            ]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::render;
+
+/// Answer of `export_mermaid`
+#[derive(Debug, Serialize, Clone)]
+pub struct MermaidReport {
+    /// Flowchart source, ready for a ```mermaid block
+    pub mermaid: String,
+    pub nodes: usize,
+    pub edges: usize,
+    /// Elements with no Mermaid equivalent, left out
+    pub skipped: Vec<SkippedElement>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SkippedElement {
+    pub id: String,
+    pub element_type: String,
+    pub reason: String,
+}
+
+fn str_field<'a>(element: &'a Value, key: &str) -> Option<&'a str> {
+    element.get(key).and_then(|v| v.as_str())
+}
+
+fn center(bounds: &render::Bounds) -> (f64, f64) {
+    ((bounds.min_x + bounds.max_x) / 2.0, (bounds.min_y + bounds.max_y) / 2.0)
+}
+
+/// A label inside double quotes; Mermaid reads entity codes there
+fn quoted(label: &str) -> String {
+    let escaped = label
+        .trim()
+        .replace('"', "#quot;")
+        .replace('\n', "<br/>");
+    format!("\"{}\"", if escaped.is_empty() { " " } else { &escaped })
+}
+
+fn node_shape(element: &Value, label: &str) -> String {
+    let label = quoted(label);
+    match str_field(element, "type") {
+        Some("ellipse") => format!("(({}))", label),
+        Some("diamond") => format!("{{{}}}", label),
+        _ if element.get("roundness").is_some_and(|r| !r.is_null()) => format!("({})", label),
+        _ => format!("[{}]", label),
+    }
+}
+
+/// The link between two nodes, following the arrow's heads and line style
+fn link(arrow: &Value, label: Option<&str>) -> (String, bool) {
+    let head = |key: &str| arrow.get(key).is_some_and(|h| !h.is_null());
+    let (start, end) = (head("startArrowhead"), head("endArrowhead"));
+    let dashed = matches!(str_field(arrow, "strokeStyle"), Some("dashed" | "dotted"));
+    let thick = arrow.get("strokeWidth").and_then(|w| w.as_f64()).unwrap_or(1.0) >= 4.0;
+
+    // Line, then the end without and with a head: ---/-->, ===/==>, -.-/-.->
+    let (line, open, head) = match (dashed, thick) {
+        (true, _) => ("-.", "-", "->"),
+        (false, true) => ("==", "=", ">"),
+        (false, false) => ("--", "-", ">"),
+    };
+    let mut arrow_text = String::new();
+    if start && end {
+        arrow_text.push('<');
+    }
+    arrow_text.push_str(line);
+    arrow_text.push_str(if start || end { head } else { open });
+    if let Some(label) = label.filter(|l| !l.trim().is_empty()) {
+        arrow_text = format!("{}|{}|", arrow_text, quoted(label));
+    }
+    // A head only at the start points the other way
+    (arrow_text, start && !end)
+}
+
+/// The node an arrow end is bound to; arrows may be bound to a shape's
+/// label rather than the shape
+fn endpoint<'a>(
+    binding: Option<&Value>,
+    by_id: &HashMap<&str, &'a Value>,
+    node_ids: &HashMap<&str, String>,
+) -> Option<&'a str> {
+    let id = binding?.get("elementId")?.as_str()?;
+    let element = by_id.get(id)?;
+    if node_ids.contains_key(id) {
+        return str_field(element, "id");
+    }
+    let container = str_field(by_id.get(str_field(element, "containerId")?)?, "id")?;
+    node_ids.contains_key(container).then_some(container)
+}
+
+/// Best-effort flowchart of a scene: rectangles, ellipses and diamonds
+/// become nodes labelled with their bound text, arrows bound at both ends
+/// become links and frames become subgraphs
+pub fn convert(scene: &Value) -> MermaidReport {
+    let elements = render::visible_elements(scene);
+    let by_id: HashMap<&str, &Value> = elements
+        .iter()
+        .filter_map(|e| Some((str_field(e, "id")?, *e)))
+        .collect();
+    let mut skipped = Vec::new();
+    let mut skip = |element: &Value, reason: &str| {
+        skipped.push(SkippedElement {
+            id: str_field(element, "id").unwrap_or_default().to_string(),
+            element_type: str_field(element, "type").unwrap_or_default().to_string(),
+            reason: reason.to_string(),
+        });
+    };
+
+    // Bound text labels its container
+    let mut labels: HashMap<&str, &str> = HashMap::new();
+    for element in &elements {
+        if str_field(element, "type") != Some("text") {
+            continue;
+        }
+        let text = str_field(element, "originalText")
+            .or_else(|| str_field(element, "text"))
+            .unwrap_or_default();
+        match str_field(element, "containerId").filter(|id| by_id.contains_key(id)) {
+            Some(container) => {
+                labels.insert(container, text);
+            }
+            None => skip(element, "Text outside a shape or arrow"),
+        }
+    }
+
+    // Nodes in reading order, so ids follow the layout
+    let mut nodes: Vec<(&Value, render::Bounds)> = elements
+        .iter()
+        .filter(|e| matches!(str_field(e, "type"), Some("rectangle" | "ellipse" | "diamond")))
+        .map(|e| (*e, render::element_bounds(e)))
+        .collect();
+    nodes.sort_by(|a, b| {
+        a.1.min_y
+            .total_cmp(&b.1.min_y)
+            .then(a.1.min_x.total_cmp(&b.1.min_x))
+    });
+    let node_ids: HashMap<&str, String> = nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (node, _))| Some((str_field(node, "id")?, format!("n{}", i + 1))))
+        .collect();
+    let centers: HashMap<&str, (f64, f64)> = nodes
+        .iter()
+        .filter_map(|(node, bounds)| Some((str_field(node, "id")?, center(bounds))))
+        .collect();
+
+    let mut links = Vec::new();
+    let (mut across, mut down) = (0.0, 0.0);
+    for element in &elements {
+        match str_field(element, "type") {
+            Some("rectangle" | "ellipse" | "diamond" | "text" | "frame" | "magicframe") => {}
+            Some("arrow") => {
+                let (Some(from), Some(to)) = (
+                    endpoint(element.get("startBinding"), &by_id, &node_ids),
+                    endpoint(element.get("endBinding"), &by_id, &node_ids),
+                ) else {
+                    skip(element, "Arrow not bound to shapes at both ends");
+                    continue;
+                };
+                let label = str_field(element, "id").and_then(|id| labels.get(id).copied());
+                let (arrow, reversed) = link(element, label);
+                let (from, to) = if reversed { (to, from) } else { (from, to) };
+                links.push(format!("{} {} {}", node_ids[from], arrow, node_ids[to]));
+                let ((x1, y1), (x2, y2)) = (centers[from], centers[to]);
+                across += (x2 - x1).abs();
+                down += (y2 - y1).abs();
+            }
+            Some("line") => skip(element, "Lines can't connect shapes; use an arrow"),
+            _ => skip(element, "No Mermaid equivalent"),
+        }
+    }
+
+    let mut lines = vec![format!("flowchart {}", if across > down { "LR" } else { "TD" })];
+    let declaration = |node: &Value| {
+        let id = str_field(node, "id").unwrap_or_default();
+        format!("{}{}", node_ids[id], node_shape(node, labels.get(id).copied().unwrap_or_default()))
+    };
+    let frames = render::frames(&elements);
+    let frame_of = |node: &Value| {
+        str_field(node, "frameId")
+            .filter(|id| frames.iter().any(|f| str_field(f, "id") == Some(id)))
+            .map(str::to_string)
+    };
+    for (i, frame) in frames.iter().enumerate() {
+        let id = str_field(frame, "id");
+        let members: Vec<&Value> = nodes
+            .iter()
+            .map(|(node, _)| *node)
+            .filter(|node| frame_of(node).is_some() && frame_of(node).as_deref() == id)
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+        let name = str_field(frame, "name").unwrap_or("Frame");
+        lines.push(format!("    subgraph f{}[{}]", i + 1, quoted(name)));
+        lines.extend(members.into_iter().map(|node| format!("        {}", declaration(node))));
+        lines.push("    end".to_string());
+    }
+    lines.extend(
+        nodes
+            .iter()
+            .filter(|(node, _)| frame_of(node).is_none())
+            .map(|(node, _)| format!("    {}", declaration(node))),
+    );
+    lines.extend(links.iter().map(|link| format!("    {}", link)));
+
+    MermaidReport {
+        mermaid: lines.join("\n") + "\n",
+        nodes: nodes.len(),
+        edges: links.len(),
+        skipped,
+    }
+}
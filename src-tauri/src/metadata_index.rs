@@ -0,0 +1,390 @@
+use regex::Regex;
+use rusqlite::{params, Connection};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::{LazyLock, Mutex};
+
+use crate::error::{AppError, ResultExt};
+use crate::paths;
+use crate::render;
+use crate::storage::EntryMetadata;
+
+/// The index database, in the app data folder
+pub const DB_FILE: &str = "metadata-index.sqlite3";
+
+/// Bumped when the tables change; an index of another version is rebuilt
+const SCHEMA_VERSION: i32 = 1;
+
+const SCHEMA: &str = "
+    CREATE TABLE files (
+        path TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        modified_at INTEGER,
+        element_count INTEGER NOT NULL,
+        element_types TEXT NOT NULL,
+        text TEXT NOT NULL,
+        search_text TEXT NOT NULL,
+        thumbnail_hash TEXT NOT NULL
+    );
+    CREATE TABLE tags (
+        path TEXT NOT NULL REFERENCES files(path) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (path, tag)
+    );
+    CREATE INDEX tags_by_tag ON tags(tag);
+";
+
+/// Longest stretch of text shown around a search match
+const SNIPPET_CHARS: usize = 80;
+
+/// `#tag` in text, as in Obsidian: letters, digits, `_`, `-` and `/`, not
+/// only digits
+static TAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|\s)#([\p{L}\p{N}_/-]*[\p{L}_/-][\p{L}\p{N}_/-]*)").expect("valid tag pattern")
+});
+
+/// What the index knows about one drawing
+#[derive(Debug, Clone, Default)]
+pub struct IndexedFile {
+    /// Normalized path, as `paths::normalize` writes it
+    pub path: String,
+    pub name: String,
+    pub size: u64,
+    pub modified_at: Option<i64>,
+    /// Elements not deleted, by type
+    pub element_types: BTreeMap<String, u64>,
+    /// Lowercase `#tags` of the text, without `#`
+    pub tags: Vec<String>,
+    /// Text elements and frame names, one per line
+    pub text: String,
+    /// Changes whenever the drawing would render differently
+    pub thumbnail_hash: String,
+}
+
+/// Answer of `search_drawings`
+#[derive(Debug, Serialize, Clone)]
+pub struct SearchHit {
+    pub path: String,
+    pub name: String,
+    /// Text around the first match inside the drawing, if the match is there
+    pub snippet: Option<String>,
+    pub tags: Vec<String>,
+    pub modified_at: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct TagCount {
+    pub tag: String,
+    pub files: u64,
+}
+
+/// Totals over the indexed drawings of a folder, part of `get_vault_stats`
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct VaultStatistics {
+    pub files: u64,
+    pub total_size: u64,
+    pub elements: u64,
+    pub element_types: BTreeMap<String, u64>,
+    /// Most used first
+    pub tags: Vec<TagCount>,
+    pub last_modified_at: Option<i64>,
+}
+
+/// Reads what the index stores from a drawing's content; content that
+/// isn't a scene still gets a row, without elements
+pub fn extract(path: &Path, content: &str, metadata: &EntryMetadata) -> IndexedFile {
+    let mut file = IndexedFile {
+        path: paths::normalize(path),
+        name: path
+            .file_name()
+            .map(|n| paths::compose(&n.to_string_lossy()))
+            .unwrap_or_default(),
+        size: metadata.size,
+        modified_at: metadata.modified_at,
+        ..Default::default()
+    };
+
+    let scene: Value = serde_json::from_str(content).unwrap_or(Value::Null);
+    let elements = render::visible_elements(&scene);
+    let mut lines = Vec::new();
+    for element in &elements {
+        let kind = element.get("type").and_then(|t| t.as_str()).unwrap_or("unknown");
+        *file.element_types.entry(kind.to_string()).or_default() += 1;
+        let text = match kind {
+            "text" => element
+                .get("originalText")
+                .or_else(|| element.get("text"))
+                .and_then(|t| t.as_str()),
+            "frame" | "magicframe" => element.get("name").and_then(|n| n.as_str()),
+            _ => None,
+        };
+        if let Some(text) = text.map(str::trim).filter(|t| !t.is_empty()) {
+            lines.push(text);
+        }
+    }
+    file.text = lines.join("\n");
+
+    let mut tags: Vec<String> = TAG
+        .captures_iter(&file.text)
+        .map(|c| c[1].to_lowercase())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    file.tags = tags;
+
+    let mut hasher = Sha256::new();
+    for element in &elements {
+        hasher.update(element.to_string());
+    }
+    if let Some(background) = scene.pointer("/appState/viewBackgroundColor") {
+        hasher.update(background.to_string());
+    }
+    file.thumbnail_hash = hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    file
+}
+
+/// `root` with a trailing `/`, so prefixes only match whole path components
+fn folder_prefix(root: &str) -> String {
+    if root.ends_with('/') {
+        root.to_string()
+    } else {
+        format!("{}/", root)
+    }
+}
+
+/// Up to `SNIPPET_CHARS` of the line of `text` containing `term`
+fn snippet(text: &str, term: &str) -> Option<String> {
+    let line = text.lines().find(|l| l.to_lowercase().contains(term))?;
+    let lower = line.to_lowercase();
+    let at = lower.find(term).map_or(0, |byte| lower[..byte].chars().count());
+    let chars: Vec<char> = line.chars().collect();
+    let start = at.saturating_sub(SNIPPET_CHARS / 4).min(chars.len());
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+    let mut snippet: String = chars[start..end].iter().collect();
+    if start > 0 {
+        snippet.insert(0, '…');
+    }
+    if end < chars.len() {
+        snippet.push('…');
+    }
+    Some(snippet)
+}
+
+fn db_error(e: rusqlite::Error) -> AppError {
+    AppError::Internal(format!("Metadata index error: {}", e))
+}
+
+/// Metadata of every drawing in the watched vaults, kept in SQLite so
+/// quick open, search and statistics don't walk the file system. Without a
+/// database every query fails and callers scan instead.
+#[derive(Default)]
+pub struct MetadataIndex {
+    db: Option<Mutex<Connection>>,
+    /// Roots indexed completely during this session
+    indexed: Mutex<HashSet<String>>,
+}
+
+impl MetadataIndex {
+    /// Opens or creates the database at `file`
+    pub fn open(file: &Path) -> Result<Self, AppError> {
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+        }
+        let connection = Connection::open(file).map_err(db_error)?;
+        connection
+            .execute_batch("PRAGMA journal_mode = WAL; PRAGMA foreign_keys = ON;")
+            .map_err(db_error)?;
+        let version: i32 = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .map_err(db_error)?;
+        if version != SCHEMA_VERSION {
+            // Only a cache of the drawings, so start over rather than migrate
+            connection
+                .execute_batch(&format!(
+                    "DROP TABLE IF EXISTS tags; DROP TABLE IF EXISTS files; {} PRAGMA user_version = {};",
+                    SCHEMA, SCHEMA_VERSION
+                ))
+                .map_err(db_error)?;
+        }
+        Ok(Self {
+            db: Some(Mutex::new(connection)),
+            indexed: Mutex::default(),
+        })
+    }
+
+    fn with<T>(&self, query: impl FnOnce(&mut Connection) -> rusqlite::Result<T>) -> Result<T, AppError> {
+        let db = self.db.as_ref()
+            .ok_or_else(|| AppError::Internal("The metadata index is not available".to_string()))?;
+        query(&mut db.lock().unwrap()).map_err(db_error)
+    }
+
+    /// Whether every drawing below `root` has been indexed this session
+    pub fn is_indexed(&self, root: &str) -> bool {
+        self.db.is_some() && self.indexed.lock().unwrap().contains(root)
+    }
+
+    pub fn mark_indexed(&self, root: &str) {
+        self.indexed.lock().unwrap().insert(root.to_string());
+    }
+
+    /// Forgets that `root` was indexed, so the next query indexes it again
+    pub fn mark_stale(&self, root: &str) {
+        self.indexed.lock().unwrap().remove(root);
+    }
+
+    pub fn upsert(&self, files: &[IndexedFile]) -> Result<(), AppError> {
+        self.with(|db| {
+            let tx = db.transaction()?;
+            for file in files {
+                let element_count: u64 = file.element_types.values().sum();
+                let element_types = serde_json::to_string(&file.element_types).unwrap_or_default();
+                let search_text = format!("{}\n{}\n{}", file.name, file.text, file.tags.join(" ")).to_lowercase();
+                tx.execute("DELETE FROM files WHERE path = ?1", params![file.path])?;
+                tx.execute(
+                    "INSERT INTO files (path, name, size, modified_at, element_count, element_types, text, search_text, thumbnail_hash)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        file.path,
+                        file.name,
+                        file.size,
+                        file.modified_at,
+                        element_count,
+                        element_types,
+                        file.text,
+                        search_text,
+                        file.thumbnail_hash,
+                    ],
+                )?;
+                for tag in &file.tags {
+                    tx.execute("INSERT INTO tags (path, tag) VALUES (?1, ?2)", params![file.path, tag])?;
+                }
+            }
+            tx.commit()
+        })
+    }
+
+    /// Drops `path` and everything below it
+    pub fn remove_under(&self, path: &str) -> Result<(), AppError> {
+        let prefix = folder_prefix(path);
+        self.with(|db| {
+            db.execute(
+                "DELETE FROM files WHERE path = ?1 OR substr(path, 1, length(?2)) = ?2",
+                params![path, prefix],
+            )
+            .map(|_| ())
+        })
+    }
+
+    /// Size and modification time of each indexed drawing below `root`
+    pub fn stamps_under(&self, root: &str) -> Result<HashMap<String, (u64, Option<i64>)>, AppError> {
+        let prefix = folder_prefix(root);
+        self.with(|db| {
+            let mut statement =
+                db.prepare("SELECT path, size, modified_at FROM files WHERE substr(path, 1, length(?1)) = ?1")?;
+            let rows = statement.query_map(params![prefix], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?;
+            rows.collect()
+        })
+    }
+
+    /// Path and name of every drawing below `root`
+    pub fn files_under(&self, root: &str) -> Result<Vec<(String, String)>, AppError> {
+        let prefix = folder_prefix(root);
+        self.with(|db| {
+            let mut statement = db.prepare("SELECT path, name FROM files WHERE substr(path, 1, length(?1)) = ?1")?;
+            let rows = statement.query_map(params![prefix], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect()
+        })
+    }
+
+    /// Drawings below `root` whose name, text or tags contain every word of
+    /// `query`; name matches first, then the most recently modified
+    pub fn search(&self, root: &str, query: &str, limit: usize) -> Result<Vec<SearchHit>, AppError> {
+        let terms: Vec<String> = query.split_whitespace().map(|t| t.trim_start_matches('#').to_lowercase()).collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+        let prefix = folder_prefix(root);
+        // search_text holds the lowercase name, text and tags
+        let mut sql = "SELECT path, name, text, modified_at,
+                              (SELECT group_concat(tag, ' ') FROM tags WHERE tags.path = files.path)
+                       FROM files
+                       WHERE substr(path, 1, length(?1)) = ?1"
+            .to_string();
+        for i in 0..terms.len() {
+            sql.push_str(&format!(" AND instr(search_text, ?{}) > 0", i + 2));
+        }
+        // Hits come back with their text, for the snippet
+        let rows: Vec<(SearchHit, String)> = self.with(|db| {
+            let mut statement = db.prepare(&sql)?;
+            let values = std::iter::once(&prefix).chain(&terms);
+            let rows = statement.query_map(rusqlite::params_from_iter(values), |row| {
+                let tags: Option<String> = row.get(4)?;
+                let hit = SearchHit {
+                    path: row.get(0)?,
+                    name: row.get(1)?,
+                    snippet: None,
+                    tags: tags.map(|t| t.split(' ').map(str::to_string).collect()).unwrap_or_default(),
+                    modified_at: row.get(3)?,
+                };
+                Ok((hit, row.get(2)?))
+            })?;
+            rows.collect()
+        })?;
+
+        let mut hits: Vec<(bool, SearchHit)> = rows
+            .into_iter()
+            .map(|(mut hit, text)| {
+                let name = hit.name.to_lowercase();
+                hit.snippet = terms.iter().find_map(|term| snippet(&text, term));
+                (terms.iter().all(|term| name.contains(term.as_str())), hit)
+            })
+            .collect();
+        hits.sort_by(|(a_name, a), (b_name, b)| b_name.cmp(a_name).then(b.modified_at.cmp(&a.modified_at)));
+        Ok(hits.into_iter().take(limit).map(|(_, hit)| hit).collect())
+    }
+
+    /// Totals over the drawings below `root`
+    pub fn statistics(&self, root: &str) -> Result<VaultStatistics, AppError> {
+        let prefix = folder_prefix(root);
+        self.with(|db| {
+            let mut stats = VaultStatistics::default();
+            let mut statement =
+                db.prepare("SELECT size, modified_at, element_count, element_types FROM files WHERE substr(path, 1, length(?1)) = ?1")?;
+            let mut rows = statement.query(params![prefix])?;
+            while let Some(row) = rows.next()? {
+                let (size, modified_at, elements, types): (u64, Option<i64>, u64, String) =
+                    (row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?);
+                stats.files += 1;
+                stats.total_size += size;
+                stats.elements += elements;
+                stats.last_modified_at = stats.last_modified_at.max(modified_at);
+                let types: BTreeMap<String, u64> = serde_json::from_str(&types).unwrap_or_default();
+                for (kind, count) in types {
+                    *stats.element_types.entry(kind).or_default() += count;
+                }
+            }
+
+            let mut statement = db.prepare(
+                "SELECT tag, count(*) AS files FROM tags
+                 WHERE substr(path, 1, length(?1)) = ?1
+                 GROUP BY tag ORDER BY files DESC, tag",
+            )?;
+            let tags = statement.query_map(params![prefix], |row| {
+                Ok(TagCount {
+                    tag: row.get(0)?,
+                    files: row.get(1)?,
+                })
+            })?;
+            stats.tags = tags.collect::<rusqlite::Result<_>>()?;
+            Ok(stats)
+        })
+    }
+}
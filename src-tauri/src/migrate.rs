@@ -0,0 +1,220 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::repair::{self, IdSource};
+
+/// Sent by `read_file` when it upgraded a drawing in memory
+pub const MIGRATED_EVENT: &str = "legacy-file-migrated";
+
+/// Scene format version written by current Excalidraw
+const CURRENT_VERSION: u64 = 2;
+
+/// Element properties every scene since version 2 has; their absence marks
+/// a file written before them
+const CORE_PROPERTIES: &[&str] = &["seed", "versionNonce", "groupIds", "isDeleted"];
+
+/// A legacy scene brought up to the current schema
+#[derive(Debug, Clone)]
+pub struct Migration {
+    /// The upgraded scene as pretty-printed JSON
+    pub content: String,
+    /// The `version` the file declared, if any
+    pub from_version: Option<u64>,
+    /// Human-readable description of every change
+    pub changes: Vec<String>,
+}
+
+/// Payload of `legacy-file-migrated`
+#[derive(Debug, Serialize, Clone)]
+pub struct MigrationNotice {
+    pub path: String,
+    pub from_version: Option<u64>,
+    pub changes: Vec<String>,
+}
+
+fn is_legacy_element(element: &Value) -> bool {
+    let Some(element) = element.as_object() else {
+        return true;
+    };
+    let kind = element.get("type").and_then(|t| t.as_str()).unwrap_or_default();
+    matches!(kind, "draw" | "selection")
+        || element.contains_key("strokeSharpness")
+        || element.contains_key("boundElementIds")
+        || CORE_PROPERTIES.iter().any(|key| !element.contains_key(*key))
+        || (kind == "text" && !element.contains_key("originalText"))
+}
+
+fn is_legacy(scene: &Map<String, Value>, elements: &[Value]) -> bool {
+    let version = scene.get("version").and_then(|v| v.as_u64());
+    let app_state = scene.get("appState").and_then(|a| a.as_object());
+    version.is_none_or(|v| v < CURRENT_VERSION)
+        || scene.get("type").and_then(|t| t.as_str()) != Some("excalidraw")
+        || app_state.is_none_or(|a| !a.contains_key("viewBackgroundColor"))
+        || elements.iter().any(is_legacy_element)
+}
+
+/// `strokeSharpness: "round"` became a roundness whose kind depends on the shape
+fn roundness(kind: &str, sharpness: &Value) -> Value {
+    match sharpness.as_str() {
+        // Adaptive radius for rectangles, proportional for the rest
+        Some("round") if kind == "rectangle" => json!({"type": 3}),
+        Some("round") => json!({"type": 2}),
+        _ => Value::Null,
+    }
+}
+
+/// Upgrades one element in place; returns what it changed besides filling
+/// style defaults, and whether defaults were filled
+fn migrate_element(
+    element: &mut Map<String, Value>,
+    index: usize,
+    kinds: &HashMap<String, String>,
+    ids: &mut IdSource,
+) -> (Vec<String>, bool) {
+    let mut changes = Vec::new();
+
+    // Freehand lines were "draw" elements before freedraw existed
+    if element.get("type").and_then(|t| t.as_str()) == Some("draw") {
+        element.insert("type".to_string(), "line".into());
+        changes.push(format!("Converted legacy draw element #{} to a line", index));
+    }
+    let kind = element
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    if let Some(sharpness) = element.remove("strokeSharpness")
+        && element.get("roundness").is_none_or(|r| r.is_null())
+    {
+        element.insert("roundness".to_string(), roundness(&kind, &sharpness));
+        changes.push(format!("Replaced strokeSharpness on element #{} ({})", index, kind));
+    }
+
+    if let Some(Value::Array(bound_ids)) = element.remove("boundElementIds") {
+        let mut bound: Vec<Value> = element
+            .get("boundElements")
+            .and_then(|b| b.as_array())
+            .cloned()
+            .unwrap_or_default();
+        for id in bound_ids.iter().filter_map(|id| id.as_str()) {
+            if bound.iter().any(|b| b.get("id").and_then(|i| i.as_str()) == Some(id)) {
+                continue;
+            }
+            let bound_kind = kinds.get(id).map(String::as_str).unwrap_or("arrow");
+            bound.push(json!({"id": id, "type": bound_kind}));
+        }
+        element.insert("boundElements".to_string(), Value::Array(bound));
+        changes.push(format!("Replaced boundElementIds on element #{} ({})", index, kind));
+    }
+
+    let filled = repair::repair_element(element, &kind, ids);
+    let (core, defaults): (Vec<&str>, Vec<&str>) = filled
+        .into_iter()
+        .partition(|key| CORE_PROPERTIES.contains(key) || *key == "originalText");
+    if !core.is_empty() {
+        changes.push(format!(
+            "Added missing {} on element #{} ({})",
+            core.join(", "),
+            index,
+            kind
+        ));
+    }
+    (changes, !defaults.is_empty())
+}
+
+/// Upgrades a scene written by an old Excalidraw: renames deprecated element
+/// types and properties and fills the fields newer versions expect. `None`
+/// when the scene is current, or isn't a scene at all.
+pub fn migrate(content: &str) -> Option<Migration> {
+    let Ok(Value::Object(mut scene)) = serde_json::from_str::<Value>(content) else {
+        return None;
+    };
+    let elements = scene.get("elements")?.as_array()?;
+    if !is_legacy(&scene, elements) {
+        return None;
+    }
+
+    let from_version = scene.get("version").and_then(|v| v.as_u64());
+    let mut changes = Vec::new();
+    if from_version != Some(CURRENT_VERSION) {
+        changes.push(format!(
+            "Upgraded scene version {} to {}",
+            from_version.map_or("(missing)".to_string(), |v| v.to_string()),
+            CURRENT_VERSION
+        ));
+        scene.insert("version".to_string(), CURRENT_VERSION.into());
+    }
+    if scene.get("type").and_then(|t| t.as_str()) != Some("excalidraw") {
+        scene.insert("type".to_string(), "excalidraw".into());
+        changes.push("Set scene type to \"excalidraw\"".to_string());
+    }
+
+    if !scene.get("appState").is_some_and(|a| a.is_object()) {
+        scene.insert("appState".to_string(), Value::Object(Map::new()));
+    }
+    if let Some(app_state) = scene.get_mut("appState").and_then(|a| a.as_object_mut()) {
+        if !app_state.contains_key("viewBackgroundColor") {
+            app_state.insert("viewBackgroundColor".to_string(), "#ffffff".into());
+            changes.push("Added missing background color".to_string());
+        }
+        if let Some(sharpness) = app_state.remove("currentItemStrokeSharpness") {
+            let roundness = if sharpness.as_str() == Some("round") { "round" } else { "sharp" };
+            app_state.insert("currentItemRoundness".to_string(), roundness.into());
+            changes.push("Replaced currentItemStrokeSharpness in the app state".to_string());
+        }
+    }
+    if !scene.get("files").is_some_and(|f| f.is_object()) {
+        scene.insert("files".to_string(), Value::Object(Map::new()));
+    }
+
+    let Some(Value::Array(elements)) = scene.get_mut("elements") else {
+        return None;
+    };
+    let kinds: HashMap<String, String> = elements
+        .iter()
+        .filter_map(|e| {
+            let kind = match e.get("type")?.as_str()? {
+                "text" => "text",
+                _ => "arrow",
+            };
+            Some((e.get("id")?.as_str()?.to_string(), kind.to_string()))
+        })
+        .collect();
+
+    let mut ids = IdSource::new();
+    let mut migrated = Vec::with_capacity(elements.len());
+    let mut default_fills = 0;
+    for (index, element) in elements.drain(..).enumerate() {
+        let Value::Object(mut element) = element else {
+            changes.push(format!("Dropped element #{}: not an object", index));
+            continue;
+        };
+        // Selection boxes were saved by mistake in some old versions
+        if element.get("type").and_then(|t| t.as_str()) == Some("selection") {
+            changes.push(format!("Dropped leftover selection element #{}", index));
+            continue;
+        }
+        let (element_changes, filled_defaults) = migrate_element(&mut element, index, &kinds, &mut ids);
+        changes.extend(element_changes);
+        if filled_defaults {
+            default_fills += 1;
+        }
+        migrated.push(Value::Object(element));
+    }
+    *elements = migrated;
+    if default_fills > 0 {
+        changes.push(format!(
+            "Filled default style properties on {} element(s)",
+            default_fills
+        ));
+    }
+
+    let content = serde_json::to_string_pretty(&Value::Object(scene)).ok()?;
+    Some(Migration {
+        content,
+        from_version,
+        changes,
+    })
+}
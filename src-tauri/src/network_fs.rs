@@ -0,0 +1,80 @@
+use std::path::Path;
+
+/// Filesystem types whose changes native watchers don't hear about: the
+/// server, or another client, makes them
+const NETWORK_TYPES: &[&str] = &[
+    "cifs", "smb3", "smbfs", "nfs", "nfs4", "afpfs", "webdav", "davfs", "fuse.sshfs", "sshfs", "9p", "afs",
+    "ceph", "glusterfs", "fuse.rclone",
+];
+
+pub fn is_network_type(fs_type: &str) -> bool {
+    NETWORK_TYPES.contains(&fs_type.to_ascii_lowercase().as_str())
+}
+
+/// Whether `path` lives on a network share; false when it can't be told
+pub fn is_network_path(path: &Path) -> bool {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if cfg!(windows) {
+        // `\\server\share`, also as `\\?\UNC\server\share` once canonicalized
+        let s = path.to_string_lossy();
+        return s.starts_with(r"\\?\UNC\") || (s.starts_with(r"\\") && !s.starts_with(r"\\?\"));
+    }
+    mount_table()
+        .and_then(|mounts| filesystem_type(&mounts, &path))
+        .is_some_and(|fs_type| is_network_type(&fs_type))
+}
+
+/// `(mount point, filesystem type)` of every mount
+#[cfg(target_os = "linux")]
+fn mount_table() -> Option<Vec<(String, String)>> {
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    Some(parse_proc_mounts(&mounts))
+}
+
+#[cfg(target_os = "macos")]
+fn mount_table() -> Option<Vec<(String, String)>> {
+    let output = std::process::Command::new("/sbin/mount").output().ok()?;
+    Some(parse_bsd_mounts(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn mount_table() -> Option<Vec<(String, String)>> {
+    None
+}
+
+/// Lines of `/proc/self/mounts`: `source mountpoint type options ...`, with
+/// spaces in paths written as `\040`
+#[cfg(target_os = "linux")]
+fn parse_proc_mounts(mounts: &str) -> Vec<(String, String)> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mount_point = fields.nth(1)?.replace("\\040", " ");
+            Some((mount_point, fields.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// Lines of `mount` on macOS: `source on /mount/point (type, options...)`
+#[cfg(target_os = "macos")]
+fn parse_bsd_mounts(mounts: &str) -> Vec<(String, String)> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let (_, rest) = line.split_once(" on ")?;
+            let (mount_point, details) = rest.rsplit_once(" (")?;
+            let fs_type = details.split([',', ')']).next()?.trim();
+            Some((mount_point.to_string(), fs_type.to_string()))
+        })
+        .collect()
+}
+
+/// Type of the innermost mount containing `path`
+fn filesystem_type(mounts: &[(String, String)], path: &Path) -> Option<String> {
+    mounts
+        .iter()
+        .filter(|(mount_point, _)| path.starts_with(mount_point))
+        .max_by_key(|(mount_point, _)| mount_point.len())
+        .map(|(_, fs_type)| fs_type.clone())
+}
@@ -0,0 +1,76 @@
+use serde_json::{Map, Value};
+
+use crate::error::AppError;
+
+/// `appState` entries describing a session rather than the drawing: who else
+/// is connected, what is selected or being edited, which menus are open and
+/// where the view is scrolled to
+const VOLATILE_APP_STATE: &[&str] = &[
+    "collaborators",
+    "selectedElementIds",
+    "selectedGroupIds",
+    "selectedLinearElement",
+    "hoveredElementIds",
+    "previousSelectedElementIds",
+    "editingGroupId",
+    "editingElement",
+    "editingLinearElement",
+    "editingTextElement",
+    "editingFrame",
+    "cursorButton",
+    "openMenu",
+    "openPopup",
+    "openSidebar",
+    "openDialog",
+    "contextMenu",
+    "scrollX",
+    "scrollY",
+    "zoom",
+];
+
+/// Rewrites a scene so saving the same drawing always gives the same text:
+/// object keys sorted, session-only `appState` entries dropped and the
+/// result pretty printed with a final newline. Elements are ordered by
+/// their fractional `index` (their stacking order), then id, when all of
+/// them have one; otherwise their order is the stacking order and is kept.
+pub fn normalize(content: &str) -> Result<String, AppError> {
+    let mut scene: Value = serde_json::from_str(content)?;
+
+    if let Some(app_state) = scene.get_mut("appState").and_then(|s| s.as_object_mut()) {
+        app_state.retain(|key, _| !VOLATILE_APP_STATE.contains(&key.as_str()));
+    }
+
+    if let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut())
+        && elements.iter().all(|e| e.get("index").is_some_and(Value::is_string))
+    {
+        let key = |e: &Value| {
+            let field = |name| e.get(name).and_then(Value::as_str).unwrap_or_default().to_string();
+            (field("index"), field("id"))
+        };
+        elements.sort_by_cached_key(key);
+    }
+
+    let mut normalized = serde_json::to_string_pretty(&sort_keys(scene))
+        .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+    normalized.push('\n');
+    Ok(normalized)
+}
+
+/// `value` with the keys of every object in it in sorted order, whether or
+/// not `serde_json` keeps insertion order
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sort_keys(value)))
+                    .collect::<Map<String, Value>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
@@ -0,0 +1,221 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::compression;
+use crate::export::{fnv1a, FNV_OFFSET};
+
+/// Emitted to a window when the drawing it has open changes on disk through
+/// something other than the window's own saves
+pub const CHANGED_EVENT: &str = "open-file-changed-externally";
+
+/// The open drawing as the app last saw it
+#[derive(Debug, Clone, Default)]
+struct Snapshot {
+    exists: bool,
+    /// `None` after our own writes, so the next event compares by content
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+    /// Hash of every live element by id; `None` when the file is not a scene
+    elements: Option<HashMap<String, u64>>,
+}
+
+impl Snapshot {
+    fn of(content: &str) -> Self {
+        Self {
+            exists: true,
+            modified: None,
+            len: content.len() as u64,
+            hash: fnv1a(content.as_bytes(), FNV_OFFSET),
+            elements: element_hashes(content),
+        }
+    }
+
+    fn read(path: &Path) -> Self {
+        let (Ok(content), Ok(metadata)) = (compression::read_to_string(path), fs::metadata(path)) else {
+            return Self::default();
+        };
+        // Compressed drawings are shorter on disk than their content
+        Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            ..Self::of(&content)
+        }
+    }
+}
+
+fn element_hashes(content: &str) -> Option<HashMap<String, u64>> {
+    let scene: Value = serde_json::from_str(content).ok()?;
+    let elements = scene.get("elements")?.as_array()?;
+    Some(
+        elements
+            .iter()
+            .filter(|e| e.get("isDeleted").and_then(Value::as_bool) != Some(true))
+            .filter_map(|e| {
+                let id = e.get("id")?.as_str()?;
+                Some((id.to_string(), fnv1a(e.to_string().as_bytes(), FNV_OFFSET)))
+            })
+            .collect(),
+    )
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Modified,
+    Deleted,
+}
+
+/// Element counts between the version the window has and the one on disk
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SceneDiff {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+    /// Live elements in the file on disk
+    pub total: usize,
+}
+
+impl SceneDiff {
+    fn between(old: &HashMap<String, u64>, new: &HashMap<String, u64>) -> Self {
+        let mut diff = Self {
+            total: new.len(),
+            ..Self::default()
+        };
+        for (id, hash) in new {
+            match old.get(id) {
+                None => diff.added += 1,
+                Some(previous) if previous != hash => diff.changed += 1,
+                Some(_) => {}
+            }
+        }
+        diff.removed = old.keys().filter(|id| !new.contains_key(*id)).count();
+        diff
+    }
+}
+
+/// Payload of `CHANGED_EVENT`
+#[derive(Debug, Serialize, Clone)]
+pub struct OpenFileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    /// Missing when either version is not a readable scene
+    pub diff: Option<SceneDiff>,
+    /// Milliseconds since the Unix epoch; 0 for deleted files
+    pub modified_at: i64,
+}
+
+struct WatchedFile {
+    path: PathBuf,
+    /// Path as the frontend knows it
+    external: String,
+    snapshot: Snapshot,
+    /// Watches the parent folder, so saves that replace the file are seen;
+    /// dropping it ends the event thread
+    _watcher: Box<dyn notify::Watcher + Send>,
+}
+
+/// The drawing each window has open, keyed by window label
+#[derive(Default)]
+pub struct OpenFileWatches {
+    files: Mutex<HashMap<String, WatchedFile>>,
+}
+
+impl OpenFileWatches {
+    /// Starts tracking `path` for window `label` from its current content,
+    /// replacing what the window watched before
+    pub fn watch(&self, label: &str, path: &Path, external: String, watcher: Box<dyn notify::Watcher + Send>) {
+        let file = WatchedFile {
+            path: path.to_path_buf(),
+            external,
+            snapshot: Snapshot::read(path),
+            _watcher: watcher,
+        };
+        self.files.lock().unwrap().insert(label.to_string(), file);
+    }
+
+    pub fn unwatch(&self, label: &str) -> bool {
+        self.files.lock().unwrap().remove(label).is_some()
+    }
+
+    /// Whether some window has the drawing at `path` open
+    pub fn is_open(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().values().any(|file| file.path == path)
+    }
+
+    /// Records content the app itself is about to write to `path`, so the
+    /// resulting file events are not reported back as external changes
+    pub fn note_write(&self, path: &Path, content: &str) {
+        for file in self.files.lock().unwrap().values_mut() {
+            if file.path == path {
+                file.snapshot = Snapshot::of(content);
+            }
+        }
+    }
+
+    /// Follows a drawing the app itself moved from `from` to `to` with
+    /// `content`, which would otherwise be reported as deleted
+    pub fn moved(&self, from: &Path, to: &Path, external: String, content: &str) {
+        for file in self.files.lock().unwrap().values_mut() {
+            if file.path == from {
+                file.path = to.to_path_buf();
+                file.external = external.clone();
+                file.snapshot = Snapshot::of(content);
+            }
+        }
+    }
+
+    /// Compares the file window `label` watches with the last seen version;
+    /// a change is reported once and becomes the new baseline
+    pub fn check(&self, label: &str) -> Option<OpenFileChange> {
+        let mut files = self.files.lock().unwrap();
+        let file = files.get_mut(label)?;
+
+        let metadata = fs::metadata(&file.path).ok();
+        if let Some(metadata) = &metadata {
+            let modified = metadata.modified().ok();
+            if file.snapshot.exists
+                && modified.is_some()
+                && modified == file.snapshot.modified
+                && metadata.len() == file.snapshot.len
+            {
+                return None;
+            }
+        }
+
+        let current = match metadata {
+            Some(_) => Snapshot::read(&file.path),
+            None => Snapshot::default(),
+        };
+        let previous = std::mem::replace(&mut file.snapshot, current);
+        let current = &file.snapshot;
+
+        let kind = match (previous.exists, current.exists) {
+            (true, false) => ChangeKind::Deleted,
+            (false, false) => return None,
+            (true, true) if previous.hash == current.hash => return None,
+            _ => ChangeKind::Modified,
+        };
+        let diff = match (kind, &previous.elements, &current.elements) {
+            (ChangeKind::Modified, Some(old), Some(new)) => Some(SceneDiff::between(old, new)),
+            _ => None,
+        };
+        let modified_at = current
+            .modified
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+
+        Some(OpenFileChange {
+            path: file.external.clone(),
+            kind,
+            diff,
+            modified_at,
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter};
+
+use crate::error::AppError;
+
+/// Emitted to the window that started an operation while it works
+pub const PROGRESS_EVENT: &str = "operation-progress";
+
+/// Payload of `PROGRESS_EVENT`
+#[derive(Debug, Serialize, Clone)]
+pub struct OperationProgress {
+    pub id: String,
+    pub done: usize,
+    pub total: usize,
+    /// What was just handled, usually a path
+    pub message: String,
+}
+
+/// Cancel flags of the operations running under an id the frontend chose
+#[derive(Default)]
+pub struct Operations {
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl Operations {
+    /// Registers an operation; without an id it reports nothing and can't
+    /// be cancelled
+    pub fn start(&self, app: &AppHandle, label: &str, id: Option<String>) -> Result<Operation<'_>, AppError> {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        if let Some(id) = &id {
+            let mut running = self.running.lock().unwrap();
+            if running.contains_key(id) {
+                return Err(AppError::AlreadyExists(format!("Operation {} is already running", id)));
+            }
+            running.insert(id.clone(), cancelled.clone());
+        }
+        Ok(Operation {
+            id,
+            app: app.clone(),
+            label: label.to_string(),
+            cancelled,
+            operations: self,
+        })
+    }
+
+    /// Asks a running operation to stop at its next step; returns whether
+    /// one was running under `id`
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.running.lock().unwrap().get(id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A running long operation; unregistered when dropped
+pub struct Operation<'a> {
+    id: Option<String>,
+    app: AppHandle,
+    label: String,
+    cancelled: Arc<AtomicBool>,
+    operations: &'a Operations,
+}
+
+impl Operation<'_> {
+    pub fn progress(&self, done: usize, total: usize, message: impl Into<String>) {
+        let Some(id) = &self.id else {
+            return;
+        };
+        let progress = OperationProgress {
+            id: id.clone(),
+            done,
+            total,
+            message: message.into(),
+        };
+        let _ = self.app.emit_to(self.label.as_str(), PROGRESS_EVENT, progress);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Fails once `cancel_operation` was called, for `?` between steps
+    pub fn check(&self) -> Result<(), AppError> {
+        if self.is_cancelled() {
+            return Err(AppError::Cancelled("Operation was cancelled".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Operation<'_> {
+    fn drop(&mut self) {
+        if let Some(id) = &self.id {
+            self.operations.running.lock().unwrap().remove(id);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+use crate::error::AppError;
+use crate::render::{self, Bounds};
+
+/// Axis-aligned rectangle in scene coordinates
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Region {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl From<Bounds> for Region {
+    fn from(bounds: Bounds) -> Self {
+        Region {
+            x: bounds.min_x,
+            y: bounds.min_y,
+            width: bounds.width(),
+            height: bounds.height(),
+        }
+    }
+}
+
+impl Region {
+    fn bounds(&self) -> Bounds {
+        Bounds {
+            min_x: self.x.min(self.x + self.width),
+            min_y: self.y.min(self.y + self.height),
+            max_x: self.x.max(self.x + self.width),
+            max_y: self.y.max(self.y + self.height),
+        }
+    }
+}
+
+/// Which part of the scene to load
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum SceneSelection {
+    /// A frame, looked up by element id first and by name second
+    Frame { frame: String },
+    /// Every element whose bounding box intersects the region
+    Region(Region),
+}
+
+/// Summary of one frame so the frontend can fetch the rest of the board lazily
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FrameOutline {
+    pub id: String,
+    pub name: Option<String>,
+    pub region: Region,
+    pub element_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartialScene {
+    /// Scene JSON containing only the selected elements and the files they use
+    pub content: String,
+    pub element_count: usize,
+    pub total_element_count: usize,
+    /// Bounds of the whole scene, for scroll extents
+    pub scene_region: Option<Region>,
+    pub frames: Vec<FrameOutline>,
+}
+
+fn id_of(element: &Value) -> &str {
+    element.get("id").and_then(|i| i.as_str()).unwrap_or("")
+}
+
+fn str_field<'a>(element: &'a Value, key: &str) -> Option<&'a str> {
+    element.get(key).and_then(|v| v.as_str()).filter(|v| !v.is_empty())
+}
+
+fn outline(elements: &[&Value]) -> Vec<FrameOutline> {
+    render::frames(elements)
+        .into_iter()
+        .map(|frame| FrameOutline {
+            id: id_of(frame).to_string(),
+            name: str_field(frame, "name").map(|n| n.to_string()),
+            region: render::element_bounds(frame).into(),
+            element_count: render::frame_children(elements, frame).len(),
+        })
+        .collect()
+}
+
+/// Extracts the selected part of a scene. Bound text and containers are pulled
+/// in together so labels never load without their shapes (or vice versa).
+pub fn read_partial(content: &str, selection: &SceneSelection) -> Result<PartialScene, AppError> {
+    let scene: Value = serde_json::from_str(content)?;
+    let elements = render::visible_elements(&scene);
+
+    let mut selected: HashSet<&str> = match selection {
+        SceneSelection::Frame { frame } => {
+            let frames = render::frames(&elements);
+            let target = frames
+                .iter()
+                .find(|f| id_of(f) == frame)
+                .or_else(|| frames.iter().find(|f| str_field(f, "name") == Some(frame.as_str())))
+                .ok_or_else(|| AppError::NotFound(format!("Frame not found: {}", frame)))?;
+            render::frame_children(&elements, target)
+                .into_iter()
+                .map(id_of)
+                .collect()
+        }
+        SceneSelection::Region(region) => {
+            let region = region.bounds();
+            elements
+                .iter()
+                .filter(|e| render::element_bounds(e).intersects(&region))
+                .map(|e| id_of(e))
+                .collect()
+        }
+    };
+
+    // Containers and their bound text travel together
+    let linked: Vec<&str> = elements
+        .iter()
+        .filter_map(|e| {
+            let container = str_field(e, "containerId")?;
+            match (selected.contains(id_of(e)), selected.contains(container)) {
+                (true, false) => Some(container),
+                (false, true) => Some(id_of(e)),
+                _ => None,
+            }
+        })
+        .collect();
+    selected.extend(linked);
+
+    // Keep the original stacking order
+    let partial: Vec<Value> = elements
+        .iter()
+        .filter(|e| selected.contains(id_of(e)))
+        .map(|e| (*e).clone())
+        .collect();
+
+    let file_ids: HashSet<&str> = partial.iter().filter_map(|e| str_field(e, "fileId")).collect();
+    let files: Map<String, Value> = scene
+        .get("files")
+        .and_then(|f| f.as_object())
+        .map(|files| {
+            files
+                .iter()
+                .filter(|(id, _)| file_ids.contains(id.as_str()))
+                .map(|(id, file)| (id.clone(), file.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let element_count = partial.len();
+    let mut result = scene.as_object().cloned().unwrap_or_default();
+    result.insert("elements".to_string(), Value::Array(partial));
+    result.insert("files".to_string(), Value::Object(files));
+
+    Ok(PartialScene {
+        content: serde_json::to_string(&Value::Object(result))
+            .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?,
+        element_count,
+        total_element_count: elements.len(),
+        scene_region: render::scene_bounds(&elements).map(Region::from),
+        frames: outline(&elements),
+    })
+}
@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Canonical compositions for the decomposed (NFD) sequences that macOS file
+/// systems hand back: `(combining mark, base characters, composed characters)`.
+/// Covers Latin-1/Latin Extended-A letters and Japanese kana; Hangul is
+/// composed algorithmically below.
+const COMPOSITIONS: &[(char, &str, &str)] = &[
+    ('\u{0300}', "AEIOUaeiou", "ÀÈÌÒÙàèìòù"),
+    ('\u{0301}', "ACEILNORSUYZaceilnorsuyz", "ÁĆÉÍĹŃÓŔŚÚÝŹáćéíĺńóŕśúýź"),
+    ('\u{0302}', "ACEGHIJOSUWYaceghijosuwy", "ÂĈÊĜĤÎĴÔŜÛŴŶâĉêĝĥîĵôŝûŵŷ"),
+    ('\u{0303}', "AINOUainou", "ÃĨÑÕŨãĩñõũ"),
+    ('\u{0304}', "AEIOUaeiou", "ĀĒĪŌŪāēīōū"),
+    ('\u{0306}', "AEGIOUaegiou", "ĂĔĞĬŎŬăĕğĭŏŭ"),
+    ('\u{0307}', "CEGIZcegz", "ĊĖĠİŻċėġż"),
+    ('\u{0308}', "AEIOUYaeiouy", "ÄËÏÖÜŸäëïöüÿ"),
+    ('\u{030A}', "AUau", "ÅŮåů"),
+    ('\u{030B}', "OUou", "ŐŰőű"),
+    ('\u{030C}', "CDELNRSTZcdelnrstz", "ČĎĚĽŇŘŠŤŽčďěľňřšťž"),
+    ('\u{0327}', "CGKLNRSTcgklnrst", "ÇĢĶĻŅŖŞŢçģķļņŗşţ"),
+    ('\u{0328}', "AEIUaeiu", "ĄĘĮŲąęįų"),
+    ('\u{3099}', "うかきくけこさしすせそたちつてとはひふへほゝウカキクケコサシスセソタチツテトハヒフヘホワヰヱヲヽ", "ゔがぎぐげござじずぜぞだぢづでどばびぶべぼゞヴガギグゲゴザジズゼゾダヂヅデドバビブベボヷヸヹヺヾ"),
+    ('\u{309A}', "はひふへほハヒフヘホ", "ぱぴぷぺぽパピプペポ"),
+];
+
+const HANGUL_S_BASE: u32 = 0xAC00;
+const HANGUL_L_BASE: u32 = 0x1100;
+const HANGUL_V_BASE: u32 = 0x1161;
+const HANGUL_T_BASE: u32 = 0x11A7;
+const HANGUL_L_COUNT: u32 = 19;
+const HANGUL_V_COUNT: u32 = 21;
+const HANGUL_T_COUNT: u32 = 28;
+
+/// A path in the form shared with the frontend: `path` for file operations and
+/// `relative_path` (relative to the open workspace) for comparisons and display
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct WorkspacePath {
+    pub path: String,
+    pub relative_path: Option<String>,
+}
+
+impl WorkspacePath {
+    pub fn new(root: Option<&Path>, path: &Path) -> Self {
+        WorkspacePath {
+            path: normalize(path),
+            relative_path: root.and_then(|root| relative(root, path)),
+        }
+    }
+}
+
+fn compose_pair(base: char, mark: char) -> Option<char> {
+    let (b, m) = (base as u32, mark as u32);
+
+    // Hangul leading consonant + vowel, and LV syllable + trailing consonant
+    if (HANGUL_L_BASE..HANGUL_L_BASE + HANGUL_L_COUNT).contains(&b)
+        && (HANGUL_V_BASE..HANGUL_V_BASE + HANGUL_V_COUNT).contains(&m)
+    {
+        let index = (b - HANGUL_L_BASE) * HANGUL_V_COUNT + (m - HANGUL_V_BASE);
+        return char::from_u32(HANGUL_S_BASE + index * HANGUL_T_COUNT);
+    }
+    let syllable_index = b.wrapping_sub(HANGUL_S_BASE);
+    if syllable_index < HANGUL_L_COUNT * HANGUL_V_COUNT * HANGUL_T_COUNT
+        && syllable_index % HANGUL_T_COUNT == 0
+        && (HANGUL_T_BASE + 1..HANGUL_T_BASE + HANGUL_T_COUNT).contains(&m)
+    {
+        return char::from_u32(b + (m - HANGUL_T_BASE));
+    }
+
+    let (_, bases, composed) = COMPOSITIONS.iter().find(|(m, _, _)| *m == mark)?;
+    let index = bases.chars().position(|c| c == base)?;
+    composed.chars().nth(index)
+}
+
+/// Composes NFD sequences into their precomposed (NFC) form where known
+pub fn compose(s: &str) -> String {
+    let mut output: Vec<char> = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        match output.last().and_then(|&last| compose_pair(last, c)) {
+            Some(composed) => *output.last_mut().unwrap() = composed,
+            None => output.push(c),
+        }
+    }
+    output.into_iter().collect()
+}
+
+/// Removes Windows verbatim prefixes added by `canonicalize`:
+/// `\\?\C:\x` becomes `C:\x` and `\\?\UNC\server\share` becomes `\\server\share`
+fn strip_verbatim(s: &str) -> String {
+    if let Some(rest) = s.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{}", rest)
+    } else if let Some(rest) = s.strip_prefix(r"\\?\") {
+        rest.to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+/// Canonical string form of a local path: no verbatim prefix, `/` separators,
+/// precomposed Unicode, no trailing separator
+pub fn normalize(path: &Path) -> String {
+    let mut s = strip_verbatim(&path.to_string_lossy());
+    // Backslashes are ordinary file name characters outside Windows
+    if cfg!(windows) {
+        s = s.replace('\\', "/");
+    }
+    let s = compose(&s);
+    match s.trim_end_matches('/') {
+        // Keep roots like `/` and `C:/` intact
+        "" => "/".to_string(),
+        trimmed if trimmed.ends_with(':') => format!("{}/", trimmed),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+/// `/`-separated path of `path` relative to `root`, or `None` if it lies outside
+pub fn relative(root: &Path, path: &Path) -> Option<String> {
+    let root = normalize(root);
+    let path = normalize(path);
+    if path == root {
+        return Some(String::new());
+    }
+    let prefix = if root.ends_with('/') {
+        root
+    } else {
+        format!("{}/", root)
+    };
+    path.strip_prefix(&prefix).map(|rest| rest.to_string())
+}
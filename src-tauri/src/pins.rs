@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+
+use crate::FileTreeNode;
+
+/// Pinned drawings of each workspace folder, in the order they were pinned
+pub type PinMap = HashMap<String, Vec<String>>;
+
+/// Returns whether `path` was not pinned yet
+pub fn pin(pins: &mut PinMap, workspace: &str, path: &str) -> bool {
+    let pinned = pins.entry(workspace.to_string()).or_default();
+    if pinned.iter().any(|p| p == path) {
+        return false;
+    }
+    pinned.push(path.to_string());
+    true
+}
+
+/// Returns whether `path` was pinned
+pub fn unpin(pins: &mut PinMap, workspace: &str, path: &str) -> bool {
+    let Some(pinned) = pins.get_mut(workspace) else {
+        return false;
+    };
+    let before = pinned.len();
+    pinned.retain(|p| p != path);
+    let removed = pinned.len() != before;
+    if pinned.is_empty() {
+        pins.remove(workspace);
+    }
+    removed
+}
+
+pub(crate) fn is_at_or_below(path: &str, prefix: &str) -> bool {
+    path == prefix
+        || path
+            .replace('\\', "/")
+            .starts_with(&format!("{}/", prefix.trim_end_matches(['/', '\\'])))
+}
+
+/// Follows a renamed or moved file, or every pin below a renamed or moved
+/// folder; returns whether any pin changed
+pub fn rename(pins: &mut PinMap, old_path: &str, new_path: &str) -> bool {
+    let mut changed = false;
+    for path in pins.values_mut().flatten() {
+        if is_at_or_below(path, old_path) {
+            *path = format!("{}{}", new_path, &path[old_path.len()..]);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Drops the pins of a deleted file or of everything in a deleted folder;
+/// returns whether any pin was dropped
+pub fn remove(pins: &mut PinMap, path: &str) -> bool {
+    let before: usize = pins.values().map(Vec::len).sum();
+    for pinned in pins.values_mut() {
+        pinned.retain(|p| !is_at_or_below(p, path));
+    }
+    pins.retain(|_, pinned| !pinned.is_empty());
+    pins.values().map(Vec::len).sum::<usize>() != before
+}
+
+/// Sets `pinned` on the nodes of pinned files and moves them to the top of
+/// their folder, keeping the order among them
+pub fn mark(tree: &mut [FileTreeNode], pinned: &[String]) {
+    if pinned.is_empty() {
+        return;
+    }
+    for node in tree.iter_mut() {
+        node.pinned = pinned.contains(&node.path);
+        if let Some(children) = node.children.as_mut() {
+            mark(children, pinned);
+        }
+    }
+    tree.sort_by_key(|node| !node.pinned);
+}
@@ -0,0 +1,162 @@
+use std::time::SystemTime;
+
+use crate::archive::ZipWriter;
+use crate::error::{AppError, ResultExt};
+use crate::render::escape_xml;
+use crate::slides::Slide;
+
+/// 16:9 slide size in EMU (914400 per inch)
+const SLIDE_WIDTH: i64 = 12_192_000;
+const SLIDE_HEIGHT: i64 = 6_858_000;
+const MARGIN: i64 = 457_200;
+const TITLE_HEIGHT: i64 = 731_520;
+
+/// Longest side of the PNG rendered for each slide, in pixels
+const MAX_IMAGE_SIDE: f32 = 3840.0;
+
+const NAMESPACES: &str = r#"xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships" xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main""#;
+const REL_TYPES: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+/// Marks the SVG PowerPoint 2016 and later show instead of the PNG
+const SVG_BLIP_EXTENSION: &str = "{96DAC541-7B7A-43D3-8B79-37D633B846F1}";
+
+const EMPTY_TREE: &str = r#"<p:nvGrpSpPr><p:cNvPr id="1" name=""/><p:cNvGrpSpPr/><p:nvPr/></p:nvGrpSpPr><p:grpSpPr/>"#;
+
+const THEME: &str = r#"<a:theme xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" name="Office"><a:themeElements><a:clrScheme name="Office"><a:dk1><a:sysClr val="windowText" lastClr="000000"/></a:dk1><a:lt1><a:sysClr val="window" lastClr="FFFFFF"/></a:lt1><a:dk2><a:srgbClr val="1E1E1E"/></a:dk2><a:lt2><a:srgbClr val="F8F9FA"/></a:lt2><a:accent1><a:srgbClr val="6965DB"/></a:accent1><a:accent2><a:srgbClr val="1971C2"/></a:accent2><a:accent3><a:srgbClr val="2F9E44"/></a:accent3><a:accent4><a:srgbClr val="F08C00"/></a:accent4><a:accent5><a:srgbClr val="E03131"/></a:accent5><a:accent6><a:srgbClr val="0C8599"/></a:accent6><a:hlink><a:srgbClr val="4263EB"/></a:hlink><a:folHlink><a:srgbClr val="9C36B5"/></a:folHlink></a:clrScheme><a:fontScheme name="Office"><a:majorFont><a:latin typeface="Calibri"/><a:ea typeface=""/><a:cs typeface=""/></a:majorFont><a:minorFont><a:latin typeface="Calibri"/><a:ea typeface=""/><a:cs typeface=""/></a:minorFont></a:fontScheme><a:fmtScheme name="Office"><a:fillStyleLst><a:solidFill><a:schemeClr val="phClr"/></a:solidFill><a:solidFill><a:schemeClr val="phClr"/></a:solidFill><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:fillStyleLst><a:lnStyleLst><a:ln w="6350"><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:ln><a:ln w="12700"><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:ln><a:ln w="19050"><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:ln></a:lnStyleLst><a:effectStyleLst><a:effectStyle><a:effectLst/></a:effectStyle><a:effectStyle><a:effectLst/></a:effectStyle><a:effectStyle><a:effectLst/></a:effectStyle></a:effectStyleLst><a:bgFillStyleLst><a:solidFill><a:schemeClr val="phClr"/></a:solidFill><a:solidFill><a:schemeClr val="phClr"/></a:solidFill><a:solidFill><a:schemeClr val="phClr"/></a:solidFill></a:bgFillStyleLst></a:fmtScheme></a:themeElements></a:theme>"#;
+
+fn xml(body: &str) -> Vec<u8> {
+    format!("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n{}", body).into_bytes()
+}
+
+/// `targets` are (type, target) pairs, numbered rId1 on
+fn relationships(targets: &[(&str, String)]) -> Vec<u8> {
+    let items: String = targets
+        .iter()
+        .enumerate()
+        .map(|(i, (kind, target))| {
+            format!(
+                "<Relationship Id=\"rId{}\" Type=\"{}/{}\" Target=\"{}\"/>",
+                i + 1,
+                REL_TYPES,
+                kind,
+                target
+            )
+        })
+        .collect();
+    xml(&format!(
+        "<Relationships xmlns=\"http://schemas.openxmlformats.org/package/2006/relationships\">{}</Relationships>",
+        items
+    ))
+}
+
+fn slide_xml(title: &str, width: f32, height: f32) -> Vec<u8> {
+    // Fit the picture below the title, centered
+    let (area_width, area_height) = (SLIDE_WIDTH - MARGIN * 2, SLIDE_HEIGHT - MARGIN * 2 - TITLE_HEIGHT);
+    let scale = (area_width as f64 / f64::from(width)).min(area_height as f64 / f64::from(height));
+    let (cx, cy) = ((f64::from(width) * scale) as i64, (f64::from(height) * scale) as i64);
+    let (x, y) = (MARGIN + (area_width - cx) / 2, MARGIN + TITLE_HEIGHT + (area_height - cy) / 2);
+    let title = escape_xml(title);
+
+    xml(&format!(
+        r#"<p:sld {NAMESPACES}><p:cSld><p:spTree>{EMPTY_TREE}<p:sp><p:nvSpPr><p:cNvPr id="2" name="Title"/><p:cNvSpPr txBox="1"/><p:nvPr/></p:nvSpPr><p:spPr><a:xfrm><a:off x="{MARGIN}" y="{MARGIN}"/><a:ext cx="{area_width}" cy="{TITLE_HEIGHT}"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></p:spPr><p:txBody><a:bodyPr anchor="ctr"/><a:lstStyle/><a:p><a:r><a:rPr lang="en-US" sz="2800" b="1"/><a:t>{title}</a:t></a:r></a:p></p:txBody></p:sp><p:pic><p:nvPicPr><p:cNvPr id="3" name="Diagram" descr="{title}"/><p:cNvPicPr><a:picLocks noChangeAspect="1"/></p:cNvPicPr><p:nvPr/></p:nvPicPr><p:blipFill><a:blip r:embed="rId2"><a:extLst><a:ext uri="{SVG_BLIP_EXTENSION}"><asvg:svgBlip xmlns:asvg="http://schemas.microsoft.com/office/drawing/2016/SVG/main" r:embed="rId3"/></a:ext></a:extLst></a:blip><a:stretch><a:fillRect/></a:stretch></p:blipFill><p:spPr><a:xfrm><a:off x="{x}" y="{y}"/><a:ext cx="{cx}" cy="{cy}"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></p:spPr></p:pic></p:spTree></p:cSld><p:clrMapOvr><a:masterClrMapping/></p:clrMapOvr></p:sld>"#
+    ))
+}
+
+/// Builds a PowerPoint deck: a title and the drawing on each slide, as SVG
+/// with a PNG fallback for viewers that can't show SVG
+pub fn render(title: &str, slides: &[Slide]) -> Result<Vec<u8>, AppError> {
+    let now = SystemTime::now();
+    let mut zip = ZipWriter::new(Vec::new());
+    let mut add = |name: &str, data: &[u8]| {
+        zip.add(name, data, now)
+            .context("Failed to build slide deck")
+    };
+
+    let slide_overrides: String = (1..=slides.len())
+        .map(|n| {
+            format!(
+                "<Override PartName=\"/ppt/slides/slide{}.xml\" ContentType=\"application/vnd.openxmlformats-officedocument.presentationml.slide+xml\"/>",
+                n
+            )
+        })
+        .collect();
+    add(
+        "[Content_Types].xml",
+        &xml(&format!(
+            r#"<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Default Extension="png" ContentType="image/png"/><Default Extension="svg" ContentType="image/svg+xml"/><Override PartName="/ppt/presentation.xml" ContentType="application/vnd.openxmlformats-officedocument.presentationml.presentation.main+xml"/><Override PartName="/ppt/slideMasters/slideMaster1.xml" ContentType="application/vnd.openxmlformats-officedocument.presentationml.slideMaster+xml"/><Override PartName="/ppt/slideLayouts/slideLayout1.xml" ContentType="application/vnd.openxmlformats-officedocument.presentationml.slideLayout+xml"/><Override PartName="/ppt/theme/theme1.xml" ContentType="application/vnd.openxmlformats-officedocument.theme+xml"/><Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/>{}</Types>"#,
+            slide_overrides
+        )),
+    )?;
+    add(
+        "_rels/.rels",
+        &xml(&format!(
+            r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="{REL_TYPES}/officeDocument" Target="ppt/presentation.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/></Relationships>"#
+        )),
+    )?;
+    add(
+        "docProps/core.xml",
+        &xml(&format!(
+            r#"<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>{}</dc:title></cp:coreProperties>"#,
+            escape_xml(title)
+        )),
+    )?;
+
+    // rId1 is the master, rId2 the theme, the slides follow
+    let slide_ids: String = (1..=slides.len())
+        .map(|n| format!("<p:sldId id=\"{}\" r:id=\"rId{}\"/>", 255 + n, n + 2))
+        .collect();
+    add(
+        "ppt/presentation.xml",
+        &xml(&format!(
+            r#"<p:presentation {NAMESPACES}><p:sldMasterIdLst><p:sldMasterId id="2147483648" r:id="rId1"/></p:sldMasterIdLst><p:sldIdLst>{slide_ids}</p:sldIdLst><p:sldSz cx="{SLIDE_WIDTH}" cy="{SLIDE_HEIGHT}"/><p:notesSz cx="{SLIDE_HEIGHT}" cy="9144000"/></p:presentation>"#
+        )),
+    )?;
+    let mut targets = vec![
+        ("slideMaster", "slideMasters/slideMaster1.xml".to_string()),
+        ("theme", "theme/theme1.xml".to_string()),
+    ];
+    targets.extend((1..=slides.len()).map(|n| ("slide", format!("slides/slide{}.xml", n))));
+    add("ppt/_rels/presentation.xml.rels", &relationships(&targets))?;
+
+    add(
+        "ppt/slideMasters/slideMaster1.xml",
+        &xml(&format!(
+            r#"<p:sldMaster {NAMESPACES}><p:cSld><p:spTree>{EMPTY_TREE}</p:spTree></p:cSld><p:clrMap bg1="lt1" tx1="dk1" bg2="lt2" tx2="dk2" accent1="accent1" accent2="accent2" accent3="accent3" accent4="accent4" accent5="accent5" accent6="accent6" hlink="hlink" folHlink="folHlink"/><p:sldLayoutIdLst><p:sldLayoutId id="2147483649" r:id="rId1"/></p:sldLayoutIdLst></p:sldMaster>"#
+        )),
+    )?;
+    add(
+        "ppt/slideMasters/_rels/slideMaster1.xml.rels",
+        &relationships(&[
+            ("slideLayout", "../slideLayouts/slideLayout1.xml".to_string()),
+            ("theme", "../theme/theme1.xml".to_string()),
+        ]),
+    )?;
+    add(
+        "ppt/slideLayouts/slideLayout1.xml",
+        &xml(&format!(
+            r#"<p:sldLayout {NAMESPACES} type="blank" preserve="1"><p:cSld name="Blank"><p:spTree>{EMPTY_TREE}</p:spTree></p:cSld><p:clrMapOvr><a:masterClrMapping/></p:clrMapOvr></p:sldLayout>"#
+        )),
+    )?;
+    add(
+        "ppt/slideLayouts/_rels/slideLayout1.xml.rels",
+        &relationships(&[("slideMaster", "../slideMasters/slideMaster1.xml".to_string())]),
+    )?;
+    add("ppt/theme/theme1.xml", &xml(THEME))?;
+
+    for (i, slide) in slides.iter().enumerate() {
+        let n = i + 1;
+        // Rasterized for viewers without SVG support
+        let (png, width, height) = crate::export::svg_to_png(&slide.svg, 2.0, MAX_IMAGE_SIDE)?;
+        add(&format!("ppt/media/image{}.png", n), &png)?;
+        add(&format!("ppt/media/image{}.svg", n), slide.svg.as_bytes())?;
+        add(&format!("ppt/slides/slide{}.xml", n), &slide_xml(&slide.title, width, height))?;
+        add(
+            &format!("ppt/slides/_rels/slide{}.xml.rels", n),
+            &relationships(&[
+                ("slideLayout", "../slideLayouts/slideLayout1.xml".to_string()),
+                ("image", format!("../media/image{}.png", n)),
+                ("image", format!("../media/image{}.svg", n)),
+            ]),
+        )?;
+    }
+    zip.finish().context("Failed to build slide deck")
+}
@@ -0,0 +1,257 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::profiles::AIProviderProfile;
+use crate::prompts::PromptTemplate;
+
+/// Version written into `schema_version`; bump it together with a new step in
+/// `migrate` whenever stored fields are renamed or change meaning
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Keys the earliest builds stored in the frontend's camelCase
+const CAMEL_CASE_KEYS: &[(&str, &str)] = &[
+    ("lastDirectory", "last_directory"),
+    ("recentDirectories", "recent_directories"),
+    ("sidebarVisible", "sidebar_visible"),
+    ("compactOnSave", "compact_on_save"),
+    ("workspaceRoots", "workspace_roots"),
+    ("ignorePatterns", "ignore_patterns"),
+    ("logLevel", "log_level"),
+];
+
+/// Preferences that only make sense on the machine that wrote them; settings
+/// exports leave them out and imports keep the local values
+const MACHINE_KEYS: &[&str] = &[
+    "last_directory",
+    "recent_directories",
+    "recent_files",
+    "workspace_roots",
+    "shortcuts",
+    "pinned_files",
+    "vault_backup",
+    "s3_backup",
+];
+
+/// Proxy fields that stay on this machine: a secret and a local file
+const LOCAL_PROXY_KEYS: &[&str] = &["password", "ca_certificate_path"];
+
+/// Value of `format` in settings exports, so other JSON files are refused
+pub const BUNDLE_FORMAT: &str = "excaliapp-settings";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+/// Font for new text; matches Excalidraw's hand-drawn, normal and code families
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FontFamily {
+    #[default]
+    HandDrawn,
+    Normal,
+    Code,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct GridSettings {
+    /// Show the grid and snap to it in drawings that don't set their own
+    pub enabled: bool,
+    pub size: u32,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: 20,
+        }
+    }
+}
+
+/// How folders are watched for changes made outside the app
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    /// Native events, polling on network shares
+    #[default]
+    Auto,
+    Native,
+    Poll,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct WatcherSettings {
+    pub mode: WatchMode,
+    /// Seconds between scans when polling
+    pub poll_interval_secs: u32,
+}
+
+impl Default for WatcherSettings {
+    fn default() -> Self {
+        Self {
+            mode: WatchMode::Auto,
+            poll_interval_secs: 5,
+        }
+    }
+}
+
+impl WatcherSettings {
+    pub fn poll_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(u64::from(self.poll_interval_secs.max(1)))
+    }
+}
+
+/// Upgrades a stored preferences object to `SCHEMA_VERSION` one step at a
+/// time; returns whether anything changed and should be written back
+pub fn migrate(value: &mut Value) -> bool {
+    if !value.is_object() {
+        *value = Value::Object(Map::new());
+    }
+    let object = value.as_object_mut().expect("replaced above");
+
+    let version = object
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    if version >= u64::from(SCHEMA_VERSION) {
+        return false;
+    }
+
+    if version < 1 {
+        v0_to_v1(object);
+    }
+
+    object.insert("schema_version".to_string(), Value::from(SCHEMA_VERSION));
+    true
+}
+
+/// Files from before versioning: camelCase keys, `null` lists and values the
+/// frontend wrote as strings
+fn v0_to_v1(object: &mut Map<String, Value>) {
+    for (old, new) in CAMEL_CASE_KEYS {
+        if let Some(value) = object.remove(*old) {
+            object.entry(new.to_string()).or_insert(value);
+        }
+    }
+
+    // Dropped keys fall back to their defaults
+    object.retain(|_, value| !value.is_null());
+
+    if let Some(Value::String(visible)) = object.get("sidebar_visible") {
+        let visible = visible != "false";
+        object.insert("sidebar_visible".to_string(), Value::Bool(visible));
+    }
+    if !matches!(
+        object.get("theme").and_then(Value::as_str),
+        Some("light" | "dark" | "system") | None
+    ) {
+        object.remove("theme");
+    }
+}
+
+/// Deserializes `stored` over `defaults` field by field, so one unreadable
+/// value resets only that field; returns the result and the skipped keys
+pub fn merge_lenient<T: Serialize + DeserializeOwned>(defaults: T, stored: &Value) -> (T, Vec<String>) {
+    let mut merged = serde_json::to_value(&defaults).unwrap_or(Value::Object(Map::new()));
+    let mut skipped = Vec::new();
+
+    if let (Some(target), Some(source)) = (merged.as_object_mut(), stored.as_object()) {
+        for (key, value) in source {
+            let previous = target.insert(key.clone(), value.clone());
+            if serde_json::from_value::<T>(Value::Object(target.clone())).is_err() {
+                skipped.push(key.clone());
+                match previous {
+                    Some(previous) => target.insert(key.clone(), previous),
+                    None => target.remove(key),
+                };
+            }
+        }
+    }
+
+    let result = serde_json::from_value(merged).unwrap_or(defaults);
+    (result, skipped)
+}
+
+/// Everything `export_settings` writes: preferences without machine-specific
+/// entries or secrets, AI profiles, prompt templates and custom shortcuts
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingsBundle {
+    pub format: String,
+    pub app_version: String,
+    /// Milliseconds since the Unix epoch
+    #[serde(default)]
+    pub exported_at: i64,
+    /// Stored form, migrated like the preferences file on import
+    #[serde(default)]
+    pub preferences: Value,
+    /// Without API keys
+    #[serde(default)]
+    pub ai_profiles: Vec<AIProviderProfile>,
+    #[serde(default)]
+    pub default_ai_profile: Option<String>,
+    #[serde(default)]
+    pub prompt_templates: Vec<PromptTemplate>,
+    #[serde(default)]
+    pub shortcuts: HashMap<String, String>,
+}
+
+/// The stored preferences minus machine-specific entries and local proxy
+/// fields
+pub fn portable(mut preferences: Value) -> Value {
+    if let Some(object) = preferences.as_object_mut() {
+        for key in MACHINE_KEYS {
+            object.remove(*key);
+        }
+        if let Some(proxy) = object.get_mut("proxy").and_then(Value::as_object_mut) {
+            for key in LOCAL_PROXY_KEYS {
+                proxy.remove(*key);
+            }
+        }
+    }
+    preferences
+}
+
+/// Lays imported preferences over the current ones; machine-specific entries
+/// and local proxy fields keep their values from this machine
+pub fn overlay_imported(current: &Value, mut imported: Value) -> Value {
+    migrate(&mut imported);
+    let mut merged = current.clone();
+    let (Some(target), Value::Object(source)) = (merged.as_object_mut(), portable(imported)) else {
+        return merged;
+    };
+
+    for (key, mut value) in source {
+        if key == "proxy"
+            && let (Some(proxy), Some(local)) = (
+                value.as_object_mut(),
+                target.get("proxy").and_then(Value::as_object),
+            )
+        {
+            for field in LOCAL_PROXY_KEYS {
+                if let Some(local) = local.get(*field) {
+                    proxy.insert(field.to_string(), local.clone());
+                }
+            }
+        }
+        target.insert(key, value);
+    }
+    merged
+}
+
+/// What `import_settings` applied
+#[derive(Debug, Serialize)]
+pub struct ImportedSettings {
+    pub ai_profiles: usize,
+    pub prompt_templates: usize,
+    pub shortcuts: usize,
+    /// Entries of the file this version could not use, e.g. `theme`
+    pub skipped: Vec<String>,
+}
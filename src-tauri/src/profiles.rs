@@ -0,0 +1,177 @@
+use serde::{Deserialize, Serialize};
+
+use crate::ai;
+use crate::error::AppError;
+
+/// Store file holding the named AI provider profiles; their API keys live
+/// in the system keychain
+pub const STORE_FILE: &str = "ai_profiles.json";
+pub const STORE_KEY: &str = "profiles";
+pub const DEFAULT_KEY: &str = "default_profile_id";
+
+/// Keychain service the API keys are stored under, one entry per profile id
+const KEYRING_SERVICE: &str = "com.yangkai.ownexcalidesk.ai";
+
+fn default_temperature() -> f32 {
+    0.7
+}
+
+fn default_max_tokens() -> u32 {
+    2000
+}
+
+/// One configured endpoint the AI commands can be pointed at by id
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AIProviderProfile {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    /// `openai`, `anthropic`, `gemini` or another OpenAI-compatible service;
+    /// guessed from `base_url` when unset
+    #[serde(default)]
+    pub provider: Option<String>,
+    pub base_url: String,
+    /// Received from the frontend and filled from the keychain for requests;
+    /// never written anywhere
+    #[serde(default, skip_serializing)]
+    pub api_key: String,
+    /// Whether the keychain holds an API key for this profile
+    #[serde(default)]
+    pub has_api_key: bool,
+    pub model: String,
+    /// Used when a request doesn't set its own
+    #[serde(default = "default_temperature")]
+    pub temperature: f32,
+    #[serde(default = "default_max_tokens")]
+    pub max_tokens: u32,
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Milliseconds since the Unix epoch
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+impl AIProviderProfile {
+    pub fn wire_provider(&self) -> ai::AIProvider {
+        ai::AIProvider::resolve(self.provider.as_deref(), &self.base_url)
+    }
+
+    /// A copy that is safe to hand to the frontend or write to an export
+    pub fn without_secret(&self) -> Self {
+        Self {
+            api_key: String::new(),
+            ..self.clone()
+        }
+    }
+}
+
+/// A profile as listed to the frontend
+#[derive(Debug, Serialize, Clone)]
+pub struct ProfileSummary {
+    #[serde(flatten)]
+    pub profile: AIProviderProfile,
+    pub is_default: bool,
+}
+
+pub fn summaries(profiles: &[AIProviderProfile], default_id: Option<&str>) -> Vec<ProfileSummary> {
+    let default_id = default_id.or_else(|| profiles.first().map(|p| p.id.as_str()));
+    profiles
+        .iter()
+        .map(|profile| ProfileSummary {
+            profile: profile.without_secret(),
+            is_default: Some(profile.id.as_str()) == default_id,
+        })
+        .collect()
+}
+
+/// Adds `profile` or replaces the one with the same id. A new API key is
+/// returned with the profile for the caller to put in the keychain; an
+/// empty one keeps the stored key, so the frontend never has to send it back.
+pub fn upsert(profiles: &mut Vec<AIProviderProfile>, mut profile: AIProviderProfile) -> AIProviderProfile {
+    if profile.id.trim().is_empty() {
+        profile.id = format!("profile_{}", crate::prompts::now_millis());
+    }
+    profile.updated_at = crate::prompts::now_millis();
+
+    let existing = profiles.iter_mut().find(|p| p.id == profile.id);
+    profile.has_api_key = !profile.api_key.is_empty() || existing.as_ref().is_some_and(|p| p.has_api_key);
+    let stored = profile.without_secret();
+    match existing {
+        Some(existing) => *existing = stored,
+        None => profiles.push(stored),
+    }
+    profile
+}
+
+fn keychain_entry(profile_id: &str) -> Result<keyring::Entry, AppError> {
+    keyring::Entry::new(KEYRING_SERVICE, profile_id)
+        .map_err(|e| AppError::Internal(format!("Keychain is unavailable: {}", e)))
+}
+
+/// Stores the API key of profile `profile_id`; an empty key removes it
+pub fn store_api_key(profile_id: &str, api_key: &str) -> Result<(), AppError> {
+    let entry = keychain_entry(profile_id)?;
+    let result = if api_key.is_empty() {
+        match entry.delete_credential() {
+            Err(keyring::Error::NoEntry) => Ok(()),
+            result => result,
+        }
+    } else {
+        entry.set_password(api_key)
+    };
+    result.map_err(|e| AppError::Internal(format!("Failed to update the keychain: {}", e)))
+}
+
+/// `profile` with its API key read from the keychain, when it has one
+pub fn with_api_key(mut profile: AIProviderProfile) -> Result<AIProviderProfile, AppError> {
+    if !profile.has_api_key {
+        return Ok(profile);
+    }
+    profile.api_key = match keychain_entry(&profile.id)?.get_password() {
+        Ok(api_key) => api_key,
+        Err(keyring::Error::NoEntry) => {
+            return Err(AppError::PermissionDenied(format!(
+                "No API key is stored for the AI profile {}",
+                profile.name
+            )));
+        }
+        Err(e) => return Err(AppError::Internal(format!("Failed to read the keychain: {}", e))),
+    };
+    Ok(profile)
+}
+
+/// Moves API keys that older versions kept in the store file into the
+/// keychain; true when a profile changed and the list needs saving again
+pub fn migrate_api_keys(profiles: &mut [AIProviderProfile]) -> bool {
+    let mut changed = false;
+    for profile in profiles.iter_mut().filter(|p| !p.api_key.is_empty()) {
+        match store_api_key(&profile.id, &profile.api_key) {
+            Ok(()) => {
+                profile.api_key.clear();
+                profile.has_api_key = true;
+                changed = true;
+            }
+            Err(e) => tracing::warn!("Failed to move the API key of {} to the keychain: {}", profile.name, e),
+        }
+    }
+    changed
+}
+
+/// The profile with `id`, or the default one when no id is given. Without a
+/// stored default the first profile is used.
+pub fn find<'a>(
+    profiles: &'a [AIProviderProfile],
+    id: Option<&str>,
+    default_id: Option<&str>,
+) -> Result<&'a AIProviderProfile, AppError> {
+    match id.filter(|id| !id.is_empty()) {
+        Some(id) => profiles
+            .iter()
+            .find(|p| p.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("AI profile not found: {}", id))),
+        None => default_id
+            .and_then(|id| profiles.iter().find(|p| p.id == id))
+            .or_else(|| profiles.first())
+            .ok_or_else(|| AppError::NotFound("No AI profile is configured".to_string())),
+    }
+}
@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Store file holding the prompt template library
+pub const STORE_FILE: &str = "prompt_templates.json";
+pub const STORE_KEY: &str = "prompt_templates";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PromptTemplate {
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Prompt text with `{{variable}}` placeholders, e.g. `{{selection}}`, `{{file_name}}`
+    pub template: String,
+    /// Overrides the profile's system prompt when set
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Milliseconds since the Unix epoch
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+pub fn now_millis() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Replaces `{{name}}` placeholders (whitespace inside the braces is allowed);
+/// unknown variables are left untouched so typos stay visible
+pub fn render(template: &str, variables: &HashMap<String, String>) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let name = after[..end].trim();
+                match variables.get(name) {
+                    Some(value) => output.push_str(value),
+                    None => output.push_str(&rest[start..start + 2 + end + 2]),
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                output.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
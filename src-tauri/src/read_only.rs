@@ -0,0 +1,58 @@
+use crate::error::AppError;
+use crate::pins::is_at_or_below;
+use crate::FileTreeNode;
+
+/// Adds or removes a lock on a drawing or folder; returns whether the list
+/// changed
+pub fn set(locked: &mut Vec<String>, path: &str, read_only: bool) -> bool {
+    let present = locked.iter().any(|p| p == path);
+    if read_only == present {
+        return false;
+    }
+    if read_only {
+        locked.push(path.to_string());
+    } else {
+        locked.retain(|p| p != path);
+    }
+    true
+}
+
+/// The lock covering `path`: its own, or that of a folder above it
+pub fn locked_by<'a>(locked: &'a [String], path: &str) -> Option<&'a str> {
+    locked
+        .iter()
+        .find(|lock| is_at_or_below(path, lock))
+        .map(String::as_str)
+}
+
+/// Fails when `path` is locked, for changes to a single drawing
+pub fn check(locked: &[String], path: &str) -> Result<(), AppError> {
+    match locked_by(locked, path) {
+        Some(lock) if lock == path => Err(AppError::ReadOnly(format!("{} is read-only", path))),
+        Some(lock) => Err(AppError::ReadOnly(format!("{} is in the read-only folder {}", path, lock))),
+        None => Ok(()),
+    }
+}
+
+/// Fails when `path` or anything below it is locked, for changes that take
+/// a whole folder along
+pub fn check_tree(locked: &[String], path: &str) -> Result<(), AppError> {
+    check(locked, path)?;
+    match locked.iter().find(|lock| is_at_or_below(lock, path)) {
+        Some(lock) => Err(AppError::ReadOnly(format!("{} contains the read-only {}", path, lock))),
+        None => Ok(()),
+    }
+}
+
+/// Sets `read_only` on locked nodes and everything below them
+pub fn mark(tree: &mut [FileTreeNode], locked: &[String]) {
+    if locked.is_empty() {
+        return;
+    }
+    for node in tree.iter_mut() {
+        node.read_only = locked_by(locked, &node.path).is_some();
+        if let Some(children) = node.children.as_mut() {
+            mark(children, locked);
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::export::{fnv1a, FNV_OFFSET};
+
+/// How long after a save the watcher events for that file are put down to it
+const WINDOW: Duration = Duration::from_secs(3);
+
+struct Save {
+    hash: u64,
+    at: Instant,
+}
+
+/// Files the app wrote itself, so the watcher does not report them back to
+/// the frontend as outside changes
+#[derive(Default)]
+pub struct RecentSaves {
+    saves: Mutex<HashMap<String, Save>>,
+}
+
+impl RecentSaves {
+    /// Call before writing `content` to `path`
+    pub fn record(&self, path: &Path, content: &str) {
+        let mut saves = self.saves.lock().unwrap();
+        saves.retain(|_, save| save.at.elapsed() < WINDOW);
+        saves.insert(
+            crate::paths::normalize(path),
+            Save {
+                hash: fnv1a(content.as_bytes(), FNV_OFFSET),
+                at: Instant::now(),
+            },
+        );
+    }
+
+    /// Whether an event for `path` comes from a recent save: the file still
+    /// holds exactly what was saved. A different write in the meantime, or a
+    /// deleted file, counts as an outside change.
+    pub fn is_own(&self, path: &Path) -> bool {
+        let key = crate::paths::normalize(path);
+        let hash = {
+            let mut saves = self.saves.lock().unwrap();
+            saves.retain(|_, save| save.at.elapsed() < WINDOW);
+            match saves.get(&key) {
+                Some(save) => save.hash,
+                None => return false,
+            }
+        };
+        fs::read(path).is_ok_and(|content| fnv1a(&content, FNV_OFFSET) == hash)
+    }
+}
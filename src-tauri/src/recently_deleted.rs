@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::compression::COMPRESSED_SUFFIX;
+use crate::error::{AppError, ResultExt};
+use crate::export::{fnv1a, FNV_OFFSET};
+
+/// Directory (under the app data dir) holding deleted drawings and folders
+pub const DELETED_DIR: &str = "recently_deleted";
+
+/// Default of `Preferences::deleted_retention_days`
+pub const DEFAULT_RETENTION_DAYS: u32 = 30;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+const ENTRY_FILE: &str = "entry.json";
+/// The content of a deleted drawing, or the deleted folder itself
+const ITEM: &str = "item";
+
+/// Something deleted from a vault that can still be restored
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeletedEntry {
+    pub id: String,
+    /// Where it was, as the frontend knows the path
+    pub original_path: String,
+    pub name: String,
+    pub is_directory: bool,
+    /// Milliseconds since the Unix epoch
+    pub deleted_at: i64,
+    /// Content length of drawings; 0 for folders
+    pub size: u64,
+}
+
+/// Ids name folders, so they must not carry path separators
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// A fresh entry folder for `original_path`, named after the time and path
+fn create_entry(dir: &Path, original_path: &str, name: &str, is_directory: bool) -> Result<(DeletedEntry, PathBuf), AppError> {
+    let deleted_at = crate::prompts::now_millis();
+    let hash = fnv1a(original_path.as_bytes(), FNV_OFFSET);
+    let mut attempt = 0u32;
+    let (id, folder) = loop {
+        let id = format!("{:x}-{:016x}-{}", deleted_at, hash, attempt);
+        let folder = dir.join(&id);
+        match fs::create_dir_all(dir).and_then(|()| fs::create_dir(&folder)) {
+            Ok(()) => break (id, folder),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(AppError::from(e).with_context("Failed to create recently deleted entry")),
+        }
+    };
+    let entry = DeletedEntry {
+        id,
+        original_path: original_path.to_string(),
+        name: name.to_string(),
+        is_directory,
+        deleted_at,
+        size: 0,
+    };
+    Ok((entry, folder))
+}
+
+fn write_entry(folder: &Path, entry: &DeletedEntry) -> Result<(), AppError> {
+    let json = serde_json::to_string_pretty(entry).map_err(|e| AppError::Internal(e.to_string()))?;
+    fs::write(folder.join(ENTRY_FILE), json).context("Failed to write recently deleted entry")
+}
+
+/// Keeps the content of a drawing about to be deleted
+pub fn keep_file(dir: &Path, original_path: &str, name: &str, content: &str) -> Result<DeletedEntry, AppError> {
+    let (mut entry, folder) = create_entry(dir, original_path, name, false)?;
+    entry.size = content.len() as u64;
+    let result = fs::write(folder.join(ITEM), content)
+        .context("Failed to keep deleted drawing")
+        .and_then(|()| write_entry(&folder, &entry));
+    if let Err(e) = result {
+        let _ = fs::remove_dir_all(&folder);
+        return Err(e);
+    }
+    Ok(entry)
+}
+
+/// Moves a local folder about to be deleted into `dir`, or copies it when
+/// it is on another volume; the caller deletes what is left in place. On
+/// failure the folder is moved back, and kept here if even that fails.
+pub fn keep_directory(dir: &Path, original_path: &str, name: &str, folder: &Path) -> Result<DeletedEntry, AppError> {
+    let (entry, entry_folder) = create_entry(dir, original_path, name, true)?;
+    let item = entry_folder.join(ITEM);
+    let moved = fs::rename(folder, &item).is_ok();
+    let copied = if moved { Ok(()) } else { copy_dir(folder, &item) };
+    let result = copied
+        .context("Failed to keep deleted folder")
+        .and_then(|()| write_entry(&entry_folder, &entry));
+    if let Err(e) = result {
+        if !moved || fs::rename(&item, folder).is_ok() {
+            let _ = fs::remove_dir_all(&entry_folder);
+        }
+        return Err(e);
+    }
+    Ok(entry)
+}
+
+/// Copies a folder with everything in it; symlinks are skipped
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Every entry in `dir`, most recently deleted first
+pub fn list(dir: &Path) -> Vec<DeletedEntry> {
+    let Ok(folders) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut entries: Vec<DeletedEntry> = folders
+        .flatten()
+        .filter_map(|folder| {
+            let content = fs::read_to_string(folder.path().join(ENTRY_FILE)).ok()?;
+            serde_json::from_str(&content).ok()
+        })
+        .collect();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+    entries
+}
+
+/// The entry `id` and where its drawing content or folder is kept
+pub fn load(dir: &Path, id: &str) -> Result<(DeletedEntry, PathBuf), AppError> {
+    if !is_valid_id(id) {
+        return Err(AppError::InvalidInput(format!("Invalid recently deleted id: {}", id)));
+    }
+    let folder = dir.join(id);
+    let content = fs::read_to_string(folder.join(ENTRY_FILE))
+        .map_err(|_| AppError::NotFound(format!("Deleted item not found: {}", id)))?;
+    let entry = serde_json::from_str(&content)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid recently deleted entry: {}", e)))?;
+    Ok((entry, folder.join(ITEM)))
+}
+
+/// Drops the entry `id` and what it kept
+pub fn remove(dir: &Path, id: &str) -> Result<(), AppError> {
+    if !is_valid_id(id) {
+        return Err(AppError::InvalidInput(format!("Invalid recently deleted id: {}", id)));
+    }
+    match fs::remove_dir_all(dir.join(id)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(AppError::from(e).with_context("Failed to remove deleted item")),
+        _ => Ok(()),
+    }
+}
+
+/// Moves the entries deleted more than `retention_days` days before `now` on
+/// to the system trash, where they can still be recovered; entries the trash
+/// won't take and folders an interrupted delete left without an entry (these
+/// go by their own modification time) are dropped. Returns how many entries
+/// left Recently Deleted.
+pub fn purge(dir: &Path, retention_days: u32, now: i64) -> usize {
+    let Ok(folders) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let cutoff = now - i64::from(retention_days) * DAY_MILLIS;
+    let mut purged = 0;
+    for folder in folders.flatten().map(|f| f.path()).filter(|p| p.is_dir()) {
+        let entry = fs::read_to_string(folder.join(ENTRY_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str::<DeletedEntry>(&content).ok());
+        let deleted_at = entry.as_ref().map(|entry| entry.deleted_at).or_else(|| {
+            let modified = fs::metadata(&folder).and_then(|m| m.modified()).ok()?;
+            Some(modified.duration_since(std::time::UNIX_EPOCH).ok()?.as_millis() as i64)
+        });
+        if deleted_at.is_none_or(|deleted_at| deleted_at >= cutoff) {
+            continue;
+        }
+        if let Some(entry) = &entry
+            && let Err(e) = move_to_trash(&folder, entry)
+        {
+            tracing::warn!("Deleting {} instead of moving it to the trash: {}", entry.original_path, e);
+        }
+        if fs::remove_dir_all(&folder).is_ok() {
+            purged += 1;
+        }
+    }
+    purged
+}
+
+/// Hands what entry `folder` keeps to the system trash under its original
+/// name. Drawings are kept decompressed, so compressed ones lose their `.gz`.
+fn move_to_trash(folder: &Path, entry: &DeletedEntry) -> Result<(), AppError> {
+    let name = match entry.name.strip_suffix(COMPRESSED_SUFFIX) {
+        Some(stem) => format!("{}.excalidraw", stem),
+        None => entry.name.clone(),
+    };
+    let name = Path::new(&name)
+        .file_name()
+        .filter(|n| *n != ENTRY_FILE && *n != ITEM)
+        .ok_or_else(|| AppError::InvalidInput(format!("Invalid name in recently deleted entry: {}", entry.name)))?;
+    let named = folder.join(name);
+    fs::rename(folder.join(ITEM), &named).context("Failed to name deleted item")?;
+    trash::delete(&named).map_err(|e| AppError::Io(format!("Failed to move to the trash: {}", e)))
+}
+
+/// Puts a kept folder back at `to`, moving it unless `to` is on another
+/// volume
+pub fn restore_directory(item: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(item, to).or_else(|_| copy_dir(item, to))
+}
+
+/// `path`, or when `exists` says it is taken, the first free of
+/// `name (restored).excalidraw`, `name (restored 2).excalidraw`, ...
+pub fn free_path(path: &Path, is_directory: bool, exists: impl Fn(&Path) -> bool) -> PathBuf {
+    if !exists(path) {
+        return path.to_path_buf();
+    }
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let suffix = if is_directory {
+        ""
+    } else if name.ends_with(COMPRESSED_SUFFIX) {
+        COMPRESSED_SUFFIX
+    } else {
+        ".excalidraw"
+    };
+    let stem = name.strip_suffix(suffix).unwrap_or(&name);
+    (1..)
+        .map(|n| match n {
+            1 => format!("{} (restored){}", stem, suffix),
+            n => format!("{} (restored {}){}", stem, n, suffix),
+        })
+        .map(|name| path.with_file_name(name))
+        .find(|candidate| !exists(candidate))
+        .unwrap_or_default()
+}
@@ -0,0 +1,437 @@
+use serde_json::{Map, Value};
+
+/// Axis-aligned bounding box in scene coordinates
+#[derive(Debug, Clone, Copy)]
+pub struct Bounds {
+    pub min_x: f64,
+    pub min_y: f64,
+    pub max_x: f64,
+    pub max_y: f64,
+}
+
+impl Bounds {
+    pub fn width(&self) -> f64 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> f64 {
+        self.max_y - self.min_y
+    }
+
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+
+    pub fn intersects(&self, other: &Bounds) -> bool {
+        self.min_x <= other.max_x
+            && other.min_x <= self.max_x
+            && self.min_y <= other.max_y
+            && other.min_y <= self.max_y
+    }
+
+    pub fn expand(&self, padding: f64) -> Bounds {
+        Bounds {
+            min_x: self.min_x - padding,
+            min_y: self.min_y - padding,
+            max_x: self.max_x + padding,
+            max_y: self.max_y + padding,
+        }
+    }
+}
+
+fn num(element: &Value, key: &str) -> f64 {
+    element.get(key).and_then(|v| v.as_f64()).unwrap_or(0.0)
+}
+
+fn text<'a>(element: &'a Value, key: &str, default: &'a str) -> &'a str {
+    element.get(key).and_then(|v| v.as_str()).unwrap_or(default)
+}
+
+fn points(element: &Value) -> Vec<(f64, f64)> {
+    element
+        .get("points")
+        .and_then(|p| p.as_array())
+        .map(|pts| {
+            pts.iter()
+                .filter_map(|p| {
+                    let p = p.as_array()?;
+                    Some((p.first()?.as_f64()?, p.get(1)?.as_f64()?))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Escapes text for use in SVG/HTML content and attribute values
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Returns the non-deleted elements of a scene
+pub fn visible_elements(scene: &Value) -> Vec<&Value> {
+    scene
+        .get("elements")
+        .and_then(|e| e.as_array())
+        .map(|elements| {
+            elements
+                .iter()
+                .filter(|e| !e.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the frame elements of a scene in their stacking order
+pub fn frames<'a>(elements: &[&'a Value]) -> Vec<&'a Value> {
+    elements
+        .iter()
+        .copied()
+        .filter(|e| matches!(text(e, "type", ""), "frame" | "magicframe"))
+        .collect()
+}
+
+/// Returns the elements that belong to the given frame, including the frame itself
+pub fn frame_children<'a>(elements: &[&'a Value], frame: &Value) -> Vec<&'a Value> {
+    let frame_id = text(frame, "id", "");
+    elements
+        .iter()
+        .copied()
+        .filter(|e| text(e, "id", "") == frame_id || text(e, "frameId", "") == frame_id)
+        .collect()
+}
+
+/// Computes the bounding box of a single element
+pub fn element_bounds(element: &Value) -> Bounds {
+    let x = num(element, "x");
+    let y = num(element, "y");
+    let pts = points(element);
+
+    if pts.is_empty() {
+        let width = num(element, "width");
+        let height = num(element, "height");
+        return Bounds {
+            min_x: x.min(x + width),
+            min_y: y.min(y + height),
+            max_x: x.max(x + width),
+            max_y: y.max(y + height),
+        };
+    }
+
+    pts.iter().fold(
+        Bounds {
+            min_x: f64::MAX,
+            min_y: f64::MAX,
+            max_x: f64::MIN,
+            max_y: f64::MIN,
+        },
+        |b, (px, py)| Bounds {
+            min_x: b.min_x.min(x + px),
+            min_y: b.min_y.min(y + py),
+            max_x: b.max_x.max(x + px),
+            max_y: b.max_y.max(y + py),
+        },
+    )
+}
+
+/// Computes the bounding box of a set of elements
+pub fn scene_bounds(elements: &[&Value]) -> Option<Bounds> {
+    elements
+        .iter()
+        .map(|e| element_bounds(e))
+        .reduce(|a, b| a.union(&b))
+}
+
+fn font_family(element: &Value) -> &'static str {
+    match element.get("fontFamily").and_then(|f| f.as_u64()).unwrap_or(1) {
+        2 => "Helvetica, Segoe UI Emoji",
+        3 => "Cascadia, Segoe UI Emoji",
+        5 => "Excalifont, Xiaolai, Segoe UI Emoji",
+        _ => "Virgil, Segoe UI Emoji",
+    }
+}
+
+fn fill_color(element: &Value) -> String {
+    match text(element, "backgroundColor", "transparent") {
+        "" | "transparent" => "none".to_string(),
+        color => escape_xml(color),
+    }
+}
+
+fn arrowhead(tip: (f64, f64), from: (f64, f64), stroke: &str, stroke_width: f64) -> String {
+    let angle = (tip.1 - from.1).atan2(tip.0 - from.0);
+    let size = 10.0 + stroke_width * 2.0;
+    let spread = 25f64.to_radians();
+    let left = (
+        tip.0 - size * (angle - spread).cos(),
+        tip.1 - size * (angle - spread).sin(),
+    );
+    let right = (
+        tip.0 - size * (angle + spread).cos(),
+        tip.1 - size * (angle + spread).sin(),
+    );
+    format!(
+        r#"<path d="M {:.2} {:.2} L {:.2} {:.2} L {:.2} {:.2}" fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"/>"#,
+        left.0, left.1, tip.0, tip.1, right.0, right.1, stroke, stroke_width
+    )
+}
+
+/// Renders one element as an SVG fragment; unsupported types render nothing
+pub fn render_element(element: &Value, files: Option<&Map<String, Value>>) -> String {
+    let kind = text(element, "type", "");
+    let x = num(element, "x");
+    let y = num(element, "y");
+    let width = num(element, "width");
+    let height = num(element, "height");
+    let stroke = escape_xml(text(element, "strokeColor", "#1e1e1e"));
+    let stroke_width = element
+        .get("strokeWidth")
+        .and_then(|v| v.as_f64())
+        .unwrap_or(2.0);
+    let fill = fill_color(element);
+    let dash = match text(element, "strokeStyle", "solid") {
+        "dashed" => r#" stroke-dasharray="8 6""#,
+        "dotted" => r#" stroke-dasharray="2 6""#,
+        _ => "",
+    };
+
+    let body = match kind {
+        "rectangle" => {
+            let radius = if element.get("roundness").map(|r| !r.is_null()).unwrap_or(false) {
+                (width.min(height) * 0.25).min(32.0)
+            } else {
+                0.0
+            };
+            format!(
+                r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" rx="{:.2}" fill="{}" stroke="{}" stroke-width="{}"{}/>"#,
+                x, y, width, height, radius, fill, stroke, stroke_width, dash
+            )
+        }
+        "ellipse" => format!(
+            r#"<ellipse cx="{:.2}" cy="{:.2}" rx="{:.2}" ry="{:.2}" fill="{}" stroke="{}" stroke-width="{}"{}/>"#,
+            x + width / 2.0,
+            y + height / 2.0,
+            width / 2.0,
+            height / 2.0,
+            fill,
+            stroke,
+            stroke_width,
+            dash
+        ),
+        "diamond" => format!(
+            r#"<polygon points="{:.2},{:.2} {:.2},{:.2} {:.2},{:.2} {:.2},{:.2}" fill="{}" stroke="{}" stroke-width="{}"{}/>"#,
+            x + width / 2.0,
+            y,
+            x + width,
+            y + height / 2.0,
+            x + width / 2.0,
+            y + height,
+            x,
+            y + height / 2.0,
+            fill,
+            stroke,
+            stroke_width,
+            dash
+        ),
+        "line" | "arrow" | "freedraw" => {
+            let pts = points(element);
+            if pts.is_empty() {
+                return String::new();
+            }
+            let path: Vec<String> = pts
+                .iter()
+                .enumerate()
+                .map(|(i, (px, py))| {
+                    format!("{} {:.2} {:.2}", if i == 0 { "M" } else { "L" }, x + px, y + py)
+                })
+                .collect();
+            let mut svg = format!(
+                r#"<path d="{}" fill="none" stroke="{}" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"{}/>"#,
+                path.join(" "),
+                stroke,
+                stroke_width,
+                dash
+            );
+            if kind == "arrow" && pts.len() >= 2 {
+                let abs = |i: usize| (x + pts[i].0, y + pts[i].1);
+                let last = pts.len() - 1;
+                if !element.get("endArrowhead").map(|a| a.is_null()).unwrap_or(false) {
+                    svg.push_str(&arrowhead(abs(last), abs(last - 1), &stroke, stroke_width));
+                }
+                if element
+                    .get("startArrowhead")
+                    .map(|a| !a.is_null())
+                    .unwrap_or(false)
+                {
+                    svg.push_str(&arrowhead(abs(0), abs(1), &stroke, stroke_width));
+                }
+            }
+            svg
+        }
+        "text" => {
+            let font_size = element
+                .get("fontSize")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(20.0);
+            let line_height = element
+                .get("lineHeight")
+                .and_then(|v| v.as_f64())
+                .unwrap_or(1.25);
+            let (anchor, text_x) = match text(element, "textAlign", "left") {
+                "center" => ("middle", x + width / 2.0),
+                "right" => ("end", x + width),
+                _ => ("start", x),
+            };
+            let lines: Vec<String> = text(element, "text", "")
+                .lines()
+                .enumerate()
+                .map(|(i, line)| {
+                    format!(
+                        r#"<tspan x="{:.2}" y="{:.2}">{}</tspan>"#,
+                        text_x,
+                        y + font_size * line_height * i as f64 + font_size,
+                        escape_xml(line)
+                    )
+                })
+                .collect();
+            format!(
+                r#"<text font-family="{}" font-size="{}" fill="{}" text-anchor="{}">{}</text>"#,
+                font_family(element),
+                font_size,
+                stroke,
+                anchor,
+                lines.join("")
+            )
+        }
+        "image" => {
+            let data_url = element
+                .get("fileId")
+                .and_then(|id| id.as_str())
+                .and_then(|id| files?.get(id))
+                .and_then(|f| f.get("dataURL"))
+                .and_then(|d| d.as_str());
+            match data_url {
+                Some(href) => format!(
+                    r#"<image x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" href="{}" preserveAspectRatio="none"/>"#,
+                    x,
+                    y,
+                    width,
+                    height,
+                    escape_xml(href)
+                ),
+                None => String::new(),
+            }
+        }
+        "frame" | "magicframe" => {
+            let name = text(element, "name", "");
+            format!(
+                r##"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" rx="8" fill="none" stroke="#bbb" stroke-width="1"/><text x="{:.2}" y="{:.2}" font-family="Helvetica" font-size="14" fill="#999">{}</text>"##,
+                x,
+                y,
+                width,
+                height,
+                x,
+                y - 6.0,
+                escape_xml(name)
+            )
+        }
+        _ => return String::new(),
+    };
+
+    let opacity = element
+        .get("opacity")
+        .and_then(|o| o.as_f64())
+        .unwrap_or(100.0)
+        / 100.0;
+    let angle = num(element, "angle");
+
+    let mut attrs = String::new();
+    if opacity < 1.0 {
+        attrs.push_str(&format!(r#" opacity="{:.2}""#, opacity));
+    }
+    if angle != 0.0 {
+        attrs.push_str(&format!(
+            r#" transform="rotate({:.4} {:.2} {:.2})""#,
+            angle.to_degrees(),
+            x + width / 2.0,
+            y + height / 2.0
+        ));
+    }
+
+    if attrs.is_empty() {
+        body
+    } else {
+        format!("<g{}>{}</g>", attrs, body)
+    }
+}
+
+/// Renders the given elements into a standalone SVG document covering `bounds`
+pub fn render_svg(
+    elements: &[&Value],
+    files: Option<&Map<String, Value>>,
+    bounds: &Bounds,
+    background: Option<&str>,
+) -> String {
+    let mut svg = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{:.2} {:.2} {:.2} {:.2}" width="{:.0}" height="{:.0}">"#,
+        bounds.min_x,
+        bounds.min_y,
+        bounds.width(),
+        bounds.height(),
+        bounds.width(),
+        bounds.height()
+    );
+
+    if let Some(color) = background {
+        svg.push_str(&format!(
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}"/>"#,
+            bounds.min_x,
+            bounds.min_y,
+            bounds.width(),
+            bounds.height(),
+            escape_xml(color)
+        ));
+    }
+
+    for element in elements {
+        svg.push_str(&render_element(element, files));
+    }
+
+    svg.push_str("</svg>");
+    svg
+}
+
+/// Renders a repeated diagonal text watermark covering `bounds`
+pub fn render_watermark(bounds: &Bounds, text: &str) -> String {
+    let font_size = (bounds.width().min(bounds.height()) / 12.0).clamp(12.0, 48.0);
+    let step_x = font_size * (text.chars().count() as f64 * 0.6 + 4.0);
+    let step_y = font_size * 4.0;
+    let center_x = bounds.min_x + bounds.width() / 2.0;
+    let center_y = bounds.min_y + bounds.height() / 2.0;
+
+    let mut svg = format!(
+        r##"<g opacity="0.15" fill="#888" font-family="Helvetica" font-size="{:.0}" text-anchor="middle" transform="rotate(-30 {:.2} {:.2})" pointer-events="none">"##,
+        font_size, center_x, center_y
+    );
+    let text = escape_xml(text);
+    // Tile past the bounds so the rotated pattern still covers the corners
+    let mut y = bounds.min_y - bounds.height() / 2.0;
+    while y <= bounds.max_y + bounds.height() / 2.0 {
+        let mut x = bounds.min_x - bounds.width() / 2.0;
+        while x <= bounds.max_x + bounds.width() / 2.0 {
+            svg.push_str(&format!(r#"<text x="{:.2}" y="{:.2}">{}</text>"#, x, y, text));
+            x += step_x;
+        }
+        y += step_y;
+    }
+    svg.push_str("</g>");
+    svg
+}
@@ -0,0 +1,352 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+use crate::error::AppError;
+use crate::scene_schema::{self, SchemaWarning, Strictness, KNOWN_ELEMENT_TYPES};
+use crate::security;
+
+/// Properties whose absence is reported individually; other missing
+/// properties are plain style defaults and only counted
+const REQUIRED_ELEMENT_PROPERTIES: &[&str] = &[
+    "id", "seed", "version", "versionNonce", "x", "y", "width", "height", "text", "points",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SceneRepairResult {
+    /// The repaired scene as pretty-printed JSON
+    pub content: String,
+    /// Human-readable description of every fix that was applied
+    pub fixes: Vec<String>,
+    /// Whether the repaired scene passes Excalidraw content validation
+    pub valid: bool,
+    /// What strict element validation still finds in the repaired scene
+    pub warnings: Vec<SchemaWarning>,
+}
+
+/// Generates ids and seeds without an RNG dependency
+pub(crate) struct IdSource(u64);
+
+impl IdSource {
+    pub(crate) fn new() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        IdSource(nanos)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn seed(&mut self) -> u64 {
+        self.next() % i32::MAX as u64
+    }
+
+    fn id(&mut self) -> String {
+        format!("{:016x}", self.next())
+    }
+}
+
+/// Returns the contents of the first fenced code block, if any
+pub fn strip_markdown_fences(input: &str) -> Option<&str> {
+    let start = input.find("```")?;
+    let after_fence = &input[start + 3..];
+    // Skip the language tag line (```json)
+    let body_start = after_fence.find('\n').map(|i| i + 1).unwrap_or(0);
+    let body = &after_fence[body_start..];
+    let end = body.find("```").unwrap_or(body.len());
+    Some(body[..end].trim())
+}
+
+/// Drops the part of the input before the first `{`/`[` and after the matching last `}`/`]`
+pub fn trim_to_json(input: &str) -> &str {
+    let start = input.find(['{', '[']).unwrap_or(0);
+    let end = input.rfind(['}', ']']).map(|i| i + 1).unwrap_or(input.len());
+    if start < end { &input[start..end] } else { input }
+}
+
+/// Removes commas directly followed by `}` or `]`, ignoring string contents
+fn remove_trailing_commas(input: &str) -> (String, usize) {
+    let chars: Vec<char> = input.chars().collect();
+    let mut output = String::with_capacity(input.len());
+    let mut removed = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        if in_string {
+            output.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => {
+                in_string = true;
+                output.push(c);
+            }
+            ',' => {
+                let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+                if matches!(next, Some('}') | Some(']')) {
+                    removed += 1;
+                } else {
+                    output.push(c);
+                }
+            }
+            _ => output.push(c),
+        }
+    }
+
+    (output, removed)
+}
+
+fn parse_lenient(content: &str, fixes: &mut Vec<String>) -> Result<Value, AppError> {
+    let mut text = content.trim();
+
+    if let Some(inner) = strip_markdown_fences(text) {
+        fixes.push("Stripped markdown code fences".to_string());
+        text = inner;
+    }
+
+    let trimmed = trim_to_json(text);
+    if trimmed.len() != text.len() {
+        fixes.push("Removed text surrounding the JSON".to_string());
+    }
+
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Ok(value);
+    }
+
+    let (without_commas, removed) = remove_trailing_commas(trimmed);
+    let value = serde_json::from_str(&without_commas)?;
+    if removed > 0 {
+        fixes.push(format!("Removed {} trailing comma(s)", removed));
+    }
+    Ok(value)
+}
+
+fn fill_default(element: &mut Map<String, Value>, key: &str, value: Value) -> bool {
+    if element.get(key).is_none_or(|v| v.is_null() && !is_nullable(key)) {
+        element.insert(key.to_string(), value);
+        true
+    } else {
+        false
+    }
+}
+
+/// Properties Excalidraw explicitly allows to be `null`
+fn is_nullable(key: &str) -> bool {
+    matches!(
+        key,
+        "frameId"
+            | "roundness"
+            | "boundElements"
+            | "link"
+            | "containerId"
+            | "startArrowhead"
+            | "endArrowhead"
+    )
+}
+
+/// Fills missing properties of one element; returns the names of filled properties
+pub(crate) fn repair_element(
+    element: &mut Map<String, Value>,
+    kind: &str,
+    ids: &mut IdSource,
+) -> Vec<&'static str> {
+    let mut filled = Vec::new();
+    let mut fill = |element: &mut Map<String, Value>, key: &'static str, value: Value| {
+        if fill_default(element, key, value) {
+            filled.push(key);
+        }
+    };
+
+    fill(element, "id", Value::String(ids.id()));
+    fill(element, "seed", ids.seed().into());
+    fill(element, "version", 1.into());
+    fill(element, "versionNonce", ids.seed().into());
+    fill(element, "x", 0.into());
+    fill(element, "y", 0.into());
+    fill(element, "width", 0.into());
+    fill(element, "height", 0.into());
+    fill(element, "angle", 0.into());
+    fill(element, "strokeColor", "#1e1e1e".into());
+    fill(element, "backgroundColor", "transparent".into());
+    fill(element, "fillStyle", "solid".into());
+    fill(element, "strokeWidth", 2.into());
+    fill(element, "strokeStyle", "solid".into());
+    fill(element, "roughness", 1.into());
+    fill(element, "opacity", 100.into());
+    fill(element, "groupIds", Value::Array(vec![]));
+    fill(element, "frameId", Value::Null);
+    fill(element, "roundness", Value::Null);
+    fill(element, "boundElements", Value::Null);
+    fill(element, "updated", 1.into());
+    fill(element, "link", Value::Null);
+    fill(element, "locked", false.into());
+    fill(element, "isDeleted", false.into());
+
+    match kind {
+        "text" => {
+            fill(element, "text", "".into());
+            let text = element.get("text").cloned().unwrap_or_default();
+            fill(element, "originalText", text);
+            fill(element, "fontSize", 20.into());
+            fill(element, "fontFamily", 1.into());
+            fill(element, "textAlign", "left".into());
+            fill(element, "verticalAlign", "top".into());
+            fill(element, "containerId", Value::Null);
+            fill(element, "autoResize", true.into());
+            fill(element, "lineHeight", 1.25.into());
+        }
+        "line" | "arrow" | "freedraw" => {
+            let width = element.get("width").and_then(|w| w.as_f64()).unwrap_or(0.0);
+            let height = element.get("height").and_then(|h| h.as_f64()).unwrap_or(0.0);
+            fill(element, "points", serde_json::json!([[0, 0], [width, height]]));
+            if kind == "arrow" {
+                fill(element, "startArrowhead", Value::Null);
+                fill(element, "endArrowhead", "arrow".into());
+            }
+        }
+        _ => {}
+    }
+
+    filled
+}
+
+/// Parses (leniently) and repairs an AI-generated scene, returning the fixed
+/// scene together with the list of applied fixes
+pub fn validate_and_repair_scene(content: &str) -> Result<SceneRepairResult, AppError> {
+    let mut fixes = Vec::new();
+    let value = parse_lenient(content, &mut fixes)?;
+    let mut ids = IdSource::new();
+
+    // A bare element array is wrapped into a scene
+    let mut scene = match value {
+        Value::Array(elements) => {
+            fixes.push("Wrapped bare element array into a scene".to_string());
+            let mut scene = Map::new();
+            scene.insert("elements".to_string(), Value::Array(elements));
+            scene
+        }
+        Value::Object(scene) => scene,
+        _ => return Err(AppError::InvalidInput("Content is not a JSON object or array".to_string())),
+    };
+
+    if scene.get("type").and_then(|t| t.as_str()) != Some("excalidraw") {
+        scene.insert("type".to_string(), "excalidraw".into());
+        fixes.push("Set scene type to \"excalidraw\"".to_string());
+    }
+    if !scene.get("version").is_some_and(|v| v.is_number()) {
+        scene.insert("version".to_string(), 2.into());
+        fixes.push("Set missing scene version".to_string());
+    }
+    if !scene.get("elements").is_some_and(|e| e.is_array()) {
+        scene.insert("elements".to_string(), Value::Array(vec![]));
+        fixes.push("Added missing elements array".to_string());
+    }
+    if !scene.get("appState").is_some_and(|a| a.is_object()) {
+        scene.insert(
+            "appState".to_string(),
+            serde_json::json!({"viewBackgroundColor": "#ffffff"}),
+        );
+    }
+    if !scene.get("files").is_some_and(|f| f.is_object()) {
+        scene.insert("files".to_string(), Value::Object(Map::new()));
+    }
+
+    let elements = scene
+        .get_mut("elements")
+        .and_then(|e| e.as_array_mut())
+        .expect("elements array was ensured above");
+
+    let mut repaired = Vec::with_capacity(elements.len());
+    let mut seen_ids = HashSet::new();
+    let mut default_fills = 0;
+    for (index, element) in elements.drain(..).enumerate() {
+        let Value::Object(mut element) = element else {
+            fixes.push(format!("Dropped element #{}: not an object", index));
+            continue;
+        };
+
+        let kind = element
+            .get("type")
+            .and_then(|t| t.as_str())
+            .unwrap_or("")
+            .to_string();
+        if !KNOWN_ELEMENT_TYPES.contains(&kind.as_str()) {
+            fixes.push(format!(
+                "Dropped element #{}: unknown type \"{}\"",
+                index, kind
+            ));
+            continue;
+        }
+
+        let filled = repair_element(&mut element, &kind, &mut ids);
+        let (required, defaults): (Vec<&str>, Vec<&str>) = filled
+            .into_iter()
+            .partition(|key| REQUIRED_ELEMENT_PROPERTIES.contains(key));
+        if !required.is_empty() {
+            fixes.push(format!(
+                "Filled missing {} on element #{} ({})",
+                required.join(", "),
+                index,
+                kind
+            ));
+        }
+        if !defaults.is_empty() {
+            default_fills += 1;
+        }
+
+        // Duplicate ids make Excalidraw merge elements; give later copies a new one
+        let id = element
+            .get("id")
+            .and_then(|i| i.as_str())
+            .unwrap_or_default()
+            .to_string();
+        if !seen_ids.insert(id.clone()) {
+            let new_id = ids.id();
+            fixes.push(format!(
+                "Replaced duplicate id \"{}\" on element #{}",
+                id, index
+            ));
+            seen_ids.insert(new_id.clone());
+            element.insert("id".to_string(), Value::String(new_id));
+        }
+
+        repaired.push(Value::Object(element));
+    }
+    fixes.extend(scene_schema::sanitize_elements(&mut repaired));
+    *elements = repaired;
+    if default_fills > 0 {
+        fixes.push(format!(
+            "Filled default style properties on {} element(s)",
+            default_fills
+        ));
+    }
+
+    let scene = Value::Object(scene);
+    let warnings = scene_schema::validate_elements(&scene, Strictness::Strict);
+    let content = serde_json::to_string_pretty(&scene)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+    let valid = security::validate_excalidraw_content(&content).is_ok();
+
+    Ok(SceneRepairResult {
+        content,
+        fixes,
+        valid,
+        warnings,
+    })
+}
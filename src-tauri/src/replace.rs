@@ -0,0 +1,143 @@
+use regex::{NoExpand, Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::AppError;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ReplaceOptions {
+    pub case_sensitive: bool,
+    /// Treat the query as a regular expression; `$1`-style groups work in the replacement
+    pub regex: bool,
+    pub whole_word: bool,
+    /// Without confirmation only a preview is returned and nothing is written
+    pub confirm: bool,
+    /// Limits a confirmed run to these files, e.g. the ones the user ticked in the preview
+    pub files: Option<Vec<String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ElementReplacement {
+    pub element_id: String,
+    pub before: String,
+    pub after: String,
+    pub matches: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileReplacement {
+    pub path: String,
+    pub matches: usize,
+    pub elements: Vec<ElementReplacement>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReplaceReport {
+    pub files: Vec<FileReplacement>,
+    pub total_matches: usize,
+    /// Set once the files have been written
+    pub written: bool,
+    /// Folder holding copies of the files as they were before writing
+    pub backup_dir: Option<String>,
+    /// Files that could not be read, parsed or written
+    pub failed: Vec<String>,
+}
+
+/// A compiled query plus how its replacement is to be interpreted
+pub struct Matcher {
+    regex: Regex,
+    expand: bool,
+}
+
+impl Matcher {
+    pub fn new(query: &str, options: &ReplaceOptions) -> Result<Self, AppError> {
+        if query.is_empty() {
+            return Err(AppError::InvalidInput("Search text is empty".to_string()));
+        }
+        let pattern = if options.regex {
+            query.to_string()
+        } else {
+            regex::escape(query)
+        };
+        let pattern = if options.whole_word {
+            format!(r"\b(?:{})\b", pattern)
+        } else {
+            pattern
+        };
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .build()
+            .map_err(|e| AppError::InvalidInput(format!("Invalid search pattern: {}", e)))?;
+        Ok(Self {
+            regex,
+            expand: options.regex,
+        })
+    }
+
+    fn replace(&self, text: &str, replacement: &str) -> String {
+        if self.expand {
+            self.regex.replace_all(text, replacement).into_owned()
+        } else {
+            self.regex.replace_all(text, NoExpand(replacement)).into_owned()
+        }
+    }
+}
+
+/// Replaces matches inside the text elements of a scene. Returns the new
+/// content and what changed, or `None` when nothing matched.
+pub fn replace_in_scene(
+    content: &str,
+    matcher: &Matcher,
+    replacement: &str,
+) -> Result<Option<(String, Vec<ElementReplacement>)>, AppError> {
+    let mut scene: Value = serde_json::from_str(content)?;
+    let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut()) else {
+        return Ok(None);
+    };
+
+    let mut changes = Vec::new();
+    for element in elements.iter_mut() {
+        let is_text = element.get("type").and_then(|t| t.as_str()) == Some("text");
+        let deleted = element.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false);
+        if !is_text || deleted {
+            continue;
+        }
+
+        // `originalText` is the unwrapped source of labels; `text` may contain wrap breaks
+        let text = element.get("text").and_then(|t| t.as_str()).unwrap_or("").to_string();
+        let source = element
+            .get("originalText")
+            .and_then(|t| t.as_str())
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| text.clone());
+        let matches = matcher.regex.find_iter(&source).count();
+        if matches == 0 {
+            continue;
+        }
+
+        let replaced = matcher.replace(&source, replacement);
+        let replaced_text = matcher.replace(&text, replacement);
+        // A match split by wrapping leaves `text` untouched; fall back to the source
+        element["text"] = Value::String(if replaced_text == text { replaced.clone() } else { replaced_text });
+        if element.get("originalText").is_some() {
+            element["originalText"] = Value::String(replaced.clone());
+        }
+        let version = element.get("version").and_then(|v| v.as_i64()).unwrap_or(1);
+        element["version"] = (version + 1).into();
+
+        changes.push(ElementReplacement {
+            element_id: element.get("id").and_then(|id| id.as_str()).unwrap_or("").to_string(),
+            before: source,
+            after: replaced,
+            matches,
+        });
+    }
+
+    if changes.is_empty() {
+        return Ok(None);
+    }
+    let content = serde_json::to_string_pretty(&scene)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize content: {}", e)))?;
+    Ok(Some((content, changes)))
+}
@@ -0,0 +1,66 @@
+use std::path::Path;
+use std::process::Command;
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_opener::OpenerExt;
+
+use crate::error::AppError;
+
+/// Shows `path` selected in Finder, Explorer or the desktop's file manager.
+/// Falls back to the opener plugin, and to opening the parent folder when
+/// selecting is not possible.
+pub fn reveal<R: Runtime>(app: &AppHandle<R>, path: &Path) -> Result<(), AppError> {
+    if select_in_file_manager(path) {
+        return Ok(());
+    }
+    if app.opener().reveal_item_in_dir(path).is_ok() {
+        return Ok(());
+    }
+
+    let folder = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+    app.opener()
+        .open_path(folder.to_string_lossy(), None::<&str>)
+        .map_err(|e| AppError::Internal(format!("Failed to open {}: {}", folder.display(), e)))
+}
+
+#[cfg(target_os = "macos")]
+fn select_in_file_manager(path: &Path) -> bool {
+    Command::new("open")
+        .arg("-R")
+        .arg(path)
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(target_os = "windows")]
+fn select_in_file_manager(path: &Path) -> bool {
+    // Explorer exits with 1 even when it worked, so only a failed spawn counts
+    let mut arg = std::ffi::OsString::from("/select,");
+    arg.push(path);
+    Command::new("explorer").arg(arg).spawn().is_ok()
+}
+
+/// The FileManager1 D-Bus interface, implemented by Nautilus, Dolphin, Nemo
+/// and most other file managers
+#[cfg(target_os = "linux")]
+fn select_in_file_manager(path: &Path) -> bool {
+    let Ok(url) = tauri::Url::from_file_path(path) else {
+        return false;
+    };
+    Command::new("dbus-send")
+        .args([
+            "--session",
+            "--dest=org.freedesktop.FileManager1",
+            "--type=method_call",
+            "/org/freedesktop/FileManager1",
+            "org.freedesktop.FileManager1.ShowItems",
+        ])
+        .arg(format!("array:string:{}", url))
+        .arg("string:")
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+fn select_in_file_manager(_path: &Path) -> bool {
+    false
+}
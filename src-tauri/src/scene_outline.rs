@@ -0,0 +1,231 @@
+use serde::de::{Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt;
+
+use crate::error::AppError;
+
+/// Scenes larger than this are checked while parsing instead of being built
+/// into a `Value` first, which for huge drawings doubles the memory they take
+pub const STREAMING_THRESHOLD: usize = 8 * 1024 * 1024;
+
+/// The `elements` field of a scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Elements {
+    Missing,
+    NotArray,
+    Array(usize),
+}
+
+/// What validation and the limits need to know about a scene: its header
+/// fields, how many elements it has and how big its embedded images are
+#[derive(Debug)]
+pub struct SceneOutline {
+    pub kind: Option<Value>,
+    pub version: Option<Value>,
+    pub elements: Elements,
+    /// Length of each embedded image's data URL, by file id
+    pub images: Vec<(String, u64)>,
+}
+
+impl SceneOutline {
+    /// Outlines `content`, streaming through it when it is large. Fails when
+    /// it is not a JSON object.
+    pub fn parse(content: &str) -> Result<Self, AppError> {
+        if content.len() > STREAMING_THRESHOLD {
+            match serde_json::from_str::<Streamed>(content) {
+                Ok(Streamed(outline)) => return Ok(outline),
+                // The streaming pass only follows well-formed scenes; odd
+                // ones take the slow path, which says what is wrong
+                Err(e) if e.is_data() => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let scene: Value = serde_json::from_str(content)?;
+        Self::of(&scene)
+    }
+
+    fn of(scene: &Value) -> Result<Self, AppError> {
+        let Some(scene) = scene.as_object() else {
+            return Err(AppError::InvalidInput("Content is not a JSON object".to_string()));
+        };
+        let elements = match scene.get("elements") {
+            None => Elements::Missing,
+            Some(Value::Array(elements)) => Elements::Array(elements.len()),
+            Some(_) => Elements::NotArray,
+        };
+        let images = scene
+            .get("files")
+            .and_then(|f| f.as_object())
+            .map(|files| {
+                files
+                    .iter()
+                    .map(|(id, file)| {
+                        let len = file.get("dataURL").and_then(|d| d.as_str()).map_or(0, str::len);
+                        (id.clone(), len as u64)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(Self {
+            kind: scene.get("type").cloned(),
+            version: scene.get("version").cloned(),
+            elements,
+            images,
+        })
+    }
+
+    pub fn element_count(&self) -> usize {
+        match self.elements {
+            Elements::Array(count) => count,
+            _ => 0,
+        }
+    }
+}
+
+/// A scene outlined in one pass; elements are counted and skipped, and of
+/// the embedded files only the length of their data is taken
+struct Streamed(SceneOutline);
+
+impl<'de> Deserialize<'de> for Streamed {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SceneVisitor;
+
+        impl<'de> Visitor<'de> for SceneVisitor {
+            type Value = SceneOutline;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an Excalidraw scene")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<SceneOutline, A::Error> {
+                let mut outline = SceneOutline {
+                    kind: None,
+                    version: None,
+                    elements: Elements::Missing,
+                    images: Vec::new(),
+                };
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "type" => outline.kind = Some(map.next_value()?),
+                        "version" => outline.version = Some(map.next_value()?),
+                        "elements" => outline.elements = Elements::Array(map.next_value::<ElementCount>()?.0),
+                        "files" => outline.images = map.next_value::<ImageSizes>()?.0,
+                        _ => {
+                            map.next_value::<IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(outline)
+            }
+        }
+
+        deserializer.deserialize_map(SceneVisitor).map(Streamed)
+    }
+}
+
+struct ElementCount(usize);
+
+impl<'de> Deserialize<'de> for ElementCount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CountVisitor;
+
+        impl<'de> Visitor<'de> for CountVisitor {
+            type Value = usize;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an array of elements")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<usize, A::Error> {
+                let mut count = 0;
+                while seq.next_element::<IgnoredAny>()?.is_some() {
+                    count += 1;
+                }
+                Ok(count)
+            }
+        }
+
+        deserializer.deserialize_seq(CountVisitor).map(ElementCount)
+    }
+}
+
+struct ImageSizes(Vec<(String, u64)>);
+
+impl<'de> Deserialize<'de> for ImageSizes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FilesVisitor;
+
+        impl<'de> Visitor<'de> for FilesVisitor {
+            type Value = Vec<(String, u64)>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of files")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut images = Vec::new();
+                while let Some((id, DataUrlLength(len))) = map.next_entry::<String, DataUrlLength>()? {
+                    images.push((id, len));
+                }
+                Ok(images)
+            }
+        }
+
+        deserializer.deserialize_map(FilesVisitor).map(ImageSizes)
+    }
+}
+
+/// Length of the `dataURL` of one embedded file
+struct DataUrlLength(u64);
+
+impl<'de> Deserialize<'de> for DataUrlLength {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FileVisitor;
+
+        impl<'de> Visitor<'de> for FileVisitor {
+            type Value = u64;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an embedded file")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<u64, A::Error> {
+                let mut len = 0;
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "dataURL" {
+                        len = map.next_value::<StrLength>()?.0;
+                    } else {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+                Ok(len)
+            }
+        }
+
+        deserializer.deserialize_map(FileVisitor).map(DataUrlLength)
+    }
+}
+
+/// Length of a string, read without keeping it
+struct StrLength(u64);
+
+impl<'de> Deserialize<'de> for StrLength {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LengthVisitor;
+
+        impl<'de> Visitor<'de> for LengthVisitor {
+            type Value = u64;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<u64, E> {
+                Ok(value.len() as u64)
+            }
+        }
+
+        deserializer.deserialize_str(LengthVisitor).map(StrLength)
+    }
+}
@@ -0,0 +1,279 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+
+pub const KNOWN_ELEMENT_TYPES: &[&str] = &[
+    "rectangle",
+    "ellipse",
+    "diamond",
+    "line",
+    "arrow",
+    "freedraw",
+    "text",
+    "image",
+    "frame",
+    "magicframe",
+    "embeddable",
+    "iframe",
+];
+
+const COORDINATES: &[&str] = &["x", "y", "width", "height"];
+
+/// How picky element validation is. Lenient only reports what breaks
+/// Excalidraw; strict also reports what it silently patches up.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Strictness {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningCode {
+    NotAnObject,
+    MissingId,
+    DuplicateId,
+    UnknownType,
+    InvalidCoordinate,
+    MissingCoordinate,
+    MissingPoints,
+    DanglingReference,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SchemaWarning {
+    /// Position of the element in the `elements` array
+    pub index: usize,
+    pub element_id: Option<String>,
+    pub code: WarningCode,
+    pub message: String,
+}
+
+/// Where one element points at another by id
+#[derive(Debug, Clone, Copy)]
+enum Reference {
+    StartBinding,
+    EndBinding,
+    BoundElement,
+    Container,
+    Frame,
+}
+
+impl Reference {
+    fn name(self) -> &'static str {
+        match self {
+            Reference::StartBinding => "startBinding",
+            Reference::EndBinding => "endBinding",
+            Reference::BoundElement => "boundElements",
+            Reference::Container => "containerId",
+            Reference::Frame => "frameId",
+        }
+    }
+}
+
+/// Every id `element` refers to
+fn references(element: &Map<String, Value>) -> Vec<(Reference, &str)> {
+    let mut found = Vec::new();
+    for (reference, key) in [
+        (Reference::StartBinding, "startBinding"),
+        (Reference::EndBinding, "endBinding"),
+    ] {
+        if let Some(id) = element
+            .get(key)
+            .and_then(|b| b.get("elementId"))
+            .and_then(|i| i.as_str())
+        {
+            found.push((reference, id));
+        }
+    }
+    if let Some(bound) = element.get("boundElements").and_then(|b| b.as_array()) {
+        found.extend(
+            bound
+                .iter()
+                .filter_map(|b| b.get("id").and_then(|i| i.as_str()))
+                .map(|id| (Reference::BoundElement, id)),
+        );
+    }
+    for (reference, key) in [(Reference::Container, "containerId"), (Reference::Frame, "frameId")] {
+        if let Some(id) = element.get(key).and_then(|i| i.as_str()) {
+            found.push((reference, id));
+        }
+    }
+    found
+}
+
+fn element_id(element: &Map<String, Value>) -> Option<&str> {
+    element
+        .get("id")
+        .and_then(|i| i.as_str())
+        .filter(|id| !id.is_empty())
+}
+
+fn is_deleted(element: &Map<String, Value>) -> bool {
+    element.get("isDeleted").and_then(|d| d.as_bool()) == Some(true)
+}
+
+/// Ids that references may resolve to; strict mode doesn't count deleted elements
+fn live_ids(elements: &[Value], strictness: Strictness) -> HashSet<&str> {
+    elements
+        .iter()
+        .filter_map(|e| e.as_object())
+        .filter(|e| strictness == Strictness::Lenient || !is_deleted(e))
+        .filter_map(element_id)
+        .collect()
+}
+
+/// Checks every element of a parsed scene. The top-level fields are
+/// `security::validate_excalidraw_content`'s job; a scene without an
+/// elements array has no warnings here.
+pub fn validate_elements(scene: &Value, strictness: Strictness) -> Vec<SchemaWarning> {
+    let Some(elements) = scene.get("elements").and_then(|e| e.as_array()) else {
+        return Vec::new();
+    };
+    let ids = live_ids(elements, strictness);
+    let mut seen = HashSet::new();
+    let mut warnings = Vec::new();
+
+    for (index, element) in elements.iter().enumerate() {
+        let Some(element) = element.as_object() else {
+            warnings.push(SchemaWarning {
+                index,
+                element_id: None,
+                code: WarningCode::NotAnObject,
+                message: format!("Element #{} is not an object", index),
+            });
+            continue;
+        };
+        let id = element_id(element);
+        let mut warn = |code: WarningCode, message: String| {
+            warnings.push(SchemaWarning {
+                index,
+                element_id: id.map(|id| id.to_string()),
+                code,
+                message,
+            });
+        };
+
+        match id {
+            None => warn(WarningCode::MissingId, format!("Element #{} has no id", index)),
+            Some(id) if !seen.insert(id) => warn(
+                WarningCode::DuplicateId,
+                format!("Element #{} reuses id \"{}\"", index, id),
+            ),
+            Some(_) => {}
+        }
+
+        let kind = element.get("type").and_then(|t| t.as_str()).unwrap_or("");
+        if !KNOWN_ELEMENT_TYPES.contains(&kind) {
+            warn(
+                WarningCode::UnknownType,
+                format!("Element #{} has unknown type \"{}\"", index, kind),
+            );
+        }
+
+        for &key in COORDINATES {
+            match element.get(key) {
+                Some(v) if v.as_f64().is_some_and(f64::is_finite) => {}
+                None if strictness == Strictness::Lenient => {}
+                None => warn(
+                    WarningCode::MissingCoordinate,
+                    format!("Element #{} has no {}", index, key),
+                ),
+                Some(v) => warn(
+                    WarningCode::InvalidCoordinate,
+                    format!("Element #{} has non-numeric {}: {}", index, key, v),
+                ),
+            }
+        }
+
+        if strictness == Strictness::Strict
+            && matches!(kind, "line" | "arrow" | "freedraw")
+            && !element.get("points").is_some_and(|p| p.is_array())
+        {
+            warn(
+                WarningCode::MissingPoints,
+                format!("Element #{} ({}) has no points", index, kind),
+            );
+        }
+
+        for (reference, target) in references(element) {
+            if !ids.contains(target) {
+                warn(
+                    WarningCode::DanglingReference,
+                    format!(
+                        "Element #{} {} refers to missing element \"{}\"",
+                        index,
+                        reference.name(),
+                        target
+                    ),
+                );
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Makes elements safe to load: coordinates become numbers and references
+/// to missing or deleted elements are dropped. Returns a description of each fix.
+pub fn sanitize_elements(elements: &mut [Value]) -> Vec<String> {
+    let ids: HashSet<String> = live_ids(elements, Strictness::Strict)
+        .into_iter()
+        .map(|id| id.to_string())
+        .collect();
+    let mut fixes = Vec::new();
+
+    for (index, element) in elements.iter_mut().enumerate() {
+        let Some(element) = element.as_object_mut() else {
+            continue;
+        };
+
+        for &key in COORDINATES {
+            let Some(value) = element.get(key) else {
+                continue;
+            };
+            if value.as_f64().is_some_and(f64::is_finite) {
+                continue;
+            }
+            // Models like to quote numbers
+            let number = value
+                .as_str()
+                .and_then(|s| s.trim().parse::<f64>().ok())
+                .filter(|n| n.is_finite())
+                .unwrap_or(0.0);
+            fixes.push(format!(
+                "Replaced non-numeric {} {} with {} on element #{}",
+                key, value, number, index
+            ));
+            element.insert(key.to_string(), number.into());
+        }
+
+        let dangling: Vec<(Reference, String)> = references(element)
+            .into_iter()
+            .filter(|(_, target)| !ids.contains(*target))
+            .map(|(reference, target)| (reference, target.to_string()))
+            .collect();
+        for (reference, target) in dangling {
+            match reference {
+                Reference::BoundElement => {
+                    if let Some(bound) = element.get_mut("boundElements").and_then(|b| b.as_array_mut()) {
+                        bound.retain(|b| b.get("id").and_then(|i| i.as_str()) != Some(target.as_str()));
+                    }
+                }
+                _ => {
+                    element.insert(reference.name().to_string(), Value::Null);
+                }
+            }
+            fixes.push(format!(
+                "Removed {} reference to missing element \"{}\" on element #{}",
+                reference.name(),
+                target,
+                index
+            ));
+        }
+    }
+
+    fixes
+}
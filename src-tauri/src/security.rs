@@ -1,7 +1,11 @@
 use std::path::{Path, PathBuf};
 
+use crate::compression;
+use crate::error::AppError;
+use crate::scene_outline::{Elements, SceneOutline};
+
 /// Validates that a path is safe to access (no path traversal attacks)
-pub fn validate_path(path: &Path, allowed_base: Option<&Path>) -> Result<PathBuf, String> {
+pub fn validate_path(path: &Path, allowed_base: Option<&Path>) -> Result<PathBuf, AppError> {
     // Check for actual path traversal patterns in components, not just string containment
     let components: Vec<String> = path
         .components()
@@ -11,78 +15,99 @@ pub fn validate_path(path: &Path, allowed_base: Option<&Path>) -> Result<PathBuf
     // Check each component for suspicious patterns
     for component in &components {
         if component == ".." || component == "~" {
-            return Err("Path contains suspicious patterns".to_string());
+            return Err(AppError::InvalidPath("Path contains suspicious patterns".to_string()));
         }
     }
     
     // Canonicalize the path to resolve symlinks and relative components
     let canonical_path = match path.canonicalize() {
         Ok(cp) => cp,
-        Err(_) => {
-            // If canonicalization fails (e.g., for relative paths that don't exist yet),
-            // return the original path since we've already validated the components
-            return Ok(path.to_path_buf());
-        }
+        // If canonicalization fails (e.g., for relative paths that don't exist yet),
+        // return the original path since we've already validated the components
+        Err(_) if allowed_base.is_none() => return Ok(path.to_path_buf()),
+        // Against a base, a path that doesn't exist yet is checked through its
+        // nearest existing ancestor
+        Err(_) => canonicalize_existing(path).ok_or_else(|| {
+            AppError::InvalidPath(format!("Cannot resolve path: {}", path.display()))
+        })?,
     };
     
     // If an allowed base directory is specified, ensure the path is within it
     if let Some(base) = allowed_base {
         let canonical_base = base
             .canonicalize()
-            .map_err(|e| format!("Failed to canonicalize base path: {}", e))?;
+            .map_err(|e| AppError::from(e).with_context("Failed to canonicalize base path"))?;
         
         if !canonical_path.starts_with(&canonical_base) {
-            return Err("Path traversal detected: path is outside allowed directory".to_string());
+            return Err(AppError::InvalidPath(
+                "Path traversal detected: path is outside allowed directory".to_string(),
+            ));
         }
     }
     
     Ok(canonical_path)
 }
 
-/// Validates that a file has the expected .excalidraw extension
-pub fn validate_excalidraw_file(path: &Path) -> Result<(), String> {
+/// Canonicalizes the longest existing prefix of `path` and appends the rest
+fn canonicalize_existing(path: &Path) -> Option<PathBuf> {
+    let mut missing = Vec::new();
+    let mut current = path;
+    loop {
+        if let Ok(canonical) = current.canonicalize() {
+            return Some(missing.iter().rev().fold(canonical, |path, name| path.join(name)));
+        }
+        missing.push(current.file_name()?);
+        current = current.parent()?;
+    }
+}
+
+/// Validates that a file has the expected .excalidraw (or .excalidraw.gz) extension
+pub fn validate_excalidraw_file(path: &Path) -> Result<(), AppError> {
+    if compression::is_compressed(path) {
+        return Ok(());
+    }
     match path.extension() {
         Some(ext) if ext == "excalidraw" => Ok(()),
-        Some(ext) => Err(format!("Invalid file extension: expected .excalidraw, got .{}", ext.to_string_lossy())),
-        None => Err("File has no extension".to_string()),
+        Some(ext) => Err(AppError::InvalidPath(format!(
+            "Invalid file extension: expected .excalidraw, got .{}",
+            ext.to_string_lossy()
+        ))),
+        None => Err(AppError::InvalidPath("File has no extension".to_string())),
     }
 }
 
-/// Validates JSON content to ensure it's a valid Excalidraw file
-pub fn validate_excalidraw_content(content: &str) -> Result<(), String> {
-    let json: serde_json::Value = serde_json::from_str(content)
-        .map_err(|e| format!("Invalid JSON: {}", e))?;
-    
-    // Check for required Excalidraw fields
-    let obj = json.as_object()
-        .ok_or("Content is not a JSON object")?;
+/// Validates JSON content to ensure it's a valid Excalidraw file; large
+/// scenes are checked while parsing, without holding a parsed copy
+pub fn validate_excalidraw_content(content: &str) -> Result<(), AppError> {
+    let invalid = |message: String| Err(AppError::InvalidInput(message));
+    let scene = SceneOutline::parse(content)?;
     
     // Validate type field
-    match obj.get("type") {
+    match &scene.kind {
         Some(t) if t == "excalidraw" => {},
-        Some(t) => return Err(format!("Invalid type field: expected 'excalidraw', got {:?}", t)),
-        None => return Err("Missing required 'type' field".to_string()),
+        Some(t) => return invalid(format!("Invalid type field: expected 'excalidraw', got {:?}", t)),
+        None => return invalid("Missing required 'type' field".to_string()),
     }
     
     // Validate version field
-    match obj.get("version") {
+    match &scene.version {
         Some(v) if v.is_number() => {},
-        Some(_) => return Err("Version field must be a number".to_string()),
-        None => return Err("Missing required 'version' field".to_string()),
+        Some(_) => return invalid("Version field must be a number".to_string()),
+        None => return invalid("Missing required 'version' field".to_string()),
     }
     
     // Validate elements field
-    match obj.get("elements") {
-        Some(e) if e.is_array() => {},
-        Some(_) => return Err("Elements field must be an array".to_string()),
-        None => return Err("Missing required 'elements' field".to_string()),
+    match scene.elements {
+        Elements::Array(_) => {},
+        Elements::NotArray => return invalid("Elements field must be an array".to_string()),
+        Elements::Missing => return invalid("Missing required 'elements' field".to_string()),
     }
     
     Ok(())
 }
 
 /// Safely joins a filename to a directory path
-pub fn safe_path_join(base: &Path, file_name: &str) -> Result<PathBuf, String> {
+pub fn safe_path_join(base: &Path, file_name: &str) -> Result<PathBuf, AppError> {
     // Remove any path separators from the filename to prevent directory traversal
     let clean_name = file_name
         .replace('/', "_")
@@ -90,8 +115,38 @@ pub fn safe_path_join(base: &Path, file_name: &str) -> Result<PathBuf, String> {
         .replace("..", "_");
     
     if clean_name.is_empty() {
-        return Err("Invalid filename".to_string());
+        return Err(AppError::InvalidPath("Invalid filename".to_string()));
     }
     
     Ok(base.join(clean_name))
-}
\ No newline at end of file
+}
+
+/// Resolves a `/`-separated path stored in an archive below `base`. Absolute
+/// paths, drive prefixes and `..` are refused rather than cleaned up, so
+/// entries cannot be written outside `base` (zip slip)
+pub fn safe_archive_path(base: &Path, entry_name: &str) -> Result<PathBuf, AppError> {
+    let unsafe_path = || AppError::InvalidPath(format!("Unsafe path in archive: {}", entry_name));
+    if entry_name.starts_with(['/', '\\']) || entry_name.contains('\0') {
+        return Err(unsafe_path());
+    }
+
+    let mut path = base.to_path_buf();
+    let mut depth = 0;
+    for component in entry_name.split(['/', '\\']) {
+        match component {
+            "" | "." => continue,
+            ".." | "~" => return Err(unsafe_path()),
+            // Drive letters and alternate data streams on Windows
+            c if c.contains(':') => return Err(unsafe_path()),
+            c => {
+                path.push(c);
+                depth += 1;
+            }
+        }
+    }
+
+    if depth == 0 {
+        return Err(AppError::InvalidPath(format!("Empty path in archive: {}", entry_name)));
+    }
+    Ok(path)
+}
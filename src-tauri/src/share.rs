@@ -0,0 +1,108 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::error::AppError;
+
+/// Store file holding per-file share permissions, keyed by absolute file path
+pub const STORE_FILE: &str = "share_permissions.json";
+pub const STORE_KEY: &str = "share_permissions";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareRole {
+    /// Rendered output only
+    #[default]
+    Viewer,
+    /// Rendered output plus the editable source scene
+    Editor,
+}
+
+/// Permission metadata attached to a drawing when it leaves the workspace
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SharePermissions {
+    #[serde(default)]
+    pub role: ShareRole,
+    /// Last day the export may be viewed, as `YYYY-MM-DD`
+    #[serde(default)]
+    pub expires_at: Option<String>,
+    /// Text stamped across every rendered page
+    #[serde(default)]
+    pub watermark: Option<String>,
+}
+
+pub type PermissionMap = HashMap<String, SharePermissions>;
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parses a `YYYY-MM-DD` date (a trailing time part is ignored) into days since the epoch
+pub fn parse_date(date: &str) -> Result<i64, AppError> {
+    let invalid = || AppError::InvalidInput(format!("Invalid date (expected YYYY-MM-DD): {}", date));
+    let day_part = date.trim().get(..10).ok_or_else(invalid)?;
+    let mut parts = day_part.split('-').map(|p| p.parse::<i64>());
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(y)), Some(Ok(m)), Some(Ok(d))) if (1..=12).contains(&m) && (1..=31).contains(&d) => {
+            Ok(days_from_civil(y, m, d))
+        }
+        _ => Err(invalid()),
+    }
+}
+
+fn today() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64 / 86_400)
+        .unwrap_or(0)
+}
+
+impl SharePermissions {
+    /// Fails when the expiry date is malformed or already past
+    pub fn check_not_expired(&self) -> Result<(), AppError> {
+        let Some(expires_at) = self.expires_at.as_deref().filter(|d| !d.trim().is_empty()) else {
+            return Ok(());
+        };
+        if parse_date(expires_at)? < today() {
+            return Err(AppError::PermissionDenied(format!("Share permissions expired on {}", expires_at)));
+        }
+        Ok(())
+    }
+
+    pub fn watermark_text(&self) -> Option<&str> {
+        self.watermark.as_deref().filter(|w| !w.trim().is_empty())
+    }
+
+    /// Combines the permissions of several files into deck-level ones:
+    /// the most restrictive role and the earliest expiry win
+    pub fn most_restrictive<'a>(all: impl Iterator<Item = &'a SharePermissions>) -> Option<SharePermissions> {
+        all.fold(None, |acc: Option<SharePermissions>, p| {
+            let Some(acc) = acc else {
+                return Some(p.clone());
+            };
+            let role = if acc.role == ShareRole::Viewer || p.role == ShareRole::Viewer {
+                ShareRole::Viewer
+            } else {
+                ShareRole::Editor
+            };
+            let expires_at = match (&acc.expires_at, &p.expires_at) {
+                (Some(a), Some(b)) => {
+                    let earlier = parse_date(a).unwrap_or(i64::MAX) <= parse_date(b).unwrap_or(i64::MAX);
+                    Some(if earlier { a.clone() } else { b.clone() })
+                }
+                (a, b) => a.clone().or_else(|| b.clone()),
+            };
+            Some(SharePermissions {
+                role,
+                expires_at,
+                watermark: acc.watermark.or_else(|| p.watermark.clone()),
+            })
+        })
+    }
+}
@@ -0,0 +1,352 @@
+use serde::Serialize;
+use std::fs;
+use std::io::Cursor;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::archive;
+use crate::branding::ExportBranding;
+use crate::error::AppError;
+use crate::ignores::IgnoreRules;
+use crate::render::escape_xml;
+use crate::share::{PermissionMap, ShareRole};
+use crate::webdav::percent_decode;
+use crate::{compression, export, paths, security};
+
+/// Emitted to every window when the server starts or stops
+pub const STATUS_EVENT: &str = "share-server-status";
+
+/// Port used when `start_share_server` names none
+pub const DEFAULT_PORT: u16 = 8765;
+
+/// PNG previews are rendered at twice the drawing's size, up to this many
+/// pixels on their longer side
+const PNG_SCALE: f32 = 2.0;
+const MAX_PNG_SIDE: f32 = 4096.0;
+
+/// Answer of `get_share_server_status` and payload of `STATUS_EVENT`
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ShareServerStatus {
+    pub running: bool,
+    pub directory: Option<String>,
+    pub port: Option<u16>,
+    /// Addresses to hand out, token included
+    pub urls: Vec<String>,
+    pub token: Option<String>,
+    pub started_at: Option<i64>,
+    /// Requests answered so far, including refused ones
+    pub requests: u64,
+}
+
+/// What the server may hand out, fixed when it starts
+pub struct ShareContext {
+    pub root: PathBuf,
+    pub ignore: IgnoreRules,
+    /// Per-file share permissions: viewers get previews only, expired files
+    /// are refused and watermarks are stamped on previews
+    pub permissions: PermissionMap,
+    pub branding: Option<ExportBranding>,
+}
+
+pub struct ShareServer {
+    server: Arc<Server>,
+    thread: Option<JoinHandle<()>>,
+    requests: Arc<AtomicU64>,
+    status: ShareServerStatus,
+}
+
+/// 128 random bits, hex-encoded
+pub fn new_token() -> Result<String, AppError> {
+    let mut bytes = [0u8; 16];
+    getrandom::fill(&mut bytes).map_err(|e| AppError::Internal(format!("No randomness available: {}", e)))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// The address other machines reach this one at. Connecting a UDP socket
+/// sends nothing; it only picks the interface of the default route.
+pub fn lan_address() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 80)).ok()?;
+    socket.local_addr().ok().map(|addr| addr.ip()).filter(|ip| !ip.is_loopback())
+}
+
+/// Compares without stopping at the first difference, so response times
+/// reveal nothing about the token
+pub fn token_matches(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+impl ShareServer {
+    /// Listens on every interface at `port`; 0 picks a free port
+    pub fn start(context: ShareContext, directory: String, port: u16, now_millis: i64) -> Result<Self, AppError> {
+        let server = Server::http(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))
+            .map_err(|e| AppError::Network(format!("Failed to listen on port {}: {}", port, e)))?;
+        let port = server
+            .server_addr()
+            .to_ip()
+            .map(|addr| addr.port())
+            .unwrap_or(port);
+        let token = new_token()?;
+        let urls = lan_address()
+            .into_iter()
+            .chain([IpAddr::V4(Ipv4Addr::LOCALHOST)])
+            .map(|ip| format!("http://{}/?token={}", SocketAddr::new(ip, port), token))
+            .collect();
+
+        let server = Arc::new(server);
+        let requests = Arc::new(AtomicU64::new(0));
+        let thread = {
+            let server = server.clone();
+            let requests = requests.clone();
+            let token = token.clone();
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    requests.fetch_add(1, Ordering::Relaxed);
+                    handle(request, &context, &token);
+                }
+            })
+        };
+
+        Ok(Self {
+            server,
+            thread: Some(thread),
+            requests,
+            status: ShareServerStatus {
+                running: true,
+                directory: Some(directory),
+                port: Some(port),
+                urls,
+                token: Some(token),
+                started_at: Some(now_millis),
+                requests: 0,
+            },
+        })
+    }
+
+    pub fn status(&self) -> ShareServerStatus {
+        ShareServerStatus {
+            requests: self.requests.load(Ordering::Relaxed),
+            ..self.status.clone()
+        }
+    }
+
+    /// Stops accepting requests and waits for the one being answered
+    pub fn stop(mut self) {
+        self.server.unblock();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+fn respond(status: u16, content_type: &str, body: Vec<u8>) -> HttpResponse {
+    let mut response = Response::from_data(body).with_status_code(status);
+    for (name, value) in [
+        ("Content-Type", content_type),
+        ("Cache-Control", "no-store"),
+        ("X-Content-Type-Options", "nosniff"),
+        // Drawings may embed SVG; keep scripts in them from running
+        ("Content-Security-Policy", "default-src 'none'; img-src 'self' data:; style-src 'unsafe-inline'"),
+    ] {
+        if let Ok(header) = Header::from_bytes(name, value) {
+            response.add_header(header);
+        }
+    }
+    response
+}
+
+fn error(status: u16, message: &str) -> HttpResponse {
+    respond(status, "text/plain; charset=utf-8", message.as_bytes().to_vec())
+}
+
+fn handle(request: Request, context: &ShareContext, token: &str) {
+    let url = request.url().to_string();
+    let response = if *request.method() != Method::Get {
+        error(405, "Only GET requests are served")
+    } else {
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let given = query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("token="))
+            .map(percent_decode)
+            .unwrap_or_default();
+        if token_matches(&given, token) {
+            route(&percent_decode(path), context, token)
+        } else {
+            error(401, "Missing or wrong access token")
+        }
+    };
+    if let Err(e) = request.respond(response) {
+        tracing::debug!("Failed to answer share request {}: {}", url, e);
+    }
+}
+
+fn route(path: &str, context: &ShareContext, token: &str) -> HttpResponse {
+    if path == "/" {
+        return index(context, token);
+    }
+    let (kind, relative) = match path.trim_start_matches('/').split_once('/') {
+        Some((kind @ ("view" | "svg" | "png" | "raw"), relative)) => (kind, relative),
+        _ => return error(404, "Not found"),
+    };
+    let file = match resolve(context, relative) {
+        Ok(file) => file,
+        Err((status, message)) => return error(status, &message),
+    };
+
+    let key = paths::normalize(&file);
+    let permissions = context.permissions.get(&key);
+    if let Some(Err(e)) = permissions.map(|p| p.check_not_expired()) {
+        return error(410, &e.to_string());
+    }
+    let is_drawing = compression::is_drawing(&file);
+    let render = || {
+        let content = compression::read_to_string(&file)?;
+        let watermark = permissions.and_then(|p| p.watermark_text());
+        export::render_file_svg(&content, context.branding.as_ref(), watermark)
+    };
+    match kind {
+        "view" => view(relative, is_drawing, token),
+        "svg" if is_drawing => match render() {
+            Ok(svg) => respond(200, "image/svg+xml", svg.into_bytes()),
+            Err(e) => error(500, &e.to_string()),
+        },
+        "png" if is_drawing => match render().and_then(|svg| export::svg_to_png(&svg, PNG_SCALE, MAX_PNG_SIDE)) {
+            Ok((png, _, _)) => respond(200, "image/png", png),
+            Err(e) => error(500, &e.to_string()),
+        },
+        "svg" | "png" => error(404, "Only drawings have previews"),
+        _ if is_drawing && permissions.is_some_and(|p| p.role == ShareRole::Viewer) => {
+            error(403, "This drawing is shared as a preview only")
+        }
+        // Compressed drawings are served as the JSON they hold
+        _ if is_drawing => match compression::read_to_string(&file) {
+            Ok(content) => respond(200, "application/json", content.into_bytes()),
+            Err(e) => error(500, &e.to_string()),
+        },
+        _ => match fs::read(&file) {
+            Ok(content) => respond(200, content_type(relative), content),
+            Err(e) => error(500, &e.to_string()),
+        },
+    }
+}
+
+/// A drawing or image below the shared folder; refuses traversal, symlinks
+/// out of the folder, ignored paths and every other file type
+fn resolve(context: &ShareContext, relative: &str) -> Result<PathBuf, (u16, String)> {
+    let not_found = || (404, "Not found".to_string());
+    let path = security::safe_archive_path(&context.root, relative).map_err(|e| (400, e.to_string()))?;
+    let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    if !(compression::is_drawing(&path) || archive::is_asset(&file_name)) {
+        return Err(not_found());
+    }
+    let canonical = path.canonicalize().map_err(|_| not_found())?;
+    let root = context.root.canonicalize().map_err(|_| not_found())?;
+    if !canonical.starts_with(&root) || !canonical.is_file() || context.ignore.is_ignored(&path, false) {
+        return Err(not_found());
+    }
+    Ok(path)
+}
+
+fn content_type(name: &str) -> &'static str {
+    match Path::new(name).extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+        Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+/// `/<kind>/<path>?token=…` with each path segment percent-encoded
+fn link(kind: &str, relative: &str, token: &str) -> String {
+    let encoded: Vec<String> = relative
+        .split('/')
+        .map(|segment| {
+            segment
+                .bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+                    _ => format!("%{:02X}", b),
+                })
+                .collect()
+        })
+        .collect();
+    format!("/{}/{}?token={}", kind, encoded.join("/"), token)
+}
+
+fn page(title: &str, body: &str) -> HttpResponse {
+    let html = format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><meta name=\"viewport\" content=\"width=device-width\">\
+         <title>{}</title><style>body{{font-family:system-ui,sans-serif;margin:2rem;color:#222}}\
+         li{{margin:.3rem 0}}a{{color:#4263eb}}img{{max-width:100%;border:1px solid #ddd}}</style></head>\
+         <body>{}</body></html>",
+        escape_xml(title),
+        body
+    );
+    respond(200, "text/html; charset=utf-8", html.into_bytes())
+}
+
+fn index(context: &ShareContext, token: &str) -> HttpResponse {
+    let mut entries = Vec::new();
+    if let Err(e) = archive::collect(&context.root, "", &context.ignore, true, Path::new(""), &mut entries) {
+        return error(500, &e.to_string());
+    }
+    let items: String = entries
+        .iter()
+        .filter(|entry| compression::is_drawing(Path::new(&entry.name)) || archive::is_asset(&entry.name))
+        .map(|entry| {
+            format!(
+                "<li><a href=\"{}\">{}</a></li>",
+                escape_xml(&link("view", &entry.name, token)),
+                escape_xml(&entry.name)
+            )
+        })
+        .collect();
+    let title = context
+        .root
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    page(&title, &format!("<h1>{}</h1><ul>{}</ul>", escape_xml(&title), items))
+}
+
+/// A drawing's SVG preview with links to it, its PNG rendering and the
+/// source, or an image
+fn view(relative: &str, is_drawing: bool, token: &str) -> HttpResponse {
+    let image = if is_drawing {
+        link("svg", relative, token)
+    } else {
+        link("raw", relative, token)
+    };
+    let mut links = format!(
+        "<a href=\"{}\">{}</a>",
+        escape_xml(&link("raw", relative, token)),
+        if is_drawing { "Source" } else { "Download" }
+    );
+    if is_drawing {
+        links = format!(
+            "<a href=\"{}\">SVG</a> · <a href=\"{}\">PNG</a> · {}",
+            escape_xml(&image),
+            escape_xml(&link("png", relative, token)),
+            links
+        );
+    }
+    page(
+        relative,
+        &format!(
+            "<p><a href=\"/?token={}\">All files</a></p><h2>{}</h2><p>{}</p><img src=\"{}\" alt=\"\">",
+            token,
+            escape_xml(relative),
+            links,
+            escape_xml(&image)
+        ),
+    )
+}
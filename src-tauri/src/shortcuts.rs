@@ -0,0 +1,192 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::error::AppError;
+
+#[cfg(target_os = "macos")]
+const FULLSCREEN: &str = "Ctrl+Cmd+F";
+#[cfg(not(target_os = "macos"))]
+const FULLSCREEN: &str = "F11";
+
+/// Menu commands that take a keyboard shortcut, with their default accelerators.
+/// Quit, Minimize and Close Window are system items with fixed keys on macOS.
+pub const DEFAULTS: &[(&str, &str)] = &[
+    ("open_directory", "CmdOrCtrl+O"),
+    ("new_file", "CmdOrCtrl+N"),
+    ("save", "CmdOrCtrl+S"),
+    ("save_as", "CmdOrCtrl+Shift+S"),
+    #[cfg(not(target_os = "macos"))]
+    ("quit", "CmdOrCtrl+Q"),
+    ("auto_layout", "Ctrl+Shift+L"),
+    ("toggle_sidebar", "CmdOrCtrl+B"),
+    ("zoom_in", "CmdOrCtrl+Plus"),
+    ("zoom_out", "CmdOrCtrl+-"),
+    ("reset_zoom", "CmdOrCtrl+0"),
+    ("fullscreen", FULLSCREEN),
+    #[cfg(not(target_os = "macos"))]
+    ("minimize", "CmdOrCtrl+M"),
+    #[cfg(not(target_os = "macos"))]
+    ("close_window", "CmdOrCtrl+W"),
+    ("keyboard_shortcuts", ""),
+];
+
+const MODIFIERS: &[&str] = &["Cmd", "Ctrl", "Alt", "Shift"];
+
+/// Named keys accepted after the modifiers, besides letters, digits and F1-F24
+const NAMED_KEYS: &[(&str, &str)] = &[
+    ("plus", "Plus"),
+    ("=", "Equal"),
+    ("equal", "Equal"),
+    ("-", "Minus"),
+    ("minus", "Minus"),
+    (",", "Comma"),
+    ("comma", "Comma"),
+    (".", "Period"),
+    ("period", "Period"),
+    ("/", "Slash"),
+    ("slash", "Slash"),
+    ("\\", "Backslash"),
+    ("backslash", "Backslash"),
+    (";", "Semicolon"),
+    ("semicolon", "Semicolon"),
+    ("'", "Quote"),
+    ("quote", "Quote"),
+    ("[", "BracketLeft"),
+    ("bracketleft", "BracketLeft"),
+    ("]", "BracketRight"),
+    ("bracketright", "BracketRight"),
+    ("`", "Backquote"),
+    ("backquote", "Backquote"),
+    ("space", "Space"),
+    ("tab", "Tab"),
+    ("enter", "Enter"),
+    ("return", "Enter"),
+    ("escape", "Escape"),
+    ("esc", "Escape"),
+    ("backspace", "Backspace"),
+    ("delete", "Delete"),
+    ("up", "ArrowUp"),
+    ("arrowup", "ArrowUp"),
+    ("down", "ArrowDown"),
+    ("arrowdown", "ArrowDown"),
+    ("left", "ArrowLeft"),
+    ("arrowleft", "ArrowLeft"),
+    ("right", "ArrowRight"),
+    ("arrowright", "ArrowRight"),
+    ("home", "Home"),
+    ("end", "End"),
+    ("pageup", "PageUp"),
+    ("pagedown", "PageDown"),
+];
+
+/// One command as listed in the shortcut settings
+#[derive(Debug, Serialize, Clone)]
+pub struct ShortcutInfo {
+    pub command: String,
+    /// Empty when the command has no shortcut
+    pub accelerator: String,
+    pub default: String,
+}
+
+pub fn is_known_command(command: &str) -> bool {
+    DEFAULTS.iter().any(|(id, _)| *id == command)
+}
+
+/// The form two accelerators are compared in: modifiers in a fixed order with
+/// `CmdOrCtrl` resolved for this platform, then the key, e.g. `Ctrl+Shift+S`.
+/// Rejects unknown modifiers and keys, and plain keys without a modifier.
+pub fn canonical(accelerator: &str) -> Result<String, AppError> {
+    let invalid = |reason: &str| AppError::InvalidInput(format!("Invalid shortcut \"{}\": {}", accelerator, reason));
+
+    let parts: Vec<&str> = accelerator.split('+').map(str::trim).collect();
+    let (key, modifiers) = parts.split_last().ok_or_else(|| invalid("empty"))?;
+
+    let mut found = [false; 4];
+    for modifier in modifiers {
+        let index = match modifier.to_ascii_lowercase().as_str() {
+            "cmdorctrl" | "commandorcontrol" if cfg!(target_os = "macos") => 0,
+            "cmdorctrl" | "commandorcontrol" => 1,
+            "cmd" | "command" | "super" | "meta" => 0,
+            "ctrl" | "control" => 1,
+            "alt" | "option" => 2,
+            "shift" => 3,
+            _ => return Err(invalid(&format!("unknown modifier {}", modifier))),
+        };
+        found[index] = true;
+    }
+
+    let lower = key.to_ascii_lowercase();
+    let function_key = lower
+        .strip_prefix('f')
+        .and_then(|n| n.parse::<u8>().ok())
+        .filter(|n| (1..=24).contains(n));
+    let key = if key.len() == 1 && key.chars().all(|c| c.is_ascii_alphanumeric()) {
+        key.to_ascii_uppercase()
+    } else if let Some(n) = function_key {
+        format!("F{}", n)
+    } else if let Some((_, name)) = NAMED_KEYS.iter().find(|(alias, _)| *alias == lower) {
+        name.to_string()
+    } else {
+        return Err(invalid(&format!("unknown key {}", key)));
+    };
+
+    if !found.contains(&true) && function_key.is_none() {
+        return Err(invalid("needs a modifier such as CmdOrCtrl"));
+    }
+
+    let mut canonical: Vec<String> = MODIFIERS
+        .iter()
+        .zip(found)
+        .filter(|(_, on)| *on)
+        .map(|(name, _)| name.to_string())
+        .collect();
+    canonical.push(key);
+    Ok(canonical.join("+"))
+}
+
+/// The accelerators in effect: the defaults with the user's overrides, where
+/// an empty override removes the shortcut
+pub struct Shortcuts {
+    accelerators: HashMap<String, String>,
+}
+
+impl Shortcuts {
+    pub fn new(overrides: &HashMap<String, String>) -> Self {
+        let accelerators = DEFAULTS
+            .iter()
+            .map(|(id, default)| {
+                let accelerator = overrides.get(*id).map(String::as_str).unwrap_or(default);
+                (id.to_string(), accelerator.to_string())
+            })
+            .collect();
+        Self { accelerators }
+    }
+
+    pub fn get(&self, command: &str) -> Option<&str> {
+        self.accelerators
+            .get(command)
+            .map(String::as_str)
+            .filter(|accelerator| !accelerator.is_empty())
+    }
+
+    /// Another command already bound to the same keys as `accelerator`
+    pub fn conflict(&self, command: &str, accelerator: &str) -> Option<String> {
+        let wanted = canonical(accelerator).ok()?;
+        self.accelerators
+            .iter()
+            .filter(|(id, _)| id.as_str() != command)
+            .find(|(_, other)| canonical(other).is_ok_and(|other| other == wanted))
+            .map(|(id, _)| id.clone())
+    }
+
+    pub fn list(&self) -> Vec<ShortcutInfo> {
+        DEFAULTS
+            .iter()
+            .map(|(id, default)| ShortcutInfo {
+                command: id.to_string(),
+                accelerator: self.get(id).unwrap_or_default().to_string(),
+                default: default.to_string(),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,379 @@
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::branding::{self, ExportBranding};
+use crate::compression;
+use crate::error::{AppError, ResultExt};
+use crate::ignores::IgnoreRules;
+use crate::render;
+use crate::share::{SharePermissions, ShareRole};
+
+/// Padding around a slide's content in scene units
+const SLIDE_PADDING: f64 = 20.0;
+
+/// Output flavour of an exported slide deck
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlideFormat {
+    /// Self-contained reveal.js HTML page
+    RevealJs,
+    /// Marp-flavoured Markdown
+    Marp,
+    /// PowerPoint deck
+    Pptx,
+}
+
+impl SlideFormat {
+    pub fn parse(format: &str) -> Result<Self, AppError> {
+        match format.trim().to_ascii_lowercase().as_str() {
+            "reveal" | "revealjs" | "reveal.js" | "html" => Ok(SlideFormat::RevealJs),
+            "marp" | "markdown" | "md" => Ok(SlideFormat::Marp),
+            "pptx" | "powerpoint" => Ok(SlideFormat::Pptx),
+            other => Err(AppError::InvalidInput(format!("Unsupported slide format: {}", other))),
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            SlideFormat::RevealJs => "slides.html",
+            SlideFormat::Marp => "slides.md",
+            SlideFormat::Pptx => "slides.pptx",
+        }
+    }
+}
+
+/// One rendered slide
+pub struct Slide {
+    pub title: String,
+    pub svg: String,
+    /// Scene area covered by the SVG
+    pub bounds: render::Bounds,
+}
+
+impl Slide {
+    fn overlay(&mut self, fragment: &str) {
+        if let Some(end) = self.svg.rfind("</svg>") {
+            self.svg.insert_str(end, fragment);
+        }
+    }
+
+    /// Stamps a watermark over the slide's content
+    pub fn stamp_watermark(&mut self, text: &str) {
+        let fragment = render::render_watermark(&self.bounds, text);
+        self.overlay(&fragment);
+    }
+
+    /// Applies the workspace's export branding to the slide
+    pub fn apply_branding(&mut self, branding: &ExportBranding) {
+        let fragment = branding::render_overlay(&self.bounds, branding);
+        self.overlay(&fragment);
+    }
+}
+
+fn scene_background(scene: &Value) -> Option<&str> {
+    scene
+        .get("appState")
+        .and_then(|a| a.get("viewBackgroundColor"))
+        .and_then(|c| c.as_str())
+}
+
+/// Renders a whole scene as a single slide
+fn whole_scene_slide(scene: &Value, title: &str) -> Option<Slide> {
+    let elements = render::visible_elements(scene);
+    let bounds = render::scene_bounds(&elements)?.expand(SLIDE_PADDING);
+    let files = scene.get("files").and_then(|f| f.as_object());
+    Some(Slide {
+        title: title.to_string(),
+        svg: render::render_svg(&elements, files, &bounds, scene_background(scene)),
+        bounds,
+    })
+}
+
+/// Frames in reading order: rows from top to bottom, each from left to
+/// right. When every frame's name starts with a number ("1 Intro",
+/// "02. Goals"), they are ordered by that number instead.
+fn ordered_frames(frames: Vec<&Value>) -> Vec<&Value> {
+    let mut frames: Vec<(&Value, render::Bounds)> =
+        frames.into_iter().map(|f| (f, render::element_bounds(f))).collect();
+    frames.sort_by(|a, b| a.1.min_y.total_cmp(&b.1.min_y));
+
+    // A frame starting above the middle of a row's first frame joins that row
+    let mut rows: Vec<Vec<(&Value, render::Bounds)>> = Vec::new();
+    for frame in frames {
+        match rows.last_mut() {
+            Some(row) if frame.1.min_y < (row[0].1.min_y + row[0].1.max_y) / 2.0 => row.push(frame),
+            _ => rows.push(vec![frame]),
+        }
+    }
+    let mut ordered: Vec<&Value> = rows
+        .into_iter()
+        .flat_map(|mut row| {
+            row.sort_by(|a, b| a.1.min_x.total_cmp(&b.1.min_x));
+            row.into_iter().map(|(frame, _)| frame)
+        })
+        .collect();
+
+    let number = |frame: &Value| -> Option<u64> {
+        let name = frame.get("name").and_then(|n| n.as_str())?.trim_start();
+        let digits: String = name.chars().take_while(|c| c.is_ascii_digit()).collect();
+        digits.parse().ok()
+    };
+    if ordered.iter().all(|frame| number(frame).is_some()) {
+        // Stable, so frames sharing a number keep their reading order
+        ordered.sort_by_key(|frame| number(frame));
+    }
+    ordered
+}
+
+/// Turns a drawing into slides: one per frame, or the whole scene when it has no frames
+pub fn slides_from_scene(scene: &Value, fallback_title: &str) -> Vec<Slide> {
+    let elements = render::visible_elements(scene);
+    let files = scene.get("files").and_then(|f| f.as_object());
+    let background = scene_background(scene);
+
+    let frames = ordered_frames(render::frames(&elements));
+    if frames.is_empty() {
+        return whole_scene_slide(scene, fallback_title).into_iter().collect();
+    }
+
+    frames
+        .iter()
+        .enumerate()
+        .map(|(i, frame)| {
+            let children = render::frame_children(&elements, frame);
+            let bounds = render::element_bounds(frame).expand(SLIDE_PADDING);
+            let title = frame
+                .get("name")
+                .and_then(|n| n.as_str())
+                .filter(|n| !n.trim().is_empty())
+                .map(|n| n.to_string())
+                .unwrap_or_else(|| format!("{} ({})", fallback_title, i + 1));
+
+            Slide {
+                title,
+                svg: render::render_svg(&children, files, &bounds, background),
+                bounds,
+            }
+        })
+        .collect()
+}
+
+fn load_scene(path: &Path) -> Result<Value, AppError> {
+    let content =
+        compression::read_to_string(path).context(format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&content)
+        .map_err(|e| AppError::InvalidInput(format!("Invalid JSON in {}: {}", path.display(), e)))
+}
+
+fn file_title(path: &Path) -> String {
+    compression::with_drawing_extension(path, false)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Slide".to_string())
+}
+
+/// Percent-encodes an SVG document so it can be used as a `data:` URI
+fn svg_data_uri(svg: &str) -> String {
+    let mut encoded = String::with_capacity(svg.len());
+    for byte in svg.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'='
+            | b':' | b'/' | b',' | b';' | b'@' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    format!("data:image/svg+xml;charset=utf-8,{}", encoded)
+}
+
+/// Source scenes embedded into decks exported with the editor role
+pub struct DeckSource {
+    pub name: String,
+    pub scene: Value,
+}
+
+/// Renders slides into a deck in the requested format. Permissions are
+/// recorded as metadata; expiry is also enforced by the reveal.js page
+/// itself, and editor decks embed their source scenes. PowerPoint decks
+/// carry neither, only the watermarks stamped on the slides.
+pub fn render_deck(
+    title: &str,
+    slides: &[Slide],
+    format: SlideFormat,
+    permissions: Option<&SharePermissions>,
+    sources: &[DeckSource],
+) -> Result<Vec<u8>, AppError> {
+    let role = permissions.map(|p| p.role).unwrap_or_default();
+    let expires_at = permissions
+        .and_then(|p| p.expires_at.as_deref())
+        .filter(|d| !d.trim().is_empty());
+
+    match format {
+        SlideFormat::RevealJs => {
+            let mut extra_head = String::new();
+            let mut extra_body = String::new();
+            if permissions.is_some() {
+                extra_head.push_str(&format!(
+                    "<meta name=\"excaliapp:role\" content=\"{}\">\n",
+                    if role == ShareRole::Editor { "editor" } else { "viewer" }
+                ));
+            }
+            if let Some(expires_at) = expires_at {
+                let expires_at = render::escape_xml(expires_at.trim());
+                extra_head.push_str(&format!(
+                    "<meta name=\"excaliapp:expires\" content=\"{}\">\n",
+                    expires_at
+                ));
+                extra_body.push_str(&format!(
+                    "<script>if (new Date() > new Date('{}T23:59:59')) {{ document.body.innerHTML = '<p style=\"font-family:sans-serif;padding:2em\">This presentation has expired.</p>'; }}</script>\n",
+                    expires_at
+                ));
+            }
+            if role == ShareRole::Editor && !sources.is_empty() {
+                let embedded: Vec<Value> = sources
+                    .iter()
+                    .map(|s| serde_json::json!({"name": s.name, "scene": s.scene}))
+                    .collect();
+                // Keep the JSON from closing the script element early
+                let json = serde_json::to_string(&embedded)
+                    .unwrap_or_default()
+                    .replace("</", "<\\/");
+                extra_body.push_str(&format!(
+                    "<script type=\"application/json\" id=\"excaliapp-sources\">{}</script>\n",
+                    json
+                ));
+            }
+
+            let sections: Vec<String> = slides
+                .iter()
+                .map(|slide| {
+                    format!(
+                        "<section data-title=\"{}\"><h3>{}</h3><div class=\"diagram\">{}</div></section>",
+                        render::escape_xml(&slide.title),
+                        render::escape_xml(&slide.title),
+                        slide.svg
+                    )
+                })
+                .collect();
+
+            let page = format!(
+                r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+{}<title>{}</title>
+<link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/reveal.js@5/dist/reveal.css">
+<link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/reveal.js@5/dist/theme/white.css">
+<style>.diagram svg {{ max-width: 100%; max-height: 80vh; height: auto; }}</style>
+</head>
+<body>
+<div class="reveal"><div class="slides">
+{}
+</div></div>
+<script src="https://cdn.jsdelivr.net/npm/reveal.js@5/dist/reveal.js"></script>
+<script>Reveal.initialize({{ hash: true }});</script>
+{}</body>
+</html>
+"#,
+                extra_head,
+                render::escape_xml(title),
+                sections.join("\n"),
+                extra_body
+            );
+            Ok(page.into_bytes())
+        }
+        SlideFormat::Marp => {
+            let mut deck = format!("---\nmarp: true\ntitle: {}\npaginate: true\n---\n", title);
+            if permissions.is_some() {
+                deck.push_str(&format!(
+                    "\n<!-- excaliapp-share role: {} expires: {} -->\n",
+                    if role == ShareRole::Editor { "editor" } else { "viewer" },
+                    expires_at.unwrap_or("never")
+                ));
+            }
+            for (i, slide) in slides.iter().enumerate() {
+                if i > 0 {
+                    deck.push_str("\n---\n");
+                }
+                deck.push_str(&format!(
+                    "\n## {}\n\n![w:100% h:80%]({})\n",
+                    slide.title,
+                    svg_data_uri(&slide.svg)
+                ));
+            }
+            Ok(deck.into_bytes())
+        }
+        SlideFormat::Pptx => crate::pptx::render(title, slides),
+    }
+}
+
+/// Exports a drawing (frames as slides) or a directory (one drawing per slide)
+/// and returns the path of the written deck, `output` or a file next to the
+/// source. `permissions_for` supplies the share permissions of each source
+/// file, which are enforced on the output.
+pub fn export(
+    path: &Path,
+    format: SlideFormat,
+    permissions_for: impl Fn(&Path) -> Option<SharePermissions>,
+    branding: Option<&ExportBranding>,
+    ignore: &IgnoreRules,
+    output: Option<&Path>,
+) -> Result<PathBuf, AppError> {
+    let is_dir = path.is_dir();
+    let sources: Vec<PathBuf> = if is_dir {
+        let mut files = Vec::new();
+        crate::collect_excalidraw_files_recursive(path, ignore, &mut files)?;
+        let mut paths: Vec<PathBuf> = files.into_iter().map(|f| PathBuf::from(f.path)).collect();
+        paths.sort();
+        paths
+    } else {
+        crate::security::validate_excalidraw_file(path)?;
+        vec![path.to_path_buf()]
+    };
+
+    let title = file_title(path);
+    let mut slides = Vec::new();
+    let mut permissions = Vec::new();
+    let mut deck_sources = Vec::new();
+
+    for source in &sources {
+        let scene = load_scene(source)?;
+        let name = file_title(source);
+
+        let mut file_slides = if is_dir {
+            whole_scene_slide(&scene, &name).into_iter().collect()
+        } else {
+            slides_from_scene(&scene, &name)
+        };
+
+        if let Some(branding) = branding {
+            file_slides.iter_mut().for_each(|s| s.apply_branding(branding));
+        }
+
+        if let Some(p) = permissions_for(source) {
+            p.check_not_expired().context(source.display().to_string())?;
+            if let Some(text) = p.watermark_text() {
+                file_slides.iter_mut().for_each(|s| s.stamp_watermark(text));
+            }
+            permissions.push(p);
+        }
+
+        slides.extend(file_slides);
+        deck_sources.push(DeckSource { name, scene });
+    }
+
+    if slides.is_empty() {
+        return Err(AppError::InvalidInput("Nothing to export: no visible elements found".to_string()));
+    }
+
+    let deck_permissions = SharePermissions::most_restrictive(permissions.iter());
+    let output = match output {
+        Some(output) => output.to_path_buf(),
+        None if is_dir => path.join(format!("{}.{}", title, format.extension())),
+        None => path.with_file_name(format!("{}.{}", title, format.extension())),
+    };
+
+    let deck = render_deck(&title, &slides, format, deck_permissions.as_ref(), &deck_sources)?;
+    fs::write(&output, deck).context("Failed to write slide deck")?;
+
+    Ok(output)
+}
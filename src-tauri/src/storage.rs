@@ -0,0 +1,616 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::compression;
+use crate::error::AppError;
+use crate::paths;
+use crate::security;
+
+/// A directory entry as reported by a storage backend
+#[derive(Debug, Clone)]
+pub struct StorageEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub is_directory: bool,
+    pub metadata: EntryMetadata,
+}
+
+/// File attributes shown in the sidebar; timestamps are Unix milliseconds
+/// and `None` where the backend or file system does not record them
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EntryMetadata {
+    pub size: u64,
+    pub modified_at: Option<i64>,
+    pub created_at: Option<i64>,
+    pub readonly: bool,
+}
+
+impl EntryMetadata {
+    pub fn from_fs(metadata: &fs::Metadata) -> Self {
+        let millis = |time: io::Result<std::time::SystemTime>| {
+            time.ok()?
+                .duration_since(std::time::UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_millis() as i64)
+        };
+        Self {
+            size: metadata.len(),
+            modified_at: millis(metadata.modified()),
+            created_at: millis(metadata.created()),
+            readonly: metadata.permissions().readonly(),
+        }
+    }
+}
+
+/// Filesystem operations the file commands need, so workspaces can live on
+/// something other than the local disk
+pub trait StorageBackend: Send + Sync {
+    /// Short backend identifier, also used as the URI scheme of mounted paths
+    fn kind(&self) -> &'static str;
+
+    fn exists(&self, path: &Path) -> bool;
+    fn is_dir(&self, path: &Path) -> bool;
+    fn is_file(&self, path: &Path) -> bool {
+        self.exists(path) && !self.is_dir(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<StorageEntry>>;
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    /// `read_to_string` that fails with `FileTooLarge` past `limit` bytes, checked
+    /// while decompressing where the backend compresses
+    fn read_limited(&self, path: &Path, limit: u64) -> io::Result<String> {
+        let content = self.read_to_string(path)?;
+        if content.len() as u64 > limit {
+            return Err(io::Error::new(io::ErrorKind::FileTooLarge, format!("{} is too large", path.display())));
+        }
+        Ok(content)
+    }
+    /// Size in bytes, without reading the file
+    fn file_size(&self, path: &Path) -> io::Result<u64>;
+    fn write(&self, path: &Path, content: &str) -> io::Result<()>;
+    /// Writes a file that must not exist yet, failing with `AlreadyExists` otherwise
+    fn create_new(&self, path: &Path, content: &str) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+}
+
+/// The local disk, addressed with absolute paths
+pub struct LocalBackend;
+
+impl StorageBackend for LocalBackend {
+    fn kind(&self) -> &'static str {
+        "local"
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<StorageEntry>> {
+        Ok(fs::read_dir(path)?
+            .flatten()
+            .map(|entry| {
+                let path = entry.path();
+                // Follows symlinks, like `Path::is_dir`
+                let metadata = fs::metadata(&path).ok();
+                StorageEntry {
+                    name: entry.file_name().to_string_lossy().to_string(),
+                    is_directory: metadata.as_ref().is_some_and(|m| m.is_dir()),
+                    metadata: metadata.as_ref().map(EntryMetadata::from_fs).unwrap_or_default(),
+                    path,
+                }
+            })
+            .collect())
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        compression::read_to_string(path)
+    }
+
+    fn read_limited(&self, path: &Path, limit: u64) -> io::Result<String> {
+        compression::read_limited(path, limit)
+    }
+
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        fs::metadata(path).map(|m| m.len())
+    }
+
+    fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        compression::write(path, content)
+    }
+
+    fn create_new(&self, path: &Path, content: &str) -> io::Result<()> {
+        use std::io::Write;
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        if compression::is_compressed(path) {
+            return file.write_all(&compression::encode(content)?);
+        }
+        file.write_all(content.as_bytes())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+}
+
+/// A volatile in-memory tree rooted at `/`, for tests and benchmarks
+#[derive(Default)]
+pub struct MemoryBackend {
+    /// File contents keyed by path; `None` marks a directory
+    entries: Mutex<BTreeMap<PathBuf, Option<String>>>,
+}
+
+impl MemoryBackend {
+    fn not_found(path: &Path) -> io::Error {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} not found", path.display()),
+        )
+    }
+
+    fn is_root(path: &Path) -> bool {
+        path.parent().is_none()
+    }
+
+    fn parent_is_dir(entries: &BTreeMap<PathBuf, Option<String>>, path: &Path) -> bool {
+        match path.parent() {
+            Some(parent) => Self::is_root(parent) || matches!(entries.get(parent), Some(None)),
+            None => false,
+        }
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn kind(&self) -> &'static str {
+        "memory"
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        Self::is_root(path) || self.entries.lock().unwrap().contains_key(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        Self::is_root(path) || matches!(self.entries.lock().unwrap().get(path), Some(None))
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<StorageEntry>> {
+        if !self.is_dir(path) {
+            return Err(Self::not_found(path));
+        }
+        let entries = self.entries.lock().unwrap();
+        Ok(entries
+            .iter()
+            .filter(|(p, _)| p.parent() == Some(path))
+            .map(|(p, content)| StorageEntry {
+                name: p
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: p.clone(),
+                is_directory: content.is_none(),
+                metadata: EntryMetadata {
+                    size: content.as_ref().map_or(0, |c| c.len() as u64),
+                    ..EntryMetadata::default()
+                },
+            })
+            .collect())
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        match self.entries.lock().unwrap().get(path) {
+            Some(Some(content)) => Ok(content.clone()),
+            Some(None) => Err(io::Error::other(format!("{} is a directory", path.display()))),
+            None => Err(Self::not_found(path)),
+        }
+    }
+
+    fn file_size(&self, path: &Path) -> io::Result<u64> {
+        self.read_to_string(path).map(|content| content.len() as u64)
+    }
+
+    fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if !Self::parent_is_dir(&entries, path) {
+            return Err(Self::not_found(path.parent().unwrap_or(path)));
+        }
+        if matches!(entries.get(path), Some(None)) {
+            return Err(io::Error::other(format!("{} is a directory", path.display())));
+        }
+        entries.insert(path.to_path_buf(), Some(content.to_string()));
+        Ok(())
+    }
+
+    fn create_new(&self, path: &Path, content: &str) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.contains_key(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        if !Self::parent_is_dir(&entries, path) {
+            return Err(Self::not_found(path.parent().unwrap_or(path)));
+        }
+        entries.insert(path.to_path_buf(), Some(content.to_string()));
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(path) {
+            Some(Some(_)) => {
+                entries.remove(path);
+                Ok(())
+            }
+            Some(None) => Err(io::Error::other(format!("{} is a directory", path.display()))),
+            None => Err(Self::not_found(path)),
+        }
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if Self::is_root(path) || entries.contains_key(path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            ));
+        }
+        if !Self::parent_is_dir(&entries, path) {
+            return Err(Self::not_found(path.parent().unwrap_or(path)));
+        }
+        entries.insert(path.to_path_buf(), None);
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if Self::is_root(path) {
+            entries.clear();
+            return Ok(());
+        }
+        if !matches!(entries.get(path), Some(None)) {
+            return Err(Self::not_found(path));
+        }
+        entries.retain(|p, _| !p.starts_with(path));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(from) {
+            return Err(Self::not_found(from));
+        }
+        if !Self::parent_is_dir(&entries, to) {
+            return Err(Self::not_found(to.parent().unwrap_or(to)));
+        }
+
+        let moved: Vec<PathBuf> = entries
+            .keys()
+            .filter(|p| p.starts_with(from))
+            .cloned()
+            .collect();
+        for old in moved {
+            if let Some(content) = entries.remove(&old) {
+                let target = match old.strip_prefix(from) {
+                    Ok(relative) if !relative.as_os_str().is_empty() => to.join(relative),
+                    _ => to.to_path_buf(),
+                };
+                entries.insert(target, content);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Describes a backend to mount as a workspace root
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackendConfig {
+    /// Backend type: "local" or "memory"
+    pub kind: String,
+    /// Mount name, used as the host part of `<kind>://<name>/...` paths
+    pub name: String,
+    /// Backend-specific root (a directory for "local")
+    pub root: Option<String>,
+}
+
+struct Mount {
+    root: PathBuf,
+    backend: Arc<dyn StorageBackend>,
+}
+
+/// A caller-facing path resolved to a backend and the backend's own path
+pub struct ResolvedPath {
+    pub backend: Arc<dyn StorageBackend>,
+    pub path: PathBuf,
+    mount: Option<(String, PathBuf)>,
+}
+
+impl ResolvedPath {
+    /// Wraps an already validated local path
+    pub fn local(path: &Path) -> ResolvedPath {
+        ResolvedPath {
+            backend: Arc::new(LocalBackend),
+            path: path.to_path_buf(),
+            mount: None,
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.mount.is_none()
+    }
+
+    /// Maps a backend path back to the path handed to the frontend
+    pub fn to_external(&self, path: &Path) -> String {
+        match &self.mount {
+            None => paths::normalize(path),
+            Some((name, root)) => {
+                let relative = path.strip_prefix(root).unwrap_or(path);
+                let parts: Vec<String> = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string())
+                    .collect();
+                format!("{}://{}/{}", self.backend.kind(), name, parts.join("/"))
+            }
+        }
+    }
+
+    /// `/`-separated path of `path` relative to this resolved path (e.g. a listed root)
+    pub fn relative(&self, path: &Path) -> String {
+        paths::relative(&self.path, path).unwrap_or_else(|| paths::normalize(path))
+    }
+
+    /// Whether two resolved paths live on the same mount
+    pub fn same_mount(&self, other: &ResolvedPath) -> bool {
+        self.mount.as_ref().map(|(name, _)| name) == other.mount.as_ref().map(|(name, _)| name)
+    }
+}
+
+/// Registry of mounted backends; plain paths always go to the local disk
+pub struct Mounts {
+    mounts: Mutex<HashMap<String, Mount>>,
+    local: Arc<dyn StorageBackend>,
+}
+
+impl Default for Mounts {
+    fn default() -> Self {
+        Self {
+            mounts: Mutex::new(HashMap::new()),
+            local: Arc::new(LocalBackend),
+        }
+    }
+}
+
+impl Mounts {
+    /// Mounts a backend and returns the root path to use for it
+    pub fn mount(&self, config: &BackendConfig) -> Result<String, AppError> {
+        let name = config.name.trim();
+        if name.is_empty() || name.contains(['/', '\\', ':']) {
+            return Err(AppError::InvalidInput("Invalid mount name".to_string()));
+        }
+
+        let mount = match config.kind.as_str() {
+            "local" => {
+                let root = config
+                    .root
+                    .as_deref()
+                    .ok_or_else(|| AppError::InvalidInput("Local mounts require a root directory".to_string()))?;
+                let root = security::validate_path(Path::new(root), None)?;
+                if !root.is_dir() {
+                    return Err(AppError::InvalidPath("Mount root is not a directory".to_string()));
+                }
+                Mount {
+                    root,
+                    backend: self.local.clone(),
+                }
+            }
+            "memory" => Mount {
+                root: PathBuf::from("/"),
+                backend: Arc::new(MemoryBackend::default()),
+            },
+            other => return Err(AppError::InvalidInput(format!("Unsupported storage backend: {}", other))),
+        };
+
+        let uri = format!("{}://{}/", mount.backend.kind(), name);
+        self.mounts
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), mount);
+        Ok(uri)
+    }
+
+    /// Resolves a frontend path: `<kind>://<mount>/<relative>` or a plain local
+    /// path, which is validated against traversal attacks
+    pub fn resolve(&self, path: &str) -> Result<ResolvedPath, AppError> {
+        if !path.contains("://") {
+            return Ok(ResolvedPath {
+                backend: self.local.clone(),
+                path: security::validate_path(Path::new(path), None)?,
+                mount: None,
+            });
+        }
+        self.resolve_root(path)
+    }
+
+    /// Like `resolve`, but keeps plain local paths exactly as given so listings
+    /// report paths in the same form the workspace was opened with
+    pub fn resolve_root(&self, path: &str) -> Result<ResolvedPath, AppError> {
+        let Some((scheme, rest)) = path.split_once("://") else {
+            return Ok(ResolvedPath {
+                backend: self.local.clone(),
+                path: PathBuf::from(path),
+                mount: None,
+            });
+        };
+
+        let (name, relative) = rest.split_once('/').unwrap_or((rest, ""));
+        let mounts = self.mounts.lock().unwrap();
+        let mount = mounts
+            .get(name)
+            .filter(|m| m.backend.kind() == scheme)
+            .ok_or_else(|| AppError::NotFound(format!("No storage mounted at {}://{}", scheme, name)))?;
+
+        let relative = Path::new(relative);
+        if relative
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_)))
+        {
+            return Err(AppError::InvalidPath("Path contains suspicious patterns".to_string()));
+        }
+
+        Ok(ResolvedPath {
+            backend: mount.backend.clone(),
+            path: mount.root.join(relative),
+            mount: Some((name.to_string(), mount.root.clone())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_backend_reads_back_writes() {
+        let backend = MemoryBackend::default();
+        backend.create_dir(Path::new("/notes")).unwrap();
+        backend.write(Path::new("/notes/a.excalidraw"), "{}").unwrap();
+
+        assert!(backend.is_dir(Path::new("/notes")));
+        assert!(backend.is_file(Path::new("/notes/a.excalidraw")));
+        assert_eq!(backend.read_to_string(Path::new("/notes/a.excalidraw")).unwrap(), "{}");
+        assert_eq!(backend.file_size(Path::new("/notes/a.excalidraw")).unwrap(), 2);
+
+        let entries = backend.read_dir(Path::new("/notes")).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "a.excalidraw");
+        assert!(!entries[0].is_directory);
+    }
+
+    #[test]
+    fn memory_backend_needs_parent_directory() {
+        let backend = MemoryBackend::default();
+        let err = backend.write(Path::new("/missing/a.excalidraw"), "{}").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(backend.create_dir(Path::new("/missing/child")).is_err());
+    }
+
+    #[test]
+    fn memory_backend_create_new_refuses_existing() {
+        let backend = MemoryBackend::default();
+        backend.create_new(Path::new("/a.excalidraw"), "one").unwrap();
+        let err = backend.create_new(Path::new("/a.excalidraw"), "two").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(backend.read_to_string(Path::new("/a.excalidraw")).unwrap(), "one");
+    }
+
+    #[test]
+    fn memory_backend_renames_and_removes_folders() {
+        let backend = MemoryBackend::default();
+        backend.create_dir(Path::new("/old")).unwrap();
+        backend.create_dir(Path::new("/old/nested")).unwrap();
+        backend.write(Path::new("/old/nested/a.excalidraw"), "{}").unwrap();
+
+        backend.rename(Path::new("/old"), Path::new("/new")).unwrap();
+        assert!(!backend.exists(Path::new("/old")));
+        assert!(backend.is_file(Path::new("/new/nested/a.excalidraw")));
+
+        backend.remove_dir_all(Path::new("/new")).unwrap();
+        assert!(!backend.exists(Path::new("/new/nested/a.excalidraw")));
+        assert!(backend.read_dir(Path::new("/")).unwrap().is_empty());
+    }
+
+    fn local_mount(name: &str) -> (Mounts, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("excaliapp-mount-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("folder")).unwrap();
+        let mounts = Mounts::default();
+        let uri = mounts
+            .mount(&BackendConfig {
+                kind: "local".to_string(),
+                name: name.to_string(),
+                root: Some(dir.to_string_lossy().to_string()),
+            })
+            .unwrap();
+        assert_eq!(uri, format!("local://{}/", name));
+        (mounts, dir)
+    }
+
+    #[test]
+    fn mounted_paths_round_trip() {
+        let (mounts, dir) = local_mount("round-trip");
+        let root = mounts.resolve_root("local://round-trip/").unwrap();
+        assert!(!root.is_local());
+
+        let file = mounts.resolve("local://round-trip/folder/a.excalidraw").unwrap();
+        assert_eq!(file.path, dir.join("folder").join("a.excalidraw"));
+        assert!(file.same_mount(&root));
+        assert!(!file.same_mount(&ResolvedPath::local(&dir)));
+        assert_eq!(file.to_external(&file.path), "local://round-trip/folder/a.excalidraw");
+        assert_eq!(root.relative(&file.path), "folder/a.excalidraw");
+
+        file.backend.write(&file.path, "{}").unwrap();
+        assert_eq!(fs::read_to_string(dir.join("folder").join("a.excalidraw")).unwrap(), "{}");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn mounted_paths_reject_traversal_and_unknown_mounts() {
+        let (mounts, dir) = local_mount("traversal");
+        assert!(matches!(
+            mounts.resolve("local://traversal/../etc/passwd"),
+            Err(AppError::InvalidPath(_))
+        ));
+        assert!(matches!(mounts.resolve("local://other/a.excalidraw"), Err(AppError::NotFound(_))));
+        assert!(matches!(mounts.resolve("memory://traversal/a.excalidraw"), Err(AppError::NotFound(_))));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn mount_configs_are_validated() {
+        let mounts = Mounts::default();
+        let config = |kind: &str, name: &str, root: Option<&str>| BackendConfig {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            root: root.map(str::to_string),
+        };
+        for name in ["", "a/b", "a:b"] {
+            assert!(matches!(mounts.mount(&config("memory", name, None)), Err(AppError::InvalidInput(_))));
+        }
+        assert!(matches!(mounts.mount(&config("ftp", "remote", None)), Err(AppError::InvalidInput(_))));
+        assert!(matches!(mounts.mount(&config("local", "disk", None)), Err(AppError::InvalidInput(_))));
+
+        let missing = std::env::temp_dir().join(format!("excaliapp-mount-{}-missing", std::process::id()));
+        let missing = missing.to_string_lossy().to_string();
+        assert!(mounts.mount(&config("local", "disk", Some(&missing))).is_err());
+    }
+}
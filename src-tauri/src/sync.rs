@@ -0,0 +1,389 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::error::{AppError, ResultExt};
+use crate::export::{fnv1a, FNV_OFFSET};
+use crate::ignores::IgnoreRules;
+use crate::storage::EntryMetadata;
+use crate::webdav::{WebDavClient, WebDavConfig};
+use crate::{archive, paths, security, FileTreeNode};
+
+pub const STORE_FILE: &str = "sync.json";
+/// WebDAV settings of each synced vault, keyed by normalized vault path
+pub const CONFIGS_KEY: &str = "webdav";
+/// What each vault looked like after its last sync
+pub const STATE_KEY: &str = "state";
+
+pub type ConfigMap = HashMap<String, WebDavConfig>;
+/// Vault path to the files of that vault, keyed by `/`-separated relative path
+pub type StateMap = HashMap<String, HashMap<String, FileSyncState>>;
+
+/// A file as it was on both sides after it was last synced
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FileSyncState {
+    /// `export::fnv1a` of the content
+    pub hash: u64,
+    /// Local size and modification time, to spot edits without reading the file
+    pub size: u64,
+    pub modified_at: Option<i64>,
+    pub etag: Option<String>,
+    /// Both sides had changed; the server's version was kept as a conflict copy
+    #[serde(default)]
+    pub conflict: bool,
+}
+
+/// Shown next to files of synced vaults in the sidebar
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    Synced,
+    /// Changed since the last sync
+    Modified,
+    /// Not synced yet
+    New,
+    Conflict,
+}
+
+/// A WebDAV configuration as listed to the frontend
+#[derive(Debug, Serialize, Clone)]
+pub struct SyncConfigSummary {
+    #[serde(flatten)]
+    pub config: WebDavConfig,
+    pub has_password: bool,
+}
+
+impl SyncConfigSummary {
+    pub fn new(config: &WebDavConfig) -> Self {
+        Self {
+            config: WebDavConfig {
+                password: String::new(),
+                ..config.clone()
+            },
+            has_password: !config.password.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyncAction {
+    Upload,
+    Download,
+    DeleteLocal,
+    DeleteRemote,
+    /// Present on both sides without a shared history; equal content is
+    /// recorded as synced, different content is a conflict
+    Compare,
+    /// Changed on both sides since the last sync
+    Conflict,
+    /// Gone from both sides
+    Forget,
+}
+
+/// What to do with every path that differs from the last sync. `local`
+/// maps paths to content hashes, `remote` to ETags.
+pub fn plan(
+    local: &HashMap<String, u64>,
+    remote: &HashMap<String, Option<String>>,
+    base: &HashMap<String, FileSyncState>,
+) -> Vec<(String, SyncAction)> {
+    let paths: BTreeSet<&String> = local.keys().chain(remote.keys()).chain(base.keys()).collect();
+    let mut actions = Vec::new();
+    for path in paths {
+        let previous = base.get(path);
+        let local_changed = |hash: &u64| previous.is_none_or(|p| p.hash != *hash);
+        // Servers that don't report ETags leave remote changes undetectable
+        let remote_changed = |etag: &Option<String>| previous.is_none_or(|p| p.etag.is_none() || p.etag != *etag);
+
+        let action = match (local.get(path), remote.get(path), previous) {
+            (Some(_), Some(_), None) => Some(SyncAction::Compare),
+            (Some(hash), Some(etag), Some(_)) => match (local_changed(hash), remote_changed(etag)) {
+                (true, true) => Some(SyncAction::Conflict),
+                (true, false) => Some(SyncAction::Upload),
+                (false, true) => Some(SyncAction::Download),
+                (false, false) => None,
+            },
+            (Some(_), None, None) => Some(SyncAction::Upload),
+            // Deleted on the server; local edits since win over the deletion
+            (Some(hash), None, Some(_)) if local_changed(hash) => Some(SyncAction::Upload),
+            (Some(_), None, Some(_)) => Some(SyncAction::DeleteLocal),
+            (None, Some(_), None) => Some(SyncAction::Download),
+            // Deleted here; edits on the server since win over the deletion
+            (None, Some(etag), Some(_)) if remote_changed(etag) => Some(SyncAction::Download),
+            (None, Some(_), Some(_)) => Some(SyncAction::DeleteRemote),
+            (None, None, Some(_)) => Some(SyncAction::Forget),
+            (None, None, None) => None,
+        };
+        if let Some(action) = action {
+            actions.push((path.clone(), action));
+        }
+    }
+    actions
+}
+
+/// `YYYYMMDD-HHMM` in UTC, for conflict copies
+pub fn stamp(now_millis: i64) -> String {
+    let minutes = now_millis.div_euclid(60_000);
+    let (year, month, day) = crate::templates::civil_from_days(minutes.div_euclid(1440));
+    let minute = minutes.rem_euclid(1440);
+    format!("{:04}{:02}{:02}-{:02}{:02}", year, month, day, minute / 60, minute % 60)
+}
+
+/// Local name for the server's version of a conflicting file:
+/// `notes/plan.excalidraw` becomes `notes/plan (conflict 20250102-1530).excalidraw`
+pub fn conflict_path(path: &str, stamp: &str) -> String {
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (format!("{}/", dir), name),
+        None => (String::new(), path),
+    };
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+        _ => (name, String::new()),
+    };
+    format!("{}{} (conflict {}){}", dir, stem, stamp, extension)
+}
+
+/// What was done by `sync_now`, by relative path
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SyncReport {
+    pub uploaded: Vec<String>,
+    pub downloaded: Vec<String>,
+    pub deleted_local: Vec<String>,
+    pub deleted_remote: Vec<String>,
+    /// Files changed on both sides; the server's versions were saved next to them
+    pub conflicts: Vec<String>,
+    pub failed: Vec<SyncFailure>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SyncFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Content hashes of the drawings, vault settings and images of a vault, as
+/// `archive` would store them
+pub fn local_files(vault: &Path, ignore: &IgnoreRules) -> Result<HashMap<String, u64>, AppError> {
+    let mut entries = Vec::new();
+    archive::collect(vault, "", ignore, true, Path::new(""), &mut entries)?;
+    let mut files = HashMap::new();
+    for entry in entries {
+        let content = fs::read(&entry.source)
+            .context(format!("Failed to read {}", entry.source.display()))?;
+        files.insert(entry.name, fnv1a(&content, FNV_OFFSET));
+    }
+    Ok(files)
+}
+
+/// Mirrors one vault with its WebDAV folder, starting from the state of the
+/// last sync; returns what was done and the new state. Failed files keep
+/// their previous state and are retried next time.
+pub async fn run(
+    client: &WebDavClient,
+    vault: &Path,
+    ignore: &IgnoreRules,
+    base: &HashMap<String, FileSyncState>,
+    stamp: &str,
+) -> Result<(SyncReport, HashMap<String, FileSyncState>), AppError> {
+    let local = local_files(vault, ignore)?;
+    let listing = client.list().await?;
+    let mut folders: HashSet<String> = listing
+        .iter()
+        .filter(|entry| entry.is_directory)
+        .map(|entry| entry.path.clone())
+        .collect();
+    let remote: HashMap<String, Option<String>> = listing
+        .into_iter()
+        .filter(|entry| !entry.is_directory)
+        .filter(|entry| {
+            let name = entry.path.rsplit('/').next().unwrap_or_default();
+            archive::is_supported(name, true) && !ignore.is_ignored(&vault.join(&entry.path), false)
+        })
+        .map(|entry| (entry.path, entry.etag))
+        .collect();
+
+    let mut state = base.clone();
+    let mut report = SyncReport::default();
+    for (path, action) in plan(&local, &remote, base) {
+        let sync = Sync {
+            client,
+            vault,
+            path: &path,
+            etag: remote.get(&path).cloned().flatten(),
+        };
+        let result = match action {
+            SyncAction::Upload => sync.upload(&mut folders).await.map(|s| {
+                report.uploaded.push(path.clone());
+                Some(s)
+            }),
+            SyncAction::Download => sync.download().await.map(|s| {
+                report.downloaded.push(path.clone());
+                Some(s)
+            }),
+            SyncAction::Compare | SyncAction::Conflict => {
+                let previous = base.get(&path).map(|p| p.hash);
+                sync.resolve(previous, stamp, &mut folders).await.map(|(s, conflict)| {
+                    if conflict {
+                        report.conflicts.push(path.clone());
+                    }
+                    Some(s)
+                })
+            }
+            SyncAction::DeleteLocal => sync.delete_local().map(|()| {
+                report.deleted_local.push(path.clone());
+                None
+            }),
+            SyncAction::DeleteRemote => client.delete(&path).await.map(|()| {
+                report.deleted_remote.push(path.clone());
+                None
+            }),
+            SyncAction::Forget => Ok(None),
+        };
+
+        match result {
+            Ok(Some(file)) => {
+                state.insert(path, file);
+            }
+            Ok(None) => {
+                state.remove(&path);
+            }
+            Err(e) => report.failed.push(SyncFailure {
+                path,
+                error: e.to_string(),
+            }),
+        }
+    }
+    Ok((report, state))
+}
+
+/// One path being synced
+struct Sync<'a> {
+    client: &'a WebDavClient,
+    vault: &'a Path,
+    path: &'a str,
+    /// From the server listing
+    etag: Option<String>,
+}
+
+impl Sync<'_> {
+    fn local_path(&self, path: &str) -> Result<std::path::PathBuf, AppError> {
+        // Paths from the server are untrusted
+        security::safe_archive_path(self.vault, path)
+    }
+
+    fn synced(&self, hash: u64, etag: Option<String>, conflict: bool) -> Result<FileSyncState, AppError> {
+        let metadata = EntryMetadata::from_fs(&fs::metadata(self.local_path(self.path)?)?);
+        Ok(FileSyncState {
+            hash,
+            size: metadata.size,
+            modified_at: metadata.modified_at,
+            etag,
+            conflict,
+        })
+    }
+
+    fn write_local(&self, path: &str, content: &[u8]) -> Result<(), AppError> {
+        let target = self.local_path(path)?;
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&target, content)?;
+        Ok(())
+    }
+
+    async fn upload(&self, folders: &mut HashSet<String>) -> Result<FileSyncState, AppError> {
+        let content = fs::read(self.local_path(self.path)?)?;
+        let hash = fnv1a(&content, FNV_OFFSET);
+
+        let mut parent = String::new();
+        for segment in self.path.split('/').rev().skip(1).collect::<Vec<_>>().into_iter().rev() {
+            if !parent.is_empty() {
+                parent.push('/');
+            }
+            parent.push_str(segment);
+            if folders.insert(parent.clone()) {
+                self.client.create_folder(&parent).await?;
+            }
+        }
+
+        let etag = self.client.upload(self.path, content).await?;
+        self.synced(hash, etag, false)
+    }
+
+    /// Leaves the local file alone when it already has the server's content
+    async fn download(&self) -> Result<FileSyncState, AppError> {
+        let content = self.client.download(self.path).await?;
+        let hash = fnv1a(&content, FNV_OFFSET);
+        let unchanged = fs::read(self.local_path(self.path)?)
+            .is_ok_and(|local| fnv1a(&local, FNV_OFFSET) == hash);
+        if !unchanged {
+            self.write_local(self.path, &content)?;
+        }
+        self.synced(hash, self.etag.clone(), false)
+    }
+
+    /// Both sides have the file. Equal content, or a server copy that
+    /// turns out unchanged since `previous`, needs no conflict; otherwise the
+    /// server's version is saved as a conflict copy and the local one uploaded.
+    /// Returns the new state and whether there was a conflict.
+    async fn resolve(
+        &self,
+        previous: Option<u64>,
+        stamp: &str,
+        folders: &mut HashSet<String>,
+    ) -> Result<(FileSyncState, bool), AppError> {
+        let remote = self.client.download(self.path).await?;
+        let remote_hash = fnv1a(&remote, FNV_OFFSET);
+        let local_hash = fnv1a(&fs::read(self.local_path(self.path)?)?, FNV_OFFSET);
+
+        if remote_hash == local_hash {
+            return Ok((self.synced(local_hash, self.etag.clone(), false)?, false));
+        }
+        if previous == Some(remote_hash) {
+            return Ok((self.upload(folders).await?, false));
+        }
+
+        self.write_local(&conflict_path(self.path, stamp), &remote)?;
+        let mut state = self.upload(folders).await?;
+        state.conflict = true;
+        Ok((state, true))
+    }
+
+    fn delete_local(&self) -> Result<(), AppError> {
+        match fs::remove_file(self.local_path(self.path)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn status(state: Option<&FileSyncState>, node: &FileTreeNode) -> SyncStatus {
+    match state {
+        None => SyncStatus::New,
+        Some(state) if state.conflict => SyncStatus::Conflict,
+        Some(state)
+            if state.size == node.metadata.size && state.modified_at == node.metadata.modified_at =>
+        {
+            SyncStatus::Synced
+        }
+        Some(_) => SyncStatus::Modified,
+    }
+}
+
+/// Sets `sync_status` on the files of the tree that lie in the synced vault
+/// at `vault`
+pub fn mark(tree: &mut [FileTreeNode], vault: &Path, files: &HashMap<String, FileSyncState>) {
+    let prefix = format!("{}/", paths::normalize(vault).trim_end_matches('/'));
+    for node in tree {
+        if let Some(children) = node.children.as_mut() {
+            mark(children, vault, files);
+        }
+        if node.is_directory {
+            continue;
+        }
+        if let Some(relative) = node.path.replace('\\', "/").strip_prefix(&prefix) {
+            node.sync_status = Some(status(files.get(relative), node));
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::error::AppError;
+
+/// Directory (under the app data dir) holding the user's drawing templates
+pub const TEMPLATE_DIR: &str = "templates";
+
+const EXTENSION: &str = ".excalidraw";
+
+/// A template as listed to the frontend and in the File menu
+#[derive(Debug, Serialize, Clone)]
+pub struct TemplateInfo {
+    /// File name without the extension; what `create_new_file` takes
+    pub name: String,
+    pub path: String,
+    /// Milliseconds since the Unix epoch
+    pub modified_at: i64,
+}
+
+pub fn dir<R: Runtime>(app: &AppHandle<R>) -> Result<PathBuf, AppError> {
+    Ok(app
+        .path()
+        .app_data_dir()?
+        .join(TEMPLATE_DIR))
+}
+
+/// The templates in `dir` sorted by name; a missing directory has none
+pub fn list(dir: &Path) -> Vec<TemplateInfo> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![];
+    };
+
+    let mut templates: Vec<TemplateInfo> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            let name = path.file_name()?.to_str()?.strip_suffix(EXTENSION)?.to_string();
+            let modified_at = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0);
+            Some(TemplateInfo {
+                name,
+                path: path.to_string_lossy().to_string(),
+                modified_at,
+            })
+        })
+        .collect();
+    templates.sort_by_key(|t| t.name.to_lowercase());
+    templates
+}
+
+/// Where the template called `name` lives; the extension is optional
+pub fn path(dir: &Path, name: &str) -> Result<PathBuf, AppError> {
+    let name = name.trim();
+    let name = name.strip_suffix(EXTENSION).unwrap_or(name).trim();
+    if name.is_empty() || name.starts_with('.') {
+        return Err(AppError::InvalidInput(format!("Invalid template name: {}", name)));
+    }
+    crate::security::safe_path_join(dir, &format!("{}{}", name, EXTENSION))
+}
+
+/// Values for `{{date}}`, `{{time}}`, `{{datetime}}`, `{{year}}`, `{{filename}}`
+/// and `{{folder}}` in a drawing created at `file`. Times are UTC here; the
+/// frontend passes local ones in `overrides`.
+pub fn variables(file: &Path, now_millis: i64, overrides: &HashMap<String, String>) -> HashMap<String, String> {
+    let seconds = now_millis.div_euclid(1000);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let minutes = seconds.rem_euclid(86_400) / 60;
+
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    let time = format!("{:02}:{:02}", minutes / 60, minutes % 60);
+    let file_name = file
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let folder = file
+        .parent()
+        .and_then(Path::file_name)
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut variables = HashMap::from([
+        ("datetime".to_string(), format!("{} {}", date, time)),
+        ("date".to_string(), date),
+        ("time".to_string(), time),
+        ("year".to_string(), year.to_string()),
+        (
+            "filename".to_string(),
+            file_name.strip_suffix(EXTENSION).unwrap_or(&file_name).to_string(),
+        ),
+        ("folder".to_string(), folder),
+    ]);
+    variables.extend(overrides.iter().map(|(k, v)| (k.clone(), v.clone())));
+    variables
+}
+
+/// Fills `{{variable}}` placeholders in the text elements of a scene;
+/// anything that is not a scene comes back unchanged
+pub fn render(content: &str, variables: &HashMap<String, String>) -> String {
+    let Ok(mut scene) = serde_json::from_str::<Value>(content) else {
+        return content.to_string();
+    };
+
+    let mut changed = false;
+    if let Some(elements) = scene.get_mut("elements").and_then(Value::as_array_mut) {
+        for element in elements {
+            if element.get("type").and_then(Value::as_str) != Some("text") {
+                continue;
+            }
+            for key in ["text", "originalText"] {
+                if let Some(Value::String(text)) = element.get_mut(key)
+                    && text.contains("{{")
+                {
+                    *text = crate::prompts::render(text, variables);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    if !changed {
+        return content.to_string();
+    }
+    serde_json::to_string_pretty(&scene).unwrap_or_else(|_| content.to_string())
+}
+
+/// Year, month and day of a count of days since 1970-01-01 in the proleptic
+/// Gregorian calendar
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::AppError;
+use crate::repair;
+
+/// Upper bounds for one AI request; larger scenes are split into several batches
+const BATCH_MAX_SEGMENTS: usize = 40;
+const BATCH_MAX_CHARS: usize = 4000;
+
+/// One translatable string of the scene
+#[derive(Debug, Clone)]
+pub struct TextSegment {
+    /// Short key sent to the model instead of the element id
+    pub key: String,
+    pub element_index: usize,
+    /// `text` for text elements (labels included), `name` for frames
+    pub field: &'static str,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TranslateSceneResult {
+    pub output_path: String,
+    pub translated: usize,
+    pub total: usize,
+    /// Source strings the model returned no translation for; they are left as-is
+    pub untranslated: Vec<String>,
+    pub batches: usize,
+}
+
+pub fn collect_segments(scene: &Value) -> Vec<TextSegment> {
+    let Some(elements) = scene.get("elements").and_then(|e| e.as_array()) else {
+        return Vec::new();
+    };
+
+    let mut segments = Vec::new();
+    for (index, element) in elements.iter().enumerate() {
+        if element.get("isDeleted").and_then(|d| d.as_bool()).unwrap_or(false) {
+            continue;
+        }
+        let field = match element.get("type").and_then(|t| t.as_str()) {
+            Some("text") => "text",
+            Some("frame") | Some("magicframe") => "name",
+            _ => continue,
+        };
+        let Some(text) = element.get(field).and_then(|t| t.as_str()) else {
+            continue;
+        };
+        // Nothing to translate in empty strings or pure numbers/symbols
+        if !text.chars().any(|c| c.is_alphabetic()) {
+            continue;
+        }
+        segments.push(TextSegment {
+            key: format!("t{}", segments.len() + 1),
+            element_index: index,
+            field,
+            text: text.to_string(),
+        });
+    }
+    segments
+}
+
+/// Splits segments into batches that fit one request
+pub fn batches(segments: &[TextSegment]) -> Vec<&[TextSegment]> {
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut chars = 0;
+
+    for (i, segment) in segments.iter().enumerate() {
+        let len = segment.text.chars().count();
+        if i > start && (i - start >= BATCH_MAX_SEGMENTS || chars + len > BATCH_MAX_CHARS) {
+            batches.push(&segments[start..i]);
+            start = i;
+            chars = 0;
+        }
+        chars += len;
+    }
+    if start < segments.len() {
+        batches.push(&segments[start..]);
+    }
+    batches
+}
+
+pub fn build_prompt(batch: &[TextSegment], target_language: &str) -> String {
+    let source: Map<String, Value> = batch
+        .iter()
+        .map(|s| (s.key.clone(), Value::String(s.text.clone())))
+        .collect();
+
+    format!(
+        "Translate the values of the following JSON object into {}. These are labels of a diagram, so keep them concise. \
+         Preserve line breaks, placeholders, code, numbers and product names. \
+         Reply with only a JSON object that has exactly the same keys and the translated strings as values.\n\n{}",
+        target_language,
+        Value::Object(source)
+    )
+}
+
+/// Extracts `key -> translation` pairs from the model reply, tolerating code fences and surrounding prose
+pub fn parse_translations(reply: &str) -> Result<HashMap<String, String>, AppError> {
+    let text = repair::strip_markdown_fences(reply).unwrap_or(reply);
+    let value: Value = serde_json::from_str(repair::trim_to_json(text))
+        .map_err(|e| AppError::InvalidInput(format!("AI reply is not valid JSON: {}", e)))?;
+    let object = value.as_object()
+        .ok_or_else(|| AppError::InvalidInput("AI reply is not a JSON object".to_string()))?;
+
+    Ok(object
+        .iter()
+        .filter_map(|(key, value)| Some((key.clone(), value.as_str()?.to_string())))
+        .collect())
+}
+
+fn longest_line(text: &str) -> usize {
+    text.lines().map(|l| l.chars().count()).max().unwrap_or(0)
+}
+
+/// Writes translations back into the scene. Ids, bindings and positions are kept;
+/// text boxes are resized proportionally so longer translations don't overflow.
+pub fn apply(scene: &mut Value, segments: &[TextSegment], translations: &HashMap<String, String>) -> usize {
+    let Some(elements) = scene.get_mut("elements").and_then(|e| e.as_array_mut()) else {
+        return 0;
+    };
+
+    let mut applied = 0;
+    for segment in segments {
+        let (Some(translation), Some(element)) = (
+            translations.get(&segment.key),
+            elements.get_mut(segment.element_index),
+        ) else {
+            continue;
+        };
+
+        element[segment.field] = Value::String(translation.clone());
+        if segment.field == "text" {
+            element["originalText"] = Value::String(translation.clone());
+
+            let (old_len, new_len) = (longest_line(&segment.text), longest_line(translation));
+            let width = element.get("width").and_then(|w| w.as_f64());
+            // Bound labels are re-wrapped by their container, so only free text is resized
+            let is_label = element.get("containerId").is_some_and(|c| c.is_string());
+            if let (Some(width), false, true) = (width, is_label, old_len > 0) {
+                element["width"] = (width * new_len as f64 / old_len as f64).into();
+            }
+        }
+
+        let version = element.get("version").and_then(|v| v.as_i64()).unwrap_or(1);
+        element["version"] = (version + 1).into();
+        applied += 1;
+    }
+    applied
+}
+
+/// `diagram.excalidraw` translated to "German" becomes `diagram.german.excalidraw`
+pub fn translated_path(source: &Path, target_language: &str) -> PathBuf {
+    let stem = source.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let suffix: String = target_language
+        .trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect::<String>()
+        .to_lowercase();
+    source.with_file_name(format!("{}.{}.excalidraw", stem, suffix.trim_matches('-')))
+}
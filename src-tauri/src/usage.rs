@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::AppError;
+
+/// Store file holding the AI usage ledger and the monthly budget
+pub const STORE_FILE: &str = "ai_usage.json";
+pub const LEDGER_KEY: &str = "ledger";
+pub const BUDGET_KEY: &str = "budget";
+
+/// Records older than this are pruned whenever the ledger is written
+const RETENTION_MS: i64 = 400 * DAY_MS;
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageRecord {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: i64,
+    pub provider: String,
+    pub model: String,
+    /// "generate", "stream" or "image"
+    pub operation: String,
+    pub tokens_used: u32,
+    /// Set when the provider did not report usage and tokens were estimated from text length
+    #[serde(default)]
+    pub estimated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct UsageBudget {
+    pub monthly_token_limit: Option<u64>,
+    pub monthly_cost_limit: Option<f64>,
+    /// Price per 1K tokens by model id; a key ending in `*` matches model prefixes
+    pub prices_per_1k_tokens: HashMap<String, f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ModelUsage {
+    pub provider: String,
+    pub model: String,
+    pub requests: u64,
+    pub tokens: u64,
+    pub cost: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageStats {
+    pub range: String,
+    pub from: Option<i64>,
+    pub total_requests: u64,
+    pub total_tokens: u64,
+    pub total_cost: f64,
+    pub by_model: Vec<ModelUsage>,
+    pub budget: UsageBudget,
+    pub month_tokens: u64,
+    pub month_cost: f64,
+    pub budget_exceeded: bool,
+}
+
+/// Rough token estimate for providers that don't report usage on streams
+pub fn estimate_tokens(text: &str) -> u32 {
+    (text.chars().count() as u32).div_ceil(4)
+}
+
+/// Converts days since the Unix epoch into a (year, month) civil date
+fn year_month_from_days(days: i64) -> (i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month)
+}
+
+fn month_of(timestamp: i64) -> (i64, i64) {
+    year_month_from_days(timestamp.div_euclid(DAY_MS))
+}
+
+/// Start (inclusive, in ms) of a named range: "today", "week", "30d", "month" or "all"
+pub fn range_start(range: &str, now: i64) -> Result<Option<i64>, AppError> {
+    match range {
+        "today" => Ok(Some(now - now.rem_euclid(DAY_MS))),
+        "week" | "7d" => Ok(Some(now - 7 * DAY_MS)),
+        "30d" => Ok(Some(now - 30 * DAY_MS)),
+        // Calendar month (UTC); walk back to the first day of the current month
+        "month" => {
+            let current = month_of(now);
+            let mut day = now.div_euclid(DAY_MS);
+            while year_month_from_days(day - 1) == current {
+                day -= 1;
+            }
+            Ok(Some(day * DAY_MS))
+        }
+        "all" => Ok(None),
+        _ => Err(AppError::InvalidInput(format!("Unknown usage range: {}", range))),
+    }
+}
+
+impl UsageBudget {
+    fn price_for(&self, model: &str) -> Option<f64> {
+        self.prices_per_1k_tokens.get(model).copied().or_else(|| {
+            self.prices_per_1k_tokens
+                .iter()
+                .filter_map(|(key, price)| Some((key.strip_suffix('*')?, price)))
+                .filter(|(prefix, _)| model.starts_with(prefix))
+                .max_by_key(|(prefix, _)| prefix.len())
+                .map(|(_, price)| *price)
+        })
+    }
+
+    pub fn cost_of(&self, record: &UsageRecord) -> f64 {
+        self.price_for(&record.model)
+            .map(|price| record.tokens_used as f64 / 1000.0 * price)
+            .unwrap_or(0.0)
+    }
+
+    /// Tokens and cost spent in the calendar month containing `now`
+    pub fn month_totals(&self, ledger: &[UsageRecord], now: i64) -> (u64, f64) {
+        let current = month_of(now);
+        ledger
+            .iter()
+            .filter(|r| month_of(r.timestamp) == current)
+            .fold((0, 0.0), |(tokens, cost), r| {
+                (tokens + r.tokens_used as u64, cost + self.cost_of(r))
+            })
+    }
+
+    /// Fails once this month's tokens or cost reached a configured limit
+    pub fn check(&self, ledger: &[UsageRecord], now: i64) -> Result<(), AppError> {
+        let (tokens, cost) = self.month_totals(ledger, now);
+        if let Some(limit) = self.monthly_token_limit.filter(|l| tokens >= *l) {
+            return Err(AppError::TooLarge(format!(
+                "Monthly AI token budget exceeded ({} of {} tokens used)",
+                tokens, limit
+            )));
+        }
+        if let Some(limit) = self.monthly_cost_limit.filter(|l| cost >= *l) {
+            return Err(AppError::TooLarge(format!(
+                "Monthly AI cost budget exceeded ({:.2} of {:.2} spent)",
+                cost, limit
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Appends a record and drops records past the retention window
+pub fn append(ledger: &mut Vec<UsageRecord>, record: UsageRecord) {
+    let cutoff = record.timestamp - RETENTION_MS;
+    ledger.retain(|r| r.timestamp >= cutoff);
+    ledger.push(record);
+}
+
+pub fn stats(ledger: &[UsageRecord], budget: &UsageBudget, range: &str, now: i64) -> Result<UsageStats, AppError> {
+    let from = range_start(range, now)?;
+    let mut by_model: BTreeMap<(String, String), ModelUsage> = BTreeMap::new();
+
+    for record in ledger.iter().filter(|r| from.is_none_or(|from| r.timestamp >= from)) {
+        let entry = by_model
+            .entry((record.provider.clone(), record.model.clone()))
+            .or_insert_with(|| ModelUsage {
+                provider: record.provider.clone(),
+                model: record.model.clone(),
+                requests: 0,
+                tokens: 0,
+                cost: 0.0,
+            });
+        entry.requests += 1;
+        entry.tokens += record.tokens_used as u64;
+        entry.cost += budget.cost_of(record);
+    }
+
+    let mut by_model: Vec<ModelUsage> = by_model.into_values().collect();
+    by_model.sort_by_key(|m| std::cmp::Reverse(m.tokens));
+    let (month_tokens, month_cost) = budget.month_totals(ledger, now);
+
+    Ok(UsageStats {
+        range: range.to_string(),
+        from,
+        total_requests: by_model.iter().map(|m| m.requests).sum(),
+        total_tokens: by_model.iter().map(|m| m.tokens).sum(),
+        total_cost: by_model.iter().map(|m| m.cost).sum(),
+        by_model,
+        budget: budget.clone(),
+        month_tokens,
+        month_cost,
+        budget_exceeded: budget.check(ledger, now).is_err(),
+    })
+}
@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::AppError;
+use crate::security;
+
+/// Local folders the file commands may work in: vaults the user picked or
+/// opened, plus single files picked as a Save As, export or import target.
+/// Mounted storage is addressed relative to its mount and needs no entry here.
+#[derive(Default)]
+pub struct VaultAccess {
+    roots: Mutex<HashSet<PathBuf>>,
+    files: Mutex<HashSet<PathBuf>>,
+}
+
+impl VaultAccess {
+    /// Ignored for folders that don't exist
+    pub fn allow_root(&self, root: &Path) {
+        if let Ok(root) = root.canonicalize() {
+            self.roots.lock().unwrap().insert(root);
+        }
+    }
+
+    /// The file may not exist yet, as for export targets, but its folder must
+    pub fn allow_file(&self, path: &Path) {
+        if let Some(path) = canonical_file(path) {
+            self.files.lock().unwrap().insert(path);
+        }
+    }
+
+    /// Whether `path` is one of the roots itself, which commands must not
+    /// rename or delete from under the open vault
+    pub fn is_root(&self, path: &Path) -> bool {
+        path.canonicalize()
+            .is_ok_and(|path| self.roots.lock().unwrap().contains(&path))
+    }
+
+    /// The canonical form of `path`, which has to lie in an allowed root or
+    /// be a file the user picked in a dialog
+    pub fn check(&self, path: &Path) -> Result<PathBuf, AppError> {
+        if let Some(canonical) = canonical_file(path)
+            && self.files.lock().unwrap().contains(&canonical)
+        {
+            return Ok(canonical);
+        }
+        self.roots
+            .lock()
+            .unwrap()
+            .iter()
+            .find_map(|root| security::validate_path(path, Some(root)).ok())
+            .ok_or_else(|| {
+                AppError::InvalidPath(format!("{} is outside the opened folders", path.display()))
+            })
+    }
+}
+
+/// The canonical form of a file that may not exist yet
+fn canonical_file(path: &Path) -> Option<PathBuf> {
+    path.canonicalize()
+        .ok()
+        .or_else(|| Some(path.parent()?.canonicalize().ok()?.join(path.file_name()?)))
+}
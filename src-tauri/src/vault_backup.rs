@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::archive;
+use crate::error::AppError;
+use crate::ignores::IgnoreRules;
+use crate::paths;
+use crate::storage::EntryMetadata;
+
+/// Emitted to every window when a backup starts, finishes or fails
+pub const STATUS_EVENT: &str = "vault-backup-status";
+
+/// How often the scheduler checks whether a backup is due
+pub const TICK: Duration = Duration::from_secs(60);
+
+/// Length of the `-YYYYMMDD-HHMMSS` suffix of backup names
+const STAMP_LEN: usize = 16;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupFormat {
+    /// One `.zip` per backup, made by `archive`
+    #[default]
+    Zip,
+    /// A plain copy of the drawings, settings and images
+    Copy,
+}
+
+/// Copies of whole vaults in a folder of the user's choice, made on an
+/// interval and when the app quits
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct VaultBackupSettings {
+    pub enabled: bool,
+    /// Must be outside every vault; nothing is backed up while unset
+    pub directory: Option<String>,
+    /// Minutes between scheduled backups; 0 only backs up on exit and on demand
+    pub interval_minutes: u32,
+    pub on_exit: bool,
+    /// Backups kept per vault, oldest removed first; 0 keeps all
+    pub keep: usize,
+    pub format: BackupFormat,
+}
+
+impl Default for VaultBackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: None,
+            interval_minutes: 60,
+            on_exit: true,
+            keep: 10,
+            format: BackupFormat::default(),
+        }
+    }
+}
+
+impl VaultBackupSettings {
+    pub fn directory(&self) -> Option<PathBuf> {
+        self.directory
+            .as_deref()
+            .filter(|d| !d.trim().is_empty())
+            .map(PathBuf::from)
+    }
+}
+
+/// A backup found in the backup folder
+#[derive(Debug, Serialize, Clone)]
+pub struct VaultBackupInfo {
+    /// Folder name of the vault it was made from
+    pub vault: String,
+    pub path: String,
+    pub format: BackupFormat,
+    #[serde(flatten)]
+    pub metadata: EntryMetadata,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupState {
+    Started,
+    Finished,
+    Failed,
+}
+
+/// Payload of `STATUS_EVENT`
+#[derive(Debug, Serialize, Clone)]
+pub struct BackupStatus {
+    pub state: BackupState,
+    pub vault: String,
+    /// The new backup, once finished
+    pub path: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Keeps the scheduler, `run_backup_now` and the exit backup from running
+/// at the same time
+#[derive(Default)]
+pub struct BackupLock(Mutex<()>);
+
+impl BackupLock {
+    pub fn try_lock(&self) -> Result<std::sync::MutexGuard<'_, ()>, AppError> {
+        self.0
+            .try_lock()
+            .map_err(|_| AppError::Internal("A backup is already running".to_string()))
+    }
+
+    /// Waits for a running backup, for the one made on exit
+    pub fn lock(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub fn vault_name(vault: &Path) -> String {
+    vault
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "vault".to_string())
+}
+
+/// `<vault>-YYYYMMDD-HHMMSS` in UTC, so names sort by age
+pub fn backup_name(vault: &str, now_millis: i64) -> String {
+    let seconds = now_millis.div_euclid(1000);
+    let (year, month, day) = crate::templates::civil_from_days(seconds.div_euclid(86_400));
+    let time = seconds.rem_euclid(86_400);
+    format!(
+        "{}-{:04}{:02}{:02}-{:02}{:02}{:02}",
+        vault,
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// The vault a backup name belongs to, if it is one
+fn parse_backup_name(name: &str) -> Option<(String, BackupFormat)> {
+    let (stem, format) = match name.strip_suffix(".zip") {
+        Some(stem) => (stem, BackupFormat::Zip),
+        None => (name, BackupFormat::Copy),
+    };
+    let split = stem.len().checked_sub(STAMP_LEN)?;
+    let stamp = stem.get(split..)?.as_bytes();
+    let well_formed = stamp[0] == b'-'
+        && stamp[9] == b'-'
+        && stamp[1..9].iter().chain(&stamp[10..]).all(u8::is_ascii_digit);
+    (well_formed && split > 0).then(|| (stem[..split].to_string(), format))
+}
+
+/// Backups in `directory`, newest first, optionally only those of `vault`
+pub fn list(directory: &Path, vault: Option<&str>) -> Vec<VaultBackupInfo> {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<(String, VaultBackupInfo)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let (owner, format) = parse_backup_name(&name)?;
+            if vault.is_some_and(|vault| vault != owner) {
+                return None;
+            }
+            let metadata = entry.metadata().ok()?;
+            if metadata.is_dir() != (format == BackupFormat::Copy) {
+                return None;
+            }
+            let stamp = name.trim_end_matches(".zip")[owner.len()..].to_string();
+            Some((
+                stamp,
+                VaultBackupInfo {
+                    vault: owner,
+                    path: paths::normalize(&entry.path()),
+                    format,
+                    metadata: EntryMetadata::from_fs(&metadata),
+                },
+            ))
+        })
+        .collect();
+    backups.sort_by(|a, b| b.0.cmp(&a.0));
+    backups.into_iter().map(|(_, backup)| backup).collect()
+}
+
+/// Whether the newest backup of `vault` is older than the interval
+pub fn is_due(settings: &VaultBackupSettings, directory: &Path, vault: &Path, now_millis: i64) -> bool {
+    if settings.interval_minutes == 0 {
+        return false;
+    }
+    let interval = i64::from(settings.interval_minutes) * 60_000;
+    list(directory, Some(&vault_name(vault)))
+        .first()
+        .and_then(|latest| latest.metadata.modified_at)
+        .is_none_or(|modified| now_millis - modified >= interval)
+}
+
+/// Backs up one vault into `directory` and removes the backups beyond
+/// `settings.keep`; returns the new backup
+pub fn run(
+    vault: &Path,
+    directory: &Path,
+    ignore: &IgnoreRules,
+    settings: &VaultBackupSettings,
+    now_millis: i64,
+) -> Result<PathBuf, AppError> {
+    if directory.starts_with(vault) {
+        return Err(AppError::InvalidPath(
+            "The backup folder must be outside the vault".to_string(),
+        ));
+    }
+    fs::create_dir_all(directory)?;
+
+    let name = vault_name(vault);
+    let stem = backup_name(&name, now_millis);
+    let target = match settings.format {
+        BackupFormat::Zip => directory.join(format!("{}.zip", stem)),
+        BackupFormat::Copy => directory.join(&stem),
+    };
+    if target.exists() {
+        return Err(AppError::AlreadyExists(format!("{} already exists", target.display())));
+    }
+
+    let mut entries = Vec::new();
+    archive::collect(vault, "", ignore, true, &target, &mut entries)?;
+    match settings.format {
+        BackupFormat::Zip => {
+            archive::write(&entries, &target, |_| true)?;
+        }
+        BackupFormat::Copy => copy_entries(&entries, &target)?,
+    }
+
+    prune(directory, &name, settings.keep);
+    Ok(target)
+}
+
+/// Copies into `<target>.part` first, so an interrupted backup never looks complete
+fn copy_entries(entries: &[archive::ArchiveEntry], target: &Path) -> Result<(), AppError> {
+    let mut temp = target.as_os_str().to_owned();
+    temp.push(".part");
+    let temp = PathBuf::from(temp);
+    let result = entries.iter().try_for_each(|entry| {
+        let destination = temp.join(&entry.name);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&entry.source, &destination).map(|_| ())
+    });
+    let result = result
+        .and_then(|()| fs::create_dir_all(&temp))
+        .and_then(|()| fs::rename(&temp, target));
+    if let Err(e) = result {
+        let _ = fs::remove_dir_all(&temp);
+        return Err(AppError::Io(format!("Failed to copy vault: {}", e)));
+    }
+    Ok(())
+}
+
+/// Best-effort: a backup that cannot be removed is retried next time
+fn prune(directory: &Path, vault: &str, keep: usize) {
+    if keep == 0 {
+        return;
+    }
+    for old in list(directory, Some(vault)).into_iter().skip(keep) {
+        let path = Path::new(&old.path);
+        let result = match old.format {
+            BackupFormat::Zip => fs::remove_file(path),
+            BackupFormat::Copy => fs::remove_dir_all(path),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to remove old backup {}: {}", old.path, e);
+        }
+    }
+}
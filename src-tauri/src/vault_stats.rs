@@ -0,0 +1,215 @@
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::background::TaskManager;
+use crate::backups;
+use crate::compression;
+use crate::config;
+use crate::error::{AppError, ResultExt};
+use crate::file_locks;
+use crate::health;
+use crate::ignores::{self, IgnoreRules};
+use crate::metadata_index::{MetadataIndex, VaultStatistics};
+use crate::paths;
+
+/// Drawings listed as the largest
+const LARGEST_FILES: usize = 10;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// Upper age of each bucket of the modification histogram, in days
+const AGE_BUCKETS: &[(&str, i64)] = &[
+    ("day", 1),
+    ("week", 7),
+    ("month", 30),
+    ("quarter", 91),
+    ("year", 365),
+];
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FileSize {
+    pub path: String,
+    pub size: u64,
+    pub modified_at: Option<i64>,
+}
+
+/// A drawing the health check found problems in
+#[derive(Debug, Serialize, Clone)]
+pub struct FileAttention {
+    pub path: String,
+    /// Problems that keep the drawing from opening
+    pub errors: usize,
+    pub warnings: usize,
+    /// The first problem found, for display
+    pub message: String,
+}
+
+/// Drawings last modified within one age range
+#[derive(Debug, Serialize, Clone)]
+pub struct AgeBucket {
+    /// One of: day, week, month, quarter, year, older, unknown
+    pub age: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+/// Answer of `get_vault_stats`; sizes in bytes
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct VaultStats {
+    pub directory: String,
+    /// Drawing count, size, elements and tags, from the metadata index
+    #[serde(flatten)]
+    pub drawings: VaultStatistics,
+    /// Biggest first
+    pub largest: Vec<FileSize>,
+    /// Drawings with health issues, those that don't open first
+    pub needs_attention: Vec<FileAttention>,
+    /// Files that aren't drawings or the vault's own settings
+    pub foreign_files: usize,
+    pub foreign_size: u64,
+    /// Files and folders hidden by the ignore rules, not looked into
+    pub untracked: usize,
+    /// Previous versions kept in `.backups` folders
+    pub backup_files: usize,
+    pub backup_size: u64,
+    /// Drawings by time since their last change, newest first
+    pub modified: Vec<AgeBucket>,
+}
+
+/// Files below `dir` and their total size, ignore rules aside
+fn folder_size(dir: &Path) -> (usize, u64) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return (0, 0);
+    };
+    entries.flatten().fold((0, 0), |(files, bytes), entry| {
+        match entry.file_type() {
+            Ok(kind) if kind.is_dir() => {
+                let (more_files, more_bytes) = folder_size(&entry.path());
+                (files + more_files, bytes + more_bytes)
+            }
+            Ok(kind) if kind.is_file() => {
+                (files + 1, bytes + entry.metadata().map(|m| m.len()).unwrap_or(0))
+            }
+            _ => (files, bytes),
+        }
+    })
+}
+
+/// Counts what the metadata index leaves out: other files, ignored paths
+/// and backups
+fn walk(dir: &Path, ignore: &IgnoreRules, stats: &mut VaultStats) -> Result<(), AppError> {
+    let entries = fs::read_dir(dir).context(format!("Failed to read {}", dir.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // Symlinks are left alone, so links to folders can't loop
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        if kind.is_dir() && entry.file_name() == backups::BACKUP_DIR {
+            let (files, bytes) = folder_size(&path);
+            stats.backup_files += files;
+            stats.backup_size += bytes;
+            continue;
+        }
+        if ignore.is_ignored(&path, kind.is_dir()) {
+            stats.untracked += 1;
+            continue;
+        }
+        if kind.is_dir() {
+            walk(&path, ignore, stats)?;
+            continue;
+        }
+        if kind.is_file()
+            && !compression::is_drawing(&path)
+            && !config::is_config_file(&path)
+            && !ignores::is_ignore_file(&path)
+            && !file_locks::is_lock_file(&path)
+        {
+            stats.foreign_files += 1;
+            stats.foreign_size += entry.metadata().map(|m| m.len()).unwrap_or(0);
+        }
+    }
+    Ok(())
+}
+
+fn histogram(drawings: &[FileSize], now: i64) -> Vec<AgeBucket> {
+    let mut buckets: Vec<AgeBucket> = AGE_BUCKETS
+        .iter()
+        .map(|(age, _)| *age)
+        .chain(["older", "unknown"])
+        .map(|age| AgeBucket {
+            age: age.to_string(),
+            files: 0,
+            bytes: 0,
+        })
+        .collect();
+    for drawing in drawings {
+        let index = match drawing.modified_at {
+            Some(modified) => AGE_BUCKETS
+                .iter()
+                .position(|(_, days)| now - modified < days * DAY_MILLIS)
+                .unwrap_or(AGE_BUCKETS.len()),
+            None => AGE_BUCKETS.len() + 1,
+        };
+        buckets[index].files += 1;
+        buckets[index].bytes += drawing.size;
+    }
+    buckets
+}
+
+/// Sizes, ages and health of everything below `directory`. Drawings come
+/// from the metadata index, which must be current; each one is health
+/// checked on the background workers.
+pub fn collect(
+    directory: &Path,
+    ignore: &IgnoreRules,
+    index: &MetadataIndex,
+    tasks: &TaskManager,
+    now: i64,
+) -> Result<VaultStats, AppError> {
+    let root = paths::normalize(directory);
+    let mut stats = VaultStats {
+        drawings: index.statistics(&root)?,
+        directory: root.clone(),
+        ..Default::default()
+    };
+    walk(directory, ignore, &mut stats)?;
+
+    let drawings: Vec<FileSize> = index
+        .stamps_under(&root)?
+        .into_iter()
+        .map(|(path, (size, modified_at))| FileSize {
+            path,
+            size,
+            modified_at,
+        })
+        .collect();
+    stats.modified = histogram(&drawings, now);
+
+    let mut largest = drawings.clone();
+    largest.sort_by(|a, b| b.size.cmp(&a.size).then(a.path.cmp(&b.path)));
+    largest.truncate(LARGEST_FILES);
+    stats.largest = largest;
+
+    let checked = tasks.run(drawings, |drawing| {
+        tasks.throttle_io(drawing.size as usize);
+        let issues = health::check_file(Path::new(&drawing.path));
+        (drawing.path, issues)
+    });
+    let mut needs_attention: Vec<FileAttention> = checked
+        .into_iter()
+        .filter_map(|(path, issues)| {
+            let errors = issues.iter().filter(|i| i.severity == "error").count();
+            Some(FileAttention {
+                message: issues.first()?.message.clone(),
+                errors,
+                warnings: issues.len() - errors,
+                path,
+            })
+        })
+        .collect();
+    needs_attention.sort_by(|a, b| b.errors.cmp(&a.errors).then(a.path.cmp(&b.path)));
+    stats.needs_attention = needs_attention;
+    Ok(stats)
+}
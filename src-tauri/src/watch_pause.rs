@@ -0,0 +1,95 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::fs_events::{ChangeKind, FileSystemChange};
+
+/// Emitted once watching resumes, in place of the held-back changes
+pub const SUMMARY_EVENT: &str = "file-system-summary";
+
+/// How long after a batch operation its last watcher events may still arrive
+pub const SETTLE_DELAY: Duration = Duration::from_millis(500);
+
+/// Changes kept for the summary; beyond this the frontend just rescans
+const MAX_QUEUED: usize = 1000;
+
+/// Payload of `SUMMARY_EVENT`
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct ChangeSummary {
+    pub created: usize,
+    pub modified: usize,
+    pub removed: usize,
+    pub renamed: usize,
+    /// In the order they happened, without repeated modifications of a path
+    pub changes: Vec<FileSystemChange>,
+    /// Too many changes were held back, or ignore rules changed; the tree has
+    /// to be read again
+    pub rescan: bool,
+}
+
+#[derive(Default)]
+struct Paused {
+    depth: usize,
+    summary: ChangeSummary,
+}
+
+/// Holds back `file-system-change` events during bulk operations. Pauses nest;
+/// events flow again when the last one ends.
+#[derive(Default)]
+pub struct WatchPause {
+    paused: Mutex<Paused>,
+}
+
+impl WatchPause {
+    pub fn pause(&self) {
+        self.paused.lock().unwrap().depth += 1;
+    }
+
+    /// Ends one pause; when it was the last, returns what happened meanwhile
+    pub fn resume(&self) -> Option<ChangeSummary> {
+        let mut paused = self.paused.lock().unwrap();
+        if paused.depth == 0 {
+            return None;
+        }
+        paused.depth -= 1;
+        if paused.depth > 0 {
+            return None;
+        }
+        let summary = std::mem::take(&mut paused.summary);
+        let empty = summary.changes.is_empty() && !summary.rescan;
+        (!empty).then_some(summary)
+    }
+
+    /// Queues `change` while paused; otherwise hands it back to be emitted
+    pub fn hold(&self, change: FileSystemChange) -> Option<FileSystemChange> {
+        let mut paused = self.paused.lock().unwrap();
+        if paused.depth == 0 {
+            return Some(change);
+        }
+
+        let summary = &mut paused.summary;
+        match change.kind {
+            ChangeKind::Created => summary.created += 1,
+            ChangeKind::Modified => summary.modified += 1,
+            ChangeKind::Removed => summary.removed += 1,
+            ChangeKind::Renamed => summary.renamed += 1,
+        }
+        summary.rescan |= change.rescan;
+
+        // Repeated saves of one file only need its last change, unless that
+        // would hide a create, remove or rename
+        if change.kind == ChangeKind::Modified
+            && summary.changes.iter().any(|c| c.path.path == change.path.path)
+        {
+            return None;
+        }
+        if summary.changes.len() >= MAX_QUEUED {
+            summary.rescan = true;
+            summary.changes.clear();
+        }
+        if !summary.rescan {
+            summary.changes.push(change);
+        }
+        None
+    }
+}
@@ -0,0 +1,237 @@
+use regex::Regex;
+use reqwest::{header, Client, Method, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+use crate::error::AppError;
+
+/// A WebDAV folder the vault is mirrored to, e.g. a Nextcloud or ownCloud
+/// `remote.php/dav/files/<user>/<folder>` URL
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WebDavConfig {
+    pub url: String,
+    pub username: String,
+    /// An app password; only read by the backend, listings report
+    /// `has_password` instead
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub password: String,
+}
+
+/// A file or folder on the server, relative to the configured folder
+#[derive(Debug, Clone)]
+pub struct RemoteEntry {
+    /// `/`-separated, without leading or trailing slashes
+    pub path: String,
+    pub is_directory: bool,
+    pub etag: Option<String>,
+}
+
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:"><d:prop><d:resourcetype/><d:getetag/></d:prop></d:propfind>"#;
+
+// Servers differ in namespace prefixes (d:, D:, none), so tags match any prefix
+static RESPONSE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<(?:\w+:)?response\b.*?</(?:\w+:)?response>").unwrap());
+static HREF: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<(?:\w+:)?href>(.*?)</(?:\w+:)?href>").unwrap());
+static ETAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<(?:\w+:)?getetag>(.*?)</(?:\w+:)?getetag>").unwrap());
+static COLLECTION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<(?:\w+:)?collection\s*/?>").unwrap());
+
+fn propfind() -> Method {
+    Method::from_bytes(b"PROPFIND").unwrap()
+}
+
+fn mkcol() -> Method {
+    Method::from_bytes(b"MKCOL").unwrap()
+}
+
+pub fn unescape_xml(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+pub fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+pub struct WebDavClient {
+    client: Client,
+    /// Always ends with `/`
+    base: Url,
+    username: String,
+    password: String,
+}
+
+impl WebDavClient {
+    pub fn new(config: &WebDavConfig, client: Client) -> Result<Self, AppError> {
+        let mut base = Url::parse(config.url.trim())
+            .map_err(|e| AppError::InvalidInput(format!("Invalid WebDAV URL: {}", e)))?;
+        if !matches!(base.scheme(), "http" | "https") {
+            return Err(AppError::InvalidInput("WebDAV URL must start with http:// or https://".to_string()));
+        }
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        Ok(Self {
+            client,
+            base,
+            username: config.username.clone(),
+            password: config.password.clone(),
+        })
+    }
+
+    /// URL of `path` below the configured folder, each segment percent-encoded
+    fn url(&self, path: &str) -> Url {
+        let mut url = self.base.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty();
+            segments.extend(path.split('/').filter(|s| !s.is_empty()));
+        }
+        url
+    }
+
+    fn request(&self, method: Method, path: &str) -> reqwest::RequestBuilder {
+        self.client
+            .request(method, self.url(path))
+            .basic_auth(&self.username, Some(&self.password))
+    }
+
+    async fn check_status(response: reqwest::Response, action: &str) -> Result<reqwest::Response, AppError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        Err(match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+                AppError::PermissionDenied(format!("WebDAV server refused to {}: {}", action, status))
+            }
+            StatusCode::NOT_FOUND => AppError::NotFound(format!("WebDAV server could not {}: {}", action, status)),
+            _ => AppError::Network(format!("WebDAV server failed to {}: {}", action, status)),
+        })
+    }
+
+    /// Makes sure the configured folder exists and the credentials work
+    pub async fn check(&self) -> Result<(), AppError> {
+        let response = self
+            .request(propfind(), "")
+            .header("Depth", "0")
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return self.create_folder("").await;
+        }
+        Self::check_status(response, "open the folder").await.map(|_| ())
+    }
+
+    /// Every entry below the configured folder, one PROPFIND per folder since
+    /// servers like Nextcloud refuse `Depth: infinity`
+    pub async fn list(&self) -> Result<Vec<RemoteEntry>, AppError> {
+        let mut entries = Vec::new();
+        let mut pending = vec![String::new()];
+        while let Some(folder) = pending.pop() {
+            for entry in self.list_folder(&folder).await? {
+                if entry.is_directory {
+                    pending.push(entry.path.clone());
+                }
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    async fn list_folder(&self, folder: &str) -> Result<Vec<RemoteEntry>, AppError> {
+        let response = self
+            .request(propfind(), folder)
+            .header("Depth", "1")
+            .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(PROPFIND_BODY)
+            .send()
+            .await?;
+        let body = Self::check_status(response, "list files").await?.text().await?;
+
+        let base_path = percent_decode(self.base.path());
+        let mut entries = Vec::new();
+        for response in RESPONSE.find_iter(&body) {
+            let response = response.as_str();
+            let Some(href) = HREF.captures(response).map(|c| unescape_xml(c[1].trim())) else {
+                continue;
+            };
+            // Hrefs are usually absolute paths, sometimes full URLs
+            let href_path = Url::parse(&href)
+                .map(|url| url.path().to_string())
+                .unwrap_or(href);
+            let decoded = percent_decode(&href_path);
+            let Some(path) = decoded.strip_prefix(&base_path) else {
+                continue;
+            };
+            let path = path.trim_matches('/').to_string();
+            // The folder itself is listed first
+            if path.is_empty() || path == folder {
+                continue;
+            }
+            entries.push(RemoteEntry {
+                path,
+                is_directory: COLLECTION.is_match(response),
+                etag: ETAG.captures(response).map(|c| unescape_xml(c[1].trim())),
+            });
+        }
+        Ok(entries)
+    }
+
+    pub async fn download(&self, path: &str) -> Result<Vec<u8>, AppError> {
+        let response = self.request(Method::GET, path).send().await?;
+        let response = Self::check_status(response, "download a file").await?;
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    /// Returns the new ETag when the server reports one
+    pub async fn upload(&self, path: &str, content: Vec<u8>) -> Result<Option<String>, AppError> {
+        let response = self.request(Method::PUT, path).body(content).send().await?;
+        let response = Self::check_status(response, "upload a file").await?;
+        Ok(response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| etag.to_string()))
+    }
+
+    /// Succeeds when the folder exists already
+    pub async fn create_folder(&self, path: &str) -> Result<(), AppError> {
+        let response = self.request(mkcol(), path).send().await?;
+        if response.status() == StatusCode::METHOD_NOT_ALLOWED {
+            return Ok(());
+        }
+        Self::check_status(response, "create a folder").await.map(|_| ())
+    }
+
+    pub async fn delete(&self, path: &str) -> Result<(), AppError> {
+        let response = self.request(Method::DELETE, path).send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        Self::check_status(response, "delete a file").await.map(|_| ())
+    }
+}
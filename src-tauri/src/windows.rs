@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager, WebviewWindow};
+
+use crate::launch::LaunchTarget;
+
+/// Label of the window created from `tauri.conf.json`
+pub const MAIN_WINDOW: &str = "main";
+
+/// What each editor window has open
+#[derive(Debug, Default, Clone)]
+pub struct WindowState {
+    pub current_directory: Option<PathBuf>,
+    /// Files with unsaved changes, as reported by `mark_file_dirty`
+    pub modified_files: Vec<String>,
+    /// Drawing shown in the editor, for greying out file-only menu items
+    pub active_file: Option<String>,
+    pub active_file_dirty: bool,
+    /// File or folder to open once the window's frontend is ready
+    pub startup_target: Option<LaunchTarget>,
+    /// Set by `frontend_ready`; until then targets are held back
+    pub ready: bool,
+}
+
+/// Per-window state keyed by window label
+#[derive(Default)]
+pub struct WindowStates {
+    windows: Mutex<HashMap<String, WindowState>>,
+    next_id: AtomicU64,
+}
+
+impl WindowStates {
+    /// A fresh label for an additional window
+    pub fn next_label(&self) -> String {
+        format!("editor-{}", self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    /// Runs `f` on the state of `label`, creating it on first use
+    pub fn with<T>(&self, label: &str, f: impl FnOnce(&mut WindowState) -> T) -> T {
+        f(self.windows.lock().unwrap().entry(label.to_string()).or_default())
+    }
+
+    pub fn current_directory(&self, label: &str) -> Option<PathBuf> {
+        self.windows
+            .lock()
+            .unwrap()
+            .get(label)
+            .and_then(|w| w.current_directory.clone())
+    }
+
+    /// Whether any window other than `label` has `directory` open
+    pub fn is_open_elsewhere(&self, label: &str, directory: &Path) -> bool {
+        self.windows
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(l, w)| l != label && w.current_directory.as_deref() == Some(directory))
+    }
+
+    /// Records that `path` has unsaved changes in window `label`, or no longer has
+    pub fn set_modified(&self, label: &str, path: &str, modified: bool) {
+        self.with(label, |w| {
+            w.modified_files.retain(|p| p != path);
+            if modified {
+                w.modified_files.push(path.to_string());
+            }
+        });
+    }
+
+    pub fn modified_files(&self, label: &str) -> Vec<String> {
+        self.with(label, |w| w.modified_files.clone())
+    }
+
+    /// Whether closing window `label` would lose unsaved changes
+    pub fn has_unsaved_changes(&self, label: &str) -> bool {
+        self.with(label, |w| w.active_file_dirty || !w.modified_files.is_empty())
+    }
+
+    pub fn remove(&self, label: &str) -> Option<WindowState> {
+        self.windows.lock().unwrap().remove(label)
+    }
+}
+
+/// The window menu commands and opened files are meant for: the focused one,
+/// else the main window, else any
+pub fn target_window(app: &AppHandle) -> Option<WebviewWindow> {
+    let windows = app.webview_windows();
+    windows
+        .values()
+        .find(|w| w.is_focused().unwrap_or(false))
+        .or_else(|| windows.get(MAIN_WINDOW))
+        .or_else(|| windows.values().next())
+        .cloned()
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::error::AppError;
+use crate::paths;
+use crate::FileTreeNode;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkspaceRoot {
+    pub path: String,
+    /// Folder name shown as the root's heading in the sidebar
+    pub name: String,
+}
+
+/// One tree of the workspace forest; a root that cannot be read carries the
+/// error instead of failing the whole forest
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkspaceTree {
+    pub root: WorkspaceRoot,
+    pub children: Vec<FileTreeNode>,
+    pub error: Option<String>,
+}
+
+pub fn root_info(path: &str) -> WorkspaceRoot {
+    let name = Path::new(path)
+        .file_name()
+        .map(|n| paths::compose(&n.to_string_lossy()))
+        .unwrap_or_else(|| path.to_string());
+    WorkspaceRoot {
+        path: path.to_string(),
+        name,
+    }
+}
+
+/// True when `inner` is `outer` or lies below it
+fn contains(outer: &str, inner: &str) -> bool {
+    inner == outer || inner.starts_with(&format!("{}/", outer.trim_end_matches('/')))
+}
+
+/// Adds a normalized root path; returns false when it is already present.
+/// Nested roots are rejected since their files would show up twice.
+pub fn add_root(roots: &mut Vec<String>, path: String) -> Result<bool, AppError> {
+    if roots.contains(&path) {
+        return Ok(false);
+    }
+    if let Some(existing) = roots.iter().find(|r| contains(r, &path) || contains(&path, r)) {
+        return Err(AppError::InvalidPath(format!(
+            "{} overlaps the workspace folder {}",
+            path, existing
+        )));
+    }
+    roots.push(path);
+    Ok(true)
+}
+
+pub fn remove_root(roots: &mut Vec<String>, path: &str) -> bool {
+    let before = roots.len();
+    roots.retain(|r| r != path);
+    roots.len() != before
+}
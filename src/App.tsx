@@ -1,18 +1,25 @@
-import { useEffect, useState } from 'react'
+import { useEffect, useRef, useState } from 'react'
 import { listen } from '@tauri-apps/api/event'
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow'
 import { invoke } from '@tauri-apps/api/core'
 import { Sidebar } from './components/Sidebar'
 import { ExcalidrawEditor } from './components/ExcalidrawEditor'
 import { useStore } from './store/useStore'
+import { FileSystemChange, FileSystemSummary, FileTreeNode, LaunchTarget, RecoverableDraft } from './types'
+import { applyTreeChange } from './lib/fileTree'
 import { useKeyboardShortcuts } from './hooks/useKeyboardShortcuts'
 import { useMenuHandler } from './hooks/useMenuHandler'
 import { useDialog } from './contexts/DialogContext'
 import { PanelLeft } from 'lucide-react'
 import { AISettingsDialog } from './components/AISettingsDialog'
 import { useI18nStore, useTranslation } from './store/useI18nStore'
-import { useAIConfig } from './store/useAIConfigStore'
+import { useAIConfig, useAIConfigStore } from './store/useAIConfigStore'
+import { AppError, errorMessage } from './lib/errors'
 import './index.css'
 
+// Events meant for one window only are emitted to its label
+const appWindow = getCurrentWebviewWindow()
+
 function App() {
   const { loadPreferences, loadDirectory, currentDirectory, sidebarVisible, isDirty, saveCurrentFile, toggleSidebar, activeFile } = useStore()
   const { showDialog } = useDialog()
@@ -40,6 +47,17 @@ function App() {
         console.log('🚀 App starting initialization...')
         await loadPreferences()
         console.log('✅ Preferences loaded')
+
+        useAIConfigStore.getState().loadProfiles().catch(error => {
+          console.error('Failed to load AI profiles:', error)
+        })
+
+        // A file or folder named on the command line wins over the last directory
+        const unlistenStartup = await appWindow.listen<LaunchTarget>('open-file-on-startup', async (event) => {
+          unlistenStartup()
+          await useStore.getState().openLaunchTarget(event.payload)
+        })
+        await invoke('frontend_ready')
         
         console.log('🌐 Initializing i18n...')
         // 不等待 i18n 初始化完成，先显示应用
@@ -56,6 +74,50 @@ function App() {
     initializeApp()
   }, [])
 
+  // Offer to restore drafts left by a session that ended without saving
+  const draftsCheckedRef = useRef(false)
+  useEffect(() => {
+    if (i18nLoading || draftsCheckedRef.current) return
+    draftsCheckedRef.current = true
+
+    const recoverDrafts = async () => {
+      try {
+        const drafts = await invoke<RecoverableDraft[]>('list_recoverable_drafts')
+        for (const draft of drafts.filter((d) => d.file_exists)) {
+          const name = draft.file_path.split('/').pop() || draft.file_path
+          const restore = await showDialog({
+            title: t('dialog.draftRecovery.title'),
+            message: t('dialog.draftRecovery.message', {
+              file: name.replace('.excalidraw', ''),
+              time: new Date(draft.saved_at).toLocaleString(),
+            }),
+            type: 'warning',
+            confirmLabel: t('dialog.draftRecovery.recover'),
+            cancelLabel: t('dialog.draftRecovery.discard'),
+            showCancel: true
+          })
+
+          if (restore === true) {
+            const content = await invoke<string>('recover_draft', { filePath: draft.file_path })
+            useStore.setState({
+              activeFile: { name, path: draft.file_path, modified: true },
+              fileContent: content,
+              isDirty: true,
+            })
+            useStore.getState().markTreeNodeAsModified(draft.file_path, true)
+            // Only one file can be open; remaining drafts are offered next time
+            break
+          } else if (restore === false) {
+            await invoke('discard_draft', { filePath: draft.file_path })
+          }
+        }
+      } catch (error) {
+        console.error('Failed to check for recoverable drafts:', error)
+      }
+    }
+    recoverDrafts()
+  }, [i18nLoading])
+
   // Listen for menu events
   useEffect(() => {
     const handleOpenAISettings = (event: any) => {
@@ -102,80 +164,109 @@ function App() {
     updateTitle()
   }, [activeFile, isDirty])
 
-  // Listen for file system changes
+  // Apply watcher changes to the file tree, rescanning only when they can't be applied in place
   useEffect(() => {
     if (!currentDirectory) return
 
-    const unlisten = listen('file-system-change', async () => {
-      // Reload the directory to refresh file list
+    const followActiveFile = (change: FileSystemChange) => {
       const state = useStore.getState()
-      await state.loadFileTree(currentDirectory)
-      
-      // If the active file was deleted, clear it
-      if (state.activeFile) {
-        const fileStillExists = state.fileTree.some(node => 
-          node.path === state.activeFile?.path || 
-          (node.children && node.children.some(child => child.path === state.activeFile?.path))
-        )
-        
-        if (!fileStillExists) {
-          state.setActiveFile(null)
-          state.setFileContent(null)
-          state.setIsDirty(false)
-        }
+      const active = state.activeFile
+      if (!active) return
+      const underRemoved = (path: string) => active.path === path || active.path.startsWith(`${path}/`)
+
+      if (change.kind === 'renamed' && change.old_path && underRemoved(change.old_path)) {
+        // Follow the open drawing to its new name
+        const rest = active.path.slice(change.old_path.length)
+        const path = change.path + rest
+        useStore.setState({
+          activeFile: {
+            ...active,
+            path,
+            name: path.slice(path.lastIndexOf('/') + 1),
+            relative_path: change.relative_path !== null ? change.relative_path + rest : undefined,
+          },
+        })
+      } else if (change.kind === 'removed' && underRemoved(change.path) && !state.isDirty) {
+        // Unsaved work stays open; the editor offers to write it back
+        state.setActiveFile(null)
+        state.setFileContent(null)
+        state.setIsDirty(false)
+      }
+    }
+
+    const applyChanges = async (changes: FileSystemChange[], rescan: boolean) => {
+      let fileTree: FileTreeNode[] | null = useStore.getState().fileTree
+      for (const change of changes) {
+        if (!fileTree || rescan) break
+        fileTree = applyTreeChange(fileTree, currentDirectory, change)
+      }
+      if (fileTree && !rescan) {
+        useStore.setState({ fileTree })
+      } else {
+        await useStore.getState().loadFileTree(currentDirectory)
+      }
+      changes.forEach(followActiveFile)
+    }
+
+    const unlistenChange = listen<FileSystemChange>('file-system-change', (event) =>
+      applyChanges([event.payload], false)
+    )
+    // Changes held back while watching was paused for a bulk operation
+    const unlistenSummary = listen<FileSystemSummary>('file-system-summary', (event) =>
+      applyChanges(event.payload.changes, event.payload.rescan)
+    )
+
+    return () => {
+      unlistenChange.then((fn) => fn())
+      unlistenSummary.then((fn) => fn())
+    }
+  }, [currentDirectory, loadDirectory])
+
+  // Later launches forward their paths to this instance
+  useEffect(() => {
+    const unlisten = appWindow.listen<LaunchTarget[]>('open-paths', async (event) => {
+      for (const target of event.payload) {
+        await useStore.getState().openLaunchTarget(target)
       }
     })
 
     return () => {
       unlisten.then((fn) => fn())
     }
-  }, [currentDirectory, loadDirectory])
+  }, [])
 
-  // Listen for window close event
+  // excaliapp:// links that failed validation or could not be carried out
   useEffect(() => {
-    const unlisten = listen('check-unsaved-before-close', async () => {
-      if (isDirty) {
-        // First ask if they want to save
-        const shouldSave = await showDialog({
-          title: t('dialog.saveConfirm.title'),
-          message: t('dialog.saveConfirm.message'),
-          type: 'info',
-          confirmLabel: t('dialog.saveConfirm.save'),
-          cancelLabel: t('dialog.saveConfirm.cancel'),
-          showCancel: true
-        })
-        
-        if (shouldSave === true) {
-          // Save before closing
-          await saveCurrentFile()
-          await invoke('force_close_app')
-        } else if (shouldSave === false) {
-          // User chose "Cancel" - ask for confirmation to close without saving
-          const reallyClose = await showDialog({
-            title: t('dialog.closeConfirm.title'),
-            message: t('dialog.closeConfirm.message'),
-            type: 'warning',
-            confirmLabel: t('dialog.closeConfirm.closeWithoutSaving'),
-            cancelLabel: t('dialog.closeConfirm.goBack'),
-            showCancel: true
-          })
-          
-          if (reallyClose === true) {
-            await invoke('force_close_app')
-          }
-          // If reallyClose is false or null (ESC/X), stay in the app
-        }
-        // If shouldSave is null (ESC/X pressed), don't close - user wants to stay
-      } else {
-        // No unsaved changes, close directly
-        await invoke('force_close_app')
+    const unlisten = appWindow.listen<AppError>('deep-link-error', (event) => {
+      showDialog({
+        title: t('dialog.error'),
+        message: errorMessage(event.payload),
+        type: 'warning',
+      })
+    })
+
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [])
+
+  // The backend asks about unsaved changes on close and has us save when the user chooses Save
+  useEffect(() => {
+    const unlisten = appWindow.listen('save-before-close', async () => {
+      let saved = false
+      try {
+        await saveCurrentFile()
+        saved = !useStore.getState().isDirty
+      } catch (error) {
+        console.error('Failed to save before closing:', error)
       }
+      await invoke('finish_save_before_close', { saved })
     })
 
     return () => {
       unlisten.then((fn) => fn())
     }
-  }, [isDirty, saveCurrentFile])
+  }, [saveCurrentFile])
 
   // Setup keyboard shortcuts
   useKeyboardShortcuts()
@@ -3,7 +3,7 @@
  * Allows users to configure AI API settings
  */
 
-import React, { useState } from 'react'
+import React, { useEffect, useState } from 'react'
 import * as Dialog from '@radix-ui/react-dialog'
 import * as Select from '@radix-ui/react-select'
 import * as Label from '@radix-ui/react-label'
@@ -17,7 +17,7 @@ export interface AISettingsDialogProps {
 }
 
 export function AISettingsDialog({ isOpen, onClose }: AISettingsDialogProps) {
-  const { config, updateConfig, validateConfig } = useAIConfig()
+  const { config, updateConfig, validateConfig, saveProfile } = useAIConfig()
   const { t } = useTranslation()
   const [tempConfig, setTempConfig] = useState(config)
   const [isValidating, setIsValidating] = useState(false)
@@ -26,6 +26,16 @@ export function AISettingsDialog({ isOpen, onClose }: AISettingsDialogProps) {
   const [hasTestedConnection, setHasTestedConnection] = useState(false)
   const [testResult, setTestResult] = useState<string>('')
 
+  // Profiles load after startup, so pick up the current one on every open
+  useEffect(() => {
+    if (isOpen) {
+      setTempConfig(config)
+    }
+  }, [isOpen])
+
+  const providerId = Object.entries(AI_PROVIDERS).find(([_, p]) => p.defaultConfig.baseUrl === tempConfig.baseUrl)?.[0] || 'custom'
+  const hasApiKey = Boolean(tempConfig.apiKey || tempConfig.hasApiKey)
+
   const handleSave = async () => {
     if (!hasTestedConnection) {
       setValidationResult(t('ai.test.testRequired'))
@@ -43,6 +53,8 @@ export function AISettingsDialog({ isOpen, onClose }: AISettingsDialogProps) {
       const isValid = await validateConfig()
       
       if (isValid) {
+        // The backend keeps the key; AI commands only name the profile
+        await saveProfile(tempConfig, providerId)
         setValidationResult(t('ai.test.saveSuccess'))
         
         setTimeout(() => {
@@ -89,10 +101,18 @@ export function AISettingsDialog({ isOpen, onClose }: AISettingsDialogProps) {
       // Use Tauri backend to test connection
       const { invoke } = await import('@tauri-apps/api/core')
       
+      // An empty key is filled in from the saved profile
       const testRequest = {
-        base_url: tempConfig.baseUrl,
-        api_key: tempConfig.apiKey,
-        model: tempConfig.model,
+        profile: {
+          id: tempConfig.profileId ?? '',
+          name: '',
+          provider: providerId,
+          base_url: tempConfig.baseUrl,
+          api_key: tempConfig.apiKey,
+          model: tempConfig.model,
+          temperature: tempConfig.temperature,
+          max_tokens: tempConfig.maxTokens,
+        },
       }
       
       const result = await invoke<{success: boolean, error_message?: string, response_data?: any}>('test_ai_connection', {
@@ -155,7 +175,7 @@ export function AISettingsDialog({ isOpen, onClose }: AISettingsDialogProps) {
                 {t('ai.settings.provider')}
               </Label.Root>
               <Select.Root
-                value={Object.entries(AI_PROVIDERS).find(([_, p]) => p.defaultConfig.baseUrl === tempConfig.baseUrl)?.[0] || 'custom'}
+                value={providerId}
                 onValueChange={handleProviderChange}
               >
                 <Select.Trigger className="w-full p-2 border border-gray-300 rounded">
@@ -192,7 +212,7 @@ export function AISettingsDialog({ isOpen, onClose }: AISettingsDialogProps) {
                   setHasTestedConnection(false)
                 }}
                 className="w-full p-2 border border-gray-300 rounded"
-                placeholder={t('ai.settings.apiKey')}
+                placeholder={tempConfig.hasApiKey ? t('ai.settings.apiKeySaved') : t('ai.settings.apiKey')}
               />
             </div>
 
@@ -234,7 +254,7 @@ export function AISettingsDialog({ isOpen, onClose }: AISettingsDialogProps) {
             <div>
               <button
                 onClick={handleTestConnection}
-                disabled={isTesting || !tempConfig.apiKey || !tempConfig.baseUrl}
+                disabled={isTesting || !hasApiKey || !tempConfig.baseUrl}
                 className="w-full p-2 bg-gray-100 hover:bg-gray-200 rounded border border-gray-300 disabled:opacity-50 text-sm"
               >
                 {isTesting ? t('ai.settings.testing') : t('ai.settings.testConnection')}
@@ -266,7 +286,7 @@ export function AISettingsDialog({ isOpen, onClose }: AISettingsDialogProps) {
             </button>
             <button
               onClick={handleSave}
-              disabled={isValidating || !tempConfig.apiKey || !tempConfig.baseUrl || !hasTestedConnection}
+              disabled={isValidating || !hasApiKey || !tempConfig.baseUrl || !hasTestedConnection}
               className="px-4 py-2 bg-blue-600 text-white rounded hover:bg-blue-700 disabled:opacity-50"
             >
               {isValidating ? t('ai.settings.saving') : t('ai.settings.save')}
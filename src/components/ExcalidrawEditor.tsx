@@ -4,12 +4,15 @@ import { Grid, TreePine, Circle, RotateCw, Cog, Bot, Columns } from 'lucide-reac
 import { openUrl } from '@tauri-apps/plugin-opener'
 import { onOpenUrl } from '@tauri-apps/plugin-deep-link'
 import { invoke } from '@tauri-apps/api/core'
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow'
 // Type definitions for Excalidraw elements and state
 type ExcalidrawElement = any
 type ExcalidrawAppState = any
 import { useStore } from '../store/useStore'
 import { setGlobalExcalidrawAPI } from '../hooks/useMenuHandler'
-import { TIMING } from '../constants'
+import { FONT_FAMILIES, TIMING } from '../constants'
+import { errorMessage } from '../lib/errors'
+import { drawingName, isReadOnly } from '../lib/fileTree'
 import { EmptyState } from './EmptyState'
 import { useLayoutTools } from './MoreToolsMenu/hooks/useLayoutTools'
 import { TextToChartDialog } from './TextToChartDialog/SimpleLayout'
@@ -20,17 +23,23 @@ import { useAIConfig } from '../store/useAIConfigStore'
 import { useTranslation } from '../store/useI18nStore'
 import { MermaidConverter } from '../services/MermaidConverter'
 import { ChartGenerationRequest } from '../types/ai-config'
+import { FileLockStatus, FileVerification, LegacyMigration, OpenFileChange, SceneMerge } from '../types'
+import { dialogService } from '../services/dialogService'
 import { LibraryImportDialog } from './LibraryImportDialog'
 
 export function ExcalidrawEditor() {
   const activeFile = useStore(state => state.activeFile)
   const fileContent = useStore(state => state.fileContent)
+  // Locked drawings open in view mode, so nothing is there to save
+  const readOnly = useStore(state => !!state.activeFile && isReadOnly(state.readOnlyPaths, state.activeFile.path))
   const [excalidrawAPI, setExcalidrawAPI] = useState<any>(null)
   const [isLoading, setIsLoading] = useState(false)
   const lastSavedContentRef = useRef<string>('')
   const lastSavedElementsRef = useRef<string>('')
   const isUserChangeRef = useRef(true)
   const debounceTimerRef = useRef<ReturnType<typeof setTimeout> | null>(null)
+  const draftTimerRef = useRef<ReturnType<typeof setTimeout> | null>(null)
+  const pendingDraftRef = useRef<{ filePath: string; content: string } | null>(null)
   const previousFilePathRef = useRef<string | null>(null)
   const initialLoadCompleteRef = useRef(false)
   
@@ -90,9 +99,13 @@ export function ExcalidrawEditor() {
       lastSavedElementsRef.current = JSON.stringify(data.elements || [])
       
       
+      // Preferred grid and font, unless the drawing brings its own
+      const { grid, defaultFont } = useStore.getState().preferences
       return {
         elements: data.elements || [],
         appState: {
+          gridSize: grid?.enabled ? grid.size : null,
+          currentItemFontFamily: FONT_FAMILIES[defaultFont ?? 'hand_drawn'],
           ...data.appState,
           zoom: { value: 1 },
           scrollX: 0,
@@ -262,8 +275,247 @@ export function ExcalidrawEditor() {
         freshStore.setFileContent(newContent)
       }
     }, TIMING.DEBOUNCE_SAVE) // Debounce save operations
+
+    // Throttled crash-recovery draft of the latest content
+    pendingDraftRef.current = { filePath: activeFile.path, content: newContent }
+    if (!draftTimerRef.current) {
+      draftTimerRef.current = setTimeout(() => {
+        draftTimerRef.current = null
+        const draft = pendingDraftRef.current
+        pendingDraftRef.current = null
+        if (draft && useStore.getState().isDirty) {
+          invoke('save_draft', draft).catch((error) => console.error('Failed to save draft:', error))
+        }
+      }, TIMING.DRAFT_INTERVAL)
+    }
   }, [activeFile])
 
+  // Periodic save of unsaved changes, when enabled in the preferences
+  const autosaveSecs = useStore((state) => state.preferences.autosaveIntervalSecs ?? 0)
+  useEffect(() => {
+    if (autosaveSecs <= 0) return
+    const timer = setInterval(() => {
+      const store = useStore.getState()
+      if (store.isDirty && store.activeFile) {
+        store.saveCurrentFile().catch((error) => console.error('Autosave failed:', error))
+      }
+    }, autosaveSecs * 1000)
+    return () => clearInterval(timer)
+  }, [autosaveSecs])
+
+  // Have the backend watch the open drawing for edits made by other programs,
+  // which also locks it, and warn when another instance holds the lock
+  useEffect(() => {
+    const path = activeFile?.path
+    if (!path) {
+      invoke('unwatch_open_file').catch((error) => console.error('Failed to unwatch the open file:', error))
+      return
+    }
+    invoke('watch_open_file', { path })
+      .then(() => invoke<FileLockStatus>('get_file_lock_status', { path }))
+      .then(async (lock) => {
+        if (lock.state !== 'other' || !lock.owner) return
+        await dialogService.showDialog({
+          title: t('dialog.fileLocked.title'),
+          message: t('dialog.fileLocked.message', {
+            file: path.split(/[\\/]/).pop()!.replace('.excalidraw', ''),
+            host: lock.owner.host || t('dialog.fileLocked.unknownHost'),
+            time: new Date(lock.owner.acquired_at).toLocaleString(),
+          }),
+          type: 'warning'
+        })
+      })
+      .catch((error) => console.error('Failed to watch the open file:', error))
+  }, [activeFile?.path])
+
+  // Offer to reload when the open drawing changes on disk instead of drifting apart
+  useEffect(() => {
+    if (!excalidrawAPI) return
+
+    const unlisten = getCurrentWebviewWindow().listen<OpenFileChange>('open-file-changed-externally', async (event) => {
+      const change = event.payload
+      const store = useStore.getState()
+      if (store.activeFile?.path !== change.path) return
+      const file = store.activeFile.name.replace('.excalidraw', '')
+
+      if (change.kind === 'deleted') {
+        // Nothing is on disk any more, so the open version is unsaved
+        store.setIsDirty(true)
+        await dialogService.showDialog({
+          title: t('dialog.externalChange.title'),
+          message: t('dialog.externalChange.deleted', { file }),
+          type: 'warning'
+        })
+        return
+      }
+
+      // Unsaved edits here and a changed file there can both be kept
+      if (store.isDirty && store.fileContent) {
+        const merge = await dialogService.showDialog({
+          title: t('dialog.externalChange.title'),
+          message: [
+            t('dialog.externalChange.modified', { file }),
+            change.diff ? t('dialog.externalChange.summary', change.diff) : '',
+            t('dialog.externalChange.mergeOffer'),
+          ].filter(Boolean).join('\n\n'),
+          type: 'warning',
+          confirmLabel: t('dialog.externalChange.merge'),
+          cancelLabel: t('dialog.externalChange.chooseOne'),
+          showCancel: true
+        })
+        if (merge === true) {
+          try {
+            const theirs = await invoke<string>('read_file', { filePath: change.path })
+            const merged = await invoke<SceneMerge>('merge_scene_changes', {
+              base: lastSavedContentRef.current || null,
+              mine: store.fileContent,
+              theirs,
+            })
+            const data = JSON.parse(merged.content)
+
+            // The file on disk is what the merged version now builds on
+            lastSavedContentRef.current = theirs
+            if (data.files) {
+              excalidrawAPI.addFiles(Object.values(data.files))
+            }
+            excalidrawAPI.updateScene({ elements: data.elements || [] })
+            useStore.setState({ fileContent: merged.content })
+            store.setIsDirty(true)
+            await dialogService.showDialog({
+              title: t('dialog.externalChange.title'),
+              message: [
+                t('dialog.externalChange.merged', { file }),
+                merged.conflicts.length > 0 ? t('dialog.externalChange.conflicts', { count: merged.conflicts.length }) : '',
+              ].filter(Boolean).join('\n\n'),
+              type: merged.conflicts.length > 0 ? 'warning' : 'info'
+            })
+          } catch (error) {
+            await dialogService.showDialog({
+              title: t('dialog.error'),
+              message: t('dialog.errors.mergeFailed', { error: errorMessage(error) }),
+              type: 'warning'
+            })
+          }
+          return
+        }
+        if (merge !== false) return
+      }
+
+      const message = [
+        t('dialog.externalChange.modified', { file }),
+        change.diff ? t('dialog.externalChange.summary', change.diff) : '',
+        store.isDirty ? t('dialog.externalChange.unsaved') : '',
+      ].filter(Boolean).join('\n\n')
+      const reload = await dialogService.showDialog({
+        title: t('dialog.externalChange.title'),
+        message,
+        type: 'warning',
+        confirmLabel: t('dialog.externalChange.reload'),
+        cancelLabel: t('dialog.externalChange.keepMine'),
+        showCancel: true
+      })
+
+      if (reload === false) {
+        // The next save writes this version over the changed file
+        store.setIsDirty(true)
+        return
+      }
+      if (reload !== true) return
+
+      try {
+        const content = await invoke<string>('read_file', { filePath: change.path })
+        const data = JSON.parse(content)
+        const elements = data.elements || []
+
+        // A programmatic update, not an edit
+        isUserChangeRef.current = false
+        lastSavedContentRef.current = content
+        lastSavedElementsRef.current = JSON.stringify(elements)
+        if (data.files) {
+          excalidrawAPI.addFiles(Object.values(data.files))
+        }
+        excalidrawAPI.updateScene({
+          elements,
+          ...(data.appState?.viewBackgroundColor && {
+            appState: { viewBackgroundColor: data.appState.viewBackgroundColor }
+          })
+        })
+        useStore.setState({ fileContent: content, isDirty: false })
+        store.markFileAsModified(change.path, false)
+        store.markTreeNodeAsModified(change.path, false)
+        setTimeout(() => {
+          isUserChangeRef.current = true
+        }, TIMING.USER_CHANGE_ENABLE_DELAY)
+      } catch (error) {
+        await dialogService.showDialog({
+          title: t('dialog.error'),
+          message: t('dialog.errors.reloadFailed', { error: errorMessage(error) }),
+          type: 'warning'
+        })
+      }
+    })
+
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [excalidrawAPI, t])
+
+  // Warn when a drawing no longer matches the checksum saved in it
+  useEffect(() => {
+    const unlisten = getCurrentWebviewWindow().listen<FileVerification>('file-integrity-mismatch', async (event) => {
+      await dialogService.showDialog({
+        title: t('dialog.checksumMismatch.title'),
+        message: t('dialog.checksumMismatch.message', {
+          file: drawingName(event.payload.path.split(/[\\/]/).pop()!),
+        }),
+        type: 'warning'
+      })
+    })
+
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [t])
+
+  // Offer to write back drawings from old Excalidraw versions, which are
+  // upgraded in memory when they are read; asks once per file and session
+  const askedUpgradesRef = useRef(new Set<string>())
+  useEffect(() => {
+    const unlisten = getCurrentWebviewWindow().listen<LegacyMigration>('legacy-file-migrated', async (event) => {
+      const { path, from_version, changes } = event.payload
+      if (askedUpgradesRef.current.has(path)) return
+      askedUpgradesRef.current.add(path)
+
+      const save = await dialogService.showDialog({
+        title: t('dialog.legacyUpgrade.title'),
+        message: t('dialog.legacyUpgrade.message', {
+          file: path.split(/[\\/]/).pop()!.replace('.excalidraw', ''),
+          version: from_version ?? t('dialog.legacyUpgrade.unknownVersion'),
+          count: changes.length,
+        }),
+        type: 'info',
+        confirmLabel: t('dialog.legacyUpgrade.save'),
+        cancelLabel: t('dialog.legacyUpgrade.later'),
+        showCancel: true
+      })
+      if (save !== true) return
+
+      try {
+        await invoke<boolean>('upgrade_legacy_file', { filePath: path })
+      } catch (error) {
+        await dialogService.showDialog({
+          title: t('dialog.error'),
+          message: t('dialog.errors.upgradeFailed', { error: errorMessage(error) }),
+          type: 'warning'
+        })
+      }
+    })
+
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [t])
+
   // Handle save - update our reference
   useEffect(() => {
     const unsubscribe = useStore.subscribe((state, prevState) => {
@@ -413,36 +665,21 @@ export function ExcalidrawEditor() {
     }
   }, [activeFile?.path])
 
-  // Keyboard shortcut listener for auto-layout
+  // Auto layout from Layout > Auto Layout (Ctrl+Shift+L unless rebound)
   useEffect(() => {
-    const handleKeydown = async (event: KeyboardEvent) => {
-      // Only check for our specific shortcut, don't log all combinations to avoid interference
-      
-      // Use Ctrl+Shift+L on all platforms for consistency
-      // ONLY handle our specific shortcut combination
-      if (event.shiftKey && event.ctrlKey && event.key === 'L') {
-        console.log('🎯 Layout shortcut triggered! Platform:', navigator.platform, 'Using Ctrl+Shift+L')
-        event.preventDefault()
-        event.stopPropagation()
-        
-        if (layoutTools?.autoLayout) {
-          try {
-            console.log('🎯 Auto Layout triggered via keyboard shortcut')
-            await layoutTools.autoLayout()
-            console.log('✨ Auto Layout completed via keyboard shortcut')
-          } catch (error) {
-            console.error('Auto Layout keyboard shortcut failed:', error)
-          }
+    const handleAutoLayout = async () => {
+      if (layoutTools?.autoLayout) {
+        try {
+          await layoutTools.autoLayout()
+          console.log('✨ Auto Layout completed via menu')
+        } catch (error) {
+          console.error('Auto Layout from menu failed:', error)
         }
       }
     }
 
-    // Use capture phase to handle before Excalidraw
-    document.addEventListener('keydown', handleKeydown, true)
-    
-    return () => {
-      document.removeEventListener('keydown', handleKeydown, true)
-    }
+    window.addEventListener('auto-layout', handleAutoLayout)
+    return () => window.removeEventListener('auto-layout', handleAutoLayout)
   }, [layoutTools.autoLayout])
 
   // Listen for direct layout events from menu
@@ -609,9 +846,7 @@ ${afterCount === beforeCount + newCount ? '✅ 导入成功，数量正确' : '
               } catch (error) {
                 console.error('❌ Failed to import library from deep link:', error)
                 setLibraryImportStatus('error')
-                const errorMessage = error instanceof Error ? error.message : 
-                                   (typeof error === 'string' ? error : 'Unknown error')
-                setLibraryImportError(`素材库导入失败: ${errorMessage}`)
+                setLibraryImportError(`素材库导入失败: ${errorMessage(error)}`)
                 isImportingRef.current = false
               }
             } else {
@@ -660,6 +895,7 @@ ${afterCount === beforeCount + newCount ? '✅ 导入成功，数量正确' : '
             setGlobalExcalidrawAPI(api)
           }}
           onChange={handleChange}
+          viewModeEnabled={readOnly}
           langCode={language} // 透传语言设置到 Excalidraw
           onLibraryChange={async (libraryItems) => {
             // 只处理删除和清空操作，避免导入时的干扰
@@ -786,7 +1022,7 @@ ${afterCount === beforeCount + newCount ? '✅ 导入成功，数量正确' : '
             <MainMenu.Group title={t('ai.aiTools')}>
               <MainMenu.Item
                 onSelect={() => {
-                  if (!aiConfig.apiKey || !aiConfig.baseUrl) {
+                  if (!aiConfig.profileId || !aiConfig.hasApiKey) {
                     window.dispatchEvent(new CustomEvent('open-ai-settings'))
                   } else {
                     setIsTextToChartOpen(true)
@@ -794,7 +1030,7 @@ ${afterCount === beforeCount + newCount ? '✅ 导入成功，数量正确' : '
                 }}
                 icon={<Bot size={16} />}
               >
-                {t('ai.textToDiagram')} {!aiConfig.apiKey ? '⚠️' : ''}
+                {t('ai.textToDiagram')} {!aiConfig.hasApiKey ? '⚠️' : ''}
               </MainMenu.Item>
               <MainMenu.Item
                 onSelect={() => {
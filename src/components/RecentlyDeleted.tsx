@@ -0,0 +1,93 @@
+import { useState } from 'react'
+import { invoke } from '@tauri-apps/api/core'
+import { ChevronDown, ChevronRight, File, Folder, RotateCcw, Trash2 } from 'lucide-react'
+import { useStore } from '../store/useStore'
+import { useTranslation } from '../store/useI18nStore'
+import { drawingName } from '../lib/fileTree'
+import { errorMessage } from '../lib/errors'
+import { DeletedEntry } from '../types'
+
+/** Collapsible list of deleted drawings and folders that can be put back */
+export function RecentlyDeleted() {
+  const [isExpanded, setIsExpanded] = useState(false)
+  const [entries, setEntries] = useState<DeletedEntry[]>([])
+  const { t } = useTranslation()
+
+  const loadEntries = async () => {
+    try {
+      setEntries(await invoke<DeletedEntry[]>('list_recently_deleted'))
+    } catch (error) {
+      console.error('Failed to list recently deleted items:', error)
+    }
+  }
+
+  const handleToggle = () => {
+    if (!isExpanded) {
+      loadEntries()
+    }
+    setIsExpanded(!isExpanded)
+  }
+
+  const handleRestore = async (entry: DeletedEntry) => {
+    try {
+      await invoke<string>('restore_deleted', { id: entry.id })
+      const { currentDirectory, loadFileTree } = useStore.getState()
+      if (currentDirectory) {
+        await loadFileTree(currentDirectory)
+      }
+      await loadEntries()
+    } catch (error) {
+      console.error('Failed to restore deleted item:', error)
+      const { message } = await import('@tauri-apps/plugin-dialog')
+      await message(t('dialog.errors.restoreDeletedFailed', { error: errorMessage(error) }), { title: t('dialog.error'), kind: 'error' })
+    }
+  }
+
+  return (
+    <div className="border-t border-gray-200">
+      <button
+        onClick={handleToggle}
+        className="w-full flex items-center gap-2 px-3 py-2 text-sm text-gray-600 hover:bg-gray-100 hover:text-gray-900 transition-colors"
+      >
+        {isExpanded ? <ChevronDown className="w-4 h-4" /> : <ChevronRight className="w-4 h-4" />}
+        <Trash2 className="w-4 h-4" />
+        <span>{t('file.recentlyDeleted.title')}</span>
+      </button>
+
+      {isExpanded && (
+        <div className="max-h-48 overflow-y-auto px-2 pb-2">
+          {entries.length === 0 ? (
+            <div className="text-xs text-gray-500 text-center py-2">{t('file.recentlyDeleted.empty')}</div>
+          ) : (
+            entries.map((entry) => (
+              <div
+                key={entry.id}
+                className="flex items-center gap-2 px-2 py-1 rounded-md hover:bg-gray-100 group"
+                title={t('file.recentlyDeleted.deletedAt', {
+                  time: new Date(entry.deleted_at).toLocaleString(),
+                  path: entry.original_path,
+                })}
+              >
+                {entry.is_directory ? (
+                  <Folder className="w-4 h-4 flex-shrink-0 text-gray-400" />
+                ) : (
+                  <File className="w-4 h-4 flex-shrink-0 text-gray-400" />
+                )}
+                <span className="text-sm truncate flex-1 text-gray-600">
+                  {entry.is_directory ? entry.name : drawingName(entry.name)}
+                </span>
+                <button
+                  onClick={() => handleRestore(entry)}
+                  className="opacity-0 group-hover:opacity-100 p-1 rounded hover:bg-gray-200 transition-opacity"
+                  title={t('file.recentlyDeleted.restore')}
+                >
+                  <RotateCcw className="w-3 h-3 text-gray-600" />
+                </button>
+              </div>
+            ))
+          )}
+        </div>
+      )}
+    </div>
+  )
+}
@@ -3,7 +3,9 @@ import { FolderOpen, Plus, PanelLeftClose, FolderPlus } from 'lucide-react'
 import { useStore } from '../store/useStore'
 import { useTranslation } from '../store/useI18nStore'
 import { TreeView } from './TreeView'
+import { RecentlyDeleted } from './RecentlyDeleted'
 import { FileTreeNode } from '../types'
+import { errorMessage } from '../lib/errors'
 import { invoke } from '@tauri-apps/api/core'
 
 function countFilesInTree(nodes: FileTreeNode[]): number {
@@ -49,9 +51,8 @@ export function Sidebar() {
       return
     }
     
-    // Create with timestamp filename for now
-    const fileName = `Untitled-${Date.now()}.excalidraw`
-    await createNewFile(fileName)
+    // Backend assigns the next free Untitled-N name
+    await createNewFile()
   }
 
   const handleNewFolder = async () => {
@@ -78,7 +79,7 @@ export function Sidebar() {
       console.log('Directory created successfully')
     } catch (error) {
       console.error('Error in handleNewFolder:', error)
-      alert(t('dialog.errors.createFolderFailed', { error: String(error) }))
+      alert(t('dialog.errors.createFolderFailed', { error: errorMessage(error) }))
     }
   }
 
@@ -142,6 +143,8 @@ export function Sidebar() {
         </div>
       </ScrollArea>
 
+      {currentDirectory && <RecentlyDeleted />}
+
       {/* Footer */}
       <div className="p-3 border-t border-gray-200">
         <div className="text-xs text-gray-500">
@@ -52,10 +52,10 @@ export const TextToChartDialog: React.FC<TextToChartDialogProps> = ({
   
   // Check if config is actually complete (real-time validation)
   const isConfigComplete = Boolean(
-    config.apiKey && 
+    config.profileId &&
+    config.hasApiKey &&
     config.baseUrl && 
     config.model && 
-    config.apiKey.length >= 10 && 
     config.baseUrl.startsWith('http') &&
     config.model.length > 0
   )
@@ -1,7 +1,12 @@
 import { useState, useRef, useEffect, memo } from 'react'
-import { ChevronDown, ChevronRight, File, Folder, FolderOpen, Edit2, Trash2, MoreVertical, FolderPlus } from 'lucide-react'
+import { invoke } from '@tauri-apps/api/core'
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow'
+import { ChevronDown, ChevronRight, File, Folder, FolderOpen, GitMerge, Lock, MoreVertical, Pin } from 'lucide-react'
 import { cn } from '../lib/utils'
-import { FileTreeNode } from '../types'
+import { drawingName } from '../lib/fileTree'
+import { errorMessage } from '../lib/errors'
+import { selectFile } from '../lib/dialogs'
+import { ArchiveReport, CanvasReport, FileTreeNode, FileTreePage, ImportReport, MermaidReport, PdfReport, SceneMerge, SkippedEntry } from '../types'
 import { useStore } from '../store/useStore'
 import { useDialog } from '../contexts/DialogContext'
 import { useTranslation } from '../store/useI18nStore'
@@ -12,30 +17,40 @@ interface TreeViewProps {
   activeFilePath?: string
 }
 
+/** Payload of the `file-context-action` event from the native context menu */
+interface FileContextAction {
+  action: string
+  path: string
+  is_directory: boolean
+}
+
 interface TreeNodeProps {
   node: FileTreeNode
   onFileClick: (node: FileTreeNode) => void
   activeFilePath?: string
   depth: number
+  /** Path of the drawing this node is a sync conflict copy of */
+  conflictOf?: string
 }
 
-const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, depth }: TreeNodeProps) {
+const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, depth, conflictOf }: TreeNodeProps) {
   const [isExpanded, setIsExpanded] = useState(depth === 0)
+  // Contents of folders the initial scan left unloaded
+  const [loadedChildren, setLoadedChildren] = useState<FileTreeNode[] | null>(null)
   const [isRenaming, setIsRenaming] = useState(false)
-  const [newName, setNewName] = useState(node.name.replace('.excalidraw', ''))
-  const [showMenu, setShowMenu] = useState(false)
+  const [newName, setNewName] = useState(drawingName(node.name))
   const [isDragOver, setIsDragOver] = useState(false)
   const [isDragging, setIsDragging] = useState(false)
   const [dragStartPos, setDragStartPos] = useState<{x: number, y: number} | null>(null)
   const [dragStartTime, setDragStartTime] = useState<number | null>(null)
   const [dragPreviewPos, setDragPreviewPos] = useState<{x: number, y: number} | null>(null)
   const renameInputRef = useRef<HTMLInputElement>(null)
-  const { renameFile, renameDirectory, deleteFile, deleteDirectory, moveFile } = useStore()
+  const { renameFile, renameDirectory, deleteFile, deleteDirectory, moveFile, moveDirectory } = useStore()
   const { showDialog } = useDialog()
   const { t } = useTranslation()
   
   // 全局拖拽状态
-  const [globalDragData, setGlobalDragData] = useState<{filePath: string, startNode: string} | null>(null)
+  const [globalDragData, setGlobalDragData] = useState<{filePath: string, startNode: string, isDirectory: boolean} | null>(null)
   
   // 从 window 对象获取全局拖拽数据
   const windowGlobalDragData = (window as any).globalDragData
@@ -114,7 +129,12 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
                 tagName: currentElement.tagName
               })
               
-              if (targetPath && targetPath !== currentGlobalDragData.filePath) {
+              // A folder can't go into itself or one of its subfolders
+              if (
+                targetPath &&
+                targetPath !== currentGlobalDragData.filePath &&
+                !targetPath.startsWith(`${currentGlobalDragData.filePath}/`)
+              ) {
                 // 计算路径深度（用于选择最具体的目标）
                 const depth = targetPath.split('/').length
                 possibleTargets.push({ 
@@ -148,8 +168,9 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
             })
             console.log('🎯 Attempting to move file:', currentGlobalDragData.filePath, 'to:', mostSpecificTarget.path)
             
-            // 执行文件移动
-            moveFile(currentGlobalDragData.filePath, mostSpecificTarget.path)
+            // 执行文件或文件夹移动
+            const move = currentGlobalDragData.isDirectory ? moveDirectory : moveFile
+            move(currentGlobalDragData.filePath, mostSpecificTarget.path)
               .then(() => {
                 console.log('✅ File moved successfully!')
                 // 强制刷新目录树
@@ -203,6 +224,28 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
     }
   }, [isRenaming])
   
+  const loadChildren = async () => {
+    try {
+      const { currentDirectory } = useStore.getState()
+      const root = currentDirectory && node.path.startsWith(currentDirectory + '/') ? currentDirectory : undefined
+      const children: FileTreeNode[] = []
+      let offset: number | null = 0
+      while (offset !== null) {
+        const page: FileTreePage = await invoke<FileTreePage>('get_file_tree_children', {
+          path: node.path,
+          root,
+          offset,
+          limit: 500,
+        })
+        children.push(...page.children)
+        offset = page.next_offset
+      }
+      setLoadedChildren(children)
+    } catch (error) {
+      console.error('Failed to load folder:', error)
+    }
+  }
+
   const handleClick = () => {
     // 清理可能残留的拖拽状态，防止点击被误识别为拖拽
     setDragStartPos(null)
@@ -213,6 +256,9 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
     setIsDragging(false)
     
     if (node.is_directory) {
+      if (!isExpanded && node.children === undefined && loadedChildren === null) {
+        loadChildren()
+      }
       setIsExpanded(!isExpanded)
     } else {
       onFileClick(node)
@@ -221,13 +267,13 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
   
   const handleRename = async () => {
     if (!newName.trim()) {
-      setNewName(node.is_directory ? node.name : node.name.replace('.excalidraw', ''))
+      setNewName(node.is_directory ? node.name : drawingName(node.name))
       setIsRenaming(false)
       return
     }
     
     const finalName = newName.trim()
-    const currentName = node.is_directory ? node.name : node.name.replace('.excalidraw', '')
+    const currentName = node.is_directory ? node.name : drawingName(node.name)
     
     if (finalName !== currentName) {
       if (node.is_directory) {
@@ -245,16 +291,13 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
       e.stopPropagation()
     }
     
-    // Close menu first
-    setShowMenu(false)
-    
     // Get the name for clear confirmation
-    const itemName = node.is_directory ? node.name : node.name.replace('.excalidraw', '')
+    const itemName = node.is_directory ? node.name : drawingName(node.name)
     const itemType = node.is_directory ? 'folder' : 'file'
     
     try {
-      // Use enhanced confirmation dialog
-      const confirmed = await showDialog({
+      // Use enhanced confirmation dialog, unless turned off in the preferences
+      const confirmed = useStore.getState().preferences.confirmOnDelete === false || await showDialog({
         title: node.is_directory ? t('dialog.deleteConfirm.folderTitle') : t('dialog.deleteConfirm.fileTitle'),
         message: node.is_directory 
           ? t('dialog.deleteConfirm.folderMessage', { folderName: itemName })
@@ -282,7 +325,7 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
           console.error('❌ Failed to delete:', error)
           // Use Tauri dialog for error too
           const { message } = await import('@tauri-apps/plugin-dialog')
-          await message(t('dialog.errors.deleteFailed', { itemType, error: String(error) }), { title: t('dialog.error'), kind: 'error' })
+          await message(t('dialog.errors.deleteFailed', { itemType, error: errorMessage(error) }), { title: t('dialog.error'), kind: 'error' })
         }
       } else {
         console.log('❌ User cancelled, item NOT deleted:', itemName)
@@ -292,28 +335,370 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
     }
   }
   
+  // Native context menu; the chosen entry comes back as a `file-context-action`
+  const showContextMenu = async (position?: { x: number, y: number }) => {
+    try {
+      await invoke('show_file_context_menu', { path: node.path, isDirectory: node.is_directory, position })
+    } catch (error) {
+      console.error('Failed to show context menu:', error)
+    }
+  }
+
   const handleContextMenu = (e: React.MouseEvent) => {
     e.preventDefault()
-    setShowMenu(true)
+    showContextMenu({ x: e.clientX, y: e.clientY })
   }
   
   const handleMenuClick = (e: React.MouseEvent) => {
     e.stopPropagation()
-    setShowMenu(!showMenu)
+    const rect = e.currentTarget.getBoundingClientRect()
+    showContextMenu({ x: rect.left, y: rect.bottom })
+  }
+
+  const showError = async (text: string) => {
+    const { message } = await import('@tauri-apps/plugin-dialog')
+    await message(text, { title: t('dialog.error'), kind: 'error' })
+  }
+
+  const handleExport = async () => {
+    try {
+      if (node.is_directory) {
+        const outputDir = await invoke<string | null>('select_directory')
+        if (outputDir) {
+          await invoke('batch_export', { directory: node.path, outputDir })
+        }
+      } else {
+        const outputPath = await selectFile(true, {
+          defaultPath: `${drawingName(node.name)}.svg`,
+          filters: [{ name: 'SVG', extensions: ['svg'] }],
+        })
+        if (outputPath) {
+          await invoke('export_file_svg', { filePath: node.path, outputPath })
+        }
+      }
+    } catch (error) {
+      console.error('Failed to export:', error)
+      await showError(t('dialog.errors.exportFailed', { error: errorMessage(error) }))
+    }
+  }
+
+  // One page per frame, on A4 with the default margins
+  const handleExportPdf = async () => {
+    try {
+      const outputPath = await selectFile(true, {
+        defaultPath: `${drawingName(node.name)}.pdf`,
+        filters: [{ name: 'PDF', extensions: ['pdf'] }],
+      })
+      if (outputPath) {
+        await invoke<PdfReport>('export_pdf', { filePath: node.path, outputPath })
+      }
+    } catch (error) {
+      console.error('Failed to export PDF:', error)
+      await showError(t('dialog.errors.exportFailed', { error: errorMessage(error) }))
+    }
+  }
+
+  // One slide per frame, in reading order or by the numbers frames are named with
+  const handleExportPptx = async () => {
+    try {
+      const output = await selectFile(true, {
+        defaultPath: `${drawingName(node.name)}.pptx`,
+        filters: [{ name: 'PowerPoint', extensions: ['pptx'] }],
+      })
+      if (output) {
+        await invoke<string>('export_slides', { pathOrDir: node.path, format: 'pptx', output })
+      }
+    } catch (error) {
+      console.error('Failed to export slides:', error)
+      await showError(t('dialog.errors.exportFailed', { error: errorMessage(error) }))
+    }
+  }
+
+  // Flowchart text for Markdown docs; elements without an equivalent are listed
+  const handleCopyMermaid = async () => {
+    try {
+      const report = await invoke<MermaidReport>('export_mermaid', { filePath: node.path })
+      await navigator.clipboard.writeText(report.mermaid)
+      if (report.skipped.length > 0) {
+        const { message } = await import('@tauri-apps/plugin-dialog')
+        const entries = report.skipped.map((e) => `${e.element_type} ${e.id}: ${e.reason}`).join('\n')
+        await message(t('dialog.mermaidCopied.skipped', { nodes: report.nodes, edges: report.edges, entries }), {
+          title: t('dialog.mermaidCopied.title'),
+          kind: 'info',
+        })
+      }
+    } catch (error) {
+      console.error('Failed to copy as Mermaid:', error)
+      await showError(t('dialog.errors.mermaidFailed', { error: errorMessage(error) }))
+    }
+  }
+
+  const reportSkippedCanvasItems = async (report: CanvasReport) => {
+    if (report.skipped === 0) return
+    const { message } = await import('@tauri-apps/plugin-dialog')
+    await message(t('dialog.canvasConverted.skipped', { nodes: report.nodes, edges: report.edges, skipped: report.skipped }), {
+      title: t('dialog.canvasConverted.title'),
+      kind: 'info',
+    })
+  }
+
+  // Obsidian's JSON Canvas format
+  const handleExportCanvas = async () => {
+    try {
+      const target = await selectFile(true, {
+        defaultPath: `${drawingName(node.name)}.canvas`,
+        filters: [{ name: 'JSON Canvas', extensions: ['canvas'] }],
+      })
+      if (target) {
+        await reportSkippedCanvasItems(await invoke<CanvasReport>('export_json_canvas', { path: node.path, target }))
+      }
+    } catch (error) {
+      console.error('Failed to export canvas:', error)
+      await showError(t('dialog.errors.canvasFailed', { error: errorMessage(error) }))
+    }
+  }
+
+  // Converts a canvas into a drawing of the same name in this folder
+  const handleImportCanvas = async () => {
+    try {
+      const path = await selectFile(false, { filters: [{ name: 'JSON Canvas', extensions: ['canvas'] }] })
+      if (!path) return
+
+      const name = path.split(/[\\/]/).pop()!.replace(/\.canvas$/, '')
+      const target = `${node.path}/${name}.excalidraw`
+      const report = await invoke<CanvasReport>('import_json_canvas', { path, target })
+      const { currentDirectory, loadFileTree } = useStore.getState()
+      if (currentDirectory) {
+        await loadFileTree(currentDirectory)
+      }
+      await reportSkippedCanvasItems(report)
+    } catch (error) {
+      console.error('Failed to import canvas:', error)
+      await showError(t('dialog.errors.canvasFailed', { error: errorMessage(error) }))
+    }
+  }
+
+  // Zips the folder with its exported images and assets
+  const handleArchive = async () => {
+    try {
+      const outputPath = await selectFile(true, {
+        defaultPath: `${node.name}.zip`,
+        filters: [{ name: 'ZIP', extensions: ['zip'] }],
+      })
+      if (outputPath) {
+        await invoke<ArchiveReport>('archive_directory', { directory: node.path, outputPath, includeExports: true })
+      }
+    } catch (error) {
+      console.error('Failed to archive folder:', error)
+      await showError(t('dialog.errors.archiveFailed', { error: errorMessage(error) }))
+    }
+  }
+
+  // Extracts a zip file into this folder and sums up what was left out
+  const handleImportArchive = async () => {
+    const { message } = await import('@tauri-apps/plugin-dialog')
+    try {
+      const zipPath = await selectFile(false, { filters: [{ name: 'ZIP', extensions: ['zip'] }] })
+      if (!zipPath) return
+
+      const report = await invoke<ImportReport>('import_archive', { zipPath, targetDirectory: node.path })
+      const { currentDirectory, loadFileTree } = useStore.getState()
+      if (currentDirectory) {
+        await loadFileTree(currentDirectory)
+      }
+
+      const list = (entries: SkippedEntry[]) => entries.map((e) => `${e.name}: ${e.reason}`).join('\n')
+      let text = t('dialog.archiveImported.message', { count: report.imported.length })
+      if (report.skipped.length > 0) {
+        text += '\n\n' + t('dialog.archiveImported.skipped', { entries: list(report.skipped) })
+      }
+      if (report.invalid.length > 0) {
+        text += '\n\n' + t('dialog.archiveImported.invalid', { entries: list(report.invalid) })
+      }
+      await message(text, { title: t('dialog.archiveImported.title'), kind: report.invalid.length > 0 ? 'warning' : 'info' })
+    } catch (error) {
+      console.error('Failed to import archive:', error)
+      await showError(t('dialog.errors.importArchiveFailed', { error: errorMessage(error) }))
+    }
+  }
+
+  // Settles this sync conflict copy with the drawing it was made of
+  const handleResolveConflict = async (action: string) => {
+    if (!conflictOf) return
+    const strategies: Record<string, string> = {
+      merge_conflict_copy: 'merge',
+      keep_conflict_copy: 'keep_conflict',
+      discard_conflict_copy: 'keep_original',
+    }
+    const strategy = strategies[action]
+    try {
+      const merged = await invoke<SceneMerge | null>('resolve_sync_conflict', { original: conflictOf, conflict: node.path, strategy })
+      const { currentDirectory, loadFileTree } = useStore.getState()
+      if (currentDirectory) {
+        await loadFileTree(currentDirectory)
+      }
+      if (merged) {
+        const { message } = await import('@tauri-apps/plugin-dialog')
+        const text = [
+          t('dialog.conflictCopy.merged', { file: drawingName(node.name) }),
+          merged.conflicts.length > 0 ? t('dialog.externalChange.conflicts', { count: merged.conflicts.length }) : '',
+        ].filter(Boolean).join('\n\n')
+        await message(text, { title: t('dialog.conflictCopy.title'), kind: merged.conflicts.length > 0 ? 'warning' : 'info' })
+      }
+    } catch (error) {
+      console.error('Failed to resolve conflict copy:', error)
+      await showError(t('dialog.errors.mergeFailed', { error: errorMessage(error) }))
+    }
+  }
+
+  const handleContextAction = async (action: string) => {
+    switch (action) {
+      case 'new_subfolder':
+        // 创建子文件夹
+        try {
+          const defaultFolderName = t('dialog.treeOperations.newFolder', { timestamp: Date.now() })
+          const { createDirectory } = useStore.getState()
+          await createDirectory(node.path, defaultFolderName)
+          console.log('子文件夹创建成功')
+        } catch (error) {
+          console.error('创建子文件夹失败:', error)
+        }
+        break
+
+      case 'open_in_new_window':
+        try {
+          await invoke('open_in_new_window', { filePath: node.path })
+        } catch (error) {
+          console.error('Failed to open new window:', error)
+          await showError(t('dialog.errors.openWindowFailed', { error: errorMessage(error) }))
+        }
+        break
+
+      case 'rename':
+        setIsRenaming(true)
+        break
+
+      case 'duplicate':
+        try {
+          await invoke<string>('duplicate_file', { filePath: node.path })
+          const { currentDirectory, loadFileTree } = useStore.getState()
+          if (currentDirectory) {
+            await loadFileTree(currentDirectory)
+          }
+        } catch (error) {
+          console.error('Failed to duplicate file:', error)
+          await showError(t('dialog.errors.duplicateFailed', { error: errorMessage(error) }))
+        }
+        break
+
+      case 'copy_to':
+        try {
+          const { copyFile } = useStore.getState()
+          const targetDirectory = await invoke<string | null>('select_directory')
+          if (targetDirectory) {
+            await copyFile(node.path, targetDirectory)
+          }
+        } catch (error) {
+          console.error('Failed to copy file:', error)
+          await showError(t('dialog.errors.copyFailed', { error: errorMessage(error) }))
+        }
+        break
+
+      case 'pin':
+      case 'unpin':
+        try {
+          const { pinFile, unpinFile } = useStore.getState()
+          await (action === 'pin' ? pinFile : unpinFile)(node.path)
+        } catch (error) {
+          console.error('Failed to pin file:', error)
+          await showError(t('dialog.errors.pinFailed', { error: errorMessage(error) }))
+        }
+        break
+
+      case 'make_read_only':
+      case 'allow_editing':
+        try {
+          await useStore.getState().setReadOnly(node.path, action === 'make_read_only')
+        } catch (error) {
+          console.error('Failed to change read-only state:', error)
+          await showError(t('dialog.errors.readOnlyFailed', { error: errorMessage(error) }))
+        }
+        break
+
+      case 'save_as_template':
+        // Named after the file; saving again updates the template
+        try {
+          await invoke('save_as_template', { filePath: node.path, name: drawingName(node.name) })
+        } catch (error) {
+          console.error('Failed to save template:', error)
+          await showError(t('dialog.errors.saveTemplateFailed', { error: errorMessage(error) }))
+        }
+        break
+
+      case 'merge_conflict_copy':
+      case 'keep_conflict_copy':
+      case 'discard_conflict_copy':
+        handleResolveConflict(action)
+        break
+
+      case 'delete':
+        handleDelete()
+        break
+
+      case 'export':
+        handleExport()
+        break
+      case 'export_pdf':
+        handleExportPdf()
+        break
+      case 'export_pptx':
+        handleExportPptx()
+        break
+      case 'copy_mermaid':
+        handleCopyMermaid()
+        break
+      case 'export_canvas':
+        handleExportCanvas()
+        break
+      case 'import_canvas':
+        handleImportCanvas()
+        break
+
+      case 'archive':
+        handleArchive()
+        break
+
+      case 'import_archive':
+        handleImportArchive()
+        break
+    }
   }
+
+  // Actions are broadcast by TreeView; only the node they were chosen for acts
+  useEffect(() => {
+    const listener = (e: Event) => {
+      const { action, path } = (e as CustomEvent<FileContextAction>).detail
+      if (path === node.path) {
+        handleContextAction(action)
+      }
+    }
+    window.addEventListener('file-context-action', listener)
+    return () => window.removeEventListener('file-context-action', listener)
+  })
   
   // 新的鼠标拖拽处理器
   const handleMouseDown = (e: React.MouseEvent) => {
-    if (node.is_directory || e.button !== 0) return // 只处理左键点击文件
+    if (e.button !== 0) return // 只处理左键
     
-    console.log('🖱️ Mouse down on file:', node.name)
+    console.log('🖱️ Mouse down on:', node.name)
     setDragStartPos({ x: e.clientX, y: e.clientY })
     setDragStartTime(Date.now())
     // 不要立即设置 isDragging = true，只在实际拖拽时才设置
-    setGlobalDragData({ filePath: node.path, startNode: node.name })
+    const dragData = { filePath: node.path, startNode: node.name, isDirectory: node.is_directory }
+    setGlobalDragData(dragData)
     
     // 将拖拽数据存储到全局 window 对象
-    ;(window as any).globalDragData = { filePath: node.path, startNode: node.name }
+    ;(window as any).globalDragData = dragData
     
     e.preventDefault()
   }
@@ -391,12 +776,13 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
       console.error('❌ Failed to move file:', error)
       // Show error message to user
       const { message } = await import('@tauri-apps/plugin-dialog')
-      await message(t('dialog.errors.moveFailed', { error: String(error) }), { title: t('dialog.error'), kind: 'error' })
+      await message(t('dialog.errors.moveFailed', { error: errorMessage(error) }), { title: t('dialog.error'), kind: 'error' })
     }
   }
   
   const isActive = activeFilePath === node.path
-  const hasChildren = node.children && node.children.length > 0
+  const children = node.children ?? loadedChildren ?? undefined
+  const hasChildren = node.children === undefined ? node.is_directory : node.children.length > 0
   
   return (
     <div className="relative">
@@ -490,7 +876,7 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
               if (e.key === 'Enter') {
                 handleRename()
               } else if (e.key === 'Escape') {
-                setNewName(node.name.replace('.excalidraw', ''))
+                setNewName(drawingName(node.name))
                 setIsRenaming(false)
               }
             }}
@@ -499,13 +885,43 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
           />
         ) : (
           <span className="text-sm truncate flex-1">
-            {node.is_directory ? node.name : node.name.replace('.excalidraw', '')}
+            {node.is_directory ? node.name : drawingName(node.name)}
           </span>
         )}
         
+        {node.pinned && (
+          <Pin className="w-3 h-3 text-gray-400 flex-shrink-0" />
+        )}
+
+        {node.read_only && (
+          <Lock className="w-3 h-3 text-gray-400 flex-shrink-0" />
+        )}
+
         {node.modified && (
           <span className="w-2 h-2 bg-orange-500 rounded-full flex-shrink-0" />
         )}
+
+        {node.conflicts && node.conflicts.length > 0 && (
+          <span title={t('file.conflictCopies', { count: node.conflicts.length })} className="flex-shrink-0">
+            <GitMerge className="w-3 h-3 text-red-500" />
+          </span>
+        )}
+
+        {conflictOf && (
+          <span title={t('file.conflictCopy')} className="flex-shrink-0">
+            <GitMerge className="w-3 h-3 text-gray-400" />
+          </span>
+        )}
+
+        {node.sync_status && node.sync_status !== 'synced' && (
+          <span
+            title={t(`file.syncStatus.${node.sync_status}`)}
+            className={cn(
+              "w-2 h-2 rounded-full flex-shrink-0",
+              node.sync_status === 'conflict' ? "bg-red-500" : "border border-blue-400"
+            )}
+          />
+        )}
         
         <button
           onClick={handleMenuClick}
@@ -521,61 +937,9 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
         </button>
       </div>
       
-      {/* Context Menu */}
-      {showMenu && (
-        <div 
-          className="absolute right-0 top-8 z-50 bg-white rounded-md shadow-lg border border-gray-200 py-1 min-w-[150px]"
-          onMouseLeave={() => setShowMenu(false)}
-        >
-          {node.is_directory && (
-            <button
-              onClick={async (e) => {
-                e.stopPropagation()
-                setShowMenu(false)
-                
-                // 创建子文件夹
-                try {
-                  const defaultFolderName = t('dialog.treeOperations.newFolder', { timestamp: Date.now() })
-                  const { createDirectory } = useStore.getState()
-                  await createDirectory(node.path, defaultFolderName)
-                  console.log('子文件夹创建成功')
-                } catch (error) {
-                  console.error('创建子文件夹失败:', error)
-                }
-              }}
-              className="w-full px-3 py-2 text-left text-sm hover:bg-gray-100 flex items-center gap-2"
-            >
-              <FolderPlus className="w-3 h-3" />
-              {t('dialog.treeOperations.newSubfolder')}
-            </button>
-          )}
-          <button
-            onClick={(e) => {
-              e.stopPropagation()
-              setIsRenaming(true)
-              setShowMenu(false)
-            }}
-            className="w-full px-3 py-2 text-left text-sm hover:bg-gray-100 flex items-center gap-2"
-          >
-            <Edit2 className="w-3 h-3" />
-            {t('dialog.treeOperations.rename')}
-          </button>
-          <button
-            onClick={(e) => {
-              console.log('Delete button clicked!')
-              handleDelete(e)
-            }}
-            className="w-full px-3 py-2 text-left text-sm hover:bg-gray-100 flex items-center gap-2 text-red-600"
-          >
-            <Trash2 className="w-3 h-3" />
-            {node.is_directory ? t('dialog.treeOperations.deleteFolder') : t('dialog.treeOperations.deleteFile')}
-          </button>
-        </div>
-      )}
-      
-      {node.is_directory && hasChildren && isExpanded && (
+      {node.is_directory && children && isExpanded && (
         <div>
-          {node.children!.map((child) => (
+          {children.map((child) => (
             <TreeNode
               key={child.path}
               node={child}
@@ -586,6 +950,17 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
           ))}
         </div>
       )}
+
+      {node.conflicts?.map((copy) => (
+        <TreeNode
+          key={copy.path}
+          node={copy}
+          onFileClick={onFileClick}
+          activeFilePath={activeFilePath}
+          depth={depth + 1}
+          conflictOf={node.path}
+        />
+      ))}
       
       {/* 拖拽预览元素 */}
       {isDragging && dragPreviewPos && windowGlobalDragData && (
@@ -598,7 +973,7 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
             opacity: 0.9
           }}
         >
-          📄 {windowGlobalDragData.startNode}
+          {windowGlobalDragData.isDirectory ? '📁' : '📄'} {windowGlobalDragData.startNode}
         </div>
       )}
     </div>
@@ -606,7 +981,17 @@ const TreeNode = memo(function TreeNode({ node, onFileClick, activeFilePath, dep
 })
 
 export function TreeView({ nodes, onFileClick, activeFilePath }: TreeViewProps) {
-  const { currentDirectory, moveFile } = useStore()
+  const { currentDirectory, moveFile, moveDirectory } = useStore()
+
+  // One event listener for the whole tree instead of one per node
+  useEffect(() => {
+    const unlisten = getCurrentWebviewWindow().listen<FileContextAction>('file-context-action', (event) => {
+      window.dispatchEvent(new CustomEvent('file-context-action', { detail: event.payload }))
+    })
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [])
   
   if (nodes.length === 0) {
     return (
@@ -641,7 +1026,8 @@ export function TreeView({ nodes, onFileClick, activeFilePath }: TreeViewProps)
       console.log('🎯 Attempting to move file to root:', dragData.filePath, 'to:', currentDirectory)
       
       try {
-        await moveFile(dragData.filePath, currentDirectory)
+        const move = dragData.isDirectory ? moveDirectory : moveFile
+        await move(dragData.filePath, currentDirectory)
         console.log('✅ File moved to root successfully!')
       } catch (error) {
         console.error('❌ Failed to move file to root:', error)
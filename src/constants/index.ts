@@ -6,6 +6,14 @@ export const TIMING = {
   LOADING_HIDE_DELAY: 200,
   USER_CHANGE_ENABLE_DELAY: 300,
   AUTO_SAVE_INTERVAL: 30000, // 30 seconds
+  DRAFT_INTERVAL: 5000, // crash-recovery snapshots of unsaved changes
+} as const
+
+// Excalidraw font family ids for the `defaultFont` preference
+export const FONT_FAMILIES = {
+  hand_drawn: 1,
+  normal: 2,
+  code: 3,
 } as const
 
 // File system constants
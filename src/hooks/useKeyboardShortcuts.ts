@@ -1,6 +1,28 @@
-import { useEffect } from 'react'
+import { useEffect, useState } from 'react'
 import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
 import { useStore } from '../store/useStore'
+import { ShortcutInfo } from '../types'
+
+/** Menu commands whose shortcut the user changed; the keys below are their defaults */
+function useCustomizedShortcuts() {
+  const [customized, setCustomized] = useState<Set<string>>(new Set())
+
+  useEffect(() => {
+    const update = (shortcuts: ShortcutInfo[]) =>
+      setCustomized(new Set(shortcuts.filter((s) => s.accelerator !== s.default).map((s) => s.command)))
+
+    invoke<ShortcutInfo[]>('get_shortcuts').then(update).catch((error) =>
+      console.error('Failed to load shortcuts:', error)
+    )
+    const unlisten = listen<ShortcutInfo[]>('shortcuts-changed', (event) => update(event.payload))
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [])
+
+  return customized
+}
 
 export function useKeyboardShortcuts() {
   const {
@@ -11,6 +33,7 @@ export function useKeyboardShortcuts() {
     loadFile,
     createNewFile,
   } = useStore()
+  const customized = useCustomizedShortcuts()
 
   useEffect(() => {
     const handleKeyDown = async (e: KeyboardEvent) => {
@@ -24,19 +47,19 @@ export function useKeyboardShortcuts() {
       }
 
       // Cmd/Ctrl + B: Toggle sidebar
-      if (modKey && e.key === 'b') {
+      if (modKey && e.key === 'b' && !customized.has('toggle_sidebar')) {
         e.preventDefault()
         toggleSidebar()
       }
 
       // Cmd/Ctrl + S: Save current file
-      if (modKey && e.key === 's') {
+      if (modKey && e.key === 's' && !customized.has('save')) {
         e.preventDefault()
         await saveCurrentFile()
       }
 
       // Cmd/Ctrl + O: Open directory
-      if (modKey && e.key === 'o') {
+      if (modKey && e.key === 'o' && !customized.has('open_directory')) {
         e.preventDefault()
         const dir = await invoke<string | null>('select_directory')
         if (dir) {
@@ -45,7 +68,7 @@ export function useKeyboardShortcuts() {
       }
 
       // Cmd/Ctrl + N: New file
-      if (modKey && e.key === 'n') {
+      if (modKey && e.key === 'n' && !customized.has('new_file')) {
         e.preventDefault()
         
         const state = useStore.getState()
@@ -59,9 +82,8 @@ export function useKeyboardShortcuts() {
           return
         }
         
-        // Create with timestamp filename
-        const fileName = `Untitled-${Date.now()}.excalidraw`
-        await createNewFile(fileName)
+        // Backend assigns the next free Untitled-N name
+        await createNewFile()
       }
 
       // Cmd/Ctrl + Tab: Switch to next file
@@ -88,5 +110,5 @@ export function useKeyboardShortcuts() {
     // Use non-capturing phase to let Excalidraw handle events first
     window.addEventListener('keydown', handleKeyDown, false)
     return () => window.removeEventListener('keydown', handleKeyDown, false)
-  }, [toggleSidebar, saveCurrentFile, files, activeFile, loadFile, createNewFile])
+  }, [toggleSidebar, saveCurrentFile, files, activeFile, loadFile, createNewFile, customized])
 }
\ No newline at end of file
@@ -1,11 +1,15 @@
 import { useEffect } from 'react'
-import { listen, UnlistenFn } from '@tauri-apps/api/event'
+import { UnlistenFn } from '@tauri-apps/api/event'
+import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow'
 import { invoke } from '@tauri-apps/api/core'
 import { getCurrentWindow } from '@tauri-apps/api/window'
 import { useStore } from '../store/useStore'
 import { convertPreferencesToRust } from '../lib/preferences'
 import { useI18nStore } from '../store/useI18nStore'
-import { dialogService } from '../services/dialogService'
+import { useAIConfigStore } from '../store/useAIConfigStore'
+import { errorMessage } from '../lib/errors'
+import { selectFile } from '../lib/dialogs'
+import { Preferences } from '../types'
 
 interface MenuCommand {
   command: string
@@ -26,6 +30,9 @@ export function useMenuHandler() {
     saveCurrentFile,
     activeFile,
     toggleSidebar,
+    setTheme,
+    sidebarVisible,
+    isDirty,
     preferences,
     savePreferences,
   } = useStore()
@@ -42,7 +49,8 @@ export function useMenuHandler() {
     let unlisten: UnlistenFn | null = null
 
     const setupListener = async () => {
-      unlisten = await listen<MenuCommand>('menu-command', async (event) => {
+      // Menu commands are emitted to the focused window only
+      unlisten = await getCurrentWebviewWindow().listen<MenuCommand>('menu-command', async (event) => {
         const { command, data } = event.payload
         console.log('🟣 [useMenuHandler] Menu command received:', command, data)
 
@@ -56,6 +64,12 @@ export function useMenuHandler() {
             handleNewFile()
             break
 
+          case 'new_from_template':
+            if (data?.template) {
+              await createNewFile(undefined, data.template)
+            }
+            break
+
           case 'save':
             await saveCurrentFile()
             break
@@ -79,12 +93,29 @@ export function useMenuHandler() {
             handleClearRecent()
             break
 
+          // Recent files
+          case command.match(/^recent_file_\d+$/)?.input:
+            if (data?.file) {
+              await useStore.getState().openLaunchTarget({ path: data.file, is_directory: false })
+            }
+            break
+
+          case 'clear_recent_files':
+            handleClearRecentFiles()
+            break
+
 
           // View menu commands
           case 'toggle_sidebar':
             toggleSidebar()
             break
 
+          case 'theme_light':
+          case 'theme_dark':
+          case 'theme_system':
+            setTheme(command.slice('theme_'.length) as Preferences['theme'])
+            break
+
           case 'zoom_in':
             handleZoomIn()
             break
@@ -125,7 +156,19 @@ export function useMenuHandler() {
             window.dispatchEvent(new CustomEvent('open-ai-settings'))
             break
 
+          case 'export_settings':
+            await handleExportSettings()
+            break
+
+          case 'import_settings':
+            await handleImportSettings()
+            break
+
           // Layout menu commands
+          case 'auto_layout':
+            window.dispatchEvent(new CustomEvent('auto-layout'))
+            break
+
           case 'layout_mrtree':
             handleDirectLayout('mrtree', { x: 120, y: 100 }, 'DOWN')
             break
@@ -148,6 +191,12 @@ export function useMenuHandler() {
             break
 
           default:
+            // Menu-only translations (e.g. user-provided locale files) that
+            // the interface itself doesn't ship with
+            if (command.startsWith('language_')) {
+              await invoke('set_app_language', { locale: command.slice('language_'.length).replace('_', '-') })
+              break
+            }
             console.log('Unknown menu command:', command)
         }
       })
@@ -166,10 +215,38 @@ export function useMenuHandler() {
     saveCurrentFile,
     activeFile,
     toggleSidebar,
+    setTheme,
     preferences,
     savePreferences,
   ])
 
+  // Keep the native checkmarks in line with this window, also when it gains
+  // focus since every window shares the app menu on macOS
+  useEffect(() => {
+    const syncMenuState = () =>
+      invoke('sync_menu_state', { state: { sidebarVisible, theme: preferences.theme } }).catch((error) =>
+        console.error('Failed to sync menu state:', error)
+      )
+
+    syncMenuState()
+    const unlistenFocus = getCurrentWindow().onFocusChanged(({ payload: focused }) => {
+      if (focused) {
+        syncMenuState()
+      }
+    })
+
+    return () => {
+      unlistenFocus.then((unlisten) => unlisten())
+    }
+  }, [sidebarVisible, preferences.theme])
+
+  // Grey out Save, Save As and Layout when they have nothing to act on
+  useEffect(() => {
+    invoke('set_active_file_state', { activeFile: activeFile?.path ?? null, isDirty }).catch((error) =>
+      console.error('Failed to update menu items:', error)
+    )
+  }, [activeFile?.path, isDirty])
+
   // File menu handlers
   const handleOpenDirectory = async () => {
     const selected = await invoke<string | null>('select_directory')
@@ -190,9 +267,8 @@ export function useMenuHandler() {
       return
     }
     
-    // Create with timestamp filename
-    const fileName = `Untitled-${Date.now()}.excalidraw`
-    await createNewFile(fileName)
+    // Backend assigns the next free Untitled-N name
+    await createNewFile()
   }
 
   const handleSaveAs = async () => {
@@ -204,13 +280,14 @@ export function useMenuHandler() {
     
     if (!content) return
 
-    const newPath = await invoke<string | null>('save_file_as', {
+    const result = await invoke<{ path: string; overwritten: boolean } | null>('save_file_as', {
       content,
+      currentFile: activeFile.path,
     })
 
-    if (newPath) {
+    if (result) {
       // Optionally update the active file to the new path
-      console.log('File saved as:', newPath)
+      console.log('File saved as:', result.path, result.overwritten ? '(overwritten)' : '')
     }
   }
 
@@ -225,6 +302,12 @@ export function useMenuHandler() {
     useStore.getState().setPreferences(newPrefs)
   }
 
+  const handleClearRecentFiles = async () => {
+    const store = useStore.getState()
+    store.setPreferences({ ...store.preferences, recentFiles: [] })
+    await store.savePreferences()
+  }
+
 
   // View menu handlers
   const handleZoomIn = () => {
@@ -336,45 +419,76 @@ export function useMenuHandler() {
 
 
   const handleLanguageSwitch = async (language: 'zh-CN' | 'en-US') => {
-    const { config, t } = useI18nStore.getState()
+    const { config } = useI18nStore.getState()
     
-    // If already in this language, no need to restart
     if (config.currentLanguage === language) {
       return
     }
     
     try {
-      // Switch language first
+      // The store also rebuilds the native menu, so no restart is needed
       await switchLanguage(language)
-      
-      // Get the updated translation function after language switch
-      const { t: newT } = useI18nStore.getState()
-      
-      // Get language display name
-      const languageName = language === 'zh-CN' ? '中文' : 'English'
-      
-      // Show restart confirmation dialog using CustomDialog style
-      const shouldRestart = await dialogService.showDialog({
-        title: newT('dialog.languageRestart.title'),
-        message: newT('dialog.languageRestart.message', { language: languageName }),
-        type: 'info',
-        confirmLabel: newT('dialog.languageRestart.restart'),
-        cancelLabel: newT('dialog.languageRestart.cancel'),
-        showCancel: true
+    } catch (error) {
+      console.error('Failed to switch language:', error)
+    }
+  }
+
+  const handleExportSettings = async () => {
+    const { message } = await import('@tauri-apps/plugin-dialog')
+    const { t } = useI18nStore.getState()
+    const path = await selectFile(true, {
+      defaultPath: 'excaliapp-settings.json',
+      filters: [{ name: 'JSON', extensions: ['json'] }],
+    })
+    if (!path) return
+
+    try {
+      await invoke('export_settings', { path })
+    } catch (error) {
+      console.error('Failed to export settings:', error)
+      await message(t('dialog.errors.settingsExportFailed', { error: errorMessage(error) }), {
+        title: t('dialog.error'),
+        kind: 'error',
       })
-      
-      if (shouldRestart === true) {
-        // Save any unsaved work first
-        const store = useStore.getState()
-        if (store.isDirty) {
-          await store.saveCurrentFile()
-        }
-        
-        // Restart the application
-        await invoke('restart_app')
+    }
+  }
+
+  const handleImportSettings = async () => {
+    const { message } = await import('@tauri-apps/plugin-dialog')
+    const { t } = useI18nStore.getState()
+    const path = await selectFile(false, { filters: [{ name: 'JSON', extensions: ['json'] }] })
+    if (!path) return
+
+    try {
+      const result = await invoke<{
+        ai_profiles: number
+        prompt_templates: number
+        shortcuts: number
+        skipped: string[]
+      }>('import_settings', { path })
+
+      await useAIConfigStore.getState().loadProfiles()
+      await useStore.getState().reloadPreferences()
+      const { language } = useStore.getState().preferences
+      if (language === 'zh-CN' || language === 'en-US') {
+        await handleLanguageSwitch(language)
+      }
+
+      let text = t('dialog.settingsImported.message', {
+        profiles: result.ai_profiles,
+        templates: result.prompt_templates,
+        shortcuts: result.shortcuts,
+      })
+      if (result.skipped.length > 0) {
+        text += '\n\n' + t('dialog.settingsImported.skipped', { entries: result.skipped.join(', ') })
       }
+      await message(text, { title: t('dialog.settingsImported.title'), kind: 'info' })
     } catch (error) {
-      console.error('Failed to switch language:', error)
+      console.error('Failed to import settings:', error)
+      await message(t('dialog.errors.settingsImportFailed', { error: errorMessage(error) }), {
+        title: t('dialog.error'),
+        kind: 'error',
+      })
     }
   }
 
@@ -0,0 +1,17 @@
+import { invoke } from '@tauri-apps/api/core'
+
+export interface FileFilter {
+  name: string
+  extensions: string[]
+}
+
+/**
+ * Picks a file to export to (`save`) or import from through the backend,
+ * which then lets the file commands use it outside the opened folders
+ */
+export function selectFile(
+  save: boolean,
+  options: { filters?: FileFilter[]; defaultPath?: string } = {}
+): Promise<string | null> {
+  return invoke<string | null>('select_file', { save, ...options })
+}
@@ -0,0 +1,29 @@
+import { describe, it, expect } from 'vitest'
+import { errorCode, errorMessage, isAppError } from './errors'
+
+describe('backend error helpers', () => {
+  const notFound = { code: 'not_found', message: 'File does not exist', context: null }
+
+  it('should recognize backend errors', () => {
+    expect(isAppError(notFound)).toBe(true)
+    expect(isAppError('File does not exist')).toBe(false)
+    expect(isAppError(null)).toBe(false)
+  })
+
+  it('should expose the error code', () => {
+    expect(errorCode(notFound)).toBe('not_found')
+    expect(errorCode(new Error('boom'))).toBeUndefined()
+  })
+
+  it('should prefix the message with its context', () => {
+    expect(errorMessage(notFound)).toBe('File does not exist')
+    expect(errorMessage({ ...notFound, context: 'Version not available' })).toBe(
+      'Version not available: File does not exist'
+    )
+  })
+
+  it('should fall back for other thrown values', () => {
+    expect(errorMessage(new Error('boom'))).toBe('boom')
+    expect(errorMessage('plain')).toBe('plain')
+  })
+})
@@ -0,0 +1,49 @@
+/**
+ * Error returned by backend commands (mirrors the Rust `AppError`)
+ */
+export interface AppError {
+  code:
+    | 'not_found'
+    | 'permission_denied'
+    | 'already_exists'
+    | 'invalid_path'
+    | 'invalid_input'
+    | 'too_large'
+    | 'read_only'
+    | 'io'
+    | 'network'
+    | 'cancelled'
+    | 'internal'
+  message: string
+  /** File or step the error concerns, outermost first */
+  context: string | null
+}
+
+export function isAppError(error: unknown): error is AppError {
+  return (
+    typeof error === 'object' &&
+    error !== null &&
+    typeof (error as AppError).code === 'string' &&
+    typeof (error as AppError).message === 'string'
+  )
+}
+
+/**
+ * Error code of a failed command, if it came from the backend
+ */
+export function errorCode(error: unknown): AppError['code'] | undefined {
+  return isAppError(error) ? error.code : undefined
+}
+
+/**
+ * Human-readable text for any thrown value, including backend errors
+ */
+export function errorMessage(error: unknown): string {
+  if (isAppError(error)) {
+    return error.context ? `${error.context}: ${error.message}` : error.message
+  }
+  if (error instanceof Error) {
+    return error.message
+  }
+  return String(error)
+}
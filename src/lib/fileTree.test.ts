@@ -0,0 +1,121 @@
+import { describe, it, expect } from 'vitest'
+import { applyTreeChange, drawingName, isReadOnly } from './fileTree'
+import { FileSystemChange, FileTreeNode } from '../types'
+
+const file = (path: string): FileTreeNode => ({
+  name: path.slice(path.lastIndexOf('/') + 1),
+  path,
+  relative_path: path.slice('/vault/'.length),
+  is_directory: false,
+  modified: false,
+})
+
+const folder = (path: string, children?: FileTreeNode[]): FileTreeNode => ({
+  ...file(path),
+  is_directory: true,
+  children,
+})
+
+const change = (kind: FileSystemChange['kind'], path: string, extra: Partial<FileSystemChange> = {}): FileSystemChange => ({
+  kind,
+  path,
+  relative_path: path.slice('/vault/'.length),
+  old_path: null,
+  is_directory: false,
+  rescan: false,
+  ...extra,
+})
+
+const tree = () => [
+  folder('/vault/notes', [file('/vault/notes/b.excalidraw')]),
+  folder('/vault/later'),
+  file('/vault/a.excalidraw'),
+]
+
+describe('applyTreeChange', () => {
+  it('should insert created files in sorted order', () => {
+    const result = applyTreeChange(tree(), '/vault', change('created', '/vault/notes/a.excalidraw'))
+    expect(result?.[0].children?.map((n) => n.name)).toEqual(['a.excalidraw', 'b.excalidraw'])
+  })
+
+  it('should keep folders above files at the top level', () => {
+    const result = applyTreeChange(tree(), '/vault', change('created', '/vault/new', { is_directory: true }))
+    expect(result?.map((n) => n.name)).toEqual(['later', 'new', 'notes', 'a.excalidraw'])
+  })
+
+  it('should keep pinned files above folders', () => {
+    const before = tree().map((n) => (n.is_directory ? n : { ...n, pinned: true }))
+    const result = applyTreeChange(before, '/vault', change('created', '/vault/new', { is_directory: true }))
+    expect(result?.map((n) => n.name)).toEqual(['a.excalidraw', 'later', 'new', 'notes'])
+  })
+
+  it('should leave folders that are not loaded yet alone', () => {
+    const before = tree()
+    const result = applyTreeChange(before, '/vault', change('created', '/vault/later/c.excalidraw'))
+    expect(result).toEqual(before)
+  })
+
+  it('should ask for a rescan when the parent is unknown', () => {
+    expect(applyTreeChange(tree(), '/vault', change('created', '/vault/x/y/c.excalidraw'))).toBeNull()
+    expect(applyTreeChange(tree(), '/vault', change('modified', '/vault/.excaliignore', { rescan: true }))).toBeNull()
+  })
+
+  it('should remove nested entries', () => {
+    const result = applyTreeChange(tree(), '/vault', change('removed', '/vault/notes/b.excalidraw'))
+    expect(result?.[0].children).toEqual([])
+  })
+
+  it('should move renamed folders with their contents', () => {
+    const result = applyTreeChange(
+      tree(),
+      '/vault',
+      change('renamed', '/vault/later/archive', { old_path: '/vault/notes', is_directory: true })
+    )
+    expect(result?.map((n) => n.name)).toEqual(['later', 'a.excalidraw'])
+    // `later` was never expanded, so the moved folder waits for it
+    expect(result?.[0].children).toBeUndefined()
+
+    const renamed = applyTreeChange(
+      tree(),
+      '/vault',
+      change('renamed', '/vault/ideas', { old_path: '/vault/notes', is_directory: true })
+    )
+    expect(renamed?.[0]).toMatchObject({
+      name: 'ideas',
+      path: '/vault/ideas',
+      relative_path: 'ideas',
+      children: [{ path: '/vault/ideas/b.excalidraw', relative_path: 'ideas/b.excalidraw' }],
+    })
+  })
+
+  it('should treat renames of unknown entries as creations', () => {
+    const result = applyTreeChange(
+      tree(),
+      '/vault',
+      change('renamed', '/vault/c.excalidraw', { old_path: '/elsewhere/c.excalidraw' })
+    )
+    expect(result?.map((n) => n.name)).toContain('c.excalidraw')
+  })
+})
+
+describe('drawingName', () => {
+  it('strips plain and compressed drawing extensions', () => {
+    expect(drawingName('plan.excalidraw')).toBe('plan')
+    expect(drawingName('plan.excalidraw.gz')).toBe('plan')
+    expect(drawingName('plan.excalidraw.bak')).toBe('plan.excalidraw.bak')
+  })
+})
+
+describe('isReadOnly', () => {
+  it('covers locked files and everything below locked folders', () => {
+    const locked = ['/vault/reference', '/vault/notes/b.excalidraw']
+    expect(isReadOnly(locked, '/vault/notes/b.excalidraw')).toBe(true)
+    expect(isReadOnly(locked, '/vault/reference/deep/a.excalidraw')).toBe(true)
+    expect(isReadOnly(locked, '/vault/reference')).toBe(true)
+  })
+
+  it('does not match siblings sharing a prefix', () => {
+    expect(isReadOnly(['/vault/ref'], '/vault/reference/a.excalidraw')).toBe(false)
+    expect(isReadOnly([], '/vault/a.excalidraw')).toBe(false)
+  })
+})
@@ -0,0 +1,117 @@
+import { FileSystemChange, FileTreeNode } from '../types'
+
+const parentOf = (path: string) => path.slice(0, path.lastIndexOf('/'))
+const nameOf = (path: string) => path.slice(path.lastIndexOf('/') + 1)
+
+/** Folders first, then by name, like the backend's default tree order */
+function compareNodes(a: FileTreeNode, b: FileTreeNode) {
+  if (!!a.pinned !== !!b.pinned) return a.pinned ? -1 : 1
+  if (a.is_directory !== b.is_directory) return a.is_directory ? -1 : 1
+  return a.name < b.name ? -1 : a.name > b.name ? 1 : 0
+}
+
+function findNode(nodes: FileTreeNode[], path: string): FileTreeNode | undefined {
+  for (const node of nodes) {
+    if (node.path === path) return node
+    if (node.children && path.startsWith(`${node.path}/`)) {
+      const found = findNode(node.children, path)
+      if (found) return found
+    }
+  }
+  return undefined
+}
+
+function removeNode(nodes: FileTreeNode[], path: string): FileTreeNode[] {
+  return nodes
+    .filter((node) => node.path !== path)
+    .map((node) =>
+      node.children && path.startsWith(`${node.path}/`)
+        ? { ...node, children: removeNode(node.children, path) }
+        : node
+    )
+}
+
+/** `node` and everything below it moved from `from` to `to` */
+function moveNode(node: FileTreeNode, from: string, to: string, relativeTo: string | null): FileTreeNode {
+  const relocate = (path: string) => to + path.slice(from.length)
+  const relocateRelative = (relative?: string) =>
+    relativeTo !== null && relative !== undefined && node.relative_path !== undefined
+      ? relativeTo + relative.slice(node.relative_path.length)
+      : relative
+  const walk = (n: FileTreeNode): FileTreeNode => ({
+    ...n,
+    path: relocate(n.path),
+    relative_path: relocateRelative(n.relative_path),
+    children: n.children?.map(walk),
+  })
+  return { ...walk(node), name: nameOf(to) }
+}
+
+/**
+ * Adds `node` under `parent`, or at the top when `parent` is the tree root.
+ * Returns null when the parent is not in the tree.
+ */
+function insertNode(nodes: FileTreeNode[], root: string, parent: string, node: FileTreeNode): FileTreeNode[] | null {
+  if (parent === root) {
+    if (nodes.some((n) => n.path === node.path)) return nodes
+    return [...nodes, node].sort(compareNodes)
+  }
+
+  let found = false
+  const walk = (list: FileTreeNode[]): FileTreeNode[] =>
+    list.map((n) => {
+      if (n.path === parent) {
+        found = true
+        // Not loaded yet; the node shows up once the folder is expanded
+        if (!n.children || n.children.some((c) => c.path === node.path)) return n
+        return { ...n, children: [...n.children, node].sort(compareNodes) }
+      }
+      if (n.children && parent.startsWith(`${n.path}/`)) {
+        return { ...n, children: walk(n.children) }
+      }
+      return n
+    })
+  const updated = walk(nodes)
+  return found ? updated : null
+}
+
+/** A drawing's file name as shown: without `.excalidraw`, or `.excalidraw.gz` for compressed ones */
+export function drawingName(name: string): string {
+  return name.replace(/\.excalidraw(\.gz)?$/, '')
+}
+
+/** Whether `path` is locked with `set_read_only`, itself or by a folder above it */
+export function isReadOnly(locked: string[], path: string): boolean {
+  return locked.some((lock) => path === lock || path.startsWith(`${lock.replace(/\/+$/, '')}/`))
+}
+
+/**
+ * The file tree under `root` after a watcher change, or null when the change
+ * cannot be applied in place and the tree has to be read again
+ */
+export function applyTreeChange(nodes: FileTreeNode[], root: string, change: FileSystemChange): FileTreeNode[] | null {
+  if (change.rescan) return null
+
+  switch (change.kind) {
+    case 'modified':
+      return nodes
+    case 'removed':
+      return removeNode(nodes, change.path)
+    case 'created':
+      return insertNode(nodes, root, parentOf(change.path), {
+        name: nameOf(change.path),
+        path: change.path,
+        relative_path: change.relative_path ?? undefined,
+        is_directory: change.is_directory,
+        modified: false,
+      })
+    case 'renamed': {
+      const existing = change.old_path ? findNode(nodes, change.old_path) : undefined
+      if (!existing || !change.old_path) {
+        return applyTreeChange(nodes, root, { ...change, kind: 'created', old_path: null })
+      }
+      const moved = moveNode(existing, change.old_path, change.path, change.relative_path)
+      return insertNode(removeNode(nodes, change.old_path), root, parentOf(change.path), moved)
+    }
+  }
+}
@@ -7,10 +7,30 @@ export function convertPreferencesFromRust(rustPrefs: any): Preferences {
   return {
     lastDirectory: rustPrefs?.last_directory || rustPrefs?.lastDirectory || null,
     recentDirectories: rustPrefs?.recent_directories || rustPrefs?.recentDirectories || [],
+    recentFiles: rustPrefs?.recent_files || [],
     theme: rustPrefs?.theme || 'system',
     sidebarVisible: rustPrefs?.sidebar_visible !== undefined 
       ? rustPrefs.sidebar_visible 
       : (rustPrefs?.sidebarVisible !== undefined ? rustPrefs.sidebarVisible : true),
+    proxy: rustPrefs?.proxy,
+    compactOnSave: rustPrefs?.compact_on_save ?? false,
+    normalizeOnSave: rustPrefs?.normalize_on_save ?? false,
+    checksumOnSave: rustPrefs?.checksum_on_save ?? false,
+    workspaceRoots: rustPrefs?.workspace_roots || [],
+    ignorePatterns: rustPrefs?.ignore_patterns,
+    backup: rustPrefs?.backup,
+    logLevel: rustPrefs?.log_level,
+    autosaveIntervalSecs: rustPrefs?.autosave_interval_secs ?? 0,
+    defaultExportFormat: rustPrefs?.default_export_format,
+    defaultFont: rustPrefs?.default_font,
+    grid: rustPrefs?.grid,
+    watcher: rustPrefs?.watcher,
+    limits: rustPrefs?.limits,
+    vaultBackup: rustPrefs?.vault_backup,
+    s3Backup: rustPrefs?.s3_backup,
+    confirmOnDelete: rustPrefs?.confirm_on_delete ?? true,
+    deletedRetentionDays: rustPrefs?.deleted_retention_days ?? 30,
+    language: rustPrefs?.language ?? null,
   }
 }
 
@@ -21,7 +41,28 @@ export function convertPreferencesToRust(tsPrefs: Preferences): any {
   return {
     last_directory: tsPrefs.lastDirectory || null,
     recent_directories: tsPrefs.recentDirectories || [],
+    recent_files: tsPrefs.recentFiles || [],
     theme: tsPrefs.theme || 'system',
     sidebar_visible: tsPrefs.sidebarVisible !== undefined ? tsPrefs.sidebarVisible : true,
+    proxy: tsPrefs.proxy,
+    compact_on_save: tsPrefs.compactOnSave ?? false,
+    normalize_on_save: tsPrefs.normalizeOnSave ?? false,
+    checksum_on_save: tsPrefs.checksumOnSave ?? false,
+    workspace_roots: tsPrefs.workspaceRoots || [],
+    // Left out when unset so the backend keeps its defaults
+    ignore_patterns: tsPrefs.ignorePatterns,
+    backup: tsPrefs.backup,
+    log_level: tsPrefs.logLevel,
+    autosave_interval_secs: tsPrefs.autosaveIntervalSecs ?? 0,
+    default_export_format: tsPrefs.defaultExportFormat,
+    default_font: tsPrefs.defaultFont,
+    grid: tsPrefs.grid,
+    watcher: tsPrefs.watcher,
+    limits: tsPrefs.limits,
+    vault_backup: tsPrefs.vaultBackup,
+    s3_backup: tsPrefs.s3Backup,
+    confirm_on_delete: tsPrefs.confirmOnDelete ?? true,
+    deleted_retention_days: tsPrefs.deletedRetentionDays ?? 30,
+    language: tsPrefs.language ?? null,
   }
 }
\ No newline at end of file
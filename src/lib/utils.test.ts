@@ -1,5 +1,5 @@
 import { describe, it, expect } from 'vitest'
-import { cn } from './utils'
+import { cn, templateDateVariables } from './utils'
 
 describe('cn utility function', () => {
   it('should combine class names', () => {
@@ -26,4 +26,16 @@ describe('cn utility function', () => {
     const result = cn('', 'class1', '', 'class2')
     expect(result).toBe('class1 class2')
   })
-})
\ No newline at end of file
+})
+
+describe('templateDateVariables', () => {
+  it('should format the local date and time with zero padding', () => {
+    const result = templateDateVariables(new Date(2024, 1, 9, 7, 5))
+    expect(result).toEqual({
+      date: '2024-02-09',
+      time: '07:05',
+      datetime: '2024-02-09 07:05',
+      year: '2024',
+    })
+  })
+})
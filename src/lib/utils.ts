@@ -3,4 +3,20 @@ import { twMerge } from 'tailwind-merge'
 
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
-}
\ No newline at end of file
+}
+
+/**
+ * Local date and time for `{{date}}`, `{{time}}`, `{{datetime}}` and `{{year}}`
+ * in new-file templates; the backend would otherwise use UTC
+ */
+export function templateDateVariables(now: Date = new Date()): Record<string, string> {
+  const pad = (n: number) => String(n).padStart(2, '0')
+  const date = `${now.getFullYear()}-${pad(now.getMonth() + 1)}-${pad(now.getDate())}`
+  const time = `${pad(now.getHours())}:${pad(now.getMinutes())}`
+  return {
+    date,
+    time,
+    datetime: `${date} ${time}`,
+    year: String(now.getFullYear()),
+  }
+}
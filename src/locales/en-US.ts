@@ -67,6 +67,7 @@ export const translations: TranslationKeys = {
       description: 'Configure AI service providers and API settings for text-to-chart functionality',
       provider: 'AI Provider',
       apiKey: 'API Key',
+      apiKeySaved: 'Saved — leave empty to keep it',
       apiUrl: 'API URL',
       model: 'Model',
       testConnection: '🔗 Test Connection',
@@ -263,6 +264,20 @@ export const translations: TranslationKeys = {
     // File status
     unsavedChanges: 'Unsaved changes',
     fileModified: 'File modified',
+    syncStatus: {
+      modified: 'Changed since the last sync',
+      new: 'Not synced yet',
+      conflict: 'Changed on another device too; the server copy was saved next to it'
+    },
+    conflictCopies: '{{count}} conflict cop{{count === 1 ? "y" : "ies"}} left by sync',
+    conflictCopy: 'Conflict copy left by sync; merge it, use it, or discard it from its menu',
+
+    recentlyDeleted: {
+      title: 'Recently Deleted',
+      empty: 'Nothing deleted recently',
+      restore: 'Restore',
+      deletedAt: 'Deleted {{time}} from {{path}}'
+    },
     
     // Empty state
     emptyState: {
@@ -286,22 +301,79 @@ export const translations: TranslationKeys = {
       cancel: 'Cancel'
     },
 
-    // Close confirmation
-    closeConfirm: {
-      title: 'Confirm Close Without Saving - OwnExcaliDesk',
-      message: 'Warning: All your unsaved changes will be permanently lost!\n\nThis action cannot be undone. Are you sure you want to continue?',
-      closeWithoutSaving: 'Close Without Saving',
-      goBack: 'Go Back'
+    // Crash recovery
+    draftRecovery: {
+      title: 'Recover Unsaved Changes - OwnExcaliDesk',
+      message: 'Unsaved changes to "{{file}}" from {{time}} were found after the app closed unexpectedly.\n\nDo you want to restore them?',
+      recover: 'Restore',
+      discard: 'Discard'
     },
 
-    // Language restart confirmation
-    languageRestart: {
-      title: '🌐 Restart App to Apply Language Settings',
-      message: 'Language has been changed to {{language}}.\n\nThe app needs to restart to update the menu bar and interface language.\n\nRestart now?',
-      restart: '🔄 Restart Now',
-      cancel: '❌ Restart Later'
+    // Open drawing changed on disk
+    externalChange: {
+      title: 'File Changed on Disk',
+      modified: '"{{file}}" was changed outside OwnExcaliDesk.',
+      summary: '{{added}} added, {{removed}} removed and {{changed}} changed elements ({{total}} in total).',
+      unsaved: 'Reloading discards your unsaved changes here; keeping your version overwrites the file on the next save.',
+      reload: 'Reload',
+      keepMine: 'Keep Mine',
+      deleted: '"{{file}}" was deleted or moved outside OwnExcaliDesk. Save to write your version back.',
+      mergeOffer: 'You have unsaved changes. Merge both versions? Changes made on only one side are combined; elements changed on both sides keep the newer edit.',
+      merge: 'Merge',
+      chooseOne: 'Choose One',
+      merged: 'Both versions of "{{file}}" were merged. Save to keep the result.',
+      conflicts: '{{count}} element(s) were changed on both sides and kept the newer edit.'
+    },
+
+    conflictCopy: {
+      title: 'Conflict Copy Merged',
+      merged: '"{{file}}" was merged into the drawing it is a copy of and deleted.'
+    },
+
+    // Drawing made by an old Excalidraw version
+    legacyUpgrade: {
+      title: 'Drawing Upgraded',
+      message: '"{{file}}" was made with an older Excalidraw (file version {{version}}) and was upgraded when it opened: {{count}} change(s). Save the upgraded version over the file? The old version is kept as a backup.',
+      unknownVersion: 'unknown',
+      save: 'Save Upgrade',
+      later: 'Not Now'
+    },
+
+    // Drawing over the size limits
+    tooLarge: {
+      title: 'Very Large Drawing',
+      open: '"{{file}}" is over the configured limits ({{reason}}). Opening it may make the app unresponsive.',
+      save: '"{{file}}" is over the configured limits ({{reason}}). Saving it may make the app unresponsive.',
+      openAnyway: 'Open Anyway',
+      saveAnyway: 'Save Anyway'
+    },
+
+    // Settings import result
+    settingsImported: {
+      title: 'Settings Imported',
+      message: 'Imported preferences, {{profiles}} AI profile(s), {{templates}} prompt template(s) and {{shortcuts}} custom shortcut(s).\n\nAPI keys are not part of the file; enter them again in AI Settings.',
+      skipped: 'These entries could not be used and kept their current values: {{entries}}'
+    },
+
+    // Archive import result
+    archiveImported: {
+      title: 'Archive Imported',
+      message: 'Imported {{count}} file(s).',
+      skipped: 'Skipped:\n{{entries}}',
+      invalid: 'Not imported because they are unsafe or invalid:\n{{entries}}'
     },
 
+    canvasConverted: {
+      title: 'Canvas Converted',
+      skipped: 'Converted {{nodes}} card(s) and {{edges}} connection(s). {{skipped}} item(s) have no equivalent and were left out.'
+    },
+
+    mermaidCopied: {
+      title: 'Copied as Mermaid',
+      skipped: 'Copied {{nodes}} shape(s) and {{edges}} arrow(s). These elements have no Mermaid equivalent and were left out:\n{{entries}}'
+    },
+
+    // Language restart confirmation
     // Delete confirmation
     deleteConfirm: {
       fileTitle: '🗑️ Delete File',
@@ -316,7 +388,24 @@ export const translations: TranslationKeys = {
     errors: {
       createFolderFailed: 'Failed to create folder: {{error}}',
       deleteFailed: 'Failed to delete {{itemType}}: {{error}}',
-      moveFailed: 'Failed to move file: {{error}}'
+      moveFailed: 'Failed to move file: {{error}}',
+      openWindowFailed: 'Failed to open a new window: {{error}}',
+      duplicateFailed: 'Failed to duplicate file: {{error}}',
+      copyFailed: 'Failed to copy file: {{error}}',
+      pinFailed: 'Failed to pin file: {{error}}',
+      readOnlyFailed: 'Failed to change read-only state: {{error}}',
+      archiveFailed: 'Failed to archive folder: {{error}}',
+      importArchiveFailed: 'Failed to import archive: {{error}}',
+      saveTemplateFailed: 'Failed to save as template: {{error}}',
+      exportFailed: 'Failed to export: {{error}}',
+      mermaidFailed: 'Failed to convert to Mermaid: {{error}}',
+      canvasFailed: 'Failed to convert the canvas: {{error}}',
+      settingsExportFailed: 'Failed to export settings: {{error}}',
+      settingsImportFailed: 'Failed to import settings: {{error}}',
+      reloadFailed: 'Failed to reload the file: {{error}}',
+      mergeFailed: 'Failed to merge the versions: {{error}}',
+      restoreDeletedFailed: 'Failed to restore: {{error}}',
+      upgradeFailed: 'Failed to save the upgraded file: {{error}}'
     },
 
     // Tree operations
@@ -325,6 +414,7 @@ export const translations: TranslationKeys = {
       deleteFolder: 'Delete Folder',
       newFolder: 'New Folder {{timestamp}}',
       newSubfolder: 'New Subfolder',
+      openInNewWindow: 'Open in New Window',
       rename: 'Rename'
     },
 
@@ -359,6 +449,17 @@ export const translations: TranslationKeys = {
       preferences: 'Preferences',
       window: 'Window Operations'
     }
+
+    // Drawing open in another instance
+    fileLocked: {
+      title: 'Drawing Open Elsewhere',
+      message: '"{{file}}" has been open on {{host}} since {{time}}. Saving here may overwrite changes made there.',
+      unknownHost: 'another computer'
+    },
+    checksumMismatch: {
+      title: 'Drawing May Be Damaged',
+      message: '"{{file}}" no longer matches the checksum saved with it. A sync may have failed or another program changed it. Check the drawing, or restore a backup.'
+    },
   }
 }
 
@@ -67,6 +67,7 @@ export const translations: TranslationKeys = {
       description: '配置 AI 服务提供商和 API 设置，用于文本转图表功能',
       provider: 'AI 提供商',
       apiKey: 'API Key',
+      apiKeySaved: '已保存，留空则保持不变',
       apiUrl: 'API 地址',
       model: '模型',
       testConnection: '🔗 测试连接',
@@ -217,6 +218,20 @@ export const translations: TranslationKeys = {
     // 文件状态
     unsavedChanges: '未保存的更改',
     fileModified: '文件已修改',
+    syncStatus: {
+      modified: '上次同步后已修改',
+      new: '尚未同步',
+      conflict: '其他设备也修改了此文件，服务器上的版本已另存在旁边'
+    },
+    conflictCopies: '同步留下了 {{count}} 个冲突副本',
+    conflictCopy: '同步留下的冲突副本；可在其菜单中合并、采用或丢弃',
+
+    recentlyDeleted: {
+      title: '最近删除',
+      empty: '最近没有删除的内容',
+      restore: '恢复',
+      deletedAt: '{{time}} 从 {{path}} 删除'
+    },
     
     // 空状态
     emptyState: {
@@ -286,22 +301,79 @@ export const translations: TranslationKeys = {
       cancel: '取消'
     },
 
-    // 关闭确认
-    closeConfirm: {
-      title: '确认不保存关闭 - OwnExcaliDesk',
-      message: '警告：所有未保存的更改将永久丢失！\n\n此操作无法撤销。您确定要继续吗？',
-      closeWithoutSaving: '不保存关闭',
-      goBack: '返回'
+    // 崩溃恢复
+    draftRecovery: {
+      title: '恢复未保存的更改 - OwnExcaliDesk',
+      message: '应用意外关闭，发现 "{{file}}" 在 {{time}} 的未保存更改。\n\n是否恢复这些更改？',
+      recover: '恢复',
+      discard: '丢弃'
     },
 
-    // 语言切换重启确认
-    languageRestart: {
-      title: '🌐 重启应用以应用语言设置',
-      message: '语言设置已更改为 {{language}}。\n\n应用需要重启以更新菜单栏和界面语言。\n\n是否立即重启？',
-      restart: '🔄 立即重启',
-      cancel: '❌ 稍后重启'
+    // 打开的绘图在磁盘上被修改
+    externalChange: {
+      title: '文件已在磁盘上更改',
+      modified: '"{{file}}" 已在 OwnExcaliDesk 之外被修改。',
+      summary: '新增 {{added}} 个、删除 {{removed}} 个、修改 {{changed}} 个元素（共 {{total}} 个）。',
+      unsaved: '重新加载将丢弃此处未保存的更改；保留当前版本会在下次保存时覆盖该文件。',
+      reload: '重新加载',
+      keepMine: '保留我的版本',
+      deleted: '"{{file}}" 已在 OwnExcaliDesk 之外被删除或移动。保存即可写回当前版本。',
+      mergeOffer: '你有未保存的更改。要合并两个版本吗？只在一方修改的内容会合并在一起；双方都修改过的元素保留较新的修改。',
+      merge: '合并',
+      chooseOne: '二选一',
+      merged: '已合并 "{{file}}" 的两个版本。保存即可保留合并结果。',
+      conflicts: '有 {{count}} 个元素在双方都被修改，已保留较新的修改。'
+    },
+
+    conflictCopy: {
+      title: '冲突副本已合并',
+      merged: '"{{file}}" 已合并到其原文件并删除。'
+    },
+
+    // 旧版 Excalidraw 文件的升级
+    legacyUpgrade: {
+      title: '绘图已升级',
+      message: '"{{file}}" 由旧版 Excalidraw 创建（文件版本 {{version}}），打开时已升级：共 {{count}} 处更改。要用升级后的版本覆盖该文件吗？旧版本会保留为备份。',
+      unknownVersion: '未知',
+      save: '保存升级',
+      later: '暂不'
+    },
+
+    // 超出大小限制的绘图
+    tooLarge: {
+      title: '绘图过大',
+      open: '"{{file}}" 超出了设置的限制（{{reason}}）。打开它可能导致应用无响应。',
+      save: '"{{file}}" 超出了设置的限制（{{reason}}）。保存它可能导致应用无响应。',
+      openAnyway: '仍然打开',
+      saveAnyway: '仍然保存'
+    },
+
+    // 设置导入结果
+    settingsImported: {
+      title: '设置已导入',
+      message: '已导入偏好设置、{{profiles}} 个 AI 配置档案、{{templates}} 个提示词模板和 {{shortcuts}} 个自定义快捷键。\n\nAPI 密钥不包含在文件中，请在 AI 设置中重新填写。',
+      skipped: '以下条目无法使用，已保留当前值：{{entries}}'
+    },
+
+    // 归档导入结果
+    archiveImported: {
+      title: '归档已导入',
+      message: '已导入 {{count}} 个文件。',
+      skipped: '已跳过：\n{{entries}}',
+      invalid: '以下条目不安全或无效，未导入：\n{{entries}}'
     },
 
+    canvasConverted: {
+      title: 'Canvas 已转换',
+      skipped: '已转换 {{nodes}} 张卡片和 {{edges}} 条连线。{{skipped}} 项没有对应内容，已略过。'
+    },
+
+    mermaidCopied: {
+      title: '已复制为 Mermaid',
+      skipped: '已复制 {{nodes}} 个图形和 {{edges}} 个箭头。以下元素没有对应的 Mermaid 写法，已略过：\n{{entries}}'
+    },
+
+    // 语言切换重启确认
     // 删除确认
     deleteConfirm: {
       fileTitle: '🗑️ 删除文件',
@@ -316,7 +388,24 @@ export const translations: TranslationKeys = {
     errors: {
       createFolderFailed: '创建文件夹失败：{{error}}',
       deleteFailed: '删除{{itemType}}失败：{{error}}',
-      moveFailed: '移动文件失败：{{error}}'
+      moveFailed: '移动文件失败：{{error}}',
+      openWindowFailed: '打开新窗口失败：{{error}}',
+      duplicateFailed: '创建副本失败：{{error}}',
+      copyFailed: '复制文件失败：{{error}}',
+      pinFailed: '置顶文件失败：{{error}}',
+      readOnlyFailed: '更改只读状态失败：{{error}}',
+      archiveFailed: '打包文件夹失败：{{error}}',
+      importArchiveFailed: '导入归档失败：{{error}}',
+      saveTemplateFailed: '存为模板失败：{{error}}',
+      exportFailed: '导出失败：{{error}}',
+      mermaidFailed: '转换为 Mermaid 失败：{{error}}',
+      canvasFailed: 'Canvas 转换失败：{{error}}',
+      settingsExportFailed: '导出设置失败：{{error}}',
+      settingsImportFailed: '导入设置失败：{{error}}',
+      reloadFailed: '重新加载文件失败：{{error}}',
+      mergeFailed: '合并版本失败：{{error}}',
+      restoreDeletedFailed: '恢复失败：{{error}}',
+      upgradeFailed: '保存升级后的文件失败：{{error}}'
     },
 
     // 目录树操作
@@ -325,6 +414,7 @@ export const translations: TranslationKeys = {
       deleteFolder: '删除文件夹', 
       newFolder: '新建文件夹 {{timestamp}}',
       newSubfolder: '新建子文件夹',
+      openInNewWindow: '在新窗口中打开',
       rename: '重命名'
     },
 
@@ -359,6 +449,17 @@ export const translations: TranslationKeys = {
       preferences: '偏好设置',
       window: '窗口操作'
     }
+
+    // 在其他实例中打开的绘图
+    fileLocked: {
+      title: '绘图已在别处打开',
+      message: '"{{file}}" 自 {{time}} 起已在 {{host}} 上打开。在此保存可能会覆盖那里的更改。',
+      unknownHost: '另一台电脑'
+    },
+    checksumMismatch: {
+      title: '绘图可能已损坏',
+      message: '"{{file}}" 与保存时记录的校验和不一致，可能是同步失败或被其他程序修改。请检查绘图，或恢复备份。'
+    },
   }
 }
 
@@ -335,13 +335,12 @@ Please generate the corresponding Mermaid code:`
     const { invoke } = await import('@tauri-apps/api/core')
     
     const generateRequest = {
-      base_url: config.baseUrl,
-      api_key: config.apiKey,
-      model: config.model,
+      profile_id: config.profileId ?? null,
       prompt: prompt,
       max_tokens: config.maxTokens,
       temperature: config.temperature,
       stream: false,
+      system_prompt: config.systemPrompt || null,
     }
     
     const result = await invoke<{success: boolean, content?: string, error_message?: string, tokens_used?: number}>('call_ai_api', {
@@ -411,13 +410,12 @@ Please generate the corresponding Mermaid code:`
     try {
       // Start streaming
       const streamRequest = {
-        base_url: config.baseUrl,
-        api_key: config.apiKey,
-        model: config.model,
+        profile_id: config.profileId ?? null,
         prompt: prompt,
         max_tokens: config.maxTokens,
         temperature: config.temperature,
         request_id: requestId,
+        system_prompt: config.systemPrompt || null,
       }
       
       await invoke('call_ai_api_stream', { request: streamRequest })
@@ -5,8 +5,10 @@
 
 import { create } from 'zustand'
 import { persist } from 'zustand/middleware'
+import { invoke } from '@tauri-apps/api/core'
 import { 
   AIConfig, 
+  AIProfileSummary,
   AIProvider, 
   AI_PROVIDERS, 
   DEFAULT_AI_CONFIG,
@@ -23,6 +25,8 @@ interface AIConfigState {
   providers: Record<string, AIProvider>
   /** 当前选择的供应商 */
   currentProvider: string
+  /** 后端保存的配置档案 */
+  profiles: AIProfileSummary[]
   /** 配置是否有效 */
   isValid: boolean
   /** 是否正在验证 */
@@ -73,6 +77,18 @@ interface AIConfigActions {
   
   /** 重置使用统计 */
   resetUsage: () => void
+
+  /** 从后端加载配置档案，并把默认档案设为当前配置 */
+  loadProfiles: () => Promise<void>
+
+  /** 把配置保存为后端档案（含 API 密钥）并设为默认 */
+  saveProfile: (config: AIConfig, providerId?: string) => Promise<void>
+
+  /** 删除配置档案 */
+  deleteProfile: (id: string) => Promise<void>
+
+  /** 设置默认配置档案 */
+  setDefaultProfile: (id: string) => Promise<void>
 }
 
 /**
@@ -80,6 +96,22 @@ interface AIConfigActions {
  */
 export type AIConfigStore = AIConfigState & AIConfigActions
 
+/**
+ * The editable view of a stored profile; the key itself stays in the backend
+ */
+function profileToConfig(profile: AIProfileSummary): Partial<AIConfig> {
+  return {
+    profileId: profile.id,
+    hasApiKey: profile.has_api_key,
+    apiKey: '',
+    baseUrl: profile.base_url,
+    model: profile.model,
+    temperature: profile.temperature,
+    maxTokens: profile.max_tokens,
+    systemPrompt: profile.system_prompt ?? undefined
+  }
+}
+
 /**
  * Default initial state
  */
@@ -87,6 +119,7 @@ const initialState: AIConfigState = {
   config: { ...DEFAULT_AI_CONFIG },
   providers: AI_PROVIDERS,
   currentProvider: 'openai',
+  profiles: [],
   isValid: false,
   isValidating: false,
   lastValidated: undefined,
@@ -144,8 +177,8 @@ export const useAIConfigStore = create<AIConfigStore>()(
             throw new Error(`Provider ${currentProvider} not found`)
           }
 
-          // Basic validation
-          if (!config.apiKey || config.apiKey.length < 10) {
+          // Basic validation; a saved profile may already hold the key
+          if (!config.hasApiKey && (!config.apiKey || config.apiKey.length < 10)) {
             throw new Error('API Key is required and must be at least 10 characters')
           }
 
@@ -283,6 +316,59 @@ export const useAIConfigStore = create<AIConfigStore>()(
             requestsToday: 0
           }
         }))
+      },
+
+      loadProfiles: async () => {
+        const profiles = await invoke<AIProfileSummary[]>('list_ai_profiles')
+        const { config } = get()
+
+        // Keys used to live in local storage; move a configured one to the backend
+        if (profiles.length === 0 && config.apiKey) {
+          await get().saveProfile(config)
+          return
+        }
+
+        const active = profiles.find(p => p.is_default)
+        set(state => ({
+          profiles,
+          ...(active && {
+            currentProvider: active.provider && state.providers[active.provider] ? active.provider : state.currentProvider,
+            config: { ...state.config, ...profileToConfig(active) }
+          })
+        }))
+      },
+
+      saveProfile: async (config: AIConfig, providerId?: string) => {
+        const provider = providerId ?? get().currentProvider
+        const saved = await invoke<AIProfileSummary>('save_ai_profile', {
+          profile: {
+            id: config.profileId ?? '',
+            name: get().providers[provider]?.displayName || provider,
+            provider,
+            base_url: config.baseUrl,
+            // Empty keeps the key the backend already has
+            api_key: config.apiKey,
+            model: config.model,
+            temperature: config.temperature,
+            max_tokens: config.maxTokens,
+            system_prompt: config.systemPrompt || null
+          }
+        })
+        await invoke('set_default_ai_profile', { id: saved.id })
+        await get().loadProfiles()
+      },
+
+      deleteProfile: async (id: string) => {
+        await invoke('delete_ai_profile', { id })
+        if (get().config.profileId === id) {
+          set(state => ({ config: { ...state.config, profileId: undefined, hasApiKey: false } }))
+        }
+        await get().loadProfiles()
+      },
+
+      setDefaultProfile: async (id: string) => {
+        await invoke('set_default_ai_profile', { id })
+        await get().loadProfiles()
       }
     }),
     {
@@ -290,7 +376,7 @@ export const useAIConfigStore = create<AIConfigStore>()(
       partialize: (state) => ({
         config: {
           ...state.config,
-          apiKey: state.config.apiKey // Persist API key in local storage
+          apiKey: '' // The key is kept by the backend profile
         },
         currentProvider: state.currentProvider,
         usage: state.usage,
@@ -326,6 +412,11 @@ export const useAIConfig = () => {
     recordUsage: store.recordUsage,
     estimateTokens: store.estimateTokens,
     getTodayUsage: store.getTodayUsage,
-    resetUsage: store.resetUsage
+    resetUsage: store.resetUsage,
+    profiles: store.profiles,
+    loadProfiles: store.loadProfiles,
+    saveProfile: store.saveProfile,
+    deleteProfile: store.deleteProfile,
+    setDefaultProfile: store.setDefaultProfile
   }
 }
\ No newline at end of file
@@ -5,6 +5,7 @@
 
 import { create } from 'zustand'
 import { persist } from 'zustand/middleware'
+import { invoke } from '@tauri-apps/api/core'
 import { 
   SupportedLanguage, 
   I18nConfig, 
@@ -95,6 +96,17 @@ function getTranslations(language: SupportedLanguage): TranslationKeys {
   return translationsMap[language] || translationsMap[DEFAULT_I18N_CONFIG.fallbackLanguage]
 }
 
+/**
+ * 让原生菜单跟随界面语言（无需重启）
+ */
+async function syncNativeMenu(language: SupportedLanguage): Promise<void> {
+  try {
+    await invoke('set_app_language', { locale: language })
+  } catch (error) {
+    console.error('Failed to update native menu language:', error)
+  }
+}
+
 /**
  * 国际化状态管理
  */
@@ -135,6 +147,8 @@ export const useI18nStore = create<I18nStore>()(
             currentTranslations: loadedTranslations,
             isLoading: false 
           })
+
+          await syncNativeMenu(language)
           
         } catch (error) {
           set({ 
@@ -228,6 +242,9 @@ export const useI18nStore = create<I18nStore>()(
             isLoading: false 
           })
           console.log('✅ I18n initialization complete')
+
+          // The native menu is built before the frontend knows the language
+          await syncNativeMenu(targetLanguage)
           
         } catch (error) {
           console.error('❌ I18n initialization failed:', error)
@@ -1,7 +1,9 @@
 import { create } from 'zustand'
 import { invoke } from '@tauri-apps/api/core'
-import { ExcalidrawFile, FileTreeNode, Preferences } from '../types'
+import { BatchReport, ExcalidrawFile, FileSizeInfo, FileTreeNode, LaunchTarget, Preferences } from '../types'
 import { convertPreferencesFromRust, convertPreferencesToRust } from '../lib/preferences'
+import { errorCode, errorMessage } from '../lib/errors'
+import { templateDateVariables } from '../lib/utils'
 import { dialogService } from '../services/dialogService'
 import { useI18nStore } from './useI18nStore'
 
@@ -12,6 +14,8 @@ interface AppStore {
   fileTree: FileTreeNode[]
   activeFile: ExcalidrawFile | null
   fileContent: string | null
+  /** Drawings and folders locked with `set_read_only` */
+  readOnlyPaths: string[]
   preferences: Preferences
   sidebarVisible: boolean
   isDirty: boolean
@@ -33,17 +37,81 @@ interface AppStore {
   loadFileTree: (dir: string) => Promise<void>
   loadFile: (file: ExcalidrawFile) => Promise<void>
   loadFileFromTree: (node: FileTreeNode) => Promise<void>
+  openLaunchTarget: (target: LaunchTarget) => Promise<void>
   saveCurrentFile: (content?: string) => Promise<void>
-  createNewFile: (fileName?: string) => Promise<void>
+  createNewFile: (fileName?: string, template?: string) => Promise<void>
   renameFile: (oldPath: string, newName: string) => Promise<void>
   renameDirectory: (oldPath: string, newName: string) => Promise<void>
   deleteFile: (filePath: string) => Promise<boolean>
   deleteDirectory: (dirPath: string) => Promise<boolean>
   moveFile: (sourcePath: string, targetDirectory: string) => Promise<void>
+  moveDirectory: (sourcePath: string, targetParent: string) => Promise<void>
+  copyFile: (sourcePath: string, targetDirectory: string, newName?: string) => Promise<string>
+  pinFile: (path: string) => Promise<void>
+  unpinFile: (path: string) => Promise<void>
+  setReadOnly: (path: string, readOnly: boolean) => Promise<void>
+  deleteFiles: (paths: string[]) => Promise<BatchReport>
+  moveFiles: (paths: string[], targetDirectory: string) => Promise<BatchReport>
   createDirectory: (parentPath: string, directoryName: string) => Promise<void>
   loadPreferences: () => Promise<void>
+  reloadPreferences: () => Promise<void>
   savePreferences: () => Promise<void>
   toggleSidebar: () => void
+  setTheme: (theme: Preferences['theme']) => void
+}
+
+// Folders past this many entries are loaded when expanded (get_file_tree_children)
+const TREE_MAX_ENTRIES = 5000
+
+// Asks before a drawing over the configured size limits is opened or saved
+async function confirmOversized(action: 'open' | 'save', file: string, reason: string) {
+  const { t } = useI18nStore.getState()
+  const response = await dialogService.showDialog({
+    title: t('dialog.tooLarge.title'),
+    message: t(`dialog.tooLarge.${action}`, { file, reason }),
+    type: 'warning',
+    confirmLabel: t(action === 'open' ? 'dialog.tooLarge.openAnyway' : 'dialog.tooLarge.saveAnyway'),
+    cancelLabel: t('common.cancel'),
+    showCancel: true
+  })
+  return response === true
+}
+
+// Reads a drawing, checking its size first so a huge file isn't loaded
+// without asking; null when the user backs out
+async function readDrawing(path: string, name: string): Promise<string | null> {
+  const { size, too_large } = await invoke<FileSizeInfo>('get_file_size', { filePath: path })
+  if (too_large) {
+    const reason = `${(size / (1024 * 1024)).toFixed(1)} MB`
+    if (!(await confirmOversized('open', name, reason))) return null
+    return invoke<string>('read_file', { filePath: path, force: true })
+  }
+
+  try {
+    return await invoke<string>('read_file', { filePath: path })
+  } catch (error) {
+    // Too many elements or an oversized image only show once it's read
+    if (errorCode(error) !== 'too_large') throw error
+    if (!(await confirmOversized('open', name, errorMessage(error)))) return null
+    return invoke<string>('read_file', { filePath: path, force: true })
+  }
+}
+
+function applyTheme(theme: Preferences['theme']) {
+  const root = document.documentElement
+  if (theme === 'dark') {
+    root.classList.add('dark')
+  } else if (theme === 'light') {
+    root.classList.remove('dark')
+  } else {
+    // System theme
+    const prefersDark = window.matchMedia('(prefers-color-scheme: dark)').matches
+    if (prefersDark) {
+      root.classList.add('dark')
+    } else {
+      root.classList.remove('dark')
+    }
+  }
 }
 
 export const useStore = create<AppStore>((set, get) => ({
@@ -53,6 +121,7 @@ export const useStore = create<AppStore>((set, get) => ({
   fileTree: [],
   activeFile: null,
   fileContent: null,
+  readOnlyPaths: [],
   preferences: {
     lastDirectory: null,
     recentDirectories: [],
@@ -81,6 +150,11 @@ export const useStore = create<AppStore>((set, get) => ({
   },
 
   markTreeNodeAsModified: (filePath, modified) => {
+    // The backend asks before closing a window with unsaved files
+    invoke(modified ? 'mark_file_dirty' : 'mark_file_clean', { path: filePath }).catch((error) =>
+      console.error('Failed to record unsaved changes:', error)
+    )
+
     const updateNode = (nodes: FileTreeNode[]): FileTreeNode[] => {
       return nodes.map(node => {
         if (node.path === filePath) {
@@ -103,7 +177,7 @@ export const useStore = create<AppStore>((set, get) => ({
     try {
       const [files, fileTree] = await Promise.all([
         invoke<ExcalidrawFile[]>('list_excalidraw_files', { directory: dir }),
-        invoke<FileTreeNode[]>('get_file_tree', { directory: dir })
+        invoke<FileTreeNode[]>('get_file_tree', { directory: dir, maxEntries: TREE_MAX_ENTRIES })
       ])
       
       set({
@@ -138,18 +212,22 @@ export const useStore = create<AppStore>((set, get) => ({
     } catch (error) {
       console.error('Failed to load directory:', error)
       // Show user-friendly error message
-      alert(`Failed to load directory: ${error}`)
+      alert(`Failed to load directory: ${errorMessage(error)}`)
     }
   },
 
   // Load file tree only
   loadFileTree: async (dir) => {
     try {
-      const fileTree = await invoke<FileTreeNode[]>('get_file_tree', {
-        directory: dir,
-      })
+      const [fileTree, readOnlyPaths] = await Promise.all([
+        invoke<FileTreeNode[]>('get_file_tree', {
+          directory: dir,
+          maxEntries: TREE_MAX_ENTRIES,
+        }),
+        invoke<string[]>('get_read_only_paths'),
+      ])
       
-      set({ fileTree })
+      set({ fileTree, readOnlyPaths })
     } catch (error) {
       console.error('Failed to load file tree:', error)
     }
@@ -199,9 +277,8 @@ export const useStore = create<AppStore>((set, get) => ({
     }
     
     try {
-      const content = await invoke<string>('read_file', {
-        filePath: file.path,
-      })
+      const content = await readDrawing(file.path, file.name)
+      if (content === null) return
       
       set({
         activeFile: file,
@@ -212,11 +289,21 @@ export const useStore = create<AppStore>((set, get) => ({
       // Clear modified state for this file
       state.markFileAsModified(file.path, false)
       state.markTreeNodeAsModified(file.path, false)
+
+      // Update preferences with recent file
+      const prefs = get().preferences
+      const recentFiles = (prefs.recentFiles || []).filter((f) => f !== file.path)
+      recentFiles.unshift(file.path)
+      if (recentFiles.length > 10) {
+        recentFiles.pop()
+      }
+      set({ preferences: { ...prefs, recentFiles } })
+      await get().savePreferences()
     } catch (error) {
       console.error('Failed to load file:', error)
       
       // If file doesn't exist, refresh the tree and show error
-      if (String(error).includes('No such file') || String(error).includes('not found')) {
+      if (errorCode(error) === 'not_found') {
         alert(`File not found: ${file.name}\n\nThe file may have been deleted or moved. Refreshing file list...`)
         
         // Clear active file if it's the one that failed
@@ -234,11 +321,31 @@ export const useStore = create<AppStore>((set, get) => ({
         }
       } else {
         // Other errors
-        alert(`Failed to load file: ${error}`)
+        alert(`Failed to load file: ${errorMessage(error)}`)
       }
     }
   },
 
+  // Open a file or folder passed on the command line
+  openLaunchTarget: async (target) => {
+    const state = get()
+
+    if (target.is_directory) {
+      if (state.currentDirectory !== target.path) {
+        await state.loadDirectory(target.path)
+      }
+      return
+    }
+
+    // Show the file within its folder unless it's already in the open one
+    const parent = target.path.slice(0, target.path.lastIndexOf('/')) || '/'
+    if (!state.currentDirectory || !target.path.startsWith(`${state.currentDirectory}/`)) {
+      await state.loadDirectory(parent)
+    }
+    const name = target.path.split('/').pop() || target.path
+    await get().loadFile({ name, path: target.path, modified: false })
+  },
+
   // Load file from tree node
   loadFileFromTree: async (node) => {
     if (node.is_directory) return
@@ -285,9 +392,8 @@ export const useStore = create<AppStore>((set, get) => ({
     }
     
     try {
-      const content = await invoke<string>('read_file', {
-        filePath: node.path,
-      })
+      const content = await readDrawing(node.path, node.name)
+      if (content === null) return
       
       // Convert tree node to ExcalidrawFile
       const file: ExcalidrawFile = {
@@ -307,7 +413,7 @@ export const useStore = create<AppStore>((set, get) => ({
       console.error('Failed to load file:', error)
       
       // If file doesn't exist, refresh the tree and show error
-      if (String(error).includes('No such file') || String(error).includes('not found')) {
+      if (errorCode(error) === 'not_found') {
         alert(`File not found: ${node.name}\n\nThe file may have been deleted or moved. Refreshing file list...`)
         
         // Clear active file if it's the one that failed
@@ -325,7 +431,7 @@ export const useStore = create<AppStore>((set, get) => ({
         }
       } else {
         // Other errors
-        alert(`Failed to load file: ${error}`)
+        alert(`Failed to load file: ${errorMessage(error)}`)
       }
     }
   },
@@ -373,23 +479,47 @@ export const useStore = create<AppStore>((set, get) => ({
     
     try {
       console.log('[saveCurrentFile] Saving file:', activeFile.path)
-      await invoke('save_file', {
-        filePath: activeFile.path,
-        content: contentToSave,
-      })
+      // A new path means the vault compresses drawings and this one was converted
+      let compressedPath: string | null
+      try {
+        compressedPath = await invoke<string | null>('save_file', {
+          filePath: activeFile.path,
+          content: contentToSave,
+        })
+      } catch (error) {
+        if (errorCode(error) !== 'too_large') throw error
+        if (!(await confirmOversized('save', activeFile.name, errorMessage(error)))) return
+        compressedPath = await invoke<string | null>('save_file', {
+          filePath: activeFile.path,
+          content: contentToSave,
+          force: true,
+        })
+      }
       
       state.markFileAsModified(activeFile.path, false)
       state.markTreeNodeAsModified(activeFile.path, false)
       set({ isDirty: false })
+      if (compressedPath && get().activeFile?.path === activeFile.path) {
+        set({
+          activeFile: {
+            ...activeFile,
+            name: compressedPath.split(/[\\/]/).pop() ?? activeFile.name,
+            path: compressedPath,
+          },
+        })
+        if (state.currentDirectory) {
+          await state.loadFileTree(state.currentDirectory)
+        }
+      }
       console.log('[saveCurrentFile] File saved successfully')
     } catch (error) {
       console.error('[saveCurrentFile] Failed to save file:', error)
-      alert(`Failed to save file: ${error}`)
+      alert(`Failed to save file: ${errorMessage(error)}`)
     }
   },
 
   // Create new file
-  createNewFile: async (fileName) => {
+  createNewFile: async (fileName, template) => {
     const state = get()
     let { currentDirectory } = state
     
@@ -429,20 +559,20 @@ export const useStore = create<AppStore>((set, get) => ({
         currentDirectory = dir
       } catch (error) {
         console.error('Failed to select directory:', error)
-        alert(`Failed to select directory: ${error}`)
+        alert(`Failed to select directory: ${errorMessage(error)}`)
         return
       }
     }
     
-    // Generate default filename if not provided
-    const finalFileName = fileName || `Untitled-${Date.now()}.excalidraw`
-    
     try {
-      // Create the new file
+      // Create the new file; without a name the backend picks the next "Untitled-N"
       const filePath = await invoke<string>('create_new_file', {
         directory: currentDirectory,
-        fileName: finalFileName,
+        fileName: fileName || null,
+        template: template || null,
+        variables: templateDateVariables(),
       })
+      const finalFileName = filePath.split(/[\\/]/).pop() || filePath
       
       // Reload the file tree to show the new file
       await state.loadFileTree(currentDirectory)
@@ -458,7 +588,7 @@ export const useStore = create<AppStore>((set, get) => ({
       await state.loadFile(file)
     } catch (error) {
       console.error('Failed to create new file:', error)
-      alert(`Failed to create file: ${error}`)
+      alert(`Failed to create file: ${errorMessage(error)}`)
     }
   },
   
@@ -494,7 +624,7 @@ export const useStore = create<AppStore>((set, get) => ({
       }
     } catch (error) {
       console.error('Failed to rename file:', error)
-      alert(`Failed to rename file: ${error}`)
+      alert(`Failed to rename file: ${errorMessage(error)}`)
     }
   },
   
@@ -514,7 +644,7 @@ export const useStore = create<AppStore>((set, get) => ({
       }
     } catch (error) {
       console.error('Failed to rename directory:', error)
-      alert(`Failed to rename directory: ${error}`)
+      alert(`Failed to rename directory: ${errorMessage(error)}`)
     }
   },
   
@@ -551,7 +681,7 @@ export const useStore = create<AppStore>((set, get) => ({
     } catch (error) {
       console.error('[deleteFile] Failed to delete file:', error)
       console.error('[deleteFile] Error details:', JSON.stringify(error))
-      alert(`Failed to delete file: ${error}`)
+      alert(`Failed to delete file: ${errorMessage(error)}`)
       throw error // Re-throw so caller knows deletion failed
     }
   },
@@ -589,7 +719,7 @@ export const useStore = create<AppStore>((set, get) => ({
     } catch (error) {
       console.error('[deleteDirectory] Failed to delete directory:', error)
       console.error('[deleteDirectory] Error details:', JSON.stringify(error))
-      alert(`Failed to delete directory: ${error}`)
+      alert(`Failed to delete directory: ${errorMessage(error)}`)
       throw error // Re-throw so caller knows deletion failed
     }
   },
@@ -609,21 +739,7 @@ export const useStore = create<AppStore>((set, get) => ({
         sidebarVisible: safePrefs.sidebarVisible,
       })
       
-      // Apply theme
-      const root = document.documentElement
-      if (safePrefs.theme === 'dark') {
-        root.classList.add('dark')
-      } else if (safePrefs.theme === 'light') {
-        root.classList.remove('dark')
-      } else {
-        // System theme
-        const prefersDark = window.matchMedia('(prefers-color-scheme: dark)').matches
-        if (prefersDark) {
-          root.classList.add('dark')
-        } else {
-          root.classList.remove('dark')
-        }
-      }
+      applyTheme(safePrefs.theme)
       
       // Auto-load last directory if it exists
       if (safePrefs.lastDirectory) {
@@ -654,6 +770,14 @@ export const useStore = create<AppStore>((set, get) => ({
     }
   },
 
+  // Pick up preferences the backend changed, e.g. after importing settings,
+  // without reopening the last directory
+  reloadPreferences: async () => {
+    const prefs = convertPreferencesFromRust(await invoke<any>('get_preferences'))
+    set({ preferences: prefs, sidebarVisible: prefs.sidebarVisible })
+    applyTheme(prefs.theme)
+  },
+
   // Save preferences
   savePreferences: async () => {
     const { preferences } = get()
@@ -699,6 +823,111 @@ export const useStore = create<AppStore>((set, get) => ({
     }
   },
   
+  // Move a directory with its contents under another directory
+  moveDirectory: async (sourcePath, targetParent) => {
+    const newPath = await invoke<string>('move_directory', { sourcePath, targetParent })
+    const state = get()
+
+    // The active file moves along when it was inside
+    const activePath = state.activeFile?.path
+    if (state.activeFile && activePath?.startsWith(`${sourcePath}/`)) {
+      set({
+        activeFile: {
+          ...state.activeFile,
+          path: newPath + activePath.slice(sourcePath.length),
+        },
+      })
+    }
+
+    if (state.currentDirectory) {
+      await state.loadFileTree(state.currentDirectory)
+    }
+  },
+
+  // Copy a file into another directory; a taken name gets a " copy" suffix
+  copyFile: async (sourcePath, targetDirectory, newName) => {
+    const newPath = await invoke<string>('copy_file', { sourcePath, targetDirectory, newName })
+    const state = get()
+    if (state.currentDirectory) {
+      await state.loadFileTree(state.currentDirectory)
+    }
+    return newPath
+  },
+
+  // Pinned drawings come first in their folder once the tree reloads
+  pinFile: async (path) => {
+    await invoke('pin_file', { path })
+    const state = get()
+    if (state.currentDirectory) {
+      await state.loadFileTree(state.currentDirectory)
+    }
+  },
+
+  unpinFile: async (path) => {
+    await invoke('unpin_file', { path })
+    const state = get()
+    if (state.currentDirectory) {
+      await state.loadFileTree(state.currentDirectory)
+    }
+  },
+
+  // Locked drawings open in view mode; the tree shows the lock once reloaded
+  setReadOnly: async (path, readOnly) => {
+    await invoke('set_read_only', { path, readOnly })
+    const state = get()
+    if (state.currentDirectory) {
+      await state.loadFileTree(state.currentDirectory)
+    } else {
+      set({ readOnlyPaths: await invoke<string[]>('get_read_only_paths') })
+    }
+  },
+
+  // Delete several files and folders; failures are reported per path
+  // NOTE: Confirmation should be handled by the caller
+  deleteFiles: async (paths) => {
+    const report = await invoke<BatchReport>('delete_files', { paths })
+    const state = get()
+
+    // Clear the active file if it, or a folder containing it, is gone
+    const activePath = state.activeFile?.path
+    const deleted = report.items.filter((item) => !item.error).map((item) => item.path)
+    if (activePath && deleted.some((path) => activePath === path || activePath.startsWith(`${path}/`))) {
+      set({
+        activeFile: null,
+        fileContent: null,
+        isDirty: false,
+      })
+    }
+
+    if (state.currentDirectory) {
+      await state.loadFileTree(state.currentDirectory)
+    }
+    return report
+  },
+
+  // Move several files into one directory; failures are reported per path
+  moveFiles: async (paths, targetDirectory) => {
+    const report = await invoke<BatchReport>('move_files', { paths, targetDirectory })
+    const state = get()
+
+    // Follow the active file to its new location
+    const moved = report.items.find((item) => item.path === state.activeFile?.path)
+    if (state.activeFile && moved?.output) {
+      set({
+        activeFile: {
+          ...state.activeFile,
+          path: moved.output,
+          name: moved.output.split('/').pop() || state.activeFile.name,
+        },
+      })
+    }
+
+    if (state.currentDirectory) {
+      await state.loadFileTree(state.currentDirectory)
+    }
+    return report
+  },
+
   // Create new directory
   createDirectory: async (parentPath, directoryName) => {
     try {
@@ -715,7 +944,7 @@ export const useStore = create<AppStore>((set, get) => ({
       }
     } catch (error) {
       console.error('Failed to create directory:', error)
-      alert(`Failed to create directory: ${error}`)
+      alert(`Failed to create directory: ${errorMessage(error)}`)
       throw error
     }
   },
@@ -732,4 +961,12 @@ export const useStore = create<AppStore>((set, get) => ({
     state.savePreferences()
   },
 
+  // Switch theme from View > Theme
+  setTheme: (theme) => {
+    const state = get()
+    applyTheme(theme)
+    set({ preferences: { ...state.preferences, theme } })
+    state.savePreferences()
+  },
+
 }))
\ No newline at end of file
@@ -21,6 +21,29 @@ export interface AIConfig {
   timeout: number
   /** 是否启用流式响应 */
   stream: boolean
+  /** 系统提示词 (可选) */
+  systemPrompt?: string
+  /** 后端保存的配置档案 ID，AI 命令按它取用密钥 */
+  profileId?: string
+  /** 后端是否已保存该档案的 API 密钥 */
+  hasApiKey?: boolean
+}
+
+/**
+ * 后端保存的 AI 配置档案（不含 API 密钥）
+ */
+export interface AIProfileSummary {
+  id: string
+  name: string
+  provider?: string | null
+  base_url: string
+  model: string
+  temperature: number
+  max_tokens: number
+  system_prompt?: string | null
+  updated_at: number
+  has_api_key: boolean
+  is_default: boolean
 }
 
 /**
@@ -166,6 +189,23 @@ export const AI_PROVIDERS: Record<string, AIProvider> = {
       return true
     }
   },
+  gemini: {
+    name: 'gemini',
+    displayName: 'Google Gemini',
+    defaultConfig: {
+      baseUrl: 'https://generativelanguage.googleapis.com/v1beta',
+      model: 'gemini-1.5-flash',
+      temperature: 0.7,
+      maxTokens: 2000,
+      timeout: 30000,
+      stream: false
+    },
+    supportedModels: ['gemini-1.5-flash', 'gemini-1.5-pro', 'gemini-2.0-flash'],
+    validateConfig: async (config: AIConfig) => {
+      // Gemini is validated through the backend test_ai_connection command
+      return config.baseUrl.length > 0 && config.apiKey.length > 0
+    }
+  },
   custom: {
     name: 'custom',
     displayName: 'Custom API',
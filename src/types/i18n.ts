@@ -89,6 +89,7 @@ export interface TranslationKeys {
       description: string
       provider: string
       apiKey: string
+      apiKeySaved: string
       apiUrl: string
       model: string
       testConnection: string
@@ -263,6 +264,21 @@ export interface TranslationKeys {
     // 文件状态
     unsavedChanges: string
     fileModified: string
+    syncStatus: {
+      modified: string
+      new: string
+      conflict: string
+    }
+    conflictCopies: string
+    conflictCopy: string
+
+    // 最近删除
+    recentlyDeleted: {
+      title: string
+      empty: string
+      restore: string
+      deletedAt: string
+    }
     
     // 空状态
     emptyState: {
@@ -286,20 +302,92 @@ export interface TranslationKeys {
       cancel: string
     }
 
-    // 关闭确认
-    closeConfirm: {
+    // 崩溃恢复
+    draftRecovery: {
       title: string
       message: string
-      closeWithoutSaving: string
-      goBack: string
+      recover: string
+      discard: string
+    }
+
+    // 打开的绘图在磁盘上被修改
+    externalChange: {
+      title: string
+      modified: string
+      summary: string
+      unsaved: string
+      reload: string
+      keepMine: string
+      deleted: string
+      mergeOffer: string
+      merge: string
+      chooseOne: string
+      merged: string
+      conflicts: string
+    }
+
+    // 同步冲突副本合并回原文件
+    conflictCopy: {
+      title: string
+      merged: string
     }
 
-    // 语言切换重启确认
-    languageRestart: {
+    // 旧版 Excalidraw 文件的升级
+    legacyUpgrade: {
       title: string
       message: string
-      restart: string
-      cancel: string
+      unknownVersion: string
+      save: string
+      later: string
+    }
+
+    // 在其他实例中打开的绘图
+    fileLocked: {
+      title: string
+      message: string
+      unknownHost: string
+    }
+
+    // 校验和不匹配的绘图
+    checksumMismatch: {
+      title: string
+      message: string
+    }
+
+    // 超出大小限制的绘图
+    tooLarge: {
+      title: string
+      open: string
+      save: string
+      openAnyway: string
+      saveAnyway: string
+    }
+
+    // 设置导入结果
+    settingsImported: {
+      title: string
+      message: string
+      skipped: string
+    }
+
+    // 归档导入结果
+    archiveImported: {
+      title: string
+      message: string
+      skipped: string
+      invalid: string
+    }
+
+    // Canvas 转换中略过的内容
+    canvasConverted: {
+      title: string
+      skipped: string
+    }
+
+    // 复制为 Mermaid 时未转换的元素
+    mermaidCopied: {
+      title: string
+      skipped: string
     }
 
     // 删除确认
@@ -317,6 +405,23 @@ export interface TranslationKeys {
       createFolderFailed: string
       deleteFailed: string
       moveFailed: string
+      openWindowFailed: string
+      duplicateFailed: string
+      copyFailed: string
+      pinFailed: string
+      readOnlyFailed: string
+      archiveFailed: string
+      importArchiveFailed: string
+      saveTemplateFailed: string
+      exportFailed: string
+      mermaidFailed: string
+      canvasFailed: string
+      settingsExportFailed: string
+      settingsImportFailed: string
+      reloadFailed: string
+      mergeFailed: string
+      restoreDeletedFailed: string
+      upgradeFailed: string
     }
 
     // 目录树操作
@@ -325,6 +430,7 @@ export interface TranslationKeys {
       deleteFolder: string
       newFolder: string
       newSubfolder: string
+      openInNewWindow: string
       rename: string
     }
 
@@ -1,17 +1,131 @@
-export interface ExcalidrawFile {
+import type { AppError } from '../lib/errors'
+import type { ExcalidrawElement } from '@excalidraw/excalidraw/dist/types/excalidraw/element/types'
+
+/** File attributes from the backend; timestamps are Unix milliseconds */
+export interface FileMetadata {
+  size?: number
+  modified_at?: number | null
+  created_at?: number | null
+  readonly?: boolean
+}
+
+export interface ExcalidrawFile extends FileMetadata {
   name: string
   path: string
+  relative_path?: string
   modified: boolean
 }
 
-export interface FileTreeNode {
+/** Order within each folder of `get_file_tree`; folders always come first */
+export type TreeSort = 'name' | 'modified' | 'size'
+
+export interface FileTreeNode extends FileMetadata {
   name: string
   path: string
+  relative_path?: string
   is_directory: boolean
   modified: boolean
+  /** Kept at the top of its folder */
+  pinned?: boolean
+  /** Locked against edits, directly or by a folder above it */
+  read_only?: boolean
+  /** Set on files of vaults synced over WebDAV */
+  sync_status?: SyncStatus
+  /** Conflict copies sync tools left next to this drawing */
+  conflicts?: FileTreeNode[]
+  /** Unset on folders whose contents are loaded on expand */
   children?: FileTreeNode[]
 }
 
+export interface FileTreePage {
+  children: FileTreeNode[]
+  total: number
+  next_offset: number | null
+}
+
+/** Answer of `search_drawings` */
+export interface SearchHit {
+  path: string
+  name: string
+  /** Text around the first match inside the drawing, if the match is there */
+  snippet: string | null
+  tags: string[]
+  modified_at: number | null
+}
+
+/** Answer of `get_vault_stats`; sizes in bytes */
+export interface VaultStats {
+  directory: string
+  /** Drawings, from the metadata index */
+  files: number
+  total_size: number
+  elements: number
+  element_types: Record<string, number>
+  /** Most used first */
+  tags: { tag: string; files: number }[]
+  last_modified_at: number | null
+  /** Biggest first */
+  largest: { path: string; size: number; modified_at: number | null }[]
+  /** Drawings with health issues, those that don't open first */
+  needs_attention: { path: string; errors: number; warnings: number; message: string }[]
+  /** Files that aren't drawings or the vault's own settings */
+  foreign_files: number
+  foreign_size: number
+  /** Files and folders hidden by the ignore rules */
+  untracked: number
+  backup_files: number
+  backup_size: number
+  /** Drawings by time since their last change, newest first */
+  modified: { age: 'day' | 'week' | 'month' | 'quarter' | 'year' | 'older' | 'unknown'; files: number; bytes: number }[]
+}
+
+/** Answer of `find_duplicate_scenes`: copies of one drawing, newest first */
+export interface DuplicateGroup {
+  kind: 'identical' | 'similar'
+  files: {
+    path: string
+    size: number
+    modified_at: number | null
+    elements: number
+    /** Share of elements matching the group's first file; 1 for copies */
+    similarity: number
+  }[]
+}
+
+/** Fixes `repair_file` can apply; mirrors the Rust `IntegrityFix` */
+export type IntegrityFix =
+  | 'repair_content'
+  | 'drop_dangling_references'
+  | 'sync_bindings'
+  | 'remove_orphaned_files'
+  | 'remove_broken_images'
+
+export interface IntegrityIssue {
+  path: string
+  element_id: string | null
+  code: 'invalid_content' | 'schema' | 'dangling_reference' | 'one_sided_binding' | 'orphaned_file' | 'missing_image_data'
+  severity: 'error' | 'warning'
+  message: string
+  /** The fix that resolves it, if one does */
+  fix: IntegrityFix | null
+}
+
+/** Answer of `scan_vault` */
+export interface IntegrityReport {
+  directory: string
+  files_scanned: number
+  files_with_issues: number
+  issues: IntegrityIssue[]
+  codes: Record<string, number>
+}
+
+/** Answer of `repair_file` */
+export interface IntegrityRepair {
+  applied: string[]
+  /** What a scan still finds afterwards */
+  remaining: IntegrityIssue[]
+}
+
 export interface AppState {
   currentDirectory: string | null
   files: ExcalidrawFile[]
@@ -22,6 +136,468 @@ export interface AppState {
 export interface Preferences {
   lastDirectory: string | null
   recentDirectories: string[]
+  /** Drawings opened last, newest first */
+  recentFiles?: string[]
   theme: 'light' | 'dark' | 'system'
   sidebarVisible: boolean
-}
\ No newline at end of file
+  proxy?: ProxySettings
+  /** Strip deleted elements and unused image data on save */
+  compactOnSave?: boolean
+  /** Save with sorted keys and without session state, for minimal diffs */
+  normalizeOnSave?: boolean
+  /** Store a checksum in saved drawings and check it when they are read */
+  checksumOnSave?: boolean
+  /** Folders shown side by side in the sidebar */
+  workspaceRoots?: string[]
+  /** Gitignore-style patterns hidden in every workspace */
+  ignorePatterns?: string[]
+  /** Rotating backups of previous versions written on save */
+  backup?: BackupSettings
+  /** Most verbose level written to the log files */
+  logLevel?: LogLevel
+  /** Seconds between automatic saves; 0 turns it off */
+  autosaveIntervalSecs?: number
+  defaultExportFormat?: 'svg' | 'png'
+  /** Font for new text */
+  defaultFont?: FontFamily
+  grid?: GridSettings
+  /** Native file events or polling, for vaults on network shares */
+  watcher?: WatcherSettings
+  /** Largest drawings opened or saved without asking */
+  limits?: FileLimits
+  /** Scheduled copies of the open vaults to a backup folder */
+  vaultBackup?: VaultBackupSettings
+  /** Bucket vault archives are uploaded to; the secret key is in the keychain */
+  s3Backup?: S3Settings
+  /** Ask before deleting files and folders */
+  confirmOnDelete?: boolean
+  /** Days deleted drawings and local folders stay in Recently Deleted before they move on to the system trash; 0 deletes them right away */
+  deletedRetentionDays?: number
+  /** Interface and menu language; unset until the user picks one */
+  language?: string | null
+}
+
+/** Mirrors the Rust `FontFamily` (snake_case) */
+export type FontFamily = 'hand_drawn' | 'normal' | 'code'
+
+export interface GridSettings {
+  enabled: boolean
+  size: number
+}
+
+export interface WatcherSettings {
+  /** `auto` polls only on network shares */
+  mode: 'auto' | 'native' | 'poll'
+  /** Seconds between scans when polling */
+  poll_interval_secs: number
+}
+
+/** Mirrors the Rust `FileLimits`; 0 turns a limit off */
+export interface FileLimits {
+  max_file_mb: number
+  max_elements: number
+  /** Per embedded image */
+  max_image_mb: number
+}
+
+/** Mirrors the Rust `VaultBackupSettings` */
+export interface VaultBackupSettings {
+  enabled: boolean
+  /** Must be outside every vault */
+  directory: string | null
+  /** 0 only backs up on exit and on demand */
+  interval_minutes: number
+  on_exit: boolean
+  /** Backups kept per vault; 0 keeps all */
+  keep: number
+  format: 'zip' | 'copy'
+}
+
+/** Answer of `list_vault_backups` */
+export interface VaultBackupInfo extends FileMetadata {
+  vault: string
+  path: string
+  format: VaultBackupSettings['format']
+}
+
+/** Payload of `vault-backup-status` */
+export interface VaultBackupStatus {
+  state: 'started' | 'finished' | 'failed'
+  vault: string
+  path: string | null
+  error: string | null
+}
+
+/** Mirrors the Rust `S3Settings`; set the secret key with `set_s3_secret` */
+export interface S3Settings {
+  endpoint: string
+  region: string
+  /** Used when a command names no bucket */
+  bucket: string
+  access_key_id: string
+  /** Needed by MinIO */
+  path_style: boolean
+}
+
+/** Payload of `s3-progress`, in bytes */
+export interface S3Progress {
+  key: string
+  done: number
+  total: number
+}
+
+/** Answer of `upload_backup` */
+export interface S3UploadReport {
+  bucket: string
+  key: string
+  bytes: number
+}
+
+/** Answer of `get_share_server_status`, payload of `share-server-status` */
+export interface ShareServerStatus {
+  running: boolean
+  directory: string | null
+  port: number | null
+  /** LAN and loopback addresses, token included */
+  urls: string[]
+  token: string | null
+  started_at: number | null
+  requests: number
+}
+
+/** Options of `export_pdf`; every field has a default */
+export interface PdfOptions {
+  paper?: 'a4' | 'a3' | 'a5' | 'letter' | 'legal'
+  /** `auto` turns each page to suit its content */
+  orientation?: 'auto' | 'portrait' | 'landscape'
+  margin_mm?: number
+  /** A page per frame, the whole canvas on one page, or tiled at `tile_scale` */
+  layout?: 'frames' | 'fit' | 'tile'
+  /** PDF points per scene pixel when tiling */
+  tile_scale?: number
+}
+
+/** Answer of `export_pdf` */
+export interface PdfReport {
+  output_path: string
+  pages: number
+}
+
+/** An element `export_mermaid` left out, and why */
+export interface MermaidSkipped {
+  id: string
+  element_type: string
+  reason: string
+}
+
+/** Answer of `export_mermaid` */
+export interface MermaidReport {
+  /** Flowchart source, ready for a ```mermaid block */
+  mermaid: string
+  nodes: number
+  edges: number
+  skipped: MermaidSkipped[]
+}
+
+/** Answer of `import_json_canvas` and `export_json_canvas` */
+export interface CanvasReport {
+  output_path: string
+  nodes: number
+  edges: number
+  /** Nodes, edges or elements the other format has no place for */
+  skipped: number
+}
+
+/** Answer of `get_collab_status`, payload of `collab-status` */
+export interface CollabStatus {
+  active: boolean
+  role: 'host' | 'guest' | null
+  /** The hosted drawing; unset for guests */
+  file_path: string | null
+  /** Addresses guests join at, token included */
+  urls: string[]
+  peers: number
+}
+
+/** Payload of `collab-update`: elements changed by peers */
+export interface CollabUpdate {
+  elements: ExcalidrawElement[]
+}
+
+/** How a file differs from its copy on the WebDAV server */
+export type SyncStatus = 'synced' | 'modified' | 'new' | 'conflict'
+
+/** Mirrors the Rust `WebDavConfig`; an empty password keeps the stored one */
+export interface WebDavConfig {
+  url: string
+  username: string
+  password?: string
+}
+
+/** Answer of `get_webdav_sync` */
+export interface WebDavConfigSummary extends WebDavConfig {
+  has_password: boolean
+}
+
+/** Answer of `sync_now` */
+export interface SyncReport {
+  uploaded: string[]
+  downloaded: string[]
+  deleted_local: string[]
+  deleted_remote: string[]
+  /** The server's versions were saved next to these */
+  conflicts: string[]
+  failed: { path: string; error: string }[]
+}
+
+/** Answer of `get_file_size` */
+export interface FileSizeInfo {
+  size: number
+  /** `null` when the file size limit is off */
+  max_bytes: number | null
+  too_large: boolean
+}
+
+/** A menu command's keyboard shortcut, from `get_shortcuts` */
+export interface ShortcutInfo {
+  command: string
+  /** Empty when the command has no shortcut */
+  accelerator: string
+  default: string
+}
+
+/** Mirrors the Rust `LogLevel` (lowercase) */
+export type LogLevel = 'error' | 'warn' | 'info' | 'debug' | 'trace'
+
+/** Mirrors the Rust `BackupSettings` (snake_case) */
+export interface BackupSettings {
+  /** Previous versions kept per file; 0 turns backups off */
+  keep: number
+  location: 'sibling' | 'folder'
+}
+
+/** Proxy settings for AI requests (mirrors the Rust `ProxyConfig`, snake_case) */
+export interface ProxySettings {
+  enabled: boolean
+  url: string
+  username?: string | null
+  password?: string | null
+  no_proxy?: string | null
+  ca_certificate_path?: string | null
+}
+
+/** Unsaved content left by a session that ended without saving */
+export interface RecoverableDraft {
+  file_path: string
+  saved_at: number
+  size: number
+  file_exists: boolean
+}
+
+/** Answer of `list_recently_deleted` */
+export interface DeletedEntry {
+  id: string
+  original_path: string
+  name: string
+  is_directory: boolean
+  deleted_at: number
+  /** Content length of drawings; 0 for folders */
+  size: number
+}
+
+/** Payload of `file-system-change`, with renames already paired up */
+export interface FileSystemChange {
+  kind: 'created' | 'modified' | 'removed' | 'renamed'
+  path: string
+  relative_path: string | null
+  /** Where a renamed entry was before */
+  old_path: string | null
+  is_directory: boolean
+  /** Ignore rules changed; the tree has to be read again */
+  rescan: boolean
+}
+
+/** Payload of `file-system-summary`, sent when watching resumes after a pause */
+export interface FileSystemSummary {
+  created: number
+  modified: number
+  removed: number
+  renamed: number
+  /** In order, without repeated modifications of a path */
+  changes: FileSystemChange[]
+  /** Too much changed to apply one by one; the tree has to be read again */
+  rescan: boolean
+}
+
+/** Payload of `open-file-changed-externally` */
+export interface OpenFileChange {
+  path: string
+  kind: 'modified' | 'deleted'
+  /** Element counts against the version this window last loaded or saved */
+  diff: { added: number; removed: number; changed: number; total: number } | null
+  modified_at: number
+}
+
+/** Answer of `get_file_lock_status` */
+export interface FileLockStatus {
+  path: string
+  /** `stale` locks were left by an instance that is gone */
+  state: 'unlocked' | 'ours' | 'other' | 'stale'
+  owner: {
+    host: string
+    user: string
+    pid: number
+    acquired_at: number
+    refreshed_at: number
+  } | null
+}
+
+/** Payload of `legacy-file-migrated`: `read_file` upgraded an old drawing */
+export interface LegacyMigration {
+  path: string
+  /** The scene version the file declared */
+  from_version: number | null
+  changes: string[]
+}
+
+/** Answer of `verify_file_integrity`, and the payload of `file-integrity-mismatch` */
+export interface FileVerification {
+  path: string
+  status: 'verified' | 'missing' | 'mismatch' | 'unreadable'
+  expected: string | null
+  actual: string | null
+  /** Why the file couldn't be read */
+  error: string | null
+}
+
+/** Answer of `verify_vault_integrity` */
+export interface VaultVerification {
+  directory: string
+  files_checked: number
+  verified: number
+  missing: number
+  /** Mismatched and unreadable drawings, by path */
+  failed: FileVerification[]
+}
+
+/** Element both versions changed, from `merge_scene_changes` */
+export interface MergeConflict {
+  id: string
+  type: string | null
+  kind: 'both_changed' | 'deleted_in_theirs' | 'deleted_in_mine'
+  /** The version the merge kept */
+  kept: 'mine' | 'theirs'
+}
+
+/** Answer of `merge_scene_changes` and `resolve_sync_conflict` */
+export interface SceneMerge {
+  content: string
+  from_mine: number
+  from_theirs: number
+  conflicts: MergeConflict[]
+}
+
+/** Answer of `find_sync_conflicts` */
+export interface SyncConflictCopy {
+  path: string
+  /** The drawing it is a copy of */
+  original: string
+  source: 'dropbox' | 'syncthing' | 'sync'
+}
+
+/** Element-level problem found by `validate_scene_elements` */
+export interface SchemaWarning {
+  index: number
+  element_id: string | null
+  code:
+    | 'not_an_object'
+    | 'missing_id'
+    | 'duplicate_id'
+    | 'unknown_type'
+    | 'invalid_coordinate'
+    | 'missing_coordinate'
+    | 'missing_points'
+    | 'dangling_reference'
+  message: string
+}
+
+/** Outcome for one path of `delete_files`, `move_files` or `export_files` */
+export interface BatchItemResult {
+  path: string
+  /** The moved or compressed file's new path, or the written export */
+  output: string | null
+  error: AppError | null
+}
+
+export interface BatchReport {
+  operation: 'delete' | 'move' | 'export' | 'compress'
+  succeeded: number
+  failed: number
+  items: BatchItemResult[]
+}
+
+/** Payload of `batch-progress`, sent for larger batches */
+export interface BatchProgress {
+  operation: BatchReport['operation']
+  done: number
+  total: number
+  path: string
+}
+
+/**
+ * Payload of `operation-progress`, sent by commands given an `operationId`;
+ * `cancel_operation` with the same id stops them
+ */
+export interface OperationProgress {
+  id: string
+  done: number
+  total: number
+  /** What was just handled, usually a path */
+  message: string
+}
+
+/** A queued or running job from `get_background_tasks`; `cancel_background_task` stops it */
+export interface BackgroundTask {
+  id: number
+  kind: 'link_index' | 'metadata_index' | 'auto_export' | 'lock_cleanup'
+  /** What the job works on, usually a path */
+  target: string
+  priority: 'low' | 'normal' | 'high'
+  state: 'queued' | 'running'
+  /** Asked to stop, but still finishing its current step */
+  cancelled: boolean
+  /** Milliseconds since the Unix epoch */
+  queued_at: number
+  started_at: number | null
+}
+
+export interface ArchiveReport {
+  output_path: string
+  files: number
+  bytes: number
+}
+
+/** Payload of `archive-progress`; `path` is the name inside the archive */
+export interface ArchiveProgress {
+  done: number
+  total: number
+  path: string
+}
+
+/** An archive entry left out of an import, and why */
+export interface SkippedEntry {
+  name: string
+  reason: string
+}
+
+export interface ImportReport {
+  imported: string[]
+  skipped: SkippedEntry[]
+  invalid: SkippedEntry[]
+}
+
+/** A file or folder passed on the command line */
+export interface LaunchTarget {
+  path: string
+  is_directory: boolean
+}